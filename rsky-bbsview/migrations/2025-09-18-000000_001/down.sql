@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS sub_state;
+DROP TABLE IF EXISTS profile;
+DROP TABLE IF EXISTS vote;
+DROP TABLE IF EXISTS section;
+DROP TABLE IF EXISTS reply;
+DROP TABLE IF EXISTS thread;
@@ -0,0 +1,63 @@
+CREATE TABLE IF NOT EXISTS thread (
+    uri character varying PRIMARY KEY,
+    cid character varying NOT NULL,
+    author character varying NOT NULL,
+    "sectionId" bigint NOT NULL,
+    title character varying NOT NULL,
+    text character varying NOT NULL,
+    "replyCount" bigint NOT NULL DEFAULT 0,
+    score bigint NOT NULL DEFAULT 0,
+    "createdAt" character varying NOT NULL,
+    "lastActivityAt" character varying NOT NULL,
+    "indexedAt" character varying NOT NULL
+);
+CREATE INDEX IF NOT EXISTS thread_cid_idx ON thread(cid);
+CREATE INDEX IF NOT EXISTS thread_section_created_idx ON thread("sectionId", "createdAt" DESC, uri DESC);
+CREATE INDEX IF NOT EXISTS thread_section_activity_idx ON thread("sectionId", "lastActivityAt" DESC, uri DESC);
+CREATE INDEX IF NOT EXISTS thread_section_score_idx ON thread("sectionId", score DESC, uri DESC);
+CREATE INDEX IF NOT EXISTS thread_search_idx ON thread USING GIN (to_tsvector('simple', title || ' ' || text));
+
+CREATE TABLE IF NOT EXISTS reply (
+    uri character varying PRIMARY KEY,
+    cid character varying NOT NULL,
+    author character varying NOT NULL,
+    -- Thread the reply was attached to when indexed, null if its root isn't known
+    "threadUri" character varying,
+    root character varying NOT NULL,
+    parent character varying NOT NULL,
+    text character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "indexedAt" character varying NOT NULL
+);
+CREATE INDEX IF NOT EXISTS reply_thread_created_idx ON reply("threadUri", "createdAt", uri);
+
+CREATE TABLE IF NOT EXISTS section (
+    id bigint PRIMARY KEY,
+    "threadCount" bigint NOT NULL DEFAULT 0,
+    "replyCount" bigint NOT NULL DEFAULT 0,
+    "lastActivityAt" character varying
+);
+
+CREATE TABLE IF NOT EXISTS vote (
+    uri character varying PRIMARY KEY,
+    author character varying NOT NULL,
+    subject character varying NOT NULL,
+    value smallint NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "indexedAt" character varying NOT NULL
+);
+CREATE INDEX IF NOT EXISTS vote_subject_idx ON vote(subject);
+
+CREATE TABLE IF NOT EXISTS profile (
+    did character varying PRIMARY KEY,
+    handle character varying,
+    active boolean NOT NULL DEFAULT true,
+    "threadCount" bigint NOT NULL DEFAULT 0,
+    "replyCount" bigint NOT NULL DEFAULT 0,
+    "indexedAt" character varying NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sub_state (
+    service character varying PRIMARY KEY,
+    cursor bigint NOT NULL
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS reply_author_created_idx;
+DROP INDEX IF EXISTS thread_author_created_idx;
+DROP TABLE IF EXISTS follow;
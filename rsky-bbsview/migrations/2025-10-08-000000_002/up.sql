@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS follow (
+    uri character varying PRIMARY KEY,
+    author character varying NOT NULL,
+    subject character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "indexedAt" character varying NOT NULL
+);
+CREATE INDEX IF NOT EXISTS follow_author_created_idx ON follow(author, "createdAt" DESC, uri DESC);
+CREATE INDEX IF NOT EXISTS follow_subject_created_idx ON follow(subject, "createdAt" DESC, uri DESC);
+
+-- For app.bbs.getAuthorFeed
+CREATE INDEX IF NOT EXISTS thread_author_created_idx ON thread(author, "createdAt" DESC, uri DESC);
+CREATE INDEX IF NOT EXISTS reply_author_created_idx ON reply(author, "createdAt" DESC, uri DESC);
@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS vote_seen_idx;
+DROP INDEX IF EXISTS vote_author_subject_idx;
+ALTER TABLE vote DROP COLUMN IF EXISTS counted;
+ALTER TABLE vote DROP COLUMN IF EXISTS "accountAgeSecs";
+ALTER TABLE vote DROP COLUMN IF EXISTS "seenAt";
+ALTER TABLE profile DROP COLUMN IF EXISTS "firstSeenAt";
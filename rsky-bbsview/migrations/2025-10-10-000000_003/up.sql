@@ -0,0 +1,18 @@
+-- When the indexer first saw an event from the account, its age as far as
+-- vote integrity checks are concerned. Existing profiles start from when they
+-- were indexed.
+ALTER TABLE profile ADD COLUMN IF NOT EXISTS "firstSeenAt" character varying;
+UPDATE profile SET "firstSeenAt" = "indexedAt" WHERE "firstSeenAt" IS NULL;
+
+-- When the PDS emitted the vote, the voter's account age at that point and
+-- whether the vote counts towards its subject's score. Only the newest vote of
+-- an account on a subject counts, and only if the account was old enough for
+-- the subject's section. Votes indexed before these checks keep counting.
+ALTER TABLE vote ADD COLUMN IF NOT EXISTS "seenAt" character varying;
+UPDATE vote SET "seenAt" = "indexedAt" WHERE "seenAt" IS NULL;
+ALTER TABLE vote ALTER COLUMN "seenAt" SET NOT NULL;
+ALTER TABLE vote ADD COLUMN IF NOT EXISTS "accountAgeSecs" bigint;
+ALTER TABLE vote ADD COLUMN IF NOT EXISTS counted boolean NOT NULL DEFAULT true;
+CREATE INDEX IF NOT EXISTS vote_author_subject_idx ON vote(author, subject, "seenAt" DESC);
+-- For app.bbs.admin.getVoteBursts
+CREATE INDEX IF NOT EXISTS vote_seen_idx ON vote("seenAt");
@@ -0,0 +1,5 @@
+ALTER TABLE thread DROP COLUMN IF EXISTS "imageMimeType";
+ALTER TABLE thread DROP COLUMN IF EXISTS "imageAlt";
+ALTER TABLE thread DROP COLUMN IF EXISTS "imageCid";
+ALTER TABLE thread DROP COLUMN IF EXISTS "previewTruncated";
+ALTER TABLE thread DROP COLUMN IF EXISTS preview;
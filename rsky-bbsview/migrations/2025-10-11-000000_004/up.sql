@@ -0,0 +1,11 @@
+-- What listings return instead of the full post: the first graphemes of the
+-- text and the first embedded image. Existing threads are cut by characters
+-- and get their image when the post is next updated or reindexed.
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS preview character varying;
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "previewTruncated" boolean NOT NULL DEFAULT false;
+UPDATE thread SET preview = left(text, 300), "previewTruncated" = char_length(text) > 300
+    WHERE preview IS NULL;
+ALTER TABLE thread ALTER COLUMN preview SET NOT NULL;
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "imageCid" character varying;
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "imageAlt" character varying;
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "imageMimeType" character varying;
@@ -0,0 +1 @@
+ALTER TABLE section DROP COLUMN IF EXISTS status;
@@ -0,0 +1,3 @@
+-- Set from the PDS's section events when a moderator archives or reopens a
+-- section. Sections without a row yet are open.
+ALTER TABLE section ADD COLUMN IF NOT EXISTS status character varying NOT NULL DEFAULT 'open';
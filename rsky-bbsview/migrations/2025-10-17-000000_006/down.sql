@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS reaction_count;
+DROP INDEX IF EXISTS reaction_subject_emoji_idx;
+DROP TABLE IF EXISTS reaction;
@@ -0,0 +1,20 @@
+-- app.bbs.reaction records with an emoji on the indexer's allowlist. An
+-- account can react with several emojis to the same post or reply.
+CREATE TABLE IF NOT EXISTS reaction (
+    uri character varying PRIMARY KEY,
+    author character varying NOT NULL,
+    subject character varying NOT NULL,
+    emoji character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "indexedAt" character varying NOT NULL
+);
+CREATE INDEX IF NOT EXISTS reaction_subject_emoji_idx ON reaction(subject, emoji);
+
+-- How many accounts reacted to each subject with each emoji, refreshed as
+-- reactions are indexed. Rows at zero are removed.
+CREATE TABLE IF NOT EXISTS reaction_count (
+    subject character varying NOT NULL,
+    emoji character varying NOT NULL,
+    count bigint NOT NULL,
+    PRIMARY KEY (subject, emoji)
+);
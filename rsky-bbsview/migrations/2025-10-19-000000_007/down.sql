@@ -0,0 +1,4 @@
+ALTER TABLE reply DROP COLUMN IF EXISTS "quoteCid";
+ALTER TABLE reply DROP COLUMN IF EXISTS "quoteUri";
+ALTER TABLE thread DROP COLUMN IF EXISTS "quoteCid";
+ALTER TABLE thread DROP COLUMN IF EXISTS "quoteUri";
@@ -0,0 +1,6 @@
+-- The post or reply each thread and reply quotes with app.bbs.embed.record,
+-- hydrated into a view when read. The quote doesn't have to be indexed.
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "quoteUri" character varying;
+ALTER TABLE thread ADD COLUMN IF NOT EXISTS "quoteCid" character varying;
+ALTER TABLE reply ADD COLUMN IF NOT EXISTS "quoteUri" character varying;
+ALTER TABLE reply ADD COLUMN IF NOT EXISTS "quoteCid" character varying;
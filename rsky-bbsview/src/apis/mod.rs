@@ -0,0 +1,790 @@
+use crate::models::{Follow, Reply, Thread};
+use anyhow::{bail, Result};
+use diesel::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Text};
+use rsky_common::time::from_str_to_millis;
+use rsky_lexicon::app::bbs::embed::{View as EmbedView, ViewNotFound, ViewRecord, ViewUnion};
+use rsky_lexicon::app::bbs::graph::{GetFollowersOutput, GetFollowsOutput};
+use rsky_lexicon::app::bbs::{
+    truncate_graphemes, AuthorFeedItem, AuthorView, GetAuthorFeedOutput, GetSectionFeedOutput,
+    GetThreadOutput, GetVoteBurstsOutput, ImagePreview, ReactionCount, ReplyView,
+    SearchPostsOutput, ThreadView, VoteBurst, PREVIEW_GRAPHEMES,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Hides threads by accounts the indexed PDS reported as inactive (deactivated, taken down)
+const ACTIVE_AUTHOR: &str =
+    "NOT EXISTS (SELECT 1 FROM profile WHERE profile.did = thread.author AND NOT profile.active)";
+const ACTIVE_REPLY_AUTHOR: &str =
+    "NOT EXISTS (SELECT 1 FROM profile WHERE profile.did = reply.author AND NOT profile.active)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedSort {
+    /// Newest threads first
+    Latest,
+    /// Threads with the most recent replies first
+    Active,
+    /// Highest voted threads first
+    Top,
+}
+
+impl FeedSort {
+    pub fn parse(sort: Option<&str>) -> Result<Self> {
+        match sort {
+            None | Some("latest") => Ok(FeedSort::Latest),
+            Some("active") => Ok(FeedSort::Active),
+            Some("top") => Ok(FeedSort::Top),
+            Some(sort) => bail!("Unknown sort: {sort}"),
+        }
+    }
+}
+
+/// Cursors are `{sort key}::{uri}`, the uri breaks ties between equal keys.
+pub fn parse_cursor(cursor: &str) -> Result<(String, String)> {
+    match cursor.split_once("::") {
+        Some((key, uri)) if !key.is_empty() && uri.starts_with("at://") => {
+            Ok((key.to_string(), uri.to_string()))
+        }
+        _ => bail!("Malformed cursor"),
+    }
+}
+
+fn load_handles(conn: &mut PgConnection, dids: Vec<String>) -> Result<HashMap<String, String>> {
+    use crate::schema::profile::dsl as ProfileSchema;
+
+    Ok(ProfileSchema::profile
+        .filter(ProfileSchema::did.eq_any(dids))
+        .filter(ProfileSchema::handle.is_not_null())
+        .select((ProfileSchema::did, ProfileSchema::handle.assume_not_null()))
+        .load::<(String, String)>(conn)?
+        .into_iter()
+        .collect())
+}
+
+fn author_view(did: String, handles: &HashMap<String, String>) -> AuthorView {
+    AuthorView {
+        handle: handles.get(&did).cloned(),
+        did,
+    }
+}
+
+/// The posts and replies among `uris` that are indexed and by active
+/// accounts, as quotes with only their previews, keyed by uri
+fn load_quotes(conn: &mut PgConnection, uris: Vec<String>) -> Result<HashMap<String, ViewRecord>> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    if uris.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let threads = ThreadSchema::thread
+        .filter(ThreadSchema::uri.eq_any(&uris))
+        .filter(sql::<Bool>(ACTIVE_AUTHOR))
+        .select(Thread::as_select())
+        .load::<Thread>(conn)?;
+    let replies = ReplySchema::reply
+        .filter(ReplySchema::uri.eq_any(&uris))
+        .filter(sql::<Bool>(ACTIVE_REPLY_AUTHOR))
+        .select(Reply::as_select())
+        .load::<Reply>(conn)?;
+    let handles = load_handles(
+        conn,
+        threads
+            .iter()
+            .map(|thread| thread.author.clone())
+            .chain(replies.iter().map(|reply| reply.author.clone()))
+            .collect(),
+    )?;
+
+    let mut quotes = HashMap::new();
+    for thread in threads {
+        let quote = ViewRecord {
+            uri: thread.uri.clone(),
+            cid: thread.cid,
+            author: author_view(thread.author, &handles),
+            thread: thread.uri.clone(),
+            title: Some(thread.title),
+            text: thread.preview,
+            truncated: thread.preview_truncated.then_some(true),
+            created_at: thread.created_at,
+            indexed_at: thread.indexed_at,
+        };
+        quotes.insert(thread.uri, quote);
+    }
+    for reply in replies {
+        let (text, truncated) = truncate_graphemes(&reply.text, PREVIEW_GRAPHEMES);
+        let quote = ViewRecord {
+            uri: reply.uri.clone(),
+            cid: reply.cid,
+            author: author_view(reply.author, &handles),
+            thread: reply.thread_uri.unwrap_or(reply.root),
+            title: None,
+            text,
+            truncated: truncated.then_some(true),
+            created_at: reply.created_at,
+            indexed_at: reply.indexed_at,
+        };
+        quotes.insert(reply.uri, quote);
+    }
+    Ok(quotes)
+}
+
+/// The quote of `uri`, or that it's gone when it's not among `quotes`
+fn quote_view(uri: String, quotes: &HashMap<String, ViewRecord>) -> EmbedView {
+    let record = match quotes.get(&uri) {
+        Some(quote) => ViewUnion::ViewRecord(quote.clone()),
+        None => ViewUnion::ViewNotFound(ViewNotFound {
+            uri,
+            not_found: true,
+        }),
+    };
+    EmbedView { record }
+}
+
+/// Views of `threads`, with only their previews unless `full_text` is set.
+fn thread_views(
+    conn: &mut PgConnection,
+    threads: Vec<Thread>,
+    full_text: bool,
+) -> Result<Vec<ThreadView>> {
+    let handles = load_handles(
+        conn,
+        threads.iter().map(|thread| thread.author.clone()).collect(),
+    )?;
+    let quotes = load_quotes(
+        conn,
+        threads
+            .iter()
+            .filter_map(|thread| thread.quote_uri.clone())
+            .collect(),
+    )?;
+    Ok(threads
+        .into_iter()
+        .map(|thread| {
+            let (text, truncated) = match full_text {
+                true => (thread.text, None),
+                false => (thread.preview, thread.preview_truncated.then_some(true)),
+            };
+            let image = match (thread.image_cid, thread.image_mime_type) {
+                (Some(cid), Some(mime_type)) => Some(ImagePreview {
+                    cid,
+                    alt: thread.image_alt.unwrap_or_default(),
+                    mime_type,
+                }),
+                _ => None,
+            };
+            ThreadView {
+                uri: thread.uri,
+                cid: thread.cid,
+                author: author_view(thread.author, &handles),
+                section_id: thread.section_id,
+                title: thread.title,
+                text,
+                reply_count: thread.reply_count,
+                score: thread.score,
+                created_at: thread.created_at,
+                last_activity_at: thread.last_activity_at,
+                indexed_at: thread.indexed_at,
+                truncated,
+                image,
+                // Link cards are unfurled by the author's PDS
+                external: None,
+                reactions: None,
+                embed: thread.quote_uri.map(|uri| quote_view(uri, &quotes)),
+            }
+        })
+        .collect())
+}
+
+fn reply_views(conn: &mut PgConnection, replies: Vec<Reply>) -> Result<Vec<ReplyView>> {
+    let handles = load_handles(
+        conn,
+        replies.iter().map(|reply| reply.author.clone()).collect(),
+    )?;
+    let quotes = load_quotes(
+        conn,
+        replies
+            .iter()
+            .filter_map(|reply| reply.quote_uri.clone())
+            .collect(),
+    )?;
+    Ok(replies
+        .into_iter()
+        .map(|reply| ReplyView {
+            uri: reply.uri,
+            cid: reply.cid,
+            author: author_view(reply.author, &handles),
+            parent: reply.parent,
+            text: reply.text,
+            created_at: reply.created_at,
+            indexed_at: reply.indexed_at,
+            reactions: None,
+            embed: reply.quote_uri.map(|uri| quote_view(uri, &quotes)),
+        })
+        .collect())
+}
+
+/// Reaction counts of each of `subjects` that has any, most used emoji first
+fn load_reactions(
+    conn: &mut PgConnection,
+    subjects: Vec<String>,
+) -> Result<HashMap<String, Vec<ReactionCount>>> {
+    use crate::schema::reaction_count::dsl as ReactionCountSchema;
+
+    let counts = ReactionCountSchema::reaction_count
+        .filter(ReactionCountSchema::subject.eq_any(subjects))
+        .order((
+            ReactionCountSchema::subject,
+            ReactionCountSchema::count.desc(),
+            ReactionCountSchema::emoji,
+        ))
+        .select((
+            ReactionCountSchema::subject,
+            ReactionCountSchema::emoji,
+            ReactionCountSchema::count,
+        ))
+        .load::<(String, String, i64)>(conn)?;
+    let mut reactions: HashMap<String, Vec<ReactionCount>> = HashMap::new();
+    for (subject, emoji, count) in counts {
+        reactions
+            .entry(subject)
+            .or_default()
+            .push(ReactionCount { emoji, count });
+    }
+    Ok(reactions)
+}
+
+pub fn get_thread(
+    conn: &mut PgConnection,
+    uri: String,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<Option<GetThreadOutput>> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let thread = ThreadSchema::thread
+        .filter(ThreadSchema::uri.eq(&uri))
+        .filter(sql::<Bool>(ACTIVE_AUTHOR))
+        .select(Thread::as_select())
+        .first(conn)
+        .optional()?;
+    let Some(thread) = thread else {
+        return Ok(None);
+    };
+
+    let mut builder = ReplySchema::reply
+        .filter(ReplySchema::threadUri.eq(&uri))
+        .filter(sql::<Bool>(ACTIVE_REPLY_AUTHOR))
+        .select(Reply::as_select())
+        .order((ReplySchema::createdAt.asc(), ReplySchema::uri.asc()))
+        .limit(limit)
+        .into_boxed();
+    if let Some((created_at, uri)) = cursor {
+        builder = builder.filter(
+            ReplySchema::createdAt
+                .gt(created_at.clone())
+                .or(ReplySchema::createdAt
+                    .eq(created_at)
+                    .and(ReplySchema::uri.gt(uri))),
+        );
+    }
+    let replies = builder.load::<Reply>(conn)?;
+    let cursor = match replies.last() {
+        Some(last) if replies.len() as i64 == limit => {
+            Some(format!("{}::{}", last.created_at, last.uri))
+        }
+        _ => None,
+    };
+
+    let mut subjects = vec![thread.uri.clone()];
+    subjects.extend(replies.iter().map(|reply| reply.uri.clone()));
+    let mut reactions = load_reactions(conn, subjects)?;
+    let replies = reply_views(conn, replies)?
+        .into_iter()
+        .map(|reply| ReplyView {
+            reactions: Some(reactions.remove(&reply.uri).unwrap_or_default()),
+            ..reply
+        })
+        .collect();
+    let thread = thread_views(conn, vec![thread], true)?.remove(0);
+    let thread = ThreadView {
+        reactions: Some(reactions.remove(&thread.uri).unwrap_or_default()),
+        ..thread
+    };
+    Ok(Some(GetThreadOutput {
+        thread,
+        replies,
+        cursor,
+    }))
+}
+
+pub fn get_section_feed(
+    conn: &mut PgConnection,
+    section_id: i64,
+    sort: FeedSort,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<GetSectionFeedOutput> {
+    use crate::schema::section::dsl as SectionSchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let mut builder = ThreadSchema::thread
+        .filter(ThreadSchema::sectionId.eq(section_id))
+        .filter(sql::<Bool>(ACTIVE_AUTHOR))
+        .select(Thread::as_select())
+        .limit(limit)
+        .into_boxed();
+    builder = match sort {
+        FeedSort::Latest => {
+            builder.order((ThreadSchema::createdAt.desc(), ThreadSchema::uri.desc()))
+        }
+        FeedSort::Active => builder.order((
+            ThreadSchema::lastActivityAt.desc(),
+            ThreadSchema::uri.desc(),
+        )),
+        FeedSort::Top => builder.order((ThreadSchema::score.desc(), ThreadSchema::uri.desc())),
+    };
+    if let Some((key, uri)) = cursor {
+        builder = match sort {
+            FeedSort::Latest => builder.filter(
+                ThreadSchema::createdAt
+                    .lt(key.clone())
+                    .or(ThreadSchema::createdAt
+                        .eq(key)
+                        .and(ThreadSchema::uri.lt(uri))),
+            ),
+            FeedSort::Active => builder.filter(
+                ThreadSchema::lastActivityAt
+                    .lt(key.clone())
+                    .or(ThreadSchema::lastActivityAt
+                        .eq(key)
+                        .and(ThreadSchema::uri.lt(uri))),
+            ),
+            FeedSort::Top => {
+                let Ok(score) = key.parse::<i64>() else {
+                    bail!("Malformed cursor");
+                };
+                builder.filter(
+                    ThreadSchema::score
+                        .lt(score)
+                        .or(ThreadSchema::score.eq(score).and(ThreadSchema::uri.lt(uri))),
+                )
+            }
+        };
+    }
+    let threads = builder.load::<Thread>(conn)?;
+    let cursor = match threads.last() {
+        Some(last) if threads.len() as i64 == limit => {
+            let key = match sort {
+                FeedSort::Latest => last.created_at.clone(),
+                FeedSort::Active => last.last_activity_at.clone(),
+                FeedSort::Top => last.score.to_string(),
+            };
+            Some(format!("{key}::{}", last.uri))
+        }
+        _ => None,
+    };
+    let status = SectionSchema::section
+        .filter(SectionSchema::id.eq(section_id))
+        .select(SectionSchema::status)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(GetSectionFeedOutput {
+        threads: thread_views(conn, threads, false)?,
+        cursor,
+        status: Some(
+            status
+                .and_then(|status| status.parse().ok())
+                .unwrap_or_default(),
+        ),
+    })
+}
+
+/// Full text search over thread titles and bodies, newest first.
+pub fn search_posts(
+    conn: &mut PgConnection,
+    q: String,
+    section_id: Option<i64>,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<SearchPostsOutput> {
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let mut builder = ThreadSchema::thread
+        .filter(
+            sql::<Bool>(
+                "to_tsvector('simple', title || ' ' || text) @@ plainto_tsquery('simple', ",
+            )
+            .bind::<Text, _>(q)
+            .sql(")"),
+        )
+        .filter(sql::<Bool>(ACTIVE_AUTHOR))
+        .select(Thread::as_select())
+        .order((ThreadSchema::createdAt.desc(), ThreadSchema::uri.desc()))
+        .limit(limit)
+        .into_boxed();
+    if let Some(section_id) = section_id {
+        builder = builder.filter(ThreadSchema::sectionId.eq(section_id));
+    }
+    if let Some((created_at, uri)) = cursor {
+        builder = builder.filter(
+            ThreadSchema::createdAt
+                .lt(created_at.clone())
+                .or(ThreadSchema::createdAt
+                    .eq(created_at)
+                    .and(ThreadSchema::uri.lt(uri))),
+        );
+    }
+    let threads = builder.load::<Thread>(conn)?;
+    let cursor = match threads.last() {
+        Some(last) if threads.len() as i64 == limit => {
+            Some(format!("{}::{}", last.created_at, last.uri))
+        }
+        _ => None,
+    };
+    Ok(SearchPostsOutput {
+        threads: thread_views(conn, threads, false)?,
+        cursor,
+    })
+}
+
+/// Threads and replies by `actor`, newest first. Both are read a page at a
+/// time with the same cursor and merged, so a page never skips anything.
+pub fn get_author_feed(
+    conn: &mut PgConnection,
+    actor: String,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<GetAuthorFeedOutput> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let mut threads = ThreadSchema::thread
+        .filter(ThreadSchema::author.eq(&actor))
+        .filter(sql::<Bool>(ACTIVE_AUTHOR))
+        .select(Thread::as_select())
+        .order((ThreadSchema::createdAt.desc(), ThreadSchema::uri.desc()))
+        .limit(limit)
+        .into_boxed();
+    let mut replies = ReplySchema::reply
+        .filter(ReplySchema::author.eq(&actor))
+        .filter(sql::<Bool>(ACTIVE_REPLY_AUTHOR))
+        .select(Reply::as_select())
+        .order((ReplySchema::createdAt.desc(), ReplySchema::uri.desc()))
+        .limit(limit)
+        .into_boxed();
+    if let Some((created_at, uri)) = cursor {
+        threads = threads.filter(
+            ThreadSchema::createdAt
+                .lt(created_at.clone())
+                .or(ThreadSchema::createdAt
+                    .eq(created_at.clone())
+                    .and(ThreadSchema::uri.lt(uri.clone()))),
+        );
+        replies = replies.filter(
+            ReplySchema::createdAt
+                .lt(created_at.clone())
+                .or(ReplySchema::createdAt
+                    .eq(created_at)
+                    .and(ReplySchema::uri.lt(uri))),
+        );
+    }
+    let threads = thread_views(conn, threads.load::<Thread>(conn)?, false)?;
+    let replies = reply_views(conn, replies.load::<Reply>(conn)?)?;
+
+    let mut feed = threads
+        .into_iter()
+        .map(|thread| {
+            (
+                (thread.created_at.clone(), thread.uri.clone()),
+                AuthorFeedItem {
+                    thread: Some(thread),
+                    reply: None,
+                },
+            )
+        })
+        .chain(replies.into_iter().map(|reply| {
+            (
+                (reply.created_at.clone(), reply.uri.clone()),
+                AuthorFeedItem {
+                    thread: None,
+                    reply: Some(reply),
+                },
+            )
+        }))
+        .collect::<Vec<_>>();
+    feed.sort_by(|(a, _), (b, _)| b.cmp(a));
+    feed.truncate(limit as usize);
+    let cursor = match feed.last() {
+        Some(((created_at, uri), _)) if feed.len() as i64 == limit => {
+            Some(format!("{created_at}::{uri}"))
+        }
+        _ => None,
+    };
+    Ok(GetAuthorFeedOutput {
+        feed: feed.into_iter().map(|(_, item)| item).collect(),
+        cursor,
+    })
+}
+
+/// One page of the accounts `actor` follows, or of its followers, most recent
+/// first and without inactive accounts.
+fn follow_page(
+    conn: &mut PgConnection,
+    actor: &String,
+    followers: bool,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<(Vec<AuthorView>, Option<String>)> {
+    use crate::schema::follow::dsl as FollowSchema;
+
+    let mut builder = FollowSchema::follow
+        .select(Follow::as_select())
+        .order((FollowSchema::createdAt.desc(), FollowSchema::uri.desc()))
+        .limit(limit)
+        .into_boxed();
+    builder = match followers {
+        true => builder.filter(FollowSchema::subject.eq(actor)).filter(sql::<Bool>(
+            "NOT EXISTS (SELECT 1 FROM profile WHERE profile.did = follow.author AND NOT profile.active)",
+        )),
+        false => builder.filter(FollowSchema::author.eq(actor)).filter(sql::<Bool>(
+            "NOT EXISTS (SELECT 1 FROM profile WHERE profile.did = follow.subject AND NOT profile.active)",
+        )),
+    };
+    if let Some((created_at, uri)) = cursor {
+        builder = builder.filter(
+            FollowSchema::createdAt
+                .lt(created_at.clone())
+                .or(FollowSchema::createdAt
+                    .eq(created_at)
+                    .and(FollowSchema::uri.lt(uri))),
+        );
+    }
+    let follows = builder.load::<Follow>(conn)?;
+    let cursor = match follows.last() {
+        Some(last) if follows.len() as i64 == limit => {
+            Some(format!("{}::{}", last.created_at, last.uri))
+        }
+        _ => None,
+    };
+    let dids = follows
+        .into_iter()
+        .map(|follow| match followers {
+            true => follow.author,
+            false => follow.subject,
+        })
+        .collect::<Vec<String>>();
+    let handles = load_handles(conn, dids.clone())?;
+    Ok((
+        dids.into_iter()
+            .map(|did| author_view(did, &handles))
+            .collect(),
+        cursor,
+    ))
+}
+
+pub fn get_follows(
+    conn: &mut PgConnection,
+    actor: String,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<GetFollowsOutput> {
+    let (follows, cursor) = follow_page(conn, &actor, false, limit, cursor)?;
+    let handles = load_handles(conn, vec![actor.clone()])?;
+    Ok(GetFollowsOutput {
+        subject: author_view(actor, &handles),
+        follows,
+        cursor,
+    })
+}
+
+pub fn get_followers(
+    conn: &mut PgConnection,
+    actor: String,
+    limit: i64,
+    cursor: Option<(String, String)>,
+) -> Result<GetFollowersOutput> {
+    let (followers, cursor) = follow_page(conn, &actor, true, limit, cursor)?;
+    let handles = load_handles(conn, vec![actor.clone()])?;
+    Ok(GetFollowersOutput {
+        subject: author_view(actor, &handles),
+        followers,
+        cursor,
+    })
+}
+
+/// A vote as the burst report sees it
+#[derive(Debug, Clone)]
+struct SeenVote {
+    seen_ms: i64,
+    seen_at: String,
+    author: String,
+    value: i64,
+    counted: bool,
+}
+
+/// The run of `votes`, sorted by time, with the most votes less than
+/// `window_ms` apart. The earliest wins ties.
+fn busiest_window(votes: &[SeenVote], window_ms: i64) -> &[SeenVote] {
+    let (mut best_start, mut best_end) = (0, 0);
+    let mut start = 0;
+    for end in 0..votes.len() {
+        while votes[end].seen_ms - votes[start].seen_ms >= window_ms {
+            start += 1;
+        }
+        if end + 1 - start > best_end - best_start {
+            (best_start, best_end) = (start, end + 1);
+        }
+    }
+    &votes[best_start..best_end]
+}
+
+/// Subjects that got at least `threshold` votes within `window_minutes` since
+/// `since`, busiest first. Times are when the PDS emitted the votes, so a
+/// reindex reports the same bursts.
+pub fn get_vote_bursts(
+    conn: &mut PgConnection,
+    since: String,
+    window_minutes: i64,
+    threshold: i64,
+    limit: i64,
+) -> Result<GetVoteBurstsOutput> {
+    use crate::schema::vote::dsl as VoteSchema;
+
+    let rows = VoteSchema::vote
+        .filter(VoteSchema::seenAt.ge(&since))
+        .order((VoteSchema::subject.asc(), VoteSchema::seenAt.asc()))
+        .select((
+            VoteSchema::subject,
+            VoteSchema::author,
+            VoteSchema::value,
+            VoteSchema::seenAt,
+            VoteSchema::counted,
+        ))
+        .load::<(String, String, i16, String, bool)>(conn)?;
+
+    let mut bursts = Vec::new();
+    for votes in rows.chunk_by(|a, b| a.0 == b.0) {
+        let subject = votes[0].0.clone();
+        let votes: Vec<SeenVote> = votes
+            .iter()
+            .filter_map(|(_, author, value, seen_at, counted)| {
+                Some(SeenVote {
+                    seen_ms: from_str_to_millis(seen_at).ok()?,
+                    seen_at: seen_at.clone(),
+                    author: author.clone(),
+                    value: *value as i64,
+                    counted: *counted,
+                })
+            })
+            .collect();
+        let window = busiest_window(&votes, window_minutes * 60 * 1000);
+        if (window.len() as i64) < threshold {
+            continue;
+        }
+        bursts.push(VoteBurst {
+            subject,
+            window_start: window[0].seen_at.clone(),
+            window_end: window[window.len() - 1].seen_at.clone(),
+            votes: window.len() as i64,
+            voters: window
+                .iter()
+                .map(|vote| &vote.author)
+                .collect::<HashSet<_>>()
+                .len() as i64,
+            score: window.iter().map(|vote| vote.value).sum(),
+            uncounted_votes: window.iter().filter(|vote| !vote.counted).count() as i64,
+        });
+    }
+    bursts.sort_by(|a, b| {
+        b.votes
+            .cmp(&a.votes)
+            .then_with(|| a.subject.cmp(&b.subject))
+    });
+    bursts.truncate(limit as usize);
+    Ok(GetVoteBurstsOutput { bursts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cursors() {
+        assert_eq!(
+            parse_cursor("2025-01-01T00:00:00.000Z::at://did:ckb:abc/app.bbs.post/3k").unwrap(),
+            (
+                "2025-01-01T00:00:00.000Z".to_string(),
+                "at://did:ckb:abc/app.bbs.post/3k".to_string()
+            )
+        );
+        assert_eq!(
+            parse_cursor("-3::at://did:ckb:abc/app.bbs.post/3k")
+                .unwrap()
+                .0,
+            "-3"
+        );
+        assert!(parse_cursor("2025-01-01T00:00:00.000Z").is_err());
+        assert!(parse_cursor("::at://did:ckb:abc/app.bbs.post/3k").is_err());
+    }
+
+    #[test]
+    fn quotes_that_are_gone_are_not_found() {
+        let uri = "at://did:ckb:abc/app.bbs.post/3k".to_string();
+        let quote = ViewRecord {
+            uri: uri.clone(),
+            cid: "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm".to_string(),
+            author: AuthorView {
+                did: "did:ckb:abc".to_string(),
+                handle: None,
+            },
+            thread: uri.clone(),
+            title: Some("Hello".to_string()),
+            text: "hello".to_string(),
+            truncated: None,
+            created_at: "2025-01-01T00:00:00.000Z".to_string(),
+            indexed_at: "2025-01-01T00:00:00.000Z".to_string(),
+        };
+        let quotes = HashMap::from([(uri.clone(), quote.clone())]);
+        assert_eq!(
+            quote_view(uri, &quotes).record,
+            ViewUnion::ViewRecord(quote)
+        );
+        assert_eq!(
+            quote_view("at://did:ckb:abc/app.bbs.reply/3l".to_string(), &quotes).record,
+            ViewUnion::ViewNotFound(ViewNotFound {
+                uri: "at://did:ckb:abc/app.bbs.reply/3l".to_string(),
+                not_found: true,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_sort() {
+        assert_eq!(FeedSort::parse(None).unwrap(), FeedSort::Latest);
+        assert_eq!(FeedSort::parse(Some("top")).unwrap(), FeedSort::Top);
+        assert!(FeedSort::parse(Some("hot")).is_err());
+    }
+
+    #[test]
+    fn finds_the_busiest_window() {
+        let votes: Vec<SeenVote> = [0, 1_000, 70_000, 71_000, 72_000, 200_000]
+            .into_iter()
+            .map(|seen_ms| SeenVote {
+                seen_ms,
+                seen_at: seen_ms.to_string(),
+                author: "did:ckb:abc".to_string(),
+                value: 1,
+                counted: true,
+            })
+            .collect();
+        let window = busiest_window(&votes, 60_000);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window[0].seen_ms, 70_000);
+        assert_eq!(busiest_window(&votes, 1_000).len(), 1);
+        assert!(busiest_window(&[], 60_000).is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+
+#[derive(Debug)]
+pub enum ApiError {
+    RuntimeError,
+    InvalidRequest(String),
+    AuthRequired(String),
+    NotFound(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    error: String,
+    message: String,
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let (status, error, message) = match self {
+            ApiError::RuntimeError => (
+                Status::InternalServerError,
+                "InternalServerError",
+                "Something went wrong".to_string(),
+            ),
+            ApiError::InvalidRequest(message) => (Status::BadRequest, "InvalidRequest", message),
+            ApiError::AuthRequired(message) => (Status::Unauthorized, "AuthRequired", message),
+            ApiError::NotFound(message) => (Status::NotFound, "NotFound", message),
+        };
+        let body = Json(ErrorBody {
+            error: error.to_string(),
+            message,
+        });
+        let mut res = body.respond_to(req)?;
+        res.set_status(status);
+        Ok(res)
+    }
+}
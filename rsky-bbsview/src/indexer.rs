@@ -0,0 +1,1052 @@
+use crate::db::establish_connection;
+use crate::models::{Reply, Thread};
+use anyhow::{bail, Result};
+use diesel::dsl::{count_distinct, sum};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use futures::StreamExt;
+use rsky_common::time::{from_millis_to_str, from_str_to_millis};
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::app::bbs::embed::PostEmbed;
+use rsky_lexicon::app::bbs::graph::Follow;
+use rsky_lexicon::app::bbs::{
+    truncate_graphemes, Post, Reaction, Reply as ReplyRecord, SectionStatus, Vote,
+    DEFAULT_REACTION_EMOJIS, PREVIEW_GRAPHEMES,
+};
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use url::Url;
+
+pub const POST_COLLECTION: &str = "app.bbs.post";
+pub const REPLY_COLLECTION: &str = "app.bbs.reply";
+pub const VOTE_COLLECTION: &str = "app.bbs.vote";
+pub const FOLLOW_COLLECTION: &str = "app.bbs.graph.follow";
+pub const REACTION_COLLECTION: &str = "app.bbs.reaction";
+
+/// Save the cursor every this many indexed events
+const CURSOR_SAVE_INTERVAL: u64 = 100;
+
+/// An event from a PDS `/subscribe` (Jetstream-style) stream. Only the fields
+/// the indexer uses are decoded.
+#[derive(Debug, Deserialize)]
+pub struct JetstreamEvt {
+    pub did: String,
+    /// When the PDS emitted the event, replays carry the original time
+    pub time_us: i64,
+    pub seq: i64,
+    #[serde(flatten)]
+    pub kind: JetstreamKind,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JetstreamKind {
+    Commit { commit: JetstreamCommit },
+    Identity { identity: JetstreamIdentity },
+    Account { account: JetstreamAccount },
+    Section { section: JetstreamSection },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JetstreamCommit {
+    pub operation: String,
+    pub collection: String,
+    pub rkey: String,
+    pub record: Option<serde_json::Value>,
+    pub cid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JetstreamIdentity {
+    pub handle: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JetstreamAccount {
+    pub active: bool,
+}
+
+/// A moderator archived or reopened a section on the PDS
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JetstreamSection {
+    pub section_id: i64,
+    pub status: SectionStatus,
+}
+
+/// How old an account must be for its votes to count, per section. Votes
+/// from younger accounts are still indexed, they are left out of the score.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VotePolicy {
+    /// For sections without their own minimum, in seconds
+    pub min_account_age_secs: i64,
+    pub section_min_account_age_secs: HashMap<i64, i64>,
+}
+
+impl VotePolicy {
+    /// From BBSVIEW_VOTE_MIN_ACCOUNT_AGE_HOURS and
+    /// BBSVIEW_SECTION_VOTE_MIN_ACCOUNT_AGE_HOURS, a comma separated list of
+    /// `section:hours` overrides, e.g. `3:168,7:0`.
+    pub fn from_env() -> Result<Self> {
+        Self::parse(
+            env::var("BBSVIEW_VOTE_MIN_ACCOUNT_AGE_HOURS")
+                .ok()
+                .as_deref(),
+            env::var("BBSVIEW_SECTION_VOTE_MIN_ACCOUNT_AGE_HOURS")
+                .ok()
+                .as_deref(),
+        )
+    }
+
+    pub fn parse(default_hours: Option<&str>, section_hours: Option<&str>) -> Result<Self> {
+        let mut policy = VotePolicy::default();
+        if let Some(hours) = default_hours.map(str::trim).filter(|h| !h.is_empty()) {
+            policy.min_account_age_secs = hours.parse::<i64>()? * 3600;
+        }
+        for entry in section_hours
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let Some((section, hours)) = entry.split_once(':') else {
+                bail!("Expected `section:hours`, got `{entry}`");
+            };
+            policy
+                .section_min_account_age_secs
+                .insert(section.trim().parse()?, hours.trim().parse::<i64>()? * 3600);
+        }
+        Ok(policy)
+    }
+
+    /// The minimum for `section_id`, or the default while the subject's
+    /// section isn't known yet.
+    pub fn min_account_age_secs(&self, section_id: Option<i64>) -> i64 {
+        section_id
+            .and_then(|id| self.section_min_account_age_secs.get(&id).copied())
+            .unwrap_or(self.min_account_age_secs)
+    }
+}
+
+/// The emojis reactions are counted with. Reactions with any other emoji
+/// aren't indexed, so a PDS with a longer allowlist can't add to the counts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReactionPolicy {
+    pub emojis: Vec<String>,
+}
+
+impl Default for ReactionPolicy {
+    fn default() -> Self {
+        ReactionPolicy {
+            emojis: DEFAULT_REACTION_EMOJIS.map(str::to_string).to_vec(),
+        }
+    }
+}
+
+impl ReactionPolicy {
+    /// From BBSVIEW_REACTION_EMOJIS, a comma separated list of shortcodes,
+    /// e.g. `+1,heart,eyes`.
+    pub fn from_env() -> Self {
+        Self::parse(env::var("BBSVIEW_REACTION_EMOJIS").ok().as_deref())
+    }
+
+    /// The listed shortcodes, or the lexicon's defaults when none are listed
+    pub fn parse(emojis: Option<&str>) -> Self {
+        let emojis: Vec<String> = emojis
+            .unwrap_or_default()
+            .split(',')
+            .map(|emoji| emoji.trim().trim_matches(':'))
+            .filter(|emoji| !emoji.is_empty())
+            .map(str::to_string)
+            .collect();
+        match emojis.is_empty() {
+            true => ReactionPolicy::default(),
+            false => ReactionPolicy { emojis },
+        }
+    }
+
+    pub fn allows(&self, emoji: &str) -> bool {
+        self.emojis.iter().any(|allowed| allowed == emoji)
+    }
+}
+
+/// Tails the app.bbs records of one PDS and keeps the global thread, section,
+/// vote, reaction, follow and profile indexes up to date. The last indexed seq
+/// is stored per endpoint so a restart resumes where it left off.
+#[derive(Debug, Clone)]
+pub struct Indexer {
+    /// Base websocket url of the PDS, e.g. `wss://pds.example.com`
+    pub endpoint: String,
+    pub vote_policy: VotePolicy,
+    pub reaction_policy: ReactionPolicy,
+}
+
+impl Indexer {
+    pub fn new(endpoint: String, vote_policy: VotePolicy, reaction_policy: ReactionPolicy) -> Self {
+        Indexer {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            vote_policy,
+            reaction_policy,
+        }
+    }
+
+    pub async fn start(&self) {
+        loop {
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: indexing {} failed: {error}", self.endpoint);
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn run(&self) -> Result<()> {
+        let conn = &mut establish_connection()?;
+        let cursor = get_cursor(conn, &self.endpoint)?;
+        let mut url = Url::parse(&format!("{}/subscribe", self.endpoint))?;
+        url.query_pairs_mut()
+            .append_pair("wantedCollections", "app.bbs.*");
+        if let Some(cursor) = cursor {
+            url.query_pairs_mut()
+                .append_pair("cursor", &cursor.to_string());
+        }
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+        tracing::info!("Connected to {} at cursor {cursor:?}", self.endpoint);
+
+        let mut last_seq: Option<i64> = None;
+        let mut unsaved: u64 = 0;
+        let res = loop {
+            let message = match socket.next().await {
+                Some(Ok(message)) => message,
+                Some(Err(error)) => break Err(error.into()),
+                None => break Ok(()),
+            };
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => break Ok(()),
+                _ => continue,
+            };
+            let evt = match serde_json::from_str::<JetstreamEvt>(&text) {
+                Ok(evt) => evt,
+                Err(_) => {
+                    tracing::warn!("{}: skipping message: {text}", self.endpoint);
+                    continue;
+                }
+            };
+            let seq = evt.seq;
+            if let Err(error) = conn.transaction::<_, anyhow::Error, _>(|conn| {
+                index_event(conn, &self.vote_policy, &self.reaction_policy, evt)
+            }) {
+                break Err(error);
+            }
+            last_seq = Some(seq);
+            unsaved += 1;
+            if unsaved >= CURSOR_SAVE_INTERVAL {
+                save_cursor(conn, &self.endpoint, seq)?;
+                unsaved = 0;
+            }
+        };
+        if let (Some(seq), true) = (last_seq, unsaved > 0) {
+            save_cursor(conn, &self.endpoint, seq)?;
+        }
+        res
+    }
+}
+
+pub fn get_cursor(conn: &mut PgConnection, service: &String) -> Result<Option<i64>> {
+    use crate::schema::sub_state::dsl as SubStateSchema;
+
+    Ok(SubStateSchema::sub_state
+        .filter(SubStateSchema::service.eq(service))
+        .select(SubStateSchema::cursor)
+        .first::<i64>(conn)
+        .optional()?)
+}
+
+pub fn save_cursor(conn: &mut PgConnection, service: &String, cursor: i64) -> Result<()> {
+    use crate::schema::sub_state::dsl as SubStateSchema;
+
+    insert_into(SubStateSchema::sub_state)
+        .values((
+            SubStateSchema::service.eq(service),
+            SubStateSchema::cursor.eq(cursor),
+        ))
+        .on_conflict(SubStateSchema::service)
+        .do_update()
+        .set(SubStateSchema::cursor.eq(cursor))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub fn index_event(
+    conn: &mut PgConnection,
+    policy: &VotePolicy,
+    reactions: &ReactionPolicy,
+    evt: JetstreamEvt,
+) -> Result<()> {
+    let JetstreamEvt {
+        did, time_us, kind, ..
+    } = evt;
+    let seen_at = from_millis_to_str(time_us / 1000);
+    note_first_seen(conn, &did, &seen_at)?;
+    match kind {
+        JetstreamKind::Commit { commit } => {
+            let uri = format!("at://{did}/{}/{}", commit.collection, commit.rkey);
+            let record = commit.record;
+            let cid = commit.cid.unwrap_or_default();
+            match (commit.collection.as_str(), commit.operation.as_str()) {
+                (POST_COLLECTION, "create" | "update") => match parse(record) {
+                    Some(post) => index_thread(conn, policy, &did, uri, cid, post),
+                    None => {
+                        tracing::debug!("Skipping malformed post: {uri}");
+                        Ok(())
+                    }
+                },
+                (POST_COLLECTION, "delete") => delete_thread(conn, &did, &uri),
+                (REPLY_COLLECTION, "create" | "update") => match parse(record) {
+                    Some(reply) => index_reply(conn, &did, uri, cid, reply),
+                    None => {
+                        tracing::debug!("Skipping malformed reply: {uri}");
+                        Ok(())
+                    }
+                },
+                (REPLY_COLLECTION, "delete") => delete_reply(conn, &did, &uri),
+                (VOTE_COLLECTION, "create" | "update") => match parse::<Vote>(record) {
+                    Some(vote) if vote.value == 1 || vote.value == -1 => {
+                        index_vote(conn, policy, &did, uri, &seen_at, vote)
+                    }
+                    _ => {
+                        tracing::debug!("Skipping malformed vote: {uri}");
+                        Ok(())
+                    }
+                },
+                (VOTE_COLLECTION, "delete") => delete_vote(conn, policy, &uri),
+                (REACTION_COLLECTION, "create" | "update") => match parse::<Reaction>(record) {
+                    Some(reaction) if reactions.allows(&reaction.emoji) => {
+                        index_reaction(conn, &did, uri, reaction)
+                    }
+                    _ => {
+                        tracing::debug!("Skipping malformed or disallowed reaction: {uri}");
+                        // An update away from a counted emoji takes the reaction back
+                        delete_reaction(conn, &uri)
+                    }
+                },
+                (REACTION_COLLECTION, "delete") => delete_reaction(conn, &uri),
+                (FOLLOW_COLLECTION, "create" | "update") => match parse::<Follow>(record) {
+                    Some(follow) if follow.subject.starts_with("did:") => {
+                        index_follow(conn, &did, uri, follow)
+                    }
+                    _ => {
+                        tracing::debug!("Skipping malformed follow: {uri}");
+                        Ok(())
+                    }
+                },
+                (FOLLOW_COLLECTION, "delete") => delete_follow(conn, &uri),
+                _ => Ok(()),
+            }
+        }
+        JetstreamKind::Identity { identity } => {
+            use crate::schema::profile::dsl as ProfileSchema;
+
+            let now = rsky_common::now();
+            insert_into(ProfileSchema::profile)
+                .values((
+                    ProfileSchema::did.eq(&did),
+                    ProfileSchema::handle.eq(&identity.handle),
+                    ProfileSchema::indexedAt.eq(&now),
+                ))
+                .on_conflict(ProfileSchema::did)
+                .do_update()
+                .set((
+                    ProfileSchema::handle.eq(&identity.handle),
+                    ProfileSchema::indexedAt.eq(&now),
+                ))
+                .execute(conn)?;
+            Ok(())
+        }
+        JetstreamKind::Account { account } => {
+            use crate::schema::profile::dsl as ProfileSchema;
+
+            let now = rsky_common::now();
+            insert_into(ProfileSchema::profile)
+                .values((
+                    ProfileSchema::did.eq(&did),
+                    ProfileSchema::active.eq(account.active),
+                    ProfileSchema::indexedAt.eq(&now),
+                ))
+                .on_conflict(ProfileSchema::did)
+                .do_update()
+                .set((
+                    ProfileSchema::active.eq(account.active),
+                    ProfileSchema::indexedAt.eq(&now),
+                ))
+                .execute(conn)?;
+            Ok(())
+        }
+        JetstreamKind::Section { section } => {
+            use crate::schema::section::dsl as SectionSchema;
+
+            insert_into(SectionSchema::section)
+                .values((
+                    SectionSchema::id.eq(section.section_id),
+                    SectionSchema::threadCount.eq(0),
+                    SectionSchema::replyCount.eq(0),
+                    SectionSchema::status.eq(section.status.as_str()),
+                ))
+                .on_conflict(SectionSchema::id)
+                .do_update()
+                .set(SectionSchema::status.eq(section.status.as_str()))
+                .execute(conn)?;
+            Ok(())
+        }
+    }
+}
+
+/// Keeps the time of the earliest event from the account, which is what its
+/// age is measured from.
+fn note_first_seen(conn: &mut PgConnection, did: &String, seen_at: &String) -> Result<()> {
+    use crate::schema::profile::dsl as ProfileSchema;
+
+    insert_into(ProfileSchema::profile)
+        .values((
+            ProfileSchema::did.eq(did),
+            ProfileSchema::firstSeenAt.eq(seen_at),
+            ProfileSchema::indexedAt.eq(rsky_common::now()),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    // Events of several PDSes, e.g. after a migration, can arrive out of order
+    update(ProfileSchema::profile)
+        .filter(ProfileSchema::did.eq(did))
+        .filter(
+            ProfileSchema::firstSeenAt
+                .is_null()
+                .or(ProfileSchema::firstSeenAt.gt(seen_at)),
+        )
+        .set(ProfileSchema::firstSeenAt.eq(seen_at))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn parse<T: serde::de::DeserializeOwned>(record: Option<serde_json::Value>) -> Option<T> {
+    record.and_then(|record| serde_json::from_value(record).ok())
+}
+
+fn bump_section(
+    conn: &mut PgConnection,
+    id: i64,
+    threads: i64,
+    replies: i64,
+    activity: Option<&String>,
+) -> Result<()> {
+    use crate::schema::section::dsl as SectionSchema;
+
+    insert_into(SectionSchema::section)
+        .values((
+            SectionSchema::id.eq(id),
+            SectionSchema::threadCount.eq(threads.max(0)),
+            SectionSchema::replyCount.eq(replies.max(0)),
+        ))
+        .on_conflict(SectionSchema::id)
+        .do_update()
+        .set((
+            SectionSchema::threadCount.eq(SectionSchema::threadCount + threads),
+            SectionSchema::replyCount.eq(SectionSchema::replyCount + replies),
+        ))
+        .execute(conn)?;
+    if let Some(activity) = activity {
+        update(SectionSchema::section)
+            .filter(SectionSchema::id.eq(id))
+            .filter(
+                SectionSchema::lastActivityAt
+                    .is_null()
+                    .or(SectionSchema::lastActivityAt.lt(activity)),
+            )
+            .set(SectionSchema::lastActivityAt.eq(activity))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+fn bump_profile(conn: &mut PgConnection, did: &String, threads: i64, replies: i64) -> Result<()> {
+    use crate::schema::profile::dsl as ProfileSchema;
+
+    insert_into(ProfileSchema::profile)
+        .values((
+            ProfileSchema::did.eq(did),
+            ProfileSchema::threadCount.eq(threads.max(0)),
+            ProfileSchema::replyCount.eq(replies.max(0)),
+            ProfileSchema::indexedAt.eq(rsky_common::now()),
+        ))
+        .on_conflict(ProfileSchema::did)
+        .do_update()
+        .set((
+            ProfileSchema::threadCount.eq(ProfileSchema::threadCount + threads),
+            ProfileSchema::replyCount.eq(ProfileSchema::replyCount + replies),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn index_thread(
+    conn: &mut PgConnection,
+    policy: &VotePolicy,
+    did: &String,
+    uri: String,
+    cid: String,
+    post: Post,
+) -> Result<()> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let section_id = post.section_id as i64;
+    let (preview, preview_truncated) = truncate_graphemes(&post.text, PREVIEW_GRAPHEMES);
+    let image = post.first_image();
+    let quote = post.embed.as_ref().and_then(PostEmbed::quoted).cloned();
+    let existing = ThreadSchema::thread
+        .find(&uri)
+        .select(Thread::as_select())
+        .first(conn)
+        .optional()?;
+    if let Some(existing) = existing {
+        update(ThreadSchema::thread)
+            .filter(ThreadSchema::uri.eq(&uri))
+            .set((
+                ThreadSchema::cid.eq(&cid),
+                ThreadSchema::sectionId.eq(section_id),
+                ThreadSchema::title.eq(&post.title),
+                ThreadSchema::text.eq(&post.text),
+                ThreadSchema::preview.eq(&preview),
+                ThreadSchema::previewTruncated.eq(preview_truncated),
+                ThreadSchema::imageCid.eq(image.as_ref().map(|image| &image.cid)),
+                ThreadSchema::imageAlt.eq(image.as_ref().map(|image| &image.alt)),
+                ThreadSchema::imageMimeType.eq(image.as_ref().map(|image| &image.mime_type)),
+                ThreadSchema::quoteUri.eq(quote.as_ref().map(|quote| &quote.uri)),
+                ThreadSchema::quoteCid.eq(quote.as_ref().map(|quote| &quote.cid)),
+            ))
+            .execute(conn)?;
+        if existing.section_id != section_id {
+            bump_section(conn, existing.section_id, -1, -existing.reply_count, None)?;
+            bump_section(
+                conn,
+                section_id,
+                1,
+                existing.reply_count,
+                Some(&existing.last_activity_at),
+            )?;
+            // The new section can have a different minimum account age
+            let voters = voters_of(conn, &uri)?;
+            count_votes(conn, policy, &uri, &voters)?;
+        }
+        return Ok(());
+    }
+
+    let created_at = format!("{}", post.created_at.format(RFC3339_VARIANT));
+    // Replies and votes from other PDSes can be indexed before the post itself
+    let reply_count = update(ReplySchema::reply)
+        .filter(ReplySchema::threadUri.is_null())
+        .filter(ReplySchema::root.eq(&uri).or(ReplySchema::root.eq(&cid)))
+        .set(ReplySchema::threadUri.eq(&uri))
+        .execute(conn)? as i64;
+    let voters = voters_of(conn, &uri)?;
+    insert_into(ThreadSchema::thread)
+        .values(Thread {
+            uri: uri.clone(),
+            cid,
+            author: did.clone(),
+            section_id,
+            title: post.title,
+            text: post.text,
+            reply_count,
+            score: 0,
+            created_at: created_at.clone(),
+            last_activity_at: created_at.clone(),
+            indexed_at: rsky_common::now(),
+            preview,
+            preview_truncated,
+            image_cid: image.as_ref().map(|image| image.cid.clone()),
+            image_alt: image.as_ref().map(|image| image.alt.clone()),
+            image_mime_type: image.map(|image| image.mime_type),
+            quote_uri: quote.as_ref().map(|quote| quote.uri.clone()),
+            quote_cid: quote.map(|quote| quote.cid),
+        })
+        .execute(conn)?;
+    // Votes that arrived first were checked against the default minimum age
+    count_votes(conn, policy, &uri, &voters)?;
+    bump_section(conn, section_id, 1, reply_count, Some(&created_at))?;
+    bump_profile(conn, did, 1, 0)
+}
+
+fn delete_thread(conn: &mut PgConnection, did: &String, uri: &String) -> Result<()> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let deleted = delete(ThreadSchema::thread)
+        .filter(ThreadSchema::uri.eq(uri))
+        .returning(Thread::as_returning())
+        .get_result::<Thread>(conn)
+        .optional()?;
+    if let Some(deleted) = deleted {
+        // Keep the replies, they are reattached if the post comes back
+        update(ReplySchema::reply)
+            .filter(ReplySchema::threadUri.eq(uri))
+            .set(ReplySchema::threadUri.eq(None::<String>))
+            .execute(conn)?;
+        bump_section(conn, deleted.section_id, -1, -deleted.reply_count, None)?;
+        bump_profile(conn, did, -1, 0)?;
+    }
+    Ok(())
+}
+
+fn index_reply(
+    conn: &mut PgConnection,
+    did: &String,
+    uri: String,
+    cid: String,
+    reply: ReplyRecord,
+) -> Result<()> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let quote = reply.embed.as_ref().and_then(PostEmbed::quoted).cloned();
+    let exists = ReplySchema::reply
+        .find(&uri)
+        .select(ReplySchema::uri)
+        .first::<String>(conn)
+        .optional()?
+        .is_some();
+    if exists {
+        update(ReplySchema::reply)
+            .filter(ReplySchema::uri.eq(&uri))
+            .set((
+                ReplySchema::cid.eq(&cid),
+                ReplySchema::parent.eq(&reply.parent),
+                ReplySchema::text.eq(&reply.text),
+                ReplySchema::quoteUri.eq(quote.as_ref().map(|quote| &quote.uri)),
+                ReplySchema::quoteCid.eq(quote.as_ref().map(|quote| &quote.cid)),
+            ))
+            .execute(conn)?;
+        return Ok(());
+    }
+
+    let created_at = format!("{}", reply.created_at.format(RFC3339_VARIANT));
+    // The root is the thread post's cid, accept its uri as well
+    let thread = ThreadSchema::thread
+        .filter(
+            ThreadSchema::uri
+                .eq(&reply.root)
+                .or(ThreadSchema::cid.eq(&reply.root)),
+        )
+        .select(Thread::as_select())
+        .first(conn)
+        .optional()?;
+    insert_into(ReplySchema::reply)
+        .values(Reply {
+            uri,
+            cid,
+            author: did.clone(),
+            thread_uri: thread.as_ref().map(|thread| thread.uri.clone()),
+            root: reply.root,
+            parent: reply.parent,
+            text: reply.text,
+            created_at: created_at.clone(),
+            indexed_at: rsky_common::now(),
+            quote_uri: quote.as_ref().map(|quote| quote.uri.clone()),
+            quote_cid: quote.map(|quote| quote.cid),
+        })
+        .execute(conn)?;
+    if let Some(thread) = thread {
+        let last_activity_at = match thread.last_activity_at < created_at {
+            true => created_at.clone(),
+            false => thread.last_activity_at,
+        };
+        update(ThreadSchema::thread)
+            .filter(ThreadSchema::uri.eq(&thread.uri))
+            .set((
+                ThreadSchema::replyCount.eq(ThreadSchema::replyCount + 1),
+                ThreadSchema::lastActivityAt.eq(last_activity_at),
+            ))
+            .execute(conn)?;
+        bump_section(conn, thread.section_id, 0, 1, Some(&created_at))?;
+    }
+    bump_profile(conn, did, 0, 1)
+}
+
+fn delete_reply(conn: &mut PgConnection, did: &String, uri: &String) -> Result<()> {
+    use crate::schema::reply::dsl as ReplySchema;
+    use crate::schema::thread::dsl as ThreadSchema;
+
+    let deleted = delete(ReplySchema::reply)
+        .filter(ReplySchema::uri.eq(uri))
+        .returning(Reply::as_returning())
+        .get_result::<Reply>(conn)
+        .optional()?;
+    let Some(deleted) = deleted else {
+        return Ok(());
+    };
+    if let Some(thread_uri) = deleted.thread_uri {
+        let section_id = update(ThreadSchema::thread)
+            .filter(ThreadSchema::uri.eq(&thread_uri))
+            .set(ThreadSchema::replyCount.eq(ThreadSchema::replyCount - 1))
+            .returning(ThreadSchema::sectionId)
+            .get_result::<i64>(conn)
+            .optional()?;
+        if let Some(section_id) = section_id {
+            bump_section(conn, section_id, 0, -1, None)?;
+        }
+    }
+    bump_profile(conn, did, 0, -1)
+}
+
+fn voters_of(conn: &mut PgConnection, subject: &String) -> Result<Vec<String>> {
+    use crate::schema::vote::dsl as VoteSchema;
+
+    Ok(VoteSchema::vote
+        .filter(VoteSchema::subject.eq(subject))
+        .select(VoteSchema::author)
+        .distinct()
+        .load::<String>(conn)?)
+}
+
+/// Lets only the newest vote of each of `voters` on `subject` count, if the
+/// account was old enough for the subject's section when casting it, then
+/// refreshes the subject's score. Changing a vote is writing a new one, the
+/// older one stops counting; deleting it brings the previous one back.
+fn count_votes(
+    conn: &mut PgConnection,
+    policy: &VotePolicy,
+    subject: &String,
+    voters: &[String],
+) -> Result<()> {
+    use crate::schema::thread::dsl as ThreadSchema;
+    use crate::schema::vote::dsl as VoteSchema;
+
+    let section_id = ThreadSchema::thread
+        .find(subject)
+        .select(ThreadSchema::sectionId)
+        .first::<i64>(conn)
+        .optional()?;
+    let min_account_age_secs = policy.min_account_age_secs(section_id);
+    for voter in voters {
+        let counted = VoteSchema::vote
+            .filter(VoteSchema::author.eq(voter))
+            .filter(VoteSchema::subject.eq(subject))
+            // Votes indexed before accounts had an age keep counting
+            .filter(
+                VoteSchema::accountAgeSecs
+                    .is_null()
+                    .or(VoteSchema::accountAgeSecs.ge(min_account_age_secs)),
+            )
+            .order((VoteSchema::seenAt.desc(), VoteSchema::uri.desc()))
+            .select(VoteSchema::uri)
+            .first::<String>(conn)
+            .optional()?;
+        update(VoteSchema::vote)
+            .filter(VoteSchema::author.eq(voter))
+            .filter(VoteSchema::subject.eq(subject))
+            .filter(VoteSchema::counted.eq(true))
+            .set(VoteSchema::counted.eq(false))
+            .execute(conn)?;
+        if let Some(counted) = counted {
+            update(VoteSchema::vote)
+                .filter(VoteSchema::uri.eq(counted))
+                .set(VoteSchema::counted.eq(true))
+                .execute(conn)?;
+        }
+    }
+
+    let score = VoteSchema::vote
+        .filter(VoteSchema::subject.eq(subject))
+        .filter(VoteSchema::counted.eq(true))
+        .select(sum(VoteSchema::value))
+        .first::<Option<i64>>(conn)?
+        .unwrap_or(0);
+    update(ThreadSchema::thread)
+        .filter(ThreadSchema::uri.eq(subject))
+        .set(ThreadSchema::score.eq(score))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Seconds between the account's first event and `seen_at`, `None` if either
+/// time is unknown.
+fn account_age_secs(
+    conn: &mut PgConnection,
+    did: &String,
+    seen_at: &String,
+) -> Result<Option<i64>> {
+    use crate::schema::profile::dsl as ProfileSchema;
+
+    let first_seen_at = ProfileSchema::profile
+        .find(did)
+        .select(ProfileSchema::firstSeenAt)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten();
+    Ok(match first_seen_at {
+        Some(first_seen_at) => match (
+            from_str_to_millis(&first_seen_at),
+            from_str_to_millis(seen_at),
+        ) {
+            (Ok(first), Ok(seen)) => Some((seen - first).max(0) / 1000),
+            _ => None,
+        },
+        None => None,
+    })
+}
+
+fn index_vote(
+    conn: &mut PgConnection,
+    policy: &VotePolicy,
+    did: &String,
+    uri: String,
+    seen_at: &String,
+    vote: Vote,
+) -> Result<()> {
+    use crate::schema::vote::dsl as VoteSchema;
+
+    let previous = VoteSchema::vote
+        .find(&uri)
+        .select(VoteSchema::subject)
+        .first::<String>(conn)
+        .optional()?;
+    let account_age_secs = account_age_secs(conn, did, seen_at)?;
+    let value = vote.value as i16;
+    insert_into(VoteSchema::vote)
+        .values((
+            VoteSchema::uri.eq(&uri),
+            VoteSchema::author.eq(did),
+            VoteSchema::subject.eq(&vote.subject),
+            VoteSchema::value.eq(value),
+            VoteSchema::createdAt.eq(format!("{}", vote.created_at.format(RFC3339_VARIANT))),
+            VoteSchema::indexedAt.eq(rsky_common::now()),
+            VoteSchema::seenAt.eq(seen_at),
+            VoteSchema::accountAgeSecs.eq(account_age_secs),
+            VoteSchema::counted.eq(false),
+        ))
+        .on_conflict(VoteSchema::uri)
+        .do_update()
+        .set((
+            VoteSchema::subject.eq(&vote.subject),
+            VoteSchema::value.eq(value),
+            VoteSchema::seenAt.eq(seen_at),
+            VoteSchema::accountAgeSecs.eq(account_age_secs),
+        ))
+        .execute(conn)?;
+    let voters = [did.clone()];
+    if let Some(previous) = previous.filter(|previous| *previous != vote.subject) {
+        count_votes(conn, policy, &previous, &voters)?;
+    }
+    count_votes(conn, policy, &vote.subject, &voters)
+}
+
+fn delete_vote(conn: &mut PgConnection, policy: &VotePolicy, uri: &String) -> Result<()> {
+    use crate::schema::vote::dsl as VoteSchema;
+
+    let deleted = delete(VoteSchema::vote)
+        .filter(VoteSchema::uri.eq(uri))
+        .returning((VoteSchema::author, VoteSchema::subject))
+        .get_result::<(String, String)>(conn)
+        .optional()?;
+    if let Some((author, subject)) = deleted {
+        count_votes(conn, policy, &subject, &[author])?;
+    }
+    Ok(())
+}
+
+/// Refreshes how many accounts reacted to `subject` with `emoji`. An account
+/// reacting twice with the same emoji counts once.
+fn count_reactions(conn: &mut PgConnection, subject: &String, emoji: &String) -> Result<()> {
+    use crate::schema::reaction::dsl as ReactionSchema;
+    use crate::schema::reaction_count::dsl as ReactionCountSchema;
+
+    let count = ReactionSchema::reaction
+        .filter(ReactionSchema::subject.eq(subject))
+        .filter(ReactionSchema::emoji.eq(emoji))
+        .select(count_distinct(ReactionSchema::author))
+        .first::<i64>(conn)?;
+    if count == 0 {
+        delete(ReactionCountSchema::reaction_count)
+            .filter(ReactionCountSchema::subject.eq(subject))
+            .filter(ReactionCountSchema::emoji.eq(emoji))
+            .execute(conn)?;
+        return Ok(());
+    }
+    insert_into(ReactionCountSchema::reaction_count)
+        .values((
+            ReactionCountSchema::subject.eq(subject),
+            ReactionCountSchema::emoji.eq(emoji),
+            ReactionCountSchema::count.eq(count),
+        ))
+        .on_conflict((ReactionCountSchema::subject, ReactionCountSchema::emoji))
+        .do_update()
+        .set(ReactionCountSchema::count.eq(count))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn index_reaction(
+    conn: &mut PgConnection,
+    did: &String,
+    uri: String,
+    reaction: Reaction,
+) -> Result<()> {
+    use crate::schema::reaction::dsl as ReactionSchema;
+
+    let previous = ReactionSchema::reaction
+        .find(&uri)
+        .select((ReactionSchema::subject, ReactionSchema::emoji))
+        .first::<(String, String)>(conn)
+        .optional()?;
+    insert_into(ReactionSchema::reaction)
+        .values((
+            ReactionSchema::uri.eq(&uri),
+            ReactionSchema::author.eq(did),
+            ReactionSchema::subject.eq(&reaction.subject),
+            ReactionSchema::emoji.eq(&reaction.emoji),
+            ReactionSchema::createdAt
+                .eq(format!("{}", reaction.created_at.format(RFC3339_VARIANT))),
+            ReactionSchema::indexedAt.eq(rsky_common::now()),
+        ))
+        .on_conflict(ReactionSchema::uri)
+        .do_update()
+        .set((
+            ReactionSchema::subject.eq(&reaction.subject),
+            ReactionSchema::emoji.eq(&reaction.emoji),
+        ))
+        .execute(conn)?;
+    let moved_from = previous
+        .filter(|(subject, emoji)| *subject != reaction.subject || *emoji != reaction.emoji);
+    if let Some((subject, emoji)) = moved_from {
+        count_reactions(conn, &subject, &emoji)?;
+    }
+    count_reactions(conn, &reaction.subject, &reaction.emoji)
+}
+
+fn delete_reaction(conn: &mut PgConnection, uri: &String) -> Result<()> {
+    use crate::schema::reaction::dsl as ReactionSchema;
+
+    let deleted = delete(ReactionSchema::reaction)
+        .filter(ReactionSchema::uri.eq(uri))
+        .returning((ReactionSchema::subject, ReactionSchema::emoji))
+        .get_result::<(String, String)>(conn)
+        .optional()?;
+    if let Some((subject, emoji)) = deleted {
+        count_reactions(conn, &subject, &emoji)?;
+    }
+    Ok(())
+}
+
+fn index_follow(conn: &mut PgConnection, did: &String, uri: String, follow: Follow) -> Result<()> {
+    use crate::schema::follow::dsl as FollowSchema;
+
+    insert_into(FollowSchema::follow)
+        .values((
+            FollowSchema::uri.eq(&uri),
+            FollowSchema::author.eq(did),
+            FollowSchema::subject.eq(&follow.subject),
+            FollowSchema::createdAt.eq(format!("{}", follow.created_at.format(RFC3339_VARIANT))),
+            FollowSchema::indexedAt.eq(rsky_common::now()),
+        ))
+        .on_conflict(FollowSchema::uri)
+        .do_update()
+        .set(FollowSchema::subject.eq(&follow.subject))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn delete_follow(conn: &mut PgConnection, uri: &String) -> Result<()> {
+    use crate::schema::follow::dsl as FollowSchema;
+
+    delete(FollowSchema::follow)
+        .filter(FollowSchema::uri.eq(uri))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commit_events() {
+        let evt: JetstreamEvt = serde_json::from_str(
+            r#"{"did":"did:ckb:abc","time_us":1,"seq":42,"kind":"commit","commit":{"rev":"3l","operation":"create","collection":"app.bbs.vote","rkey":"3m","record":{"$type":"app.bbs.vote","createdAt":"2025-01-01T00:00:00.000Z","subject":"at://did:ckb:abc/app.bbs.post/3k","value":-1},"cid":"bafy"}}"#,
+        )
+        .unwrap();
+        assert_eq!(evt.seq, 42);
+        let JetstreamKind::Commit { commit } = evt.kind else {
+            panic!("expected a commit");
+        };
+        let vote: Vote = parse(commit.record).unwrap();
+        assert_eq!(vote.value, -1);
+    }
+
+    #[test]
+    fn parses_vote_policies() {
+        let policy = VotePolicy::parse(Some("24"), Some("3:168, 7:0")).unwrap();
+        assert_eq!(policy.min_account_age_secs(None), 24 * 3600);
+        assert_eq!(policy.min_account_age_secs(Some(1)), 24 * 3600);
+        assert_eq!(policy.min_account_age_secs(Some(3)), 168 * 3600);
+        assert_eq!(policy.min_account_age_secs(Some(7)), 0);
+        assert_eq!(
+            VotePolicy::parse(None, None).unwrap(),
+            VotePolicy::default()
+        );
+        assert!(VotePolicy::parse(None, Some("3")).is_err());
+        assert!(VotePolicy::parse(Some("a day"), None).is_err());
+    }
+
+    #[test]
+    fn parses_reaction_policies() {
+        let policy = ReactionPolicy::parse(Some(" heart, :+1:,,eyes"));
+        assert_eq!(policy.emojis, vec!["heart", "+1", "eyes"]);
+        assert!(policy.allows("+1"));
+        assert!(!policy.allows("rocket"));
+        assert_eq!(ReactionPolicy::parse(None), ReactionPolicy::default());
+        assert_eq!(
+            ReactionPolicy::parse(Some(" , ")),
+            ReactionPolicy::default()
+        );
+        assert!(ReactionPolicy::default().allows("rocket"));
+
+        let evt: JetstreamEvt = serde_json::from_str(
+            r#"{"did":"did:ckb:abc","time_us":1,"seq":43,"kind":"commit","commit":{"rev":"3l","operation":"create","collection":"app.bbs.reaction","rkey":"3n","record":{"$type":"app.bbs.reaction","createdAt":"2025-01-01T00:00:00.000Z","subject":"at://did:ckb:abc/app.bbs.post/3k","emoji":"heart"},"cid":"bafy"}}"#,
+        )
+        .unwrap();
+        let JetstreamKind::Commit { commit } = evt.kind else {
+            panic!("expected a commit");
+        };
+        let reaction: Reaction = parse(commit.record).unwrap();
+        assert_eq!(reaction.emoji, "heart");
+    }
+
+    #[test]
+    fn parses_account_events() {
+        let evt: JetstreamEvt = serde_json::from_str(
+            r#"{"did":"did:ckb:abc","time_us":1,"seq":7,"kind":"account","account":{"did":"did:ckb:abc","active":false,"status":"takendown","seq":7,"time":"2025-01-01T00:00:00.000Z"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            evt.kind,
+            JetstreamKind::Account {
+                account: JetstreamAccount { active: false }
+            }
+        ));
+    }
+
+    #[test]
+    fn parses_section_events() {
+        let evt: JetstreamEvt = serde_json::from_str(
+            r#"{"did":"did:ckb:mod","time_us":1,"seq":8,"kind":"section","section":{"sectionId":3,"status":"archived","seq":8,"time":"2025-01-01T00:00:00.000Z"}}"#,
+        )
+        .unwrap();
+        let JetstreamKind::Section { section } = evt.kind else {
+            panic!("expected a section event");
+        };
+        assert_eq!(section.section_id, 3);
+        assert_eq!(section.status, SectionStatus::Archived);
+    }
+}
@@ -0,0 +1,21 @@
+#[macro_use]
+extern crate serde_derive;
+
+extern crate serde;
+extern crate serde_json;
+
+extern crate rsky_lexicon;
+
+use diesel::pg::PgConnection;
+use rocket_sync_db_pools::database;
+
+#[database("pg_db")]
+pub struct DbConn(PgConnection);
+
+pub mod apis;
+pub mod db;
+pub mod error;
+pub mod indexer;
+pub mod models;
+pub mod routes;
+pub mod schema;
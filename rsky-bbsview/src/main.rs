@@ -0,0 +1,60 @@
+#[macro_use]
+extern crate rocket;
+use dotenvy::dotenv;
+use rocket::figment::{
+    util::map,
+    value::{Map, Value},
+};
+use rsky_bbsview::indexer::{Indexer, ReactionPolicy, VotePolicy};
+use rsky_bbsview::routes::*;
+use rsky_bbsview::DbConn;
+use std::env;
+
+#[launch]
+async fn rocket() -> _ {
+    dotenv().ok();
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+
+    let database_url = env::var("DATABASE_URL").unwrap_or("".into());
+    let db: Map<_, Value> = map! {
+        "url" => database_url.into(),
+        "pool_size" => 20.into(),
+        "timeout" => 30.into(),
+    };
+    let figment = rocket::Config::figment().merge(("databases", map!["pg_db" => db]));
+
+    // Comma separated websocket urls of the PDSes to index, e.g. wss://pds.example.com
+    let endpoints = env::var("BBSVIEW_PDS_ENDPOINTS").unwrap_or("".into());
+    let vote_policy = VotePolicy::from_env().expect("Invalid vote minimum account age");
+    let reaction_policy = ReactionPolicy::from_env();
+    for endpoint in endpoints
+        .split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    {
+        let indexer = Indexer::new(
+            endpoint.to_string(),
+            vote_policy.clone(),
+            reaction_policy.clone(),
+        );
+        tokio::spawn(async move { indexer.start().await });
+    }
+
+    rocket::custom(figment)
+        .mount(
+            "/",
+            routes![
+                index,
+                get_author_feed,
+                get_followers,
+                get_follows,
+                get_section_feed,
+                get_vote_bursts,
+                get_thread,
+                search_posts,
+                well_known
+            ],
+        )
+        .attach(DbConn::fairing())
+}
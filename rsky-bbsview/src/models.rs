@@ -0,0 +1,137 @@
+use diesel::prelude::*;
+
+#[derive(Queryable, Selectable, Insertable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::thread)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Thread {
+    pub uri: String,
+    pub cid: String,
+    pub author: String,
+    #[diesel(column_name = sectionId)]
+    #[serde(rename = "sectionId")]
+    pub section_id: i64,
+    pub title: String,
+    pub text: String,
+    #[diesel(column_name = replyCount)]
+    #[serde(rename = "replyCount")]
+    pub reply_count: i64,
+    pub score: i64,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = lastActivityAt)]
+    #[serde(rename = "lastActivityAt")]
+    pub last_activity_at: String,
+    #[diesel(column_name = indexedAt)]
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+    /// The first graphemes of `text`, what listings return
+    pub preview: String,
+    #[diesel(column_name = previewTruncated)]
+    #[serde(rename = "previewTruncated")]
+    pub preview_truncated: bool,
+    #[diesel(column_name = imageCid)]
+    #[serde(rename = "imageCid")]
+    pub image_cid: Option<String>,
+    #[diesel(column_name = imageAlt)]
+    #[serde(rename = "imageAlt")]
+    pub image_alt: Option<String>,
+    #[diesel(column_name = imageMimeType)]
+    #[serde(rename = "imageMimeType")]
+    pub image_mime_type: Option<String>,
+    /// The post or reply quoted with app.bbs.embed.record
+    #[diesel(column_name = quoteUri)]
+    #[serde(rename = "quoteUri")]
+    pub quote_uri: Option<String>,
+    #[diesel(column_name = quoteCid)]
+    #[serde(rename = "quoteCid")]
+    pub quote_cid: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::reply)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Reply {
+    pub uri: String,
+    pub cid: String,
+    pub author: String,
+    #[diesel(column_name = threadUri)]
+    #[serde(rename = "threadUri")]
+    pub thread_uri: Option<String>,
+    pub root: String,
+    pub parent: String,
+    pub text: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = indexedAt)]
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+    #[diesel(column_name = quoteUri)]
+    #[serde(rename = "quoteUri")]
+    pub quote_uri: Option<String>,
+    #[diesel(column_name = quoteCid)]
+    #[serde(rename = "quoteCid")]
+    pub quote_cid: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::follow)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Follow {
+    pub uri: String,
+    pub author: String,
+    pub subject: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = indexedAt)]
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::profile)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Profile {
+    pub did: String,
+    pub handle: Option<String>,
+    pub active: bool,
+    #[diesel(column_name = threadCount)]
+    #[serde(rename = "threadCount")]
+    pub thread_count: i64,
+    #[diesel(column_name = replyCount)]
+    #[serde(rename = "replyCount")]
+    pub reply_count: i64,
+    #[diesel(column_name = indexedAt)]
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+    #[diesel(column_name = firstSeenAt)]
+    #[serde(rename = "firstSeenAt")]
+    pub first_seen_at: Option<String>,
+}
+
+#[derive(Queryable, Selectable, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::sub_state)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubState {
+    pub service: String,
+    pub cursor: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WellKnown {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    pub id: String,
+    pub service: Vec<KnownService>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KnownService {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub r#type: String,
+    #[serde(rename = "serviceEndpoint")]
+    pub service_endpoint: String,
+}
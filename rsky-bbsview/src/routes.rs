@@ -0,0 +1,297 @@
+use crate::apis::{self, parse_cursor, FeedSort};
+use crate::error::ApiError;
+use crate::models::{KnownService, WellKnown};
+use crate::DbConn;
+use chrono::{DateTime, Duration, Utc};
+use rocket::request::{FromRequest, Outcome};
+use rocket::serde::json::Json;
+use rocket::Request;
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::app::bbs::graph::{GetFollowersOutput, GetFollowsOutput};
+use rsky_lexicon::app::bbs::{
+    GetAuthorFeedOutput, GetSectionFeedOutput, GetThreadOutput, GetVoteBurstsOutput,
+    SearchPostsOutput,
+};
+use std::env;
+
+const DEFAULT_LIMIT: i64 = 30;
+const MAX_LIMIT: i64 = 100;
+
+fn validate_limit(limit: Option<i64>) -> Result<i64, ApiError> {
+    match limit.unwrap_or(DEFAULT_LIMIT) {
+        limit if (1..=MAX_LIMIT).contains(&limit) => Ok(limit),
+        _ => Err(ApiError::InvalidRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        ))),
+    }
+}
+
+fn validate_cursor(cursor: Option<String>) -> Result<Option<(String, String)>, ApiError> {
+    cursor
+        .map(|cursor| parse_cursor(&cursor))
+        .transpose()
+        .map_err(|error| ApiError::InvalidRequest(error.to_string()))
+}
+
+fn validate_actor(actor: &str) -> Result<(), ApiError> {
+    match actor.starts_with("did:") {
+        true => Ok(()),
+        false => Err(ApiError::InvalidRequest(format!(
+            "actor must be a DID: {actor}"
+        ))),
+    }
+}
+
+/// The bearer token a request was sent with, if any
+pub struct BearerToken(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(str::to_string);
+        Outcome::Success(BearerToken(token))
+    }
+}
+
+/// Admin endpoints only exist with BBSVIEW_ADMIN_TOKEN set, and take it as
+/// the bearer token.
+fn validate_admin(token: &BearerToken) -> Result<(), ApiError> {
+    let expected = env::var("BBSVIEW_ADMIN_TOKEN").unwrap_or("".into());
+    if expected.is_empty() {
+        return Err(ApiError::NotFound("Not Found".to_string()));
+    }
+    match token.0 {
+        Some(ref token) if *token == expected => Ok(()),
+        _ => Err(ApiError::AuthRequired("Invalid admin token".to_string())),
+    }
+}
+
+#[rocket::get("/")]
+pub async fn index() -> &'static str {
+    "Welcome to the BBS AppView"
+}
+
+/// DID document for `did:web:{BBSVIEW_HOSTNAME}`, so a PDS can resolve
+/// `atproto-proxy: did:web:{hostname}#bbs_appview` to this service.
+#[rocket::get("/.well-known/did.json")]
+pub async fn well_known() -> Result<Json<WellKnown>, ApiError> {
+    let hostname = env::var("BBSVIEW_HOSTNAME").unwrap_or("".into());
+    if hostname.is_empty() {
+        return Err(ApiError::NotFound("Not Found".to_string()));
+    }
+    Ok(Json(WellKnown {
+        context: vec!["https://www.w3.org/ns/did/v1".into()],
+        id: format!("did:web:{hostname}"),
+        service: vec![KnownService {
+            id: "#bbs_appview".to_owned(),
+            r#type: "BbsAppView".to_owned(),
+            service_endpoint: format!("https://{hostname}"),
+        }],
+    }))
+}
+
+/// A thread with its replies, oldest first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getThread?<uri>&<limit>&<cursor>")]
+pub async fn get_thread(
+    uri: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<GetThreadOutput>, ApiError> {
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    let not_found = format!("Thread not found: {uri}");
+    match connection
+        .run(move |conn| apis::get_thread(conn, uri, limit, cursor))
+        .await
+    {
+        Ok(Some(res)) => Ok(Json(res)),
+        Ok(None) => Err(ApiError::NotFound(not_found)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Threads in a section, sorted by `latest` (default), `active` or `top`.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getSectionFeed?<section>&<sort>&<limit>&<cursor>")]
+pub async fn get_section_feed(
+    section: i64,
+    sort: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<GetSectionFeedOutput>, ApiError> {
+    let sort = FeedSort::parse(sort.as_deref())
+        .map_err(|error| ApiError::InvalidRequest(error.to_string()))?;
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    if let (FeedSort::Top, Some((score, _))) = (sort, &cursor) {
+        if score.parse::<i64>().is_err() {
+            return Err(ApiError::InvalidRequest("Malformed cursor".to_string()));
+        }
+    }
+    match connection
+        .run(move |conn| apis::get_section_feed(conn, section, sort, limit, cursor))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Full text search over thread titles and bodies, optionally within one section.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.searchPosts?<q>&<section>&<limit>&<cursor>")]
+pub async fn search_posts(
+    q: String,
+    section: Option<i64>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<SearchPostsOutput>, ApiError> {
+    if q.trim().is_empty() {
+        return Err(ApiError::InvalidRequest("q must not be empty".to_string()));
+    }
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    match connection
+        .run(move |conn| apis::search_posts(conn, q, section, limit, cursor))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Threads and replies written by one account, newest first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getAuthorFeed?<actor>&<limit>&<cursor>")]
+pub async fn get_author_feed(
+    actor: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<GetAuthorFeedOutput>, ApiError> {
+    validate_actor(&actor)?;
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    match connection
+        .run(move |conn| apis::get_author_feed(conn, actor, limit, cursor))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Accounts an account follows, most recently followed first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.graph.getFollows?<actor>&<limit>&<cursor>")]
+pub async fn get_follows(
+    actor: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<GetFollowsOutput>, ApiError> {
+    validate_actor(&actor)?;
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    match connection
+        .run(move |conn| apis::get_follows(conn, actor, limit, cursor))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Accounts following an account, most recent followers first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.graph.getFollowers?<actor>&<limit>&<cursor>")]
+pub async fn get_followers(
+    actor: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    connection: DbConn,
+) -> Result<Json<GetFollowersOutput>, ApiError> {
+    validate_actor(&actor)?;
+    let limit = validate_limit(limit)?;
+    let cursor = validate_cursor(cursor)?;
+    match connection
+        .run(move |conn| apis::get_followers(conn, actor, limit, cursor))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Subjects that got an unusual number of votes within a short window, for
+/// spotting brigading. Only for the AppView's operator.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.admin.getVoteBursts?<since>&<window>&<threshold>&<limit>")]
+pub async fn get_vote_bursts(
+    since: Option<String>,
+    window: Option<i64>,
+    threshold: Option<i64>,
+    limit: Option<i64>,
+    token: BearerToken,
+    connection: DbConn,
+) -> Result<Json<GetVoteBurstsOutput>, ApiError> {
+    validate_admin(&token)?;
+    let since = match since {
+        Some(since) => DateTime::parse_from_rfc3339(&since)
+            .map_err(|_| ApiError::InvalidRequest(format!("since isn't a datetime: {since}")))?
+            .with_timezone(&Utc),
+        None => Utc::now() - Duration::hours(24),
+    };
+    let since = format!("{}", since.format(RFC3339_VARIANT));
+    let window = window.unwrap_or(10);
+    if !(1..=24 * 60).contains(&window) {
+        return Err(ApiError::InvalidRequest(
+            "window must be between 1 and 1440 minutes".to_string(),
+        ));
+    }
+    let threshold = threshold.unwrap_or(20);
+    if threshold < 2 {
+        return Err(ApiError::InvalidRequest(
+            "threshold must be at least 2".to_string(),
+        ));
+    }
+    let limit = validate_limit(limit)?;
+    match connection
+        .run(move |conn| apis::get_vote_bursts(conn, since, window, threshold, limit))
+        .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -12,8 +12,13 @@ const RAWCODEC: u64 = 0x77;
 
 pub fn cid_for_cbor<T: Serialize>(data: &T) -> Result<Cid> {
     let bytes = crate::struct_to_cbor(data)?;
+    cid_for_cbor_bytes(&bytes)
+}
+
+/// CID of an already encoded DAG-CBOR block
+pub fn cid_for_cbor_bytes(bytes: &[u8]) -> Result<Cid> {
     let mut sha = Sha256::new();
-    sha.update(&bytes);
+    sha.update(bytes);
     let hash = sha.finalize();
     let cid = Cid::new_v1(
         DAGCBORCODEC,
@@ -40,6 +40,14 @@ impl TID {
         Ok(TID(no_dashes))
     }
 
+    /// Whether `str` is a TID exactly as it's written into a commit: 13
+    /// sortable base32 characters, no dashes, with the top bit clear.
+    pub fn is_canonical(str: &str) -> bool {
+        str.len() == TID_LEN
+            && str.chars().all(|c| S32_CHAR.contains(c))
+            && str.starts_with(|c| "234567abcdefghij".contains(c))
+    }
+
     pub fn from_time(timestamp: usize, clock_id: usize) -> Self {
         let str = format!("{0}{1:2>2}", s32encode(timestamp), s32encode(clock_id));
         TID(str)
@@ -23,7 +23,7 @@ pub fn extract_prefixed_bytes(multikey: String) -> Result<Vec<u8>> {
 }
 
 pub fn has_prefix(bytes: &Vec<u8>, prefix: &Vec<u8>) -> bool {
-    *prefix == bytes[0..prefix.len()]
+    bytes.starts_with(prefix)
 }
 
 pub fn random_bytes(len: usize) -> Vec<u8> {
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// Namespace every BBS client preference's `$type` has to be in
+pub const PREFERENCES_NAMESPACE: &str = "app.bbs";
+/// `$type` of [`CrosspostPref`], which preferences are named by
+pub const CROSSPOST_PREF: &str = "app.bbs.actor.defs#crosspostPref";
+
+/// The requester's BBS client preferences. Each is an object whose `$type`
+/// is under app.bbs, e.g. `app.bbs.actor.defs#themePref`, and whose other
+/// fields are up to the client.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct GetPreferencesOutput {
+    pub preferences: Vec<serde_json::Value>,
+}
+
+/// Replaces all of the requester's BBS client preferences
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PutPreferencesInput {
+    pub preferences: Vec<serde_json::Value>,
+}
+
+/// Mirrors the account's new BBS posts to app.bsky.feed.post records, each
+/// cut short with a link back to its thread. Only PDSes running the bridge
+/// honor it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.actor.defs#crosspostPref")]
+#[serde(rename_all = "camelCase")]
+pub struct CrosspostPref {
+    pub enabled: bool,
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// An automoderation rule for one BBS section. A post or reply trips the rule
+/// when any one of its conditions holds; a rule without conditions never
+/// trips. Only rules published by the section's moderators are enforced.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.automod.rule")]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// Client-declared timestamp when this rule was created.
+    pub created_at: DateTime<Utc>,
+    /// Section the rule applies to
+    pub section_id: usize,
+    /// What happens to a post that trips the rule
+    pub action: RuleAction,
+    /// Label applied by the `label` action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Shown to the author of a rejected post and to moderators reviewing a held one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Words or phrases, matched case-insensitively against title and text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+    /// Regular expressions matched against title and text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<Vec<String>>,
+    /// Trips for accounts younger than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_account_age_hours: Option<usize>,
+    /// Trips for posts with more links than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_links: Option<usize>,
+    /// Trips for posts linking anywhere else, subdomains included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+    /// Trips once the author has made this many BBS posts and replies within the past hour
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_posts_per_hour: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Refuse the write
+    Reject,
+    /// Commit the write but keep it out of listings until a moderator reviews it
+    Hold,
+    /// Commit the write and label it
+    Label,
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Send a direct message, either into a conversation the sender is already in
+/// or to a set of members, which starts their conversation if there isn't one.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendMessageInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub convo_id: Option<String>,
+    /// DIDs of the other members, accounts on the sender's PDS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub members: Option<Vec<String>>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageView {
+    pub id: String,
+    pub convo_id: String,
+    /// DID of the member who sent the message
+    pub sender: String,
+    pub text: String,
+    pub sent_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvoView {
+    pub id: String,
+    /// DIDs of every member, the requester included
+    pub members: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_message: Option<MessageView>,
+    /// Messages from other members newer than the requester's read marker
+    pub unread_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListConvosOutput {
+    pub convos: Vec<ConvoView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListMessagesOutput {
+    /// Newest first
+    pub messages: Vec<MessageView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// Moves the requester's read marker in a conversation forward.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkReadInput {
+    pub convo_id: String,
+    /// Newest message read, the conversation's latest when left out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+}
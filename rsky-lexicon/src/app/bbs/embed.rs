@@ -0,0 +1,106 @@
+use crate::app::bbs::AuthorView;
+use crate::app::bsky::embed::Embeds;
+use crate::com::atproto::repo::StrongRef;
+use serde::{Deserialize, Serialize};
+
+/// Quotes another BBS post or reply, in this thread or any other.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.embed.record")]
+#[serde(rename_all = "camelCase")]
+pub struct Record {
+    /// The app.bbs.post or app.bbs.reply quoted
+    pub record: StrongRef,
+}
+
+/// What a BBS post or reply may embed: a quote of another BBS post, or any
+/// of the app.bsky embeds.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PostEmbed {
+    // tried first, its variants are told apart by `$type`
+    Bsky(Embeds),
+    Record(Record),
+}
+
+impl PostEmbed {
+    /// The BBS post or reply quoted, if that's what's embedded
+    pub fn quoted(&self) -> Option<&StrongRef> {
+        match self {
+            PostEmbed::Record(embed) => Some(&embed.record),
+            PostEmbed::Bsky(_) => None,
+        }
+    }
+}
+
+/// A quote as hydrated by the BBS AppView, then by the reader's PDS
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.embed.record#view")]
+#[serde(rename_all = "camelCase")]
+pub struct View {
+    pub record: ViewUnion,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ViewUnion {
+    ViewRecord(ViewRecord),
+    ViewNotFound(ViewNotFound),
+    ViewBlocked(ViewBlocked),
+}
+
+impl ViewUnion {
+    /// AT URI of the quoted post or reply, whichever way it's shown
+    pub fn uri(&self) -> &str {
+        match self {
+            ViewUnion::ViewRecord(view) => &view.uri,
+            ViewUnion::ViewNotFound(view) => &view.uri,
+            ViewUnion::ViewBlocked(view) => &view.uri,
+        }
+    }
+}
+
+/// The quoted post or reply
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.embed.record#viewRecord")]
+#[serde(rename_all = "camelCase")]
+pub struct ViewRecord {
+    pub uri: String,
+    pub cid: String,
+    pub author: AuthorView,
+    /// AT URI of the thread the quote is from, the post itself or the root
+    /// of the reply
+    pub thread: String,
+    /// Set when a post is quoted, replies have none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub text: String,
+    /// Set when `text` is only the start of the quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<bool>,
+    pub created_at: String,
+    pub indexed_at: String,
+}
+
+/// The quote was deleted, taken down or never indexed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.embed.record#viewNotFound")]
+#[serde(rename_all = "camelCase")]
+pub struct ViewNotFound {
+    pub uri: String,
+    pub not_found: bool,
+}
+
+/// The reader blocks or mutes the quote's author, or is blocked by them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.embed.record#viewBlocked")]
+#[serde(rename_all = "camelCase")]
+pub struct ViewBlocked {
+    pub uri: String,
+    pub blocked: bool,
+    pub author: AuthorView,
+}
@@ -0,0 +1,61 @@
+use crate::app::bbs::AuthorView;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Blocks an account on the BBS. Neither side sees the other's posts and
+/// replies, and the blocker isn't notified about the blocked account.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.graph.block")]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    /// Client-declared timestamp when this block was created.
+    pub created_at: DateTime<Utc>,
+    /// DID of the blocked account
+    pub subject: String,
+}
+
+/// Mutes an account on the BBS. Only the muter stops seeing the muted
+/// account's posts and replies, and being notified about them.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.graph.mute")]
+#[serde(rename_all = "camelCase")]
+pub struct Mute {
+    /// Client-declared timestamp when this mute was created.
+    pub created_at: DateTime<Utc>,
+    /// DID of the muted account
+    pub subject: String,
+}
+
+/// Follows an account on the BBS.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.graph.follow")]
+#[serde(rename_all = "camelCase")]
+pub struct Follow {
+    /// Client-declared timestamp when this follow was created.
+    pub created_at: DateTime<Utc>,
+    /// DID of the followed account
+    pub subject: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetFollowsOutput {
+    pub subject: AuthorView,
+    /// Accounts the subject follows, most recently followed first
+    pub follows: Vec<AuthorView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetFollowersOutput {
+    pub subject: AuthorView,
+    /// Accounts following the subject, most recent followers first
+    pub followers: Vec<AuthorView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
@@ -1,7 +1,33 @@
+pub mod actor;
+pub mod automod;
+pub mod dm;
+pub mod embed;
+pub mod graph;
+pub mod moderation;
+
+use crate::app::bbs::embed::{PostEmbed, View as EmbedView};
 use crate::app::bsky::feed::PostLabels;
-use crate::app::bsky::{embed::Embeds, feed::EntityRef};
 use crate::app::bsky::richtext::Facet;
+use crate::app::bsky::{
+    embed::{external::ViewExternal, Embeds, MediaUnion},
+    feed::EntityRef,
+};
+use crate::com::atproto::repo::Blob;
+use anyhow::bail;
 use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How much of a post's text listings like app.bbs.getSectionFeed return
+pub const PREVIEW_GRAPHEMES: usize = 300;
+
+/// `text` cut after `max_graphemes` graphemes, and whether anything was cut.
+pub fn truncate_graphemes(text: &str, max_graphemes: usize) -> (String, bool) {
+    match text.grapheme_indices(true).nth(max_graphemes) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text.to_string(), false),
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(tag = "$type")]
@@ -23,7 +49,7 @@ pub struct Post {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<PostLabels>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub embed: Option<Embeds>,
+    pub embed: Option<PostEmbed>,
     /// Additional hashtags, in addition to any included in post text and facets.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
@@ -33,6 +59,31 @@ pub struct Post {
     pub title: String,
 }
 
+impl Post {
+    /// The post's first image, embedded on its own or next to a quoted record.
+    pub fn first_image(&self) -> Option<ImagePreview> {
+        let images = match self.embed {
+            Some(PostEmbed::Bsky(Embeds::Images(ref images))) => images,
+            Some(PostEmbed::Bsky(Embeds::RecordWithMedia(ref embed))) => match embed.media {
+                MediaUnion::Images(ref images) => images,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let image = images.images.first()?;
+        let cid = match (&image.image.r#ref, &image.image.cid) {
+            (Some(cid), _) => cid.to_string(),
+            (None, Some(cid)) => cid.clone(),
+            (None, None) => return None,
+        };
+        Some(ImagePreview {
+            cid,
+            alt: image.alt.clone(),
+            mime_type: image.image.mime_type.clone(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(tag = "$type")]
 #[serde(rename = "app.bbs.reply")]
@@ -53,7 +104,7 @@ pub struct Reply {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<PostLabels>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub embed: Option<Embeds>,
+    pub embed: Option<PostEmbed>,
     /// Additional hashtags, in addition to any included in post text and facets.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tags: Option<Vec<String>>,
@@ -62,3 +113,394 @@ pub struct Reply {
     /// reply parent cid
     pub parent: String,
 }
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.vote")]
+#[serde(rename_all = "camelCase")]
+pub struct Vote {
+    /// Client-declared timestamp when this vote was cast.
+    pub created_at: DateTime<Utc>,
+    /// AT URI of the post being voted on
+    pub subject: String,
+    /// 1 for an up vote, -1 for a down vote
+    pub value: i8,
+}
+
+/// Emoji shortcodes app.bbs.reaction records may use, unless the PDS or the
+/// BBS AppView is configured with a list of its own
+pub const DEFAULT_REACTION_EMOJIS: [&str; 8] = [
+    "+1", "-1", "laugh", "hooray", "confused", "heart", "rocket", "eyes",
+];
+
+/// Reacts to a BBS post or reply with an emoji. Unlike votes, an account can
+/// react with several emojis to the same post.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.reaction")]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    /// Client-declared timestamp when this reaction was created.
+    pub created_at: DateTime<Utc>,
+    /// AT URI of the post or reply reacted to
+    pub subject: String,
+    /// Shortcode of the emoji, without colons, e.g. `heart`
+    pub emoji: String,
+}
+
+/// Subscribes the account to new replies in a BBS thread it hasn't posted in,
+/// or with `muted` set, stops replies in the thread from notifying it at all.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.watchThread")]
+#[serde(rename_all = "camelCase")]
+pub struct WatchThread {
+    /// Client-declared timestamp when the thread was watched.
+    pub created_at: DateTime<Utc>,
+    /// AT URI of the thread's app.bbs.post
+    pub subject: String,
+    /// Mutes the thread instead, including replies to the account's own posts in it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muted: Option<bool>,
+}
+
+/// A post or reply in a private section, encrypted with the section's symmetric
+/// key. The PDS stores and serves it without looking inside the ciphertext.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "$type")]
+#[serde(rename = "app.bbs.encryptedPost")]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedPost {
+    /// Client-declared timestamp when this post was originally created.
+    pub created_at: DateTime<Utc>,
+    /// Private bbs section the post belongs to
+    pub section_id: usize,
+    /// Root of the thread, set when the envelope wraps a reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// Post being replied to, set when the envelope wraps a reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Cipher the ciphertext was sealed with, e.g. `xchacha20-poly1305`
+    pub algorithm: String,
+    /// Version of the section key that encrypted the ciphertext
+    pub key_id: String,
+    /// Base64 nonce the ciphertext was sealed with
+    pub nonce: String,
+    /// Members the section key is wrapped for
+    pub recipients: Vec<KeyRecipient>,
+    /// The sealed app.bbs.post or app.bbs.reply record
+    pub ciphertext: Blob,
+}
+
+/// The section key wrapped for one member of a private section.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRecipient {
+    pub did: String,
+    /// Verification method of the member's DID doc the key was wrapped to,
+    /// e.g. `did:web5:alice#atproto`
+    pub key_ref: String,
+    /// Base64 section key, encrypted to `key_ref`
+    pub wrapped_key: String,
+}
+
+/// Whether a section takes new posts and replies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionStatus {
+    #[default]
+    Open,
+    /// Read-only: existing threads stay readable, nothing new is accepted
+    Archived,
+}
+
+impl SectionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SectionStatus::Open => "open",
+            SectionStatus::Archived => "archived",
+        }
+    }
+}
+
+impl FromStr for SectionStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "open" => Ok(SectionStatus::Open),
+            "archived" => Ok(SectionStatus::Archived),
+            _ => bail!("Unknown section status `{status}`"),
+        }
+    }
+}
+
+/// Per-section activity as reported by app.bbs.getStats
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionStats {
+    pub section_id: usize,
+    pub total_posts: i64,
+    pub total_replies: i64,
+    /// Posts created within the last 7 days
+    pub posts_7d: i64,
+    /// Posts created within the last 30 days
+    pub posts_30d: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_activity_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<SectionStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStatsOutput {
+    pub total_posts: i64,
+    pub total_replies: i64,
+    /// Distinct authors of posts or replies within the last 7 days
+    pub active_users_7d: i64,
+    /// Distinct authors of posts or replies within the last 30 days
+    pub active_users_30d: i64,
+    pub sections: Vec<SectionStats>,
+    /// When the aggregation job last refreshed these numbers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub computed_at: Option<String>,
+}
+
+/// The busiest window of votes on one subject, as reported by
+/// app.bbs.admin.getVoteBursts
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteBurst {
+    pub subject: String,
+    pub window_start: String,
+    pub window_end: String,
+    pub votes: i64,
+    pub voters: i64,
+    /// Sum of the values of the votes in the window
+    pub score: i64,
+    /// Votes in the window left out of the score, repeats by the same voter or
+    /// voters below the section's minimum account age
+    pub uncounted_votes: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetVoteBurstsOutput {
+    pub bursts: Vec<VoteBurst>,
+}
+
+/// A thread (an app.bbs.post and its aggregates) as indexed by the BBS AppView
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadView {
+    pub uri: String,
+    pub cid: String,
+    pub author: AuthorView,
+    pub section_id: i64,
+    pub title: String,
+    pub text: String,
+    pub reply_count: i64,
+    /// Sum of up (+1) and down (-1) votes
+    pub score: i64,
+    pub created_at: String,
+    pub last_activity_at: String,
+    pub indexed_at: String,
+    /// Set when `text` is only the start of the post, app.bbs.getThread
+    /// returns all of it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImagePreview>,
+    /// Card for the first link in the post, unfurled by the author's PDS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external: Option<ViewExternal>,
+    /// How many accounts reacted with each emoji, most used first. Only
+    /// app.bbs.getThread returns them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reactions: Option<Vec<ReactionCount>>,
+    /// The post or reply quoted with app.bbs.embed.record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embed: Option<EmbedView>,
+}
+
+/// The first image of a post, fetched with com.atproto.sync.getBlob
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagePreview {
+    pub cid: String,
+    pub alt: String,
+    pub mime_type: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplyView {
+    pub uri: String,
+    pub cid: String,
+    pub author: AuthorView,
+    pub parent: String,
+    pub text: String,
+    pub created_at: String,
+    pub indexed_at: String,
+    /// How many accounts reacted with each emoji, most used first. Only
+    /// app.bbs.getThread returns them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reactions: Option<Vec<ReactionCount>>,
+    /// The post or reply quoted with app.bbs.embed.record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embed: Option<EmbedView>,
+}
+
+/// How many accounts reacted to a post or reply with one emoji
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorView {
+    pub did: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetThreadOutput {
+    pub thread: ThreadView,
+    /// Replies oldest first, paginated with `cursor`
+    pub replies: Vec<ReplyView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetSectionFeedOutput {
+    pub threads: Vec<ThreadView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Whether the section takes new posts, so clients can hide the composer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<SectionStatus>,
+}
+
+/// Threads ranked by the trending job, hottest first
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTrendingOutput {
+    pub threads: Vec<ThreadView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPostsOutput {
+    pub threads: Vec<ThreadView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// One entry of an author feed, either a thread the author started or a reply
+/// they wrote
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorFeedItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread: Option<ThreadView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<ReplyView>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuthorFeedOutput {
+    /// Newest first
+    pub feed: Vec<AuthorFeedItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// An unfinished post or reply the PDS keeps for its author, outside their
+/// repo, so the composer can pick it up again on another device
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DraftView {
+    pub id: String,
+    /// The collection the draft will be published to, app.bbs.post or app.bbs.reply
+    pub collection: String,
+    /// The record as composed so far, not validated against its lexicon
+    pub value: serde_json::Value,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Creates a draft, or replaces the requester's draft `id`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveDraftInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub collection: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDraftsOutput {
+    /// Most recently saved first
+    pub drafts: Vec<DraftView>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteDraftInput {
+    pub id: String,
+}
+
+/// How far the requester has read a thread, kept by their PDS so unread
+/// counts agree across their devices
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadState {
+    /// Uri of the thread's root post
+    pub thread: String,
+    /// Replies the requester had seen, the rest of the thread's `replyCount`
+    /// is unread
+    pub read_reply_count: i64,
+    /// `createdAt` of the newest reply the requester had seen, later ones are new
+    pub last_read_at: String,
+    pub updated_at: String,
+}
+
+/// A read position reported by the client, see [`ReadState`]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadStateUpdate {
+    pub thread: String,
+    pub read_reply_count: i64,
+    pub last_read_at: DateTime<Utc>,
+}
+
+/// Read positions for several threads at once, so a client can report what
+/// was read over a session in one call
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateReadStateInput {
+    pub states: Vec<ReadStateUpdate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetReadStatesOutput {
+    /// Only the requested threads the requester has read
+    pub states: Vec<ReadState>,
+}
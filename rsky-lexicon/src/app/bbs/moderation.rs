@@ -0,0 +1,19 @@
+use crate::app::bbs::SectionStatus;
+use serde::{Deserialize, Serialize};
+
+/// Input for app.bbs.moderation.setSectionStatus
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSectionStatusInput {
+    pub section_id: usize,
+    pub status: SectionStatus,
+}
+
+/// Output for app.bbs.moderation.setSectionStatus
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSectionStatusOutput {
+    pub section_id: usize,
+    pub status: SectionStatus,
+    pub updated_at: String,
+}
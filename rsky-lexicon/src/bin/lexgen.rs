@@ -0,0 +1,48 @@
+//! Writes the com.atproto.web5 and app.bbs Lexicon documents as JSON, one
+//! file per document at `<out>/<nsid with dots as slashes>.json`.
+//!
+//!     cargo run -p rsky-lexicon --bin lexgen -- [out dir, default `lexicons`] [--check]
+//!
+//! With `--check` nothing is written; it fails if any file in the out dir is
+//! missing or differs, so CI can catch schemas that weren't regenerated.
+
+use rsky_lexicon::schema::lexicons;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::{env, fs};
+
+fn main() -> ExitCode {
+    let mut check = false;
+    let mut out = PathBuf::from("lexicons");
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--check" => check = true,
+            _ => out = PathBuf::from(arg),
+        }
+    }
+
+    let mut stale = 0;
+    for doc in lexicons() {
+        let path = out.join(format!("{}.json", doc.id.replace('.', "/")));
+        let json = serde_json::to_string_pretty(&doc).unwrap() + "\n";
+        if check {
+            if fs::read_to_string(&path).ok().as_deref() != Some(json.as_str()) {
+                eprintln!("{} is out of date", path.display());
+                stale += 1;
+            }
+            continue;
+        }
+        if let Err(error) =
+            fs::create_dir_all(path.parent().unwrap()).and_then(|()| fs::write(&path, json))
+        {
+            eprintln!("Failed to write {}: {error}", path.display());
+            return ExitCode::FAILURE;
+        }
+        println!("Wrote {}", path.display());
+    }
+    if stale > 0 {
+        eprintln!("{stale} lexicons are out of date, run lexgen to regenerate them");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
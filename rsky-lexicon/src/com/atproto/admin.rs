@@ -1,5 +1,7 @@
+use crate::com::atproto::label::Label;
 use crate::com::atproto::repo::StrongRef;
 use crate::com::atproto::server::InviteCode;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -9,6 +11,175 @@ pub struct DeleteAccountInput {
     pub did: String,
 }
 
+/// Drop blocks no longer reachable from a repo's current root.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CompactRepoInput {
+    pub did: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactRepoOutput {
+    pub blocks_kept: u64,
+    pub blocks_removed: u64,
+    pub reclaimed_bytes: u64,
+}
+
+/// Most recent sequence number emitted on the firehose.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct GetLatestSeqOutput {
+    pub seq: Option<i64>,
+}
+
+/// Who an admin request was authenticated as.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WhoamiOutput {
+    /// `service` for a service JWT, `password` for the admin password
+    pub auth_type: String,
+    /// The calling service's DID, unset for the admin password
+    pub did: Option<String>,
+}
+
+/// Web5 accounts whose DID doc cell on chain disagrees with the PDS.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckChainConsistencyOutput {
+    /// Pass back to check the next page of accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Accounts checked on this page
+    pub checked: usize,
+    pub inconsistencies: Vec<ChainInconsistency>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainInconsistency {
+    pub did: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    pub ckb_address: String,
+    /// `missingCell`, `chainUnavailable`, `handleMismatch` or `pdsMismatch`
+    pub problems: Vec<String>,
+}
+
+/// Re-emit already sequenced commit events onto the firehose, for a repo and/or a seq range.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayEventsInput {
+    pub did: Option<String>,
+    /// First seq to replay, inclusive
+    pub from_seq: Option<i64>,
+    /// Last seq to replay, inclusive
+    pub to_seq: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayEventsOutput {
+    pub replayed: usize,
+    /// Pass as `fromSeq` to continue when the limit was hit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<i64>,
+}
+
+/// Register an HTTPS endpoint to receive signed JSON payloads for matching repo events.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegisterWebhookInput {
+    pub url: String,
+    /// NSIDs or `app.bbs.*` style prefixes, all collections if empty
+    #[serde(default)]
+    pub collections: Vec<String>,
+    /// Repos to deliver events for, all repos if empty
+    #[serde(default)]
+    pub dids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RegisterWebhookOutput {
+    pub id: String,
+    /// HMAC key for verifying deliveries, only ever returned here
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookView {
+    pub id: String,
+    pub url: String,
+    pub collections: Vec<String>,
+    pub dids: Vec<String>,
+    /// Last seq handled for this webhook
+    pub cursor: i64,
+    /// Failed attempts at delivering the next event
+    pub attempts: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub dead_letters: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ListWebhooksOutput {
+    pub webhooks: Vec<WebhookView>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DeleteWebhookInput {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct QueryAuditLogOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    pub entries: Vec<AuditLogEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub action: String,
+    /// Who acted, unset for the admin password and the PDS itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_did: Option<String>,
+    pub subject_did: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_cid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Value>,
+    pub created_at: String,
+}
+
+/// Issue (or negate) labels on accounts and records, signed by this service.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CreateLabelsInput {
+    pub labels: Vec<CreateLabel>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CreateLabel {
+    /// AT URI of the record, or the DID of the account, being labeled.
+    pub uri: String,
+    pub cid: Option<String>,
+    pub val: String,
+    /// Set to negate a label previously applied to the subject.
+    pub neg: Option<bool>,
+    pub exp: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CreateLabelsOutput {
+    pub labels: Vec<Label>,
+}
+
 /// Disable an account from receiving new invite codes, but does not invalidate existing codes.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DisableAccountInvitesInput {
@@ -86,12 +257,17 @@ pub struct SubjectStatus {
     pub takedown: Option<StatusAttr>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub deactivated: Option<StatusAttr>,
+    /// Takedown of an account that is lifted on its own, only applies to repo subjects
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<SuspensionAttr>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct UpdateSubjectStatusOutput {
     pub subject: Subject,
     pub takedown: Option<StatusAttr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspended: Option<SuspensionAttr>,
 }
 
 // Defs
@@ -124,6 +300,20 @@ pub struct StatusAttr {
     pub r#ref: Option<String>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SuspensionAttr {
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<String>,
+    /// How long to suspend the account for, required to apply a suspension
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_in_hours: Option<u64>,
+    /// When the suspension is lifted, set in responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "$type")]
 pub enum Subject {
@@ -147,3 +337,60 @@ pub struct RepoBlobRef {
     #[serde(rename = "recordUri")]
     pub record_uri: Option<String>,
 }
+
+/// Settings operators can change without restarting the PDS
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeFlags {
+    pub invite_required: bool,
+    /// Rejects writes to app.bbs.* collections
+    pub bbs_writes_frozen: bool,
+    /// Fails directWrites when the DID doc can't be fetched from CKB, instead
+    /// of relying on the commit signature alone
+    pub chain_strict: bool,
+    pub rate_limits_enabled: bool,
+    /// `name:points:duration_ms` entries applied over the configured rate
+    /// limit policies
+    pub rate_limit_overrides: Vec<String>,
+}
+
+/// Flags left unset keep their current value
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRuntimeFlagsInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_required: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbs_writes_frozen: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_strict: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limits_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit_overrides: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateJwtKeyOutput {
+    /// `kid` of the new signing key
+    pub kid: String,
+    pub keys: Vec<JwtKeyView>,
+}
+
+/// A key access and refresh tokens are verified with
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JwtKeyView {
+    pub kid: String,
+    /// Whether new tokens are signed with it
+    pub current: bool,
+    /// When a rotated in key starts signing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retired_at: Option<String>,
+    /// When tokens signed with a retired key stop being accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifiable_until: Option<String>,
+}
@@ -219,6 +219,16 @@ pub enum ApplyWritesInputRefWrite {
     Delete(RefWriteDelete),
 }
 
+impl ApplyWritesInputRefWrite {
+    pub fn collection(&self) -> &str {
+        match self {
+            ApplyWritesInputRefWrite::Create(write) => &write.collection,
+            ApplyWritesInputRefWrite::Update(write) => &write.collection,
+            ApplyWritesInputRefWrite::Delete(write) => &write.collection,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ListMissingBlobsRefRecordBlob {
     pub cid: String,
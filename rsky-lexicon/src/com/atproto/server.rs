@@ -48,6 +48,10 @@ pub struct CreateAccountInput {
 pub struct CreateAppPasswordInput {
     /// A short name for the App Password, to help distinguish them.
     pub name: String,
+    /// Collections sessions created with the App Password may write to, as exact NSIDs
+    /// or `prefix.*` patterns such as `app.bbs.*`. Omitted for any collection.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
 }
 
 /// Create an authentication session.
@@ -137,6 +141,8 @@ pub struct CreateAppPasswordOutput {
     pub password: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -176,6 +182,15 @@ pub struct GetSessionOutput {
     pub email_confirmed: Option<bool>,
     #[serde(rename = "didDoc", skip_serializing_if = "Option::is_none")]
     pub did_doc: Option<String>,
+    /// CKB address the account's web5 DID is bound to
+    #[serde(rename = "ckbAddress", skip_serializing_if = "Option::is_none")]
+    pub ckb_address: Option<String>,
+    /// The `atproto` verification method currently published in the on-chain DID doc
+    #[serde(rename = "signingKey", skip_serializing_if = "Option::is_none")]
+    pub signing_key: Option<String>,
+    /// Collections this session may write to, omitted when it may write to any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
 }
 
 /// Describes the server's account creation requirements and capabilities. Implemented by PDS.
@@ -230,6 +245,18 @@ pub struct CheckAccountStatusOutput {
     pub imported_blobs: i64,
 }
 
+/// Storage used by the requesting account, counted against the server's per-account quota.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStorageUsageOutput {
+    pub blob_bytes: i64,
+    pub repo_bytes: i64,
+    pub total_bytes: i64,
+    /// Absent when the server doesn't enforce a quota
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<i64>,
+}
+
 /// List all App Passwords.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ListAppPasswordsOutput {
@@ -300,4 +327,6 @@ pub struct AppPassword {
     pub name: String,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
 }
@@ -79,6 +79,19 @@ pub struct ListReposOutput {
     pub repos: Vec<RefRepo>,
 }
 
+/// Enumerates all the DIDs which have records with the given collection NSID.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListReposByCollectionOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    pub repos: Vec<ListReposByCollectionRepo>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListReposByCollectionRepo {
+    pub did: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RepoStatus {
@@ -147,6 +160,14 @@ impl fmt::Display for AccountStatus {
     }
 }
 
+/// Informational message from the stream, e.g. that the consumer fell behind and events were
+/// skipped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscribeReposInfo {
+    pub name: String,
+    pub message: Option<String>,
+}
+
 /// DEPRECATED -- Use #account event instead
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SubscribeReposTombstone {
@@ -1,6 +1,9 @@
 use crate::com::atproto::repo::Blob;
+use anyhow::bail;
+use chrono::{DateTime, Utc};
 use serde_json::Value;
 use std::collections::BTreeMap;
+use std::str::FromStr;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -32,6 +35,12 @@ pub struct CreateAccountInput {
     pub root: SignedRoot,
     pub ckb_addr: String,
     pub invite_code: Option<String>,
+    /// hCaptcha or Turnstile response token, required when the server has a captcha configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captcha_token: Option<String>,
+    /// Address for account mail and notifications, the account has none if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -106,6 +115,16 @@ pub enum PreDirectWritesInputRefWrite {
     Delete(RefWriteDelete),
 }
 
+impl PreDirectWritesInputRefWrite {
+    pub fn collection(&self) -> &str {
+        match self {
+            PreDirectWritesInputRefWrite::Create(write) => &write.collection,
+            PreDirectWritesInputRefWrite::Update(write) => &write.collection,
+            PreDirectWritesInputRefWrite::Delete(write) => &write.collection,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(tag = "$type")]
 pub enum DirectWritesInputRefWrite {
@@ -117,11 +136,23 @@ pub enum DirectWritesInputRefWrite {
     Delete(RefWriteDelete),
 }
 
+impl DirectWritesInputRefWrite {
+    pub fn collection(&self) -> &str {
+        match self {
+            DirectWritesInputRefWrite::Create(write) => &write.collection,
+            DirectWritesInputRefWrite::Update(write) => &write.collection,
+            DirectWritesInputRefWrite::Delete(write) => &write.collection,
+        }
+    }
+}
+
 /// Operation which creates a new record.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct RefWriteCreate {
     pub collection: String,
     pub rkey: Option<String>,
+    /// The record, or `{"$bytes": "<base64>"}` holding its DAG-CBOR block
+    /// when validate is false, for records the PDS has no lexicon for
     pub value: Value,
 }
 
@@ -130,6 +161,8 @@ pub struct RefWriteCreate {
 pub struct RefWriteUpdate {
     pub collection: String,
     pub rkey: String,
+    /// The record, or `{"$bytes": "<base64>"}` holding its DAG-CBOR block
+    /// when validate is false, for records the PDS has no lexicon for
     pub value: Value,
 }
 
@@ -170,6 +203,8 @@ pub enum PreIndexActionInputRef {
     CreateSessionIndex(RefCreateSessionIndex),
     #[serde(rename = "com.atproto.web5.preIndexAction#deleteAccount")]
     DeleteAccountIndex(RefDeleteAccountIndex),
+    #[serde(rename = "com.atproto.web5.preIndexAction#stepUp")]
+    StepUpIndex(RefStepUpIndex),
 }
 
 impl PreIndexActionInputRef {
@@ -182,6 +217,9 @@ impl PreIndexActionInputRef {
                 "Sign this message to authenticate with delete account on pds: web5.bbs.fans."
                     .to_string()
             }
+            PreIndexActionInputRef::StepUpIndex(RefStepUpIndex { lxm }) => {
+                format!("Sign this message to confirm {lxm} on pds: web5.bbs.fans.")
+            }
         }
     }
 }
@@ -192,6 +230,13 @@ pub struct RefCreateSessionIndex {}
 #[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
 pub struct RefDeleteAccountIndex {}
 
+/// Re-confirms a logged in session with the wallet before a sensitive operation.
+#[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
+pub struct RefStepUpIndex {
+    /// Lexicon (XRPC) method the step-up token will be bound to
+    pub lxm: String,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PreIndexActionOutput {
@@ -221,6 +266,8 @@ pub enum IndexActionInputRef {
     CreateSessionIndex(RefCreateSessionIndex),
     #[serde(rename = "com.atproto.web5.indexAction#deleteAccount")]
     DeleteAccountIndex(RefDeleteAccountIndex),
+    #[serde(rename = "com.atproto.web5.indexAction#stepUp")]
+    StepUpIndex(RefStepUpIndex),
 }
 
 impl IndexActionInputRef {
@@ -233,6 +280,9 @@ impl IndexActionInputRef {
                 "Sign this message to authenticate with delete account on pds: web5.bbs.fans."
                     .to_string()
             }
+            IndexActionInputRef::StepUpIndex(RefStepUpIndex { lxm }) => {
+                format!("Sign this message to confirm {lxm} on pds: web5.bbs.fans.")
+            }
         }
     }
 }
@@ -249,6 +299,8 @@ pub enum IndexActionOutputRefResult {
     CreateSessionResult(RefCreateSessionResult),
     #[serde(rename = "com.atproto.web5.indexAction#deleteAccountResult")]
     DeleteAccountResult(RefDeleteAccountResult),
+    #[serde(rename = "com.atproto.web5.indexAction#stepUpResult")]
+    StepUpResult(RefStepUpResult),
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
@@ -269,6 +321,17 @@ pub struct RefCreateSessionResult {
 #[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
 pub struct RefDeleteAccountResult {}
 
+#[derive(Debug, Deserialize, PartialEq, Serialize, Clone)]
+pub struct RefStepUpResult {
+    /// Short-lived token to send in the `Web5-Step-Up` header alongside the access token
+    #[serde(rename = "stepUpToken")]
+    pub step_up_token: String,
+    pub lxm: String,
+    /// Lifetime of the token in seconds
+    #[serde(rename = "expiresIn")]
+    pub expires_in: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DirectWritesOutput {
@@ -324,3 +387,232 @@ pub struct BlobOutput {
     pub blob_server: String,
     pub blob: Blob,
 }
+
+/// Starts a resumable blob upload.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadInput {
+    pub mime_type: String,
+    /// Total size of the blob in bytes.
+    pub size: i64,
+    /// CID the finished blob is expected to have, checked on finalize.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+/// Progress of a resumable blob upload. `offset` is where the next chunk must start.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadStatusOutput {
+    pub upload_id: String,
+    pub offset: i64,
+    pub size: i64,
+    pub expires_at: String,
+}
+
+/// Completes a resumable blob upload once every chunk has been received.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizeUploadInput {
+    pub upload_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+/// Which BBS activity is emailed to an account. Everything is on until the
+/// account opts out.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailNotificationPrefs {
+    /// Replies to the account's posts and replies
+    pub replies: bool,
+    /// Posts and replies that mention the account
+    pub mentions: bool,
+}
+
+/// Asks the PDS to hold the handle an on-chain DID doc claims for the chain
+/// key holder, before an account with it exists.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveHandleInput {
+    pub ckb_addr: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReserveHandleOutput {
+    pub handle: String,
+    pub expires_at: String,
+}
+
+/// Moves a web5 account to a new CKB address after the old wallet is lost. The
+/// message is signed both by the repo signing key and by the new address.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebindAddressInput {
+    pub did: String,
+    pub message: String,
+    pub signing_key: String,
+    /// Signature of the repo signing key over the message
+    pub signed_bytes: String,
+    /// The address to bind the account to
+    pub ckb_addr: String,
+    /// Signature of the new address over the message, as CKB wallets sign messages
+    pub ckb_signed_bytes: String,
+}
+
+impl RebindAddressInput {
+    pub fn statement(&self) -> String {
+        format!(
+            "Sign this message to rebind {} to a new address on pds: web5.bbs.fans.",
+            self.did
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebindAddressOutput {
+    pub did: String,
+    pub ckb_address: String,
+}
+
+/// Sets the password a web5 account may sign in with through
+/// `com.atproto.server.createSession` when its wallet isn't at hand.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetPasswordInput {
+    /// Unset keeps the current password
+    pub password: Option<String>,
+    /// Whether the password may be used to sign in
+    pub password_login: bool,
+}
+
+/// Earlier versions of a record, newest first, read back from the repo's
+/// commit history.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRecordHistoryOutput {
+    pub uri: String,
+    pub versions: Vec<RecordVersion>,
+    /// Set when there may be older versions past the ones returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// The record as one commit left it. Deletes have no `cid` or `value`, and
+/// neither does a version whose commit was too big to carry its blocks.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordVersion {
+    /// create, update or delete
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+    /// Rev of the commit that made this version
+    pub rev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    pub created_at: String,
+}
+
+/// Records the requesting account deleted through directWrites that the PDS
+/// still holds, most recently deleted first.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListTombstonesOutput {
+    pub tombstones: Vec<Tombstone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// A deleted record, as it was before the delete. Creating a record at the
+/// same uri undoes the delete.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub uri: String,
+    pub cid: String,
+    pub value: Value,
+    pub deleted_at: String,
+    /// When the PDS removes it for good
+    pub purge_at: String,
+}
+
+/// A directWrites batch, commit signature included, for the PDS to apply at
+/// `publishAt` instead of right away.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleDirectWritesInput {
+    #[serde(flatten)]
+    pub writes: DirectWritesInput,
+    pub publish_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledWriteStatus {
+    Pending,
+    Published,
+    /// The repo moved on from the commit the writes were signed over, or the
+    /// writes no longer validate
+    Failed,
+    Canceled,
+}
+
+impl ScheduledWriteStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduledWriteStatus::Pending => "pending",
+            ScheduledWriteStatus::Published => "published",
+            ScheduledWriteStatus::Failed => "failed",
+            ScheduledWriteStatus::Canceled => "canceled",
+        }
+    }
+}
+
+impl FromStr for ScheduledWriteStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(ScheduledWriteStatus::Pending),
+            "published" => Ok(ScheduledWriteStatus::Published),
+            "failed" => Ok(ScheduledWriteStatus::Failed),
+            "canceled" => Ok(ScheduledWriteStatus::Canceled),
+            _ => bail!("Unknown scheduled write status `{status}`"),
+        }
+    }
+}
+
+/// A batch of signed writes waiting for, or past, its publish time
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledWrite {
+    pub id: String,
+    pub publish_at: String,
+    pub status: ScheduledWriteStatus,
+    pub created_at: String,
+    /// The commit the writes went out in, once published
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitMeta>,
+    /// Why publishing failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The requesting account's scheduled writes, soonest first.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListScheduledWritesOutput {
+    pub scheduled: Vec<ScheduledWrite>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+/// Drops a pending scheduled write.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelScheduledWriteInput {
+    pub id: String,
+}
@@ -0,0 +1,141 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+macro_rules! error_codes {
+    ($($(#[$doc:meta])* $code:ident,)*) => {
+        /// The `error` name of an XRPC error body, so clients can match on what
+        /// went wrong instead of parsing messages. Names this crate doesn't
+        /// know, from a newer server or a proxied service, are kept as
+        /// [`ErrorCode::Other`].
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($(#[$doc])* $code,)*
+            Other(String),
+        }
+
+        impl ErrorCode {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(ErrorCode::$code => stringify!($code),)*
+                    ErrorCode::Other(name) => name,
+                }
+            }
+        }
+
+        impl From<&str> for ErrorCode {
+            fn from(name: &str) -> Self {
+                match name {
+                    $(stringify!($code) => ErrorCode::$code,)*
+                    _ => ErrorCode::Other(name.to_string()),
+                }
+            }
+        }
+    };
+}
+
+error_codes!(
+    InternalServerError,
+    /// A service the request was proxied to couldn't be reached
+    UpstreamFailure,
+    ServiceUnavailable,
+    InvalidRequest,
+    InvalidRecord,
+    RecordNotFound,
+    /// Anything else that couldn't be found, e.g. a BBS thread
+    NotFound,
+    BlobNotFound,
+    InvalidImage,
+    AuthRequiredError,
+    InvalidLogin,
+    InvalidToken,
+    ExpiredToken,
+    /// The token is valid but isn't scoped to what the request does, e.g. an app
+    /// password writing outside its collections
+    InsufficientScope,
+    AccountNotFound,
+    AccountTakendown,
+    RepoNotFound,
+    RepoTakendown,
+    RepoSuspended,
+    RepoDeactivated,
+    InvalidHandle,
+    HandleNotAvailable,
+    UnsupportedDomain,
+    InvalidEmail,
+    EmailNotAvailable,
+    InvalidPassword,
+    InvalidInviteCode,
+    UnresolvableDid,
+    IncompatibleDidDoc,
+    WellKnownNotFound,
+    InvalidSignedRoot,
+    ConcurrentWriteError,
+    WritesFrozen,
+    /// A BBS section's automoderation rules refused the post
+    RejectedByAutomod,
+    /// The BBS section is archived and takes no new posts or replies
+    SectionArchived,
+    BadExpiration,
+    QuotaExceeded,
+    /// The request body is over the route's size or nesting limit
+    PayloadTooLarge,
+    RateLimitExceeded,
+    CkbAddrNotFound,
+    CkbDidocCellNotFound,
+    CkbAddrNoCell,
+    InvalidCkbAddr,
+    InvalidS3Error,
+);
+
+impl ErrorCode {
+    /// The HTTP status a response carrying this error is sent with
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCode::InternalServerError => 500,
+            ErrorCode::UpstreamFailure => 502,
+            ErrorCode::ServiceUnavailable => 503,
+            ErrorCode::AuthRequiredError => 401,
+            ErrorCode::InsufficientScope => 403,
+            ErrorCode::RecordNotFound | ErrorCode::NotFound | ErrorCode::WellKnownNotFound => 404,
+            ErrorCode::QuotaExceeded | ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::RateLimitExceeded => 429,
+            _ => 400,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for ErrorCode {
+    fn from(name: String) -> Self {
+        ErrorCode::from(name.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(ErrorCode::from(String::deserialize(deserializer)?))
+    }
+}
+
+/// Body of an XRPC error response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct XrpcErrorBody {
+    pub error: ErrorCode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Where in the record a lexicon validation failed, for `InvalidRecord`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
@@ -8,3 +8,5 @@ pub mod app;
 pub mod blob_refs;
 pub mod chat;
 pub mod com;
+pub mod error;
+pub mod schema;
@@ -0,0 +1,1299 @@
+use crate::schema::{
+    array, blob, boolean, bounded, doc, formatted, integer, json_body, json_ref_body, object,
+    params, reference, string, union, unknown, LexBody, LexDef, LexField, LexObject, LexParams,
+    LexiconDoc,
+};
+
+const MAX_LIMIT: i64 = 100;
+/// Longest post or reply body, far above a screenful but short of a document
+const MAX_TEXT_GRAPHEMES: usize = 10_000;
+const MAX_TITLE_GRAPHEMES: usize = 128;
+/// Most members an encrypted post's section key can be wrapped for
+const MAX_RECIPIENTS: usize = 1000;
+/// Most keywords, patterns or domains one automoderation rule can list
+const MAX_RULE_TERMS: usize = 100;
+/// Longest direct message
+pub const MAX_MESSAGE_GRAPHEMES: usize = 1000;
+/// Most members besides the sender a direct message conversation can have
+pub const MAX_CONVO_MEMBERS: usize = 10;
+/// Most preferences an account can keep for the BBS client
+const MAX_PREFERENCES: usize = 100;
+/// Longest emoji shortcode a reaction can carry, allowlists are far shorter
+const MAX_SHORTCODE_LENGTH: usize = 64;
+
+/// The fields app.bbs.post and app.bbs.reply share with app.bsky.feed.post
+fn post_fields() -> Vec<(&'static str, LexField)> {
+    vec![
+        (
+            "createdAt*",
+            formatted("datetime")
+                .describe("Client-declared timestamp when this post was originally created."),
+        ),
+        (
+            "text*",
+            string()
+                .max_length(MAX_TEXT_GRAPHEMES * 10)
+                .max_graphemes(MAX_TEXT_GRAPHEMES)
+                .describe(
+                    "The primary post content. Might be an empty string, if there are embeds.",
+                ),
+        ),
+        (
+            "entities?",
+            array(reference("app.bsky.feed.post#entity"))
+                .describe("DEPRECATED: replaced by app.bsky.richtext.facet."),
+        ),
+        (
+            "facets",
+            array(reference("app.bsky.richtext.facet"))
+                .describe("Annotations of text (mentions, URLs, hashtags, .etc)"),
+        ),
+        (
+            "langs",
+            array(formatted("language"))
+                .max_length(3)
+                .describe("Indicates human language of post primary text content."),
+        ),
+        ("labels", union(&["com.atproto.label.defs#selfLabels"])),
+        (
+            "embed",
+            union(&[
+                "app.bsky.embed.images",
+                "app.bsky.embed.video",
+                "app.bsky.embed.external",
+                "app.bsky.embed.record",
+                "app.bsky.embed.recordWithMedia",
+                "app.bbs.embed.record",
+            ]),
+        ),
+        (
+            "tags",
+            array(string().max_length(640).max_graphemes(64))
+                .max_length(8)
+                .describe(
+                    "Additional hashtags, in addition to any included in post text and facets.",
+                ),
+        ),
+    ]
+}
+
+fn record(description: &str, record: LexObject) -> LexDef {
+    LexDef::Record {
+        description: Some(description.to_string()),
+        key: "tid".to_string(),
+        record,
+    }
+}
+
+fn query(description: &str, parameters: Option<LexParams>, output: LexObject) -> LexDef {
+    LexDef::Query {
+        description: Some(description.to_string()),
+        parameters,
+        output: Some(json_body(output)),
+    }
+}
+
+fn procedure(description: &str, input: LexObject, output: Option<LexBody>) -> LexDef {
+    LexDef::Procedure {
+        description: Some(description.to_string()),
+        parameters: None,
+        input: Some(json_body(input)),
+        output,
+    }
+}
+
+/// The collections a draft can be published to
+fn draft_collection() -> LexField {
+    LexField::String {
+        description: None,
+        format: Some("nsid".to_string()),
+        known_values: Some(vec![
+            "app.bbs.post".to_string(),
+            "app.bbs.reply".to_string(),
+        ]),
+        max_length: None,
+        max_graphemes: None,
+    }
+}
+
+/// A BBS client's preferences, each an object whose `$type` is under app.bbs
+/// and whose other fields only the client knows about
+fn preferences() -> LexField {
+    array(unknown()).max_length(MAX_PREFERENCES)
+}
+
+fn limit() -> LexField {
+    bounded(1, Some(MAX_LIMIT))
+}
+
+fn section_status() -> LexField {
+    LexField::String {
+        description: Some(
+            "Archived sections are read-only, the PDS refuses new posts and replies in them"
+                .to_string(),
+        ),
+        format: None,
+        known_values: Some(vec!["open".to_string(), "archived".to_string()]),
+        max_length: Some(64),
+        max_graphemes: None,
+    }
+}
+
+fn threads_page() -> LexObject {
+    object(vec![
+        ("threads*", array(reference("app.bbs.defs#threadView"))),
+        ("cursor", string()),
+    ])
+}
+
+pub(crate) fn lexicons() -> Vec<LexiconDoc> {
+    let mut post = post_fields();
+    post.push(("sectionId*", bounded(0, None).describe("Chose bbs section")));
+    post.push((
+        "title*",
+        string()
+            .max_length(MAX_TITLE_GRAPHEMES * 10)
+            .max_graphemes(MAX_TITLE_GRAPHEMES)
+            .describe("BBS Post title"),
+    ));
+    let mut reply = post_fields();
+    reply.push(("root*", formatted("at-uri").describe("reply root cid")));
+    reply.push(("parent*", formatted("at-uri").describe("reply parent cid")));
+
+    vec![
+        doc(
+            "app.bbs.post",
+            "A thread opened in a BBS section.",
+            vec![("main", record("Record containing a BBS post.", object(post)))],
+        ),
+        doc(
+            "app.bbs.reply",
+            "A reply in a BBS thread.",
+            vec![(
+                "main",
+                record("Record containing a reply to a BBS post.", object(reply)),
+            )],
+        ),
+        doc(
+            "app.bbs.vote",
+            "An up or down vote on a BBS post.",
+            vec![(
+                "main",
+                record(
+                    "Record containing a vote.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this vote was cast."),
+                        ),
+                        (
+                            "subject*",
+                            formatted("at-uri").describe("AT URI of the post being voted on"),
+                        ),
+                        (
+                            "value*",
+                            LexField::Integer {
+                                description: Some(
+                                    "1 for an up vote, -1 for a down vote".to_string(),
+                                ),
+                                minimum: None,
+                                maximum: None,
+                                one_of: Some(vec![-1, 1]),
+                            },
+                        ),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.reaction",
+            "An emoji reaction to a BBS post or reply.",
+            vec![(
+                "main",
+                record(
+                    "Record containing a reaction.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this reaction was created."),
+                        ),
+                        (
+                            "subject*",
+                            formatted("at-uri").describe("AT URI of the post or reply reacted to"),
+                        ),
+                        (
+                            "emoji*",
+                            string().max_length(MAX_SHORTCODE_LENGTH).describe(
+                                "Shortcode of the emoji, without colons, e.g. heart. Servers only accept the ones on their allowlist.",
+                            ),
+                        ),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.watchThread",
+            "Notifies the account of new replies in a BBS thread, or keeps them from notifying it.",
+            vec![(
+                "main",
+                record(
+                    "Record watching or muting a thread.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when the thread was watched."),
+                        ),
+                        (
+                            "subject*",
+                            formatted("at-uri").describe("AT URI of the thread's app.bbs.post"),
+                        ),
+                        (
+                            "muted",
+                            boolean().describe(
+                                "Mutes the thread instead, including replies to the account's own posts in it",
+                            ),
+                        ),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.graph.block",
+            "Hides an account's BBS posts and replies from the blocker, and the blocker's from them.",
+            vec![(
+                "main",
+                record(
+                    "Record declaring a block.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this block was created."),
+                        ),
+                        ("subject*", formatted("did").describe("DID of the blocked account")),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.graph.mute",
+            "Hides an account's BBS posts and replies from the muter only.",
+            vec![(
+                "main",
+                record(
+                    "Record declaring a mute.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this mute was created."),
+                        ),
+                        ("subject*", formatted("did").describe("DID of the muted account")),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.graph.follow",
+            "Follows an account on the BBS.",
+            vec![(
+                "main",
+                record(
+                    "Record declaring a follow.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this follow was created."),
+                        ),
+                        ("subject*", formatted("did").describe("DID of the followed account")),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.encryptedPost",
+            "A post or reply in a private section, readable only by members holding the section key.",
+            vec![
+                (
+                    "main",
+                    record(
+                        "Envelope around an encrypted app.bbs.post or app.bbs.reply. The PDS validates the envelope but never the ciphertext.",
+                        object(vec![
+                            (
+                                "createdAt*",
+                                formatted("datetime").describe(
+                                    "Client-declared timestamp when this post was originally created.",
+                                ),
+                            ),
+                            (
+                                "sectionId*",
+                                bounded(0, None)
+                                    .describe("Private bbs section the post belongs to"),
+                            ),
+                            (
+                                "root",
+                                formatted("at-uri").describe(
+                                    "Root of the thread, set when the envelope wraps a reply",
+                                ),
+                            ),
+                            (
+                                "parent",
+                                formatted("at-uri").describe(
+                                    "Post being replied to, set when the envelope wraps a reply",
+                                ),
+                            ),
+                            (
+                                "algorithm*",
+                                LexField::String {
+                                    description: Some(
+                                        "Cipher the ciphertext was sealed with".to_string(),
+                                    ),
+                                    format: None,
+                                    known_values: Some(vec!["xchacha20-poly1305".to_string()]),
+                                    max_length: Some(64),
+                                    max_graphemes: None,
+                                },
+                            ),
+                            (
+                                "keyId*",
+                                string().max_length(128).describe(
+                                    "Version of the section key that encrypted the ciphertext",
+                                ),
+                            ),
+                            (
+                                "nonce*",
+                                string()
+                                    .max_length(64)
+                                    .describe("Base64 nonce the ciphertext was sealed with"),
+                            ),
+                            (
+                                "recipients*",
+                                array(reference("#keyRecipient"))
+                                    .max_length(MAX_RECIPIENTS)
+                                    .describe("Members the section key is wrapped for"),
+                            ),
+                            (
+                                "ciphertext*",
+                                blob().describe(
+                                    "The sealed app.bbs.post or app.bbs.reply record",
+                                ),
+                            ),
+                        ]),
+                    ),
+                ),
+                (
+                    "keyRecipient",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The section key wrapped for one member of a private section."
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("did*", formatted("did")),
+                            (
+                                "keyRef*",
+                                string().max_length(512).describe(
+                                    "Verification method of the member's DID doc the key was wrapped to",
+                                ),
+                            ),
+                            (
+                                "wrappedKey*",
+                                string()
+                                    .max_length(1024)
+                                    .describe("Base64 section key, encrypted to keyRef"),
+                            ),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.automod.rule",
+            "An automoderation rule for a BBS section, enforced by the PDS when published by one of the section's moderators.",
+            vec![(
+                "main",
+                record(
+                    "Record containing a rule. A post or reply trips the rule when any one of its conditions holds.",
+                    object(vec![
+                        (
+                            "createdAt*",
+                            formatted("datetime")
+                                .describe("Client-declared timestamp when this rule was created."),
+                        ),
+                        (
+                            "sectionId*",
+                            bounded(0, None).describe("Section the rule applies to"),
+                        ),
+                        (
+                            "action*",
+                            LexField::String {
+                                description: Some(
+                                    "What happens to a post that trips the rule".to_string(),
+                                ),
+                                format: None,
+                                known_values: Some(vec![
+                                    "reject".to_string(),
+                                    "hold".to_string(),
+                                    "label".to_string(),
+                                ]),
+                                max_length: Some(64),
+                                max_graphemes: None,
+                            },
+                        ),
+                        (
+                            "label",
+                            string()
+                                .max_length(128)
+                                .describe("Label applied by the label action"),
+                        ),
+                        (
+                            "reason",
+                            string().max_length(3000).max_graphemes(300).describe(
+                                "Shown to the author of a rejected post and to moderators reviewing a held one",
+                            ),
+                        ),
+                        (
+                            "keywords",
+                            array(string().max_length(640).max_graphemes(64))
+                                .max_length(MAX_RULE_TERMS)
+                                .describe("Words or phrases, matched case-insensitively against title and text"),
+                        ),
+                        (
+                            "patterns",
+                            array(string().max_length(1000))
+                                .max_length(MAX_RULE_TERMS)
+                                .describe("Regular expressions matched against title and text"),
+                        ),
+                        (
+                            "minAccountAgeHours",
+                            bounded(0, None).describe("Trips for accounts younger than this"),
+                        ),
+                        (
+                            "maxLinks",
+                            bounded(0, None).describe("Trips for posts with more links than this"),
+                        ),
+                        (
+                            "allowedDomains",
+                            array(string().max_length(253))
+                                .max_length(MAX_RULE_TERMS)
+                                .describe("Trips for posts linking anywhere else, subdomains included"),
+                        ),
+                        (
+                            "maxPostsPerHour",
+                            bounded(1, None).describe(
+                                "Trips once the author has made this many BBS posts and replies within the past hour",
+                            ),
+                        ),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.embed.record",
+            "Quotes another BBS post or reply, in this thread or any other.",
+            vec![
+                (
+                    "main",
+                    LexDef::Object(object(vec![(
+                        "record*",
+                        reference("com.atproto.repo.strongRef")
+                            .describe("The app.bbs.post or app.bbs.reply quoted"),
+                    )])),
+                ),
+                (
+                    "view",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "A quote as hydrated by the BBS AppView, then by the reader's PDS"
+                                .to_string(),
+                        ),
+                        ..object(vec![(
+                            "record*",
+                            union(&["#viewRecord", "#viewNotFound", "#viewBlocked"]),
+                        )])
+                    }),
+                ),
+                (
+                    "viewRecord",
+                    LexDef::Object(LexObject {
+                        description: Some("The quoted post or reply".to_string()),
+                        ..object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("cid*", formatted("cid")),
+                            ("author*", reference("app.bbs.defs#authorView")),
+                            (
+                                "thread*",
+                                formatted("at-uri").describe(
+                                    "AT URI of the thread the quote is from, the post itself or the root of the reply",
+                                ),
+                            ),
+                            (
+                                "title",
+                                string().describe("Set when a post is quoted, replies have none"),
+                            ),
+                            ("text*", string()),
+                            (
+                                "truncated",
+                                boolean().describe("Set when text is only the start of the quote"),
+                            ),
+                            ("createdAt*", formatted("datetime")),
+                            ("indexedAt*", formatted("datetime")),
+                        ])
+                    }),
+                ),
+                (
+                    "viewNotFound",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The quote was deleted, taken down or never indexed".to_string(),
+                        ),
+                        ..object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("notFound*", boolean()),
+                        ])
+                    }),
+                ),
+                (
+                    "viewBlocked",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The reader blocks or mutes the quote's author, or is blocked by them"
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("blocked*", boolean()),
+                            ("author*", reference("app.bbs.defs#authorView")),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.defs",
+            "Views of BBS records as indexed by the BBS AppView.",
+            vec![
+                (
+                    "threadView",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "A thread (an app.bbs.post and its aggregates) as indexed by the BBS AppView"
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("cid*", formatted("cid")),
+                            ("author*", reference("#authorView")),
+                            ("sectionId*", integer()),
+                            ("title*", string()),
+                            ("text*", string()),
+                            ("replyCount*", integer()),
+                            (
+                                "score*",
+                                integer().describe("Sum of up (+1) and down (-1) votes"),
+                            ),
+                            ("createdAt*", formatted("datetime")),
+                            ("lastActivityAt*", formatted("datetime")),
+                            ("indexedAt*", formatted("datetime")),
+                            (
+                                "truncated",
+                                boolean().describe(
+                                    "Set when text is only the start of the post, app.bbs.getThread returns all of it",
+                                ),
+                            ),
+                            ("image", reference("#imagePreview")),
+                            (
+                                "external",
+                                reference("app.bsky.embed.external#viewExternal").describe(
+                                    "Card for the first link in the post, unfurled by the author's PDS",
+                                ),
+                            ),
+                            (
+                                "reactions",
+                                array(reference("#reactionCount")).describe(
+                                    "How many accounts reacted with each emoji, most used first. Only app.bbs.getThread returns them.",
+                                ),
+                            ),
+                            (
+                                "embed",
+                                reference("app.bbs.embed.record#view").describe(
+                                    "The post or reply quoted with app.bbs.embed.record",
+                                ),
+                            ),
+                        ])
+                    }),
+                ),
+                (
+                    "imagePreview",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The first image of a post, fetched with com.atproto.sync.getBlob"
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("cid*", formatted("cid")),
+                            ("alt*", string()),
+                            ("mimeType*", string()),
+                        ])
+                    }),
+                ),
+                (
+                    "replyView",
+                    LexDef::Object(object(vec![
+                        ("uri*", formatted("at-uri")),
+                        ("cid*", formatted("cid")),
+                        ("author*", reference("#authorView")),
+                        ("parent*", formatted("at-uri")),
+                        ("text*", string()),
+                        ("createdAt*", formatted("datetime")),
+                        ("indexedAt*", formatted("datetime")),
+                        (
+                            "reactions",
+                            array(reference("#reactionCount")).describe(
+                                "How many accounts reacted with each emoji, most used first. Only app.bbs.getThread returns them.",
+                            ),
+                        ),
+                        (
+                            "embed",
+                            reference("app.bbs.embed.record#view").describe(
+                                "The post or reply quoted with app.bbs.embed.record",
+                            ),
+                        ),
+                    ])),
+                ),
+                (
+                    "reactionCount",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "How many accounts reacted to a post or reply with one emoji"
+                                .to_string(),
+                        ),
+                        ..object(vec![("emoji*", string()), ("count*", integer())])
+                    }),
+                ),
+                (
+                    "authorView",
+                    LexDef::Object(object(vec![
+                        ("did*", formatted("did")),
+                        ("handle", formatted("handle")),
+                    ])),
+                ),
+                (
+                    "authorFeedItem",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "A thread the author started or a reply they wrote, one of the two is set"
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("thread", reference("#threadView")),
+                            ("reply", reference("#replyView")),
+                        ])
+                    }),
+                ),
+                (
+                    "sectionStats",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "Per-section activity as reported by app.bbs.getStats".to_string(),
+                        ),
+                        ..object(vec![
+                            ("sectionId*", integer()),
+                            ("totalPosts*", integer()),
+                            ("totalReplies*", integer()),
+                            (
+                                "posts7d*",
+                                integer().describe("Posts created within the last 7 days"),
+                            ),
+                            (
+                                "posts30d*",
+                                integer().describe("Posts created within the last 30 days"),
+                            ),
+                            ("lastActivityAt", formatted("datetime")),
+                            ("status", section_status()),
+                        ])
+                    }),
+                ),
+                (
+                    "voteBurst",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The busiest window of votes on one subject, as reported by app.bbs.admin.getVoteBursts"
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("subject*", formatted("at-uri")),
+                            ("windowStart*", formatted("datetime")),
+                            ("windowEnd*", formatted("datetime")),
+                            ("votes*", integer()),
+                            ("voters*", integer()),
+                            (
+                                "score*",
+                                integer().describe("Sum of the values of the votes in the window"),
+                            ),
+                            (
+                                "uncountedVotes*",
+                                integer().describe(
+                                    "Votes in the window left out of the score, repeats by the same voter or voters below the section's minimum account age",
+                                ),
+                            ),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.dm.defs",
+            "Direct messages between accounts on the same PDS, kept out of their repos.",
+            vec![
+                (
+                    "messageView",
+                    LexDef::Object(object(vec![
+                        ("id*", string()),
+                        ("convoId*", string()),
+                        (
+                            "sender*",
+                            formatted("did").describe("DID of the member who sent the message"),
+                        ),
+                        ("text*", string()),
+                        ("sentAt*", formatted("datetime")),
+                    ])),
+                ),
+                (
+                    "convoView",
+                    LexDef::Object(object(vec![
+                        ("id*", string()),
+                        (
+                            "members*",
+                            array(formatted("did"))
+                                .describe("DIDs of every member, the requester included"),
+                        ),
+                        ("lastMessage", reference("#messageView")),
+                        (
+                            "unreadCount*",
+                            integer().describe(
+                                "Messages from other members newer than the requester's read marker",
+                            ),
+                        ),
+                    ])),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.dm.sendMessage",
+            "Sends a direct message into a conversation, starting it if needed.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth. Give either convoId, for a conversation the sender is in, or members.",
+                    object(vec![
+                        ("convoId", string()),
+                        (
+                            "members",
+                            array(formatted("did"))
+                                .max_length(MAX_CONVO_MEMBERS)
+                                .describe("DIDs of the other members, accounts on the sender's PDS"),
+                        ),
+                        (
+                            "text*",
+                            string()
+                                .max_length(MAX_MESSAGE_GRAPHEMES * 10)
+                                .max_graphemes(MAX_MESSAGE_GRAPHEMES),
+                        ),
+                    ]),
+                    Some(json_ref_body("app.bbs.dm.defs#messageView")),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.dm.listConvos",
+            "The requester's conversations, most recently active first.",
+            vec![(
+                "main",
+                query(
+                    "Requires auth.",
+                    Some(params(vec![("limit", limit()), ("cursor", string())])),
+                    object(vec![
+                        ("convos*", array(reference("app.bbs.dm.defs#convoView"))),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.dm.listMessages",
+            "Messages in one of the requester's conversations.",
+            vec![(
+                "main",
+                query(
+                    "Requires auth, as a member of the conversation.",
+                    Some(params(vec![
+                        ("convoId*", string()),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        (
+                            "messages*",
+                            array(reference("app.bbs.dm.defs#messageView"))
+                                .describe("Newest first"),
+                        ),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.dm.markRead",
+            "Moves the requester's read marker in a conversation forward.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth, as a member of the conversation.",
+                    object(vec![
+                        ("convoId*", string()),
+                        (
+                            "messageId",
+                            string().describe(
+                                "Newest message read, the conversation's latest when left out",
+                            ),
+                        ),
+                    ]),
+                    None,
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.getStats",
+            "Post, reply and active user counts for the whole BBS and per section.",
+            vec![(
+                "main",
+                query(
+                    "Does not require auth.",
+                    None,
+                    object(vec![
+                        ("totalPosts*", integer()),
+                        ("totalReplies*", integer()),
+                        (
+                            "activeUsers7d*",
+                            integer().describe(
+                                "Distinct authors of posts or replies within the last 7 days",
+                            ),
+                        ),
+                        (
+                            "activeUsers30d*",
+                            integer().describe(
+                                "Distinct authors of posts or replies within the last 30 days",
+                            ),
+                        ),
+                        (
+                            "sections*",
+                            array(reference("app.bbs.defs#sectionStats")),
+                        ),
+                        (
+                            "computedAt",
+                            formatted("datetime")
+                                .describe("When the aggregation job last refreshed these numbers"),
+                        ),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.getThread",
+            "A thread and its replies.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("uri*", formatted("at-uri")),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        ("thread*", reference("app.bbs.defs#threadView")),
+                        (
+                            "replies*",
+                            array(reference("app.bbs.defs#replyView"))
+                                .describe("Replies oldest first, paginated with `cursor`"),
+                        ),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.getSectionFeed",
+            "Threads in a section.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("section*", integer()),
+                        (
+                            "sort",
+                            LexField::String {
+                                description: Some("Defaults to latest".to_string()),
+                                format: None,
+                                known_values: Some(vec![
+                                    "latest".to_string(),
+                                    "active".to_string(),
+                                    "top".to_string(),
+                                ]),
+                                max_length: None,
+                                max_graphemes: None,
+                            },
+                        ),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        ("threads*", array(reference("app.bbs.defs#threadView"))),
+                        ("cursor", string()),
+                        ("status", section_status()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.getLinkThumb",
+            "The thumbnail of a link card, a JPEG made from the linked page's og:image.",
+            vec![(
+                "main",
+                LexDef::Query {
+                    description: Some(
+                        "Link cards on threads point their thumb here. Does not require auth."
+                            .to_string(),
+                    ),
+                    parameters: Some(params(vec![("cid*", formatted("cid"))])),
+                    output: Some(LexBody {
+                        encoding: "image/jpeg".to_string(),
+                        description: None,
+                        schema: None,
+                    }),
+                },
+            )],
+        ),
+        doc(
+            "app.bbs.getTrending",
+            "Threads started within the last 7 days ranked by votes and replies, decayed by age.",
+            vec![(
+                "main",
+                query(
+                    "Ranked periodically from this PDS's records. Does not require auth.",
+                    Some(params(vec![
+                        ("section", integer()),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    threads_page(),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.searchPosts",
+            "Full text search over thread titles and bodies, optionally within one section.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("q*", string()),
+                        ("section", integer()),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    threads_page(),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.getAuthorFeed",
+            "Threads and replies written by one account.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("actor*", formatted("did")),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        (
+                            "feed*",
+                            array(reference("app.bbs.defs#authorFeedItem")).describe("Newest first"),
+                        ),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.saveDraft",
+            "Saves an unfinished post or reply for the requester to pick up on any device.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Requires auth. Drafts are kept by the PDS, never written to the repo or signed. Give id to replace one of the requester's drafts.",
+                        object(vec![
+                            ("id", string()),
+                            ("collection*", draft_collection()),
+                            ("value*", unknown()),
+                        ]),
+                        Some(json_ref_body("#draftView")),
+                    ),
+                ),
+                (
+                    "draftView",
+                    LexDef::Object(object(vec![
+                        ("id*", string()),
+                        ("collection*", draft_collection()),
+                        (
+                            "value*",
+                            unknown().describe(
+                                "The record as composed so far, not validated against its lexicon",
+                            ),
+                        ),
+                        ("createdAt*", formatted("datetime")),
+                        ("updatedAt*", formatted("datetime")),
+                    ])),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.listDrafts",
+            "The requester's drafts, most recently saved first.",
+            vec![(
+                "main",
+                query(
+                    "Requires auth.",
+                    Some(params(vec![("limit", limit()), ("cursor", string())])),
+                    object(vec![
+                        ("drafts*", array(reference("app.bbs.saveDraft#draftView"))),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.deleteDraft",
+            "Deletes one of the requester's drafts, e.g. once it's published.",
+            vec![(
+                "main",
+                procedure("Requires auth.", object(vec![("id*", string())]), None),
+            )],
+        ),
+        doc(
+            "app.bbs.updateReadState",
+            "Records how far the requester has read some threads, so their other devices show the same unread counts.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Requires auth. Read positions are kept by the PDS, never written to the repo. One that's behind the position already kept is ignored, as is reading a thread again on a device that fell behind.",
+                        object(vec![(
+                            "states*",
+                            array(reference("#readStateUpdate")).max_length(MAX_LIMIT as usize),
+                        )]),
+                        None,
+                    ),
+                ),
+                (
+                    "readStateUpdate",
+                    LexDef::Object(object(vec![
+                        ("thread*", formatted("at-uri").describe("Uri of the thread's root post")),
+                        (
+                            "readReplyCount*",
+                            bounded(0, None).describe("Replies the requester has seen"),
+                        ),
+                        (
+                            "lastReadAt*",
+                            formatted("datetime")
+                                .describe("createdAt of the newest reply the requester has seen"),
+                        ),
+                    ])),
+                ),
+                (
+                    "readState",
+                    LexDef::Object(object(vec![
+                        ("thread*", formatted("at-uri")),
+                        (
+                            "readReplyCount*",
+                            integer().describe(
+                                "The rest of the thread's replyCount is unread",
+                            ),
+                        ),
+                        (
+                            "lastReadAt*",
+                            formatted("datetime").describe("Replies created later are new"),
+                        ),
+                        ("updatedAt*", formatted("datetime")),
+                    ])),
+                ),
+            ],
+        ),
+        doc(
+            "app.bbs.getReadStates",
+            "How far the requester has read some threads.",
+            vec![(
+                "main",
+                query(
+                    "Requires auth. Threads the requester hasn't read, or read too long ago to still be kept, are left out.",
+                    Some(params(vec![(
+                        "threads*",
+                        array(formatted("at-uri")).max_length(MAX_LIMIT as usize),
+                    )])),
+                    object(vec![(
+                        "states*",
+                        array(reference("app.bbs.updateReadState#readState")),
+                    )]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.actor.defs",
+            "BBS client preferences the PDS itself acts on.",
+            vec![(
+                "crosspostPref",
+                LexDef::Object(LexObject {
+                    description: Some(
+                        "Mirrors the account's new BBS posts to app.bsky.feed.post records, each cut short with a link back to its thread. Only PDSes running the bridge honor it."
+                            .to_string(),
+                    ),
+                    ..object(vec![("enabled*", boolean())])
+                }),
+            )],
+        ),
+        doc(
+            "app.bbs.actor.getPreferences",
+            "The requester's BBS client preferences, e.g. theme, feed ordering or muted sections.",
+            vec![(
+                "main",
+                query(
+                    "Requires auth.",
+                    None,
+                    object(vec![("preferences*", preferences())]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.actor.putPreferences",
+            "Replaces the requester's BBS client preferences.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth. Preferences are kept by the PDS, never written to the repo. Any the requester had before and left out are dropped.",
+                    object(vec![("preferences*", preferences())]),
+                    None,
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.admin.getVoteBursts",
+            "Subjects that received an unusual number of votes within a short window, busiest first.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Requires the AppView's admin token.",
+                    Some(params(vec![
+                        (
+                            "since",
+                            formatted("datetime").describe("Defaults to 24 hours ago"),
+                        ),
+                        (
+                            "window",
+                            bounded(1, Some(24 * 60))
+                                .describe("Window length in minutes, defaults to 10"),
+                        ),
+                        (
+                            "threshold",
+                            bounded(2, None).describe(
+                                "Fewest votes within one window to report, defaults to 20",
+                            ),
+                        ),
+                        ("limit", limit()),
+                    ])),
+                    object(vec![(
+                        "bursts*",
+                        array(reference("app.bbs.defs#voteBurst")),
+                    )]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.moderation.setSectionStatus",
+            "Opens or archives a BBS section. Appviews hear about the change from the PDS's event stream.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth, as one of the section's moderators.",
+                    object(vec![
+                        ("sectionId*", bounded(0, None)),
+                        ("status*", section_status()),
+                    ]),
+                    Some(json_body(object(vec![
+                        ("sectionId*", integer()),
+                        ("status*", section_status()),
+                        ("updatedAt*", formatted("datetime")),
+                    ]))),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.graph.getFollows",
+            "Accounts an account follows on the BBS.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("actor*", formatted("did")),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        ("subject*", reference("app.bbs.defs#authorView")),
+                        (
+                            "follows*",
+                            array(reference("app.bbs.defs#authorView"))
+                                .describe("Most recently followed first"),
+                        ),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+        doc(
+            "app.bbs.graph.getFollowers",
+            "Accounts following an account on the BBS.",
+            vec![(
+                "main",
+                query(
+                    "Served by the BBS AppView. Does not require auth.",
+                    Some(params(vec![
+                        ("actor*", formatted("did")),
+                        ("limit", limit()),
+                        ("cursor", string()),
+                    ])),
+                    object(vec![
+                        ("subject*", reference("app.bbs.defs#authorView")),
+                        (
+                            "followers*",
+                            array(reference("app.bbs.defs#authorView"))
+                                .describe("Most recent followers first"),
+                        ),
+                        ("cursor", string()),
+                    ]),
+                ),
+            )],
+        ),
+    ]
+}
@@ -0,0 +1,706 @@
+//! Lexicon documents for the methods and records this crate adds on top of
+//! atproto: `com.atproto.web5.*` and `app.bbs.*`. They're written next to the
+//! Rust types they describe and checked against them in `tests/schema.rs`, so
+//! the JSON `lexgen` emits for other clients matches what the PDS accepts.
+
+mod bbs;
+mod web5;
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A Lexicon schema file, as described at <https://atproto.com/specs/lexicon>.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LexiconDoc {
+    pub lexicon: u8,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub defs: BTreeMap<String, LexDef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LexDef {
+    Record {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        /// How record keys are formed, e.g. `tid`
+        key: String,
+        record: LexObject,
+    },
+    Query {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parameters: Option<LexParams>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<LexBody>,
+    },
+    Procedure {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parameters: Option<LexParams>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        input: Option<LexBody>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<LexBody>,
+    },
+    Object(LexObject),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct LexObject {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    /// Properties that may be sent as `null` rather than left out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nullable: Vec<String>,
+    pub properties: BTreeMap<String, LexField>,
+}
+
+/// Query string parameters of a query or procedure.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename = "params")]
+pub struct LexParams {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    pub properties: BTreeMap<String, LexField>,
+}
+
+/// Request or response body of a method.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LexBody {
+    pub encoding: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<LexField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LexField {
+    Boolean {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    Integer {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        minimum: Option<i64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        maximum: Option<i64>,
+        #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+        one_of: Option<Vec<i64>>,
+    },
+    String {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        format: Option<String>,
+        #[serde(rename = "knownValues", skip_serializing_if = "Option::is_none")]
+        known_values: Option<Vec<String>>,
+        /// In UTF-8 bytes
+        #[serde(rename = "maxLength", skip_serializing_if = "Option::is_none")]
+        max_length: Option<usize>,
+        #[serde(rename = "maxGraphemes", skip_serializing_if = "Option::is_none")]
+        max_graphemes: Option<usize>,
+    },
+    Blob {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    Unknown {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    Ref {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(rename = "ref")]
+        target: String,
+    },
+    Union {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        refs: Vec<String>,
+    },
+    Array {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        items: Box<LexField>,
+        #[serde(rename = "maxLength", skip_serializing_if = "Option::is_none")]
+        max_length: Option<usize>,
+    },
+    Object(LexObject),
+}
+
+impl LexField {
+    /// Sets the description of any kind of field.
+    pub fn describe(mut self, text: &str) -> Self {
+        let text = Some(text.to_string());
+        match &mut self {
+            LexField::Boolean { description }
+            | LexField::Integer { description, .. }
+            | LexField::String { description, .. }
+            | LexField::Blob { description }
+            | LexField::Unknown { description }
+            | LexField::Ref { description, .. }
+            | LexField::Union { description, .. }
+            | LexField::Array { description, .. } => *description = text,
+            LexField::Object(object) => object.description = text,
+        }
+        self
+    }
+
+    /// Caps a string's length in UTF-8 bytes, or an array's in items.
+    pub fn max_length(mut self, max: usize) -> Self {
+        match &mut self {
+            LexField::String { max_length, .. } | LexField::Array { max_length, .. } => {
+                *max_length = Some(max)
+            }
+            _ => panic!("maxLength only applies to strings and arrays"),
+        }
+        self
+    }
+
+    pub fn max_graphemes(mut self, max: usize) -> Self {
+        match &mut self {
+            LexField::String { max_graphemes, .. } => *max_graphemes = Some(max),
+            _ => panic!("maxGraphemes only applies to strings"),
+        }
+        self
+    }
+}
+
+pub(crate) fn boolean() -> LexField {
+    LexField::Boolean { description: None }
+}
+
+pub(crate) fn integer() -> LexField {
+    LexField::Integer {
+        description: None,
+        minimum: None,
+        maximum: None,
+        one_of: None,
+    }
+}
+
+pub(crate) fn bounded(minimum: i64, maximum: Option<i64>) -> LexField {
+    LexField::Integer {
+        description: None,
+        minimum: Some(minimum),
+        maximum,
+        one_of: None,
+    }
+}
+
+pub(crate) fn string() -> LexField {
+    LexField::String {
+        description: None,
+        format: None,
+        known_values: None,
+        max_length: None,
+        max_graphemes: None,
+    }
+}
+
+/// A string in one of the Lexicon string formats, e.g. `did` or `datetime`
+pub(crate) fn formatted(format: &str) -> LexField {
+    LexField::String {
+        description: None,
+        format: Some(format.to_string()),
+        known_values: None,
+        max_length: None,
+        max_graphemes: None,
+    }
+}
+
+pub(crate) fn blob() -> LexField {
+    LexField::Blob { description: None }
+}
+
+pub(crate) fn unknown() -> LexField {
+    LexField::Unknown { description: None }
+}
+
+pub(crate) fn reference(target: &str) -> LexField {
+    LexField::Ref {
+        description: None,
+        target: target.to_string(),
+    }
+}
+
+pub(crate) fn union(refs: &[&str]) -> LexField {
+    LexField::Union {
+        description: None,
+        refs: refs.iter().map(|target| target.to_string()).collect(),
+    }
+}
+
+pub(crate) fn array(items: LexField) -> LexField {
+    LexField::Array {
+        description: None,
+        items: Box::new(items),
+        max_length: None,
+    }
+}
+
+/// Builds an object from `(name, field)` pairs. Names ending in `*` are
+/// required and names ending in `?` are nullable; the marker isn't part of the
+/// property name.
+pub(crate) fn object(properties: Vec<(&str, LexField)>) -> LexObject {
+    let mut object = LexObject::default();
+    for (name, field) in properties {
+        let name = if let Some(name) = name.strip_suffix('*') {
+            object.required.push(name.to_string());
+            name
+        } else if let Some(name) = name.strip_suffix('?') {
+            object.nullable.push(name.to_string());
+            name
+        } else {
+            name
+        };
+        object.properties.insert(name.to_string(), field);
+    }
+    object
+}
+
+pub(crate) fn params(properties: Vec<(&str, LexField)>) -> LexParams {
+    let LexObject {
+        required,
+        properties,
+        ..
+    } = object(properties);
+    LexParams {
+        required,
+        properties,
+    }
+}
+
+pub(crate) fn json_body(schema: LexObject) -> LexBody {
+    LexBody {
+        encoding: "application/json".to_string(),
+        description: None,
+        schema: Some(LexField::Object(schema)),
+    }
+}
+
+pub(crate) fn json_ref_body(target: &str) -> LexBody {
+    LexBody {
+        encoding: "application/json".to_string(),
+        description: None,
+        schema: Some(reference(target)),
+    }
+}
+
+pub(crate) fn doc(id: &str, description: &str, defs: Vec<(&str, LexDef)>) -> LexiconDoc {
+    LexiconDoc {
+        lexicon: 1,
+        id: id.to_string(),
+        description: Some(description.to_string()),
+        defs: defs
+            .into_iter()
+            .map(|(name, def)| (name.to_string(), def))
+            .collect(),
+    }
+}
+
+/// Every Lexicon document this crate defines, ordered by id.
+pub fn lexicons() -> Vec<LexiconDoc> {
+    let mut docs = web5::lexicons();
+    docs.extend(bbs::lexicons());
+    docs.sort_by(|a, b| a.id.cmp(&b.id));
+    docs
+}
+
+/// Resolves a ref as written in a document with id `base` to `nsid#name`.
+fn absolute_ref(base: &str, target: &str) -> String {
+    match target.split_once('#') {
+        Some(("", name)) => format!("{base}#{name}"),
+        Some(_) => target.to_string(),
+        None => format!("{target}#main"),
+    }
+}
+
+/// The `$type` a value of the def at `nsid#name` carries, which leaves off `#main`.
+fn type_name(absolute: &str) -> &str {
+    absolute.strip_suffix("#main").unwrap_or(absolute)
+}
+
+/// Why a value doesn't match its schema, and where: `path` is the JSON path of
+/// the offending field, such as `writes[3].value.title`, empty for the value
+/// itself.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.message),
+            path => write!(f, "{path}: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl ValidationError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        ValidationError {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Places the error under `prefix`, for a value that was validated on its
+    /// own but sits inside a larger request.
+    pub fn within(mut self, prefix: &str) -> Self {
+        self.path = if self.path.is_empty() {
+            prefix.to_string()
+        } else if self.path.starts_with('[') {
+            format!("{prefix}{}", self.path)
+        } else {
+            format!("{prefix}.{}", self.path)
+        };
+        self
+    }
+}
+
+fn property_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+/// Checks the string formats that are cheap to check and easy to get wrong.
+fn check_format(format: &str, value: &str) -> Result<(), String> {
+    let valid = match format {
+        "datetime" => chrono::DateTime::parse_from_rfc3339(value).is_ok(),
+        "did" => value.starts_with("did:") && value.len() > 4,
+        "at-uri" => value.starts_with("at://") && value.len() > 5,
+        _ => true,
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(format!("isn't a valid {format}")),
+    }
+}
+
+/// Checks JSON values against a set of Lexicon documents: required and
+/// nullable properties, the JSON type, length and format of each field and
+/// union `$type`s. Refs to documents outside the set, such as `app.bsky.*`,
+/// aren't followed.
+pub struct Validator {
+    docs: BTreeMap<String, LexiconDoc>,
+    deny_unknown_properties: bool,
+}
+
+impl Validator {
+    pub fn new(docs: Vec<LexiconDoc>) -> Self {
+        Validator {
+            docs: docs.into_iter().map(|doc| (doc.id.clone(), doc)).collect(),
+            deny_unknown_properties: false,
+        }
+    }
+
+    /// Also rejects object properties the schema doesn't declare. Lexicon
+    /// lets newer clients add fields, so only use this to catch schemas that
+    /// have fallen behind their Rust types.
+    pub fn deny_unknown_properties(mut self) -> Self {
+        self.deny_unknown_properties = true;
+        self
+    }
+
+    fn def(&self, absolute: &str) -> Option<(&str, &LexDef)> {
+        let (nsid, name) = absolute.split_once('#')?;
+        let doc = self.docs.get(nsid)?;
+        Some((doc.id.as_str(), doc.defs.get(name)?))
+    }
+
+    /// Whether `nsid` is a record type in the set.
+    pub fn has_record(&self, nsid: &str) -> bool {
+        matches!(
+            self.def(&format!("{nsid}#main")),
+            Some((_, LexDef::Record { .. }))
+        )
+    }
+
+    /// Every ref in the documents that points into the set and doesn't resolve.
+    pub fn dangling_refs(&self) -> Vec<String> {
+        fn walk(field: &LexField, base: &str, refs: &mut Vec<String>) {
+            match field {
+                LexField::Ref { target, .. } => refs.push(absolute_ref(base, target)),
+                LexField::Union { refs: targets, .. } => {
+                    refs.extend(targets.iter().map(|target| absolute_ref(base, target)))
+                }
+                LexField::Array { items, .. } => walk(items, base, refs),
+                LexField::Object(object) => {
+                    for field in object.properties.values() {
+                        walk(field, base, refs)
+                    }
+                }
+                _ => (),
+            }
+        }
+        let mut refs = Vec::new();
+        for doc in self.docs.values() {
+            for def in doc.defs.values() {
+                let mut fields: Vec<LexField> = Vec::new();
+                match def {
+                    LexDef::Record { record, .. } => fields.push(LexField::Object(record.clone())),
+                    LexDef::Object(object) => fields.push(LexField::Object(object.clone())),
+                    LexDef::Query {
+                        parameters, output, ..
+                    } => {
+                        fields.extend(
+                            parameters
+                                .iter()
+                                .flat_map(|p| p.properties.values().cloned()),
+                        );
+                        fields.extend(output.iter().flat_map(|body| body.schema.clone()));
+                    }
+                    LexDef::Procedure {
+                        parameters,
+                        input,
+                        output,
+                        ..
+                    } => {
+                        fields.extend(
+                            parameters
+                                .iter()
+                                .flat_map(|p| p.properties.values().cloned()),
+                        );
+                        fields.extend(input.iter().flat_map(|body| body.schema.clone()));
+                        fields.extend(output.iter().flat_map(|body| body.schema.clone()));
+                    }
+                }
+                for field in &fields {
+                    walk(field, &doc.id, &mut refs);
+                }
+            }
+        }
+        refs.into_iter()
+            .filter(|target| {
+                let nsid = target.split('#').next().unwrap_or_default();
+                self.docs.contains_key(nsid) && self.def(target).is_none()
+            })
+            .collect()
+    }
+
+    /// Validates a record against `nsid`'s record def.
+    pub fn validate_record(&self, nsid: &str, value: &Value) -> Result<(), ValidationError> {
+        match self.def(&format!("{nsid}#main")) {
+            Some((base, LexDef::Record { record, .. })) => {
+                if value.get("$type").and_then(Value::as_str) != Some(nsid) {
+                    return Err(ValidationError::new("$type", format!("isn't {nsid}")));
+                }
+                self.validate_object(base, record, value, "")
+            }
+            _ => Err(ValidationError::new("", format!("{nsid} isn't a record"))),
+        }
+    }
+
+    /// Validates a procedure's input body against `nsid`'s schema.
+    pub fn validate_input(&self, nsid: &str, value: &Value) -> Result<(), ValidationError> {
+        match self.def(&format!("{nsid}#main")) {
+            Some((base, LexDef::Procedure { input, .. })) => {
+                self.validate_body(base, input.as_ref(), value)
+            }
+            _ => Err(ValidationError::new(
+                "",
+                format!("{nsid} isn't a procedure"),
+            )),
+        }
+    }
+
+    /// Validates a query's or procedure's output body against `nsid`'s schema.
+    pub fn validate_output(&self, nsid: &str, value: &Value) -> Result<(), ValidationError> {
+        match self.def(&format!("{nsid}#main")) {
+            Some((base, LexDef::Query { output, .. } | LexDef::Procedure { output, .. })) => {
+                self.validate_body(base, output.as_ref(), value)
+            }
+            _ => Err(ValidationError::new(
+                "",
+                format!("{nsid} isn't a query or procedure"),
+            )),
+        }
+    }
+
+    fn validate_body(
+        &self,
+        base: &str,
+        body: Option<&LexBody>,
+        value: &Value,
+    ) -> Result<(), ValidationError> {
+        match body.and_then(|body| body.schema.as_ref()) {
+            Some(schema) => self.validate_field(base, schema, value, ""),
+            None => Err(ValidationError::new("", "method has no JSON body")),
+        }
+    }
+
+    fn validate_object(
+        &self,
+        base: &str,
+        object: &LexObject,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), ValidationError> {
+        let Some(map) = value.as_object() else {
+            return Err(ValidationError::new(path, "expected an object"));
+        };
+        for name in &object.required {
+            if map.get(name).map_or(true, Value::is_null) {
+                return Err(ValidationError::new(&property_path(path, name), "required"));
+            }
+        }
+        for (name, property) in map {
+            let path = property_path(path, name);
+            if name == "$type" {
+                continue;
+            }
+            let Some(field) = object.properties.get(name) else {
+                if self.deny_unknown_properties {
+                    return Err(ValidationError::new(&path, "not in the schema"));
+                }
+                continue;
+            };
+            if property.is_null() {
+                if object.nullable.contains(name) {
+                    continue;
+                }
+                return Err(ValidationError::new(&path, "not nullable"));
+            }
+            self.validate_field(base, field, property, &path)?;
+        }
+        Ok(())
+    }
+
+    fn validate_field(
+        &self,
+        base: &str,
+        field: &LexField,
+        value: &Value,
+        path: &str,
+    ) -> Result<(), ValidationError> {
+        let result = match field {
+            LexField::Boolean { .. } => match value.is_boolean() {
+                true => Ok(()),
+                false => Err("expected a boolean".to_string()),
+            },
+            LexField::Integer {
+                minimum,
+                maximum,
+                one_of,
+                ..
+            } => match value.as_i64() {
+                None => Err("expected an integer".to_string()),
+                Some(int) if minimum.map_or(false, |min| int < min) => {
+                    Err(format!("must be at least {}", minimum.unwrap_or_default()))
+                }
+                Some(int) if maximum.map_or(false, |max| int > max) => {
+                    Err(format!("must be at most {}", maximum.unwrap_or_default()))
+                }
+                Some(int) => match one_of {
+                    Some(values) if !values.contains(&int) => Err(format!(
+                        "must be one of {}",
+                        values
+                            .iter()
+                            .map(i64::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                    _ => Ok(()),
+                },
+            },
+            LexField::String {
+                format,
+                max_length,
+                max_graphemes,
+                ..
+            } => match value.as_str() {
+                None => Err("expected a string".to_string()),
+                Some(string) if max_length.map_or(false, |max| string.len() > max) => {
+                    Err(format!("exceeds {} bytes", max_length.unwrap_or_default()))
+                }
+                Some(string)
+                    if max_graphemes.map_or(false, |max| string.graphemes(true).count() > max) =>
+                {
+                    Err(format!(
+                        "exceeds {} graphemes",
+                        max_graphemes.unwrap_or_default()
+                    ))
+                }
+                Some(string) => match format {
+                    Some(format) => check_format(format, string),
+                    None => Ok(()),
+                },
+            },
+            LexField::Blob { .. } => match value.is_object() {
+                true => Ok(()),
+                false => Err("expected a blob".to_string()),
+            },
+            LexField::Unknown { .. } => Ok(()),
+            LexField::Ref { target, .. } => {
+                let target = absolute_ref(base, target);
+                return match self.def(&target) {
+                    Some((base, LexDef::Object(object))) => {
+                        self.validate_object(base, object, value, path)
+                    }
+                    Some(_) => Err(ValidationError::new(
+                        path,
+                        format!("{target} isn't an object"),
+                    )),
+                    None => Ok(()),
+                };
+            }
+            LexField::Union { refs, .. } => {
+                let Some(value_type) = value.get("$type").and_then(Value::as_str) else {
+                    return Err(ValidationError::new(
+                        &property_path(path, "$type"),
+                        "required in a union",
+                    ));
+                };
+                let Some(target) = refs
+                    .iter()
+                    .map(|target| absolute_ref(base, target))
+                    .find(|target| type_name(target) == value_type)
+                else {
+                    return Err(ValidationError::new(
+                        &property_path(path, "$type"),
+                        format!("{value_type} isn't one of the union's types"),
+                    ));
+                };
+                return self.validate_field(base, &reference(&target), value, path);
+            }
+            LexField::Array {
+                items, max_length, ..
+            } => {
+                let Some(values) = value.as_array() else {
+                    return Err(ValidationError::new(path, "expected an array"));
+                };
+                if let Some(max) = max_length.filter(|max| values.len() > *max) {
+                    return Err(ValidationError::new(path, format!("exceeds {max} items")));
+                }
+                for (index, item) in values.iter().enumerate() {
+                    self.validate_field(base, items, item, &format!("{path}[{index}]"))?;
+                }
+                return Ok(());
+            }
+            LexField::Object(object) => return self.validate_object(base, object, value, path),
+        };
+        result.map_err(|message| ValidationError::new(path, message))
+    }
+}
@@ -0,0 +1,766 @@
+use crate::schema::{
+    array, blob, boolean, bounded, doc, formatted, integer, json_body, json_ref_body, object,
+    params, reference, string, union, unknown, LexBody, LexDef, LexField, LexObject, LexiconDoc,
+};
+
+fn procedure(description: &str, input: Option<LexBody>, output: Option<LexBody>) -> LexDef {
+    LexDef::Procedure {
+        description: Some(description.to_string()),
+        parameters: None,
+        input,
+        output,
+    }
+}
+
+fn query(description: &str, output: LexBody) -> LexDef {
+    LexDef::Query {
+        description: Some(description.to_string()),
+        parameters: None,
+        output: Some(output),
+    }
+}
+
+/// The repo commit a wallet is asked to sign, see `PreCreateAccountOutput`
+fn unsigned_root() -> LexObject {
+    object(vec![
+        ("did*", formatted("did")),
+        ("rev*", formatted("tid")),
+        ("data*", formatted("cid")),
+        ("prev", formatted("cid")),
+        ("version*", integer()),
+        (
+            "unSignBytes*",
+            string().describe("Hex of the DAG-CBOR commit to sign, without its signature"),
+        ),
+    ])
+}
+
+fn record_value() -> LexField {
+    unknown().describe(
+        "The record, or {\"$bytes\": \"<base64>\"} holding its DAG-CBOR block when validate is false, for records the PDS has no lexicon for",
+    )
+}
+
+fn create_write() -> LexDef {
+    LexDef::Object(LexObject {
+        description: Some("Operation which creates a new record.".to_string()),
+        ..object(vec![
+            ("collection*", formatted("nsid")),
+            ("rkey?", formatted("record-key")),
+            ("value*", record_value()),
+        ])
+    })
+}
+
+fn update_write() -> LexDef {
+    LexDef::Object(LexObject {
+        description: Some("Operation which updates an existing record.".to_string()),
+        ..object(vec![
+            ("collection*", formatted("nsid")),
+            ("rkey*", formatted("record-key")),
+            ("value*", record_value()),
+        ])
+    })
+}
+
+fn delete_write() -> LexDef {
+    LexDef::Object(LexObject {
+        description: Some("Operation which deletes an existing record.".to_string()),
+        ..object(vec![
+            ("collection*", formatted("nsid")),
+            ("rkey*", formatted("record-key")),
+        ])
+    })
+}
+
+fn write_result() -> LexDef {
+    LexDef::Object(object(vec![
+        ("uri*", formatted("at-uri")),
+        ("cid*", formatted("cid")),
+        ("validationStatus", string()),
+    ]))
+}
+
+/// directWrites' input, shared with scheduleDirectWrites which refers to the
+/// write defs by their full nsid
+fn direct_writes_input(write_refs: &[&str]) -> Vec<(&'static str, LexField)> {
+    vec![
+        (
+            "repo*",
+            formatted("at-identifier")
+                .describe("The handle or DID of the repo (aka, current account)."),
+        ),
+        (
+            "validate?",
+            boolean().describe(
+                "Can be set to 'false' to skip Lexicon schema validation of record data, for all operations.",
+            ),
+        ),
+        ("writes*", array(union(write_refs))),
+        (
+            "swapCommit",
+            formatted("cid").describe("Compare and swap with the previous commit by CID."),
+        ),
+        ("signingKey*", formatted("did")),
+        ("ckbAddr?", string()),
+        (
+            "root*",
+            reference("com.atproto.web5.createAccount#signedRoot"),
+        ),
+    ]
+}
+
+fn step_up() -> LexDef {
+    LexDef::Object(LexObject {
+        description: Some(
+            "Re-confirms a logged in session with the wallet before a sensitive operation."
+                .to_string(),
+        ),
+        ..object(vec![(
+            "lxm*",
+            formatted("nsid").describe("Lexicon (XRPC) method the step-up token will be bound to"),
+        )])
+    })
+}
+
+fn upload_status() -> LexBody {
+    json_body(LexObject {
+        description: Some(
+            "Progress of a resumable blob upload. `offset` is where the next chunk must start."
+                .to_string(),
+        ),
+        ..object(vec![
+            ("uploadId*", string()),
+            ("offset*", integer()),
+            ("size*", integer()),
+            ("expiresAt*", formatted("datetime")),
+        ])
+    })
+}
+
+fn scheduled_write() -> LexDef {
+    LexDef::Object(object(vec![
+        ("id*", string()),
+        ("publishAt*", formatted("datetime")),
+        (
+            "status*",
+            LexField::String {
+                description: None,
+                format: None,
+                known_values: Some(vec![
+                    "pending".to_string(),
+                    "published".to_string(),
+                    "failed".to_string(),
+                    "canceled".to_string(),
+                ]),
+                max_length: Some(64),
+                max_graphemes: None,
+            },
+        ),
+        ("createdAt*", formatted("datetime")),
+        ("commit?", reference("com.atproto.repo.defs#commitMeta")),
+        (
+            "error?",
+            string().describe("Why the writes couldn't be applied"),
+        ),
+    ]))
+}
+
+fn email_notification_prefs() -> LexObject {
+    object(vec![
+        (
+            "replies*",
+            boolean().describe("Replies to the account's posts and replies"),
+        ),
+        (
+            "mentions*",
+            boolean().describe("Posts and replies that mention the account"),
+        ),
+    ])
+}
+
+pub(crate) fn lexicons() -> Vec<LexiconDoc> {
+    vec![
+        doc(
+            "com.atproto.web5.preCreateAccount",
+            "Returns the first commit of a new web5 account's repo for the wallet to sign.",
+            vec![(
+                "main",
+                procedure(
+                    "Step one of createAccount. Does not require auth.",
+                    Some(json_body(object(vec![
+                        ("handle*", formatted("handle")),
+                        ("did*", formatted("did")),
+                        ("signingKey?", formatted("did")),
+                        ("inviteCode?", string()),
+                    ]))),
+                    Some(json_body(unsigned_root())),
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.createAccount",
+            "Creates a web5 account from the commit returned by preCreateAccount, signed by the wallet.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Does not require auth.",
+                        Some(json_body(object(vec![
+                            ("handle*", formatted("handle")),
+                            ("signingKey*", formatted("did")),
+                            ("password?", string()),
+                            ("root*", reference("#signedRoot")),
+                            ("ckbAddr*", string()),
+                            ("inviteCode?", string()),
+                            (
+                                "captchaToken",
+                                string().describe(
+                                    "hCaptcha or Turnstile response token, required when the server has a captcha configured",
+                                ),
+                            ),
+                            (
+                                "email",
+                                string().describe(
+                                    "Address for account mail and notifications, the account has none if unset",
+                                ),
+                            ),
+                        ]))),
+                        Some(json_body(object(vec![
+                            ("handle*", formatted("handle")),
+                            ("did*", formatted("did")),
+                            ("didDoc", unknown()),
+                            ("accessJwt*", string()),
+                            ("refreshJwt*", string()),
+                        ]))),
+                    ),
+                ),
+                (
+                    "signedRoot",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "An unsigned root from a pre* method with the wallet's signature."
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("did*", formatted("did")),
+                            ("rev*", formatted("tid")),
+                            ("data*", formatted("cid")),
+                            ("prev", formatted("cid")),
+                            ("version*", integer()),
+                            (
+                                "signedBytes*",
+                                string().describe("Hex of the compact secp256k1 signature"),
+                            ),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.preDirectWrites",
+            "Returns the commit a batch of writes would make, for the wallet to sign.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Pre apply a batch transaction of repository creates, updates, and deletes. Requires auth, implemented by PDS.",
+                        Some(json_body(object(vec![
+                            (
+                                "repo*",
+                                formatted("at-identifier").describe(
+                                    "The handle or DID of the repo (aka, current account).",
+                                ),
+                            ),
+                            (
+                                "validate?",
+                                boolean().describe(
+                                    "Can be set to 'false' to skip Lexicon schema validation of record data, for all operations.",
+                                ),
+                            ),
+                            (
+                                "writes*",
+                                array(union(&["#create", "#update", "#delete"])),
+                            ),
+                            (
+                                "swapCommit",
+                                formatted("cid")
+                                    .describe("Compare and swap with the previous commit by CID."),
+                            ),
+                        ]))),
+                        Some(json_body(unsigned_root())),
+                    ),
+                ),
+                ("create", create_write()),
+                ("update", update_write()),
+                ("delete", delete_write()),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.directWrites",
+            "Applies a batch of writes with the commit from preDirectWrites, signed by the wallet.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Direct apply a batch transaction of repository creates, updates, and deletes. Requires auth, implemented by PDS.",
+                        Some(json_body(object(direct_writes_input(&[
+                            "#create", "#update", "#delete",
+                        ])))),
+                        Some(json_body(object(vec![
+                            ("commit?", reference("com.atproto.repo.defs#commitMeta")),
+                            (
+                                "results?",
+                                array(union(&[
+                                    "#createResult",
+                                    "#updateResult",
+                                    "#deleteResult",
+                                ])),
+                            ),
+                        ]))),
+                    ),
+                ),
+                ("create", create_write()),
+                ("update", update_write()),
+                ("delete", delete_write()),
+                ("createResult", write_result()),
+                ("updateResult", write_result()),
+                ("deleteResult", LexDef::Object(object(vec![]))),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.preIndexAction",
+            "Returns the message the wallet signs to log in, delete the account or step up a session.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Does not require auth.",
+                        Some(json_body(object(vec![
+                            ("did*", formatted("did")),
+                            ("ckbAddr?", string()),
+                            (
+                                "index*",
+                                union(&["#createSession", "#deleteAccount", "#stepUp"]),
+                            ),
+                        ]))),
+                        Some(json_body(object(vec![
+                            ("did*", formatted("did")),
+                            ("handle*", formatted("handle")),
+                            ("message*", string()),
+                        ]))),
+                    ),
+                ),
+                ("createSession", LexDef::Object(object(vec![]))),
+                ("deleteAccount", LexDef::Object(object(vec![]))),
+                ("stepUp", step_up()),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.indexAction",
+            "Carries out the action preIndexAction returned a message for, given the wallet's signature over it.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Does not require auth.",
+                        Some(json_body(object(vec![
+                            ("did*", formatted("did")),
+                            ("message*", string()),
+                            ("signingKey*", formatted("did")),
+                            ("signedBytes*", string()),
+                            ("ckbAddr?", string()),
+                            (
+                                "index*",
+                                union(&["#createSession", "#deleteAccount", "#stepUp"]),
+                            ),
+                        ]))),
+                        Some(json_body(object(vec![(
+                            "result*",
+                            union(&[
+                                "#createSessionResult",
+                                "#deleteAccountResult",
+                                "#stepUpResult",
+                            ]),
+                        )]))),
+                    ),
+                ),
+                ("createSession", LexDef::Object(object(vec![]))),
+                ("deleteAccount", LexDef::Object(object(vec![]))),
+                ("stepUp", step_up()),
+                (
+                    "createSessionResult",
+                    LexDef::Object(object(vec![
+                        ("accessJwt*", string()),
+                        ("refreshJwt*", string()),
+                        ("handle*", formatted("handle")),
+                        ("did*", formatted("did")),
+                        ("didDoc", unknown()),
+                        ("email?", string()),
+                        ("emailConfirmed", boolean()),
+                    ])),
+                ),
+                ("deleteAccountResult", LexDef::Object(object(vec![]))),
+                (
+                    "stepUpResult",
+                    LexDef::Object(object(vec![
+                        (
+                            "stepUpToken*",
+                            string().describe(
+                                "Short-lived token to send in the `Web5-Step-Up` header alongside the access token",
+                            ),
+                        ),
+                        ("lxm*", formatted("nsid")),
+                        (
+                            "expiresIn*",
+                            bounded(0, None).describe("Lifetime of the token in seconds"),
+                        ),
+                    ])),
+                ),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.createUpload",
+            "Starts a resumable blob upload.",
+            vec![(
+                "main",
+                procedure(
+                    "The blob is then sent in chunks with appendUpload and completed with finalizeUpload. Requires auth.",
+                    Some(json_body(object(vec![
+                        ("mimeType*", string()),
+                        (
+                            "size*",
+                            bounded(0, None).describe("Total size of the blob in bytes."),
+                        ),
+                        (
+                            "cid",
+                            formatted("cid").describe(
+                                "CID the finished blob is expected to have, checked on finalize.",
+                            ),
+                        ),
+                    ]))),
+                    Some(upload_status()),
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.appendUpload",
+            "Appends a chunk to a resumable blob upload.",
+            vec![(
+                "main",
+                LexDef::Procedure {
+                    description: Some(
+                        "On an offset mismatch the error carries the offset the server expects, which is also available from getUpload. Requires auth."
+                            .to_string(),
+                    ),
+                    parameters: Some(params(vec![
+                        ("uploadId*", string()),
+                        ("offset*", bounded(0, None)),
+                    ])),
+                    input: Some(LexBody {
+                        encoding: "*/*".to_string(),
+                        description: Some("The chunk's bytes".to_string()),
+                        schema: None,
+                    }),
+                    output: Some(upload_status()),
+                },
+            )],
+        ),
+        doc(
+            "com.atproto.web5.getUpload",
+            "Reports the progress of a resumable blob upload.",
+            vec![(
+                "main",
+                LexDef::Query {
+                    description: Some("Requires auth.".to_string()),
+                    parameters: Some(params(vec![("uploadId*", string())])),
+                    output: Some(upload_status()),
+                },
+            )],
+        ),
+        doc(
+            "com.atproto.web5.finalizeUpload",
+            "Completes a resumable blob upload once every chunk has been received.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth.",
+                    Some(json_body(object(vec![
+                        ("uploadId*", string()),
+                        ("cid", formatted("cid")),
+                    ]))),
+                    Some(json_body(object(vec![
+                        ("blobServer*", string()),
+                        ("blob*", blob()),
+                    ]))),
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.reserveHandle",
+            "Asks the PDS to hold the handle an on-chain DID doc claims for the chain key holder, before an account with it exists.",
+            vec![(
+                "main",
+                procedure(
+                    "Does not require auth.",
+                    Some(json_body(object(vec![("ckbAddr*", string())]))),
+                    Some(json_body(object(vec![
+                        ("handle*", formatted("handle")),
+                        ("expiresAt*", formatted("datetime")),
+                    ]))),
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.rebindAddress",
+            "Moves a web5 account to a new CKB address after the old wallet is lost.",
+            vec![(
+                "main",
+                procedure(
+                    "The message is signed both by the repo signing key and by the new address. Does not require auth.",
+                    Some(json_body(object(vec![
+                        ("did*", formatted("did")),
+                        ("message*", string()),
+                        ("signingKey*", formatted("did")),
+                        (
+                            "signedBytes*",
+                            string().describe("Signature of the repo signing key over the message"),
+                        ),
+                        (
+                            "ckbAddr*",
+                            string().describe("The address to bind the account to"),
+                        ),
+                        (
+                            "ckbSignedBytes*",
+                            string().describe(
+                                "Signature of the new address over the message, as CKB wallets sign messages",
+                            ),
+                        ),
+                    ]))),
+                    Some(json_body(object(vec![
+                        ("did*", formatted("did")),
+                        ("ckbAddress*", string()),
+                    ]))),
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.setPassword",
+            "Sets the password a web5 account may sign in with through com.atproto.server.createSession when its wallet isn't at hand.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth and a step-up token from com.atproto.web5.indexAction#stepUp.",
+                    Some(json_body(object(vec![
+                        (
+                            "password",
+                            string().describe("Unset keeps the current password"),
+                        ),
+                        (
+                            "passwordLogin*",
+                            boolean().describe("Whether the password may be used to sign in"),
+                        ),
+                    ]))),
+                    None,
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.getEmailNotificationPrefs",
+            "Reports which BBS activity the requesting account is emailed about.",
+            vec![
+                ("main", query("Requires auth.", json_ref_body("#prefs"))),
+                (
+                    "prefs",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "Which BBS activity is emailed to an account. Everything is on until the account opts out."
+                                .to_string(),
+                        ),
+                        ..email_notification_prefs()
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.putEmailNotificationPrefs",
+            "Opts the requesting account in or out of BBS activity emails.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth.",
+                    Some(json_ref_body(
+                        "com.atproto.web5.getEmailNotificationPrefs#prefs",
+                    )),
+                    None,
+                ),
+            )],
+        ),
+        doc(
+            "com.atproto.web5.getRecordHistory",
+            "Earlier versions of a record, read back from the repo's commit history.",
+            vec![
+                (
+                    "main",
+                    LexDef::Query {
+                        description: Some(
+                            "Newest first. Only commits still held by the PDS are walked, up to a limit the operator sets. Does not require auth."
+                                .to_string(),
+                        ),
+                        parameters: Some(params(vec![
+                            ("repo*", formatted("at-identifier")),
+                            ("collection*", formatted("nsid")),
+                            ("rkey*", formatted("record-key")),
+                            ("limit", bounded(1, Some(100))),
+                            ("cursor", string()),
+                        ])),
+                        output: Some(json_body(object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("versions*", array(reference("#recordVersion"))),
+                            ("cursor", string()),
+                        ]))),
+                    },
+                ),
+                (
+                    "recordVersion",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "The record as one commit left it. Deletes have no cid or value, and neither does a version whose commit was too big to carry its blocks."
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            (
+                                "action*",
+                                LexField::String {
+                                    description: None,
+                                    format: None,
+                                    known_values: Some(vec![
+                                        "create".to_string(),
+                                        "update".to_string(),
+                                        "delete".to_string(),
+                                    ]),
+                                    max_length: None,
+                                    max_graphemes: None,
+                                },
+                            ),
+                            ("cid", formatted("cid")),
+                            (
+                                "rev*",
+                                formatted("tid").describe("Rev of the commit that made this version"),
+                            ),
+                            ("value", unknown()),
+                            ("createdAt*", formatted("datetime")),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.listTombstones",
+            "Records the requesting account deleted through directWrites that the PDS still holds.",
+            vec![
+                (
+                    "main",
+                    LexDef::Query {
+                        description: Some(
+                            "Most recently deleted first. Tombstones are purged once the operator's retention window passes. Requires auth."
+                                .to_string(),
+                        ),
+                        parameters: Some(params(vec![
+                            ("limit", bounded(1, Some(100))),
+                            ("cursor", string()),
+                        ])),
+                        output: Some(json_body(object(vec![
+                            ("tombstones*", array(reference("#tombstone"))),
+                            ("cursor", string()),
+                        ]))),
+                    },
+                ),
+                (
+                    "tombstone",
+                    LexDef::Object(LexObject {
+                        description: Some(
+                            "A deleted record, as it was before the delete. Creating a record at the same uri undoes the delete."
+                                .to_string(),
+                        ),
+                        ..object(vec![
+                            ("uri*", formatted("at-uri")),
+                            ("cid*", formatted("cid")),
+                            ("value*", unknown()),
+                            ("deletedAt*", formatted("datetime")),
+                            (
+                                "purgeAt*",
+                                formatted("datetime").describe("When the PDS removes it for good"),
+                            ),
+                        ])
+                    }),
+                ),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.scheduleDirectWrites",
+            "Holds a batch of signed writes and applies it at a later time.",
+            vec![
+                (
+                    "main",
+                    procedure(
+                        "Takes the same input as directWrites plus publishAt. The signature is checked now; at publishAt the PDS applies the writes as directWrites would, so any other write to the repo in between fails them. Requires auth.",
+                        Some(json_body(object({
+                            let mut fields = direct_writes_input(&[
+                                "com.atproto.web5.directWrites#create",
+                                "com.atproto.web5.directWrites#update",
+                                "com.atproto.web5.directWrites#delete",
+                            ]);
+                            fields.push((
+                                "publishAt*",
+                                formatted("datetime")
+                                    .describe("When to apply the writes, within the operator's horizon"),
+                            ));
+                            fields
+                        }))),
+                        Some(json_ref_body("#scheduledWrite")),
+                    ),
+                ),
+                ("scheduledWrite", scheduled_write()),
+            ],
+        ),
+        doc(
+            "com.atproto.web5.listScheduledWrites",
+            "The requesting account's scheduled writes.",
+            vec![(
+                "main",
+                LexDef::Query {
+                    description: Some(
+                        "Soonest publishAt first, including ones already published, failed or canceled. Requires auth."
+                            .to_string(),
+                    ),
+                    parameters: Some(params(vec![
+                        ("limit", bounded(1, Some(100))),
+                        ("cursor", string()),
+                    ])),
+                    output: Some(json_body(object(vec![
+                        (
+                            "scheduled*",
+                            array(reference("com.atproto.web5.scheduleDirectWrites#scheduledWrite")),
+                        ),
+                        ("cursor", string()),
+                    ]))),
+                },
+            )],
+        ),
+        doc(
+            "com.atproto.web5.cancelScheduledWrite",
+            "Drops a scheduled write that hasn't been published yet.",
+            vec![(
+                "main",
+                procedure(
+                    "Requires auth.",
+                    Some(json_body(object(vec![("id*", string())]))),
+                    Some(json_ref_body(
+                        "com.atproto.web5.scheduleDirectWrites#scheduledWrite",
+                    )),
+                ),
+            )],
+        ),
+    ]
+}
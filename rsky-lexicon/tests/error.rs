@@ -0,0 +1,27 @@
+use rsky_lexicon::error::{ErrorCode, XrpcErrorBody};
+
+#[test]
+fn codes_round_trip_through_their_names() {
+    let body: XrpcErrorBody =
+        serde_json::from_str(r#"{"error":"CkbAddrNotFound","message":"gone"}"#).unwrap();
+    assert_eq!(body.error, ErrorCode::CkbAddrNotFound);
+    assert_eq!(
+        serde_json::to_string(&body).unwrap(),
+        r#"{"error":"CkbAddrNotFound","message":"gone"}"#
+    );
+    assert_eq!(
+        ErrorCode::from("SomethingNew"),
+        ErrorCode::Other("SomethingNew".to_string())
+    );
+    assert_eq!(ErrorCode::from("SomethingNew").as_str(), "SomethingNew");
+}
+
+#[test]
+fn statuses_follow_the_code() {
+    assert_eq!(ErrorCode::RecordNotFound.http_status(), 404);
+    assert_eq!(ErrorCode::AuthRequiredError.http_status(), 401);
+    assert_eq!(ErrorCode::RateLimitExceeded.http_status(), 429);
+    assert_eq!(ErrorCode::PayloadTooLarge.http_status(), 413);
+    assert_eq!(ErrorCode::InvalidRequest.http_status(), 400);
+    assert_eq!(ErrorCode::Other("Nope".to_string()).http_status(), 400);
+}
@@ -0,0 +1,394 @@
+//! Property tests that the web5 and bbs lexicon types survive JSON and DAG-CBOR
+//! round trips, ignore fields they don't know and carry the right `$type`.
+
+use chrono::{DateTime, Utc};
+use proptest::prelude::*;
+use rsky_lexicon::app::bbs::{Post, Reply, Vote};
+use rsky_lexicon::app::bsky::richtext::{ByteSlice, Facet, Features, Link, Mention, Tag};
+use rsky_lexicon::com::atproto::web5::{
+    CreateAccountInput, DirectWritesInput, DirectWritesInputRefWrite, IndexActionInput,
+    IndexActionInputRef, PreDirectWritesInput, PreDirectWritesInputRefWrite, PreIndexActionInput,
+    PreIndexActionInputRef, RefCreateSessionIndex, RefDeleteAccountIndex, RefStepUpIndex,
+    RefWriteCreate, RefWriteDelete, RefWriteUpdate, SignedRoot,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Serializes `value`, reads it back from JSON, from DAG-CBOR and from JSON
+/// with an extra field, and checks each reads back to the same JSON.
+fn check_round_trips<T: Serialize + DeserializeOwned>(value: &T) -> Result<Value, TestCaseError> {
+    let json = serde_json::to_value(value).unwrap();
+    let from_json: T = serde_json::from_value(json.clone()).unwrap();
+    prop_assert_eq!(serde_json::to_value(&from_json).unwrap(), json.clone());
+
+    let cbor = serde_ipld_dagcbor::to_vec(value).unwrap();
+    prop_assert_eq!(
+        serde_ipld_dagcbor::from_slice::<Value>(&cbor).unwrap(),
+        json.clone()
+    );
+    let from_cbor: T = serde_ipld_dagcbor::from_slice(&cbor).unwrap();
+    prop_assert_eq!(serde_json::to_value(&from_cbor).unwrap(), json.clone());
+
+    // Fields added in newer lexicon versions are ignored
+    let mut extended = json.clone();
+    extended
+        .as_object_mut()
+        .unwrap()
+        .insert("unknownField".to_string(), Value::from("ignored"));
+    let from_extended: T = serde_json::from_value(extended).unwrap();
+    prop_assert_eq!(serde_json::to_value(&from_extended).unwrap(), json.clone());
+    Ok(json)
+}
+
+fn did() -> impl Strategy<Value = String> {
+    "did:(web5|plc|ckb):[a-z2-7]{8,24}"
+}
+
+fn did_key() -> impl Strategy<Value = String> {
+    "did:key:z[1-9A-HJ-NP-Za-km-z]{44,48}"
+}
+
+fn datetime() -> impl Strategy<Value = DateTime<Utc>> {
+    // Up to the year 2100
+    (0i64..4_102_444_800_000).prop_map(|millis| DateTime::from_timestamp_millis(millis).unwrap())
+}
+
+/// Record values: anything JSON but floats, which DAG-CBOR doesn't allow in records
+fn record_value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<String>().prop_map(Value::String),
+    ];
+    let value = leaf.prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+            prop::collection::btree_map("[a-zA-Z$]{1,12}", inner, 0..4)
+                .prop_map(|fields| Value::Object(fields.into_iter().collect::<Map<_, _>>())),
+        ]
+    });
+    prop::collection::btree_map("[a-zA-Z]{1,12}", value, 0..6)
+        .prop_map(|fields| Value::Object(fields.into_iter().collect()))
+}
+
+fn facet() -> impl Strategy<Value = Facet> {
+    let feature = prop_oneof![
+        did().prop_map(|did| Features::Mention(Mention { did })),
+        "https://[a-z]{1,12}\\.[a-z]{2,4}/[a-z0-9/]{0,16}"
+            .prop_map(|uri| Features::Link(Link { uri })),
+        "[^\\s#]{1,64}".prop_map(|tag| Features::Tag(Tag { tag })),
+    ];
+    (
+        0usize..3000,
+        0usize..300,
+        prop::collection::vec(feature, 1..3),
+    )
+        .prop_map(|(byte_start, len, features)| Facet {
+            index: ByteSlice {
+                byte_start,
+                byte_end: byte_start + len,
+            },
+            features,
+        })
+}
+
+fn facets() -> impl Strategy<Value = Option<Vec<Facet>>> {
+    prop::option::of(prop::collection::vec(facet(), 0..4))
+}
+
+fn langs() -> impl Strategy<Value = Option<Vec<String>>> {
+    prop::option::of(prop::collection::vec("[a-z]{2}(-[A-Z]{2})?", 0..3))
+}
+
+fn tags() -> impl Strategy<Value = Option<Vec<String>>> {
+    prop::option::of(prop::collection::vec("[^\\s#]{1,64}", 0..4))
+}
+
+fn at_uri() -> impl Strategy<Value = String> {
+    (did(), "[a-z2-7]{13}").prop_map(|(did, rkey)| format!("at://{did}/app.bbs.post/{rkey}"))
+}
+
+prop_compose! {
+    fn post()(
+        created_at in datetime(),
+        text in any::<String>(),
+        facets in facets(),
+        langs in langs(),
+        tags in tags(),
+        section_id in 0usize..1000,
+        title in any::<String>(),
+    ) -> Post {
+        Post {
+            created_at,
+            text,
+            entities: None,
+            facets,
+            langs,
+            labels: None,
+            embed: None,
+            tags,
+            section_id,
+            title,
+        }
+    }
+}
+
+prop_compose! {
+    fn reply()(
+        created_at in datetime(),
+        text in any::<String>(),
+        facets in facets(),
+        langs in langs(),
+        tags in tags(),
+        root in at_uri(),
+        parent in at_uri(),
+    ) -> Reply {
+        Reply {
+            created_at,
+            text,
+            entities: None,
+            facets,
+            langs,
+            labels: None,
+            embed: None,
+            tags,
+            root,
+            parent,
+        }
+    }
+}
+
+prop_compose! {
+    fn vote()(created_at in datetime(), subject in at_uri(), up in any::<bool>()) -> Vote {
+        Vote {
+            created_at,
+            subject,
+            value: if up { 1 } else { -1 },
+        }
+    }
+}
+
+fn collection() -> impl Strategy<Value = String> {
+    "(app\\.bbs\\.(post|reply|vote)|app\\.bsky\\.feed\\.like)"
+}
+
+fn rkey() -> impl Strategy<Value = String> {
+    "[a-z2-7]{13}"
+}
+
+fn pre_write() -> impl Strategy<Value = PreDirectWritesInputRefWrite> {
+    prop_oneof![
+        (collection(), prop::option::of(rkey()), record_value()).prop_map(
+            |(collection, rkey, value)| {
+                PreDirectWritesInputRefWrite::Create(RefWriteCreate {
+                    collection,
+                    rkey,
+                    value,
+                })
+            }
+        ),
+        (collection(), rkey(), record_value()).prop_map(|(collection, rkey, value)| {
+            PreDirectWritesInputRefWrite::Update(RefWriteUpdate {
+                collection,
+                rkey,
+                value,
+            })
+        }),
+        (collection(), rkey()).prop_map(|(collection, rkey)| {
+            PreDirectWritesInputRefWrite::Delete(RefWriteDelete { collection, rkey })
+        }),
+    ]
+}
+
+/// The signed counterpart of a preDirectWrites write.
+fn to_write(write: PreDirectWritesInputRefWrite) -> DirectWritesInputRefWrite {
+    match write {
+        PreDirectWritesInputRefWrite::Create(write) => DirectWritesInputRefWrite::Create(write),
+        PreDirectWritesInputRefWrite::Update(write) => DirectWritesInputRefWrite::Update(write),
+        PreDirectWritesInputRefWrite::Delete(write) => DirectWritesInputRefWrite::Delete(write),
+    }
+}
+
+fn write_type(write: &PreDirectWritesInputRefWrite) -> &'static str {
+    match write {
+        PreDirectWritesInputRefWrite::Create(_) => "create",
+        PreDirectWritesInputRefWrite::Update(_) => "update",
+        PreDirectWritesInputRefWrite::Delete(_) => "delete",
+    }
+}
+
+prop_compose! {
+    fn signed_root()(
+        did in did(),
+        rev in "[a-z2-7]{13}",
+        data in "bafyrei[a-z2-7]{52}",
+        prev in prop::option::of("bafyrei[a-z2-7]{52}"),
+        signed_bytes in "[0-9a-f]{128}",
+    ) -> SignedRoot {
+        SignedRoot { did, rev, data, prev, version: 3, signed_bytes }
+    }
+}
+
+fn pre_index() -> impl Strategy<Value = PreIndexActionInputRef> {
+    prop_oneof![
+        Just(PreIndexActionInputRef::CreateSessionIndex(
+            RefCreateSessionIndex {}
+        )),
+        Just(PreIndexActionInputRef::DeleteAccountIndex(
+            RefDeleteAccountIndex {}
+        )),
+        "com\\.atproto\\.[a-z]{1,10}\\.[a-zA-Z]{1,16}"
+            .prop_map(|lxm| PreIndexActionInputRef::StepUpIndex(RefStepUpIndex { lxm })),
+    ]
+}
+
+fn to_index(index: PreIndexActionInputRef) -> IndexActionInputRef {
+    match index {
+        PreIndexActionInputRef::CreateSessionIndex(index) => {
+            IndexActionInputRef::CreateSessionIndex(index)
+        }
+        PreIndexActionInputRef::DeleteAccountIndex(index) => {
+            IndexActionInputRef::DeleteAccountIndex(index)
+        }
+        PreIndexActionInputRef::StepUpIndex(index) => IndexActionInputRef::StepUpIndex(index),
+    }
+}
+
+fn index_type(index: &PreIndexActionInputRef) -> &'static str {
+    match index {
+        PreIndexActionInputRef::CreateSessionIndex(_) => "createSession",
+        PreIndexActionInputRef::DeleteAccountIndex(_) => "deleteAccount",
+        PreIndexActionInputRef::StepUpIndex(_) => "stepUp",
+    }
+}
+
+proptest! {
+    #[test]
+    fn bbs_posts_round_trip(post in post()) {
+        let json = check_round_trips(&post)?;
+        prop_assert_eq!(&json["$type"], "app.bbs.post");
+        if let Some(facets) = json["facets"].as_array() {
+            for feature in facets.iter().flat_map(|facet| facet["features"].as_array().unwrap()) {
+                prop_assert!(feature["$type"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("app.bsky.richtext.facet#"));
+            }
+        }
+    }
+
+    #[test]
+    fn bbs_replies_round_trip(reply in reply()) {
+        let json = check_round_trips(&reply)?;
+        prop_assert_eq!(&json["$type"], "app.bbs.reply");
+    }
+
+    #[test]
+    fn bbs_votes_round_trip(vote in vote()) {
+        let json = check_round_trips(&vote)?;
+        prop_assert_eq!(&json["$type"], "app.bbs.vote");
+    }
+
+    #[test]
+    fn direct_writes_round_trip(
+        repo in did(),
+        validate in prop::option::of(any::<bool>()),
+        writes in prop::collection::vec(pre_write(), 1..6),
+        swap_commit in prop::option::of("bafyrei[a-z2-7]{52}"),
+        signing_key in did_key(),
+        ckb_addr in prop::option::of("ckt1[a-z0-9]{40,90}"),
+        root in signed_root(),
+    ) {
+        let types: Vec<&str> = writes.iter().map(write_type).collect();
+        let pre = PreDirectWritesInput {
+            repo: repo.clone(),
+            validate,
+            writes: writes.clone(),
+            swap_commit: swap_commit.clone(),
+        };
+        let json = check_round_trips(&pre)?;
+        for (write, write_type) in json["writes"].as_array().unwrap().iter().zip(&types) {
+            prop_assert_eq!(
+                &write["$type"],
+                &format!("com.atproto.web5.preDirectWrites#{write_type}")
+            );
+        }
+
+        let signed = DirectWritesInput {
+            repo,
+            validate,
+            writes: writes.into_iter().map(to_write).collect(),
+            swap_commit,
+            signing_key,
+            ckb_addr,
+            root,
+        };
+        let json = check_round_trips(&signed)?;
+        for (write, write_type) in json["writes"].as_array().unwrap().iter().zip(&types) {
+            prop_assert_eq!(
+                &write["$type"],
+                &format!("com.atproto.web5.directWrites#{write_type}")
+            );
+        }
+    }
+
+    #[test]
+    fn index_actions_round_trip(
+        did in did(),
+        message in any::<String>(),
+        signing_key in did_key(),
+        signed_bytes in "(0x)?[0-9a-f]{128,130}",
+        ckb_addr in prop::option::of("ckt1[a-z0-9]{40,90}"),
+        index in pre_index(),
+    ) {
+        let index_type = index_type(&index);
+        let pre = PreIndexActionInput {
+            did: did.clone(),
+            ckb_addr: ckb_addr.clone(),
+            index: index.clone(),
+        };
+        let json = check_round_trips(&pre)?;
+        prop_assert_eq!(
+            &json["index"]["$type"],
+            &format!("com.atproto.web5.preIndexAction#{index_type}")
+        );
+
+        let signed = IndexActionInput {
+            did,
+            message,
+            signing_key,
+            signed_bytes,
+            ckb_addr,
+            index: to_index(index),
+        };
+        let json = check_round_trips(&signed)?;
+        prop_assert_eq!(
+            &json["index"]["$type"],
+            &format!("com.atproto.web5.indexAction#{index_type}")
+        );
+    }
+
+    #[test]
+    fn create_account_round_trips(
+        handle in "[a-z0-9]{1,16}\\.bbs\\.fans",
+        signing_key in did_key(),
+        password in prop::option::of(any::<String>()),
+        root in signed_root(),
+        ckb_addr in "ckt1[a-z0-9]{40,90}",
+        invite_code in prop::option::of("[a-z0-9-]{5,30}"),
+        email in prop::option::of("[a-z]{1,10}@[a-z]{1,10}\\.com"),
+    ) {
+        check_round_trips(&CreateAccountInput {
+            handle,
+            signing_key,
+            password,
+            root,
+            ckb_addr,
+            invite_code,
+            captcha_token: None,
+            email,
+        })?;
+    }
+}
@@ -0,0 +1,1281 @@
+//! Checks the Lexicon documents `lexgen` emits against the Rust types the PDS
+//! and BBS AppView (de)serialize, so the two can't drift apart unnoticed.
+
+use chrono::{DateTime, Utc};
+use rsky_lexicon::app::bbs::actor::{CrosspostPref, GetPreferencesOutput, PutPreferencesInput};
+use rsky_lexicon::app::bbs::automod::{Rule, RuleAction};
+use rsky_lexicon::app::bbs::dm::{
+    ConvoView, ListConvosOutput, ListMessagesOutput, MarkReadInput, MessageView, SendMessageInput,
+};
+use rsky_lexicon::app::bbs::embed::{
+    PostEmbed, Record as EmbedRecord, View as EmbedView, ViewBlocked, ViewNotFound, ViewRecord,
+    ViewUnion,
+};
+use rsky_lexicon::app::bbs::graph::{Block, Follow, GetFollowersOutput, GetFollowsOutput, Mute};
+use rsky_lexicon::app::bbs::moderation::{SetSectionStatusInput, SetSectionStatusOutput};
+use rsky_lexicon::app::bbs::{
+    truncate_graphemes, AuthorFeedItem, AuthorView, DeleteDraftInput, DraftView, EncryptedPost,
+    GetAuthorFeedOutput, GetReadStatesOutput, GetSectionFeedOutput, GetStatsOutput,
+    GetThreadOutput, GetTrendingOutput, GetVoteBurstsOutput, ImagePreview, KeyRecipient,
+    ListDraftsOutput, Post, Reaction, ReactionCount, ReadState, ReadStateUpdate, Reply, ReplyView,
+    SaveDraftInput, SectionStats, SectionStatus, ThreadView, UpdateReadStateInput, Vote, VoteBurst,
+    WatchThread, DEFAULT_REACTION_EMOJIS,
+};
+use rsky_lexicon::app::bsky::embed::external::ViewExternal;
+use rsky_lexicon::app::bsky::richtext::{ByteSlice, Facet, Features, Tag};
+use rsky_lexicon::com::atproto::repo::{Blob, StrongRef};
+use rsky_lexicon::com::atproto::web5::{
+    CancelScheduledWriteInput, CommitMeta, CreateAccountInput, CreateAccountOutput,
+    CreateUploadInput, DirectWritesInput, DirectWritesInputRefWrite, DirectWritesOutput,
+    DirectWritesOutputRefWrite, EmailNotificationPrefs, FinalizeUploadInput,
+    GetRecordHistoryOutput, IndexActionInput, IndexActionInputRef, IndexActionOutput,
+    IndexActionOutputRefResult, ListScheduledWritesOutput, ListTombstonesOutput,
+    PreCreateAccountInput, PreCreateAccountOutput, PreDirectWritesInput,
+    PreDirectWritesInputRefWrite, PreIndexActionInput, PreIndexActionInputRef,
+    PreIndexActionOutput, RebindAddressInput, RebindAddressOutput, RecordVersion,
+    RefCreateSessionIndex, RefCreateSessionResult, RefDeleteAccountIndex, RefStepUpIndex,
+    RefStepUpResult, RefWriteCreate, RefWriteCreateResult, RefWriteDelete, RefWriteDeleteResult,
+    RefWriteUpdate, ReserveHandleInput, ReserveHandleOutput, ScheduleDirectWritesInput,
+    ScheduledWrite, ScheduledWriteStatus, SetPasswordInput, SignedRoot, Tombstone,
+    UploadStatusOutput,
+};
+use rsky_lexicon::schema::{lexicons, LexDef, LexiconDoc, ValidationError, Validator};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+const DID: &str = "did:web5:alice";
+const SIGNING_KEY: &str = "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+const CID: &str = "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm";
+const POST_URI: &str = "at://did:web5:alice/app.bbs.post/3l4qxdfqfwk2a";
+
+fn json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap()
+}
+
+fn created_at() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_735_689_600, 0).unwrap()
+}
+
+fn validator() -> Validator {
+    Validator::new(lexicons()).deny_unknown_properties()
+}
+
+fn signed_root(prev: Option<&str>) -> SignedRoot {
+    SignedRoot {
+        did: DID.to_string(),
+        rev: "3l4qxdfqfwk2a".to_string(),
+        data: CID.to_string(),
+        prev: prev.map(str::to_string),
+        version: 3,
+        signed_bytes: "ab".repeat(64),
+    }
+}
+
+fn post() -> Post {
+    Post {
+        created_at: created_at(),
+        text: "hello #web5".to_string(),
+        entities: None,
+        facets: Some(vec![Facet {
+            index: ByteSlice {
+                byte_start: 6,
+                byte_end: 11,
+            },
+            features: vec![Features::Tag(Tag {
+                tag: "web5".to_string(),
+            })],
+        }]),
+        langs: Some(vec!["en".to_string()]),
+        labels: None,
+        embed: None,
+        tags: Some(vec!["ckb".to_string()]),
+        section_id: 2,
+        title: "Hello".to_string(),
+    }
+}
+
+fn thread_view() -> ThreadView {
+    ThreadView {
+        uri: POST_URI.to_string(),
+        cid: CID.to_string(),
+        author: AuthorView {
+            did: DID.to_string(),
+            handle: Some("alice.bbs.fans".to_string()),
+        },
+        section_id: 2,
+        title: "Hello".to_string(),
+        text: "hello".to_string(),
+        reply_count: 1,
+        score: -1,
+        created_at: "2025-01-01T00:00:00.000Z".to_string(),
+        last_activity_at: "2025-01-01T00:00:00.000Z".to_string(),
+        indexed_at: "2025-01-01T00:00:00.000Z".to_string(),
+        truncated: None,
+        image: None,
+        external: None,
+        reactions: None,
+        embed: None,
+    }
+}
+
+#[test]
+fn ids_are_unique_and_refs_resolve() {
+    let docs = lexicons();
+    let ids: BTreeSet<&str> = docs.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids.len(), docs.len());
+    for doc in &docs {
+        assert!(
+            doc.id.starts_with("com.atproto.web5.") || doc.id.starts_with("app.bbs."),
+            "{}",
+            doc.id
+        );
+        assert!(!doc.defs.is_empty(), "{} has no defs", doc.id);
+    }
+    assert_eq!(validator().dangling_refs(), Vec::<String>::new());
+}
+
+#[test]
+fn docs_round_trip_through_json() {
+    for doc in lexicons() {
+        let json = serde_json::to_string_pretty(&doc).unwrap();
+        let parsed: LexiconDoc = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, doc);
+    }
+    let post = lexicons()
+        .into_iter()
+        .find(|doc| doc.id == "app.bbs.post")
+        .unwrap();
+    let value = json(&post);
+    assert_eq!(value["lexicon"], 1);
+    assert_eq!(value["defs"]["main"]["type"], "record");
+    assert_eq!(value["defs"]["main"]["key"], "tid");
+    assert_eq!(
+        value["defs"]["main"]["record"]["properties"]["createdAt"],
+        json!({
+            "type": "string",
+            "format": "datetime",
+            "description": "Client-declared timestamp when this post was originally created."
+        })
+    );
+}
+
+#[test]
+fn every_web5_method_has_a_main_def() {
+    for doc in lexicons() {
+        if doc.id.starts_with("com.atproto.web5.") {
+            assert!(
+                matches!(
+                    doc.defs.get("main"),
+                    Some(LexDef::Procedure { .. } | LexDef::Query { .. })
+                ),
+                "{}",
+                doc.id
+            );
+        }
+    }
+}
+
+#[test]
+fn account_types_match_their_schemas() {
+    let validator = validator();
+    let pre = PreCreateAccountInput {
+        handle: "alice.bbs.fans".to_string(),
+        did: DID.to_string(),
+        signing_key: None,
+        invite_code: None,
+    };
+    validator
+        .validate_input("com.atproto.web5.preCreateAccount", &json(&pre))
+        .unwrap();
+    let unsigned = PreCreateAccountOutput {
+        did: DID.to_string(),
+        rev: "3l4qxdfqfwk2a".to_string(),
+        data: CID.to_string(),
+        prev: None,
+        version: 3,
+        un_sign_bytes: "a5".repeat(40),
+    };
+    validator
+        .validate_output("com.atproto.web5.preCreateAccount", &json(&unsigned))
+        .unwrap();
+    for (password, email) in [(None, None), (Some("hunter2"), Some("alice@example.com"))] {
+        let input = CreateAccountInput {
+            handle: "alice.bbs.fans".to_string(),
+            signing_key: SIGNING_KEY.to_string(),
+            password: password.map(str::to_string),
+            root: signed_root(None),
+            ckb_addr: "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsq".to_string(),
+            invite_code: None,
+            captcha_token: None,
+            email: email.map(str::to_string),
+        };
+        validator
+            .validate_input("com.atproto.web5.createAccount", &json(&input))
+            .unwrap();
+    }
+    let output = CreateAccountOutput {
+        handle: "alice.bbs.fans".to_string(),
+        did: DID.to_string(),
+        did_doc: Some(json!({ "id": DID })),
+        access_jwt: "access".to_string(),
+        refresh_jwt: "refresh".to_string(),
+    };
+    validator
+        .validate_output("com.atproto.web5.createAccount", &json(&output))
+        .unwrap();
+    let rebind = RebindAddressInput {
+        did: DID.to_string(),
+        message: "message".to_string(),
+        signing_key: SIGNING_KEY.to_string(),
+        signed_bytes: "ab".repeat(64),
+        ckb_addr: "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsq".to_string(),
+        ckb_signed_bytes: "cd".repeat(65),
+    };
+    validator
+        .validate_input("com.atproto.web5.rebindAddress", &json(&rebind))
+        .unwrap();
+    let rebound = RebindAddressOutput {
+        did: DID.to_string(),
+        ckb_address: rebind.ckb_addr.clone(),
+    };
+    validator
+        .validate_output("com.atproto.web5.rebindAddress", &json(&rebound))
+        .unwrap();
+    let reserve = ReserveHandleInput {
+        ckb_addr: rebind.ckb_addr,
+    };
+    validator
+        .validate_input("com.atproto.web5.reserveHandle", &json(&reserve))
+        .unwrap();
+    let reserved = ReserveHandleOutput {
+        handle: "alice.bbs.fans".to_string(),
+        expires_at: "2025-01-01T00:00:00.000Z".to_string(),
+    };
+    validator
+        .validate_output("com.atproto.web5.reserveHandle", &json(&reserved))
+        .unwrap();
+    let set_password = SetPasswordInput {
+        password: Some("correct horse".to_string()),
+        password_login: true,
+    };
+    validator
+        .validate_input("com.atproto.web5.setPassword", &json(&set_password))
+        .unwrap();
+    let prefs = EmailNotificationPrefs {
+        replies: true,
+        mentions: false,
+    };
+    validator
+        .validate_output("com.atproto.web5.getEmailNotificationPrefs", &json(&prefs))
+        .unwrap();
+    validator
+        .validate_input("com.atproto.web5.putEmailNotificationPrefs", &json(&prefs))
+        .unwrap();
+}
+
+#[test]
+fn write_types_match_their_schemas() {
+    let validator = validator();
+    let value = json(&post());
+    let pre = PreDirectWritesInput {
+        repo: DID.to_string(),
+        validate: None,
+        writes: vec![
+            PreDirectWritesInputRefWrite::Create(RefWriteCreate {
+                collection: "app.bbs.post".to_string(),
+                rkey: None,
+                value: value.clone(),
+            }),
+            PreDirectWritesInputRefWrite::Update(RefWriteUpdate {
+                collection: "app.bbs.post".to_string(),
+                rkey: "3l4qxdfqfwk2a".to_string(),
+                value: value.clone(),
+            }),
+            PreDirectWritesInputRefWrite::Delete(RefWriteDelete {
+                collection: "app.bbs.post".to_string(),
+                rkey: "3l4qxdfqfwk2b".to_string(),
+            }),
+        ],
+        swap_commit: Some(CID.to_string()),
+    };
+    validator
+        .validate_input("com.atproto.web5.preDirectWrites", &json(&pre))
+        .unwrap();
+    let input = DirectWritesInput {
+        repo: DID.to_string(),
+        validate: Some(true),
+        writes: vec![DirectWritesInputRefWrite::Create(RefWriteCreate {
+            collection: "app.bbs.post".to_string(),
+            rkey: Some("3l4qxdfqfwk2a".to_string()),
+            value,
+        })],
+        swap_commit: None,
+        signing_key: SIGNING_KEY.to_string(),
+        ckb_addr: None,
+        root: signed_root(Some(CID)),
+    };
+    validator
+        .validate_input("com.atproto.web5.directWrites", &json(&input))
+        .unwrap();
+    // preDirectWrites' writes aren't accepted by directWrites
+    let mut mixed = json(&input);
+    mixed["writes"] = json(&pre)["writes"].clone();
+    assert!(validator
+        .validate_input("com.atproto.web5.directWrites", &mixed)
+        .is_err());
+
+    let output = DirectWritesOutput {
+        commit: Some(CommitMeta {
+            cid: CID.to_string(),
+            rev: "3l4qxdfqfwk2a".to_string(),
+        }),
+        results: Some(vec![
+            DirectWritesOutputRefWrite::Create(RefWriteCreateResult {
+                uri: POST_URI.to_string(),
+                cid: CID.to_string(),
+                validation_status: Some("valid".to_string()),
+            }),
+            DirectWritesOutputRefWrite::Delete(RefWriteDeleteResult {}),
+        ]),
+    };
+    validator
+        .validate_output("com.atproto.web5.directWrites", &json(&output))
+        .unwrap();
+    let empty = DirectWritesOutput {
+        commit: None,
+        results: None,
+    };
+    validator
+        .validate_output("com.atproto.web5.directWrites", &json(&empty))
+        .unwrap();
+}
+
+#[test]
+fn index_action_types_match_their_schemas() {
+    let validator = validator();
+    let indexes = [
+        PreIndexActionInputRef::CreateSessionIndex(RefCreateSessionIndex {}),
+        PreIndexActionInputRef::DeleteAccountIndex(RefDeleteAccountIndex {}),
+        PreIndexActionInputRef::StepUpIndex(RefStepUpIndex {
+            lxm: "com.atproto.server.deleteAccount".to_string(),
+        }),
+    ];
+    for index in indexes {
+        let pre = PreIndexActionInput {
+            did: DID.to_string(),
+            ckb_addr: None,
+            index: index.clone(),
+        };
+        validator
+            .validate_input("com.atproto.web5.preIndexAction", &json(&pre))
+            .unwrap();
+        let input = IndexActionInput {
+            did: DID.to_string(),
+            message: index.statement(),
+            signing_key: SIGNING_KEY.to_string(),
+            signed_bytes: "ab".repeat(64),
+            ckb_addr: None,
+            index: match index {
+                PreIndexActionInputRef::CreateSessionIndex(index) => {
+                    IndexActionInputRef::CreateSessionIndex(index)
+                }
+                PreIndexActionInputRef::DeleteAccountIndex(index) => {
+                    IndexActionInputRef::DeleteAccountIndex(index)
+                }
+                PreIndexActionInputRef::StepUpIndex(index) => {
+                    IndexActionInputRef::StepUpIndex(index)
+                }
+            },
+        };
+        validator
+            .validate_input("com.atproto.web5.indexAction", &json(&input))
+            .unwrap();
+    }
+    let message = PreIndexActionOutput {
+        did: DID.to_string(),
+        handle: "alice.bbs.fans".to_string(),
+        message: "message".to_string(),
+    };
+    validator
+        .validate_output("com.atproto.web5.preIndexAction", &json(&message))
+        .unwrap();
+    let results = [
+        IndexActionOutputRefResult::CreateSessionResult(RefCreateSessionResult {
+            access_jwt: "access".to_string(),
+            refresh_jwt: "refresh".to_string(),
+            handle: "alice.bbs.fans".to_string(),
+            did: DID.to_string(),
+            did_doc: None,
+            email: None,
+            email_confirmed: None,
+        }),
+        IndexActionOutputRefResult::StepUpResult(RefStepUpResult {
+            step_up_token: "token".to_string(),
+            lxm: "com.atproto.server.deleteAccount".to_string(),
+            expires_in: 300,
+        }),
+    ];
+    for result in results {
+        validator
+            .validate_output(
+                "com.atproto.web5.indexAction",
+                &json(&IndexActionOutput { result }),
+            )
+            .unwrap();
+    }
+}
+
+#[test]
+fn upload_types_match_their_schemas() {
+    let validator = validator();
+    let create = CreateUploadInput {
+        mime_type: "image/png".to_string(),
+        size: 1 << 20,
+        cid: None,
+    };
+    validator
+        .validate_input("com.atproto.web5.createUpload", &json(&create))
+        .unwrap();
+    let status = UploadStatusOutput {
+        upload_id: "upload".to_string(),
+        offset: 0,
+        size: 1 << 20,
+        expires_at: "2025-01-01T00:00:00.000Z".to_string(),
+    };
+    for method in [
+        "com.atproto.web5.createUpload",
+        "com.atproto.web5.appendUpload",
+        "com.atproto.web5.getUpload",
+    ] {
+        validator.validate_output(method, &json(&status)).unwrap();
+    }
+    let finalize = FinalizeUploadInput {
+        upload_id: "upload".to_string(),
+        cid: Some(CID.to_string()),
+    };
+    validator
+        .validate_input("com.atproto.web5.finalizeUpload", &json(&finalize))
+        .unwrap();
+}
+
+#[test]
+fn bbs_records_match_their_schemas() {
+    let validator = validator();
+    validator
+        .validate_record("app.bbs.post", &json(&post()))
+        .unwrap();
+    let reply = Reply {
+        created_at: created_at(),
+        text: "hi".to_string(),
+        entities: None,
+        facets: None,
+        langs: None,
+        labels: None,
+        embed: None,
+        tags: None,
+        root: POST_URI.to_string(),
+        parent: POST_URI.to_string(),
+    };
+    validator
+        .validate_record("app.bbs.reply", &json(&reply))
+        .unwrap();
+    let quote = Reply {
+        embed: Some(PostEmbed::Record(EmbedRecord {
+            record: StrongRef {
+                uri: "at://did:web5:bob/app.bbs.post/3l4qxdfqfwk2b".to_string(),
+                cid: CID.to_string(),
+            },
+        })),
+        ..reply
+    };
+    validator
+        .validate_record("app.bbs.reply", &json(&quote))
+        .unwrap();
+    let parsed: Reply = serde_json::from_value(json(&quote)).unwrap();
+    assert_eq!(parsed, quote);
+    assert_eq!(
+        parsed.embed.as_ref().and_then(PostEmbed::quoted),
+        Some(&StrongRef {
+            uri: "at://did:web5:bob/app.bbs.post/3l4qxdfqfwk2b".to_string(),
+            cid: CID.to_string(),
+        })
+    );
+    for value in [1, -1] {
+        let vote = Vote {
+            created_at: created_at(),
+            subject: POST_URI.to_string(),
+            value,
+        };
+        validator
+            .validate_record("app.bbs.vote", &json(&vote))
+            .unwrap();
+    }
+    for emoji in DEFAULT_REACTION_EMOJIS {
+        let reaction = Reaction {
+            created_at: created_at(),
+            subject: POST_URI.to_string(),
+            emoji: emoji.to_string(),
+        };
+        validator
+            .validate_record("app.bbs.reaction", &json(&reaction))
+            .unwrap();
+    }
+    let reaction = Reaction {
+        created_at: created_at(),
+        subject: POST_URI.to_string(),
+        emoji: "x".repeat(65),
+    };
+    assert!(validator
+        .validate_record("app.bbs.reaction", &json(&reaction))
+        .is_err());
+    for muted in [None, Some(true)] {
+        let watch = WatchThread {
+            created_at: created_at(),
+            subject: POST_URI.to_string(),
+            muted,
+        };
+        validator
+            .validate_record("app.bbs.watchThread", &json(&watch))
+            .unwrap();
+    }
+}
+
+#[test]
+fn blocks_and_mutes_match_their_schemas() {
+    let validator = validator();
+    let block = Block {
+        created_at: created_at(),
+        subject: "did:web5:bob".to_string(),
+    };
+    validator
+        .validate_record("app.bbs.graph.block", &json(&block))
+        .unwrap();
+    let mute = Mute {
+        created_at: created_at(),
+        subject: "did:web5:bob".to_string(),
+    };
+    validator
+        .validate_record("app.bbs.graph.mute", &json(&mute))
+        .unwrap();
+
+    let mut not_a_did = json(&block);
+    not_a_did["subject"] = json!(POST_URI);
+    assert!(validator
+        .validate_record("app.bbs.graph.block", &not_a_did)
+        .is_err());
+}
+
+#[test]
+fn follows_and_author_feeds_match_their_schemas() {
+    let validator = validator();
+    let follow = Follow {
+        created_at: created_at(),
+        subject: "did:web5:bob".to_string(),
+    };
+    validator
+        .validate_record("app.bbs.graph.follow", &json(&follow))
+        .unwrap();
+
+    let alice = AuthorView {
+        did: DID.to_string(),
+        handle: Some("alice.example.com".to_string()),
+    };
+    let bob = AuthorView {
+        did: "did:web5:bob".to_string(),
+        handle: None,
+    };
+    let follows = GetFollowsOutput {
+        subject: alice.clone(),
+        follows: vec![bob.clone()],
+        cursor: Some("cursor".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.graph.getFollows", &json(&follows))
+        .unwrap();
+    let followers = GetFollowersOutput {
+        subject: bob.clone(),
+        followers: vec![alice],
+        cursor: None,
+    };
+    validator
+        .validate_output("app.bbs.graph.getFollowers", &json(&followers))
+        .unwrap();
+
+    let feed = GetAuthorFeedOutput {
+        feed: vec![
+            AuthorFeedItem {
+                thread: None,
+                reply: Some(ReplyView {
+                    uri: "at://did:web5:bob/app.bbs.reply/3l4qxdfqfwk2c".to_string(),
+                    cid: CID.to_string(),
+                    author: bob,
+                    parent: POST_URI.to_string(),
+                    text: "hi".to_string(),
+                    created_at: "2025-01-02T00:00:00.000Z".to_string(),
+                    indexed_at: "2025-01-02T00:00:00.000Z".to_string(),
+                    reactions: None,
+                    embed: None,
+                }),
+            },
+            AuthorFeedItem {
+                thread: Some(thread_view()),
+                reply: None,
+            },
+        ],
+        cursor: Some("cursor".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.getAuthorFeed", &json(&feed))
+        .unwrap();
+}
+
+#[test]
+fn previews_match_their_schema() {
+    let (text, truncated) = truncate_graphemes("ne\u{301}e, naïve", 2);
+    assert_eq!(text, "ne\u{301}");
+    assert!(truncated);
+    assert_eq!(truncate_graphemes("hello", 5), ("hello".to_string(), false));
+
+    let thread = ThreadView {
+        truncated: Some(true),
+        image: Some(ImagePreview {
+            cid: CID.to_string(),
+            alt: "A cat".to_string(),
+            mime_type: "image/jpeg".to_string(),
+        }),
+        external: Some(ViewExternal {
+            uri: "https://example.com/article".to_string(),
+            title: "An article".to_string(),
+            description: "What it's about".to_string(),
+            thumb: Some(format!(
+                "https://pds.example.com/xrpc/app.bbs.getLinkThumb?cid={CID}"
+            )),
+        }),
+        ..thread_view()
+    };
+    let feed = GetSectionFeedOutput {
+        threads: vec![thread],
+        cursor: None,
+        status: None,
+    };
+    validator()
+        .validate_output("app.bbs.getSectionFeed", &json(&feed))
+        .unwrap();
+}
+
+#[test]
+fn encrypted_posts_match_their_schema() {
+    let validator = validator();
+    let encrypted = EncryptedPost {
+        created_at: created_at(),
+        section_id: 7,
+        root: Some(POST_URI.to_string()),
+        parent: Some(POST_URI.to_string()),
+        algorithm: "xchacha20-poly1305".to_string(),
+        key_id: "2".to_string(),
+        nonce: "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX".to_string(),
+        recipients: vec![KeyRecipient {
+            did: DID.to_string(),
+            key_ref: format!("{DID}#atproto"),
+            wrapped_key: "c2VjdGlvbiBrZXk".to_string(),
+        }],
+        ciphertext: Blob {
+            r#type: Some("blob".to_string()),
+            r#ref: Some(CID.parse().unwrap()),
+            cid: None,
+            mime_type: "application/octet-stream".to_string(),
+            size: Some(2048),
+            original: None,
+        },
+    };
+    let value = json(&encrypted);
+    validator
+        .validate_record("app.bbs.encryptedPost", &value)
+        .unwrap();
+
+    let mut missing_key = value;
+    missing_key["recipients"][0]
+        .as_object_mut()
+        .unwrap()
+        .remove("wrappedKey");
+    assert!(validator
+        .validate_record("app.bbs.encryptedPost", &missing_key)
+        .is_err());
+}
+
+#[test]
+fn automod_rules_match_their_schema() {
+    let validator = validator();
+    let rule = Rule {
+        created_at: created_at(),
+        section_id: 3,
+        action: RuleAction::Hold,
+        label: None,
+        reason: Some("New accounts are reviewed before posting here".to_string()),
+        keywords: Some(vec!["free airdrop".to_string()]),
+        patterns: Some(vec![r"(?i)\bt\.me/".to_string()]),
+        min_account_age_hours: Some(24),
+        max_links: Some(2),
+        allowed_domains: Some(vec!["github.com".to_string()]),
+        max_posts_per_hour: Some(10),
+    };
+    let value = json(&rule);
+    assert_eq!(value["action"], "hold");
+    validator
+        .validate_record("app.bbs.automod.rule", &value)
+        .unwrap();
+
+    let mut no_action = value;
+    no_action.as_object_mut().unwrap().remove("action");
+    assert!(validator
+        .validate_record("app.bbs.automod.rule", &no_action)
+        .is_err());
+}
+
+#[test]
+fn section_status_matches_its_schema() {
+    let validator = validator();
+    let input = SetSectionStatusInput {
+        section_id: 3,
+        status: SectionStatus::Archived,
+    };
+    let value = json(&input);
+    assert_eq!(value["status"], "archived");
+    validator
+        .validate_input("app.bbs.moderation.setSectionStatus", &value)
+        .unwrap();
+    let output = SetSectionStatusOutput {
+        section_id: 3,
+        status: SectionStatus::Archived,
+        updated_at: "2025-01-01T00:00:00.000Z".to_string(),
+    };
+    validator
+        .validate_output("app.bbs.moderation.setSectionStatus", &json(&output))
+        .unwrap();
+    assert_eq!("archived".parse::<SectionStatus>().unwrap(), input.status);
+    assert!("closed".parse::<SectionStatus>().is_err());
+
+    let feed = GetSectionFeedOutput {
+        threads: vec![
+            thread_view(),
+            ThreadView {
+                embed: Some(blocked),
+                ..thread_view()
+            },
+        ],
+        cursor: None,
+        status: Some(SectionStatus::Archived),
+    };
+    validator
+        .validate_output("app.bbs.getSectionFeed", &json(&feed))
+        .unwrap();
+}
+
+#[test]
+fn bbs_views_match_their_schemas() {
+    let validator = validator();
+    let stats = GetStatsOutput {
+        total_posts: 10,
+        total_replies: 20,
+        active_users_7d: 3,
+        active_users_30d: 5,
+        sections: vec![SectionStats {
+            section_id: 2,
+            total_posts: 10,
+            total_replies: 20,
+            posts_7d: 1,
+            posts_30d: 4,
+            last_activity_at: Some("2025-01-01T00:00:00.000Z".to_string()),
+            status: Some(SectionStatus::Open),
+        }],
+        computed_at: None,
+    };
+    validator
+        .validate_output("app.bbs.getStats", &json(&stats))
+        .unwrap();
+    let bursts = GetVoteBurstsOutput {
+        bursts: vec![VoteBurst {
+            subject: POST_URI.to_string(),
+            window_start: "2025-01-01T00:00:00.000Z".to_string(),
+            window_end: "2025-01-01T00:09:30.000Z".to_string(),
+            votes: 40,
+            voters: 25,
+            score: 18,
+            uncounted_votes: 22,
+        }],
+    };
+    validator
+        .validate_output("app.bbs.admin.getVoteBursts", &json(&bursts))
+        .unwrap();
+    let thread = GetThreadOutput {
+        thread: ThreadView {
+            reactions: Some(vec![
+                ReactionCount {
+                    emoji: "heart".to_string(),
+                    count: 3,
+                },
+                ReactionCount {
+                    emoji: "+1".to_string(),
+                    count: 1,
+                },
+            ]),
+            embed: Some(EmbedView {
+                record: ViewUnion::ViewRecord(ViewRecord {
+                    uri: "at://did:web5:bob/app.bbs.reply/3l4qxdfqfwk2b".to_string(),
+                    cid: CID.to_string(),
+                    author: AuthorView {
+                        did: "did:web5:bob".to_string(),
+                        handle: None,
+                    },
+                    thread: "at://did:web5:bob/app.bbs.post/3l4qxdfqfwk2a".to_string(),
+                    title: None,
+                    text: "quoted".to_string(),
+                    truncated: Some(true),
+                    created_at: "2025-01-01T00:00:00.000Z".to_string(),
+                    indexed_at: "2025-01-01T00:00:00.000Z".to_string(),
+                }),
+            }),
+            ..thread_view()
+        },
+        replies: vec![ReplyView {
+            uri: "at://did:web5:bob/app.bbs.reply/3l4qxdfqfwk2c".to_string(),
+            cid: CID.to_string(),
+            author: AuthorView {
+                did: "did:web5:bob".to_string(),
+                handle: None,
+            },
+            parent: POST_URI.to_string(),
+            text: "hi".to_string(),
+            created_at: "2025-01-01T00:00:00.000Z".to_string(),
+            indexed_at: "2025-01-01T00:00:00.000Z".to_string(),
+            reactions: Some(vec![]),
+            embed: Some(EmbedView {
+                record: ViewUnion::ViewNotFound(ViewNotFound {
+                    uri: "at://did:web5:carol/app.bbs.post/3l4qxdfqfwk2d".to_string(),
+                    not_found: true,
+                }),
+            }),
+        }],
+        cursor: Some("cursor".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.getThread", &json(&thread))
+        .unwrap();
+    let parsed: GetThreadOutput = serde_json::from_value(json(&thread)).unwrap();
+    assert_eq!(parsed.thread.embed, thread.thread.embed);
+    assert_eq!(parsed.replies[0].embed, thread.replies[0].embed);
+    let blocked = EmbedView {
+        record: ViewUnion::ViewBlocked(ViewBlocked {
+            uri: "at://did:web5:carol/app.bbs.post/3l4qxdfqfwk2d".to_string(),
+            blocked: true,
+            author: AuthorView {
+                did: "did:web5:carol".to_string(),
+                handle: None,
+            },
+        }),
+    };
+    let parsed: EmbedView = serde_json::from_value(json(&blocked)).unwrap();
+    assert_eq!(parsed, blocked);
+    let feed = GetSectionFeedOutput {
+        threads: vec![thread_view()],
+        cursor: None,
+        status: None,
+    };
+    validator
+        .validate_output("app.bbs.getSectionFeed", &json(&feed))
+        .unwrap();
+    validator
+        .validate_output("app.bbs.searchPosts", &json(&feed))
+        .unwrap();
+    let trending = GetTrendingOutput {
+        threads: vec![thread_view()],
+        cursor: Some("1".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.getTrending", &json(&trending))
+        .unwrap();
+}
+
+#[test]
+fn dm_types_match_their_schemas() {
+    let validator = validator();
+    let send = SendMessageInput {
+        convo_id: None,
+        members: Some(vec!["did:web5:bob".to_string()]),
+        text: "hi bob".to_string(),
+    };
+    validator
+        .validate_input("app.bbs.dm.sendMessage", &json(&send))
+        .unwrap();
+    let too_long = SendMessageInput {
+        text: "a".repeat(1001),
+        ..send
+    };
+    assert!(validator
+        .validate_input("app.bbs.dm.sendMessage", &json(&too_long))
+        .is_err());
+
+    let message = MessageView {
+        id: "42".to_string(),
+        convo_id: "convo".to_string(),
+        sender: DID.to_string(),
+        text: "hi bob".to_string(),
+        sent_at: "2025-01-01T00:00:00.000Z".to_string(),
+    };
+    validator
+        .validate_output("app.bbs.dm.sendMessage", &json(&message))
+        .unwrap();
+    let convos = ListConvosOutput {
+        convos: vec![ConvoView {
+            id: "convo".to_string(),
+            members: vec![DID.to_string(), "did:web5:bob".to_string()],
+            last_message: Some(message.clone()),
+            unread_count: 0,
+        }],
+        cursor: Some("cursor".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.dm.listConvos", &json(&convos))
+        .unwrap();
+    let messages = ListMessagesOutput {
+        messages: vec![message],
+        cursor: None,
+    };
+    validator
+        .validate_output("app.bbs.dm.listMessages", &json(&messages))
+        .unwrap();
+    let mark_read = MarkReadInput {
+        convo_id: "convo".to_string(),
+        message_id: Some("42".to_string()),
+    };
+    validator
+        .validate_input("app.bbs.dm.markRead", &json(&mark_read))
+        .unwrap();
+}
+
+#[test]
+fn draft_types_match_their_schemas() {
+    let validator = validator();
+    // an unfinished post, missing its title
+    let mut value = json(&post());
+    value.as_object_mut().unwrap().remove("title");
+    let save = SaveDraftInput {
+        id: None,
+        collection: "app.bbs.post".to_string(),
+        value: value.clone(),
+    };
+    validator
+        .validate_input("app.bbs.saveDraft", &json(&save))
+        .unwrap();
+
+    let draft = DraftView {
+        id: "7".to_string(),
+        collection: "app.bbs.post".to_string(),
+        value,
+        created_at: "2025-01-01T00:00:00.000Z".to_string(),
+        updated_at: "2025-01-02T00:00:00.000Z".to_string(),
+    };
+    validator
+        .validate_output("app.bbs.saveDraft", &json(&draft))
+        .unwrap();
+    let drafts = ListDraftsOutput {
+        drafts: vec![draft],
+        cursor: Some("2025-01-02T00:00:00.000Z::7".to_string()),
+    };
+    validator
+        .validate_output("app.bbs.listDrafts", &json(&drafts))
+        .unwrap();
+    validator
+        .validate_input(
+            "app.bbs.deleteDraft",
+            &json(&DeleteDraftInput {
+                id: "7".to_string(),
+            }),
+        )
+        .unwrap();
+}
+
+#[test]
+fn read_state_types_match_their_schemas() {
+    let validator = validator();
+    let thread = format!("at://{DID}/app.bbs.post/3k2a");
+    let update = UpdateReadStateInput {
+        states: vec![ReadStateUpdate {
+            thread: thread.clone(),
+            read_reply_count: 12,
+            last_read_at: created_at(),
+        }],
+    };
+    validator
+        .validate_input("app.bbs.updateReadState", &json(&update))
+        .unwrap();
+
+    let states = GetReadStatesOutput {
+        states: vec![ReadState {
+            thread,
+            read_reply_count: 12,
+            last_read_at: "2025-01-01T00:00:00.000Z".to_string(),
+            updated_at: "2025-01-02T00:00:00.000Z".to_string(),
+        }],
+    };
+    validator
+        .validate_output("app.bbs.getReadStates", &json(&states))
+        .unwrap();
+
+    let mut behind = json(&update);
+    behind["states"][0]["readReplyCount"] = json!(-1);
+    assert!(validator
+        .validate_input("app.bbs.updateReadState", &behind)
+        .is_err());
+}
+
+#[test]
+fn preference_types_match_their_schemas() {
+    let validator = validator();
+    let preferences = vec![
+        json!({"$type": "app.bbs.actor.defs#themePref", "theme": "dark"}),
+        json!({"$type": "app.bbs.actor.defs#mutedSectionsPref", "sections": ["0", "3"]}),
+        json(&CrosspostPref { enabled: true }),
+    ];
+    assert_eq!(
+        preferences[2],
+        json!({"$type": "app.bbs.actor.defs#crosspostPref", "enabled": true})
+    );
+    validator
+        .validate_input(
+            "app.bbs.actor.putPreferences",
+            &json(&PutPreferencesInput {
+                preferences: preferences.clone(),
+            }),
+        )
+        .unwrap();
+    validator
+        .validate_output(
+            "app.bbs.actor.getPreferences",
+            &json(&GetPreferencesOutput { preferences }),
+        )
+        .unwrap();
+
+    let too_many = PutPreferencesInput {
+        preferences: vec![json!({"$type": "app.bbs.actor.defs#themePref"}); 101],
+    };
+    assert!(validator
+        .validate_input("app.bbs.actor.putPreferences", &json(&too_many))
+        .is_err());
+}
+
+#[test]
+fn rejects_values_that_differ_from_the_schema() {
+    let validator = validator();
+    let mut post = json(&post());
+    post["unknown"] = json!(true);
+    assert!(validator.validate_record("app.bbs.post", &post).is_err());
+
+    let mut post = json(&self::post());
+    post.as_object_mut().unwrap().remove("title");
+    assert!(validator.validate_record("app.bbs.post", &post).is_err());
+
+    let mut post = json(&self::post());
+    post["sectionId"] = json!("2");
+    assert!(validator.validate_record("app.bbs.post", &post).is_err());
+
+    let reply = json!({ "$type": "app.bbs.post", "createdAt": "2025-01-01T00:00:00.000Z" });
+    assert!(validator.validate_record("app.bbs.reply", &reply).is_err());
+
+    let vote = json!({
+        "$type": "app.bbs.vote",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+        "subject": POST_URI,
+        "value": 2
+    });
+    assert!(validator.validate_record("app.bbs.vote", &vote).is_err());
+
+    // Properties that serialize as null must be declared nullable
+    let root = json!({
+        "did": DID, "rev": "3l4qxdfqfwk2a", "data": CID, "prev": null,
+        "version": 3, "signedBytes": "ab"
+    });
+    let input = json!({
+        "handle": "alice.bbs.fans",
+        "signingKey": SIGNING_KEY,
+        "root": root,
+        "ckbAddr": "ckt1"
+    });
+    assert!(validator
+        .validate_input("com.atproto.web5.createAccount", &input)
+        .is_err());
+}
+
+#[test]
+fn history_types_match_their_schemas() {
+    let output = GetRecordHistoryOutput {
+        uri: POST_URI.to_string(),
+        versions: vec![
+            RecordVersion {
+                action: "delete".to_string(),
+                cid: None,
+                rev: "3l4qxdfqfwk2c".to_string(),
+                value: None,
+                created_at: "2025-01-03T00:00:00.000Z".to_string(),
+            },
+            RecordVersion {
+                action: "update".to_string(),
+                cid: Some(CID.to_string()),
+                rev: "3l4qxdfqfwk2b".to_string(),
+                value: Some(json(&post())),
+                created_at: "2025-01-02T00:00:00.000Z".to_string(),
+            },
+        ],
+        cursor: Some("42".to_string()),
+    };
+    validator()
+        .validate_output("com.atproto.web5.getRecordHistory", &json(&output))
+        .unwrap();
+}
+
+#[test]
+fn tombstone_types_match_their_schemas() {
+    let output = ListTombstonesOutput {
+        tombstones: vec![Tombstone {
+            uri: POST_URI.to_string(),
+            cid: CID.to_string(),
+            value: json(&post()),
+            deleted_at: "2025-01-03T00:00:00.000Z".to_string(),
+            purge_at: "2025-02-02T00:00:00.000Z".to_string(),
+        }],
+        cursor: Some(format!("2025-01-03T00:00:00.000Z::{POST_URI}")),
+    };
+    validator()
+        .validate_output("com.atproto.web5.listTombstones", &json(&output))
+        .unwrap();
+}
+
+#[test]
+fn scheduled_write_types_match_their_schemas() {
+    let validator = validator();
+    let input = ScheduleDirectWritesInput {
+        writes: DirectWritesInput {
+            repo: DID.to_string(),
+            validate: None,
+            writes: vec![DirectWritesInputRefWrite::Delete(RefWriteDelete {
+                collection: "app.bbs.post".to_string(),
+                rkey: "3l4qxdfqfwk2a".to_string(),
+            })],
+            swap_commit: Some(CID.to_string()),
+            signing_key: SIGNING_KEY.to_string(),
+            ckb_addr: None,
+            root: signed_root(Some(CID)),
+        },
+        publish_at: created_at(),
+    };
+    let value = json(&input);
+    assert_eq!(value["repo"], json!(DID));
+    validator
+        .validate_input("com.atproto.web5.scheduleDirectWrites", &value)
+        .unwrap();
+    assert_eq!(
+        serde_json::from_value::<ScheduleDirectWritesInput>(value).unwrap(),
+        input
+    );
+
+    let published = ScheduledWrite {
+        id: "1".to_string(),
+        publish_at: "2025-01-03T00:00:00.000Z".to_string(),
+        status: ScheduledWriteStatus::Published,
+        created_at: "2025-01-01T00:00:00.000Z".to_string(),
+        commit: Some(CommitMeta {
+            cid: CID.to_string(),
+            rev: "3l4qxdfqfwk2a".to_string(),
+        }),
+        error: None,
+    };
+    validator
+        .validate_output("com.atproto.web5.scheduleDirectWrites", &json(&published))
+        .unwrap();
+    let failed = ScheduledWrite {
+        id: "2".to_string(),
+        status: ScheduledWriteStatus::Failed,
+        commit: None,
+        error: Some("repo has moved on".to_string()),
+        ..published.clone()
+    };
+    assert_eq!(json(&failed)["status"], json!("failed"));
+    let output = ListScheduledWritesOutput {
+        scheduled: vec![published, failed],
+        cursor: Some("2025-01-03T00:00:00.000Z::2".to_string()),
+    };
+    validator
+        .validate_output("com.atproto.web5.listScheduledWrites", &json(&output))
+        .unwrap();
+    validator
+        .validate_input(
+            "com.atproto.web5.cancelScheduledWrite",
+            &json(&CancelScheduledWriteInput {
+                id: "1".to_string(),
+            }),
+        )
+        .unwrap();
+}
+
+#[test]
+fn reports_where_values_fail() {
+    let validator = validator();
+    let mut post = json(&post());
+    post["title"] = json!("é".repeat(129));
+    let error = validator
+        .validate_record("app.bbs.post", &post)
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ValidationError {
+            path: "title".to_string(),
+            message: "exceeds 128 graphemes".to_string(),
+        }
+    );
+    assert_eq!(
+        error.within("writes[3].value").to_string(),
+        "writes[3].value.title: exceeds 128 graphemes"
+    );
+    // Combining marks count once
+    post["title"] = json!("e\u{301}".repeat(128));
+    validator.validate_record("app.bbs.post", &post).unwrap();
+
+    let mut post = json(&self::post());
+    post["tags"] = json!(["ok", "x".repeat(65)]);
+    let error = validator
+        .validate_record("app.bbs.post", &post)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "tags[1]: exceeds 64 graphemes");
+
+    let mut post = json(&self::post());
+    post["createdAt"] = json!("yesterday");
+    let error = validator
+        .validate_record("app.bbs.post", &post)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "createdAt: isn't a valid datetime");
+
+    let mut input = json!({
+        "repo": DID,
+        "writes": [{ "$type": "com.atproto.web5.preDirectWrites#create", "collection": "app.bbs.post" }]
+    });
+    let error = validator
+        .validate_input("com.atproto.web5.preDirectWrites", &input)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "writes[0].value: required");
+    input["writes"][0]["$type"] = json!("com.atproto.repo.applyWrites#create");
+    let error = validator
+        .validate_input("com.atproto.web5.preDirectWrites", &input)
+        .unwrap_err();
+    assert_eq!(error.path, "writes[0].$type");
+}
+
+#[test]
+fn lets_records_carry_fields_from_newer_lexicons() {
+    let mut post = json(&post());
+    post["pinned"] = json!(true);
+    Validator::new(lexicons())
+        .validate_record("app.bbs.post", &post)
+        .unwrap();
+    assert!(validator().validate_record("app.bbs.post", &post).is_err());
+}
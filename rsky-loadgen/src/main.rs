@@ -0,0 +1,302 @@
+mod pds;
+mod stats;
+
+use crate::pds::{Account, Pds};
+use crate::stats::Stats;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use clap::Parser;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use rsky_lexicon::app::bbs::{Post, Reply};
+use rsky_lexicon::com::atproto::web5::RefWriteCreate;
+use rsky_mock_chain::{address_with_args, did_document, MockChain};
+use rsky_web5_client::{LocalSigner, Signer};
+use secp256k1::SecretKey;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+const POST_COLLECTION: &str = "app.bbs.post";
+const REPLY_COLLECTION: &str = "app.bbs.reply";
+const WORDS: [&str; 24] = [
+    "ckb", "web5", "bbs", "thread", "post", "reply", "cell", "wallet", "sign", "repo", "commit",
+    "node", "chain", "lock", "script", "address", "handle", "did", "section", "forum", "hello",
+    "question", "answer", "update",
+];
+
+/// Creates synthetic web5 accounts backed by a mock CKB chain, then posts,
+/// replies and reads at a PDS at a fixed rate and reports latencies per
+/// endpoint.
+///
+/// The PDS has to look DID cells up on this process's mock chain, so start
+/// loadgen first and the PDS with the PDS_CKB_EXPLORER_URL and PDS_CKB_RPC_URL
+/// it prints.
+#[derive(Parser, Debug)]
+#[command(name = "loadgen")]
+struct Args {
+    /// URL of the PDS under load
+    #[arg(long, env = "LOADGEN_PDS_URL", default_value = "http://localhost:2583")]
+    pds: String,
+    /// Where to serve the mock chain
+    #[arg(long, default_value = "127.0.0.1:8114")]
+    chain_addr: SocketAddr,
+    /// Synthetic accounts to create before generating traffic
+    #[arg(long, default_value_t = 20)]
+    accounts: usize,
+    /// Operations per second across all accounts
+    #[arg(long, default_value_t = 10.0)]
+    rate: f64,
+    /// Seconds to generate traffic for
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+    /// Share of operations that read a record back rather than write one
+    #[arg(long, default_value_t = 0.5)]
+    read_ratio: f64,
+    /// Share of writes that reply to an existing post
+    #[arg(long, default_value_t = 0.6)]
+    reply_ratio: f64,
+    /// BBS sections to spread posts over
+    #[arg(long, default_value_t = 4)]
+    sections: usize,
+    /// Accounts to create at once
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+    /// Admin password, for invite codes when the PDS requires them
+    #[arg(long, env = "PDS_ADMIN_PASS")]
+    admin_password: Option<String>,
+    /// Seconds to wait for the PDS to come up
+    #[arg(long, default_value_t = 120)]
+    wait: u64,
+}
+
+/// What the traffic loop picks from on every tick.
+struct Traffic {
+    pds: Pds,
+    accounts: Vec<tokio::sync::Mutex<Account>>,
+    /// `at://` uris of the posts made so far
+    posts: Mutex<Vec<String>>,
+    read_ratio: f64,
+    reply_ratio: f64,
+    sections: usize,
+}
+
+fn words(min: usize, max: usize) -> String {
+    let mut rng = thread_rng();
+    let len = rng.gen_range(min..=max);
+    (0..len)
+        .map(|_| *WORDS.choose(&mut rng).unwrap())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Traffic {
+    fn random_post(&self) -> Option<String> {
+        self.posts
+            .lock()
+            .unwrap()
+            .choose(&mut thread_rng())
+            .cloned()
+    }
+
+    fn post(&self) -> Result<RefWriteCreate> {
+        Ok(RefWriteCreate {
+            collection: POST_COLLECTION.to_string(),
+            rkey: None,
+            value: serde_json::to_value(Post {
+                created_at: Utc::now(),
+                text: words(5, 80),
+                entities: None,
+                facets: None,
+                langs: Some(vec!["en".to_string()]),
+                labels: None,
+                embed: None,
+                tags: None,
+                section_id: thread_rng().gen_range(0..self.sections),
+                title: words(2, 8),
+            })?,
+        })
+    }
+
+    fn reply(&self, post: String) -> Result<RefWriteCreate> {
+        Ok(RefWriteCreate {
+            collection: REPLY_COLLECTION.to_string(),
+            rkey: None,
+            value: serde_json::to_value(Reply {
+                created_at: Utc::now(),
+                text: words(3, 40),
+                entities: None,
+                facets: None,
+                langs: Some(vec!["en".to_string()]),
+                labels: None,
+                embed: None,
+                tags: None,
+                root: post.clone(),
+                parent: post,
+            })?,
+        })
+    }
+
+    async fn step(&self) -> Result<()> {
+        let (read, reply, index) = {
+            let mut rng = thread_rng();
+            (
+                rng.gen_bool(self.read_ratio),
+                rng.gen_bool(self.reply_ratio),
+                rng.gen_range(0..self.accounts.len()),
+            )
+        };
+        if read {
+            if let Some(post) = self.random_post() {
+                self.pds.get_record(&post).await?;
+                return Ok(());
+            }
+        }
+        let write = match self.random_post().filter(|_| reply) {
+            Some(post) => self.reply(post)?,
+            None => self.post()?,
+        };
+        let is_post = write.collection == POST_COLLECTION;
+        // A repo takes one commit at a time, so writes for an account queue here
+        let account = self.accounts[index].lock().await;
+        let created = self.pds.create_record(&account, write).await?;
+        if is_post {
+            self.posts.lock().unwrap().push(created.uri);
+        }
+        Ok(())
+    }
+}
+
+async fn wait_for_pds(pds: &Pds, wait: Duration) -> Result<Vec<String>> {
+    let start = Instant::now();
+    loop {
+        match pds.describe_server().await {
+            Ok(server) => return Ok(server.available_user_domains),
+            Err(error) if start.elapsed() > wait => {
+                return Err(error).context(format!("PDS at {} didn't come up", pds.service()))
+            }
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+}
+
+async fn create_account(
+    pds: &Pds,
+    chain: &MockChain,
+    run: &str,
+    domain: &str,
+    index: usize,
+    admin_password: Option<&str>,
+) -> Result<Account> {
+    let secret_key = SecretKey::new(&mut thread_rng());
+    let signing_key = LocalSigner::new(secret_key).did_key();
+    let did = format!("did:web5:loadgen{run}{index}");
+    let handle = format!("lg{run}{index}{domain}");
+    let mut args = [0u8; 20];
+    args[..8].copy_from_slice(&u64::from_str_radix(run, 16)?.to_be_bytes());
+    args[12..].copy_from_slice(&(index as u64).to_be_bytes());
+    let ckb_addr = address_with_args(args);
+    let invite_code = match admin_password {
+        Some(admin_password) => Some(pds.create_invite_code(admin_password).await?),
+        None => None,
+    };
+    let access_jwt = pds
+        .create_account(
+            &did,
+            &handle,
+            &ckb_addr,
+            &secret_key,
+            &signing_key,
+            invite_code,
+        )
+        .await?;
+    // As the wallet would once the account exists
+    chain.put_did_doc(
+        &ckb_addr,
+        &did_document(&signing_key, &handle, pds.service()),
+    )?;
+    Ok(Account {
+        did,
+        ckb_addr,
+        secret_key,
+        signing_key,
+        access_jwt,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    if args.accounts == 0 || args.rate <= 0.0 || args.sections == 0 {
+        bail!("--accounts, --rate and --sections must be above zero");
+    }
+    let stats = Arc::new(Stats::default());
+    let pds = Pds::new(&args.pds, stats.clone());
+
+    let chain = MockChain::start_on(args.chain_addr).await?;
+    println!("Mock chain listening, start the PDS with:");
+    for (key, value) in chain.env() {
+        println!("  {key}={value}");
+    }
+    let domains = wait_for_pds(&pds, Duration::from_secs(args.wait)).await?;
+    let domain = domains
+        .first()
+        .context("PDS has no available user domains")?;
+
+    // Short and unique per run so handles and DIDs from earlier runs don't clash
+    let run = format!("{:x}", Utc::now().timestamp() % 0xffffff);
+    let start = Instant::now();
+    let accounts: Vec<Account> = stream::iter(0..args.accounts)
+        .map(|index| {
+            create_account(
+                &pds,
+                &chain,
+                &run,
+                domain,
+                index,
+                args.admin_password.as_deref(),
+            )
+        })
+        .buffer_unordered(args.concurrency)
+        .try_collect()
+        .await?;
+    println!(
+        "Created {} accounts in {:.1}s",
+        accounts.len(),
+        start.elapsed().as_secs_f64()
+    );
+
+    let traffic = Arc::new(Traffic {
+        pds: pds.clone(),
+        accounts: accounts.into_iter().map(tokio::sync::Mutex::new).collect(),
+        posts: Mutex::new(Vec::new()),
+        read_ratio: args.read_ratio.clamp(0.0, 1.0),
+        reply_ratio: args.reply_ratio.clamp(0.0, 1.0),
+        sections: args.sections,
+    });
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / args.rate));
+    let mut tasks = JoinSet::new();
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(args.duration);
+    while Instant::now() < deadline {
+        interval.tick().await;
+        let traffic = traffic.clone();
+        // failures are counted against their endpoint in the report
+        tasks.spawn(async move { traffic.step().await });
+    }
+    let mut ops = 0;
+    while let Some(result) = tasks.join_next().await {
+        if matches!(result, Ok(Ok(()))) {
+            ops += 1;
+        }
+    }
+    println!(
+        "{ops} operations succeeded in {:.1}s ({:.1}/s)\n",
+        start.elapsed().as_secs_f64(),
+        ops as f64 / start.elapsed().as_secs_f64()
+    );
+    print!("{}", stats.report());
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use crate::stats::Stats;
+use anyhow::{bail, Result};
+use reqwest::RequestBuilder;
+use rsky_lexicon::com::atproto::server::{
+    CreateInviteCodeInput, CreateInviteCodeOutput, DescribeServerOutput,
+};
+use rsky_lexicon::com::atproto::web5::{
+    CreateAccountInput, CreateAccountOutput, DirectWritesInput, DirectWritesInputRefWrite,
+    DirectWritesOutput, DirectWritesOutputRefWrite, PreCreateAccountInput, PreCreateAccountOutput,
+    PreDirectWritesInput, PreDirectWritesInputRefWrite, PreDirectWritesOutput, RefWriteCreate,
+    RefWriteCreateResult,
+};
+use rsky_web5_client::UnsignedRoot;
+use secp256k1::SecretKey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A synthetic account and the key its repo is signed with.
+pub struct Account {
+    pub did: String,
+    pub ckb_addr: String,
+    pub secret_key: SecretKey,
+    pub signing_key: String,
+    pub access_jwt: String,
+}
+
+/// XRPC calls to the PDS under load, each timed into `stats` under its method.
+#[derive(Clone)]
+pub struct Pds {
+    service: String,
+    http: reqwest::Client,
+    stats: Arc<Stats>,
+}
+
+impl Pds {
+    pub fn new(service: &str, stats: Arc<Stats>) -> Self {
+        Pds {
+            service: service.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            stats,
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    async fn call<O: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        req: RequestBuilder,
+    ) -> Result<O> {
+        let start = Instant::now();
+        let result = async {
+            let res = req.send().await?;
+            let status = res.status();
+            if !status.is_success() {
+                let body = res.text().await.unwrap_or_default();
+                bail!("{method} returned {status}: {body}");
+            }
+            Ok(res.json().await?)
+        }
+        .await;
+        self.stats.record(method, start.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn post<I: Serialize, O: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        input: &I,
+        access_jwt: Option<&str>,
+    ) -> Result<O> {
+        let mut req = self
+            .http
+            .post(format!("{}/xrpc/{method}", self.service))
+            .json(input);
+        if let Some(access_jwt) = access_jwt {
+            req = req.bearer_auth(access_jwt);
+        }
+        self.call(method, req).await
+    }
+
+    async fn get<O: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        query: &[(&str, &str)],
+    ) -> Result<O> {
+        let req = self
+            .http
+            .get(format!("{}/xrpc/{method}", self.service))
+            .query(query);
+        self.call(method, req).await
+    }
+
+    pub async fn describe_server(&self) -> Result<DescribeServerOutput> {
+        self.get("com.atproto.server.describeServer", &[]).await
+    }
+
+    pub async fn create_invite_code(&self, admin_password: &str) -> Result<String> {
+        let req = self
+            .http
+            .post(format!(
+                "{}/xrpc/com.atproto.server.createInviteCode",
+                self.service
+            ))
+            .basic_auth("admin", Some(admin_password))
+            .json(&CreateInviteCodeInput {
+                use_count: 1,
+                for_account: None,
+            });
+        let output: CreateInviteCodeOutput = self
+            .call("com.atproto.server.createInviteCode", req)
+            .await?;
+        Ok(output.code)
+    }
+
+    /// preCreateAccount then createAccount, signing the first commit with
+    /// `secret_key`. Returns the new session's access token.
+    pub async fn create_account(
+        &self,
+        did: &str,
+        handle: &str,
+        ckb_addr: &str,
+        secret_key: &SecretKey,
+        signing_key: &str,
+        invite_code: Option<String>,
+    ) -> Result<String> {
+        let unsigned: PreCreateAccountOutput = self
+            .post(
+                "com.atproto.web5.preCreateAccount",
+                &PreCreateAccountInput {
+                    handle: handle.to_string(),
+                    did: did.to_string(),
+                    signing_key: Some(signing_key.to_string()),
+                    invite_code: invite_code.clone(),
+                },
+                None,
+            )
+            .await?;
+        let root = UnsignedRoot::from(unsigned).sign_with_key(secret_key)?;
+        let output: CreateAccountOutput = self
+            .post(
+                "com.atproto.web5.createAccount",
+                &CreateAccountInput {
+                    handle: handle.to_string(),
+                    signing_key: signing_key.to_string(),
+                    password: None,
+                    root,
+                    ckb_addr: ckb_addr.to_string(),
+                    invite_code,
+                    captcha_token: None,
+                    email: None,
+                },
+                None,
+            )
+            .await?;
+        Ok(output.access_jwt)
+    }
+
+    /// preDirectWrites then directWrites for a single record create.
+    pub async fn create_record(
+        &self,
+        account: &Account,
+        write: RefWriteCreate,
+    ) -> Result<RefWriteCreateResult> {
+        let unsigned: PreDirectWritesOutput = self
+            .post(
+                "com.atproto.web5.preDirectWrites",
+                &PreDirectWritesInput {
+                    repo: account.did.clone(),
+                    validate: None,
+                    writes: vec![PreDirectWritesInputRefWrite::Create(write.clone())],
+                    swap_commit: None,
+                },
+                Some(&account.access_jwt),
+            )
+            .await?;
+        let root = UnsignedRoot::from(unsigned).sign_with_key(&account.secret_key)?;
+        let output: DirectWritesOutput = self
+            .post(
+                "com.atproto.web5.directWrites",
+                &DirectWritesInput {
+                    repo: account.did.clone(),
+                    validate: None,
+                    writes: vec![DirectWritesInputRefWrite::Create(write)],
+                    swap_commit: None,
+                    signing_key: account.signing_key.clone(),
+                    ckb_addr: Some(account.ckb_addr.clone()),
+                    root,
+                },
+                Some(&account.access_jwt),
+            )
+            .await?;
+        match output.results.unwrap_or_default().pop() {
+            Some(DirectWritesOutputRefWrite::Create(result)) => Ok(result),
+            other => bail!("expected a create result, got {other:?}"),
+        }
+    }
+
+    /// Reads a record back by its `at://` uri.
+    pub async fn get_record(&self, uri: &str) -> Result<Value> {
+        let Some([repo, collection, rkey]) = uri
+            .strip_prefix("at://")
+            .and_then(|path| path.splitn(3, '/').collect::<Vec<_>>().try_into().ok())
+        else {
+            bail!("not a record uri: {uri}");
+        };
+        self.get(
+            "com.atproto.repo.getRecord",
+            &[("repo", repo), ("collection", collection), ("rkey", rkey)],
+        )
+        .await
+    }
+}
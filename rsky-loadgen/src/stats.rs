@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Endpoint {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+/// Latencies of every XRPC call the load generator made, by method.
+#[derive(Debug, Default)]
+pub struct Stats {
+    endpoints: Mutex<BTreeMap<&'static str, Endpoint>>,
+}
+
+impl Stats {
+    /// Records a call; failed calls count as errors and their latency isn't kept.
+    pub fn record(&self, method: &'static str, latency: Duration, ok: bool) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let endpoint = endpoints.entry(method).or_default();
+        if ok {
+            endpoint.latencies.push(latency);
+        } else {
+            endpoint.errors += 1;
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let endpoints = self.endpoints.lock().unwrap();
+        Report(
+            endpoints
+                .iter()
+                .map(|(method, endpoint)| {
+                    let mut latencies = endpoint.latencies.clone();
+                    latencies.sort();
+                    EndpointReport {
+                        method,
+                        ok: latencies.len(),
+                        errors: endpoint.errors,
+                        p50: percentile(&latencies, 50.0),
+                        p99: percentile(&latencies, 99.0),
+                        max: latencies.last().copied(),
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies.
+pub fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+pub struct EndpointReport {
+    pub method: &'static str,
+    pub ok: usize,
+    pub errors: usize,
+    pub p50: Option<Duration>,
+    pub p99: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+pub struct Report(pub Vec<EndpointReport>);
+
+fn ms(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{:.1}", latency.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<40} {:>8} {:>8} {:>10} {:>10} {:>10}",
+            "endpoint", "ok", "errors", "p50 ms", "p99 ms", "max ms"
+        )?;
+        for endpoint in &self.0 {
+            writeln!(
+                f,
+                "{:<40} {:>8} {:>8} {:>10} {:>10} {:>10}",
+                endpoint.method,
+                endpoint.ok,
+                endpoint.errors,
+                ms(endpoint.p50),
+                ms(endpoint.p99),
+                ms(endpoint.max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_nearest_rank_percentiles() {
+        let latencies: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(
+            percentile(&latencies, 50.0),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(
+            percentile(&latencies, 99.0),
+            Some(Duration::from_millis(99))
+        );
+        assert_eq!(
+            percentile(&latencies[..1], 99.0),
+            Some(Duration::from_millis(1))
+        );
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn keeps_errors_out_of_latencies() {
+        let stats = Stats::default();
+        stats.record(
+            "com.atproto.web5.directWrites",
+            Duration::from_millis(5),
+            true,
+        );
+        stats.record(
+            "com.atproto.web5.directWrites",
+            Duration::from_secs(30),
+            false,
+        );
+        let report = stats.report();
+        assert_eq!(report.0.len(), 1);
+        assert_eq!(report.0[0].ok, 1);
+        assert_eq!(report.0[0].errors, 1);
+        assert_eq!(report.0[0].max, Some(Duration::from_millis(5)));
+    }
+}
@@ -0,0 +1,432 @@
+//! An in-process stand-in for the two CKB services rsky-pds reads DID cells
+//! through: the explorer's `referring_cells` API, which finds the DID cell of
+//! an address, and the node's `get_live_cell` RPC, which returns its data.
+//! Tests script which cells exist, then point the PDS at it with
+//! `PDS_CKB_EXPLORER_URL` and `PDS_CKB_RPC_URL`.
+
+use anyhow::{anyhow, Result};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use ckb_hash::blake2b_256;
+use ckb_jsonrpc_types::{
+    CellData, CellInfo, CellOutput, CellWithStatus, JsonBytes, OutPoint, Script, ScriptHashType,
+    Uint64,
+};
+use ckb_sdk::constants::SIGHASH_TYPE_HASH;
+use ckb_sdk::{Address, AddressPayload, NetworkType};
+use ckb_types::bytes::Bytes;
+use ckb_types::core::ScriptHashType as CoreScriptHashType;
+use ckb_types::packed;
+use ckb_types::prelude::Pack;
+use ckb_types::H256;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+/// Code hash of the DID cell type script on testnet
+const DID_TYPE_CODE_HASH: &str = "510150477b10d6ab551a509b71265f3164e9fd4137fcb5a4322f49f03092c7c5";
+
+/// The DID cell an address refers to. `data` is `None` once the cell has
+/// been consumed, which the explorer doesn't notice straight away.
+struct DidCell {
+    out_point: OutPoint,
+    lock: Script,
+    data: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct ChainState {
+    /// Keyed by lock script hash, the way the explorer is queried
+    cells: HashMap<String, DidCell>,
+    /// Answer every request with a 503
+    unavailable: bool,
+    transactions: u64,
+    explorer_requests: usize,
+    rpc_requests: usize,
+}
+
+type SharedChainState = Arc<Mutex<ChainState>>;
+
+/// A running mock chain. It stops when dropped.
+pub struct MockChain {
+    addr: SocketAddr,
+    state: SharedChainState,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockChain {
+    /// Serves the mock on a free port on localhost.
+    pub async fn start() -> Result<MockChain> {
+        MockChain::start_on(SocketAddr::from(([127, 0, 0, 1], 0))).await
+    }
+
+    /// Serves the mock on `addr`, for a PDS in another process that has to be
+    /// pointed at it before it starts.
+    pub async fn start_on(addr: SocketAddr) -> Result<MockChain> {
+        let state = SharedChainState::default();
+        let app = Router::new()
+            .route("/api/v2/scripts/referring_cells", get(referring_cells))
+            .route("/", post(rpc))
+            .with_state(state.clone());
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+        Ok(MockChain {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// What to set `PDS_CKB_EXPLORER_URL` to
+    pub fn explorer_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// What to set `PDS_CKB_RPC_URL` to
+    pub fn rpc_url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// The environment that points rsky-pds at this chain.
+    pub fn env(&self) -> [(&'static str, String); 2] {
+        [
+            ("PDS_CKB_EXPLORER_URL", self.explorer_url()),
+            ("PDS_CKB_RPC_URL", self.rpc_url()),
+        ]
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ChainState> {
+        self.state.lock().expect("mock chain state poisoned")
+    }
+
+    /// Publishes `document` in a new DID cell for `ckb_addr`, replacing any
+    /// it had, as a wallet updating its DID doc would.
+    pub fn put_did_doc<T: Serialize>(&self, ckb_addr: &str, document: &T) -> Result<OutPoint> {
+        self.put_cell_data(
+            ckb_addr,
+            encode_did_cell(&serde_ipld_dagcbor::to_vec(document)?),
+        )
+    }
+
+    /// Like `put_did_doc`, but with raw cell data, for testing malformed cells.
+    pub fn put_cell_data(&self, ckb_addr: &str, data: Vec<u8>) -> Result<OutPoint> {
+        let (address_hash, lock) = lock_script(ckb_addr)?;
+        let mut state = self.state();
+        state.transactions += 1;
+        let out_point = OutPoint {
+            tx_hash: H256(blake2b_256(state.transactions.to_le_bytes())),
+            index: 0u32.into(),
+        };
+        state.cells.insert(
+            address_hash,
+            DidCell {
+                out_point: out_point.clone(),
+                lock,
+                data: Some(data),
+            },
+        );
+        Ok(out_point)
+    }
+
+    /// Spends the DID cell of `ckb_addr`. The explorer still lists it, but
+    /// the node no longer has it live.
+    pub fn consume_cell(&self, ckb_addr: &str) -> Result<()> {
+        let (address_hash, _) = lock_script(ckb_addr)?;
+        match self.state().cells.get_mut(&address_hash) {
+            Some(cell) => {
+                cell.data = None;
+                Ok(())
+            }
+            None => Err(anyhow!("{ckb_addr} has no DID cell")),
+        }
+    }
+
+    /// Forgets the DID cell of `ckb_addr` entirely.
+    pub fn remove_cell(&self, ckb_addr: &str) -> Result<()> {
+        let (address_hash, _) = lock_script(ckb_addr)?;
+        self.state().cells.remove(&address_hash);
+        Ok(())
+    }
+
+    /// Makes both services fail until turned back off.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state().unavailable = unavailable;
+    }
+
+    pub fn explorer_requests(&self) -> usize {
+        self.state().explorer_requests
+    }
+
+    pub fn rpc_requests(&self) -> usize {
+        self.state().rpc_requests
+    }
+}
+
+impl Drop for MockChain {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// A testnet secp256k1-blake160 address whose lock args are `seed` repeated,
+/// so tests get distinct, valid addresses without keys.
+pub fn test_address(seed: u8) -> String {
+    address_with_args([seed; 20])
+}
+
+/// A testnet secp256k1-blake160 address with the given lock args, for when
+/// 256 `test_address` seeds aren't enough.
+pub fn address_with_args(args: [u8; 20]) -> String {
+    let payload = AddressPayload::new_full(
+        CoreScriptHashType::Type,
+        SIGHASH_TYPE_HASH.pack(),
+        Bytes::from(args.to_vec()),
+    );
+    Address::new(NetworkType::Testnet, payload, true).to_string()
+}
+
+/// A DID doc in the shape rsky-pds reads from DID cells.
+pub fn did_document(signing_key: &str, handle: &str, pds_endpoint: &str) -> Value {
+    json!({
+        "verificationMethods": { "atproto": signing_key },
+        "alsoKnownAs": [format!("at://{handle}")],
+        "services": {
+            "atproto_pds": {
+                "type": "AtprotoPersonalDataServer",
+                "endpoint": pds_endpoint,
+            }
+        },
+    })
+}
+
+/// Wraps a DAG-CBOR DID doc in the molecule `DidWeb5Data` union, as version 1
+/// with no local id.
+pub fn encode_did_cell(document: &[u8]) -> Vec<u8> {
+    // Table header: total size, then the offsets of `document` and `localId`
+    let header_len = 4 * 3;
+    let document_len = 4 + document.len();
+    let total_len = header_len + document_len;
+    let mut data = Vec::with_capacity(4 + total_len);
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&(total_len as u32).to_le_bytes());
+    data.extend_from_slice(&(header_len as u32).to_le_bytes());
+    data.extend_from_slice(&((header_len + document_len) as u32).to_le_bytes());
+    data.extend_from_slice(&(document.len() as u32).to_le_bytes());
+    data.extend_from_slice(document);
+    data
+}
+
+/// Lock script hash of `ckb_addr`, as the explorer is queried with, and the
+/// lock script itself.
+fn lock_script(ckb_addr: &str) -> Result<(String, Script)> {
+    let addr = Address::from_str(ckb_addr).map_err(|error| anyhow!("{ckb_addr}: {error}"))?;
+    let script: packed::Script = (&addr).into();
+    let address_hash = format!("0x{}", hex::encode(script.calc_script_hash().raw_data()));
+    Ok((address_hash, script.into()))
+}
+
+fn unavailable() -> Response {
+    (StatusCode::SERVICE_UNAVAILABLE, "mock chain unavailable").into_response()
+}
+
+#[derive(Deserialize)]
+struct ReferringCellsQuery {
+    address_hash: String,
+}
+
+async fn referring_cells(
+    State(state): State<SharedChainState>,
+    Query(query): Query<ReferringCellsQuery>,
+) -> Response {
+    let mut state = state.lock().expect("mock chain state poisoned");
+    state.explorer_requests += 1;
+    if state.unavailable {
+        return unavailable();
+    }
+    let referring_cells: Vec<Value> = match state.cells.get(&query.address_hash) {
+        Some(cell) => vec![json!({
+            "tx_hash": cell.out_point.tx_hash,
+            "cell_index": cell.out_point.index.value(),
+            "status": if cell.data.is_some() { "live" } else { "dead" },
+        })],
+        None => vec![],
+    };
+    Json(json!({
+        "data": {
+            "referring_cells": referring_cells,
+            "meta": { "total": referring_cells.len(), "page_size": 1 },
+        }
+    }))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+fn rpc_result(id: Value, result: Result<Value, (i64, String)>) -> Json<Value> {
+    Json(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => {
+            json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+        }
+    })
+}
+
+async fn rpc(State(state): State<SharedChainState>, Json(request): Json<RpcRequest>) -> Response {
+    let mut state = state.lock().expect("mock chain state poisoned");
+    state.rpc_requests += 1;
+    if state.unavailable {
+        return unavailable();
+    }
+    let result = match request.method.as_str() {
+        "get_tip_block_number" => Ok(json!(Uint64::from(state.transactions))),
+        "get_live_cell" => get_live_cell(&state, &request.params),
+        method => Err((-32601, format!("Method not found: {method}"))),
+    };
+    rpc_result(request.id, result).into_response()
+}
+
+fn get_live_cell(state: &ChainState, params: &[Value]) -> Result<Value, (i64, String)> {
+    let out_point: OutPoint = params
+        .first()
+        .cloned()
+        .and_then(|out_point| serde_json::from_value(out_point).ok())
+        .ok_or((-32602, "Invalid params: expected an out point".to_string()))?;
+    let with_data = params.get(1).and_then(Value::as_bool).unwrap_or(false);
+    let cell = state
+        .cells
+        .values()
+        .find(|cell| cell.out_point == out_point)
+        .and_then(|cell| Some((cell, cell.data.as_ref()?)));
+    let status = match cell {
+        Some((cell, data)) => CellWithStatus {
+            cell: Some(CellInfo {
+                output: CellOutput {
+                    capacity: Uint64::from((data.len() as u64 + 200) * 100_000_000),
+                    lock: cell.lock.clone(),
+                    type_: Some(Script {
+                        code_hash: H256::from_str(DID_TYPE_CODE_HASH).expect("valid code hash"),
+                        hash_type: ScriptHashType::Type,
+                        args: JsonBytes::from_vec(cell.out_point.tx_hash.0[..20].to_vec()),
+                    }),
+                },
+                data: with_data.then(|| CellData {
+                    content: JsonBytes::from_vec(data.clone()),
+                    hash: H256(blake2b_256(data)),
+                }),
+            }),
+            status: "live".to_string(),
+        },
+        None => CellWithStatus {
+            cell: None,
+            status: "unknown".to_string(),
+        },
+    };
+    serde_json::to_value(status).map_err(|error| (-32603, error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ckb_sdk::CkbRpcAsyncClient;
+    use std::collections::BTreeMap;
+
+    async fn referring_cells(chain: &MockChain, ckb_addr: &str) -> Vec<Value> {
+        let (address_hash, _) = lock_script(ckb_addr).unwrap();
+        let response: Value = reqwest::get(format!(
+            "{}/api/v2/scripts/referring_cells?address_hash={address_hash}&page=1&page_size=1",
+            chain.explorer_url()
+        ))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+        response["data"]["referring_cells"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn serves_scripted_did_cells() {
+        let chain = MockChain::start().await.unwrap();
+        let alice = test_address(1);
+        assert!(referring_cells(&chain, &alice).await.is_empty());
+
+        let document = did_document("did:key:zAlice", "alice.test", "https://pds.test");
+        let out_point = chain.put_did_doc(&alice, &document).unwrap();
+        let cells = referring_cells(&chain, &alice).await;
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0]["cell_index"], 0);
+
+        let client = CkbRpcAsyncClient::new(&chain.rpc_url());
+        let cell = client.get_live_cell(out_point.clone(), true).await.unwrap();
+        let content = cell.cell.unwrap().data.unwrap().content;
+        let expected = encode_did_cell(&serde_ipld_dagcbor::to_vec(&document).unwrap());
+        assert_eq!(content.as_bytes(), &expected[..]);
+
+        chain.consume_cell(&alice).unwrap();
+        assert_eq!(referring_cells(&chain, &alice).await.len(), 1);
+        let cell = client.get_live_cell(out_point, true).await.unwrap();
+        assert!(cell.cell.is_none());
+        assert_eq!(chain.rpc_requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn fails_while_unavailable() {
+        let chain = MockChain::start().await.unwrap();
+        chain.set_unavailable(true);
+        let response = reqwest::get(format!(
+            "{}/api/v2/scripts/referring_cells?address_hash=0x00",
+            chain.explorer_url()
+        ))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let client = CkbRpcAsyncClient::new(&chain.rpc_url());
+        assert!(client.get_tip_block_number().await.is_err());
+    }
+
+    #[test]
+    fn encodes_did_cells_as_molecule() {
+        let data = encode_did_cell(&[0xa0]);
+        // Union id 0, table of 17 bytes, document at 12, empty localId at 17
+        assert_eq!(
+            data,
+            [
+                vec![0, 0, 0, 0, 17, 0, 0, 0, 12, 0, 0, 0, 17, 0, 0, 0],
+                vec![1, 0, 0, 0, 0xa0],
+            ]
+            .concat()
+        );
+        let doc: BTreeMap<String, Value> = BTreeMap::new();
+        assert_eq!(
+            encode_did_cell(&serde_ipld_dagcbor::to_vec(&doc).unwrap()),
+            data
+        );
+    }
+}
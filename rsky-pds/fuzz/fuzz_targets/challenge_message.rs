@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rsky_lexicon::com::atproto::web5::{
+    IndexActionInputRef, RefCreateSessionIndex, RefDeleteAccountIndex, RefStepUpIndex,
+};
+use rsky_pds::plc::web5_types::{extract_timestamp, statement_check};
+
+#[derive(Debug, Arbitrary)]
+enum Index {
+    CreateSession,
+    DeleteAccount,
+    StepUp(String),
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    message: String,
+    index: Index,
+}
+
+// The message an indexAction caller sends back is whatever their wallet signed
+fuzz_target!(|input: Input| {
+    let _ = extract_timestamp(&input.message);
+    let index = match input.index {
+        Index::CreateSession => IndexActionInputRef::CreateSessionIndex(RefCreateSessionIndex {}),
+        Index::DeleteAccount => IndexActionInputRef::DeleteAccountIndex(RefDeleteAccountIndex {}),
+        Index::StepUp(lxm) => IndexActionInputRef::StepUpIndex(RefStepUpIndex { lxm }),
+    };
+    let _ = statement_check(&input.message, &index);
+});
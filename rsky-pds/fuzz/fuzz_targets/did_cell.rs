@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rsky_pds::plc::web5_types::decode_did_document;
+
+// Anyone can put anything in a cell the explorer hands back for an address
+fuzz_target!(|cell_data: &[u8]| {
+    let _ = decode_did_document(cell_data);
+});
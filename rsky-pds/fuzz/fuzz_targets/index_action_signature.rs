@@ -0,0 +1,25 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rsky_pds::plc::web5_types::{decode_signed_bytes, verify_ckb_message_signature};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    message: String,
+    signing_key: String,
+    signed_bytes: String,
+    ckb_addr: String,
+}
+
+// Everything indexAction and the CKB wallet rebind check do with the caller's
+// key, address and signature before trusting them
+fuzz_target!(|input: Input| {
+    let hash = Sha256::digest(&input.message);
+    if let Ok(sig) = decode_signed_bytes(&input.signed_bytes) {
+        let _ =
+            rsky_crypto::verify::verify_signature(&input.signing_key, hash.as_ref(), &sig, None);
+        let _ = verify_ckb_message_signature(&input.ckb_addr, &input.message, &sig);
+    }
+});
@@ -1,5 +1,4 @@
 -- This file should undo anything in `up.sql`
 ALTER TABLE pds.actor
-    DROP COLUMN IF EXISTS deactivatedAt,
-    DROP COLUMN IF EXISTS deleteAfter,
-    DROP COLUMN IF EXISTS ckbAddress;
\ No newline at end of file
+    DROP COLUMN IF EXISTS "deactivatedAt",
+    DROP COLUMN IF EXISTS "deleteAfter";
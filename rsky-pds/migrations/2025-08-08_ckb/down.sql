@@ -0,0 +1 @@
+ALTER TABLE pds.actor DROP COLUMN IF EXISTS "ckbAddress";
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS pds.record_collection_indexed_at_idx;
+DROP TABLE IF EXISTS pds.bbs_section_stats;
+DROP TABLE IF EXISTS pds.bbs_stats;
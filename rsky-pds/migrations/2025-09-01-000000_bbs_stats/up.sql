@@ -0,0 +1,22 @@
+-- Sitewide BBS statistics, refreshed by the periodic aggregation job
+CREATE TABLE IF NOT EXISTS pds.bbs_stats (
+    id integer PRIMARY KEY,
+    "totalPosts" bigint NOT NULL DEFAULT 0,
+    "totalReplies" bigint NOT NULL DEFAULT 0,
+    "activeUsers7d" bigint NOT NULL DEFAULT 0,
+    "activeUsers30d" bigint NOT NULL DEFAULT 0,
+    "computedAt" character varying NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS pds.bbs_section_stats (
+    "sectionId" bigint PRIMARY KEY,
+    "totalPosts" bigint NOT NULL DEFAULT 0,
+    "totalReplies" bigint NOT NULL DEFAULT 0,
+    "posts7d" bigint NOT NULL DEFAULT 0,
+    "posts30d" bigint NOT NULL DEFAULT 0,
+    "lastActivityAt" character varying,
+    "computedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS record_collection_indexed_at_idx
+    ON pds.record (collection, "indexedAt");
@@ -0,0 +1,11 @@
+-- Orphaned blobs waiting out the grace period before being deleted
+CREATE TABLE IF NOT EXISTS pds.blob_gc (
+    cid character varying NOT NULL,
+    did character varying NOT NULL,
+    size integer NOT NULL,
+    "quarantinedAt" character varying NOT NULL,
+    CONSTRAINT blob_gc_pkey PRIMARY KEY (cid, did)
+);
+
+CREATE INDEX IF NOT EXISTS blob_gc_quarantined_at_idx
+    ON pds.blob_gc ("quarantinedAt");
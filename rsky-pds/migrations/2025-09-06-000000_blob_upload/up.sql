@@ -0,0 +1,19 @@
+-- In-progress resumable blob uploads, chunks are appended to content until finalized
+CREATE TABLE IF NOT EXISTS pds.blob_upload (
+    id character varying NOT NULL,
+    did character varying NOT NULL,
+    "mimeType" character varying NOT NULL,
+    size integer NOT NULL,
+    "bytesReceived" integer NOT NULL DEFAULT 0,
+    content bytea NOT NULL DEFAULT '',
+    "expectedCid" character varying,
+    "createdAt" character varying NOT NULL,
+    "expiresAt" character varying NOT NULL,
+    CONSTRAINT blob_upload_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS blob_upload_did_idx
+    ON pds.blob_upload (did);
+
+CREATE INDEX IF NOT EXISTS blob_upload_expires_at_idx
+    ON pds.blob_upload ("expiresAt");
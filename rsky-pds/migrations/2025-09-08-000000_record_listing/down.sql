@@ -0,0 +1 @@
+DROP INDEX IF EXISTS pds.record_did_collection_rkey_idx;
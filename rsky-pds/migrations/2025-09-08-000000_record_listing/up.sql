@@ -0,0 +1,4 @@
+-- Serves listRecords for a single collection or a collection prefix (e.g. app.bbs.*)
+-- straight from the index, ordered by rkey
+CREATE INDEX IF NOT EXISTS record_did_collection_rkey_idx
+    ON pds.record (did, collection varchar_pattern_ops, rkey);
@@ -0,0 +1,7 @@
+-- Last event delivered to each named firehose subscriber, so a consumer can
+-- resume where it left off after either side restarts
+CREATE TABLE IF NOT EXISTS pds.subscriber_cursor (
+    id character varying PRIMARY KEY,
+    cursor bigint NOT NULL,
+    "updatedAt" character varying NOT NULL
+);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS pds.record_collection_did_idx;
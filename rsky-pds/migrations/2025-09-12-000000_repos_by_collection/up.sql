@@ -0,0 +1,3 @@
+-- Serves listReposByCollection, which walks the dids holding a collection in order
+CREATE INDEX IF NOT EXISTS record_collection_did_idx
+    ON pds.record (collection, did);
@@ -0,0 +1,28 @@
+-- Operator registered endpoints that receive matching repo events, each with
+-- its own position in repo_seq and retry state for the event at that position
+CREATE TABLE IF NOT EXISTS pds.webhook (
+    id character varying PRIMARY KEY,
+    url character varying NOT NULL,
+    secret character varying NOT NULL,
+    collections text[] NOT NULL DEFAULT '{}',
+    dids text[] NOT NULL DEFAULT '{}',
+    cursor bigint NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    "nextAttemptAt" character varying,
+    "lastError" character varying,
+    "createdAt" character varying NOT NULL
+);
+
+-- Deliveries that were given up on after exhausting their retries
+CREATE TABLE IF NOT EXISTS pds.webhook_dead_letter (
+    id bigserial PRIMARY KEY,
+    "webhookId" character varying NOT NULL REFERENCES pds.webhook (id) ON DELETE CASCADE,
+    seq bigint NOT NULL,
+    payload character varying NOT NULL,
+    attempts integer NOT NULL,
+    "lastError" character varying NOT NULL,
+    "failedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS webhook_dead_letter_webhook_id_idx
+    ON pds.webhook_dead_letter ("webhookId", seq);
@@ -0,0 +1,15 @@
+-- Moderation labels issued by this service, seq doubles as the subscribeLabels cursor
+CREATE TABLE IF NOT EXISTS pds.label (
+    seq bigserial PRIMARY KEY,
+    src character varying NOT NULL,
+    uri character varying NOT NULL,
+    cid character varying,
+    val character varying NOT NULL,
+    neg boolean NOT NULL DEFAULT false,
+    cts character varying NOT NULL,
+    exp character varying,
+    sig bytea NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS label_uri_idx
+    ON pds.label (uri varchar_pattern_ops, seq);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS pds.oauth_session;
+DROP TABLE IF EXISTS pds.oauth_request;
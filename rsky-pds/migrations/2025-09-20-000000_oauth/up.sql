@@ -0,0 +1,31 @@
+-- Pushed authorization requests, kept from PAR until their code is exchanged
+CREATE TABLE IF NOT EXISTS pds.oauth_request (
+    id character varying PRIMARY KEY,
+    "clientId" character varying NOT NULL,
+    "redirectUri" character varying NOT NULL,
+    scope character varying NOT NULL,
+    state character varying,
+    "codeChallenge" character varying NOT NULL,
+    "loginHint" character varying,
+    "dpopJkt" character varying NOT NULL,
+    did character varying,
+    code character varying UNIQUE,
+    "expiresAt" character varying NOT NULL
+);
+
+-- One row per OAuth session, keyed by the current refresh token of the family
+CREATE TABLE IF NOT EXISTS pds.oauth_session (
+    "refreshTokenId" character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    "clientId" character varying NOT NULL,
+    scope character varying NOT NULL,
+    "dpopJkt" character varying NOT NULL,
+    "accessTokenHash" character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "updatedAt" character varying NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS oauth_session_access_token_hash_idx
+    ON pds.oauth_session ("accessTokenHash");
+CREATE INDEX IF NOT EXISTS oauth_session_did_idx
+    ON pds.oauth_session (did);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS pds.revoked_session;
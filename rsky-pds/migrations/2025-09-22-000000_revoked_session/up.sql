@@ -0,0 +1,10 @@
+-- Explicitly revoked sessions, keyed by the refresh token id their access tokens
+-- carry as jti. Rows are kept until those access tokens have expired.
+CREATE TABLE IF NOT EXISTS pds.revoked_session (
+    id character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    "expiresAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS revoked_session_expires_at_idx
+    ON pds.revoked_session ("expiresAt");
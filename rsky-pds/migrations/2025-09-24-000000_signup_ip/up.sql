@@ -0,0 +1,10 @@
+-- Address each account was created from, to cap signups per ip. Rows are only
+-- needed for a day and are pruned as new accounts come in.
+CREATE TABLE IF NOT EXISTS pds.signup_ip (
+    did character varying PRIMARY KEY,
+    ip character varying NOT NULL,
+    "createdAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS signup_ip_ip_created_at_idx
+    ON pds.signup_ip (ip, "createdAt");
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS pds.email_notification_pref;
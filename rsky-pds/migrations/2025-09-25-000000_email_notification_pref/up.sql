@@ -0,0 +1,8 @@
+-- Opt-outs from BBS activity emails. Accounts without a row get every
+-- notification.
+CREATE TABLE IF NOT EXISTS pds.email_notification_pref (
+    did character varying PRIMARY KEY,
+    replies boolean NOT NULL DEFAULT true,
+    mentions boolean NOT NULL DEFAULT true,
+    "updatedAt" character varying NOT NULL
+);
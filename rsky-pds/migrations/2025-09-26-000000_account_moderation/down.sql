@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS pds.audit_log;
+ALTER TABLE pds.actor DROP COLUMN IF EXISTS "suspendedUntil";
@@ -0,0 +1,22 @@
+-- A suspension is a takedown that is lifted once this passes
+ALTER TABLE pds.actor ADD COLUMN IF NOT EXISTS "suspendedUntil" character varying;
+
+-- Append-only record of identity and moderation actions. Subjects are kept as
+-- plain values so entries outlive the accounts and records they refer to.
+CREATE TABLE IF NOT EXISTS pds.audit_log (
+    id bigserial PRIMARY KEY,
+    action character varying NOT NULL,
+    -- Who acted, unset for the admin password and the PDS itself
+    "actorDid" character varying,
+    "subjectDid" character varying NOT NULL,
+    "subjectUri" character varying,
+    "subjectCid" character varying,
+    -- JSON with action specific details
+    detail character varying,
+    "createdAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_subject_did_created_at_idx
+    ON pds.audit_log ("subjectDid", "createdAt");
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx
+    ON pds.audit_log ("createdAt");
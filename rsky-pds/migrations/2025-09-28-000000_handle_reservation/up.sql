@@ -0,0 +1,13 @@
+-- Handles claimed by an on-chain DID doc before an account with them exists
+-- here. Only the chain key holder can sign up with the handle until it expires.
+CREATE TABLE IF NOT EXISTS pds.handle_reservation (
+    handle character varying PRIMARY KEY,
+    "ckbAddress" character varying NOT NULL,
+    -- did:key from the doc's atproto verification method
+    "signingKey" character varying NOT NULL,
+    "reservedAt" character varying NOT NULL,
+    "expiresAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS handle_reservation_expires_at_idx
+    ON pds.handle_reservation ("expiresAt");
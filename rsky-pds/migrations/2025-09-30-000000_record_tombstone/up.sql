@@ -0,0 +1,19 @@
+-- Records deleted through directWrites, kept with their last block after the
+-- commit removes them from the repo so the owner can undo the delete and
+-- moderators can review what was there. Purged once the retention window
+-- passes.
+CREATE TABLE IF NOT EXISTS pds.record_tombstone (
+    uri character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    cid character varying NOT NULL,
+    -- the record's DAG-CBOR block, no longer in repo_block
+    content bytea NOT NULL,
+    -- rev of the commit that deleted the record
+    "repoRev" character varying NOT NULL,
+    "deletedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS record_tombstone_did_idx
+    ON pds.record_tombstone (did);
+CREATE INDEX IF NOT EXISTS record_tombstone_deleted_at_idx
+    ON pds.record_tombstone ("deletedAt");
@@ -0,0 +1 @@
+ALTER TABLE pds.repo_root DROP COLUMN IF EXISTS "issuedRev";
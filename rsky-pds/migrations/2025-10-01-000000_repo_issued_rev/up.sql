@@ -0,0 +1,4 @@
+-- The last rev handed to a client in an unsigned commit. Revs issued for the
+-- next commit start after both this and the head's rev, so they keep
+-- increasing across concurrent pre* calls and a clock that has gone backwards.
+ALTER TABLE pds.repo_root ADD COLUMN IF NOT EXISTS "issuedRev" character varying;
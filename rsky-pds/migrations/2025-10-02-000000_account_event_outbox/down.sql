@@ -0,0 +1 @@
+DROP TABLE IF EXISTS pds.account_event_outbox;
@@ -0,0 +1,16 @@
+-- Account and identity events waiting to be handled by each registered
+-- handler. A row is only deleted once its handler succeeds, so an event that
+-- was sequenced is handled at least once even across crashes and restarts.
+CREATE TABLE IF NOT EXISTS pds.account_event_outbox (
+    id bigserial PRIMARY KEY,
+    handler character varying NOT NULL,
+    did character varying NOT NULL,
+    event character varying NOT NULL,
+    attempts integer NOT NULL DEFAULT 0,
+    "nextAttemptAt" character varying NOT NULL,
+    "lastError" character varying,
+    "createdAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS account_event_outbox_next_attempt_at_idx
+    ON pds.account_event_outbox ("nextAttemptAt", id);
@@ -0,0 +1 @@
+ALTER TABLE pds.account DROP COLUMN IF EXISTS "passwordLogin";
@@ -0,0 +1,4 @@
+-- Web5 accounts sign in with their wallet and get a random password they never
+-- see. Once they set one with com.atproto.web5.setPassword, this lets them
+-- sign in with it through createSession as well. Other accounts ignore it.
+ALTER TABLE pds.account ADD COLUMN IF NOT EXISTS "passwordLogin" boolean NOT NULL DEFAULT false;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS pds.rotated_refresh_token;
+DROP INDEX IF EXISTS pds.refresh_token_family_id_idx;
+ALTER TABLE pds.refresh_token DROP COLUMN IF EXISTS "familyId";
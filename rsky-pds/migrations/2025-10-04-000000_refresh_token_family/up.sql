@@ -0,0 +1,19 @@
+-- Refresh tokens descended from the same login share a family, named by the
+-- first token's id. Tokens from before this have none and are their own family.
+ALTER TABLE pds.refresh_token ADD COLUMN IF NOT EXISTS "familyId" character varying;
+
+-- Refresh token ids that have been rotated, kept until the token would have
+-- expired so a replay is caught even after its grace period row is gone.
+CREATE TABLE IF NOT EXISTS pds.rotated_refresh_token (
+    id character varying PRIMARY KEY,
+    "familyId" character varying NOT NULL,
+    did character varying NOT NULL,
+    "expiresAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS refresh_token_family_id_idx
+    ON pds.refresh_token ("familyId");
+CREATE INDEX IF NOT EXISTS rotated_refresh_token_family_id_idx
+    ON pds.rotated_refresh_token ("familyId");
+CREATE INDEX IF NOT EXISTS rotated_refresh_token_did_expires_at_idx
+    ON pds.rotated_refresh_token (did, "expiresAt");
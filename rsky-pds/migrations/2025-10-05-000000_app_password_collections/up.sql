@@ -0,0 +1,3 @@
+-- Collections an app password may write to, comma separated exact NSIDs or
+-- `prefix.*` patterns such as `app.bbs.*`. Unset means any collection.
+ALTER TABLE pds.app_password ADD COLUMN IF NOT EXISTS "collections" character varying;
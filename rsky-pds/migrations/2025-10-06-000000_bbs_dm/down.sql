@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS pds.bbs_dm_message;
+DROP TABLE IF EXISTS pds.bbs_dm_member;
+DROP TABLE IF EXISTS pds.bbs_dm_convo;
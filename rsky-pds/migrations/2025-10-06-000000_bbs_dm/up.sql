@@ -0,0 +1,27 @@
+-- Direct messages between accounts on this PDS. They live here rather than in
+-- the senders' repos, so they're never published or relayed.
+CREATE TABLE IF NOT EXISTS pds.bbs_dm_convo (
+    id character varying PRIMARY KEY,
+    "createdAt" character varying NOT NULL,
+    "lastMessageAt" character varying
+);
+
+-- Who may read and post in a conversation, and how far each member has read
+CREATE TABLE IF NOT EXISTS pds.bbs_dm_member (
+    "convoId" character varying NOT NULL REFERENCES pds.bbs_dm_convo (id) ON DELETE CASCADE,
+    did character varying NOT NULL,
+    "lastReadId" bigint,
+    PRIMARY KEY ("convoId", did)
+);
+
+CREATE TABLE IF NOT EXISTS pds.bbs_dm_message (
+    id bigserial PRIMARY KEY,
+    "convoId" character varying NOT NULL REFERENCES pds.bbs_dm_convo (id) ON DELETE CASCADE,
+    "senderDid" character varying NOT NULL,
+    text character varying NOT NULL,
+    "createdAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_dm_member_did_idx ON pds.bbs_dm_member (did);
+CREATE INDEX IF NOT EXISTS bbs_dm_message_convo_id_idx ON pds.bbs_dm_message ("convoId", id);
+CREATE INDEX IF NOT EXISTS bbs_dm_message_sender_did_idx ON pds.bbs_dm_message ("senderDid");
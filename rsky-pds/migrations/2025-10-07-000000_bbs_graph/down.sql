@@ -0,0 +1 @@
+DROP TABLE IF EXISTS pds.bbs_graph;
@@ -0,0 +1,11 @@
+-- Blocks and mutes written by accounts on this PDS, indexed from their
+-- app.bbs.graph.* records so reads can filter without loading records.
+CREATE TABLE IF NOT EXISTS pds.bbs_graph (
+    uri character varying PRIMARY KEY,
+    collection character varying NOT NULL,
+    "creatorDid" character varying NOT NULL,
+    "subjectDid" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_graph_creator_did_idx ON pds.bbs_graph ("creatorDid", collection);
+CREATE INDEX IF NOT EXISTS bbs_graph_subject_did_idx ON pds.bbs_graph ("subjectDid", collection);
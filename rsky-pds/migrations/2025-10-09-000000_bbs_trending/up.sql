@@ -0,0 +1,21 @@
+-- Snapshot of recent threads ranked by hot score, rebuilt by the trending job
+-- so app.bbs.getTrending only reads one small table
+CREATE TABLE IF NOT EXISTS pds.bbs_trending (
+    uri character varying PRIMARY KEY,
+    cid character varying NOT NULL,
+    did character varying NOT NULL,
+    "sectionId" bigint NOT NULL,
+    title character varying NOT NULL,
+    text character varying NOT NULL,
+    -- 1 for the hottest thread on the PDS
+    rank bigint NOT NULL,
+    "hotScore" double precision NOT NULL,
+    "voteScore" bigint NOT NULL,
+    "replyCount" bigint NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "lastActivityAt" character varying NOT NULL,
+    "computedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_trending_rank_idx ON pds.bbs_trending (rank);
+CREATE INDEX IF NOT EXISTS bbs_trending_section_rank_idx ON pds.bbs_trending ("sectionId", rank);
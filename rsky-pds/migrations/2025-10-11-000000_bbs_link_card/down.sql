@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS pds.bbs_link_card;
+DROP TABLE IF EXISTS pds.bbs_post_link;
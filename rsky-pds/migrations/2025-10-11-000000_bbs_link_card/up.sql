@@ -0,0 +1,25 @@
+-- The link each app.bbs.post on this PDS unfurls into a card: the first
+-- link facet of the post.
+CREATE TABLE IF NOT EXISTS pds.bbs_post_link (
+    uri character varying PRIMARY KEY,
+    url character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_post_link_url_idx ON pds.bbs_post_link (url);
+
+-- OpenGraph metadata fetched for those links, shared by every post linking
+-- the same URL. Failed fetches are retried until "nextAttemptAt" is null.
+CREATE TABLE IF NOT EXISTS pds.bbs_link_card (
+    url character varying PRIMARY KEY,
+    status character varying NOT NULL,
+    title character varying,
+    description character varying,
+    "thumbCid" character varying,
+    thumb bytea,
+    attempts integer NOT NULL DEFAULT 0,
+    "nextAttemptAt" character varying,
+    "fetchedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_link_card_next_attempt_idx ON pds.bbs_link_card ("nextAttemptAt");
+CREATE INDEX IF NOT EXISTS bbs_link_card_thumb_cid_idx ON pds.bbs_link_card ("thumbCid");
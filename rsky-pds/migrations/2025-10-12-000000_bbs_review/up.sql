@@ -0,0 +1,15 @@
+-- BBS posts and replies held for a moderator to look at, e.g. because a
+-- content classifier flagged them. One entry per record, the first reason
+-- it was flagged for wins.
+CREATE TABLE IF NOT EXISTS pds.bbs_review (
+    uri character varying PRIMARY KEY,
+    cid character varying NOT NULL,
+    did character varying NOT NULL,
+    -- What flagged the record, e.g. `heuristic` or `http`
+    source character varying NOT NULL,
+    reason character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "resolvedAt" character varying
+);
+
+CREATE INDEX IF NOT EXISTS bbs_review_pending_idx ON pds.bbs_review ("createdAt") WHERE "resolvedAt" IS NULL;
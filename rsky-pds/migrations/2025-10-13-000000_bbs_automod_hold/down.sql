@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS pds.bbs_review_held_idx;
+ALTER TABLE pds.bbs_review DROP COLUMN IF EXISTS held;
@@ -0,0 +1,5 @@
+-- Posts a section's automoderation rules held back are kept out of reads
+-- until a moderator resolves their review entry.
+ALTER TABLE pds.bbs_review ADD COLUMN IF NOT EXISTS held boolean NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS bbs_review_held_idx ON pds.bbs_review (uri) WHERE held AND "resolvedAt" IS NULL;
@@ -0,0 +1,8 @@
+-- Moderator-set state of a BBS section. Sections without a row are open.
+CREATE TABLE IF NOT EXISTS pds.bbs_section (
+    "sectionId" bigint PRIMARY KEY,
+    -- `open` or `archived`, archived sections take no new posts or replies
+    status character varying NOT NULL,
+    "updatedAt" character varying NOT NULL,
+    "updatedBy" character varying NOT NULL
+);
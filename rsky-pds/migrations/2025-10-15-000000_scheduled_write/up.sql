@@ -0,0 +1,25 @@
+-- Signed directWrites batches held until their publish time. The commit was
+-- signed over the repo head at scheduling time, so publishing fails if
+-- anything else was written to the repo in between.
+CREATE TABLE IF NOT EXISTS pds.scheduled_write (
+    id bigserial PRIMARY KEY,
+    did character varying NOT NULL,
+    -- the com.atproto.web5.directWrites input, as JSON
+    input character varying NOT NULL,
+    "publishAt" character varying NOT NULL,
+    -- `pending`, `published`, `failed` or `canceled`
+    status character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    -- set while a replica is publishing the row, so others leave it alone
+    "claimedUntil" character varying,
+    "commitCid" character varying,
+    "commitRev" character varying,
+    error character varying,
+    "finishedAt" character varying
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_write_pending_idx
+    ON pds.scheduled_write ("publishAt", id) WHERE status = 'pending';
+
+CREATE INDEX IF NOT EXISTS scheduled_write_did_idx
+    ON pds.scheduled_write (did, "publishAt", id);
@@ -0,0 +1,15 @@
+-- Unfinished posts and replies, kept for their author outside the repo so
+-- they're never signed, published or relayed.
+CREATE TABLE IF NOT EXISTS pds.bbs_draft (
+    id bigserial PRIMARY KEY,
+    did character varying NOT NULL,
+    -- the collection the draft will be published to
+    collection character varying NOT NULL,
+    -- the record as composed so far, as JSON
+    value character varying NOT NULL,
+    "createdAt" character varying NOT NULL,
+    "updatedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_draft_did_updated_at_idx
+    ON pds.bbs_draft (did, "updatedAt", id);
@@ -0,0 +1,19 @@
+-- How far each account has read each thread, so unread counts agree across
+-- their devices. Kept outside the repo, like drafts.
+CREATE TABLE IF NOT EXISTS pds.bbs_read_state (
+    did character varying NOT NULL,
+    -- uri of the thread's root post
+    thread character varying NOT NULL,
+    -- replies the account had seen when it last read the thread
+    "readReplyCount" bigint NOT NULL,
+    -- createdAt of the newest reply the account had seen
+    "lastReadAt" character varying NOT NULL,
+    "updatedAt" character varying NOT NULL,
+    PRIMARY KEY (did, thread)
+);
+
+-- for trimming an account's oldest entries and compacting stale ones
+CREATE INDEX IF NOT EXISTS bbs_read_state_did_updated_at_idx
+    ON pds.bbs_read_state (did, "updatedAt");
+CREATE INDEX IF NOT EXISTS bbs_read_state_updated_at_idx
+    ON pds.bbs_read_state ("updatedAt");
@@ -0,0 +1,12 @@
+-- Threads accounts on this PDS watch or mute, indexed from their
+-- app.bbs.watchThread records so the email notifier can look them up.
+CREATE TABLE IF NOT EXISTS pds.bbs_thread_watch (
+    uri character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    -- uri of the thread's root post
+    thread character varying NOT NULL,
+    muted boolean NOT NULL DEFAULT false
+);
+
+CREATE INDEX IF NOT EXISTS bbs_thread_watch_thread_idx ON pds.bbs_thread_watch (thread);
+CREATE INDEX IF NOT EXISTS bbs_thread_watch_did_idx ON pds.bbs_thread_watch (did);
@@ -0,0 +1,11 @@
+-- BBS posts the bridge mirrored to app.bsky.feed.post. A row is claimed
+-- before the mirror is written, so a post is mirrored at most once.
+CREATE TABLE IF NOT EXISTS pds.bbs_crosspost (
+    uri character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    -- uri of the app.bsky.feed.post, null until it's written
+    "mirrorUri" character varying,
+    "createdAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_crosspost_did_idx ON pds.bbs_crosspost (did);
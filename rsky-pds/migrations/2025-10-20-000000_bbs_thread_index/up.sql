@@ -0,0 +1,19 @@
+-- Every BBS post and reply on this PDS with the thread it's in, kept up to
+-- date by the stats aggregator from records indexed since its last run so
+-- statistics are summed in SQL instead of decoding every record each time.
+CREATE TABLE IF NOT EXISTS pds.bbs_thread_index (
+    uri character varying PRIMARY KEY,
+    did character varying NOT NULL,
+    collection character varying NOT NULL,
+    -- uri of the thread's root post, the post's own for posts. For a reply
+    -- whose root isn't hosted here, the root as the reply names it.
+    thread character varying NOT NULL,
+    -- the section a post declares, null for replies
+    "sectionId" bigint,
+    -- "indexedAt" of the record when it was last indexed here
+    "indexedAt" character varying NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS bbs_thread_index_thread_idx ON pds.bbs_thread_index (thread);
+CREATE INDEX IF NOT EXISTS bbs_thread_index_did_idx ON pds.bbs_thread_index (did);
+CREATE INDEX IF NOT EXISTS bbs_thread_index_indexed_at_idx ON pds.bbs_thread_index ("indexedAt");
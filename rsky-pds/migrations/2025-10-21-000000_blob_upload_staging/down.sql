@@ -0,0 +1,2 @@
+ALTER TABLE pds.blob_upload DROP COLUMN IF EXISTS "chunkCount";
+ALTER TABLE pds.blob_upload ADD COLUMN IF NOT EXISTS content bytea NOT NULL DEFAULT '';
@@ -0,0 +1,4 @@
+-- Chunks of resumable uploads are staged in the blobstore's temp area as
+-- "<id>.<n>", the row only tracks how far along the upload is
+ALTER TABLE pds.blob_upload DROP COLUMN IF EXISTS content;
+ALTER TABLE pds.blob_upload ADD COLUMN IF NOT EXISTS "chunkCount" integer NOT NULL DEFAULT 0;
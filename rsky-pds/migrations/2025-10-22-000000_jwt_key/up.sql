@@ -0,0 +1,10 @@
+-- JWT signing keys rotated in through com.atproto.admin.rotateJwtKey, so
+-- every replica signs and verifies with the same keys, across restarts too.
+-- A key starts signing at "activeAt", after every replica has loaded it, and
+-- stops at "retiredAt", when the next key starts.
+CREATE TABLE IF NOT EXISTS pds.jwt_key (
+    kid character varying PRIMARY KEY,
+    "privateKeyHex" character varying NOT NULL,
+    "activeAt" character varying NOT NULL,
+    "retiredAt" character varying
+);
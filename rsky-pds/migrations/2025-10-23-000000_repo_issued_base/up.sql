@@ -0,0 +1,4 @@
+-- The head the commit for "issuedRev" was built on. A signed commit is only
+-- applied while this is still the head, so it can't land on top of another
+-- commit prepared from the same base.
+ALTER TABLE pds.repo_root ADD COLUMN IF NOT EXISTS "issuedBase" character varying;
@@ -0,0 +1,122 @@
+use crate::account_events::{AccountEvent, AccountEventHandler, SharedAccountEventHandler};
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::bbs::stats::aggregate_stats;
+use crate::db::establish_connection_for_jobs;
+use anyhow::Result;
+use diesel::delete;
+use diesel::prelude::*;
+use std::sync::Arc;
+
+/// The handlers every PDS runs
+pub fn builtin() -> Vec<SharedAccountEventHandler> {
+    vec![Arc::new(BbsStatsHandler), Arc::new(AccountDataHandler)]
+}
+
+/// Recomputes the BBS statistics when an account changes status, so a
+/// deleted or taken down account stops counting straight away instead of on
+/// the aggregator's next tick.
+pub struct BbsStatsHandler;
+
+#[rocket::async_trait]
+impl AccountEventHandler for BbsStatsHandler {
+    fn name(&self) -> &'static str {
+        "bbs_stats"
+    }
+
+    fn accepts(&self, evt: &AccountEvent) -> bool {
+        matches!(evt, AccountEvent::Account { .. })
+    }
+
+    async fn handle(&self, _evt: &AccountEvent) -> Result<()> {
+        tokio::task::spawn_blocking(|| {
+            let conn = &mut establish_connection_for_jobs()?;
+            aggregate_stats(conn)
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+/// Removes what deleting an account leaves keyed by its DID outside the repo
+/// and the account tables, and drops a cached DID document once the identity
+/// changes.
+pub struct AccountDataHandler;
+
+#[rocket::async_trait]
+impl AccountEventHandler for AccountDataHandler {
+    fn name(&self) -> &'static str {
+        "account_data"
+    }
+
+    fn accepts(&self, evt: &AccountEvent) -> bool {
+        matches!(
+            evt,
+            AccountEvent::Identity { .. }
+                | AccountEvent::Account {
+                    status: AccountStatus::Deleted,
+                    ..
+                }
+        )
+    }
+
+    async fn handle(&self, evt: &AccountEvent) -> Result<()> {
+        let evt = evt.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = &mut establish_connection_for_jobs()?;
+            match &evt {
+                AccountEvent::Identity { did, .. } => purge_did_doc(conn, did),
+                AccountEvent::Account { did, .. } => purge_account_data(conn, did),
+                AccountEvent::SessionRevoked { .. } => Ok(()),
+            }
+        })
+        .await?
+    }
+}
+
+fn purge_did_doc(conn: &mut PgConnection, did: &str) -> Result<()> {
+    use crate::schema::pds::did_doc::dsl as DidDocSchema;
+
+    delete(DidDocSchema::did_doc)
+        .filter(DidDocSchema::did.eq(did))
+        .execute(conn)?;
+    Ok(())
+}
+
+fn purge_account_data(conn: &mut PgConnection, did: &str) -> Result<()> {
+    use crate::schema::pds::app_password::dsl as AppPasswordSchema;
+    use crate::schema::pds::bbs_dm_member::dsl as DmMemberSchema;
+    use crate::schema::pds::bbs_dm_message::dsl as DmMessageSchema;
+    use crate::schema::pds::bbs_draft::dsl as DraftSchema;
+    use crate::schema::pds::bbs_read_state::dsl as ReadStateSchema;
+    use crate::schema::pds::email_notification_pref::dsl as EmailNotificationPrefSchema;
+    use crate::schema::pds::oauth_session::dsl as OAuthSessionSchema;
+    use crate::schema::pds::scheduled_write::dsl as ScheduledWriteSchema;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(AppPasswordSchema::app_password)
+            .filter(AppPasswordSchema::did.eq(did))
+            .execute(conn)?;
+        delete(DmMessageSchema::bbs_dm_message)
+            .filter(DmMessageSchema::senderDid.eq(did))
+            .execute(conn)?;
+        delete(DmMemberSchema::bbs_dm_member)
+            .filter(DmMemberSchema::did.eq(did))
+            .execute(conn)?;
+        delete(DraftSchema::bbs_draft)
+            .filter(DraftSchema::did.eq(did))
+            .execute(conn)?;
+        delete(ReadStateSchema::bbs_read_state)
+            .filter(ReadStateSchema::did.eq(did))
+            .execute(conn)?;
+        delete(EmailNotificationPrefSchema::email_notification_pref)
+            .filter(EmailNotificationPrefSchema::did.eq(did))
+            .execute(conn)?;
+        delete(OAuthSessionSchema::oauth_session)
+            .filter(OAuthSessionSchema::did.eq(did))
+            .execute(conn)?;
+        delete(ScheduledWriteSchema::scheduled_write)
+            .filter(ScheduledWriteSchema::did.eq(did))
+            .execute(conn)?;
+        purge_did_doc(conn, did)
+    })
+}
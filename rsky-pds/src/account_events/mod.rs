@@ -0,0 +1,336 @@
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::config::AccountEventsConfig;
+use crate::db::establish_connection_for_jobs;
+use crate::models::AccountEventOutbox;
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use lazy_static::lazy_static;
+use rsky_common::RFC3339_VARIANT;
+use std::cmp;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+pub mod handlers;
+
+const MAX_BACKOFF_MS: i64 = 60 * 60 * 1000;
+/// How long a claimed delivery is left to its replica before another one may
+/// pick it up, in case the first died while handling it
+const CLAIM_LEASE_MS: i64 = 5 * 60 * 1000;
+
+lazy_static! {
+    /// Registered once at boot and shared by everything that sequences account
+    /// or identity events
+    pub static ref ACCOUNT_EVENTS: AccountEventBus = AccountEventBus::new(handlers::builtin());
+}
+
+/// An account or identity change, as handed to [`AccountEventHandler`]s
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "$type", rename_all = "camelCase")]
+pub enum AccountEvent {
+    Account {
+        did: String,
+        status: AccountStatus,
+    },
+    Identity {
+        did: String,
+        handle: Option<String>,
+    },
+    /// A refresh token was replayed after rotation, so every session descended
+    /// from the same login was revoked. Not sequenced on the firehose.
+    SessionRevoked {
+        did: String,
+        #[serde(rename = "familyId")]
+        family_id: String,
+    },
+}
+
+impl AccountEvent {
+    pub fn did(&self) -> &str {
+        match self {
+            AccountEvent::Account { did, .. } => did,
+            AccountEvent::Identity { did, .. } => did,
+            AccountEvent::SessionRevoked { did, .. } => did,
+        }
+    }
+}
+
+/// Keeps state derived from an account in step with it. Delivery is at least
+/// once and a failed event is retried behind later ones, so handlers must be
+/// idempotent and shouldn't rely on the order events arrive in.
+#[rocket::async_trait]
+pub trait AccountEventHandler: Send + Sync {
+    /// Stored with each pending delivery, so must not change between releases
+    fn name(&self) -> &'static str;
+
+    /// Whether this handler cares about `evt` at all
+    fn accepts(&self, _evt: &AccountEvent) -> bool {
+        true
+    }
+
+    async fn handle(&self, evt: &AccountEvent) -> Result<()>;
+}
+
+pub type SharedAccountEventHandler = Arc<dyn AccountEventHandler>;
+
+/// Fans account and identity events out to the registered handlers through
+/// the `account_event_outbox` table.
+///
+/// Events are written to the outbox before they're sequenced, one row per
+/// handler that accepts them, and [`AccountEventDispatcher`] works through the
+/// rows on every replica. A row is deleted only once its handler succeeds, and
+/// a failure is retried with exponential backoff. In-process caches aren't
+/// handled here, since the sequencer already drops them on every replica as
+/// it emits the event, and webhooks get the same events from the firehose.
+pub struct AccountEventBus {
+    handlers: RwLock<Vec<SharedAccountEventHandler>>,
+}
+
+impl AccountEventBus {
+    pub fn new(handlers: Vec<SharedAccountEventHandler>) -> Self {
+        AccountEventBus {
+            handlers: RwLock::new(handlers),
+        }
+    }
+
+    /// Adds a handler for events enqueued from now on. Names must be unique.
+    pub fn register(&self, handler: SharedAccountEventHandler) {
+        let mut handlers = self.handlers.write().expect("handlers lock poisoned");
+        handlers.retain(|existing| existing.name() != handler.name());
+        handlers.push(handler);
+    }
+
+    pub fn handler(&self, name: &str) -> Option<SharedAccountEventHandler> {
+        self.handlers
+            .read()
+            .expect("handlers lock poisoned")
+            .iter()
+            .find(|handler| handler.name() == name)
+            .cloned()
+    }
+
+    /// Names of the handlers that will be given `evt`
+    pub fn handler_names(&self, evt: &AccountEvent) -> Vec<&'static str> {
+        self.handlers
+            .read()
+            .expect("handlers lock poisoned")
+            .iter()
+            .filter(|handler| handler.accepts(evt))
+            .map(|handler| handler.name())
+            .collect()
+    }
+
+    /// Records `evt` in the outbox for every handler that accepts it.
+    pub async fn enqueue(&self, evt: &AccountEvent) -> Result<()> {
+        use crate::schema::pds::account_event_outbox::dsl as OutboxSchema;
+
+        let names = self.handler_names(evt);
+        if names.is_empty() {
+            return Ok(());
+        }
+        let event = serde_json::to_string(evt)?;
+        let now = rsky_common::now();
+        let rows = names
+            .into_iter()
+            .map(|name| {
+                (
+                    OutboxSchema::handler.eq(name),
+                    OutboxSchema::did.eq(evt.did().to_string()),
+                    OutboxSchema::event.eq(event.clone()),
+                    OutboxSchema::nextAttemptAt.eq(now.clone()),
+                    OutboxSchema::createdAt.eq(now.clone()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let conn = &mut establish_connection_for_jobs()?;
+        insert_into(OutboxSchema::account_event_outbox)
+            .values(rows)
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+/// Delivers the events in `account_event_outbox` to their handlers. Runs on
+/// every replica, each claiming a different batch of due rows.
+pub struct AccountEventDispatcher {
+    pub interval_ms: u64,
+    pub batch_size: i64,
+}
+
+impl AccountEventDispatcher {
+    pub fn new(cfg: &AccountEventsConfig) -> Self {
+        AccountEventDispatcher {
+            interval_ms: cfg.interval_ms.max(100),
+            batch_size: cfg.batch_size.max(1),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run(&ACCOUNT_EVENTS).await {
+                tracing::error!("@LOG: ERROR: account event delivery run failed: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self, bus: &AccountEventBus) -> Result<()> {
+        let conn = &mut establish_connection_for_jobs()?;
+        for row in self.claim(conn)? {
+            self.deliver(conn, bus, row).await?;
+        }
+        Ok(())
+    }
+
+    /// Pushes the due rows' next attempt past the lease, so other replicas
+    /// leave them alone while this one works through them.
+    fn claim(&self, conn: &mut PgConnection) -> Result<Vec<AccountEventOutbox>> {
+        use crate::schema::pds::account_event_outbox::dsl as OutboxSchema;
+
+        let now = rsky_common::now();
+        Ok(conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            let ids = OutboxSchema::account_event_outbox
+                .filter(OutboxSchema::nextAttemptAt.le(&now))
+                .order(OutboxSchema::id.asc())
+                .limit(self.batch_size)
+                .select(OutboxSchema::id)
+                .for_update()
+                .skip_locked()
+                .load::<i64>(conn)?;
+            let mut rows = update(OutboxSchema::account_event_outbox)
+                .filter(OutboxSchema::id.eq_any(ids))
+                .set((
+                    OutboxSchema::attempts.eq(OutboxSchema::attempts + 1),
+                    OutboxSchema::nextAttemptAt.eq(format_after(CLAIM_LEASE_MS)),
+                ))
+                .returning(AccountEventOutbox::as_returning())
+                .get_results(conn)?;
+            rows.sort_by_key(|row| row.id);
+            Ok(rows)
+        })?)
+    }
+
+    async fn deliver(
+        &self,
+        conn: &mut PgConnection,
+        bus: &AccountEventBus,
+        row: AccountEventOutbox,
+    ) -> Result<()> {
+        use crate::schema::pds::account_event_outbox::dsl as OutboxSchema;
+
+        let Some(handler) = bus.handler(&row.handler) else {
+            tracing::warn!(
+                "Dropping account event {} for {}: no handler named {}",
+                row.id,
+                row.did,
+                row.handler
+            );
+            delete(OutboxSchema::account_event_outbox.find(row.id)).execute(conn)?;
+            return Ok(());
+        };
+        let res = match serde_json::from_str::<AccountEvent>(&row.event) {
+            Ok(evt) => handler.handle(&evt).await,
+            Err(error) => Err(error.into()),
+        };
+        match res {
+            Ok(()) => {
+                delete(OutboxSchema::account_event_outbox.find(row.id)).execute(conn)?;
+            }
+            Err(error) => {
+                tracing::warn!(
+                    "Account event {} for {}: {} failed on attempt {}: {error}",
+                    row.id,
+                    row.did,
+                    row.handler,
+                    row.attempts
+                );
+                update(OutboxSchema::account_event_outbox.find(row.id))
+                    .set((
+                        OutboxSchema::nextAttemptAt.eq(format_after(backoff_ms(row.attempts))),
+                        OutboxSchema::lastError.eq(Some(error.to_string())),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn backoff_ms(attempts: i32) -> i64 {
+    cmp::min(
+        1000i64.saturating_mul(2i64.saturating_pow(attempts.max(0) as u32)),
+        MAX_BACKOFF_MS,
+    )
+}
+
+fn format_after(ms: i64) -> String {
+    format!(
+        "{}",
+        (Utc::now() + ChronoDuration::milliseconds(ms)).format(RFC3339_VARIANT)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str);
+
+    #[rocket::async_trait]
+    impl AccountEventHandler for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn accepts(&self, evt: &AccountEvent) -> bool {
+            matches!(evt, AccountEvent::Account { .. })
+        }
+
+        async fn handle(&self, _evt: &AccountEvent) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let evt = AccountEvent::Account {
+            did: "did:plc:alice".to_string(),
+            status: AccountStatus::Deleted,
+        };
+        let json = serde_json::to_string(&evt).unwrap();
+        assert_eq!(
+            json,
+            r#"{"$type":"account","did":"did:plc:alice","status":"Deleted"}"#
+        );
+        assert_eq!(serde_json::from_str::<AccountEvent>(&json).unwrap(), evt);
+    }
+
+    #[test]
+    fn only_handlers_that_accept_an_event_get_it() {
+        let bus = AccountEventBus::new(vec![]);
+        bus.register(Arc::new(Named("first")));
+        bus.register(Arc::new(Named("second")));
+        bus.register(Arc::new(Named("first")));
+
+        let account = AccountEvent::Account {
+            did: "did:plc:alice".to_string(),
+            status: AccountStatus::Active,
+        };
+        let identity = AccountEvent::Identity {
+            did: "did:plc:alice".to_string(),
+            handle: None,
+        };
+        assert_eq!(bus.handler_names(&account), vec!["second", "first"]);
+        assert!(bus.handler_names(&identity).is_empty());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff_ms(0), 1000);
+        assert_eq!(backoff_ms(3), 8000);
+        assert_eq!(backoff_ms(40), MAX_BACKOFF_MS);
+    }
+}
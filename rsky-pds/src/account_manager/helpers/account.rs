@@ -1,3 +1,4 @@
+use crate::account_manager::helpers::account_cache;
 use crate::db::DbConn;
 use crate::schema::pds::account::dsl as AccountSchema;
 use crate::schema::pds::account::table as AccountTable;
@@ -13,7 +14,7 @@ use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use diesel::*;
 use rsky_common;
 use rsky_common::RFC3339_VARIANT;
-use rsky_lexicon::com::atproto::admin::StatusAttr;
+use rsky_lexicon::com::atproto::admin::{StatusAttr, SuspensionAttr};
 use std::ops::Add;
 use std::time::SystemTime;
 use thiserror::Error;
@@ -52,6 +53,7 @@ pub struct FormattedAccountStatus {
 pub struct GetAccountAdminStatusOutput {
     pub takedown: StatusAttr,
     pub deactivated: StatusAttr,
+    pub suspended: SuspensionAttr,
 }
 
 pub type ActorJoinAccount =
@@ -77,6 +79,8 @@ pub struct ActorAccount {
     pub email_confirmed_at: Option<String>,
     #[serde(rename = "ckbAddress")]
     pub ckb_address: Option<String>,
+    #[serde(rename = "suspendedUntil")]
+    pub suspended_until: Option<String>,
 }
 
 pub fn select_account_qb(flags: Option<AvailabilityFlags>) -> BoxedQuery<'static> {
@@ -129,6 +133,7 @@ pub async fn get_account(
                     AccountSchema::email.nullable(),
                     AccountSchema::emailConfirmedAt.nullable(),
                     AccountSchema::invitesDisabled.nullable(),
+                    ActorSchema::suspendedUntil,
                 ))
                 .first::<(
                     String,
@@ -141,6 +146,7 @@ pub async fn get_account(
                     Option<String>,
                     Option<String>,
                     Option<i16>,
+                    Option<String>,
                 )>(conn)
                 .map(|res| ActorAccount {
                     did: res.0,
@@ -153,6 +159,7 @@ pub async fn get_account(
                     email: res.7,
                     email_confirmed_at: res.8,
                     invites_disabled: res.9,
+                    suspended_until: res.10,
                 })
                 .optional()
         })
@@ -180,6 +187,7 @@ pub async fn get_account_by_email(
                     AccountSchema::email.nullable(),
                     AccountSchema::emailConfirmedAt.nullable(),
                     AccountSchema::invitesDisabled.nullable(),
+                    ActorSchema::suspendedUntil,
                 ))
                 .filter(AccountSchema::email.eq(email.to_lowercase()))
                 .first::<(
@@ -193,6 +201,7 @@ pub async fn get_account_by_email(
                     Option<String>,
                     Option<String>,
                     Option<i16>,
+                    Option<String>,
                 )>(conn)
                 .map(|res| ActorAccount {
                     did: res.0,
@@ -205,6 +214,7 @@ pub async fn get_account_by_email(
                     email: res.7,
                     email_confirmed_at: res.8,
                     invites_disabled: res.9,
+                    suspended_until: res.10,
                 })
                 .optional()
         })
@@ -212,6 +222,85 @@ pub async fn get_account_by_email(
     Ok(found)
 }
 
+/// Looks up many accounts by DID in one query, for hydrating views. Served
+/// from the account cache where possible, so unlike `get_account` it may be
+/// briefly behind; don't use it for auth decisions.
+pub async fn get_accounts(
+    dids: &[String],
+    flags: Option<AvailabilityFlags>,
+    db: &DbConn,
+) -> Result<Vec<ActorAccount>> {
+    let (mut accounts, missing) = account_cache::get_many(dids);
+    if !missing.is_empty() {
+        // Fetched regardless of availability so the cache serves any flags
+        let fetched = db
+            .run(move |conn| {
+                select_account_qb(Some(AvailabilityFlags {
+                    include_taken_down: Some(true),
+                    include_deactivated: Some(true),
+                }))
+                .select((
+                    ActorSchema::did,
+                    ActorSchema::handle,
+                    ActorSchema::createdAt,
+                    ActorSchema::takedownRef,
+                    ActorSchema::deactivatedAt,
+                    ActorSchema::deleteAfter,
+                    ActorSchema::ckbAddress,
+                    AccountSchema::email.nullable(),
+                    AccountSchema::emailConfirmedAt.nullable(),
+                    AccountSchema::invitesDisabled.nullable(),
+                    ActorSchema::suspendedUntil,
+                ))
+                .filter(ActorSchema::did.eq_any(missing))
+                .load::<(
+                    String,
+                    Option<String>,
+                    String,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                    Option<i16>,
+                    Option<String>,
+                )>(conn)
+            })
+            .await?
+            .into_iter()
+            .map(|res| ActorAccount {
+                did: res.0,
+                handle: res.1,
+                created_at: res.2,
+                takedown_ref: res.3,
+                deactivated_at: res.4,
+                delete_after: res.5,
+                ckb_address: res.6,
+                email: res.7,
+                email_confirmed_at: res.8,
+                invites_disabled: res.9,
+                suspended_until: res.10,
+            })
+            .collect::<Vec<ActorAccount>>();
+        account_cache::put_many(&fetched);
+        accounts.extend(fetched);
+    }
+
+    let AvailabilityFlags {
+        include_taken_down,
+        include_deactivated,
+    } = flags.unwrap_or(AvailabilityFlags {
+        include_taken_down: Some(false),
+        include_deactivated: Some(false),
+    });
+    accounts.retain(|account| {
+        (include_taken_down.unwrap_or(false) || account.takedown_ref.is_none())
+            && (include_deactivated.unwrap_or(false) || account.deactivated_at.is_none())
+    });
+    Ok(accounts)
+}
+
 pub async fn register_actor(
     did: String,
     handle: String,
@@ -322,7 +411,33 @@ pub async fn update_account_takedown_status(
     db.run(move |conn| {
         update(ActorSchema::actor)
             .filter(ActorSchema::did.eq(did))
-            .set((ActorSchema::takedownRef.eq(takedown_ref),))
+            .set((
+                ActorSchema::takedownRef.eq(takedown_ref),
+                // A takedown replaces a suspension, and reversing either lifts both
+                ActorSchema::suspendedUntil.eq(None::<String>),
+            ))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Takes the account down until `until`, when the suspension job lifts it.
+pub async fn suspend_account(
+    did: &str,
+    takedown_ref: Option<String>,
+    until: String,
+    db: &DbConn,
+) -> Result<()> {
+    let takedown_ref = takedown_ref.unwrap_or_else(rsky_common::now);
+    let did = did.to_owned();
+    db.run(move |conn| {
+        update(ActorSchema::actor)
+            .filter(ActorSchema::did.eq(did))
+            .set((
+                ActorSchema::takedownRef.eq(takedown_ref),
+                ActorSchema::suspendedUntil.eq(until),
+            ))
             .execute(conn)
     })
     .await?;
@@ -417,6 +532,42 @@ pub async fn update_handle(did: &str, handle: &str, db: &DbConn) -> Result<()> {
     Ok(())
 }
 
+/// Moves the account from `old_ckb_addr` to `new_ckb_addr`. Fails if another
+/// account is bound to the new address or the account moved in the meantime.
+pub async fn update_ckb_address(
+    did: &str,
+    old_ckb_addr: &str,
+    new_ckb_addr: &str,
+    db: &DbConn,
+) -> Result<()> {
+    use crate::schema::pds::actor;
+
+    let actor2 = diesel::alias!(actor as actor2);
+
+    let did = did.to_owned();
+    let old_ckb_addr = old_ckb_addr.to_owned();
+    let new_ckb_addr = new_ckb_addr.to_owned();
+    let res = db
+        .run(move |conn| {
+            update(ActorSchema::actor)
+                .filter(ActorSchema::did.eq(did))
+                .filter(ActorSchema::ckbAddress.eq(old_ckb_addr))
+                .filter(not(exists(
+                    actor2.filter(ActorSchema::ckbAddress.eq(&new_ckb_addr)),
+                )))
+                .set((ActorSchema::ckbAddress.eq(&new_ckb_addr),))
+                .execute(conn)
+        })
+        .await?;
+
+    if res < 1 {
+        return Err(anyhow::Error::new(
+            AccountHelperError::UserAlreadyExistsError,
+        ));
+    }
+    Ok(())
+}
+
 pub async fn set_email_confirmed_at(
     did: &str,
     email_confirmed_at: String,
@@ -438,11 +589,15 @@ pub async fn get_account_admin_status(
     db: &DbConn,
 ) -> Result<Option<GetAccountAdminStatusOutput>> {
     let did = did.to_owned();
-    let res: Option<(Option<String>, Option<String>)> = db
+    let res: Option<(Option<String>, Option<String>, Option<String>)> = db
         .run(move |conn| {
             ActorSchema::actor
                 .filter(ActorSchema::did.eq(did))
-                .select((ActorSchema::takedownRef, ActorSchema::deactivatedAt))
+                .select((
+                    ActorSchema::takedownRef,
+                    ActorSchema::deactivatedAt,
+                    ActorSchema::suspendedUntil,
+                ))
                 .first(conn)
                 .optional()
         })
@@ -470,9 +625,16 @@ pub async fn get_account_admin_status(
                     r#ref: None,
                 },
             };
+            let suspended = SuspensionAttr {
+                applied: res.2.is_some(),
+                r#ref: None,
+                duration_in_hours: None,
+                expires_at: res.2,
+            };
             Ok(Some(GetAccountAdminStatusOutput {
                 takedown,
                 deactivated,
+                suspended,
             }))
         }
     }
@@ -484,6 +646,12 @@ pub fn format_account_status(account: Option<ActorAccount>) -> FormattedAccountS
             active: false,
             status: Some(AccountStatus::Deleted),
         },
+        Some(got) if got.takedown_ref.is_some() && got.suspended_until.is_some() => {
+            FormattedAccountStatus {
+                active: false,
+                status: Some(AccountStatus::Suspended),
+            }
+        }
         Some(got) if got.takedown_ref.is_some() => FormattedAccountStatus {
             active: false,
             status: Some(AccountStatus::Takendown),
@@ -498,3 +666,32 @@ pub fn format_account_status(account: Option<ActorAccount>) -> FormattedAccountS
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspension_is_reported_over_takedown() {
+        let account = ActorAccount {
+            did: "did:web:alice.test".to_string(),
+            handle: Some("alice.test".to_string()),
+            created_at: rsky_common::now(),
+            takedown_ref: Some("mod-123".to_string()),
+            deactivated_at: None,
+            delete_after: None,
+            email: None,
+            invites_disabled: None,
+            email_confirmed_at: None,
+            ckb_address: None,
+            suspended_until: Some(rsky_common::now()),
+        };
+        let suspended = format_account_status(Some(account.clone()));
+        assert_eq!(suspended.status, Some(AccountStatus::Suspended));
+        let taken_down = format_account_status(Some(ActorAccount {
+            suspended_until: None,
+            ..account
+        }));
+        assert_eq!(taken_down.status, Some(AccountStatus::Takendown));
+    }
+}
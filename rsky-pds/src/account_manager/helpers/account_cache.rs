@@ -0,0 +1,98 @@
+use crate::account_manager::helpers::account::ActorAccount;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Accounts kept in memory, enough for the active users of a busy PDS
+const ACCOUNT_CACHE_CAPACITY: usize = 10_000;
+/// Changes that aren't sequenced, like an email update on another instance,
+/// show up after at most this long
+const ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+struct CachedAccount {
+    account: ActorAccount,
+    cached_at: Instant,
+}
+
+lazy_static! {
+    /// Accounts by DID for batch lookups. Entries are dropped when the
+    /// sequencer sees an identity or account event for the DID, and when
+    /// this process changes the account.
+    static ref ACCOUNT_CACHE: Mutex<LruCache<String, CachedAccount>> = Mutex::new(LruCache::new(
+        NonZeroUsize::new(ACCOUNT_CACHE_CAPACITY).expect("non-zero capacity")
+    ));
+}
+
+/// Splits `dids` into the accounts cached for them and the DIDs to query.
+pub fn get_many(dids: &[String]) -> (Vec<ActorAccount>, Vec<String>) {
+    let mut cache = ACCOUNT_CACHE.lock().expect("account cache poisoned");
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for did in dids {
+        match cache.get(did) {
+            Some(cached) if cached.cached_at.elapsed() < ACCOUNT_CACHE_TTL => {
+                found.push(cached.account.clone())
+            }
+            _ => missing.push(did.clone()),
+        }
+    }
+    (found, missing)
+}
+
+pub fn put_many(accounts: &[ActorAccount]) {
+    let mut cache = ACCOUNT_CACHE.lock().expect("account cache poisoned");
+    for account in accounts {
+        cache.put(
+            account.did.clone(),
+            CachedAccount {
+                account: account.clone(),
+                cached_at: Instant::now(),
+            },
+        );
+    }
+}
+
+pub fn invalidate(did: &str) {
+    ACCOUNT_CACHE
+        .lock()
+        .expect("account cache poisoned")
+        .pop(did);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(did: &str, handle: &str) -> ActorAccount {
+        ActorAccount {
+            did: did.to_string(),
+            handle: Some(handle.to_string()),
+            created_at: "2025-01-01T00:00:00.000Z".to_string(),
+            takedown_ref: None,
+            deactivated_at: None,
+            delete_after: None,
+            email: None,
+            invites_disabled: None,
+            email_confirmed_at: None,
+            ckb_address: None,
+            suspended_until: None,
+        }
+    }
+
+    #[test]
+    fn invalidates_cached_accounts() {
+        let alice = "did:web5:cache-test-alice".to_string();
+        let bob = "did:web5:cache-test-bob".to_string();
+        put_many(&[account(&alice, "alice.test")]);
+        let (found, missing) = get_many(&[alice.clone(), bob.clone()]);
+        assert_eq!(found, vec![account(&alice, "alice.test")]);
+        assert_eq!(missing, vec![bob.clone()]);
+
+        invalidate(&alice);
+        let (found, missing) = get_many(&[alice.clone()]);
+        assert!(found.is_empty());
+        assert_eq!(missing, vec![alice]);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::*;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+    Takedown,
+    ReverseTakedown,
+    Suspend,
+    Unsuspend,
+    Deactivate,
+    Reactivate,
+    CreateSession,
+    DeleteAccount,
+    StepUp,
+    /// A wallet signature or key didn't match the account's DID doc
+    KeyCheckFailure,
+    InviteCodeUse,
+    /// The account moved to a new CKB address
+    RebindAddress,
+    /// A web5 account set its password or turned password login on or off
+    SetPassword,
+    /// A rotated refresh token was presented again and its family was revoked
+    RefreshTokenReuse,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Takedown => "takedown",
+            AuditAction::ReverseTakedown => "reverseTakedown",
+            AuditAction::Suspend => "suspend",
+            AuditAction::Unsuspend => "unsuspend",
+            AuditAction::Deactivate => "deactivate",
+            AuditAction::Reactivate => "reactivate",
+            AuditAction::CreateSession => "createSession",
+            AuditAction::DeleteAccount => "deleteAccount",
+            AuditAction::StepUp => "stepUp",
+            AuditAction::KeyCheckFailure => "keyCheckFailure",
+            AuditAction::InviteCodeUse => "inviteCodeUse",
+            AuditAction::RebindAddress => "rebindAddress",
+            AuditAction::SetPassword => "setPassword",
+            AuditAction::RefreshTokenReuse => "refreshTokenReuse",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    /// Who acted, unset for the admin password and the PDS itself
+    pub actor_did: Option<String>,
+    pub subject_did: String,
+    pub subject_uri: Option<String>,
+    pub subject_cid: Option<String>,
+    pub detail: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, actor_did: Option<String>, subject_did: String) -> Self {
+        AuditEvent {
+            action,
+            actor_did,
+            subject_did,
+            subject_uri: None,
+            subject_cid: None,
+            detail: None,
+        }
+    }
+}
+
+/// Appends to the audit log on a connection the caller already holds, e.g. in
+/// a background job or inside a transaction.
+pub fn insert_audit_event(conn: &mut PgConnection, event: AuditEvent) -> Result<()> {
+    use crate::schema::pds::audit_log::dsl as AuditLogSchema;
+
+    insert_into(AuditLogSchema::audit_log)
+        .values((
+            AuditLogSchema::action.eq(event.action.as_str()),
+            AuditLogSchema::actorDid.eq(event.actor_did),
+            AuditLogSchema::subjectDid.eq(event.subject_did),
+            AuditLogSchema::subjectUri.eq(event.subject_uri),
+            AuditLogSchema::subjectCid.eq(event.subject_cid),
+            AuditLogSchema::detail.eq(event.detail.map(|detail| detail.to_string())),
+            AuditLogSchema::createdAt.eq(rsky_common::now()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+pub async fn record_audit_event(event: AuditEvent, db: &DbConn) -> Result<()> {
+    db.run(move |conn| insert_audit_event(conn, event)).await
+}
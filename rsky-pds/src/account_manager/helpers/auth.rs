@@ -1,23 +1,30 @@
+use crate::account_manager::helpers::jwt_keys::{JwtKey, JwtKeyRing};
 use crate::auth_verifier::AuthScope;
 use crate::db::DbConn;
 use crate::models;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
 use diesel::*;
 use jwt_simple::prelude::*;
-use rsky_common::time::{from_micros_to_utc, MINUTE};
+use rsky_common::time::{from_micros_to_utc, MINUTE, SECOND};
 use rsky_common::{get_random_str, json_to_b64url, RFC3339_VARIANT};
-use secp256k1::{Keypair, Message, SecretKey};
+use secp256k1::{Message, SecretKey};
 use sha2::{Digest, Sha256};
 use std::time::SystemTime;
 use thiserror::Error;
 
 pub struct CreateTokensOpts {
     pub did: String,
-    pub jwt_key: Keypair,
+    pub jwt_key: JwtKey,
     pub service_did: String,
     pub scope: Option<AuthScope>,
     pub jti: Option<String>,
     pub expires_in: Option<Duration>,
+    /// Collections an app password may write to, carried in the access token
+    pub collections: Option<Vec<String>>,
+    /// Thumbprint of the DPoP key an OAuth access token is bound to, carried
+    /// in the access token as `cnf.jkt`
+    pub dpop_jkt: Option<String>,
 }
 
 pub struct RefreshGracePeriodOpts {
@@ -46,6 +53,9 @@ pub struct ServiceJwtPayload {
     pub exp: Option<u64>,
     pub lxm: Option<String>,
     pub jti: Option<String>,
+    /// Collections the issuing session may write to, see [`ServiceJwtParams::collections`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -61,11 +71,35 @@ pub struct ServiceJwtParams {
     pub lxm: Option<String>,
     pub jti: Option<String>,
     pub keypair: SecretKey,
+    /// Set when the token is minted for a session restricted to these
+    /// collections, so the receiving service can hold it to the same scope
+    pub collections: Option<Vec<String>>,
 }
 
+pub const ACCESS_TOKEN_EXPIRES_IN_SECS: u64 = 2 * 60 * 60;
+
 #[derive(Serialize, Deserialize)]
 pub struct CustomClaimObj {
     pub scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collections: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+/// Proof-of-possession key of a token (RFC 7800), `jkt` for DPoP (RFC 9449)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+pub const STEP_UP_SCOPE: &str = "com.atproto.web5.stepUp";
+pub const STEP_UP_TOKEN_EXPIRES_IN_SECS: u64 = 5 * 60;
+
+#[derive(Serialize, Deserialize)]
+pub struct StepUpClaimObj {
+    pub scope: String,
+    pub lxm: String,
 }
 
 #[derive(Error, Debug)]
@@ -82,22 +116,31 @@ pub fn create_tokens(opts: CreateTokensOpts) -> Result<(String, String)> {
         scope,
         jti,
         expires_in,
+        collections,
+        dpop_jkt,
     } = opts;
+    // The access token carries the refresh token id as its jti, so revoking the
+    // session also revokes the access tokens issued with it.
+    let jti = jti.unwrap_or_else(get_refresh_token_id);
     let access_jwt = create_access_token(CreateTokensOpts {
         did: did.clone(),
-        jwt_key,
+        jwt_key: jwt_key.clone(),
         service_did: service_did.clone(),
         scope,
         expires_in,
-        jti: None,
+        jti: Some(jti.clone()),
+        collections,
+        dpop_jkt,
     })?;
     let refresh_jwt = create_refresh_token(CreateTokensOpts {
         did,
         jwt_key,
         service_did,
-        jti,
+        jti: Some(jti),
         expires_in,
         scope: None,
+        collections: None,
+        dpop_jkt: None,
     })?;
     Ok((access_jwt, refresh_jwt))
 }
@@ -108,22 +151,29 @@ pub fn create_access_token(opts: CreateTokensOpts) -> Result<String> {
         jwt_key,
         service_did,
         scope,
+        jti,
         expires_in,
-        ..
+        collections,
+        dpop_jkt,
     } = opts;
     let scope = scope.unwrap_or(AuthScope::Access);
-    let expires_in = expires_in.unwrap_or_else(|| Duration::from_hours(2));
-    let claims = Claims::with_custom_claims(
+    let expires_in =
+        expires_in.unwrap_or_else(|| Duration::from_secs(ACCESS_TOKEN_EXPIRES_IN_SECS));
+    let mut claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: scope.as_str().to_owned(),
+            collections,
+            cnf: dpop_jkt.map(|jkt| Confirmation { jkt }),
         },
         expires_in,
     )
     .with_audience(service_did)
     .with_subject(did);
+    if let Some(jti) = jti {
+        claims = claims.with_jwt_id(jti);
+    }
     // alg ES256K
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let token = key.sign(claims)?;
+    let token = jwt_key.signing_key()?.sign(claims)?;
     Ok(token)
 }
 
@@ -141,6 +191,8 @@ pub fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
     let claims = Claims::with_custom_claims(
         CustomClaimObj {
             scope: AuthScope::Refresh.as_str().to_owned(),
+            collections: None,
+            cnf: None,
         },
         expires_in,
     )
@@ -148,22 +200,23 @@ pub fn create_refresh_token(opts: CreateTokensOpts) -> Result<String> {
     .with_subject(did)
     .with_jwt_id(jti);
     // alg ES256K
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let token = key.sign(claims)?;
+    let token = jwt_key.signing_key()?.sign(claims)?;
     Ok(token)
 }
 
 pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
     let ServiceJwtParams {
-        iss, aud, keypair, ..
+        iss,
+        aud,
+        keypair,
+        collections,
+        ..
     } = params;
     let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("timestamp in micros since UNIX epoch")
-        .as_micros() as usize;
-    let exp = params
-        .exp
-        .unwrap_or(((now + MINUTE as usize) / 1000) as u64);
+        .expect("timestamp in seconds since UNIX epoch")
+        .as_secs();
+    let exp = params.exp.unwrap_or(now + (MINUTE / SECOND) as u64);
     let lxm = params.lxm;
     let jti = get_random_str();
     let header = ServiceJwtHeader {
@@ -176,6 +229,7 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
         exp: Some(exp),
         lxm,
         jti: Some(jti),
+        collections,
     };
     let to_sign_str = format!(
         "{0}.{1}",
@@ -196,16 +250,56 @@ pub async fn create_service_jwt(params: ServiceJwtParams) -> Result<String> {
     ))
 }
 
+/// Token proving the wallet re-signed a challenge for `lxm`, required on top of the
+/// access token by routes with step-up enabled.
+pub fn create_step_up_token(
+    did: String,
+    lxm: String,
+    service_did: String,
+    jwt_key: JwtKey,
+) -> Result<String> {
+    let claims = Claims::with_custom_claims(
+        StepUpClaimObj {
+            scope: STEP_UP_SCOPE.to_string(),
+            lxm,
+        },
+        Duration::from_secs(STEP_UP_TOKEN_EXPIRES_IN_SECS),
+    )
+    .with_audience(service_did)
+    .with_subject(did)
+    .with_jwt_id(get_random_str());
+    // alg ES256K
+    Ok(jwt_key.signing_key()?.sign(claims)?)
+}
+
+pub fn verify_step_up_token(
+    token: &str,
+    did: &str,
+    lxm: &str,
+    service_did: String,
+    jwt_keys: &JwtKeyRing,
+) -> Result<()> {
+    let mut options = VerificationOptions::default();
+    options.allowed_audiences = Some(HashSet::from_strings(&[service_did]));
+    let claims = jwt_keys.verify::<StepUpClaimObj>(token, Some(options))?;
+    if claims.custom.scope != STEP_UP_SCOPE {
+        bail!("not a step-up token");
+    }
+    if claims.subject.as_deref() != Some(did) {
+        bail!("step-up token was issued for another account");
+    }
+    if claims.custom.lxm != lxm {
+        bail!("step-up token was issued for another method");
+    }
+    Ok(())
+}
+
 // @NOTE unsafe for verification, should only be used w/ direct output from createRefreshToken() or createTokens()
-pub fn decode_refresh_token(jwt: String, jwt_key: Keypair) -> Result<RefreshToken> {
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let public_key = key.public_key();
-    let claims = public_key.verify_token::<CustomClaimObj>(&jwt, None)?;
-    assert_eq!(
-        claims.custom.scope,
-        AuthScope::Refresh.as_str().to_owned(),
-        "not a refresh token"
-    );
+pub fn decode_refresh_token(jwt: String, jwt_keys: &JwtKeyRing) -> Result<RefreshToken> {
+    let claims = jwt_keys.verify::<CustomClaimObj>(&jwt, None)?;
+    if claims.custom.scope != AuthScope::Refresh.as_str() {
+        bail!("not a refresh token");
+    }
     Ok(RefreshToken {
         scope: AuthScope::from_str(&claims.custom.scope)?,
         sub: claims.subject.unwrap(),
@@ -214,14 +308,18 @@ pub fn decode_refresh_token(jwt: String, jwt_key: Keypair) -> Result<RefreshToke
     })
 }
 
+/// Stores a refresh token in `family_id`'s family, or as the first of a new
+/// family when that's unset.
 pub async fn store_refresh_token(
     payload: RefreshToken,
     app_password_name: Option<String>,
+    family_id: Option<String>,
     db: &DbConn,
 ) -> Result<()> {
     use crate::schema::pds::refresh_token::dsl as RefreshTokenSchema;
 
     let exp = from_micros_to_utc((payload.exp.as_millis() / 1000) as i64);
+    let family_id = family_id.unwrap_or_else(|| payload.jti.clone());
 
     db.run(move |conn| {
         insert_into(RefreshTokenSchema::refresh_token)
@@ -230,6 +328,7 @@ pub async fn store_refresh_token(
                 RefreshTokenSchema::did.eq(payload.sub),
                 RefreshTokenSchema::appPasswordName.eq(app_password_name),
                 RefreshTokenSchema::expiresAt.eq(format!("{}", exp.format(RFC3339_VARIANT))),
+                RefreshTokenSchema::familyId.eq(family_id),
             ))
             .on_conflict_do_nothing() // E.g. when re-granting during a refresh grace period
             .execute(conn)
@@ -251,6 +350,173 @@ pub async fn revoke_refresh_token(id: String, db: &DbConn) -> Result<bool> {
     .await
 }
 
+/// Revokes a session: its refresh token and any it was rotated from are deleted,
+/// and their ids go on the revocation list until the tokens themselves expire.
+/// Access tokens minted shortly before a refresh token expires outlive it, so
+/// an entry is kept for at least an access token lifetime.
+pub async fn revoke_session(id: String, db: &DbConn) -> Result<bool> {
+    use crate::schema::pds::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::pds::revoked_session::dsl as RevokedSessionSchema;
+
+    let now = SystemTime::now();
+    let access_expiry =
+        DateTime::<Utc>::from(now + std::time::Duration::from_secs(ACCESS_TOKEN_EXPIRES_IN_SECS));
+    let access_expiry = format!("{}", access_expiry.format(RFC3339_VARIANT));
+    let now = format!("{}", DateTime::<Utc>::from(now).format(RFC3339_VARIANT));
+    db.run(move |conn| {
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            let Some(token) = RefreshTokenSchema::refresh_token
+                .find(&id)
+                .first::<models::RefreshToken>(conn)
+                .optional()?
+            else {
+                return Ok(false);
+            };
+            // Walk back through tokens still in their rotation grace period
+            let mut revoked = vec![(token.id, token.expires_at)];
+            let mut next = vec![revoked[0].0.clone()];
+            while !next.is_empty() {
+                let ids = revoked.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+                let found = RefreshTokenSchema::refresh_token
+                    .filter(RefreshTokenSchema::nextId.eq_any(&next))
+                    .filter(RefreshTokenSchema::id.ne_all(&ids))
+                    .select((RefreshTokenSchema::id, RefreshTokenSchema::expiresAt))
+                    .get_results::<(String, String)>(conn)?;
+                next = found.iter().map(|(id, _)| id.clone()).collect();
+                revoked.extend(found);
+            }
+            let ids = revoked.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>();
+            let rows = revoked
+                .into_iter()
+                .map(|(id, expires_at)| {
+                    (
+                        RevokedSessionSchema::id.eq(id),
+                        RevokedSessionSchema::did.eq(token.did.clone()),
+                        RevokedSessionSchema::expiresAt.eq(expires_at.max(access_expiry.clone())),
+                    )
+                })
+                .collect::<Vec<_>>();
+            insert_into(RevokedSessionSchema::revoked_session)
+                .values(rows)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            delete(RefreshTokenSchema::refresh_token)
+                .filter(RefreshTokenSchema::id.eq_any(&ids))
+                .execute(conn)?;
+            // take the chance to tidy the revocation list
+            delete(RevokedSessionSchema::revoked_session)
+                .filter(RevokedSessionSchema::expiresAt.le(now))
+                .execute(conn)?;
+            Ok(true)
+        })
+    })
+    .await
+}
+
+pub async fn is_session_revoked(id: String, db: &DbConn) -> Result<bool> {
+    use crate::schema::pds::revoked_session::dsl as RevokedSessionSchema;
+
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        let found = RevokedSessionSchema::revoked_session
+            .filter(RevokedSessionSchema::id.eq(id))
+            .filter(RevokedSessionSchema::expiresAt.gt(now))
+            .select(RevokedSessionSchema::id)
+            .first::<String>(conn)
+            .optional()?;
+        Ok(found.is_some())
+    })
+    .await
+}
+
+/// Remembers that `token` has been rotated, until it would have expired.
+pub async fn mark_refresh_token_rotated(token: models::RefreshToken, db: &DbConn) -> Result<()> {
+    use crate::schema::pds::rotated_refresh_token::dsl as RotatedSchema;
+
+    let family_id = token.family_id.unwrap_or_else(|| token.id.clone());
+    db.run(move |conn| {
+        insert_into(RotatedSchema::rotated_refresh_token)
+            .values((
+                RotatedSchema::id.eq(token.id),
+                RotatedSchema::familyId.eq(family_id),
+                RotatedSchema::did.eq(token.did),
+                RotatedSchema::expiresAt.eq(token.expires_at),
+            ))
+            .on_conflict_do_nothing() // E.g. when re-granting during a refresh grace period
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
+
+/// The family and DID of a refresh token that has been rotated, if `id` was.
+pub async fn get_rotated_refresh_token(id: &str, db: &DbConn) -> Result<Option<(String, String)>> {
+    use crate::schema::pds::rotated_refresh_token::dsl as RotatedSchema;
+
+    let id = id.to_owned();
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        Ok(RotatedSchema::rotated_refresh_token
+            .filter(RotatedSchema::id.eq(id))
+            .filter(RotatedSchema::expiresAt.gt(now))
+            .select((RotatedSchema::familyId, RotatedSchema::did))
+            .first::<(String, String)>(conn)
+            .optional()?)
+    })
+    .await
+}
+
+/// Revokes every refresh token in `family_id`'s family, and puts the ids of
+/// the whole lineage on the revocation list so access tokens issued from any
+/// of them stop working too. Returns how many refresh tokens were deleted.
+pub async fn revoke_refresh_token_family(family_id: String, db: &DbConn) -> Result<usize> {
+    use crate::schema::pds::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::pds::revoked_session::dsl as RevokedSessionSchema;
+    use crate::schema::pds::rotated_refresh_token::dsl as RotatedSchema;
+
+    let revoked_until = DateTime::<Utc>::from(
+        SystemTime::now() + std::time::Duration::from_secs(ACCESS_TOKEN_EXPIRES_IN_SECS),
+    );
+    let expires_at = format!("{}", revoked_until.format(RFC3339_VARIANT));
+    db.run(move |conn| {
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            // Tokens from before families were tracked only name the first token
+            let live = RefreshTokenSchema::refresh_token
+                .filter(
+                    RefreshTokenSchema::familyId
+                        .eq(&family_id)
+                        .or(RefreshTokenSchema::id.eq(&family_id)),
+                )
+                .select((RefreshTokenSchema::id, RefreshTokenSchema::did))
+                .get_results::<(String, String)>(conn)?;
+            let rotated = RotatedSchema::rotated_refresh_token
+                .filter(RotatedSchema::familyId.eq(&family_id))
+                .select((RotatedSchema::id, RotatedSchema::did))
+                .get_results::<(String, String)>(conn)?;
+            let rows = live
+                .iter()
+                .chain(rotated.iter())
+                .map(|(id, did)| {
+                    (
+                        RevokedSessionSchema::id.eq(id.clone()),
+                        RevokedSessionSchema::did.eq(did.clone()),
+                        RevokedSessionSchema::expiresAt.eq(expires_at.clone()),
+                    )
+                })
+                .collect::<Vec<_>>();
+            insert_into(RevokedSessionSchema::revoked_session)
+                .values(rows)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            let ids = live.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+            Ok(delete(RefreshTokenSchema::refresh_token)
+                .filter(RefreshTokenSchema::id.eq_any(&ids))
+                .execute(conn)?)
+        })
+    })
+    .await
+}
+
 pub async fn revoke_refresh_tokens_by_did(did: &str, db: &DbConn) -> Result<bool> {
     use crate::schema::pds::refresh_token::dsl as RefreshTokenSchema;
     let did = did.to_owned();
@@ -298,12 +564,17 @@ pub async fn get_refresh_token(id: &str, db: &DbConn) -> Result<Option<models::R
 
 pub async fn delete_expired_refresh_tokens(did: &str, now: String, db: &DbConn) -> Result<()> {
     use crate::schema::pds::refresh_token::dsl as RefreshTokenSchema;
+    use crate::schema::pds::rotated_refresh_token::dsl as RotatedSchema;
     let did = did.to_owned();
 
     db.run(move |conn| {
         delete(RefreshTokenSchema::refresh_token)
-            .filter(RefreshTokenSchema::did.eq(did))
-            .filter(RefreshTokenSchema::expiresAt.le(now))
+            .filter(RefreshTokenSchema::did.eq(&did))
+            .filter(RefreshTokenSchema::expiresAt.le(&now))
+            .execute(conn)?;
+        delete(RotatedSchema::rotated_refresh_token)
+            .filter(RotatedSchema::did.eq(&did))
+            .filter(RotatedSchema::expiresAt.le(&now))
             .execute(conn)?;
         Ok(())
     })
@@ -343,3 +614,70 @@ pub async fn add_refresh_grace_period(opts: RefreshGracePeriodOpts, db: &DbConn)
 pub fn get_refresh_token_id() -> String {
     get_random_str()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::{Keypair, Secp256k1};
+
+    #[test]
+    fn step_up_token_is_bound_to_did_and_method() {
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret_key);
+        let jwt_keys = JwtKeyRing::new(keypair, std::time::Duration::from_secs(60));
+        let service_did = "did:web:pds.example.com".to_string();
+        let lxm = "com.atproto.identity.updateHandle";
+        let token = create_step_up_token(
+            "did:ckb:alice".to_string(),
+            lxm.to_string(),
+            service_did.clone(),
+            jwt_keys.current(),
+        )
+        .unwrap();
+        assert!(
+            verify_step_up_token(&token, "did:ckb:alice", lxm, service_did.clone(), &jwt_keys)
+                .is_ok()
+        );
+        assert!(
+            verify_step_up_token(&token, "did:ckb:bob", lxm, service_did.clone(), &jwt_keys)
+                .is_err()
+        );
+        assert!(verify_step_up_token(
+            &token,
+            "did:ckb:alice",
+            "com.atproto.server.deactivateAccount",
+            service_did,
+            &jwt_keys
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn only_oauth_access_tokens_carry_their_dpop_key() {
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret_key);
+        let jwt_keys = JwtKeyRing::new(keypair, std::time::Duration::from_secs(60));
+        let (access_jwt, refresh_jwt) = create_tokens(CreateTokensOpts {
+            did: "did:ckb:alice".to_string(),
+            jwt_key: jwt_keys.current(),
+            service_did: "did:web:pds.example.com".to_string(),
+            scope: Some(AuthScope::AppPass),
+            jti: None,
+            expires_in: None,
+            collections: None,
+            dpop_jkt: Some("jkt-alice".to_string()),
+        })
+        .unwrap();
+        let access = jwt_keys
+            .verify::<CustomClaimObj>(&access_jwt, None)
+            .unwrap();
+        assert_eq!(
+            access.custom.cnf.map(|cnf| cnf.jkt).as_deref(),
+            Some("jkt-alice")
+        );
+        let refresh = jwt_keys
+            .verify::<CustomClaimObj>(&refresh_jwt, None)
+            .unwrap();
+        assert!(refresh.custom.cnf.is_none());
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, bail, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+
+/// Argon2id costs account passwords are hashed with, oldest first. To raise
+/// them, append a version: hashes made with an earlier one keep verifying and
+/// are redone with the newest the next time their owner signs in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashParams {
+    pub version: u32,
+    /// Memory in KiB
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+pub const HASH_VERSIONS: &[HashParams] = &[
+    // argon2's defaults, which passwords were hashed with before versioning
+    HashParams {
+        version: 1,
+        m_cost: 19 * 1024,
+        t_cost: 2,
+        p_cost: 1,
+    },
+    // RFC 9106's second recommended option, now that web5 accounts can set
+    // real passwords
+    HashParams {
+        version: 2,
+        m_cost: 64 * 1024,
+        t_cost: 3,
+        p_cost: 4,
+    },
+];
+
+/// scrypt costs of passwords carried over from the TypeScript PDS, stored
+/// as `<hex salt>:<hex hash>`
+const SCRYPT_LOG_N: u8 = 14;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+const SCRYPT_KEY_LEN: usize = 64;
+
+/// How a stored password hash was made
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashScheme {
+    /// A PHC string from argon2, with the [`HashParams`] version its costs
+    /// match, if any
+    Argon2(Option<u32>),
+    Bcrypt,
+    /// `<hex salt>:<hex hash>`, as written by the TypeScript PDS
+    Scrypt,
+}
+
+impl HashScheme {
+    pub fn detect(stored_hash: &str) -> Result<Self> {
+        if stored_hash.starts_with("$argon2") {
+            let parsed = PasswordHash::new(stored_hash).map_err(|error| anyhow!("{error}"))?;
+            return Ok(HashScheme::Argon2(argon2_version(&parsed)));
+        }
+        if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| stored_hash.starts_with(prefix))
+        {
+            return Ok(HashScheme::Bcrypt);
+        }
+        match stored_hash.split_once(':') {
+            Some((salt, hash)) if is_hex(salt) && is_hex(hash) => Ok(HashScheme::Scrypt),
+            _ => bail!("unrecognized password hash"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PasswordCheck {
+    pub valid: bool,
+    /// The password matched a hash made some other way than the current
+    /// [`HashParams`], and should be hashed again while it's at hand
+    pub needs_rehash: bool,
+}
+
+fn current_params() -> &'static HashParams {
+    HASH_VERSIONS.last().expect("at least one hash version")
+}
+
+fn hasher(params: &HashParams) -> Result<Argon2<'static>> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, None)
+        .map_err(|error| anyhow!("{error}"))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+/// The [`HashParams`] version an argon2id hash was made with
+fn argon2_version(parsed: &PasswordHash<'_>) -> Option<u32> {
+    if parsed.algorithm != Algorithm::Argon2id.ident() || parsed.version != Some(0x13) {
+        return None;
+    }
+    let params = Params::try_from(parsed).ok()?;
+    HASH_VERSIONS
+        .iter()
+        .find(|version| {
+            params.m_cost() == version.m_cost
+                && params.t_cost() == version.t_cost
+                && params.p_cost() == version.p_cost
+        })
+        .map(|version| version.version)
+}
+
+fn is_hex(value: &str) -> bool {
+    !value.is_empty() && value.len() % 2 == 0 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Hashes a password with the newest [`HashParams`]
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(hasher(current_params())?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|error| anyhow!("{error}"))?
+        .to_string())
+}
+
+/// Checks a password against a stored hash of any [`HashScheme`]
+pub fn check_password(password: &str, stored_hash: &str) -> Result<PasswordCheck> {
+    let scheme = HashScheme::detect(stored_hash)?;
+    let valid = match scheme {
+        HashScheme::Argon2(_) => {
+            let parsed = PasswordHash::new(stored_hash).map_err(|error| anyhow!("{error}"))?;
+            // The costs and variant are read from the hash itself
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        }
+        HashScheme::Bcrypt => bcrypt::verify(password, stored_hash)?,
+        HashScheme::Scrypt => check_scrypt(password, stored_hash)?,
+    };
+    Ok(PasswordCheck {
+        valid,
+        needs_rehash: valid && scheme != HashScheme::Argon2(Some(current_params().version)),
+    })
+}
+
+fn check_scrypt(password: &str, stored_hash: &str) -> Result<bool> {
+    let (salt, hash) = stored_hash
+        .split_once(':')
+        .ok_or_else(|| anyhow!("malformed scrypt hash"))?;
+    let expected = hex::decode(hash)?;
+    let params = scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, SCRYPT_KEY_LEN)
+        .map_err(|error| anyhow!("{error}"))?;
+    let mut derived = vec![0u8; expected.len()];
+    // The TypeScript PDS salts with the hex string itself, not its bytes
+    scrypt::scrypt(password.as_bytes(), salt.as_bytes(), &params, &mut derived)
+        .map_err(|error| anyhow!("{error}"))?;
+    // Compare without stopping at the first difference
+    Ok(derived.len() == expected.len()
+        && derived
+            .iter()
+            .zip(expected.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_hashes_need_no_rehash() {
+        let hash = hash_password("correct horse").unwrap();
+        assert_eq!(
+            HashScheme::detect(&hash).unwrap(),
+            HashScheme::Argon2(Some(current_params().version))
+        );
+        let check = check_password("correct horse", &hash).unwrap();
+        assert!(check.valid && !check.needs_rehash);
+        assert!(!check_password("battery staple", &hash).unwrap().valid);
+    }
+
+    #[test]
+    fn older_hashes_verify_and_ask_for_a_rehash() {
+        let salt = SaltString::generate(&mut OsRng);
+        let v1 = hasher(&HASH_VERSIONS[0])
+            .unwrap()
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            HashScheme::detect(&v1).unwrap(),
+            HashScheme::Argon2(Some(1))
+        );
+        let check = check_password("correct horse", &v1).unwrap();
+        assert!(check.valid && check.needs_rehash);
+
+        let bcrypt = bcrypt::hash("correct horse", 4).unwrap();
+        assert_eq!(HashScheme::detect(&bcrypt).unwrap(), HashScheme::Bcrypt);
+        let check = check_password("correct horse", &bcrypt).unwrap();
+        assert!(check.valid && check.needs_rehash);
+    }
+
+    #[test]
+    fn wrong_passwords_never_ask_for_a_rehash() {
+        let bcrypt = bcrypt::hash("correct horse", 4).unwrap();
+        let check = check_password("battery staple", &bcrypt).unwrap();
+        assert!(!check.valid && !check.needs_rehash);
+        assert!(HashScheme::detect("plaintext").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::db::DbConn;
+use crate::models::EmailNotificationPref;
+use anyhow::Result;
+use diesel::*;
+use rsky_lexicon::com::atproto::web5::EmailNotificationPrefs;
+
+pub async fn get_email_notification_prefs(
+    did: &str,
+    db: &DbConn,
+) -> Result<EmailNotificationPrefs> {
+    use crate::schema::pds::email_notification_pref::dsl as EmailPrefSchema;
+
+    let did = did.to_owned();
+    db.run(move |conn| {
+        let pref = EmailPrefSchema::email_notification_pref
+            .find(did)
+            .select(EmailNotificationPref::as_select())
+            .first(conn)
+            .optional()?;
+        Ok(match pref {
+            Some(pref) => EmailNotificationPrefs {
+                replies: pref.replies,
+                mentions: pref.mentions,
+            },
+            None => EmailNotificationPrefs {
+                replies: true,
+                mentions: true,
+            },
+        })
+    })
+    .await
+}
+
+pub async fn put_email_notification_prefs(
+    did: &str,
+    prefs: EmailNotificationPrefs,
+    db: &DbConn,
+) -> Result<()> {
+    use crate::schema::pds::email_notification_pref::dsl as EmailPrefSchema;
+
+    let did = did.to_owned();
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        insert_into(EmailPrefSchema::email_notification_pref)
+            .values((
+                EmailPrefSchema::did.eq(did),
+                EmailPrefSchema::replies.eq(prefs.replies),
+                EmailPrefSchema::mentions.eq(prefs.mentions),
+                EmailPrefSchema::updatedAt.eq(&now),
+            ))
+            .on_conflict(EmailPrefSchema::did)
+            .do_update()
+            .set((
+                EmailPrefSchema::replies.eq(prefs.replies),
+                EmailPrefSchema::mentions.eq(prefs.mentions),
+                EmailPrefSchema::updatedAt.eq(&now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
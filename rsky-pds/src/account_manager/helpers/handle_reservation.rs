@@ -0,0 +1,75 @@
+use crate::db::DbConn;
+use crate::models::models::HandleReservation;
+use anyhow::Result;
+use diesel::*;
+
+/// The reservation holding `handle` at `now`, if one hasn't expired
+pub async fn get_reservation(
+    handle: &str,
+    now: String,
+    db: &DbConn,
+) -> Result<Option<HandleReservation>> {
+    use crate::schema::pds::handle_reservation::dsl as HandleReservationSchema;
+
+    let handle = handle.to_owned();
+    db.run(move |conn| {
+        let reservation = HandleReservationSchema::handle_reservation
+            .filter(HandleReservationSchema::handle.eq(handle))
+            .filter(HandleReservationSchema::expiresAt.gt(now))
+            .select(HandleReservation::as_select())
+            .first(conn)
+            .optional()?;
+        Ok(reservation)
+    })
+    .await
+}
+
+/// Reserves the handle unless someone else already holds it, returning
+/// whether `reservation` is now the one in force. Reserving again for the same
+/// chain address extends the window. Expired reservations are pruned.
+pub async fn reserve_handle(
+    reservation: HandleReservation,
+    now: String,
+    db: &DbConn,
+) -> Result<bool> {
+    use crate::schema::pds::handle_reservation::dsl as HandleReservationSchema;
+
+    db.run(move |conn| {
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            delete(HandleReservationSchema::handle_reservation)
+                .filter(HandleReservationSchema::expiresAt.le(now))
+                .execute(conn)?;
+            let held_by = HandleReservationSchema::handle_reservation
+                .filter(HandleReservationSchema::handle.eq(reservation.handle.clone()))
+                .select(HandleReservationSchema::ckbAddress)
+                .for_update()
+                .first::<String>(conn)
+                .optional()?;
+            if matches!(held_by, Some(ref address) if *address != reservation.ckb_address) {
+                return Ok(false);
+            }
+            insert_into(HandleReservationSchema::handle_reservation)
+                .values(&reservation)
+                .on_conflict(HandleReservationSchema::handle)
+                .do_update()
+                .set(&reservation)
+                .execute(conn)?;
+            Ok(true)
+        })
+    })
+    .await
+}
+
+/// Drops the reservation once the handle belongs to an account
+pub async fn release_handle(handle: &str, db: &DbConn) -> Result<()> {
+    use crate::schema::pds::handle_reservation::dsl as HandleReservationSchema;
+
+    let handle = handle.to_owned();
+    db.run(move |conn| {
+        delete(HandleReservationSchema::handle_reservation)
+            .filter(HandleReservationSchema::handle.eq(handle))
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
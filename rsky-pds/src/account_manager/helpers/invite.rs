@@ -1,3 +1,4 @@
+use crate::account_manager::helpers::audit::{insert_audit_event, AuditAction, AuditEvent};
 use crate::account_manager::DisableInviteCodesOpts;
 use crate::db::DbConn;
 use crate::models::models;
@@ -8,6 +9,7 @@ use rsky_lexicon::com::atproto::server::AccountCodes;
 use rsky_lexicon::com::atproto::server::{
     InviteCode as LexiconInviteCode, InviteCodeUse as LexiconInviteCodeUse,
 };
+use serde_json::json;
 use std::collections::BTreeMap;
 use std::mem;
 
@@ -59,19 +61,43 @@ pub async fn record_invite_use(
         use crate::schema::pds::invite_code_use::dsl as InviteCodeUseSchema;
 
         db.run(move |conn| {
-            insert_into(InviteCodeUseSchema::invite_code_use)
-                .values((
-                    InviteCodeUseSchema::code.eq(invite_code),
-                    InviteCodeUseSchema::usedBy.eq(did),
-                    InviteCodeUseSchema::usedAt.eq(now),
-                ))
-                .execute(conn)
+            conn.transaction(|conn| {
+                insert_into(InviteCodeUseSchema::invite_code_use)
+                    .values((
+                        InviteCodeUseSchema::code.eq(&invite_code),
+                        InviteCodeUseSchema::usedBy.eq(&did),
+                        InviteCodeUseSchema::usedAt.eq(now),
+                    ))
+                    .execute(conn)?;
+                insert_audit_event(
+                    conn,
+                    AuditEvent {
+                        detail: Some(json!({ "code": invite_code })),
+                        ..AuditEvent::new(AuditAction::InviteCodeUse, Some(did.clone()), did)
+                    },
+                )
+            })
         })
         .await?;
     }
     Ok(())
 }
 
+/// Frees the invite `did` signed up with, for an account creation that was
+/// rolled back
+pub async fn release_invite_use(did: &str, db: &DbConn) -> Result<()> {
+    use crate::schema::pds::invite_code_use::dsl as InviteCodeUseSchema;
+
+    let did = did.to_owned();
+    db.run(move |conn| {
+        delete(InviteCodeUseSchema::invite_code_use)
+            .filter(InviteCodeUseSchema::usedBy.eq(did))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
 pub async fn create_invite_codes(
     to_create: Vec<AccountCodes>,
     use_count: i32,
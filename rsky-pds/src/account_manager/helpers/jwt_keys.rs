@@ -0,0 +1,383 @@
+use crate::db::establish_connection_for_jobs;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{insert_into, update};
+use jwt_simple::prelude::*;
+use lazy_static::lazy_static;
+use rsky_common::env::{env_int, env_list, env_str};
+use rsky_common::RFC3339_VARIANT;
+use secp256k1::{Keypair, Secp256k1, SecretKey};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+/// How long a rotated out key still verifies tokens, unless PDS_JWT_KEY_GRACE_PERIOD_SECS
+/// says otherwise. Sessions refreshed within it move over to the new key.
+pub const DEFAULT_JWT_KEY_GRACE_PERIOD_SECS: u64 = 7 * 24 * 60 * 60;
+/// How often every replica reloads the keys rotated in through the database
+pub const JWT_KEY_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+/// How long after its rotation a key starts signing, by when every replica
+/// has loaded it and verifies what it signs
+pub const JWT_KEY_ACTIVATION_DELAY: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// Keys for access, refresh and step-up tokens: PDS_JWT_KEY_K256_PRIVATE_KEY_HEX signs,
+    /// the comma separated PDS_JWT_PREVIOUS_KEYS_K256_PRIVATE_KEY_HEX still verify, until
+    /// keys are rotated in through the database.
+    pub static ref JWT_KEYS: JwtKeyRing =
+        JwtKeyRing::from_env().expect("JWT keys are not configured correctly");
+}
+
+/// A key tokens are signed or verified with, named by the `kid` in their header.
+#[derive(Clone, Debug)]
+pub struct JwtKey {
+    pub kid: String,
+    pub keypair: Keypair,
+    /// When the key started signing, unset for a key from the env
+    pub active_at: Option<SystemTime>,
+    /// When the key stopped signing, unset for the current key
+    pub retired_at: Option<SystemTime>,
+}
+
+impl JwtKey {
+    pub fn new(keypair: Keypair) -> Self {
+        JwtKey {
+            kid: key_id(&keypair),
+            keypair,
+            active_at: None,
+            retired_at: None,
+        }
+    }
+
+    /// The key in jwt_simple's form, tagged with its `kid`.
+    pub fn signing_key(&self) -> Result<ES256kKeyPair> {
+        Ok(
+            ES256kKeyPair::from_bytes(self.keypair.secret_bytes().as_slice())?
+                .with_key_id(&self.kid),
+        )
+    }
+
+    fn active_since(&self) -> SystemTime {
+        self.active_at.unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+/// Derives the `kid` from the public key, so every replica names a key the same way.
+pub fn key_id(keypair: &Keypair) -> String {
+    let hash = Sha256::digest(keypair.public_key().serialize());
+    hex::encode(&hash[..8])
+}
+
+pub fn parse_key(private_key_hex: &str) -> Result<Keypair> {
+    let secret_key = SecretKey::from_slice(&hex::decode(private_key_hex.trim().as_bytes())?)?;
+    Ok(Keypair::from_secret_key(&Secp256k1::new(), &secret_key))
+}
+
+fn format_time(time: SystemTime) -> String {
+    format!("{}", DateTime::<Utc>::from(time).format(RFC3339_VARIANT))
+}
+
+fn parse_time(value: &str) -> Result<SystemTime> {
+    Ok(DateTime::parse_from_rfc3339(value)?
+        .with_timezone(&Utc)
+        .into())
+}
+
+/// The current signing key and the retired keys still inside their grace period.
+pub struct JwtKeyRing {
+    /// Keys from the env layered under the stored ones, newest first
+    keys: RwLock<Vec<JwtKey>>,
+    /// Keys from the env, the first one signing until a key is rotated in
+    configured: Vec<JwtKey>,
+    grace_period: Duration,
+}
+
+impl JwtKeyRing {
+    pub fn new(current: Keypair, grace_period: Duration) -> Self {
+        Self::with_configured(vec![JwtKey::new(current)], grace_period)
+    }
+
+    fn with_configured(configured: Vec<JwtKey>, grace_period: Duration) -> Self {
+        JwtKeyRing {
+            keys: RwLock::new(configured.clone()),
+            configured,
+            grace_period,
+        }
+    }
+
+    /// Previous keys from the env count as retired at startup, so their grace
+    /// period runs from the restart that rotated them out.
+    pub fn from_env() -> Result<Self> {
+        let current = env_str("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX")
+            .ok_or_else(|| anyhow!("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX is unset"))?;
+        let grace_period = env_int("PDS_JWT_KEY_GRACE_PERIOD_SECS")
+            .map(|secs| secs as u64)
+            .unwrap_or(DEFAULT_JWT_KEY_GRACE_PERIOD_SECS);
+        let mut configured = vec![JwtKey::new(parse_key(&current)?)];
+        let now = SystemTime::now();
+        for previous in env_list("PDS_JWT_PREVIOUS_KEYS_K256_PRIVATE_KEY_HEX") {
+            if previous.trim().is_empty() {
+                continue;
+            }
+            let key = JwtKey {
+                retired_at: Some(now),
+                ..JwtKey::new(parse_key(&previous)?)
+            };
+            if configured.iter().all(|existing| existing.kid != key.kid) {
+                configured.push(key);
+            }
+        }
+        Ok(Self::with_configured(
+            configured,
+            Duration::from_secs(grace_period),
+        ))
+    }
+
+    /// The key new tokens are signed with: the latest to have become active.
+    pub fn current(&self) -> JwtKey {
+        let now = SystemTime::now();
+        let keys = self.keys.read().expect("jwt keys poisoned");
+        keys.iter()
+            .filter(|key| key.active_since() <= now)
+            .max_by_key(|key| key.active_since())
+            .unwrap_or(&keys[0])
+            .clone()
+    }
+
+    /// The current key, the retired keys that still verify, and rotated in
+    /// keys that will start signing shortly.
+    pub fn keys(&self) -> Vec<JwtKey> {
+        let now = SystemTime::now();
+        self.keys
+            .read()
+            .expect("jwt keys poisoned")
+            .iter()
+            .filter(|key| self.is_usable(key, now))
+            .cloned()
+            .collect()
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// When a retired key stops verifying tokens.
+    pub fn verifiable_until(&self, key: &JwtKey) -> Option<SystemTime> {
+        key.retired_at
+            .map(|retired_at| retired_at + self.grace_period)
+    }
+
+    fn is_usable(&self, key: &JwtKey, now: SystemTime) -> bool {
+        match self.verifiable_until(key) {
+            Some(until) => until > now,
+            None => true,
+        }
+    }
+
+    /// Layers the keys rotated in through the database over the env's. The
+    /// env's signing key retired when the first of them became active.
+    pub fn load(&self, stored: Vec<JwtKey>) {
+        let now = SystemTime::now();
+        let first_rotation = stored.iter().filter_map(|key| key.active_at).min();
+        let mut keys = stored;
+        for key in self.configured.iter() {
+            if keys.iter().any(|existing| existing.kid == key.kid) {
+                continue;
+            }
+            keys.push(JwtKey {
+                retired_at: key.retired_at.or(first_rotation),
+                ..key.clone()
+            });
+        }
+        keys.retain(|key| self.is_usable(key, now));
+        keys.sort_by_key(|key| std::cmp::Reverse(key.active_since()));
+        *self.keys.write().expect("jwt keys poisoned") = keys;
+    }
+
+    /// Verifies `token` with the key its `kid` names. Tokens issued before
+    /// keys had ids are tried against every key still in use.
+    pub fn verify<C: Serialize + DeserializeOwned>(
+        &self,
+        token: &str,
+        options: Option<VerificationOptions>,
+    ) -> Result<JWTClaims<C>> {
+        let metadata = Token::decode_metadata(token)?;
+        let keys = self.keys();
+        let candidates: Vec<&JwtKey> = match metadata.key_id() {
+            Some(kid) => keys.iter().filter(|key| key.kid == kid).collect(),
+            None => keys.iter().collect(),
+        };
+        let mut last_error = anyhow!("token was signed with an unknown or expired key");
+        for key in candidates {
+            let public_key = key.signing_key()?.public_key();
+            match public_key.verify_token::<C>(token, options.clone()) {
+                Ok(claims) => return Ok(claims),
+                Err(error) => last_error = error,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+/// Every key rotated in through the database. One is stored per rotation, and
+/// the first says when the env's key retired, so none are deleted.
+pub fn load_stored_keys(conn: &mut PgConnection) -> Result<Vec<JwtKey>> {
+    use crate::schema::pds::jwt_key::dsl as JwtKeySchema;
+
+    let rows: Vec<(String, String, Option<String>)> = JwtKeySchema::jwt_key
+        .select((
+            JwtKeySchema::privateKeyHex,
+            JwtKeySchema::activeAt,
+            JwtKeySchema::retiredAt,
+        ))
+        .load(conn)?;
+    rows.into_iter()
+        .map(|(private_key_hex, active_at, retired_at)| {
+            Ok(JwtKey {
+                active_at: Some(parse_time(&active_at)?),
+                retired_at: retired_at.as_deref().map(parse_time).transpose()?,
+                ..JwtKey::new(parse_key(&private_key_hex)?)
+            })
+        })
+        .collect()
+}
+
+/// Generates a key and stores it to start signing once every replica has
+/// loaded it, retiring the signing key then. Returns the new key.
+pub fn rotate_stored_key(conn: &mut PgConnection) -> Result<JwtKey> {
+    use crate::schema::pds::jwt_key::dsl as JwtKeySchema;
+
+    let keypair = Keypair::new(&Secp256k1::new(), &mut rand::thread_rng());
+    let active_at = SystemTime::now() + JWT_KEY_ACTIVATION_DELAY;
+    let next = JwtKey {
+        active_at: Some(active_at),
+        ..JwtKey::new(keypair)
+    };
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        update(JwtKeySchema::jwt_key)
+            .filter(JwtKeySchema::retiredAt.is_null())
+            .set(JwtKeySchema::retiredAt.eq(format_time(active_at)))
+            .execute(conn)?;
+        insert_into(JwtKeySchema::jwt_key)
+            .values((
+                JwtKeySchema::kid.eq(&next.kid),
+                JwtKeySchema::privateKeyHex.eq(hex::encode(next.keypair.secret_bytes())),
+                JwtKeySchema::activeAt.eq(format_time(active_at)),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+    tracing::info!("Rotated JWT signing key to {}", next.kid);
+    Ok(next)
+}
+
+/// Reloads [`JWT_KEYS`] from the database.
+pub async fn reload_jwt_keys() -> Result<()> {
+    let stored = tokio::task::spawn_blocking(|| {
+        let conn = &mut establish_connection_for_jobs()?;
+        load_stored_keys(conn)
+    })
+    .await??;
+    JWT_KEYS.load(stored);
+    Ok(())
+}
+
+/// Keeps every replica's [`JWT_KEYS`] in step with the keys rotated in
+/// through the database.
+pub struct JwtKeyReloader;
+
+impl JwtKeyReloader {
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(JWT_KEY_RELOAD_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(error) = reload_jwt_keys().await {
+                tracing::error!("@LOG: ERROR: failed to reload JWT keys: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keypair(byte: u8) -> Keypair {
+        parse_key(&hex::encode([byte; 32])).unwrap()
+    }
+
+    fn sign(key: &JwtKey) -> String {
+        let claims = Claims::create(jwt_simple::prelude::Duration::from_secs(60))
+            .with_subject("did:ckb:alice");
+        key.signing_key().unwrap().sign(claims).unwrap()
+    }
+
+    fn stored(byte: u8, active_at: SystemTime, retired_at: Option<SystemTime>) -> JwtKey {
+        JwtKey {
+            active_at: Some(active_at),
+            retired_at,
+            ..JwtKey::new(keypair(byte))
+        }
+    }
+
+    #[test]
+    fn retired_keys_verify_until_the_grace_period_ends() {
+        let now = SystemTime::now();
+        let ring = JwtKeyRing::new(keypair(1), Duration::from_secs(60));
+        let old_token = sign(&ring.current());
+        ring.load(vec![stored(2, now - Duration::from_secs(1), None)]);
+        assert_eq!(ring.current().kid, key_id(&keypair(2)));
+        let new_token = sign(&ring.current());
+        assert!(ring.verify::<NoCustomClaims>(&old_token, None).is_ok());
+        assert!(ring.verify::<NoCustomClaims>(&new_token, None).is_ok());
+
+        let expired = JwtKeyRing::new(keypair(1), Duration::ZERO);
+        let old_token = sign(&expired.current());
+        expired.load(vec![stored(2, now - Duration::from_secs(1), None)]);
+        assert!(expired.verify::<NoCustomClaims>(&old_token, None).is_err());
+        assert_eq!(expired.keys().len(), 1);
+    }
+
+    #[test]
+    fn tokens_without_kid_try_every_key() {
+        let ring = JwtKeyRing::new(keypair(1), Duration::from_secs(60));
+        let legacy = ES256kKeyPair::from_bytes(&[1u8; 32])
+            .unwrap()
+            .sign(Claims::create(jwt_simple::prelude::Duration::from_secs(60)))
+            .unwrap();
+        ring.load(vec![stored(
+            2,
+            SystemTime::now() - Duration::from_secs(1),
+            None,
+        )]);
+        assert!(ring.verify::<NoCustomClaims>(&legacy, None).is_ok());
+    }
+
+    #[test]
+    fn rotated_in_keys_verify_before_they_sign() {
+        let ring = JwtKeyRing::new(keypair(1), Duration::from_secs(60));
+        let pending = stored(2, SystemTime::now() + Duration::from_secs(60), None);
+        let early_token = sign(&pending);
+        ring.load(vec![pending]);
+        assert_eq!(ring.current().kid, key_id(&keypair(1)));
+        assert!(ring.verify::<NoCustomClaims>(&early_token, None).is_ok());
+    }
+
+    #[test]
+    fn the_env_key_retires_at_the_first_rotation() {
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let ring = JwtKeyRing::new(keypair(1), Duration::from_secs(60 * 60));
+        ring.load(vec![
+            stored(2, now - 2 * day, Some(now - day)),
+            stored(3, now - day, None),
+        ]);
+        let keys = ring.keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].kid, key_id(&keypair(3)));
+        assert_eq!(ring.current().kid, key_id(&keypair(3)));
+    }
+}
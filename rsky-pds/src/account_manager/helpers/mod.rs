@@ -1,6 +1,14 @@
 pub mod account;
+pub mod account_cache;
+pub mod audit;
 pub mod auth;
+pub mod credentials;
+pub mod email_pref;
 pub mod email_token;
+pub mod handle_reservation;
 pub mod invite;
+pub mod jwt_keys;
 pub mod password;
 pub mod repo;
+pub mod signup;
+pub mod usage;
@@ -1,9 +1,11 @@
+use crate::account_manager::helpers::credentials;
 use crate::db::DbConn;
 use crate::models;
 use crate::models::AppPassword;
+use crate::repo::prepare::validate_collection_pattern;
 use anyhow::{anyhow, bail, Result};
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    password_hash::{PasswordHasher, SaltString},
     Argon2,
 };
 use base64ct::{Base64, Encoding};
@@ -30,11 +32,26 @@ pub async fn verify_account_password(did: &str, password: &String, db: &DbConn)
                 .optional()
         })
         .await?;
-    if let Some(found) = found {
-        verify(password, &found.password)
-    } else {
-        Ok(false)
+    let Some(found) = found else {
+        return Ok(false);
+    };
+    let check = credentials::check_password(password, &found.password)?;
+    if check.needs_rehash {
+        // The password is at hand, so move it to the current hash parameters
+        match credentials::hash_password(password) {
+            Ok(password_encrypted) => {
+                let opts = UpdateUserPasswordOpts {
+                    did: found.did,
+                    password_encrypted,
+                };
+                if let Err(error) = update_user_password(opts, db).await {
+                    tracing::error!("failed to rehash password: {error}");
+                }
+            }
+            Err(error) => tracing::error!("failed to rehash password: {error}"),
+        }
     }
+    Ok(check.valid)
 }
 
 pub async fn verify_app_password(did: &str, password: &str, db: &DbConn) -> Result<Option<String>> {
@@ -60,16 +77,10 @@ pub async fn verify_app_password(did: &str, password: &str, db: &DbConn) -> Resu
     }
 }
 
-// We use Argon because it's 3x faster than scrypt.
+/// Hashes an account password with the current argon2id parameters, see
+/// [`credentials::HASH_VERSIONS`]
 pub fn gen_salt_and_hash(password: String) -> Result<String> {
-    let salt = SaltString::generate(&mut OsRng);
-    // Hash password to PHC string
-    let argon2 = Argon2::default();
-    let password_hash = argon2
-        .hash_password(password.as_ref(), &salt)
-        .map_err(|error| anyhow!(error.to_string()))?
-        .to_string();
-    Ok(password_hash)
+    credentials::hash_password(&password)
 }
 
 pub fn hash_with_salt(password: &String, salt: &str) -> Result<String> {
@@ -83,10 +94,7 @@ pub fn hash_with_salt(password: &String, salt: &str) -> Result<String> {
 }
 
 pub fn verify(password: &String, stored_hash: &str) -> Result<bool> {
-    let parsed_hash = PasswordHash::new(stored_hash).map_err(|error| anyhow!(error.to_string()))?;
-    Ok(Argon2::default()
-        .verify_password(password.as_ref(), &parsed_hash)
-        .is_ok())
+    Ok(credentials::check_password(password, stored_hash)?.valid)
 }
 
 pub async fn hash_app_password(did: &String, password: &String) -> Result<String> {
@@ -97,11 +105,21 @@ pub async fn hash_app_password(did: &String, password: &String) -> Result<String
 
 /// create an app password with format:
 /// 1234-abcd-5678-efgh
+/// restricted to writing `collections` when given
 pub async fn create_app_password(
     did: String,
     name: String,
+    collections: Option<Vec<String>>,
     db: &DbConn,
 ) -> Result<CreateAppPasswordOutput> {
+    if let Some(ref collections) = collections {
+        if collections.is_empty() {
+            bail!("collections may not be empty")
+        }
+        for pattern in collections {
+            validate_collection_pattern(pattern)?;
+        }
+    }
     let str = &get_random_str()[0..16].to_lowercase();
     let chunks = [&str[0..4], &str[4..8], &str[8..12], &str[12..16]];
     let password = chunks.join("-");
@@ -118,6 +136,7 @@ pub async fn create_app_password(
                 AppPasswordSchema::name.eq(&name),
                 AppPasswordSchema::password.eq(password_encrypted),
                 AppPasswordSchema::createdAt.eq(&created_at),
+                AppPasswordSchema::collections.eq(collections.as_ref().map(|c| c.join(","))),
             ))
             .returning(AppPassword::as_select())
             .get_result(conn)
@@ -127,6 +146,7 @@ pub async fn create_app_password(
                 name,
                 password,
                 created_at,
+                collections,
             })
         } else {
             bail!("could not create app-specific password")
@@ -135,17 +155,63 @@ pub async fn create_app_password(
     .await
 }
 
-pub async fn list_app_passwords(did: &str, db: &DbConn) -> Result<Vec<(String, String)>> {
+/// Name, creation time and collections of each of the account's app passwords
+pub async fn list_app_passwords(
+    did: &str,
+    db: &DbConn,
+) -> Result<Vec<(String, String, Option<Vec<String>>)>> {
     use crate::schema::pds::app_password::dsl as AppPasswordSchema;
 
     let did = did.to_owned();
-    db.run(move |conn| {
-        Ok(AppPasswordSchema::app_password
-            .filter(AppPasswordSchema::did.eq(did))
-            .select((AppPasswordSchema::name, AppPasswordSchema::createdAt))
-            .get_results(conn)?)
+    let found = db
+        .run(move |conn| {
+            AppPasswordSchema::app_password
+                .filter(AppPasswordSchema::did.eq(did))
+                .select(AppPassword::as_select())
+                .get_results(conn)
+        })
+        .await?;
+    Ok(found
+        .into_iter()
+        .map(|app_password| {
+            let collections = parse_collections(app_password.collections);
+            (app_password.name, app_password.created_at, collections)
+        })
+        .collect())
+}
+
+/// The collections app password `name` may write to, `None` when unrestricted
+/// or when the password no longer exists.
+pub async fn get_app_password_collections(
+    did: &str,
+    name: &str,
+    db: &DbConn,
+) -> Result<Option<Vec<String>>> {
+    use crate::schema::pds::app_password::dsl as AppPasswordSchema;
+
+    let did = did.to_owned();
+    let name = name.to_owned();
+    let found = db
+        .run(move |conn| {
+            AppPasswordSchema::app_password
+                .filter(AppPasswordSchema::did.eq(did))
+                .filter(AppPasswordSchema::name.eq(name))
+                .select(AppPasswordSchema::collections)
+                .first::<Option<String>>(conn)
+                .optional()
+        })
+        .await?;
+    Ok(parse_collections(found.flatten()))
+}
+
+fn parse_collections(collections: Option<String>) -> Option<Vec<String>> {
+    collections.map(|collections| {
+        collections
+            .split(',')
+            .filter(|pattern| !pattern.is_empty())
+            .map(str::to_owned)
+            .collect()
     })
-    .await
 }
 
 pub async fn update_user_password(opts: UpdateUserPasswordOpts, db: &DbConn) -> Result<()> {
@@ -161,6 +227,50 @@ pub async fn update_user_password(opts: UpdateUserPasswordOpts, db: &DbConn) ->
     .await
 }
 
+/// Sets whether a web5 account may sign in with its password, replacing the
+/// password when one is given.
+pub async fn update_password_login(
+    did: String,
+    password_encrypted: Option<String>,
+    password_login: bool,
+    db: &DbConn,
+) -> Result<()> {
+    use crate::schema::pds::account::dsl as AccountSchema;
+
+    db.run(move |conn| {
+        conn.transaction::<_, anyhow::Error, _>(|conn| {
+            if let Some(password_encrypted) = password_encrypted {
+                update(AccountSchema::account)
+                    .filter(AccountSchema::did.eq(&did))
+                    .set(AccountSchema::password.eq(password_encrypted))
+                    .execute(conn)?;
+            }
+            update(AccountSchema::account)
+                .filter(AccountSchema::did.eq(&did))
+                .set(AccountSchema::passwordLogin.eq(password_login))
+                .execute(conn)?;
+            Ok(())
+        })
+    })
+    .await
+}
+
+pub async fn password_login_enabled(did: &str, db: &DbConn) -> Result<bool> {
+    use crate::schema::pds::account::dsl as AccountSchema;
+
+    let did = did.to_owned();
+    let enabled = db
+        .run(move |conn| {
+            AccountSchema::account
+                .filter(AccountSchema::did.eq(did))
+                .select(AccountSchema::passwordLogin)
+                .first::<bool>(conn)
+                .optional()
+        })
+        .await?;
+    Ok(enabled.unwrap_or(false))
+}
+
 pub async fn delete_app_password(did: &str, name: &str, db: &DbConn) -> Result<()> {
     use crate::schema::pds::app_password::dsl as AppPasswordSchema;
 
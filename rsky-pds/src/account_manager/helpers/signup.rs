@@ -0,0 +1,49 @@
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::*;
+
+/// Accounts created from `ip` since `since`
+pub async fn count_signups_from_ip(ip: &str, since: String, db: &DbConn) -> Result<i64> {
+    use crate::schema::pds::signup_ip::dsl as SignupIpSchema;
+
+    let ip = ip.to_owned();
+    db.run(move |conn| {
+        let count = SignupIpSchema::signup_ip
+            .filter(SignupIpSchema::ip.eq(ip))
+            .filter(SignupIpSchema::createdAt.gt(since))
+            .count()
+            .get_result::<i64>(conn)?;
+        Ok(count)
+    })
+    .await
+}
+
+/// Records the address an account was created from, pruning rows older than `prune_before`.
+pub async fn record_signup_ip(
+    did: &str,
+    ip: &str,
+    prune_before: String,
+    db: &DbConn,
+) -> Result<()> {
+    use crate::schema::pds::signup_ip::dsl as SignupIpSchema;
+
+    let did = did.to_owned();
+    let ip = ip.to_owned();
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        insert_into(SignupIpSchema::signup_ip)
+            .values((
+                SignupIpSchema::did.eq(did),
+                SignupIpSchema::ip.eq(ip),
+                SignupIpSchema::createdAt.eq(now),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        // take the chance to tidy addresses that no longer count towards a cap
+        delete(SignupIpSchema::signup_ip)
+            .filter(SignupIpSchema::createdAt.lt(prune_before))
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,46 @@
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::dsl::sum;
+use diesel::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StorageUsage {
+    pub blob_bytes: i64,
+    pub repo_bytes: i64,
+}
+
+impl StorageUsage {
+    pub fn total_bytes(&self) -> i64 {
+        self.blob_bytes + self.repo_bytes
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Storage quota exceeded: {used} of {quota} bytes used")]
+pub struct StorageQuotaError {
+    pub used: i64,
+    pub quota: i64,
+}
+
+/// Bytes held by an account across its blobs and repo blocks
+pub async fn get_storage_usage(did: &str, db: &DbConn) -> Result<StorageUsage> {
+    use crate::schema::pds::blob::dsl as BlobSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let did = did.to_owned();
+    db.run(move |conn| {
+        let blob_bytes = BlobSchema::blob
+            .filter(BlobSchema::did.eq(&did))
+            .select(sum(BlobSchema::size))
+            .first::<Option<i64>>(conn)?;
+        let repo_bytes = RepoBlockSchema::repo_block
+            .filter(RepoBlockSchema::did.eq(&did))
+            .select(sum(RepoBlockSchema::size))
+            .first::<Option<i64>>(conn)?;
+        Ok(StorageUsage {
+            blob_bytes: blob_bytes.unwrap_or(0),
+            repo_bytes: repo_bytes.unwrap_or(0),
+        })
+    })
+    .await
+}
@@ -1,20 +1,27 @@
+use crate::account_events::{AccountEvent, ACCOUNT_EVENTS};
 use crate::account_manager::helpers::account::{
     AccountStatus, ActorAccount, AvailabilityFlags, GetAccountAdminStatusOutput,
 };
+use crate::account_manager::helpers::audit::{AuditAction, AuditEvent};
 use crate::account_manager::helpers::auth::{
     AuthHelperError, CreateTokensOpts, RefreshGracePeriodOpts,
 };
 use crate::account_manager::helpers::invite::CodeDetail;
+use crate::account_manager::helpers::jwt_keys::JWT_KEYS;
 use crate::account_manager::helpers::password::UpdateUserPasswordOpts;
 use crate::account_manager::helpers::repo;
+use crate::account_manager::helpers::usage::{StorageQuotaError, StorageUsage};
 use crate::auth_verifier::AuthScope;
 use crate::db::DbConn;
-use crate::models::models::EmailTokenPurpose;
+use crate::models::models::{EmailTokenPurpose, HandleReservation};
 use anyhow::Result;
 use chrono::offset::Utc as UtcOffset;
 use chrono::DateTime;
 use futures::try_join;
-use helpers::{account, auth, email_token, invite, password};
+use helpers::{
+    account, account_cache, audit, auth, email_pref, email_token, handle_reservation, invite,
+    password, signup, usage,
+};
 use lexicon_cid::Cid;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
@@ -24,7 +31,8 @@ use rsky_common::time::{from_micros_to_str, from_str_to_micros, HOUR};
 use rsky_common::RFC3339_VARIANT;
 use rsky_lexicon::com::atproto::admin::StatusAttr;
 use rsky_lexicon::com::atproto::server::{AccountCodes, CreateAppPasswordOutput};
-use secp256k1::{Keypair, Secp256k1, SecretKey};
+use rsky_lexicon::com::atproto::web5::EmailNotificationPrefs;
+use serde_json::json;
 use std::collections::BTreeMap;
 use std::env;
 use std::sync::Arc;
@@ -59,6 +67,13 @@ pub struct UpdateAccountPasswordOpts {
     pub password: String,
 }
 
+pub struct SetPasswordOpts {
+    pub did: String,
+    /// Unset keeps the current password
+    pub password: Option<String>,
+    pub password_login: bool,
+}
+
 pub struct UpdateEmailOpts {
     pub did: String,
     pub email: String,
@@ -94,6 +109,21 @@ impl AccountManager {
         account::get_account(handle_or_did, flags, db.as_ref()).await
     }
 
+    /// Accounts for `dids` keyed by DID, from one query and the account cache.
+    /// DIDs without an available account are left out.
+    pub async fn get_accounts(
+        &self,
+        dids: &[String],
+        flags: Option<AvailabilityFlags>,
+    ) -> Result<BTreeMap<String, ActorAccount>> {
+        let db = self.db.clone();
+        Ok(account::get_accounts(dids, flags, db.as_ref())
+            .await?
+            .into_iter()
+            .map(|account| (account.did.clone(), account))
+            .collect())
+    }
+
     pub async fn get_account_by_email(
         &self,
         email: &str,
@@ -148,12 +178,7 @@ impl AccountManager {
             Some(password) => Some(password::gen_salt_and_hash(password)?),
             None => None,
         };
-        // Should be a global var so this only happens once
-        let secp = Secp256k1::new();
-        let private_key = env::var("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX")?;
-        let secret_key =
-            SecretKey::from_slice(&Result::unwrap(hex::decode(private_key.as_bytes())))?;
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
+        let jwt_key = JWT_KEYS.current();
         let (access_jwt, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
             did: did.clone(),
             jwt_key,
@@ -161,8 +186,10 @@ impl AccountManager {
             scope: Some(AuthScope::Access),
             jti: None,
             expires_in: None,
+            collections: None,
+            dpop_jkt: None,
         })?;
-        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
+        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), &JWT_KEYS)?;
         let now = rsky_common::now();
 
         if let Some(invite_code) = invite_code.clone() {
@@ -173,7 +200,7 @@ impl AccountManager {
             account::register_account(did.clone(), email, password_encrypted, db.as_ref()).await?;
         }
         invite::record_invite_use(did.clone(), invite_code, now, db.as_ref()).await?;
-        auth::store_refresh_token(refresh_payload, None, db.as_ref()).await?;
+        auth::store_refresh_token(refresh_payload, None, None, db.as_ref()).await?;
         repo::update_root(did, repo_cid, repo_rev, db.as_ref()).await?;
         Ok((access_jwt, refresh_jwt))
     }
@@ -191,9 +218,43 @@ impl AccountManager {
         repo::update_root(did, cid, rev, db.as_ref()).await
     }
 
+    pub async fn get_storage_usage(&self, did: &str) -> Result<StorageUsage> {
+        let db = self.db.clone();
+        usage::get_storage_usage(did, db.as_ref()).await
+    }
+
+    /// Errors with `StorageQuotaError` if storing `additional_bytes` more would
+    /// take the account over `quota`. A `None` quota means unlimited.
+    pub async fn assert_storage_available(
+        &self,
+        did: &str,
+        additional_bytes: i64,
+        quota: Option<u64>,
+    ) -> Result<StorageUsage> {
+        let usage = self.get_storage_usage(did).await?;
+        if let Some(quota) = quota {
+            let quota = quota as i64;
+            if usage.total_bytes() + additional_bytes > quota {
+                return Err(StorageQuotaError {
+                    used: usage.total_bytes(),
+                    quota,
+                }
+                .into());
+            }
+        }
+        Ok(usage)
+    }
+
     pub async fn delete_account(&self, did: &str) -> Result<()> {
         let db = self.db.clone();
-        account::delete_account(did, db.as_ref()).await
+        account::delete_account(did, db.as_ref()).await?;
+        account_cache::invalidate(did);
+        Ok(())
+    }
+
+    /// Frees the invite code a rolled back signup used, see `CreateAccountSaga`
+    pub async fn release_invite_use(&self, did: &str) -> Result<()> {
+        invite::release_invite_use(did, self.db.as_ref()).await
     }
 
     pub async fn takedown_account(&self, did: &str, takedown: StatusAttr) -> Result<()> {
@@ -201,22 +262,60 @@ impl AccountManager {
             account::update_account_takedown_status(did, takedown, self.db.as_ref()),
             auth::revoke_refresh_tokens_by_did(did, self.db.as_ref())
         )?;
+        account_cache::invalidate(did);
         Ok(())
     }
 
+    /// Takes the account down for `duration_in_hours`, returning when it's lifted.
+    pub async fn suspend_account(
+        &self,
+        did: &str,
+        takedown_ref: Option<String>,
+        duration_in_hours: u64,
+    ) -> Result<String> {
+        let until = UtcOffset::now() + chrono::Duration::hours(duration_in_hours as i64);
+        let until = format!("{}", until.format(RFC3339_VARIANT));
+        try_join!(
+            account::suspend_account(did, takedown_ref, until.clone(), self.db.as_ref()),
+            auth::revoke_refresh_tokens_by_did(did, self.db.as_ref())
+        )?;
+        account_cache::invalidate(did);
+        Ok(until)
+    }
+
     // @NOTE should always be paired with a sequenceHandle().
     pub async fn update_handle(&self, did: &str, handle: &str) -> Result<()> {
         let db = self.db.clone();
-        account::update_handle(did, handle, db.as_ref()).await
+        account::update_handle(did, handle, db.as_ref()).await?;
+        account_cache::invalidate(did);
+        Ok(())
+    }
+
+    /// Binds the account to a new CKB address and signs it out everywhere, as
+    /// the old wallet may be in someone else's hands.
+    pub async fn rebind_ckb_address(
+        &self,
+        did: &str,
+        old_ckb_addr: &str,
+        new_ckb_addr: &str,
+    ) -> Result<()> {
+        account::update_ckb_address(did, old_ckb_addr, new_ckb_addr, self.db.as_ref()).await?;
+        account_cache::invalidate(did);
+        auth::revoke_refresh_tokens_by_did(did, self.db.as_ref()).await?;
+        Ok(())
     }
 
     pub async fn deactivate_account(&self, did: &str, delete_after: Option<String>) -> Result<()> {
-        account::deactivate_account(did, delete_after, self.db.as_ref()).await
+        account::deactivate_account(did, delete_after, self.db.as_ref()).await?;
+        account_cache::invalidate(did);
+        Ok(())
     }
 
     pub async fn activate_account(&self, did: &str) -> Result<()> {
         let db = self.db.clone();
-        account::activate_account(did, db.as_ref()).await
+        account::activate_account(did, db.as_ref()).await?;
+        account_cache::invalidate(did);
+        Ok(())
     }
 
     pub async fn get_account_status(&self, handle_or_did: &str) -> Result<AccountStatus> {
@@ -238,21 +337,27 @@ impl AccountManager {
 
     // Auth
     // ----------
+    /// Issues a token pair. `dpop_jkt` binds the access token to an OAuth
+    /// client's DPoP key.
     pub async fn create_session(
         &self,
         did: String,
         app_password_name: Option<String>,
+        dpop_jkt: Option<String>,
     ) -> Result<(String, String)> {
         let db = self.db.clone();
-        let secp = Secp256k1::new();
-        let private_key = env::var("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX")?;
-        let secret_key = SecretKey::from_slice(&hex::decode(private_key.as_bytes())?)?;
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
+        let jwt_key = JWT_KEYS.current();
         let scope = if app_password_name.is_none() {
             AuthScope::Access
         } else {
             AuthScope::AppPass
         };
+        let collections = match app_password_name {
+            Some(ref name) => {
+                password::get_app_password_collections(&did, name, db.as_ref()).await?
+            }
+            None => None,
+        };
         let (access_jwt, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
             did,
             jwt_key,
@@ -260,15 +365,38 @@ impl AccountManager {
             scope: Some(scope),
             jti: None,
             expires_in: None,
+            collections,
+            dpop_jkt,
         })?;
-        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
-        auth::store_refresh_token(refresh_payload, app_password_name, db.as_ref()).await?;
+        let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), &JWT_KEYS)?;
+        auth::store_refresh_token(refresh_payload, app_password_name, None, db.as_ref()).await?;
         Ok((access_jwt, refresh_jwt))
     }
 
-    pub async fn rotate_refresh_token(&self, id: &String) -> Result<Option<(String, String)>> {
+    /// Swaps refresh token `id` for a new pair. A token that was already
+    /// rotated can be swapped again during its grace period, for a client that
+    /// lost the response, but not once its successor has been rotated too or
+    /// after the grace period: then it has been copied, and its whole family
+    /// is revoked. `dpop_jkt` binds the new access token like in
+    /// [`Self::create_session`].
+    pub async fn rotate_refresh_token(
+        &self,
+        id: &String,
+        dpop_jkt: Option<String>,
+    ) -> Result<Option<(String, String)>> {
         let token = auth::get_refresh_token(id, self.db.as_ref()).await?;
         if let Some(token) = token {
+            if let Some(ref next_id) = token.next_id {
+                if auth::get_rotated_refresh_token(next_id, self.db.as_ref())
+                    .await?
+                    .is_some()
+                {
+                    let family_id = token.family_id.unwrap_or(token.id);
+                    self.revoke_reused_refresh_token(id, &token.did, family_id)
+                        .await?;
+                    return Ok(None);
+                }
+            }
             let system_time = SystemTime::now();
             let dt: DateTime<UtcOffset> = system_time.into();
             let now = format!("{}", dt.format(RFC3339_VARIANT));
@@ -296,16 +424,23 @@ impl AccountManager {
 
             // Determine the next refresh token id: upon refresh token
             // reuse you always receive a refresh token with the same id.
-            let next_id = token.next_id.unwrap_or_else(auth::get_refresh_token_id);
-
-            let secp = Secp256k1::new();
-            let private_key = env::var("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX").unwrap();
-            let secret_key =
-                SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-            let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
+            let next_id = token
+                .next_id
+                .clone()
+                .unwrap_or_else(auth::get_refresh_token_id);
+
+            let jwt_key = JWT_KEYS.current();
+            let family_id = token.family_id.clone().unwrap_or(token.id.clone());
+            let collections = match token.app_password_name {
+                Some(ref name) => {
+                    password::get_app_password_collections(&token.did, name, self.db.as_ref())
+                        .await?
+                }
+                None => None,
+            };
 
             let (access_jwt, refresh_jwt) = auth::create_tokens(CreateTokensOpts {
-                did: token.did,
+                did: token.did.clone(),
                 jwt_key,
                 service_did: env::var("PDS_SERVICE_DID").unwrap(),
                 scope: Some(if token.app_password_name.is_none() {
@@ -315,8 +450,10 @@ impl AccountManager {
                 }),
                 jti: Some(next_id.clone()),
                 expires_in: None,
+                collections,
+                dpop_jkt: dpop_jkt.clone(),
             })?;
-            let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), jwt_key)?;
+            let refresh_payload = auth::decode_refresh_token(refresh_jwt.clone(), &JWT_KEYS)?;
             match try_join!(
                 auth::add_refresh_grace_period(
                     RefreshGracePeriodOpts {
@@ -328,27 +465,144 @@ impl AccountManager {
                 ),
                 auth::store_refresh_token(
                     refresh_payload,
-                    token.app_password_name,
+                    token.app_password_name.clone(),
+                    Some(family_id),
                     self.db.as_ref()
-                )
+                ),
+                auth::mark_refresh_token_rotated(token, self.db.as_ref())
             ) {
                 Ok(_) => Ok(Some((access_jwt, refresh_jwt))),
                 Err(e) => match e.downcast_ref() {
                     Some(AuthHelperError::ConcurrentRefresh) => {
-                        Box::pin(self.rotate_refresh_token(id)).await
+                        Box::pin(self.rotate_refresh_token(id, dpop_jkt)).await
                     }
                     _ => Err(e),
                 },
             }
         } else {
+            if let Some((family_id, did)) =
+                auth::get_rotated_refresh_token(id, self.db.as_ref()).await?
+            {
+                self.revoke_reused_refresh_token(id, &did, family_id)
+                    .await?;
+            }
             Ok(None)
         }
     }
 
+    /// Ends every session descended from the login `family_id` started. A
+    /// web5 account then has to sign in with its wallet again, which whoever
+    /// copied the token can't do.
+    async fn revoke_reused_refresh_token(
+        &self,
+        id: &str,
+        did: &str,
+        family_id: String,
+    ) -> Result<()> {
+        let revoked =
+            auth::revoke_refresh_token_family(family_id.clone(), self.db.as_ref()).await?;
+        tracing::warn!(
+            "@LOG: refresh token {id} of {did} was reused, revoked {revoked} tokens of family {family_id}"
+        );
+        self.try_record_audit_event(AuditEvent {
+            detail: Some(json!({
+                "tokenId": id,
+                "familyId": family_id,
+                "revoked": revoked,
+            })),
+            ..AuditEvent::new(AuditAction::RefreshTokenReuse, None, did.to_string())
+        })
+        .await;
+        ACCOUNT_EVENTS
+            .enqueue(&AccountEvent::SessionRevoked {
+                did: did.to_string(),
+                family_id,
+            })
+            .await
+    }
+
     pub async fn revoke_refresh_token(&self, id: String) -> Result<bool> {
         auth::revoke_refresh_token(id, self.db.as_ref()).await
     }
 
+    pub async fn revoke_session(&self, id: String) -> Result<bool> {
+        auth::revoke_session(id, self.db.as_ref()).await
+    }
+
+    pub async fn is_session_revoked(&self, id: String) -> Result<bool> {
+        auth::is_session_revoked(id, self.db.as_ref()).await
+    }
+
+    // Signups
+    // ----------
+
+    /// Accounts created from `ip` within the last day
+    pub async fn count_daily_signups_from_ip(&self, ip: &str) -> Result<i64> {
+        signup::count_signups_from_ip(ip, day_ago(), self.db.as_ref()).await
+    }
+
+    pub async fn record_signup_ip(&self, did: &str, ip: &str) -> Result<()> {
+        signup::record_signup_ip(did, ip, day_ago(), self.db.as_ref()).await
+    }
+
+    // Handle Reservations
+    // ----------
+
+    /// The unexpired reservation on `handle`, if any
+    pub async fn get_handle_reservation(&self, handle: &str) -> Result<Option<HandleReservation>> {
+        handle_reservation::get_reservation(handle, rsky_common::now(), self.db.as_ref()).await
+    }
+
+    /// Reserves `handle` for `window_ms` on behalf of the chain address whose
+    /// DID doc claims it. None when another address already holds it.
+    pub async fn reserve_handle(
+        &self,
+        handle: &str,
+        ckb_address: &str,
+        signing_key: &str,
+        window_ms: u64,
+    ) -> Result<Option<HandleReservation>> {
+        let now = UtcOffset::now();
+        let reservation = HandleReservation {
+            handle: handle.to_owned(),
+            ckb_address: ckb_address.to_owned(),
+            signing_key: signing_key.to_owned(),
+            reserved_at: format!("{}", now.format(RFC3339_VARIANT)),
+            expires_at: format!(
+                "{}",
+                (now + chrono::Duration::milliseconds(window_ms as i64)).format(RFC3339_VARIANT)
+            ),
+        };
+        let reserved = handle_reservation::reserve_handle(
+            reservation.clone(),
+            rsky_common::now(),
+            self.db.as_ref(),
+        )
+        .await?;
+        Ok(reserved.then_some(reservation))
+    }
+
+    pub async fn release_handle_reservation(&self, handle: &str) -> Result<()> {
+        handle_reservation::release_handle(handle, self.db.as_ref()).await
+    }
+
+    // Audit Log
+    // ----------
+
+    pub async fn record_audit_event(&self, event: AuditEvent) -> Result<()> {
+        audit::record_audit_event(event, self.db.as_ref()).await
+    }
+
+    /// Records an audit event without failing the caller, for actions that
+    /// have already happened or failed by the time they're audited.
+    pub async fn try_record_audit_event(&self, event: AuditEvent) {
+        let action = event.action.as_str();
+        let did = event.subject_did.clone();
+        if let Err(error) = self.record_audit_event(event).await {
+            tracing::error!("@LOG: ERROR: failed to audit {action} of {did}: {error}");
+        }
+    }
+
     // Invites
     // ----------
 
@@ -392,7 +646,9 @@ impl AccountManager {
     }
 
     pub async fn set_account_invites_disabled(&self, did: &str, disabled: bool) -> Result<()> {
-        invite::set_account_invites_disabled(did, disabled, self.db.as_ref()).await
+        invite::set_account_invites_disabled(did, disabled, self.db.as_ref()).await?;
+        account_cache::invalidate(did);
+        Ok(())
     }
 
     pub async fn disable_invite_codes(&self, opts: DisableInviteCodesOpts) -> Result<()> {
@@ -406,11 +662,15 @@ impl AccountManager {
         &self,
         did: String,
         name: String,
+        collections: Option<Vec<String>>,
     ) -> Result<CreateAppPasswordOutput> {
-        password::create_app_password(did, name, self.db.as_ref()).await
+        password::create_app_password(did, name, collections, self.db.as_ref()).await
     }
 
-    pub async fn list_app_passwords(&self, did: &str) -> Result<Vec<(String, String)>> {
+    pub async fn list_app_passwords(
+        &self,
+        did: &str,
+    ) -> Result<Vec<(String, String, Option<Vec<String>>)>> {
         password::list_app_passwords(did, self.db.as_ref()).await
     }
 
@@ -419,6 +679,20 @@ impl AccountManager {
         password::verify_account_password(did, password_str, db.as_ref()).await
     }
 
+    /// Checks the password an account signs in with. Web5 accounts sign in
+    /// with their wallet, and only have a password once they've set one with
+    /// com.atproto.web5.setPassword.
+    pub async fn verify_login_password(
+        &self,
+        account: &ActorAccount,
+        password_str: &String,
+    ) -> Result<bool> {
+        if account.ckb_address.is_some() && !self.password_login_enabled(&account.did).await? {
+            return Ok(false);
+        }
+        self.verify_account_password(&account.did, password_str).await
+    }
+
     pub async fn verify_app_password(
         &self,
         did: &str,
@@ -462,6 +736,28 @@ impl AccountManager {
         Ok(())
     }
 
+    /// Whether a web5 account may sign in with its password as well as its wallet
+    pub async fn password_login_enabled(&self, did: &str) -> Result<bool> {
+        password::password_login_enabled(did, self.db.as_ref()).await
+    }
+
+    /// Sets the password a web5 account may sign in with when its wallet isn't
+    /// at hand. Unlike a password reset, existing sessions are kept, since the
+    /// wallet already vouched for the change.
+    pub async fn set_password(&self, opts: SetPasswordOpts) -> Result<()> {
+        let SetPasswordOpts {
+            did,
+            password,
+            password_login,
+        } = opts;
+        let password_encrypted = match password {
+            Some(password) => Some(password::gen_salt_and_hash(password)?),
+            None => None,
+        };
+        password::update_password_login(did, password_encrypted, password_login, self.db.as_ref())
+            .await
+    }
+
     pub async fn revoke_app_password(&self, did: String, name: String) -> Result<()> {
         try_join!(
             password::delete_app_password(&did, &name, self.db.as_ref()),
@@ -488,6 +784,7 @@ impl AccountManager {
             email_token::delete_email_token(did, EmailTokenPurpose::ConfirmEmail, db.as_ref()),
             account::set_email_confirmed_at(did, now, self.db.as_ref())
         )?;
+        account_cache::invalidate(did);
         Ok(())
     }
 
@@ -498,6 +795,7 @@ impl AccountManager {
             account::update_email(&did, &email, db.as_ref()),
             email_token::delete_all_email_tokens(&did, db.as_ref())
         )?;
+        account_cache::invalidate(&did);
         Ok(())
     }
 
@@ -530,9 +828,31 @@ impl AccountManager {
         let db = self.db.clone();
         email_token::create_email_token(did, purpose, db.as_ref()).await
     }
+
+    // Email Notifications
+    // ----------
+
+    pub async fn get_email_notification_prefs(&self, did: &str) -> Result<EmailNotificationPrefs> {
+        email_pref::get_email_notification_prefs(did, self.db.as_ref()).await
+    }
+
+    pub async fn put_email_notification_prefs(
+        &self,
+        did: &str,
+        prefs: EmailNotificationPrefs,
+    ) -> Result<()> {
+        email_pref::put_email_notification_prefs(did, prefs, self.db.as_ref()).await
+    }
+}
+
+fn day_ago() -> String {
+    let day_ago = UtcOffset::now() - chrono::Duration::days(1);
+    format!("{}", day_ago.format(RFC3339_VARIANT))
 }
 
 pub mod helpers;
+pub mod saga;
+pub mod suspension;
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for AccountManager {
@@ -0,0 +1,107 @@
+use crate::account_manager::helpers::account::{AccountStatus, AvailabilityFlags};
+use crate::account_manager::AccountManager;
+use crate::actor_store::ActorStore;
+use crate::SharedSequencer;
+use anyhow::{bail, Result};
+
+/// Records how far account creation got so a failure part way through can be
+/// undone instead of leaving a half created account behind. Each step is
+/// marked before it starts, since a step that fails may still have written
+/// some of its rows, and `compensate` reverts them in reverse order. Every
+/// revert deletes whatever is there, so it's safe for a step that never got
+/// to write anything.
+#[derive(Debug)]
+pub struct CreateAccountSaga {
+    pub did: String,
+    repo_created: bool,
+    account_created: bool,
+    events_sequenced: bool,
+}
+
+impl CreateAccountSaga {
+    /// Refuses to start for a did that's already hosted here, since rolling
+    /// back would otherwise destroy the existing account's repo.
+    pub async fn begin(
+        did: String,
+        actor_store: &ActorStore,
+        account_manager: &AccountManager,
+    ) -> Result<Self> {
+        let existing = account_manager
+            .get_account(
+                &did,
+                Some(AvailabilityFlags {
+                    include_deactivated: Some(true),
+                    include_taken_down: Some(true),
+                }),
+            )
+            .await?;
+        if existing.is_some() || actor_store.get_repo_root().await.is_some() {
+            bail!("Account already exists for {did}");
+        }
+        Ok(CreateAccountSaga {
+            did,
+            repo_created: false,
+            account_created: false,
+            events_sequenced: false,
+        })
+    }
+
+    /// Call before creating the repo
+    pub fn creating_repo(&mut self) {
+        self.repo_created = true;
+    }
+
+    /// Call before creating the account, which isn't written in one
+    /// transaction
+    pub fn creating_account(&mut self) {
+        self.account_created = true;
+    }
+
+    /// Call before sequencing the first event, a failed write to the
+    /// sequencer may still have left a row behind
+    pub fn sequencing(&mut self) {
+        self.events_sequenced = true;
+    }
+
+    /// Best effort: every step is attempted even if an earlier one fails, and
+    /// failures are logged since the caller is already returning an error.
+    pub async fn compensate(
+        self,
+        actor_store: &mut ActorStore,
+        account_manager: &AccountManager,
+        sequencer: &SharedSequencer,
+    ) {
+        let did = self.did;
+        tracing::warn!("Rolling back account creation for {did}");
+        if self.events_sequenced {
+            // subscribers may already have seen the account, so tell them it's gone
+            let mut lock = sequencer.sequencer.write().await;
+            let excluding = match lock
+                .sequence_account_evt(did.clone(), AccountStatus::Deleted)
+                .await
+            {
+                Ok(seq) => Some(vec![seq]),
+                Err(error) => {
+                    tracing::error!("Rollback: failed to sequence deletion for {did}\n{error}");
+                    None
+                }
+            };
+            if let Err(error) = lock.delete_all_for_user(&did, excluding).await {
+                tracing::error!("Rollback: failed to delete events for {did}\n{error}");
+            }
+        }
+        if self.account_created {
+            if let Err(error) = account_manager.delete_account(&did).await {
+                tracing::error!("Rollback: failed to delete account {did}\n{error}");
+            }
+            if let Err(error) = account_manager.release_invite_use(&did).await {
+                tracing::error!("Rollback: failed to release invite used by {did}\n{error}");
+            }
+        }
+        if self.repo_created || self.account_created {
+            if let Err(error) = actor_store.destroy().await {
+                tracing::error!("Rollback: failed to destroy actor store for {did}\n{error}");
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::helpers::audit::{insert_audit_event, AuditAction, AuditEvent};
+use crate::db::establish_connection_for_jobs;
+use crate::sequencer::Sequencer;
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::update;
+use serde_json::json;
+use std::time::Duration;
+
+/// Lifts suspensions once they expire, announcing that the account is active
+/// again just as a moderator reversing the takedown would.
+#[derive(Clone)]
+pub struct SuspensionReaper {
+    pub sequencer: Sequencer,
+    pub interval_ms: u64,
+}
+
+impl SuspensionReaper {
+    pub fn new(sequencer: Sequencer, interval_ms: u64) -> Self {
+        SuspensionReaper {
+            sequencer,
+            interval_ms: interval_ms.max(1000),
+        }
+    }
+
+    pub async fn start(&mut self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: failed to lift expired suspensions: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        use crate::schema::pds::actor::dsl as ActorSchema;
+
+        let conn = &mut establish_connection_for_jobs()?;
+        let lifted = update(ActorSchema::actor)
+            .filter(ActorSchema::suspendedUntil.le(rsky_common::now()))
+            .set((
+                ActorSchema::takedownRef.eq(None::<String>),
+                ActorSchema::suspendedUntil.eq(None::<String>),
+            ))
+            .returning((ActorSchema::did, ActorSchema::deactivatedAt))
+            .get_results::<(String, Option<String>)>(conn)?;
+        for (did, deactivated_at) in lifted {
+            tracing::info!("Suspension of {did} expired");
+            insert_audit_event(
+                conn,
+                AuditEvent {
+                    detail: Some(json!({ "reason": "expired" })),
+                    ..AuditEvent::new(AuditAction::Unsuspend, None, did.clone())
+                },
+            )?;
+            let status = match deactivated_at {
+                Some(_) => AccountStatus::Deactivated,
+                None => AccountStatus::Active,
+            };
+            self.sequencer.sequence_account_evt(did, status).await?;
+        }
+        Ok(())
+    }
+}
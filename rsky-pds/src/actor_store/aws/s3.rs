@@ -1,6 +1,8 @@
 use std::str::FromStr;
 // based on https://github.com/bluesky-social/atproto/blob/main/packages/aws/src/s3.ts
+use crate::actor_store::blob::store::{BlobStore, BlobStoreCreator};
 use crate::apis::ApiError;
+use crate::config::S3BlobstoreConfig;
 use anyhow::Result;
 use aws_sdk_s3 as s3;
 use aws_sdk_s3::error::SdkError;
@@ -34,8 +36,8 @@ impl S3BlobStore {
         S3BlobStore { client, bucket }
     }
 
-    pub fn creator(cfg: Config) -> Box<dyn Fn(String) -> S3BlobStore> {
-        Box::new(move |did: String| S3BlobStore::new(did, cfg.clone()))
+    pub fn creator(cfg: Config) -> BlobStoreCreator {
+        Box::new(move |did: String| Box::new(S3BlobStore::new(did, cfg.clone())))
     }
 
     fn gen_key(&self) -> String {
@@ -58,7 +60,129 @@ impl S3BlobStore {
         format!("quarantine/{0}/{1}", self.bucket, cid)
     }
 
-    pub async fn put_temp(&self, bytes: Vec<u8>) -> Result<String> {
+    fn get_derived_prefix(&self, cid: Cid) -> String {
+        format!("derived/{0}/{1}/", self.bucket, cid)
+    }
+
+    fn get_derived_path(&self, cid: Cid, variant: &String) -> String {
+        format!("{0}{1}", self.get_derived_prefix(cid), variant)
+    }
+
+    async fn get_object(&self, cid: Cid) -> Result<ByteStream> {
+        self.get_key(self.get_stored_path(cid)).await
+    }
+
+    async fn get_key(&self, key: String) -> Result<ByteStream> {
+        let res = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await;
+        match res {
+            Ok(res) => Ok(res.body),
+            Err(SdkError::ServiceError(s)) => Err(anyhow::Error::new(s.into_err())),
+            Err(e) => Err(anyhow::Error::new(e.into_service_error())),
+        }
+    }
+
+    async fn has_key(&self, key: String) -> bool {
+        let res = self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await;
+        res.is_ok()
+    }
+
+    async fn delete_key(&self, key: String) -> Result<()> {
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_many_keys(&self, keys: Vec<String>) -> Result<()> {
+        let objects: Vec<ObjectIdentifier> = keys
+            .into_iter()
+            .map(|key| Ok(ObjectIdentifier::builder().key(key).build()?))
+            .collect::<Result<Vec<ObjectIdentifier>>>()?;
+        let deletes = Delete::builder().set_objects(Some(objects)).build()?;
+        self.client
+            .delete_objects()
+            .bucket(&self.bucket)
+            .delete(deletes)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_derived(&self, cid: Cid) -> Result<()> {
+        let res = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(self.get_derived_prefix(cid))
+            .send()
+            .await?;
+        let keys: Vec<String> = res
+            .contents()
+            .iter()
+            .filter_map(|object| object.key().map(|key| key.to_string()))
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.delete_many_keys(keys).await
+    }
+
+    async fn move_object(&self, keys: MoveObject) -> Result<()> {
+        self.client
+            .copy_object()
+            .bucket(&self.bucket)
+            .copy_source(keys.from.clone())
+            .key(keys.to)
+            .acl(ObjectCannedAcl::PublicRead)
+            .send()
+            .await?;
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(keys.from)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+impl From<&S3BlobstoreConfig> for Config {
+    fn from(cfg: &S3BlobstoreConfig) -> Self {
+        let credentials = aws_sdk_s3::config::Credentials::new(
+            cfg.access_key_id.clone(),
+            cfg.secret_access_key.clone(),
+            None, // Session Token
+            None, // Expires
+            "localstack",
+        );
+        aws_sdk_s3::config::Builder::new()
+            .endpoint_url(cfg.endpoint.clone())
+            .region(aws_config::Region::new(cfg.region.clone()))
+            .credentials_provider(credentials)
+            .behavior_version_latest()
+            .force_path_style(cfg.force_path_style)
+            .build()
+    }
+}
+
+#[rocket::async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put_temp(&self, bytes: Vec<u8>) -> Result<String> {
         let key = self.gen_key();
         let body = ByteStream::from(bytes);
         self.client
@@ -72,7 +196,32 @@ impl S3BlobStore {
         Ok(key)
     }
 
-    pub async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
+    async fn put_temp_with_key(&self, key: String, bytes: Vec<u8>) -> Result<()> {
+        self.client
+            .put_object()
+            .body(ByteStream::from(bytes))
+            .bucket(&self.bucket)
+            .key(self.get_tmp_path(&key))
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    async fn get_temp_bytes(&self, key: String) -> Result<Vec<u8>> {
+        let res = self.get_key(self.get_tmp_path(&key)).await?;
+        let bytes = res.collect().await.map(|data| data.into_bytes())?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn delete_temp_many(&self, keys: Vec<String>) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let keys: Vec<String> = keys.iter().map(|key| self.get_tmp_path(key)).collect();
+        self.delete_many_keys(keys).await
+    }
+
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
         let already_has = self.has_stored(cid).await?;
         if !already_has {
             Ok(self
@@ -87,7 +236,7 @@ impl S3BlobStore {
         }
     }
 
-    pub async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
         let body = ByteStream::from(bytes);
         self.client
             .put_object()
@@ -100,7 +249,7 @@ impl S3BlobStore {
         Ok(())
     }
 
-    pub async fn quarantine(&self, cid: Cid) -> Result<()> {
+    async fn quarantine(&self, cid: Cid) -> Result<()> {
         self.move_object(MoveObject {
             from: self.get_stored_path(cid),
             to: self.get_quarantined_path(cid),
@@ -108,7 +257,7 @@ impl S3BlobStore {
         .await
     }
 
-    pub async fn unquarantine(&self, cid: Cid) -> Result<()> {
+    async fn unquarantine(&self, cid: Cid) -> Result<()> {
         self.move_object(MoveObject {
             from: self.get_quarantined_path(cid),
             to: self.get_stored_path(cid),
@@ -116,37 +265,49 @@ impl S3BlobStore {
         .await
     }
 
-    async fn get_object(&self, cid: Cid) -> Result<ByteStream> {
-        let res = self
-            .client
-            .get_object()
+    async fn delete_quarantined(&self, cid: Cid) -> Result<()> {
+        self.delete_derived(cid).await?;
+        self.delete_key(self.get_quarantined_path(cid)).await
+    }
+
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()> {
+        self.client
+            .put_object()
+            .body(ByteStream::from(bytes))
             .bucket(&self.bucket)
-            .key(self.get_stored_path(cid))
+            .key(self.get_derived_path(cid, &variant))
+            .acl(ObjectCannedAcl::PublicRead)
             .send()
-            .await;
-        match res {
-            Ok(res) => Ok(res.body),
-            Err(SdkError::ServiceError(s)) => Err(anyhow::Error::new(s.into_err())),
-            Err(e) => Err(anyhow::Error::new(e.into_service_error())),
-        }
+            .await?;
+        Ok(())
     }
 
-    pub async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
+    async fn get_derived_bytes(&self, cid: Cid, variant: String) -> Result<Vec<u8>> {
+        let res = self.get_key(self.get_derived_path(cid, &variant)).await?;
+        let bytes = res.collect().await.map(|data| data.into_bytes())?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
         let res = self.get_object(cid).await?;
         let bytes = res.collect().await.map(|data| data.into_bytes())?;
         Ok(bytes.to_vec())
     }
 
-    pub async fn get_stream(&self, cid: Cid) -> Result<ByteStream> {
+    async fn get_stream(&self, cid: Cid) -> Result<ByteStream> {
         self.get_object(cid).await
     }
 
-    pub async fn delete(&self, cid: String) -> Result<()> {
-        self.delete_key(self.get_stored_path(Cid::from_str(&cid)?))
-            .await
+    async fn delete(&self, cid: String) -> Result<()> {
+        let cid = Cid::from_str(&cid)?;
+        self.delete_derived(cid).await?;
+        self.delete_key(self.get_stored_path(cid)).await
     }
 
-    pub async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+        for cid in cids.iter() {
+            self.delete_derived(*cid).await?;
+        }
         let keys: Vec<String> = cids
             .into_iter()
             .map(|cid| self.get_stored_path(cid))
@@ -154,69 +315,15 @@ impl S3BlobStore {
         self.delete_many_keys(keys).await
     }
 
-    pub async fn has_stored(&self, cid: Cid) -> Result<bool> {
+    async fn has_stored(&self, cid: Cid) -> Result<bool> {
         Ok(self.has_key(self.get_stored_path(cid)).await)
     }
 
-    pub async fn has_temp(&self, key: String) -> Result<bool> {
+    async fn has_temp(&self, key: String) -> Result<bool> {
         Ok(self.has_key(self.get_tmp_path(&key)).await)
     }
 
-    async fn has_key(&self, key: String) -> bool {
-        let res = self
-            .client
-            .head_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
-            .await;
-        res.is_ok()
-    }
-
-    async fn delete_key(&self, key: String) -> Result<()> {
-        self.client
-            .delete_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
-            .await?;
-        Ok(())
-    }
-
-    async fn delete_many_keys(&self, keys: Vec<String>) -> Result<()> {
-        let objects: Vec<ObjectIdentifier> = keys
-            .into_iter()
-            .map(|key| Ok(ObjectIdentifier::builder().key(key).build()?))
-            .collect::<Result<Vec<ObjectIdentifier>>>()?;
-        let deletes = Delete::builder().set_objects(Some(objects)).build()?;
-        self.client
-            .delete_objects()
-            .bucket(&self.bucket)
-            .delete(deletes)
-            .send()
-            .await?;
-        Ok(())
-    }
-
-    async fn move_object(&self, keys: MoveObject) -> Result<()> {
-        self.client
-            .copy_object()
-            .bucket(&self.bucket)
-            .copy_source(keys.from.clone())
-            .key(keys.to)
-            .acl(ObjectCannedAcl::PublicRead)
-            .send()
-            .await?;
-        self.client
-            .delete_object()
-            .bucket(&self.bucket)
-            .key(keys.from)
-            .send()
-            .await?;
-        Ok(())
-    }
-
-    pub async fn bucket_exists(&self) -> Result<bool, ApiError> {
+    async fn bucket_exists(&self) -> Result<bool, ApiError> {
         match self.client.list_buckets().send().await {
             Ok(response) => Ok(response
                 .buckets
@@ -233,7 +340,7 @@ impl S3BlobStore {
         }
     }
 
-    pub async fn create_bucket(&self) -> Result<bool, ApiError> {
+    async fn create_bucket(&self) -> Result<bool, ApiError> {
         match self
             .client
             .create_bucket()
@@ -0,0 +1,331 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::db::establish_connection_for_jobs;
+use crate::models::BlobGc;
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::dsl::{exists, not};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+use lexicon_cid::Cid;
+use rsky_common::RFC3339_VARIANT;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Running totals for the blob garbage collector, readable from anywhere in
+/// the process (e.g. a metrics endpoint).
+#[derive(Debug, Default)]
+pub struct BlobGcMetrics {
+    pub runs: AtomicU64,
+    pub quarantined_blobs: AtomicU64,
+    pub restored_blobs: AtomicU64,
+    pub deleted_blobs: AtomicU64,
+    pub reclaimed_bytes: AtomicU64,
+}
+
+impl BlobGcMetrics {
+    pub const fn new() -> Self {
+        BlobGcMetrics {
+            runs: AtomicU64::new(0),
+            quarantined_blobs: AtomicU64::new(0),
+            restored_blobs: AtomicU64::new(0),
+            deleted_blobs: AtomicU64::new(0),
+            reclaimed_bytes: AtomicU64::new(0),
+        }
+    }
+}
+
+pub static BLOB_GC_METRICS: BlobGcMetrics = BlobGcMetrics::new();
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BlobGcRun {
+    pub quarantined: u64,
+    pub restored: u64,
+    pub deleted: u64,
+    pub reclaimed_bytes: u64,
+}
+
+/// Reclaims storage held by blobs that no record references anymore, most
+/// notably the blobs left behind when a web5 account is deleted.
+///
+/// Orphans are first moved into quarantine and only deleted once they've sat
+/// there for the grace period, so a blob that gets referenced again in the
+/// meantime (or one flagged by mistake) can still be restored.
+pub struct BlobGarbageCollector {
+    pub blob_store: SharedBlobStore,
+    pub interval_ms: u64,
+    pub grace_period_ms: u64,
+    pub min_orphan_age_ms: u64,
+    pub batch_size: i64,
+}
+
+impl BlobGarbageCollector {
+    pub fn new(
+        blob_store: SharedBlobStore,
+        interval_ms: u64,
+        grace_period_ms: u64,
+        min_orphan_age_ms: u64,
+        batch_size: i64,
+    ) -> Self {
+        BlobGarbageCollector {
+            blob_store,
+            interval_ms: interval_ms.max(1000),
+            grace_period_ms,
+            min_orphan_age_ms,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            match self.run().await {
+                Ok(run) => tracing::info!(
+                    "Blob gc: quarantined {}, restored {}, deleted {} ({} bytes reclaimed)",
+                    run.quarantined,
+                    run.restored,
+                    run.deleted,
+                    run.reclaimed_bytes
+                ),
+                Err(error) => tracing::error!("@LOG: ERROR: blob gc run failed: {error}"),
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<BlobGcRun> {
+        let mut run = BlobGcRun::default();
+        self.sweep(&mut run).await?;
+        self.mark(&mut run).await?;
+        BLOB_GC_METRICS.runs.fetch_add(1, Ordering::Relaxed);
+        Ok(run)
+    }
+
+    /// Quarantines blobs that are old enough and no longer referenced by any
+    /// record, or whose owner no longer exists.
+    async fn mark(&self, run: &mut BlobGcRun) -> Result<()> {
+        use crate::schema::pds::blob_gc::dsl as BlobGcSchema;
+
+        let quarantined_at = rsky_common::now();
+        let created_before = format_cutoff(self.min_orphan_age_ms);
+        let limit = self.batch_size;
+        let candidates = with_conn(move |conn| find_orphans(conn, &created_before, limit)).await?;
+        let mut quarantined = Vec::new();
+        for (cid, did, size) in candidates {
+            let parsed = match Cid::from_str(&cid) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    tracing::warn!("Blob gc: skipping invalid cid {cid} for {did}: {error}");
+                    continue;
+                }
+            };
+            if let Err(error) = self
+                .blob_store
+                .for_did(did.clone())
+                .quarantine(parsed)
+                .await
+            {
+                tracing::warn!("Blob gc: failed to quarantine {cid} for {did}: {error}");
+                continue;
+            }
+            quarantined.push(BlobGc {
+                cid,
+                did,
+                size,
+                quarantined_at: quarantined_at.clone(),
+            });
+        }
+        if quarantined.is_empty() {
+            return Ok(());
+        }
+        let count = quarantined.len() as u64;
+        with_conn(move |conn| {
+            insert_into(BlobGcSchema::blob_gc)
+                .values(&quarantined)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            Ok(())
+        })
+        .await?;
+        run.quarantined += count;
+        BLOB_GC_METRICS
+            .quarantined_blobs
+            .fetch_add(count, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Deletes blobs whose grace period has elapsed, restoring any that were
+    /// referenced again while in quarantine.
+    async fn sweep(&self, run: &mut BlobGcRun) -> Result<()> {
+        use crate::schema::pds::blob_gc::dsl as BlobGcSchema;
+
+        let quarantined_before = format_cutoff(self.grace_period_ms);
+        let limit = self.batch_size;
+        let expired = with_conn(move |conn| {
+            let entries = BlobGcSchema::blob_gc
+                .filter(BlobGcSchema::quarantinedAt.lt(quarantined_before))
+                .order(BlobGcSchema::quarantinedAt.asc())
+                .limit(limit)
+                .select(BlobGc::as_select())
+                .load::<BlobGc>(conn)?;
+            entries
+                .into_iter()
+                .map(|entry| {
+                    let referenced = is_referenced(conn, &entry.cid, &entry.did)?;
+                    Ok((entry, referenced))
+                })
+                .collect::<Result<Vec<(BlobGc, bool)>>>()
+        })
+        .await?;
+
+        // Blob store calls happen between the two blocking batches; whatever
+        // got done is still recorded if one of them fails part way through.
+        let mut done = Vec::new();
+        let mut failure = None;
+        for (entry, referenced) in expired {
+            let parsed = match Cid::from_str(&entry.cid) {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    failure = Some(error.into());
+                    break;
+                }
+            };
+            let blob_store = self.blob_store.for_did(entry.did.clone());
+            let result = if referenced {
+                blob_store.unquarantine(parsed).await
+            } else {
+                blob_store.delete_quarantined(parsed).await
+            };
+            if let Err(error) = result {
+                failure = Some(error);
+                break;
+            }
+            done.push((entry, referenced));
+        }
+        let done = with_conn(move |conn| {
+            forget_swept(conn, &done)?;
+            Ok(done)
+        })
+        .await?;
+
+        for (entry, referenced) in done {
+            if referenced {
+                run.restored += 1;
+                BLOB_GC_METRICS
+                    .restored_blobs
+                    .fetch_add(1, Ordering::Relaxed);
+            } else {
+                let size = entry.size.max(0) as u64;
+                run.deleted += 1;
+                run.reclaimed_bytes += size;
+                BLOB_GC_METRICS
+                    .deleted_blobs
+                    .fetch_add(1, Ordering::Relaxed);
+                BLOB_GC_METRICS
+                    .reclaimed_bytes
+                    .fetch_add(size, Ordering::Relaxed);
+            }
+        }
+        match failure {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs blocking diesel work off the async executor on a fresh job
+/// connection.
+async fn with_conn<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut PgConnection) -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let conn = &mut establish_connection_for_jobs()?;
+        f(conn)
+    })
+    .await?
+}
+
+/// Drops the quarantine entries of swept blobs, along with the blob rows of
+/// the ones that were deleted for good. A blob a record write took back out of
+/// quarantine in the meantime no longer has an entry, and keeps its row.
+fn forget_swept(conn: &mut PgConnection, swept: &[(BlobGc, bool)]) -> Result<()> {
+    use crate::schema::pds::blob::dsl as BlobSchema;
+    use crate::schema::pds::blob_gc::dsl as BlobGcSchema;
+
+    conn.transaction(|conn| {
+        for (entry, referenced) in swept {
+            let forgotten = delete(BlobGcSchema::blob_gc)
+                .filter(BlobGcSchema::cid.eq(&entry.cid))
+                .filter(BlobGcSchema::did.eq(&entry.did))
+                .execute(conn)?;
+            if !referenced && forgotten > 0 {
+                delete(BlobSchema::blob)
+                    .filter(BlobSchema::cid.eq(&entry.cid))
+                    .filter(BlobSchema::did.eq(&entry.did))
+                    .execute(conn)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+fn format_cutoff(age_ms: u64) -> String {
+    format!(
+        "{}",
+        (Utc::now() - ChronoDuration::milliseconds(age_ms as i64)).format(RFC3339_VARIANT)
+    )
+}
+
+fn find_orphans(
+    conn: &mut PgConnection,
+    created_before: &String,
+    limit: i64,
+) -> Result<Vec<(String, String, i32)>> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::blob::dsl as BlobSchema;
+    use crate::schema::pds::blob_gc::dsl as BlobGcSchema;
+    use crate::schema::pds::record_blob::dsl as RecordBlobSchema;
+
+    Ok(BlobSchema::blob
+        .filter(BlobSchema::createdAt.lt(created_before))
+        .filter(BlobSchema::takedownRef.is_null())
+        .filter(not(exists(
+            BlobGcSchema::blob_gc
+                .filter(BlobGcSchema::cid.eq(BlobSchema::cid))
+                .filter(BlobGcSchema::did.eq(BlobSchema::did)),
+        )))
+        .filter(
+            not(exists(
+                RecordBlobSchema::record_blob
+                    .filter(RecordBlobSchema::blobCid.eq(BlobSchema::cid))
+                    .filter(RecordBlobSchema::did.eq(BlobSchema::did)),
+            ))
+            .or(not(exists(
+                ActorSchema::actor.filter(ActorSchema::did.eq(BlobSchema::did)),
+            ))),
+        )
+        .order(BlobSchema::createdAt.asc())
+        .limit(limit)
+        .select((BlobSchema::cid, BlobSchema::did, BlobSchema::size))
+        .load::<(String, String, i32)>(conn)?)
+}
+
+fn is_referenced(conn: &mut PgConnection, cid: &String, did: &String) -> Result<bool> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record_blob::dsl as RecordBlobSchema;
+
+    let has_actor = diesel::select(exists(ActorSchema::actor.filter(ActorSchema::did.eq(did))))
+        .get_result::<bool>(conn)?;
+    if !has_actor {
+        return Ok(false);
+    }
+    Ok(diesel::select(exists(
+        RecordBlobSchema::record_blob
+            .filter(RecordBlobSchema::blobCid.eq(cid))
+            .filter(RecordBlobSchema::did.eq(did)),
+    ))
+    .get_result::<bool>(conn)?)
+}
@@ -1,6 +1,8 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::gc::BLOB_GC_METRICS;
+use crate::actor_store::blob::store::BlobStore;
 use crate::db::DbConn;
 use crate::image;
+use crate::metrics;
 use crate::models::models;
 use anyhow::{bail, Result};
 use aws_sdk_s3::operation::get_object::GetObjectError;
@@ -9,7 +11,7 @@ use diesel::dsl::{count_distinct, exists, not};
 use diesel::result::Error;
 use diesel::sql_types::{Integer, Nullable, Text};
 use diesel::*;
-use futures::stream::{self, StreamExt};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use futures::try_join;
 use lexicon_cid::Cid;
 use rocket::data::{Data, ToByteUnit};
@@ -22,8 +24,15 @@ use rsky_lexicon::com::atproto::repo::ListMissingBlobsRefRecordBlob;
 use rsky_repo::error::BlobError;
 use rsky_repo::types::{PreparedBlobRef, PreparedWrite};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+/// Temp blobs moved to permanent storage at once when applying a batch of
+/// writes
+const MAKE_PERMANENT_CONCURRENCY: usize = 8;
+
 pub struct BlobMetadata {
     pub temp_key: String,
     pub size: i64,
@@ -34,7 +43,7 @@ pub struct BlobMetadata {
 }
 
 pub struct BlobReader {
-    pub blobstore: S3BlobStore,
+    pub blobstore: Box<dyn BlobStore>,
     pub did: String,
     pub db: Arc<DbConn>,
 }
@@ -63,17 +72,8 @@ pub struct GetBlobMetadataOutput {
 
 // Basically handles getting blob records from db
 impl BlobReader {
-    pub fn new(blobstore: S3BlobStore, db: Arc<DbConn>) -> Self {
-        let did = if blobstore.bucket.starts_with("did") {
-            blobstore.bucket.clone()
-        } else {
-            format!("did:web5:{}", blobstore.bucket)
-        };
-        BlobReader {
-            did,
-            blobstore,
-            db,
-        }
+    pub fn new(did: String, blobstore: Box<dyn BlobStore>, db: Arc<DbConn>) -> Self {
+        BlobReader { did, blobstore, db }
     }
 
     pub async fn get_blob_metadata(&self, cid: Cid) -> Result<GetBlobMetadataOutput> {
@@ -111,7 +111,12 @@ impl BlobReader {
                     Some(GetObjectError::NoSuchKey(key)) => {
                         Err(anyhow::Error::new(GetObjectError::NoSuchKey(key.clone())))
                     }
-                    _ => bail!(e.to_string()),
+                    _ => match e.downcast_ref() {
+                        Some(BlobError::BlobNotFoundError) => {
+                            Err(anyhow::Error::new(BlobError::BlobNotFoundError))
+                        }
+                        _ => bail!(e.to_string()),
+                    },
                 }
             }
         };
@@ -122,6 +127,36 @@ impl BlobReader {
         })
     }
 
+    /// Serves a processed rendition of an image blob, falling back to the
+    /// original when none was generated for it.
+    pub async fn get_blob_variant(
+        &self,
+        cid: Cid,
+        variant: image::ImageVariant,
+    ) -> Result<GetBlobOutput> {
+        let metadata = self.get_blob_metadata(cid).await?;
+        match self
+            .blobstore
+            .get_derived_bytes(cid, variant.as_str().to_string())
+            .await
+        {
+            Ok(bytes) => Ok(GetBlobOutput {
+                size: bytes.len() as i32,
+                mime_type: image::mime_type_from_bytes(bytes.clone())
+                    .await?
+                    .or(metadata.mime_type),
+                stream: ByteStream::from(bytes),
+            }),
+            Err(e) if matches!(e.downcast_ref(), Some(BlobError::BlobNotFoundError)) => {
+                self.get_blob(cid).await
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(GetObjectError::NoSuchKey(_))) => {
+                self.get_blob(cid).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn get_records_for_blob(&self, cid: Cid) -> Result<Vec<String>> {
         use crate::schema::pds::record_blob::dsl as RecordBlobSchema;
 
@@ -149,16 +184,38 @@ impl BlobReader {
     ) -> Result<BlobMetadata> {
         let blob_stream = blob.open(100.mebibytes());
         let bytes = blob_stream.into_bytes().await?;
-        let size = bytes.n.written;
-        let bytes = bytes.into_inner();
+        self.upload_bytes_and_get_metadata(user_suggested_mime, bytes.into_inner())
+            .await
+    }
+
+    /// Same as `upload_blob_and_get_metadata` for a blob that's already been
+    /// buffered, e.g. one assembled from a resumable upload.
+    pub async fn upload_bytes_and_get_metadata(
+        &self,
+        user_suggested_mime: String,
+        bytes: Vec<u8>,
+    ) -> Result<BlobMetadata> {
+        #[cfg(feature = "image-processing")]
+        let (bytes, derivatives) = match image::processing::process_image(bytes.clone()).await? {
+            Some(processed) => (processed.bytes, processed.derivatives),
+            None => (bytes, Vec::new()),
+        };
+        let size = bytes.len();
         let (temp_key, sha256, img_info, sniffed_mime) = try_join!(
             self.blobstore.put_temp(bytes.clone()),
             sha256_stream(bytes.clone()),
             image::maybe_get_info(bytes.clone()),
             image::mime_type_from_bytes(bytes.clone())
         )?;
+        metrics::BLOB_UPLOAD_BYTES.inc_by(size as u64);
         let cid = sha256_to_cid(sha256);
         let mime_type = sniffed_mime.unwrap_or(user_suggested_mime);
+        #[cfg(feature = "image-processing")]
+        for (variant, derived) in derivatives {
+            self.blobstore
+                .put_derived(cid, variant.as_str().to_string(), derived)
+                .await?;
+        }
 
         Ok(BlobMetadata {
             temp_key,
@@ -230,28 +287,107 @@ impl BlobReader {
 
     pub async fn process_write_blobs(&self, writes: Vec<PreparedWrite>) -> Result<()> {
         self.delete_dereferenced_blobs(writes.clone()).await?;
-        let _ = stream::iter(writes)
-            .then(|write| async move {
-                Ok::<(), anyhow::Error>(match write {
-                    PreparedWrite::Create(w) => {
-                        for blob in w.blobs {
-                            self.verify_blob_and_make_permanent(blob.clone()).await?;
-                            self.associate_blob(blob, w.uri.clone()).await?;
-                        }
-                    }
-                    PreparedWrite::Update(w) => {
-                        for blob in w.blobs {
-                            self.verify_blob_and_make_permanent(blob.clone()).await?;
-                            self.associate_blob(blob, w.uri.clone()).await?;
-                        }
-                    }
-                    _ => (),
-                })
+        let refs: Vec<(PreparedBlobRef, String)> = writes
+            .into_iter()
+            .flat_map(|write| match write {
+                PreparedWrite::Create(w) | PreparedWrite::Update(w) => w
+                    .blobs
+                    .into_iter()
+                    .map(|blob| (blob, w.uri.clone()))
+                    .collect::<Vec<_>>(),
+                PreparedWrite::Delete(_) => vec![],
             })
-            .collect::<Vec<_>>()
-            .await
+            .collect();
+        if refs.is_empty() {
+            return Ok(());
+        }
+        self.verify_blobs_and_make_permanent(refs.iter().map(|(blob, _)| blob.clone()).collect())
+            .await?;
+        let cids: Vec<String> = refs.iter().map(|(blob, _)| blob.cid.to_string()).collect();
+        for (blob, uri) in refs {
+            self.associate_blob(blob, uri).await?;
+        }
+        self.restore_quarantined(cids).await
+    }
+
+    /// Takes blobs the garbage collector quarantined back out, now that a
+    /// record references them again, rather than leaving them unreadable until
+    /// its next sweep
+    async fn restore_quarantined(&self, cids: Vec<String>) -> Result<()> {
+        use crate::schema::pds::blob_gc::dsl as BlobGcSchema;
+
+        let did = self.did.clone();
+        let restored: Vec<String> = self
+            .db
+            .run(move |conn| {
+                delete(BlobGcSchema::blob_gc)
+                    .filter(BlobGcSchema::did.eq(did))
+                    .filter(BlobGcSchema::cid.eq_any(cids))
+                    .returning(BlobGcSchema::cid)
+                    .get_results(conn)
+            })
+            .await?;
+        for cid in restored {
+            self.blobstore.unquarantine(Cid::from_str(&cid)?).await?;
+            BLOB_GC_METRICS
+                .restored_blobs
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Batched `verify_blob_and_make_permanent` for a set of writes: the blob
+    /// rows come back in one query and the temp files are moved to permanent
+    /// storage concurrently.
+    pub async fn verify_blobs_and_make_permanent(&self, blobs: Vec<PreparedBlobRef>) -> Result<()> {
+        use crate::schema::pds::blob::dsl as BlobSchema;
+
+        let cids: Vec<String> = blobs.iter().map(|blob| blob.cid.to_string()).collect();
+        let did = self.did.clone();
+        let found: HashMap<String, models::Blob> = self
+            .db
+            .run(move |conn| {
+                BlobSchema::blob
+                    .filter(BlobSchema::did.eq(did))
+                    .filter(BlobSchema::cid.eq_any(cids))
+                    .filter(BlobSchema::takedownRef.is_null())
+                    .select(models::Blob::as_select())
+                    .load::<models::Blob>(conn)
+            })
+            .await?
             .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(|found| (found.cid.clone(), found))
+            .collect();
+        // the same blob can be referenced by several of the writes
+        let mut temp_keys: HashMap<String, Cid> = HashMap::new();
+        for blob in &blobs {
+            match found.get(&blob.cid.to_string()) {
+                Some(found) => {
+                    verify_blob(blob, found).await?;
+                    if let Some(ref temp_key) = found.temp_key {
+                        temp_keys.insert(temp_key.clone(), blob.cid);
+                    }
+                }
+                None => bail!("Cound not find blob: {:?}", blob.cid.to_string()),
+            }
+        }
+        if temp_keys.is_empty() {
+            return Ok(());
+        }
+        stream::iter(temp_keys.clone())
+            .map(|(temp_key, cid)| self.blobstore.make_permanent(temp_key, cid))
+            .buffer_unordered(MAKE_PERMANENT_CONCURRENCY)
+            .try_collect::<Vec<()>>()
+            .await?;
+        let temp_keys: Vec<String> = temp_keys.into_keys().collect();
+        self.db
+            .run(move |conn| {
+                update(BlobSchema::blob)
+                    .filter(BlobSchema::tempKey.eq_any(temp_keys))
+                    .set(BlobSchema::tempKey.eq::<Option<String>>(None))
+                    .execute(conn)
+            })
+            .await?;
         Ok(())
     }
 
@@ -653,3 +789,6 @@ pub async fn sha256_stream(to_hash: Vec<u8>) -> Result<Vec<u8>> {
     let hash: &[u8] = digest.as_ref();
     Ok(hash.to_vec())
 }
+
+pub mod gc;
+pub mod store;
@@ -0,0 +1,74 @@
+use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::disk::DiskBlobStore;
+use crate::actor_store::memory::MemoryBlobStore;
+use crate::apis::ApiError;
+use crate::config::BlobstoreConfig;
+use anyhow::Result;
+use aws_sdk_s3::primitives::ByteStream;
+use lexicon_cid::Cid;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Storage backend for the blobs of a single actor. Mirrors the BlobStore
+/// interface of the reference implementation.
+#[rocket::async_trait]
+pub trait BlobStore: Send + Sync + Debug {
+    async fn put_temp(&self, bytes: Vec<u8>) -> Result<String>;
+    /// Stores bytes in the temp area under a key chosen by the caller, e.g. the
+    /// chunks of a resumable upload
+    async fn put_temp_with_key(&self, key: String, bytes: Vec<u8>) -> Result<()>;
+    async fn get_temp_bytes(&self, key: String) -> Result<Vec<u8>>;
+    async fn delete_temp_many(&self, keys: Vec<String>) -> Result<()>;
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()>;
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()>;
+    async fn quarantine(&self, cid: Cid) -> Result<()>;
+    async fn unquarantine(&self, cid: Cid) -> Result<()>;
+    /// Permanently removes a blob that was previously quarantined
+    async fn delete_quarantined(&self, cid: Cid) -> Result<()>;
+    /// Stores a processed rendition of a blob (thumbnail, ...) under a key derived from its cid
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()>;
+    async fn get_derived_bytes(&self, cid: Cid, variant: String) -> Result<Vec<u8>>;
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>>;
+    async fn get_stream(&self, cid: Cid) -> Result<ByteStream>;
+    async fn delete(&self, cid: String) -> Result<()>;
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()>;
+    async fn has_stored(&self, cid: Cid) -> Result<bool>;
+    async fn has_temp(&self, key: String) -> Result<bool>;
+    /// Whether the actor's storage location (bucket, directory, ...) has been set up
+    async fn bucket_exists(&self) -> Result<bool, ApiError>;
+    async fn create_bucket(&self) -> Result<bool, ApiError>;
+}
+
+/// Builds the blob store for a given did
+pub type BlobStoreCreator = Box<dyn Fn(String) -> Box<dyn BlobStore> + Send + Sync>;
+
+/// Blob store backend held in managed state, selected by `BlobstoreConfig`
+#[derive(Clone)]
+pub struct SharedBlobStore {
+    pub blob_store: Arc<BlobStoreCreator>,
+}
+
+impl SharedBlobStore {
+    pub fn new(cfg: &BlobstoreConfig) -> Self {
+        let blob_store = match cfg {
+            BlobstoreConfig::S3(s3_cfg) => S3BlobStore::creator(s3_cfg.into()),
+            BlobstoreConfig::Disk {
+                location,
+                tmp_location,
+                quarantine_location,
+            } => DiskBlobStore::creator(
+                location.clone(),
+                tmp_location.clone(),
+                quarantine_location.clone(),
+            ),
+            BlobstoreConfig::Memory => MemoryBlobStore::creator(),
+        };
+        SharedBlobStore {
+            blob_store: Arc::new(blob_store),
+        }
+    }
+
+    pub fn for_did(&self, did: String) -> Box<dyn BlobStore> {
+        (self.blob_store)(did)
+    }
+}
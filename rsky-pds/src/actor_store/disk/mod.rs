@@ -0,0 +1,255 @@
+// based on https://github.com/bluesky-social/atproto/blob/main/packages/pds/src/disk-blobstore.ts
+use crate::actor_store::blob::store::{BlobStore, BlobStoreCreator};
+use crate::apis::ApiError;
+use anyhow::Result;
+use aws_sdk_s3::primitives::ByteStream;
+use lexicon_cid::Cid;
+use rsky_common::get_random_str;
+use rsky_repo::error::BlobError;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::fs;
+
+/// Stores blobs on the local filesystem, for operators running without an
+/// S3-compatible object store. Each actor gets its own directory under the
+/// configured locations.
+#[derive(Debug, Clone)]
+pub struct DiskBlobStore {
+    pub location: PathBuf,
+    pub tmp_location: PathBuf,
+    pub quarantine_location: PathBuf,
+    pub derived_location: PathBuf,
+}
+
+impl DiskBlobStore {
+    pub fn new(
+        did: String,
+        location: &PathBuf,
+        tmp_location: &PathBuf,
+        quarantine_location: &PathBuf,
+    ) -> Self {
+        DiskBlobStore {
+            location: location.join(&did),
+            tmp_location: tmp_location.join(&did),
+            quarantine_location: quarantine_location.join(&did),
+            derived_location: location.join("derived").join(&did),
+        }
+    }
+
+    pub fn creator(
+        location: String,
+        tmp_location: Option<String>,
+        quarantine_location: Option<String>,
+    ) -> BlobStoreCreator {
+        let location = PathBuf::from(location);
+        let tmp_location = match tmp_location {
+            Some(tmp_location) => PathBuf::from(tmp_location),
+            None => location.join("temp"),
+        };
+        let quarantine_location = match quarantine_location {
+            Some(quarantine_location) => PathBuf::from(quarantine_location),
+            None => location.join("quarantine"),
+        };
+        Box::new(move |did: String| {
+            Box::new(DiskBlobStore::new(
+                did,
+                &location,
+                &tmp_location,
+                &quarantine_location,
+            ))
+        })
+    }
+
+    fn gen_key(&self) -> String {
+        get_random_str()
+    }
+
+    fn get_tmp_path(&self, key: &String) -> PathBuf {
+        self.tmp_location.join(key)
+    }
+
+    fn get_stored_path(&self, cid: Cid) -> PathBuf {
+        self.location.join(cid.to_string())
+    }
+
+    fn get_quarantined_path(&self, cid: Cid) -> PathBuf {
+        self.quarantine_location.join(cid.to_string())
+    }
+
+    fn get_derived_dir(&self, cid: Cid) -> PathBuf {
+        self.derived_location.join(cid.to_string())
+    }
+
+    async fn delete_derived(&self, cid: Cid) -> Result<()> {
+        match fs::remove_dir_all(self.get_derived_dir(cid)).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn move_file(&self, from: &Path, to: &Path) -> Result<()> {
+        match fs::rename(from, to).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(anyhow::Error::new(BlobError::BlobNotFoundError))
+            }
+            // rename() can't cross filesystems, e.g. when tmp is mounted separately
+            Err(_) => {
+                fs::copy(from, to).await?;
+                fs::remove_file(from).await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn remove_if_exists(&self, path: &Path) -> Result<()> {
+        match fs::remove_file(path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl BlobStore for DiskBlobStore {
+    async fn put_temp(&self, bytes: Vec<u8>) -> Result<String> {
+        fs::create_dir_all(&self.tmp_location).await?;
+        let key = self.gen_key();
+        fs::write(self.get_tmp_path(&key), bytes).await?;
+        Ok(key)
+    }
+
+    async fn put_temp_with_key(&self, key: String, bytes: Vec<u8>) -> Result<()> {
+        fs::create_dir_all(&self.tmp_location).await?;
+        fs::write(self.get_tmp_path(&key), bytes).await?;
+        Ok(())
+    }
+
+    async fn get_temp_bytes(&self, key: String) -> Result<Vec<u8>> {
+        match fs::read(self.get_tmp_path(&key)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(anyhow::Error::new(BlobError::BlobNotFoundError))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_temp_many(&self, keys: Vec<String>) -> Result<()> {
+        for key in keys {
+            self.remove_if_exists(&self.get_tmp_path(&key)).await?;
+        }
+        Ok(())
+    }
+
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
+        fs::create_dir_all(&self.location).await?;
+        let tmp_path = self.get_tmp_path(&key);
+        if self.has_stored(cid).await? {
+            // already saved, so we no-op & just delete the temp
+            self.remove_if_exists(&tmp_path).await
+        } else {
+            self.move_file(&tmp_path, &self.get_stored_path(cid)).await
+        }
+    }
+
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        fs::create_dir_all(&self.location).await?;
+        fs::write(self.get_stored_path(cid), bytes).await?;
+        Ok(())
+    }
+
+    async fn quarantine(&self, cid: Cid) -> Result<()> {
+        fs::create_dir_all(&self.quarantine_location).await?;
+        self.move_file(&self.get_stored_path(cid), &self.get_quarantined_path(cid))
+            .await
+    }
+
+    async fn unquarantine(&self, cid: Cid) -> Result<()> {
+        fs::create_dir_all(&self.location).await?;
+        self.move_file(&self.get_quarantined_path(cid), &self.get_stored_path(cid))
+            .await
+    }
+
+    async fn delete_quarantined(&self, cid: Cid) -> Result<()> {
+        self.delete_derived(cid).await?;
+        self.remove_if_exists(&self.get_quarantined_path(cid)).await
+    }
+
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()> {
+        let dir = self.get_derived_dir(cid);
+        fs::create_dir_all(&dir).await?;
+        fs::write(dir.join(variant), bytes).await?;
+        Ok(())
+    }
+
+    async fn get_derived_bytes(&self, cid: Cid, variant: String) -> Result<Vec<u8>> {
+        match fs::read(self.get_derived_dir(cid).join(variant)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(anyhow::Error::new(BlobError::BlobNotFoundError))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
+        match fs::read(self.get_stored_path(cid)).await {
+            Ok(bytes) => Ok(bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(anyhow::Error::new(BlobError::BlobNotFoundError))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_stream(&self, cid: Cid) -> Result<ByteStream> {
+        let path = self.get_stored_path(cid);
+        if !fs::try_exists(&path).await? {
+            return Err(anyhow::Error::new(BlobError::BlobNotFoundError));
+        }
+        Ok(ByteStream::from_path(path).await?)
+    }
+
+    async fn delete(&self, cid: String) -> Result<()> {
+        let cid = Cid::from_str(&cid)?;
+        self.delete_derived(cid).await?;
+        self.remove_if_exists(&self.get_stored_path(cid)).await
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+        for cid in cids {
+            self.delete_derived(cid).await?;
+            self.remove_if_exists(&self.get_stored_path(cid)).await?;
+        }
+        Ok(())
+    }
+
+    async fn has_stored(&self, cid: Cid) -> Result<bool> {
+        Ok(fs::try_exists(self.get_stored_path(cid)).await?)
+    }
+
+    async fn has_temp(&self, key: String) -> Result<bool> {
+        Ok(fs::try_exists(self.get_tmp_path(&key)).await?)
+    }
+
+    async fn bucket_exists(&self) -> Result<bool, ApiError> {
+        fs::try_exists(&self.location).await.map_err(|e| {
+            tracing::error!("@LOG: ERROR: failed to stat {:?}: {e}", self.location);
+            ApiError::RuntimeError
+        })
+    }
+
+    async fn create_bucket(&self) -> Result<bool, ApiError> {
+        match fs::create_dir_all(&self.location).await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::error!("@LOG: ERROR: failed to create {:?}: {e}", self.location);
+                Err(ApiError::RuntimeError)
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::actor_store::blob::store::{BlobStore, BlobStoreCreator};
+use crate::apis::ApiError;
+use anyhow::Result;
+use aws_sdk_s3::primitives::ByteStream;
+use lexicon_cid::Cid;
+use rsky_common::get_random_str;
+use rsky_repo::error::BlobError;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+pub struct MemoryBlobs {
+    buckets: HashSet<String>,
+    objects: HashMap<String, Vec<u8>>,
+}
+
+/// Keeps blobs in process memory. Nothing survives a restart, so this is only
+/// meant for tests and local development without any storage dependency.
+#[derive(Debug, Clone)]
+pub struct MemoryBlobStore {
+    blobs: Arc<Mutex<MemoryBlobs>>,
+    pub bucket: String,
+}
+
+impl MemoryBlobStore {
+    pub fn new(did: String, blobs: Arc<Mutex<MemoryBlobs>>) -> Self {
+        MemoryBlobStore { blobs, bucket: did }
+    }
+
+    /// All stores built by one creator share the same backing map, so blobs
+    /// written through one request are visible to the next.
+    pub fn creator() -> BlobStoreCreator {
+        let blobs = Arc::new(Mutex::new(MemoryBlobs::default()));
+        Box::new(move |did: String| Box::new(MemoryBlobStore::new(did, blobs.clone())))
+    }
+
+    fn get_tmp_path(&self, key: &String) -> String {
+        format!("tmp/{0}/{1}", self.bucket, key)
+    }
+
+    fn get_stored_path(&self, cid: Cid) -> String {
+        format!("blocks/{0}/{1}", self.bucket, cid)
+    }
+
+    fn get_quarantined_path(&self, cid: Cid) -> String {
+        format!("quarantine/{0}/{1}", self.bucket, cid)
+    }
+
+    fn get_derived_prefix(&self, cid: Cid) -> String {
+        format!("derived/{0}/{1}/", self.bucket, cid)
+    }
+
+    fn remove_derived(blobs: &mut MemoryBlobs, prefix: &String) {
+        blobs.objects.retain(|key, _| !key.starts_with(prefix));
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryBlobs>> {
+        self.blobs
+            .lock()
+            .map_err(|_| anyhow::anyhow!("memory blobstore lock poisoned"))
+    }
+
+    fn move_object(&self, from: String, to: String) -> Result<()> {
+        let mut blobs = self.lock()?;
+        match blobs.objects.remove(&from) {
+            None => Err(anyhow::Error::new(BlobError::BlobNotFoundError)),
+            Some(bytes) => {
+                blobs.objects.insert(to, bytes);
+                Ok(())
+            }
+        }
+    }
+
+    fn has_key(&self, key: String) -> Result<bool> {
+        Ok(self.lock()?.objects.contains_key(&key))
+    }
+}
+
+#[rocket::async_trait]
+impl BlobStore for MemoryBlobStore {
+    async fn put_temp(&self, bytes: Vec<u8>) -> Result<String> {
+        let key = get_random_str();
+        self.lock()?.objects.insert(self.get_tmp_path(&key), bytes);
+        Ok(key)
+    }
+
+    async fn put_temp_with_key(&self, key: String, bytes: Vec<u8>) -> Result<()> {
+        self.lock()?.objects.insert(self.get_tmp_path(&key), bytes);
+        Ok(())
+    }
+
+    async fn get_temp_bytes(&self, key: String) -> Result<Vec<u8>> {
+        match self.lock()?.objects.get(&self.get_tmp_path(&key)) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(anyhow::Error::new(BlobError::BlobNotFoundError)),
+        }
+    }
+
+    async fn delete_temp_many(&self, keys: Vec<String>) -> Result<()> {
+        let mut blobs = self.lock()?;
+        for key in keys {
+            blobs.objects.remove(&self.get_tmp_path(&key));
+        }
+        Ok(())
+    }
+
+    async fn make_permanent(&self, key: String, cid: Cid) -> Result<()> {
+        if self.has_stored(cid).await? {
+            // already saved, so we no-op & just delete the temp
+            self.lock()?.objects.remove(&self.get_tmp_path(&key));
+            Ok(())
+        } else {
+            self.move_object(self.get_tmp_path(&key), self.get_stored_path(cid))
+        }
+    }
+
+    async fn put_permanent(&self, cid: Cid, bytes: Vec<u8>) -> Result<()> {
+        self.lock()?
+            .objects
+            .insert(self.get_stored_path(cid), bytes);
+        Ok(())
+    }
+
+    async fn quarantine(&self, cid: Cid) -> Result<()> {
+        self.move_object(self.get_stored_path(cid), self.get_quarantined_path(cid))
+    }
+
+    async fn unquarantine(&self, cid: Cid) -> Result<()> {
+        self.move_object(self.get_quarantined_path(cid), self.get_stored_path(cid))
+    }
+
+    async fn delete_quarantined(&self, cid: Cid) -> Result<()> {
+        let path = self.get_quarantined_path(cid);
+        let mut blobs = self.lock()?;
+        blobs.objects.remove(&path);
+        Self::remove_derived(&mut blobs, &self.get_derived_prefix(cid));
+        Ok(())
+    }
+
+    async fn put_derived(&self, cid: Cid, variant: String, bytes: Vec<u8>) -> Result<()> {
+        let path = format!("{0}{1}", self.get_derived_prefix(cid), variant);
+        self.lock()?.objects.insert(path, bytes);
+        Ok(())
+    }
+
+    async fn get_derived_bytes(&self, cid: Cid, variant: String) -> Result<Vec<u8>> {
+        let path = format!("{0}{1}", self.get_derived_prefix(cid), variant);
+        match self.lock()?.objects.get(&path) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(anyhow::Error::new(BlobError::BlobNotFoundError)),
+        }
+    }
+
+    async fn get_bytes(&self, cid: Cid) -> Result<Vec<u8>> {
+        match self.lock()?.objects.get(&self.get_stored_path(cid)) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(anyhow::Error::new(BlobError::BlobNotFoundError)),
+        }
+    }
+
+    async fn get_stream(&self, cid: Cid) -> Result<ByteStream> {
+        Ok(ByteStream::from(self.get_bytes(cid).await?))
+    }
+
+    async fn delete(&self, cid: String) -> Result<()> {
+        let cid = Cid::from_str(&cid)?;
+        let mut blobs = self.lock()?;
+        blobs.objects.remove(&self.get_stored_path(cid));
+        Self::remove_derived(&mut blobs, &self.get_derived_prefix(cid));
+        Ok(())
+    }
+
+    async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
+        let mut blobs = self.lock()?;
+        for cid in cids {
+            blobs.objects.remove(&self.get_stored_path(cid));
+            Self::remove_derived(&mut blobs, &self.get_derived_prefix(cid));
+        }
+        Ok(())
+    }
+
+    async fn has_stored(&self, cid: Cid) -> Result<bool> {
+        self.has_key(self.get_stored_path(cid))
+    }
+
+    async fn has_temp(&self, key: String) -> Result<bool> {
+        self.has_key(self.get_tmp_path(&key))
+    }
+
+    async fn bucket_exists(&self) -> Result<bool, ApiError> {
+        match self.lock() {
+            Ok(blobs) => Ok(blobs.buckets.contains(&self.bucket)),
+            Err(_) => Err(ApiError::RuntimeError),
+        }
+    }
+
+    async fn create_bucket(&self) -> Result<bool, ApiError> {
+        match self.lock() {
+            Ok(mut blobs) => {
+                blobs.buckets.insert(self.bucket.clone());
+                Ok(true)
+            }
+            Err(_) => Err(ApiError::RuntimeError),
+        }
+    }
+}
@@ -1,18 +1,20 @@
 // based on https://github.com/bluesky-social/atproto/blob/main/packages/repo/src/repo.ts
 // also adds components from https://github.com/bluesky-social/atproto/blob/main/packages/pds/src/actor-store/repo/transactor.ts
 
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::BlobStore;
 use crate::actor_store::blob::BlobReader;
 use crate::actor_store::preference::PreferenceReader;
 use crate::actor_store::record::RecordReader;
 use crate::actor_store::repo::sql_repo::SqlRepoReader;
-use crate::actor_store::repo::types::SyncEvtData;
+use crate::actor_store::repo::types::{RepoCompaction, SyncEvtData};
+use crate::actor_store::write_lock::{RepoWriteGuard, REPO_WRITE_LOCKS};
 use crate::db::DbConn;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use diesel::*;
 use futures::stream::{self, StreamExt};
 use lexicon_cid::Cid;
 use rsky_common;
+use rsky_common::tid::TID;
 use rsky_lexicon::com::atproto::web5::{PreCreateAccountOutput, SignedRoot};
 use rsky_repo::repo::Repo;
 use rsky_repo::storage::readable_blockstore::ReadableBlockstore;
@@ -54,6 +56,26 @@ impl fmt::Display for FormatCommitError {
 
 impl std::error::Error for FormatCommitError {}
 
+/// Returned when a write raced another commit to the same repo, carrying the
+/// head the client should rebuild its commit on top of.
+#[derive(thiserror::Error, Debug)]
+#[error("ConcurrentWriteError: repo head is now {head} at rev {rev}")]
+pub struct ConcurrentWriteError {
+    pub head: Cid,
+    pub rev: String,
+}
+
+/// MST keys the writes touch, to prefetch the tree nodes along their paths
+fn write_keys(writes: &[PreparedWrite]) -> Result<Vec<String>> {
+    writes
+        .iter()
+        .map(|write| {
+            let uri: AtUri = write.uri().try_into()?;
+            Ok(format_data_key(uri.get_collection(), uri.get_rkey()))
+        })
+        .collect()
+}
+
 pub struct ActorStore {
     pub did: String,
     pub storage: Arc<RwLock<SqlRepoReader>>, // get ipld blocks from db
@@ -64,8 +86,8 @@ pub struct ActorStore {
 
 // Combination of RepoReader/Transactor, BlobReader/Transactor, SqlRepoReader/Transactor
 impl ActorStore {
-    /// Concrete reader of an individual repo (hence S3BlobStore which takes `did` param)
-    pub fn new(did: String, blobstore: S3BlobStore, db: DbConn) -> Self {
+    /// Concrete reader of an individual repo (hence BlobStore which is built for a `did`)
+    pub fn new(did: String, blobstore: Box<dyn BlobStore>, db: DbConn) -> Self {
         let db = Arc::new(db);
         ActorStore {
             storage: Arc::new(RwLock::new(SqlRepoReader::new(
@@ -76,10 +98,15 @@ impl ActorStore {
             record: RecordReader::new(did.clone(), db.clone()),
             pref: PreferenceReader::new(did.clone(), db.clone()),
             did,
-            blob: BlobReader::new(blobstore, db.clone()), // Unlike TS impl, just use blob reader vs generator
+            blob: BlobReader::new(did.clone(), blobstore, db.clone()), // Unlike TS impl, just use blob reader vs generator
         }
     }
 
+    /// Serializes commit application for this repo, see `RepoWriteLocks`
+    pub async fn lock_writes(&self) -> Result<RepoWriteGuard> {
+        REPO_WRITE_LOCKS.acquire(&self.did).await
+    }
+
     pub async fn get_repo_root(&self) -> Option<Cid> {
         let storage_guard = self.storage.read().await;
         storage_guard.get_root().await
@@ -266,6 +293,7 @@ impl ActorStore {
         commit: CommitData,
         writes: Vec<PreparedWrite>,
     ) -> Result<()> {
+        REPO_WRITE_LOCKS.ensure_held(&self.did)?;
         {
             let immutable_borrow = &self;
             // & send to indexing
@@ -294,6 +322,7 @@ impl ActorStore {
         // https://github.com/bluesky-social/atproto/pull/3585/files#diff-7627844a4a6b50190014e947d1331a96df3c64d4c5273fa0ce544f85c3c1265f
         let commit = self.format_commit(writes.clone(), swap_commit_cid).await?;
         // }
+        REPO_WRITE_LOCKS.ensure_held(&self.did)?;
         {
             let immutable_borrow = &self;
             // & send to indexing
@@ -321,7 +350,15 @@ impl ActorStore {
         let commit: CommitDataWithOps = self
             .verify_commit(writes.clone(), swap_commit_cid, signing_key, root)
             .await?;
-        // }
+        REPO_WRITE_LOCKS.ensure_held(&self.did)?;
+        // persist the commit to repo storage, keeping deleted records around for undo.
+        // This reads the records being deleted, so it goes before indexing drops them
+        {
+            let storage_guard = self.storage.read().await;
+            storage_guard
+                .apply_commit_with_tombstones(commit.commit_data.clone(), writes.clone())
+                .await?;
+        }
         {
             let immutable_borrow = &self;
             // & send to indexing
@@ -329,11 +366,6 @@ impl ActorStore {
                 .index_writes(writes.clone(), &commit.commit_data.rev)
                 .await?;
         }
-        // persist the commit to repo storage
-        let storage_guard: tokio::sync::RwLockReadGuard<'_, SqlRepoReader> = self.storage.read().await;
-        storage_guard
-            .apply_commit(commit.commit_data.clone(), None)
-            .await?;
         // process blobs
         self.blob.process_write_blobs(writes).await?;
         Ok(commit)
@@ -450,6 +482,12 @@ impl ActorStore {
             }
             let mut repo = Repo::load(self.storage.clone(), Some(current_root.cid)).await?;
             let previous_data = repo.commit.data;
+            {
+                let storage_guard = self.storage.read().await;
+                storage_guard
+                    .prefetch_mst_paths(previous_data, write_keys(&writes)?)
+                    .await?;
+            }
             let write_ops: Vec<RecordWriteOp> = writes
                 .into_iter()
                 .map(write_to_op)
@@ -494,6 +532,9 @@ impl ActorStore {
         }
     }
 
+    /// Checks a commit the client signed against the repo's current head.
+    /// Callers hold [`ActorStore::lock_writes`], so the head can't move between
+    /// this check and the commit being applied.
     pub async fn verify_commit(
         &mut self,
         writes: Vec<PreparedWrite>,
@@ -506,12 +547,22 @@ impl ActorStore {
             storage_guard.get_root_detailed().await
         };
         if let Ok(current_root) = current_root {
-            if let Some(swap_commit) = swap_commit {
-                if !current_root.cid.eq(&swap_commit) {
-                    return Err(
-                        FormatCommitError::BadCommitSwap(current_root.cid.to_string()).into(),
-                    );
+            // preDirectWrites records the head it built the commit on with the
+            // rev it issued, so the commit only lines up if that's still the
+            // head. Comparing revs alone would let a commit issued later land
+            // second on the same base.
+            let issued_base = {
+                let storage_guard = self.storage.read().await;
+                storage_guard.issued_base(&root.rev).await?
+            };
+            let head_moved = issued_base != Some(current_root.cid)
+                || swap_commit.is_some_and(|swap_commit| !current_root.cid.eq(&swap_commit));
+            if head_moved {
+                return Err(ConcurrentWriteError {
+                    head: current_root.cid,
+                    rev: current_root.rev,
                 }
+                .into());
             }
             {
                 let mut storage_guard = self.storage.write().await;
@@ -596,6 +647,12 @@ impl ActorStore {
             }
             let mut repo = Repo::load(self.storage.clone(), Some(current_root.cid)).await?;
             let previous_data = repo.commit.data;
+            {
+                let storage_guard = self.storage.read().await;
+                storage_guard
+                    .prefetch_mst_paths(previous_data, write_keys(&writes)?)
+                    .await?;
+            }
             let write_ops: Vec<RecordWriteOp> = writes
                 .into_iter()
                 .map(write_to_op)
@@ -707,7 +764,13 @@ impl ActorStore {
                 .map(write_to_op)
                 .collect::<Result<Vec<RecordWriteOp>>>()?;
 
-            repo.generate_commit(RecordWriteEnum::List(write_ops)).await
+            // issued rather than taken from the local clock, so revs handed out to
+            // concurrent pre* sessions keep increasing
+            let rev = {
+                let storage_guard = self.storage.read().await;
+                storage_guard.issue_rev(current_root.cid).await?
+            };
+            repo.generate_commit_at(RecordWriteEnum::List(write_ops), TID(rev)).await
         } else {
             Err(FormatCommitError::MissingRepoRoot(self.did.clone()).into())
         }
@@ -782,9 +845,90 @@ impl ActorStore {
             .await
             .into_iter()
             .collect::<Result<Vec<_>, _>>()?;
+        self.destroy_rows(db).await
+    }
+
+    /// Removes everything the actor store keeps in postgres for this did.
+    /// The repo root itself belongs to the account and goes with it.
+    async fn destroy_rows(&self, db: Arc<DbConn>) -> Result<()> {
+        use crate::schema::pds::account_pref::dsl as AccountPrefSchema;
+        use crate::schema::pds::backlink::dsl as BacklinkSchema;
+        use crate::schema::pds::bbs_crosspost::dsl as CrosspostSchema;
+        use crate::schema::pds::bbs_graph::dsl as GraphSchema;
+        use crate::schema::pds::bbs_thread_index::dsl as ThreadIndexSchema;
+        use crate::schema::pds::bbs_thread_watch::dsl as WatchSchema;
+        use crate::schema::pds::blob::dsl as BlobSchema;
+        use crate::schema::pds::record::dsl as RecordSchema;
+        use crate::schema::pds::record_blob::dsl as RecordBlobSchema;
+        use crate::schema::pds::record_tombstone::dsl as TombstoneSchema;
+        use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+        let did: String = self.did.clone();
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                delete(RecordBlobSchema::record_blob)
+                    .filter(RecordBlobSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(BacklinkSchema::backlink)
+                    .filter(BacklinkSchema::uri.like(format!("at://{did}/%")))
+                    .execute(conn)?;
+                delete(GraphSchema::bbs_graph)
+                    .filter(GraphSchema::creatorDid.eq(&did))
+                    .execute(conn)?;
+                delete(WatchSchema::bbs_thread_watch)
+                    .filter(WatchSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(ThreadIndexSchema::bbs_thread_index)
+                    .filter(ThreadIndexSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(CrosspostSchema::bbs_crosspost)
+                    .filter(CrosspostSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(RecordSchema::record)
+                    .filter(RecordSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(TombstoneSchema::record_tombstone)
+                    .filter(TombstoneSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(RepoBlockSchema::repo_block)
+                    .filter(RepoBlockSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(BlobSchema::blob)
+                    .filter(BlobSchema::did.eq(&did))
+                    .execute(conn)?;
+                delete(AccountPrefSchema::account_pref)
+                    .filter(AccountPrefSchema::did.eq(&did))
+                    .execute(conn)?;
+                Ok::<_, diesel::result::Error>(())
+            })
+        })
+        .await?;
         Ok(())
     }
 
+    /// Rewrites the blockstore to only hold blocks reachable from the current
+    /// root, dropping the MST nodes and records left behind by old commits.
+    /// Safe to run while the actor keeps writing: the tree is walked without a
+    /// lock and the cleanup only holds a read lock on this actor's storage.
+    pub async fn compact_repo(&self) -> Result<RepoCompaction> {
+        let root = {
+            let storage_guard = self.storage.read().await;
+            storage_guard.get_root_detailed().await?
+        };
+        let repo = Repo::load(self.storage.clone(), Some(root.cid)).await?;
+        let mut reachable = repo.data.all_cids().await?;
+        reachable.add(root.cid);
+
+        let storage_guard = self.storage.read().await;
+        match storage_guard
+            .delete_unreachable_blocks(root, reachable)
+            .await?
+        {
+            Some(compaction) => Ok(compaction),
+            None => bail!("Repo root for {} moved during compaction", self.did),
+        }
+    }
+
     pub async fn get_duplicate_record_cids(
         &self,
         cids: Vec<Cid>,
@@ -818,6 +962,9 @@ impl ActorStore {
 
 pub mod aws;
 pub mod blob;
+pub mod disk;
+pub mod memory;
 pub mod preference;
 pub mod record;
 pub mod repo;
+pub mod write_lock;
@@ -6,6 +6,7 @@ use crate::models::AccountPref;
 use anyhow::{bail, Result};
 use diesel::*;
 use rsky_lexicon::app::bsky::actor::RefPreferences;
+use serde_json::Value;
 use std::sync::Arc;
 
 pub struct PreferenceReader {
@@ -23,6 +24,20 @@ impl PreferenceReader {
         namespace: Option<String>,
         scope: AuthScope,
     ) -> Result<Vec<RefPreferences>> {
+        self.get_preference_values(namespace, scope)
+            .await?
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value::<RefPreferences>(value)?))
+            .collect()
+    }
+
+    /// Like `get_preferences`, for namespaces whose preferences the PDS
+    /// doesn't know the shape of. Each is left as the JSON it was put as.
+    pub async fn get_preference_values(
+        &self,
+        namespace: Option<String>,
+        scope: AuthScope,
+    ) -> Result<Vec<Value>> {
         use crate::schema::pds::account_pref::dsl as AccountPrefSchema;
 
         let did = self.did.clone();
@@ -43,14 +58,14 @@ impl PreferenceReader {
                     .map(|pref| {
                         let value_json_res = match pref.value_json {
                             None => bail!("preferences json null for {}", pref.name),
-                            Some(value_json) => serde_json::from_str::<RefPreferences>(&value_json),
+                            Some(value_json) => serde_json::from_str::<Value>(&value_json),
                         };
                         match value_json_res {
                             Err(error) => bail!(error.to_string()),
                             Ok(value_json) => Ok(value_json),
                         }
                     })
-                    .collect::<Result<Vec<RefPreferences>>>()?;
+                    .collect::<Result<Vec<Value>>>()?;
                 Ok(account_prefs)
             })
             .await
@@ -62,20 +77,43 @@ impl PreferenceReader {
         values: Vec<RefPreferences>,
         namespace: String,
         scope: AuthScope,
+    ) -> Result<()> {
+        let values = values
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<serde_json::Result<Vec<Value>>>()?;
+        self.put_preference_values(values, namespace, scope).await
+    }
+
+    /// Like `put_preferences`, for namespaces whose preferences the PDS
+    /// doesn't know the shape of. Each value is named by its `$type`.
+    #[tracing::instrument(skip_all)]
+    pub async fn put_preference_values(
+        &self,
+        values: Vec<Value>,
+        namespace: String,
+        scope: AuthScope,
     ) -> Result<()> {
         let did = self.did.clone();
         self.db
             .run(move |conn| {
-                match values
+                let Some(names) = values
+                    .iter()
+                    .map(|value| value.get("$type")?.as_str().map(str::to_string))
+                    .collect::<Option<Vec<String>>>()
+                else {
+                    bail!("Some preferences are missing a $type")
+                };
+                match names
                     .iter()
-                    .all(|value| pref_match_namespace(&namespace, &value.get_type()))
+                    .all(|name| pref_match_namespace(&namespace, name))
                 {
                     false => bail!("Some preferences are not in the {namespace} namespace"),
                     true => {
-                        let not_in_scope = values
+                        let not_in_scope = names
                             .iter()
-                            .filter(|value| !pref_in_scope(scope.clone(), value.get_type()))
-                            .collect::<Vec<&RefPreferences>>();
+                            .filter(|name| !pref_in_scope(scope.clone(), name.to_string()))
+                            .collect::<Vec<&String>>();
                         if !not_in_scope.is_empty() {
                             tracing::info!(
                         "@LOG: PreferenceReader::put_preferences() debug scope: {:?}, values: {:?}",
@@ -90,12 +128,13 @@ impl PreferenceReader {
                             .filter(AccountPrefSchema::did.eq(&did))
                             .select(models::AccountPref::as_select())
                             .load(conn)?;
-                        let put_prefs = values
+                        let put_prefs = names
                             .into_iter()
-                            .map(|value| {
+                            .zip(values)
+                            .map(|(name, value)| {
                                 Ok(AccountPref {
                                     id: 0,
-                                    name: value.get_type(),
+                                    name,
                                     value_json: Some(serde_json::to_string(&value)?),
                                 })
                             })
@@ -1,12 +1,22 @@
+use crate::bbs::graph::{index_relation, relation_subject};
+use crate::bbs::unfurl::{first_link, index_post_link};
+use crate::bbs::watch::{index_watch, watched_thread};
+use crate::bbs::POST_COLLECTION;
+use crate::db::replica::ReadConn;
 use crate::db::DbConn;
-use crate::models::{models, Backlink, Record};
+use crate::models::{models, Backlink, BbsGraph, BbsThreadWatch, Record};
+use crate::sequencer::events::{CommitEvt, CommitEvtOpAction};
 use anyhow::{bail, Result};
+use diesel::dsl::sql;
 use diesel::result::Error;
+use diesel::sql_types::{Bool, Text};
 use diesel::*;
 use futures::stream::{self, StreamExt};
 use lexicon_cid::Cid;
 use rsky_common;
+use rsky_common::cbor_to_struct;
 use rsky_lexicon::com::atproto::admin::StatusAttr;
+use rsky_repo::car::read_car;
 use rsky_repo::storage::Ipld;
 use rsky_repo::types::{Ids, Lex, RepoRecord, WriteOpAction};
 use rsky_repo::util::cbor_to_lex_record;
@@ -14,6 +24,7 @@ use rsky_syntax::aturi::AtUri;
 use rsky_syntax::aturi_validation::ensure_valid_at_uri;
 use rsky_syntax::did::ensure_valid_did;
 use serde_json::Value as JsonValue;
+use std::collections::HashSet;
 use std::env;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -79,6 +90,181 @@ pub fn get_backlinks(uri: &AtUri, record: &RepoRecord) -> Result<Vec<Backlink>>
     Ok(Vec::new())
 }
 
+/// Lists records in `collection` ordered by rkey. A collection ending in
+/// `.*` (e.g. `app.bbs.*`) matches every collection under that prefix; the
+/// cursor for such a listing is `{rkey}/{collection}` since rkeys alone
+/// aren't unique across collections. Takes a [`ReadConn`] since listings
+/// don't need to see the caller's own writes and can be served by a replica.
+#[allow(clippy::too_many_arguments)]
+pub async fn list_records_for_collection(
+    db: &ReadConn,
+    did: String,
+    collection: String,
+    limit: i64,
+    reverse: bool,
+    cursor: Option<String>,
+    rkey_start: Option<String>,
+    rkey_end: Option<String>,
+    include_soft_deleted: Option<bool>,
+) -> Result<Vec<RecordsForCollection>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let include_soft_deleted: bool = if let Some(include_soft_deleted) = include_soft_deleted {
+        include_soft_deleted
+    } else {
+        false
+    };
+    let mut builder = RecordSchema::record
+        .inner_join(
+            RepoBlockSchema::repo_block.on(RepoBlockSchema::cid
+                .eq(RecordSchema::cid)
+                .and(RepoBlockSchema::did.eq(RecordSchema::did))),
+        )
+        .limit(limit)
+        .select((models::Record::as_select(), models::RepoBlock::as_select()))
+        .filter(RecordSchema::did.eq(did))
+        .into_boxed();
+    let collection_prefix = collection_prefix(&collection)?;
+    match collection_prefix {
+        Some(ref prefix) => {
+            builder = builder.filter(RecordSchema::collection.like(format!("{prefix}%")))
+        }
+        None => builder = builder.filter(RecordSchema::collection.eq(collection.clone())),
+    }
+    if !include_soft_deleted {
+        builder = builder.filter(RecordSchema::takedownRef.is_null());
+    }
+    if reverse {
+        builder = builder.order((RecordSchema::rkey.asc(), RecordSchema::collection.asc()));
+    } else {
+        builder = builder.order((RecordSchema::rkey.desc(), RecordSchema::collection.desc()));
+    }
+
+    if let Some(cursor) = cursor {
+        let (rkey, cursor_collection) = match collection_prefix {
+            Some(_) => match cursor.split_once('/') {
+                Some((rkey, collection)) => (rkey.to_string(), collection.to_string()),
+                None => bail!("Invalid cursor for collection prefix: {cursor}"),
+            },
+            None => (cursor, collection),
+        };
+        // compare (rkey, collection) as a tuple so paging across collections
+        // never skips records that share an rkey
+        let comparison = if reverse { ") > (" } else { ") < (" };
+        builder = builder.filter(
+            sql::<Bool>("((")
+                .bind(RecordSchema::rkey)
+                .sql(", ")
+                .bind(RecordSchema::collection)
+                .sql(comparison)
+                .bind::<Text, _>(rkey)
+                .sql(", ")
+                .bind::<Text, _>(cursor_collection)
+                .sql("))"),
+        );
+    } else {
+        if let Some(rkey_start) = rkey_start {
+            builder = builder.filter(RecordSchema::rkey.gt(rkey_start));
+        }
+        if let Some(rkey_end) = rkey_end {
+            builder = builder.filter(RecordSchema::rkey.lt(rkey_end));
+        }
+    }
+    let res: Vec<(models::Record, models::RepoBlock)> =
+        db.run(move |conn| builder.load(conn)).await?;
+    res.into_iter()
+        .map(|row| {
+            Ok(RecordsForCollection {
+                uri: row.0.uri,
+                cid: row.0.cid,
+                value: cbor_to_lex_record(row.1.content)?,
+            })
+        })
+        .collect::<Result<Vec<RecordsForCollection>>>()
+}
+
+/// A record as one commit left it. Deletes have no `cid` or `value`, and
+/// neither does a version whose commit event was too big to carry blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordVersion {
+    pub action: CommitEvtOpAction,
+    pub cid: Option<Cid>,
+    pub rev: String,
+    pub value: Option<RepoRecord>,
+    pub sequenced_at: String,
+}
+
+/// Walks the account's commit events newest first, from before `before_seq`
+/// when given, collecting up to `limit` versions of the record at `uri`.
+/// repo_block drops a record's block once a commit replaces it, so the
+/// blocks each sequenced commit carries are where earlier versions live.
+/// At most `max_commits` events are read; the seq to resume from is
+/// returned unless the walk reached the account's first commit.
+pub async fn get_record_history(
+    db: &ReadConn,
+    uri: &AtUri,
+    limit: usize,
+    before_seq: Option<i64>,
+    max_commits: i64,
+) -> Result<(Vec<RecordVersion>, Option<i64>)> {
+    use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+
+    let path = format!("{}/{}", uri.get_collection(), uri.get_rkey());
+    let mut builder = RepoSeqSchema::repo_seq
+        .select(models::RepoSeq::as_select())
+        .filter(RepoSeqSchema::did.eq(uri.get_hostname().clone()))
+        .filter(RepoSeqSchema::invalidated.eq(0))
+        .filter(RepoSeqSchema::eventType.eq_any(vec!["append", "rebase"]))
+        .order_by(RepoSeqSchema::seq.desc())
+        .limit(max_commits)
+        .into_boxed();
+    if let Some(before_seq) = before_seq {
+        builder = builder.filter(RepoSeqSchema::seq.lt(before_seq));
+    }
+    let rows: Vec<models::RepoSeq> = db.run(move |conn| builder.load(conn)).await?;
+    let walked_all = (rows.len() as i64) < max_commits;
+
+    let mut versions = Vec::new();
+    // replayed commits are sequenced again as copies of the original event
+    let mut seen_commits = HashSet::new();
+    let mut last_seq = None;
+    for row in rows {
+        let Some(seq) = row.seq else { continue };
+        last_seq = Some(seq);
+        let evt: CommitEvt = cbor_to_struct(row.event)?;
+        if !seen_commits.insert(evt.commit) {
+            continue;
+        }
+        let Some(op) = evt.ops.into_iter().find(|op| op.path == path) else {
+            continue;
+        };
+        let value = match op.cid {
+            Some(cid) if !evt.too_big => read_car(evt.blocks)
+                .await?
+                .blocks
+                .get(cid)
+                .map(|block| cbor_to_lex_record(block.clone()))
+                .transpose()?,
+            _ => None,
+        };
+        versions.push(RecordVersion {
+            action: op.action,
+            cid: op.cid,
+            rev: evt.rev,
+            value,
+            sequenced_at: row.sequenced_at,
+        });
+        if versions.len() == limit {
+            return Ok((versions, Some(seq)));
+        }
+    }
+    match walked_all {
+        true => Ok((versions, None)),
+        false => Ok((versions, last_seq)),
+    }
+}
+
 pub struct RecordReader {
     pub did: String,
     pub db: Arc<DbConn>,
@@ -120,67 +306,6 @@ impl RecordReader {
             .await
     }
 
-    pub async fn list_records_for_collection(
-        &mut self,
-        collection: String,
-        limit: i64,
-        reverse: bool,
-        cursor: Option<String>,
-        rkey_start: Option<String>,
-        rkey_end: Option<String>,
-        include_soft_deleted: Option<bool>,
-    ) -> Result<Vec<RecordsForCollection>> {
-        use crate::schema::pds::record::dsl as RecordSchema;
-        use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
-
-        let include_soft_deleted: bool = if let Some(include_soft_deleted) = include_soft_deleted {
-            include_soft_deleted
-        } else {
-            false
-        };
-        let mut builder = RecordSchema::record
-            .inner_join(RepoBlockSchema::repo_block.on(RepoBlockSchema::cid.eq(RecordSchema::cid)))
-            .limit(limit)
-            .select((models::Record::as_select(), models::RepoBlock::as_select()))
-            .filter(RecordSchema::did.eq(self.did.clone()))
-            .filter(RecordSchema::collection.eq(collection))
-            .into_boxed();
-        if !include_soft_deleted {
-            builder = builder.filter(RecordSchema::takedownRef.is_null());
-        }
-        if reverse {
-            builder = builder.order(RecordSchema::rkey.asc());
-        } else {
-            builder = builder.order(RecordSchema::rkey.desc());
-        }
-
-        if let Some(cursor) = cursor {
-            if reverse {
-                builder = builder.filter(RecordSchema::rkey.gt(cursor));
-            } else {
-                builder = builder.filter(RecordSchema::rkey.lt(cursor));
-            }
-        } else {
-            if let Some(rkey_start) = rkey_start {
-                builder = builder.filter(RecordSchema::rkey.gt(rkey_start));
-            }
-            if let Some(rkey_end) = rkey_end {
-                builder = builder.filter(RecordSchema::rkey.lt(rkey_end));
-            }
-        }
-        let res: Vec<(models::Record, models::RepoBlock)> =
-            self.db.run(move |conn| builder.load(conn)).await?;
-        res.into_iter()
-            .map(|row| {
-                Ok(RecordsForCollection {
-                    uri: row.0.uri,
-                    cid: row.0.cid,
-                    value: cbor_to_lex_record(row.1.content)?,
-                })
-            })
-            .collect::<Result<Vec<RecordsForCollection>>>()
-    }
-
     pub async fn get_record(
         &mut self,
         uri: &AtUri,
@@ -431,6 +556,39 @@ impl RecordReader {
                 self.remove_backlinks_by_uri(&uri).await?;
             }
             self.add_backlinks(backlinks).await?;
+
+            // Maintain the link each BBS post unfurls into a card
+            if collection == POST_COLLECTION {
+                let link = first_link(&collection, &record);
+                let post_uri = uri.to_string();
+                self.db
+                    .run(move |conn| index_post_link(conn, post_uri, link))
+                    .await?;
+            }
+
+            // Maintain the index of watched threads the email notifier reads
+            if let Some((thread, muted)) = watched_thread(&collection, &record) {
+                let watch = BbsThreadWatch {
+                    uri: uri.to_string(),
+                    did: self.did.clone(),
+                    thread,
+                    muted,
+                };
+                self.db.run(move |conn| index_watch(conn, watch)).await?;
+            }
+
+            // Maintain the index of BBS blocks and mutes that reads filter with
+            if let Some(subject_did) = relation_subject(&collection, &record) {
+                let relation = BbsGraph {
+                    uri: uri.to_string(),
+                    collection,
+                    creator_did: self.did.clone(),
+                    subject_did,
+                };
+                self.db
+                    .run(move |conn| index_relation(conn, relation))
+                    .await?;
+            }
         }
         tracing::debug!("@LOG DEBUG RecordReader::index_record, indexed record {uri}");
         Ok(())
@@ -440,6 +598,10 @@ impl RecordReader {
     pub async fn delete_record(&self, uri: &AtUri) -> Result<()> {
         tracing::debug!("@LOG DEBUG RecordReader::delete_record, deleting indexed record {uri}");
         use crate::schema::pds::backlink::dsl as BacklinkSchema;
+        use crate::schema::pds::bbs_graph::dsl as GraphSchema;
+        use crate::schema::pds::bbs_post_link::dsl as PostLinkSchema;
+        use crate::schema::pds::bbs_thread_index::dsl as ThreadIndexSchema;
+        use crate::schema::pds::bbs_thread_watch::dsl as WatchSchema;
         use crate::schema::pds::record::dsl as RecordSchema;
         let uri = uri.to_string();
         self.db
@@ -450,6 +612,18 @@ impl RecordReader {
                 delete(BacklinkSchema::backlink)
                     .filter(BacklinkSchema::uri.eq(&uri))
                     .execute(conn)?;
+                delete(GraphSchema::bbs_graph)
+                    .filter(GraphSchema::uri.eq(&uri))
+                    .execute(conn)?;
+                delete(PostLinkSchema::bbs_post_link)
+                    .filter(PostLinkSchema::uri.eq(&uri))
+                    .execute(conn)?;
+                delete(WatchSchema::bbs_thread_watch)
+                    .filter(WatchSchema::uri.eq(&uri))
+                    .execute(conn)?;
+                delete(ThreadIndexSchema::bbs_thread_index)
+                    .filter(ThreadIndexSchema::uri.eq(&uri))
+                    .execute(conn)?;
                 tracing::debug!(
                     "@LOG DEBUG RecordReader::delete_record, deleted indexed record {uri}"
                 );
@@ -515,3 +689,24 @@ impl RecordReader {
             .await
     }
 }
+
+/// Returns the NSID prefix for a `app.bbs.*` style collection filter, or
+/// `None` if `collection` names a single collection.
+pub fn collection_prefix(collection: &str) -> Result<Option<String>> {
+    match collection.strip_suffix('*') {
+        None => Ok(None),
+        Some(prefix) => {
+            let valid = prefix.ends_with('.')
+                && prefix.len() > 1
+                && prefix
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+            if !valid {
+                bail!("Invalid collection prefix: {collection}");
+            }
+            Ok(Some(prefix.to_string()))
+        }
+    }
+}
+
+pub mod tombstone;
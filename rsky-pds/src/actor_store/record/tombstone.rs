@@ -0,0 +1,202 @@
+use crate::actor_store::record::RecordReader;
+use crate::db::establish_connection_for_jobs;
+use crate::models::{models, RecordTombstone};
+use anyhow::{bail, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::dsl::sql;
+use diesel::pg::PgConnection;
+use diesel::sql_types::{Bool, Text};
+use diesel::upsert::excluded;
+use diesel::*;
+use rsky_common::RFC3339_VARIANT;
+use rsky_repo::types::PreparedWrite;
+use std::time::Duration;
+
+/// Copies each record a batch of writes deletes into record_tombstone,
+/// along with its block, which the commit is about to drop from
+/// repo_block. A create at a tombstoned uri undoes the delete, so its
+/// tombstone goes. Takes the connection the commit is applied on so both
+/// land in the same transaction.
+pub fn update_tombstones(
+    conn: &mut PgConnection,
+    did: &str,
+    writes: &[PreparedWrite],
+    rev: &str,
+) -> QueryResult<()> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+    use crate::schema::pds::record_tombstone::dsl as TombstoneSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let mut deleted = Vec::new();
+    let mut created = Vec::new();
+    for write in writes {
+        match write {
+            PreparedWrite::Delete(write) => deleted.push(write.uri.clone()),
+            PreparedWrite::Create(write) => created.push(write.uri.clone()),
+            PreparedWrite::Update(_) => (),
+        }
+    }
+    if deleted.is_empty() && created.is_empty() {
+        return Ok(());
+    }
+    let deleted_at = rsky_common::now();
+    delete(TombstoneSchema::record_tombstone)
+        .filter(TombstoneSchema::uri.eq_any(&created))
+        .execute(conn)?;
+    let tombstones = RecordSchema::record
+        .inner_join(
+            RepoBlockSchema::repo_block.on(RepoBlockSchema::cid
+                .eq(RecordSchema::cid)
+                .and(RepoBlockSchema::did.eq(RecordSchema::did))),
+        )
+        .filter(RecordSchema::uri.eq_any(&deleted))
+        .select((models::Record::as_select(), models::RepoBlock::as_select()))
+        .load::<(models::Record, models::RepoBlock)>(conn)?
+        .into_iter()
+        .map(|(record, block)| RecordTombstone {
+            uri: record.uri,
+            did: did.to_string(),
+            cid: record.cid,
+            content: block.content,
+            repo_rev: rev.to_string(),
+            deleted_at: deleted_at.clone(),
+        })
+        .collect::<Vec<_>>();
+    if tombstones.is_empty() {
+        return Ok(());
+    }
+    insert_into(TombstoneSchema::record_tombstone)
+        .values(&tombstones)
+        .on_conflict(TombstoneSchema::uri)
+        .do_update()
+        .set((
+            TombstoneSchema::cid.eq(excluded(TombstoneSchema::cid)),
+            TombstoneSchema::content.eq(excluded(TombstoneSchema::content)),
+            TombstoneSchema::repoRev.eq(excluded(TombstoneSchema::repoRev)),
+            TombstoneSchema::deletedAt.eq(excluded(TombstoneSchema::deletedAt)),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+impl RecordReader {
+    /// This account's tombstones, most recently deleted first. The cursor is
+    /// `{deletedAt}::{uri}` of the last tombstone on the previous page, since
+    /// a batch of deletes shares one `deletedAt`.
+    pub async fn list_tombstones(
+        &self,
+        limit: i64,
+        cursor: Option<String>,
+    ) -> Result<Vec<RecordTombstone>> {
+        use crate::schema::pds::record_tombstone::dsl as TombstoneSchema;
+
+        let mut builder = TombstoneSchema::record_tombstone
+            .select(RecordTombstone::as_select())
+            .filter(TombstoneSchema::did.eq(self.did.clone()))
+            .order((
+                TombstoneSchema::deletedAt.desc(),
+                TombstoneSchema::uri.desc(),
+            ))
+            .limit(limit)
+            .into_boxed();
+        if let Some(cursor) = cursor {
+            let Some((deleted_at, uri)) = cursor.split_once("::") else {
+                bail!("Invalid cursor: {cursor}");
+            };
+            builder = builder.filter(
+                sql::<Bool>("((")
+                    .bind(TombstoneSchema::deletedAt)
+                    .sql(", ")
+                    .bind(TombstoneSchema::uri)
+                    .sql(") < (")
+                    .bind::<Text, _>(deleted_at.to_string())
+                    .sql(", ")
+                    .bind::<Text, _>(uri.to_string())
+                    .sql("))"),
+            );
+        }
+        Ok(self.db.run(move |conn| builder.load(conn)).await?)
+    }
+}
+
+/// Physically removes tombstoned records once they've been kept for the
+/// retention window. Until then their owner can read them back to undo the
+/// delete, and moderators can see what was there.
+#[derive(Debug, Clone)]
+pub struct TombstonePurger {
+    pub retention_ms: u64,
+    pub interval_ms: u64,
+    pub batch_size: i64,
+}
+
+impl TombstonePurger {
+    pub fn new(retention_ms: u64, interval_ms: u64, batch_size: i64) -> Self {
+        TombstonePurger {
+            retention_ms,
+            interval_ms: interval_ms.max(1000),
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            let purger = self.clone();
+            let res = tokio::task::spawn_blocking(move || {
+                let conn = &mut establish_connection_for_jobs()?;
+                purger.run(conn)
+            })
+            .await;
+            match res {
+                Ok(Ok(0)) => (),
+                Ok(Ok(purged)) => tracing::info!("Purged {purged} record tombstones"),
+                Ok(Err(error)) => {
+                    tracing::error!("@LOG: ERROR: failed to purge record tombstones: {error}")
+                }
+                Err(error) => {
+                    tracing::error!("@LOG: ERROR: tombstone purge task panicked: {error}")
+                }
+            }
+        }
+    }
+
+    /// Deletes tombstones past the retention window, a batch at a time so a
+    /// large backlog doesn't hold one long transaction. Returns how many.
+    pub fn run(&self, conn: &mut PgConnection) -> Result<usize> {
+        use crate::schema::pds::record_tombstone::dsl as TombstoneSchema;
+
+        let cutoff = purge_cutoff(self.retention_ms);
+        let mut purged = 0;
+        loop {
+            let uris: Vec<String> = TombstoneSchema::record_tombstone
+                .select(TombstoneSchema::uri)
+                .filter(TombstoneSchema::deletedAt.lt(&cutoff))
+                .limit(self.batch_size)
+                .load(conn)?;
+            if uris.is_empty() {
+                return Ok(purged);
+            }
+            purged += delete(TombstoneSchema::record_tombstone)
+                .filter(TombstoneSchema::uri.eq_any(&uris))
+                .execute(conn)?;
+        }
+    }
+}
+
+/// When a tombstone deleted at `deleted_at` is purged
+pub fn purge_at(deleted_at: &str, retention_ms: u64) -> Result<String> {
+    let deleted_at = chrono::DateTime::parse_from_rfc3339(deleted_at)?;
+    Ok(format!(
+        "{}",
+        (deleted_at.with_timezone(&Utc) + ChronoDuration::milliseconds(retention_ms as i64))
+            .format(RFC3339_VARIANT)
+    ))
+}
+
+fn purge_cutoff(retention_ms: u64) -> String {
+    format!(
+        "{}",
+        (Utc::now() - ChronoDuration::milliseconds(retention_ms as i64)).format(RFC3339_VARIANT)
+    )
+}
@@ -1,7 +1,9 @@
+use crate::actor_store::record::tombstone::update_tombstones;
+use crate::actor_store::repo::types::RepoCompaction;
 use crate::db::DbConn;
 use crate::models;
 use crate::models::RepoBlock;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use diesel::dsl::sql;
 use diesel::prelude::*;
 use diesel::sql_types::{Bool, Text};
@@ -9,20 +11,29 @@ use diesel::*;
 use futures::{stream, StreamExt, TryStreamExt};
 use lexicon_cid::Cid;
 use rsky_common;
+use rsky_common::cbor_to_struct;
+use rsky_common::tid::{Ticker, TID};
 use rsky_repo::block_map::{BlockMap, BlocksAndMissing};
-use rsky_repo::car::blocks_to_car_file;
+use rsky_repo::car::write_car_to;
 use rsky_repo::cid_set::CidSet;
+use rsky_repo::mst::NodeData;
 use rsky_repo::storage::readable_blockstore::ReadableBlockstore;
 use rsky_repo::storage::types::RepoStorage;
 use rsky_repo::storage::CidAndRev;
 use rsky_repo::storage::RepoRootError::RepoRootNotFoundError;
-use rsky_repo::types::CommitData;
+use rsky_repo::types::{Commit, CommitData, PreparedWrite};
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
+use tokio::io::DuplexStream;
 use tokio::sync::RwLock;
 
+/// How much of a repo export is buffered ahead of the client reading it
+const CAR_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
 #[derive(Clone, Debug)]
 pub struct SqlRepoReader {
     pub cache: Arc<RwLock<BlockMap>>,
@@ -317,6 +328,23 @@ impl RepoStorage for SqlRepoReader {
     }
 }
 
+/// The subtree of `node` that `key` lives under, or None when the key is an
+/// entry of this node or falls in an empty gap.
+fn subtree_for_key(node: &NodeData, key: &str) -> Option<Cid> {
+    let mut subtree = node.l;
+    let mut entry_key: Vec<u8> = Vec::new();
+    for entry in &node.e {
+        entry_key.truncate(entry.p as usize);
+        entry_key.extend_from_slice(&entry.k);
+        match key.as_bytes().cmp(entry_key.as_slice()) {
+            Ordering::Less => break,
+            Ordering::Equal => return None,
+            Ordering::Greater => subtree = entry.t,
+        }
+    }
+    subtree
+}
+
 // Basically handles getting ipld blocks from db
 impl SqlRepoReader {
     pub fn new(did: String, now: Option<String>, db: Arc<DbConn>) -> Self {
@@ -331,33 +359,99 @@ impl SqlRepoReader {
         }
     }
 
-    pub async fn get_car_stream(&self, since: Option<String>) -> Result<Vec<u8>> {
-        match self.get_root().await {
-            None => Err(anyhow::Error::new(RepoRootNotFoundError)),
-            Some(root) => {
-                let mut car = BlockMap::new();
-                let mut cursor: Option<CidAndRev> = None;
-                let mut write_rows = |rows: Vec<RepoBlock>| -> Result<()> {
-                    for row in rows {
-                        car.set(Cid::from_str(&row.cid)?, row.content);
-                    }
-                    Ok(())
+    /// Loads the MST nodes on the paths to `keys` into the block cache, one
+    /// multi-get per tree level, so applying a large batch of writes doesn't
+    /// go to the database once per node as the tree is walked.
+    pub async fn prefetch_mst_paths(&self, data: Cid, keys: Vec<String>) -> Result<()> {
+        let mut level: HashMap<Cid, Vec<String>> = HashMap::from([(data, keys)]);
+        while !level.is_empty() {
+            let found = self.get_blocks(level.keys().cloned().collect()).await?;
+            let mut next: HashMap<Cid, Vec<String>> = HashMap::new();
+            for (cid, keys) in level {
+                let Some(bytes) = found.blocks.get(cid) else {
+                    continue;
                 };
-                loop {
-                    let res = self.get_block_range(&since, &cursor).await?;
-                    write_rows(res.clone())?;
-                    if let Some(last_row) = res.last() {
-                        cursor = Some(CidAndRev {
-                            cid: Cid::from_str(&last_row.cid)?,
-                            rev: last_row.repo_rev.clone(),
-                        });
-                    } else {
-                        break;
+                let node: NodeData = cbor_to_struct(bytes.clone())?;
+                for key in keys {
+                    if let Some(subtree) = subtree_for_key(&node, &key) {
+                        next.entry(subtree).or_default().push(key);
                     }
                 }
-                blocks_to_car_file(Some(&root), car).await
             }
+            level = next;
+        }
+        Ok(())
+    }
+
+    /// Exports the repo as a CAR file rooted at the current commit. With `since`
+    /// only blocks written after that rev are included, but the root commit is
+    /// always part of the export so the receiver can check its signature.
+    ///
+    /// The root is checked up front, then blocks are paged out of the database
+    /// and written to the returned pipe as it's read, so a large repo is never
+    /// held in memory. An error past that point ends the CAR early.
+    pub async fn get_car_stream(&self, since: Option<String>) -> Result<DuplexStream> {
+        let Some(root) = self.get_root().await else {
+            return Err(anyhow::Error::new(RepoRootNotFoundError));
+        };
+        let Some(commit_bytes) = self.get_bytes(&root).await? else {
+            return Err(anyhow::Error::new(RepoRootNotFoundError));
+        };
+        self.assert_signed_commit(&commit_bytes)?;
+
+        let reader = self.clone();
+        // Pages through the blocks until one comes back empty
+        let pages = stream::try_unfold(None, move |cursor: Option<CidAndRev>| {
+            let reader = reader.clone();
+            let since = since.clone();
+            async move {
+                let rows = reader.get_block_range(&since, &cursor).await?;
+                let next = match rows.last() {
+                    Some(last_row) => CidAndRev {
+                        cid: Cid::from_str(&last_row.cid)?,
+                        rev: last_row.repo_rev.clone(),
+                    },
+                    None => return Ok(None),
+                };
+                Ok::<_, anyhow::Error>(Some((rows, Some(next))))
+            }
+        });
+        let blocks = stream::once(async move { Ok((root, commit_bytes)) }).chain(
+            pages
+                .map_ok(|rows| stream::iter(rows.into_iter().map(Ok)))
+                .try_flatten()
+                .and_then(|row: RepoBlock| async move {
+                    Ok::<_, anyhow::Error>((Cid::from_str(&row.cid)?, row.content))
+                })
+                // Already written first
+                .try_filter(move |(cid, _)| futures::future::ready(*cid != root)),
+        );
+
+        let (writer, car) = tokio::io::duplex(CAR_STREAM_BUFFER_SIZE);
+        let did = self.did.clone();
+        tokio::spawn(async move {
+            if let Err(error) = write_car_to(writer, Some(&root), blocks).await {
+                tracing::error!("@LOG: ERROR: failed to stream repo export for {did}: {error}");
+            }
+        });
+        Ok(car)
+    }
+
+    /// web5 commits are signed by the account's own key rather than the PDS, so
+    /// make sure the root we're about to hand out actually carries a signature.
+    fn assert_signed_commit(&self, bytes: &Vec<u8>) -> Result<()> {
+        let commit: Commit = cbor_to_struct(bytes.clone())?;
+        if commit.did != self.did {
+            bail!(
+                "Root commit belongs to {} rather than {}",
+                commit.did,
+                self.did
+            );
+        }
+        if commit.sig.is_empty() {
+            bail!("Root commit for {} is unsigned", self.did);
         }
+        Ok(())
     }
 
     pub async fn get_block_range(
@@ -402,6 +496,66 @@ impl SqlRepoReader {
             .await?)
     }
 
+    /// Deletes every block written at or before `root`'s rev that isn't in
+    /// `reachable`. The repo root row is locked for the duration so a commit
+    /// landing mid-compaction can't lose blocks; if the root has moved on since
+    /// `reachable` was computed nothing is deleted.
+    pub async fn delete_unreachable_blocks(
+        &self,
+        root: CidAndRev,
+        reachable: CidSet,
+    ) -> Result<Option<RepoCompaction>> {
+        let did: String = self.did.clone();
+        let db: Arc<DbConn> = self.db.clone();
+        use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+        use crate::schema::pds::repo_root::dsl as RepoRootSchema;
+
+        let compaction = db
+            .run(move |conn| {
+                conn.transaction(|conn| {
+                    let current: Option<String> = RepoRootSchema::repo_root
+                        .filter(RepoRootSchema::did.eq(&did))
+                        .select(RepoRootSchema::cid)
+                        .for_update()
+                        .first(conn)
+                        .optional()?;
+                    if current != Some(root.cid.to_string()) {
+                        return Ok::<_, anyhow::Error>(None);
+                    }
+                    let rows: Vec<(String, i32)> = RepoBlockSchema::repo_block
+                        .filter(RepoBlockSchema::did.eq(&did))
+                        .filter(RepoBlockSchema::repoRev.le(&root.rev))
+                        .select((RepoBlockSchema::cid, RepoBlockSchema::size))
+                        .load(conn)?;
+                    let mut compaction = RepoCompaction::default();
+                    let mut stale: Vec<String> = Vec::new();
+                    for (cid, size) in rows {
+                        if reachable.has(Cid::from_str(&cid)?) {
+                            compaction.blocks_kept += 1;
+                        } else {
+                            compaction.blocks_removed += 1;
+                            compaction.reclaimed_bytes += size.max(0) as u64;
+                            stale.push(cid);
+                        }
+                    }
+                    for chunk in stale.chunks(500) {
+                        delete(RepoBlockSchema::repo_block)
+                            .filter(RepoBlockSchema::did.eq(&did))
+                            .filter(RepoBlockSchema::cid.eq_any(chunk))
+                            .execute(conn)?;
+                    }
+                    Ok(Some(compaction))
+                })
+            })
+            .await?;
+        if compaction.is_some() {
+            // the cache may still be holding blocks we just removed
+            let mut cache_guard = self.cache.write().await;
+            *cache_guard = BlockMap::new();
+        }
+        Ok(compaction)
+    }
+
     pub async fn count_blocks(&self) -> Result<i64> {
         let did: String = self.did.clone();
         let db: Arc<DbConn> = self.db.clone();
@@ -444,6 +598,68 @@ impl SqlRepoReader {
         Ok(())
     }
 
+    /// Applies a commit the way [`RepoStorage::apply_commit`] does, but in one
+    /// transaction that first keeps the records `writes` deletes as
+    /// tombstones, so a crash can't leave tombstones out of step with the repo.
+    pub async fn apply_commit_with_tombstones(
+        &self,
+        commit: CommitData,
+        writes: Vec<PreparedWrite>,
+    ) -> Result<()> {
+        let did: String = self.did.clone();
+        let now: String = self.now.clone();
+        let db: Arc<DbConn> = self.db.clone();
+        use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+        use crate::schema::pds::repo_root::dsl as RepoRootSchema;
+
+        let blocks: Vec<RepoBlock> = commit
+            .new_blocks
+            .map
+            .iter()
+            .map(|(cid, bytes)| RepoBlock {
+                cid: cid.to_string(),
+                did: did.clone(),
+                repo_rev: commit.rev.clone(),
+                size: bytes.0.len() as i32,
+                content: bytes.0.clone(),
+            })
+            .collect();
+        let removed: Vec<String> = commit
+            .removed_cids
+            .to_list()
+            .into_iter()
+            .map(|c| c.to_string())
+            .collect();
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                update_tombstones(conn, &did, &writes, &commit.rev)?;
+                update(RepoRootSchema::repo_root)
+                    .filter(RepoRootSchema::did.eq(&did))
+                    .set((
+                        RepoRootSchema::cid.eq(commit.cid.to_string()),
+                        RepoRootSchema::rev.eq(&commit.rev),
+                        RepoRootSchema::indexedAt.eq(now),
+                    ))
+                    .execute(conn)?;
+                for batch in blocks.chunks(50) {
+                    insert_into(RepoBlockSchema::repo_block)
+                        .values(batch)
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                }
+                if !removed.is_empty() {
+                    delete(RepoBlockSchema::repo_block)
+                        .filter(RepoBlockSchema::did.eq(&did))
+                        .filter(RepoBlockSchema::cid.eq_any(removed))
+                        .execute(conn)?;
+                }
+                Ok::<_, diesel::result::Error>(())
+            })
+        })
+        .await?;
+        Ok(())
+    }
+
     pub async fn delete_many(&self, cids: Vec<Cid>) -> Result<()> {
         if cids.is_empty() {
             return Ok(());
@@ -463,6 +679,66 @@ impl SqlRepoReader {
         Ok(())
     }
 
+    /// Issues the rev for an unsigned commit a client is about to sign on top
+    /// of `base`. It's after the head's rev and after every rev issued before
+    /// it, so concurrent pre* calls and a clock that has gone backwards still
+    /// hand out revs in order. The row lock serializes issuers across PDS
+    /// instances. `base` is kept with the rev, see [`SqlRepoReader::issued_base`].
+    pub async fn issue_rev(&self, base: Cid) -> Result<String> {
+        let did: String = self.did.clone();
+        let base = base.to_string();
+        let db: Arc<DbConn> = self.db.clone();
+        use crate::schema::pds::repo_root::dsl as RepoRootSchema;
+
+        let rev = db
+            .run(move |conn| {
+                conn.transaction(|conn| {
+                    let (rev, issued): (String, Option<String>) = RepoRootSchema::repo_root
+                        .filter(RepoRootSchema::did.eq(&did))
+                        .select((RepoRootSchema::rev, RepoRootSchema::issuedRev))
+                        .for_update()
+                        .first(conn)?;
+                    let latest = issued.filter(|issued| issued > &rev).unwrap_or(rev);
+                    let next = Ticker::new().next(Some(TID(latest))).0;
+                    update(RepoRootSchema::repo_root)
+                        .filter(RepoRootSchema::did.eq(&did))
+                        .set((
+                            RepoRootSchema::issuedRev.eq(&next),
+                            RepoRootSchema::issuedBase.eq(&base),
+                        ))
+                        .execute(conn)?;
+                    Ok::<_, diesel::result::Error>(next)
+                })
+            })
+            .await?;
+        Ok(rev)
+    }
+
+    /// The head a commit signed with `rev` was built on, if `rev` is the last
+    /// one issued. A commit issued earlier may have been built on the same
+    /// head as a later one, so it has no base once another rev is issued.
+    pub async fn issued_base(&self, rev: &str) -> Result<Option<Cid>> {
+        let did: String = self.did.clone();
+        let db: Arc<DbConn> = self.db.clone();
+        use crate::schema::pds::repo_root::dsl as RepoRootSchema;
+
+        let issued = db
+            .run(move |conn| {
+                RepoRootSchema::repo_root
+                    .filter(RepoRootSchema::did.eq(did))
+                    .select((RepoRootSchema::issuedRev, RepoRootSchema::issuedBase))
+                    .first::<(Option<String>, Option<String>)>(conn)
+                    .optional()
+            })
+            .await?;
+        match issued {
+            Some((Some(issued_rev), Some(base))) if issued_rev == rev => {
+                Ok(Some(Cid::from_str(&base)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
     pub async fn get_root_detailed(&self) -> Result<CidAndRev> {
         let did: String = self.did.clone();
         let db: Arc<DbConn> = self.db.clone();
@@ -483,3 +759,41 @@ impl SqlRepoReader {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_repo::mst::TreeEntry;
+
+    fn cid(n: u8) -> Cid {
+        let hash = lexicon_cid::multihash::Multihash::<64>::wrap(0x12, &[n; 32]).unwrap();
+        Cid::new_v1(0x71, hash)
+    }
+
+    #[test]
+    fn finds_subtree_for_key() {
+        // entries "app.bsky.feed.post/b" and "app.bsky.feed.post/d", the
+        // second sharing a 19 byte prefix with the first
+        let node = NodeData {
+            l: Some(cid(1)),
+            e: vec![
+                TreeEntry {
+                    p: 0,
+                    k: b"app.bsky.feed.post/b".to_vec(),
+                    v: cid(10),
+                    t: Some(cid(2)),
+                },
+                TreeEntry {
+                    p: 19,
+                    k: b"d".to_vec(),
+                    v: cid(11),
+                    t: None,
+                },
+            ],
+        };
+        assert_eq!(subtree_for_key(&node, "app.bsky.feed.post/a"), Some(cid(1)));
+        assert_eq!(subtree_for_key(&node, "app.bsky.feed.post/c"), Some(cid(2)));
+        assert_eq!(subtree_for_key(&node, "app.bsky.feed.post/b"), None);
+        assert_eq!(subtree_for_key(&node, "app.bsky.feed.post/e"), None);
+    }
+}
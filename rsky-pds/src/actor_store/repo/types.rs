@@ -5,3 +5,11 @@ pub struct SyncEvtData {
     pub rev: String,
     pub blocks: BlockMap,
 }
+
+/// Outcome of compacting an actor's blockstore
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepoCompaction {
+    pub blocks_kept: u64,
+    pub blocks_removed: u64,
+    pub reclaimed_bytes: u64,
+}
@@ -0,0 +1,379 @@
+use crate::config::{ClusterConfig, RepoLockConfig};
+use crate::db::establish_connection_for_jobs;
+use anyhow::{Context, Result};
+use diesel::sql_types::{Integer, Text};
+use diesel::{sql_query, RunQueryDsl};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// First key of the advisory locks taken on repos, so they can't collide with
+/// other advisory locks on the same database
+const REPO_LOCK_CLASS: i32 = 0x7265706f;
+
+lazy_static! {
+    /// Shared by every ActorStore in the process, since a new store is built per request
+    pub static ref REPO_WRITE_LOCKS: RepoWriteLocks = RepoWriteLocks::new();
+}
+
+/// Hands out one async mutex per DID so commits to the same repo are applied
+/// one at a time, while writes to different repos still run concurrently.
+/// With several replicas, the repo is also locked in Postgres or Redis once
+/// this process holds it, see [`RepoWriteLocks::use_cluster_locks`].
+#[derive(Debug, Default)]
+pub struct RepoWriteLocks {
+    locks: Mutex<HashMap<String, Weak<AsyncMutex<()>>>>,
+    cluster: OnceLock<(SharedClusterLocks, Duration)>,
+    /// Whether the cluster lease on each locked repo is still this replica's
+    leases: Mutex<HashMap<String, Weak<AtomicBool>>>,
+}
+
+/// Held while a repo is written to, see [`RepoWriteLocks::acquire`]
+pub struct RepoWriteGuard {
+    // dropped first, so the replica lock is given back before the next local
+    // waiter tries to take it
+    _cluster: Option<ClusterLease>,
+    _local: OwnedMutexGuard<()>,
+}
+
+impl RepoWriteLocks {
+    pub fn new() -> Self {
+        RepoWriteLocks {
+            locks: Mutex::new(HashMap::new()),
+            cluster: OnceLock::new(),
+            leases: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Makes every later `acquire` lock the repo for the other replicas too.
+    /// Only the first call has any effect.
+    pub async fn use_cluster_locks(&self, cfg: &ClusterConfig) -> Result<()> {
+        let locks: SharedClusterLocks = match cfg.repo_locks {
+            RepoLockConfig::Local => return Ok(()),
+            RepoLockConfig::Postgres => Arc::new(PostgresLocks),
+            #[cfg(feature = "cluster-redis")]
+            RepoLockConfig::Redis { ref url } => Arc::new(RedisLocks::new(url).await?),
+            #[cfg(not(feature = "cluster-redis"))]
+            RepoLockConfig::Redis { .. } => anyhow::bail!(
+                "PDS_CLUSTER_REPO_LOCKS is redis but rsky-pds was built without the cluster-redis feature"
+            ),
+        };
+        let timeout = Duration::from_millis(cfg.repo_lock_timeout_ms);
+        let _ = self.cluster.set((locks, timeout));
+        Ok(())
+    }
+
+    /// Waits for any in-flight write to `did` to finish. The repo stays locked
+    /// until the returned guard is dropped.
+    pub async fn acquire(&self, did: &str) -> Result<RepoWriteGuard> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            // entries only live as long as someone holds or waits on the lock
+            locks.retain(|_, lock| lock.strong_count() > 0);
+            match locks.get(did).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    let lock = Arc::new(AsyncMutex::new(()));
+                    locks.insert(did.to_string(), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+        // taken locally first, so a replica only ever waits on the others with
+        // one request per repo
+        let local = lock.lock_owned().await;
+        let cluster = match self.cluster.get() {
+            Some((locks, timeout)) => Some(locks.acquire(did, *timeout).await?),
+            None => None,
+        };
+        if let Some(ref lease) = cluster {
+            let mut leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+            leases.retain(|_, held| held.strong_count() > 0);
+            leases.insert(did.to_string(), Arc::downgrade(&lease.held));
+        }
+        Ok(RepoWriteGuard {
+            _cluster: cluster,
+            _local: local,
+        })
+    }
+
+    /// Fails if the other replicas may no longer be locked out of `did`,
+    /// because a lease that expires on its own couldn't be renewed. Checked by
+    /// the lock's holder right before it commits, so a write that outlived its
+    /// lease is dropped instead of racing whoever took the repo next.
+    pub fn ensure_held(&self, did: &str) -> Result<()> {
+        let leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+        match leases.get(did).and_then(Weak::upgrade) {
+            Some(held) if !held.load(Ordering::SeqCst) => {
+                anyhow::bail!("Lost the lock on {did} to another replica before committing")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Repo locks shared by every replica
+#[rocket::async_trait]
+pub trait ClusterLocks: Debug + Send + Sync {
+    /// Locks `did` for every replica, failing if it can't within `timeout`
+    async fn acquire(&self, did: &str, timeout: Duration) -> Result<ClusterLease>;
+}
+
+pub type SharedClusterLocks = Arc<dyn ClusterLocks>;
+
+/// A repo locked for every replica, released when dropped
+pub struct ClusterLease {
+    release: Option<Box<dyn FnOnce() + Send>>,
+    held: Arc<AtomicBool>,
+}
+
+impl ClusterLease {
+    /// A lease that lasts until it's released
+    pub fn new(release: impl FnOnce() + Send + 'static) -> Self {
+        Self::expiring(release, Arc::new(AtomicBool::new(true)))
+    }
+
+    /// A lease that can run out before it's released, which whoever renews
+    /// it records by clearing `held`
+    pub fn expiring(release: impl FnOnce() + Send + 'static, held: Arc<AtomicBool>) -> Self {
+        ClusterLease {
+            release: Some(Box::new(release)),
+            held,
+        }
+    }
+}
+
+impl Drop for ClusterLease {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+/// Session advisory locks, each on a connection of its own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostgresLocks;
+
+#[rocket::async_trait]
+impl ClusterLocks for PostgresLocks {
+    async fn acquire(&self, did: &str, timeout: Duration) -> Result<ClusterLease> {
+        let did = did.to_string();
+        let conn = tokio::task::spawn_blocking(move || {
+            let mut conn = establish_connection_for_jobs()?;
+            sql_query(format!("SET lock_timeout = {}", timeout.as_millis())).execute(&mut conn)?;
+            sql_query("SELECT pg_advisory_lock($1, hashtext($2))")
+                .bind::<Integer, _>(REPO_LOCK_CLASS)
+                .bind::<Text, _>(&did)
+                .execute(&mut conn)
+                .with_context(|| format!("Failed to lock {did} against the other replicas"))?;
+            Ok::<_, anyhow::Error>(conn)
+        })
+        .await??;
+        // closing the session releases its advisory locks
+        Ok(ClusterLease::new(move || drop(conn)))
+    }
+}
+
+/// Keys set with NX, which expire after the lock timeout in case the replica
+/// holding them dies mid-write. The holder keeps extending the key while the
+/// write runs, and if it can't the lease is marked lost so the write isn't
+/// committed, see [`RepoWriteLocks::ensure_held`].
+#[cfg(feature = "cluster-redis")]
+pub struct RedisLocks {
+    conn: redis::aio::ConnectionManager,
+}
+
+#[cfg(feature = "cluster-redis")]
+impl Debug for RedisLocks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisLocks").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "cluster-redis")]
+impl RedisLocks {
+    /// How often a held lock is retried
+    const RETRY_INTERVAL: Duration = Duration::from_millis(25);
+    /// Deletes the key only if this replica still holds it, not one that
+    /// expired and was taken by another
+    const RELEASE_SCRIPT: &'static str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+    /// Extends the key only if this replica still holds it
+    const RENEW_SCRIPT: &'static str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+    pub async fn new(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(RedisLocks {
+            conn: redis::aio::ConnectionManager::new(client).await?,
+        })
+    }
+}
+
+#[cfg(feature = "cluster-redis")]
+#[rocket::async_trait]
+impl ClusterLocks for RedisLocks {
+    async fn acquire(&self, did: &str, timeout: Duration) -> Result<ClusterLease> {
+        let mut conn = self.conn.clone();
+        let key = format!("rsky-pds:repo_lock:{did}");
+        let token = hex::encode(rand::random::<[u8; 16]>());
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let set: Option<String> = redis::cmd("SET")
+                .arg(&key)
+                .arg(&token)
+                .arg("NX")
+                .arg("PX")
+                .arg(timeout.as_millis() as u64)
+                .query_async(&mut conn)
+                .await?;
+            if set.is_some() {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                anyhow::bail!("Timed out waiting for another replica to write {did}");
+            }
+            tokio::time::sleep(Self::RETRY_INTERVAL).await;
+        }
+        let held = Arc::new(AtomicBool::new(true));
+        let renewal = {
+            let (mut conn, key, token, held) =
+                (conn.clone(), key.clone(), token.clone(), held.clone());
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval((timeout / 3).max(Self::RETRY_INTERVAL));
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    let renewed: Result<i64, _> = redis::Script::new(Self::RENEW_SCRIPT)
+                        .key(&key)
+                        .arg(&token)
+                        .arg(timeout.as_millis() as u64)
+                        .invoke_async(&mut conn)
+                        .await;
+                    if !matches!(renewed, Ok(1)) {
+                        tracing::error!("@LOG: ERROR: failed to renew repo lock {key}");
+                        held.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                }
+            })
+        };
+        Ok(ClusterLease::expiring(
+            move || {
+                renewal.abort();
+                tokio::spawn(async move {
+                    let released: Result<i64, _> = redis::Script::new(Self::RELEASE_SCRIPT)
+                        .key(&key)
+                        .arg(&token)
+                        .invoke_async(&mut conn)
+                        .await;
+                    if let Err(error) = released {
+                        tracing::warn!(
+                            "Failed to release repo lock {key}, it expires on its own: {error}"
+                        );
+                    }
+                });
+            },
+            held,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the leases held instead of locking anything
+    #[derive(Debug)]
+    struct CountingLocks(Arc<AtomicUsize>);
+
+    #[rocket::async_trait]
+    impl ClusterLocks for CountingLocks {
+        async fn acquire(&self, _did: &str, _timeout: Duration) -> Result<ClusterLease> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let held = self.0.clone();
+            Ok(ClusterLease::new(move || {
+                held.fetch_sub(1, Ordering::SeqCst);
+            }))
+        }
+    }
+
+    #[tokio::test]
+    async fn serializes_writes_to_the_same_repo() {
+        let locks = RepoWriteLocks::new();
+        let guard = locks.acquire("did:web5:alice").await.unwrap();
+
+        let blocked =
+            tokio::time::timeout(Duration::from_millis(50), locks.acquire("did:web5:alice")).await;
+        assert!(blocked.is_err());
+        let other =
+            tokio::time::timeout(Duration::from_millis(50), locks.acquire("did:web5:bob")).await;
+        assert!(other.is_ok());
+
+        drop(guard);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), locks.acquire("did:web5:alice"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn holds_the_cluster_lock_as_long_as_the_local_one() {
+        let held = Arc::new(AtomicUsize::new(0));
+        let locks = RepoWriteLocks::new();
+        let _ = locks.cluster.set((
+            Arc::new(CountingLocks(held.clone())),
+            Duration::from_secs(1),
+        ));
+
+        let guard = locks.acquire("did:web5:alice").await.unwrap();
+        assert_eq!(held.load(Ordering::SeqCst), 1);
+        drop(guard);
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+    }
+
+    /// Hands out leases that have already run out
+    #[derive(Debug)]
+    struct ExpiredLocks;
+
+    #[rocket::async_trait]
+    impl ClusterLocks for ExpiredLocks {
+        async fn acquire(&self, _did: &str, _timeout: Duration) -> Result<ClusterLease> {
+            Ok(ClusterLease::expiring(
+                || {},
+                Arc::new(AtomicBool::new(false)),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_commit_once_the_lease_is_lost() {
+        let locks = RepoWriteLocks::new();
+        let guard = locks.acquire("did:web5:alice").await.unwrap();
+        assert!(locks.ensure_held("did:web5:alice").is_ok());
+        drop(guard);
+
+        let locks = RepoWriteLocks::new();
+        let _ = locks
+            .cluster
+            .set((Arc::new(ExpiredLocks), Duration::from_secs(1)));
+        let _guard = locks.acquire("did:web5:alice").await.unwrap();
+        assert!(locks.ensure_held("did:web5:alice").is_err());
+        assert!(locks.ensure_held("did:web5:bob").is_ok());
+    }
+}
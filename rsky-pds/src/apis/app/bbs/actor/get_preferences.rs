@@ -0,0 +1,44 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::ActorStore;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::db::DbConn;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::app::bbs::actor::{GetPreferencesOutput, PREFERENCES_NAMESPACE};
+
+async fn inner_get_preferences(
+    blob_store: &State<SharedBlobStore>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<GetPreferencesOutput> {
+    let auth = auth.access.credentials.unwrap();
+    let requester = auth.did.unwrap();
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester), db);
+    let preferences = actor_store
+        .pref
+        .get_preference_values(Some(PREFERENCES_NAMESPACE.to_string()), auth.scope.unwrap())
+        .await?;
+
+    Ok(GetPreferencesOutput { preferences })
+}
+
+/// The BBS client's preferences for the current account, e.g. theme, feed
+/// ordering or muted sections, so they follow the account across devices.
+/// Requires auth.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.actor.getPreferences")]
+pub async fn get_preferences(
+    blob_store: &State<SharedBlobStore>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<GetPreferencesOutput>, ApiError> {
+    match inner_get_preferences(blob_store, auth, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::preference::pref_match_namespace;
+use crate::actor_store::ActorStore;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::db::DbConn;
+use crate::repo::prepare::assert_input_valid;
+use crate::xrpc_server::body::{BoundedJson, PREFERENCES_MAX_BYTES};
+use rocket::State;
+use rsky_lexicon::app::bbs::actor::{CrosspostPref, PutPreferencesInput, PREFERENCES_NAMESPACE};
+use serde_json::Value;
+
+/// Whether `preferences` turn on `app.bbs.actor.defs#crosspostPref`
+fn enables_crosspost(preferences: &[Value]) -> bool {
+    preferences.iter().any(|preference| {
+        serde_json::from_value::<CrosspostPref>(preference.clone()).is_ok_and(|pref| pref.enabled)
+    })
+}
+
+async fn inner_put_preferences(
+    body: PutPreferencesInput,
+    blob_store: &State<SharedBlobStore>,
+    auth: AccessStandard,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    let body_value = serde_json::to_value(&body).map_err(|_| ApiError::RuntimeError)?;
+    if let Err(error) = assert_input_valid("app.bbs.actor.putPreferences", &body_value) {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    let PutPreferencesInput { preferences } = body;
+    // the PDS doesn't know their shapes, only that each names itself in the namespace
+    let namespace = PREFERENCES_NAMESPACE.to_string();
+    for preference in preferences.iter() {
+        match preference.get("$type").and_then(|name| name.as_str()) {
+            Some(name) if pref_match_namespace(&namespace, &name.to_string()) => (),
+            Some(name) => {
+                return Err(ApiError::InvalidRequest(format!(
+                    "{name} isn't a {namespace} preference"
+                )))
+            }
+            None => {
+                return Err(ApiError::InvalidRequest(
+                    "Every preference needs a $type".to_string(),
+                ))
+            }
+        }
+    }
+
+    let auth = auth.access.credentials.unwrap();
+    let requester = auth.did.unwrap();
+    // Mirrors are signed with the PDS's repo key, and a web5 account's repo
+    // only verifies against the key its owner signs with
+    if enables_crosspost(&preferences) {
+        match account_manager.get_account(&requester, None).await {
+            Ok(Some(account)) if account.ckb_address.is_some() => {
+                return Err(ApiError::InvalidRequest(
+                    "Crossposting isn't available to accounts that sign their own commits"
+                        .to_string(),
+                ))
+            }
+            Ok(_) => (),
+            Err(error) => {
+                tracing::error!("@LOG: ERROR: {error}");
+                return Err(ApiError::RuntimeError);
+            }
+        }
+    }
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester), db);
+    match actor_store
+        .pref
+        .put_preference_values(preferences, namespace, auth.scope.unwrap())
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Replaces the BBS client's preferences for the current account. They're
+/// kept in the PDS database next to the app.bsky ones, never in the repo.
+/// Web5 accounts can't turn on crossposting.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.actor.putPreferences", format = "json", data = "<body>")]
+pub async fn put_preferences(
+    body: BoundedJson<PutPreferencesInput, PREFERENCES_MAX_BYTES>,
+    blob_store: &State<SharedBlobStore>,
+    auth: AccessStandard,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    inner_put_preferences(body.into_inner(), blob_store, auth, account_manager, db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_an_enabled_crosspost_pref_enables_crossposting() {
+        let theme = json!({ "$type": "app.bbs.actor.defs#themePref", "enabled": true });
+        let crosspost = |enabled: bool| json!({ "$type": "app.bbs.actor.defs#crosspostPref", "enabled": enabled });
+        assert!(enables_crosspost(&[theme.clone(), crosspost(true)]));
+        assert!(!enables_crosspost(&[theme.clone(), crosspost(false)]));
+        assert!(!enables_crosspost(&[theme]));
+        assert!(!enables_crosspost(&[]));
+    }
+}
@@ -0,0 +1,35 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::draft::{self, DraftNotFoundError};
+use crate::db::DbConn;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::DeleteDraftInput;
+
+async fn inner_delete_draft(did: String, body: DeleteDraftInput, db: DbConn) -> Result<()> {
+    let Ok(id) = body.id.parse::<i64>() else {
+        return Err(DraftNotFoundError.into());
+    };
+    db.run(move |conn| draft::delete_draft(conn, &did, id))
+        .await
+}
+
+/// Deletes one of the requesting account's drafts, e.g. once the client has
+/// published it.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.deleteDraft", format = "json", data = "<body>")]
+pub async fn delete_draft(
+    body: Json<DeleteDraftInput>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_delete_draft(did, body.into_inner(), db).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.is::<DraftNotFoundError>() => Err(ApiError::from(error)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
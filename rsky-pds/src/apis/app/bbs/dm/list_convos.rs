@@ -0,0 +1,63 @@
+use crate::apis::app::bbs::dm::message_view;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::dm;
+use crate::db::DbConn;
+use anyhow::{bail, Result};
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::dm::{ConvoView, ListConvosOutput};
+
+async fn inner_list_convos(
+    did: String,
+    limit: u16,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<ListConvosOutput> {
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let convos = db
+        .run(move |conn| dm::list_convos(conn, &did, limit as i64, cursor))
+        .await?;
+    let cursor = match convos.len() == limit as usize {
+        true => convos.last().map(|last| {
+            format!(
+                "{}::{}",
+                last.convo.last_message_at.clone().unwrap_or_default(),
+                last.convo.id
+            )
+        }),
+        false => None,
+    };
+    Ok(ListConvosOutput {
+        convos: convos
+            .into_iter()
+            .map(|summary| ConvoView {
+                id: summary.convo.id,
+                members: summary.members,
+                last_message: summary.last_message.map(message_view),
+                unread_count: summary.unread_count,
+            })
+            .collect(),
+        cursor,
+    })
+}
+
+/// The requesting account's conversations, most recently active first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.dm.listConvos?<limit>&<cursor>")]
+pub async fn list_convos(
+    limit: Option<u16>,
+    cursor: Option<String>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<ListConvosOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_list_convos(did, limit.unwrap_or(50), cursor, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
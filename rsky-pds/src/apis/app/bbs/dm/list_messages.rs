@@ -0,0 +1,58 @@
+use crate::apis::app::bbs::dm::message_view;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::dm;
+use crate::bbs::dm::ConvoNotFoundError;
+use crate::db::DbConn;
+use anyhow::{bail, Result};
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::dm::ListMessagesOutput;
+
+async fn inner_list_messages(
+    did: String,
+    convo_id: String,
+    limit: u16,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<ListMessagesOutput> {
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let cursor = match cursor {
+        Some(cursor) => Some(cursor.parse::<i64>()?),
+        None => None,
+    };
+    let messages = db
+        .run(move |conn| dm::list_messages(conn, &did, &convo_id, limit as i64, cursor))
+        .await?;
+    let cursor = match messages.len() == limit as usize {
+        true => messages.last().map(|last| last.id.to_string()),
+        false => None,
+    };
+    Ok(ListMessagesOutput {
+        messages: messages.into_iter().map(message_view).collect(),
+        cursor,
+    })
+}
+
+/// Messages in one of the requesting account's conversations, newest first.
+#[allow(non_snake_case)]
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.dm.listMessages?<convoId>&<limit>&<cursor>")]
+pub async fn list_messages(
+    convoId: String,
+    limit: Option<u16>,
+    cursor: Option<String>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<ListMessagesOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_list_messages(did, convoId, limit.unwrap_or(50), cursor, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) if error.is::<ConvoNotFoundError>() => Err(ApiError::from(error)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::dm;
+use crate::bbs::dm::ConvoNotFoundError;
+use crate::db::DbConn;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::dm::MarkReadInput;
+
+async fn inner_mark_read(did: String, body: MarkReadInput, db: DbConn) -> Result<()> {
+    let MarkReadInput {
+        convo_id,
+        message_id,
+    } = body;
+    let message_id = match message_id {
+        Some(message_id) => Some(message_id.parse::<i64>()?),
+        None => None,
+    };
+    db.run(move |conn| dm::mark_read(conn, &did, &convo_id, message_id))
+        .await
+}
+
+/// Marks the requesting account's messages in a conversation as read, up to
+/// the given message or the newest one.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.dm.markRead", format = "json", data = "<body>")]
+pub async fn mark_read(
+    body: Json<MarkReadInput>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_mark_read(did, body.into_inner(), db).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.is::<ConvoNotFoundError>() => Err(ApiError::from(error)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
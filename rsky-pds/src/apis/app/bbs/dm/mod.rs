@@ -0,0 +1,17 @@
+use crate::models::BbsDmMessage;
+use rsky_lexicon::app::bbs::dm::MessageView;
+
+pub mod list_convos;
+pub mod list_messages;
+pub mod mark_read;
+pub mod send_message;
+
+fn message_view(message: BbsDmMessage) -> MessageView {
+    MessageView {
+        id: message.id.to_string(),
+        convo_id: message.convo_id,
+        sender: message.sender_did,
+        text: message.text,
+        sent_at: message.created_at,
+    }
+}
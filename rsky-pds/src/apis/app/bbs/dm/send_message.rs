@@ -0,0 +1,89 @@
+use crate::account_manager::AccountManager;
+use crate::apis::app::bbs::dm::message_view;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::dm;
+use crate::bbs::dm::ConvoNotFoundError;
+use crate::db::DbConn;
+use crate::repo::prepare::assert_input_valid;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::dm::{MessageView, SendMessageInput};
+
+async fn inner_send_message(
+    body: SendMessageInput,
+    did: String,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<MessageView, ApiError> {
+    let body_value = serde_json::to_value(&body).map_err(|_| ApiError::RuntimeError)?;
+    if let Err(error) = assert_input_valid("app.bbs.dm.sendMessage", &body_value) {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    let SendMessageInput {
+        convo_id,
+        members,
+        text,
+    } = body;
+
+    let members = match (&convo_id, members) {
+        (Some(_), _) => None,
+        (None, Some(mut members)) => {
+            members.retain(|member| member != &did);
+            members.sort();
+            members.dedup();
+            if members.is_empty() {
+                return Err(ApiError::InvalidRequest(
+                    "members must name someone besides the sender".to_string(),
+                ));
+            }
+            // Messages never leave this PDS, so every member has to be one of its accounts
+            for member in members.iter() {
+                match account_manager.get_account(member, None).await {
+                    Ok(Some(_)) => (),
+                    Ok(None) => {
+                        return Err(ApiError::InvalidRequest(format!(
+                            "{member} isn't an active account on this PDS"
+                        )))
+                    }
+                    Err(error) => {
+                        tracing::error!("@LOG: ERROR: {error}");
+                        return Err(ApiError::RuntimeError);
+                    }
+                }
+            }
+            Some(members)
+        }
+        (None, None) => {
+            return Err(ApiError::InvalidRequest(
+                "Either convoId or members is required".to_string(),
+            ))
+        }
+    };
+
+    match db
+        .run(move |conn| dm::send_message(conn, &did, convo_id, members, text))
+        .await
+    {
+        Ok(message) => Ok(message_view(message)),
+        Err(error) if error.is::<ConvoNotFoundError>() => Err(ApiError::from(error)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Sends a direct message to other accounts on this PDS. Messages are kept
+/// in the PDS database, never in a repo, so they aren't relayed.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.dm.sendMessage", format = "json", data = "<body>")]
+pub async fn send_message(
+    body: Json<SendMessageInput>,
+    auth: AccessStandard,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<Json<MessageView>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let message = inner_send_message(body.into_inner(), did, account_manager, db).await?;
+    Ok(Json(message))
+}
@@ -0,0 +1,41 @@
+use crate::apis::com::atproto::sync::get_blob::BlobResponder;
+use crate::apis::ApiError;
+use crate::db::replica::ReadConn;
+use anyhow::Result;
+use diesel::prelude::*;
+use rocket::http::Header;
+
+async fn inner_get_link_thumb(cid: String, db: ReadConn) -> Result<Option<Vec<u8>>> {
+    use crate::schema::pds::bbs_link_card::dsl as CardSchema;
+
+    let thumb = db
+        .run(move |conn| {
+            CardSchema::bbs_link_card
+                .filter(CardSchema::thumbCid.eq(cid))
+                .select(CardSchema::thumb)
+                .first::<Option<Vec<u8>>>(conn)
+                .optional()
+        })
+        .await?;
+    Ok(thumb.flatten())
+}
+
+/// The thumbnail of a link card, made from the linked page's og:image when
+/// it was unfurled. Does not require auth.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getLinkThumb?<cid>")]
+pub async fn get_link_thumb(cid: String, db: ReadConn) -> Result<BlobResponder, ApiError> {
+    match inner_get_link_thumb(cid, db).await {
+        Ok(Some(bytes)) => Ok(BlobResponder(
+            bytes.clone(),
+            Header::new("content-length", bytes.len().to_string()),
+            Header::new("content-type", "image/jpeg"),
+            Header::new("content-security-policy", "default-src 'none'; sandbox"),
+        )),
+        Ok(None) => Err(ApiError::BlobNotFound),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
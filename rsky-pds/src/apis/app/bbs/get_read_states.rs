@@ -0,0 +1,48 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::read_state;
+use crate::db::DbConn;
+use anyhow::{bail, Result};
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::{GetReadStatesOutput, ReadState};
+
+async fn inner_get_read_states(
+    did: String,
+    threads: Vec<String>,
+    db: DbConn,
+) -> Result<GetReadStatesOutput> {
+    if threads.is_empty() || threads.len() > 100 {
+        bail!("Error: threads must list between 1 and 100 threads")
+    }
+    let states = db
+        .run(move |conn| read_state::get_read_states(conn, &did, threads))
+        .await?
+        .into_iter()
+        .map(|state| ReadState {
+            thread: state.thread,
+            read_reply_count: state.read_reply_count,
+            last_read_at: state.last_read_at,
+            updated_at: state.updated_at,
+        })
+        .collect();
+    Ok(GetReadStatesOutput { states })
+}
+
+/// How far the requesting account has read the given threads, for showing
+/// unread counts next to a section feed.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getReadStates?<threads>")]
+pub async fn get_read_states(
+    threads: Vec<String>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<GetReadStatesOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_get_read_states(did, threads, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::apis::ApiError;
+use crate::hydration::Hydrator;
+use crate::pipethrough::{pipethrough, OverrideOpts, ProxyRequest};
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::GetSectionFeedOutput;
+
+async fn inner_get_section_feed(
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<GetSectionFeedOutput> {
+    let res = pipethrough(
+        &req,
+        None,
+        OverrideOpts {
+            aud: None,
+            lxm: None,
+        },
+    )
+    .await?;
+    let output: GetSectionFeedOutput = serde_json::from_slice(res.buffer.as_slice())?;
+    Ok(GetSectionFeedOutput {
+        threads: hydrator.hydrate_threads(output.threads).await?,
+        cursor: output.cursor,
+        status: output.status,
+    })
+}
+
+/// Threads in a section from the BBS AppView, without what was taken down on
+/// this PDS or written by its inactive accounts. A page may come back short,
+/// the cursor still points past everything the AppView returned.
+#[tracing::instrument(skip_all)]
+#[allow(unused_variables)]
+#[rocket::get("/xrpc/app.bbs.getSectionFeed?<section>&<sort>&<limit>&<cursor>")]
+pub async fn get_section_feed(
+    section: i64,
+    sort: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<Json<GetSectionFeedOutput>, ApiError> {
+    match inner_get_section_feed(req, hydrator).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(error.into())
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::apis::ApiError;
+use crate::bbs::section;
+use crate::bbs::stats::STATS_ROW_ID;
+use crate::db::replica::ReadConn;
+use crate::models::{BbsSectionStats, BbsStats};
+use anyhow::Result;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::{GetStatsOutput, SectionStats};
+
+async fn inner_get_stats(db: ReadConn) -> Result<GetStatsOutput> {
+    use crate::schema::pds::bbs_section_stats::dsl as SectionStatsSchema;
+    use crate::schema::pds::bbs_stats::dsl as StatsSchema;
+
+    let (stats, sections, statuses) = db
+        .run(move |conn| {
+            let stats = StatsSchema::bbs_stats
+                .filter(StatsSchema::id.eq(STATS_ROW_ID))
+                .select(BbsStats::as_select())
+                .first(conn)
+                .optional()?;
+            let sections = SectionStatsSchema::bbs_section_stats
+                .select(BbsSectionStats::as_select())
+                .order(SectionStatsSchema::sectionId.asc())
+                .load(conn)?;
+            let statuses = section::statuses(conn)?;
+            Ok::<_, diesel::result::Error>((stats, sections, statuses))
+        })
+        .await?;
+
+    // Until the aggregation job has run once there is nothing to report
+    let stats = stats.unwrap_or_default();
+    Ok(GetStatsOutput {
+        total_posts: stats.total_posts,
+        total_replies: stats.total_replies,
+        active_users_7d: stats.active_users_7d,
+        active_users_30d: stats.active_users_30d,
+        sections: sections
+            .into_iter()
+            .map(|section| SectionStats {
+                section_id: section.section_id as usize,
+                total_posts: section.total_posts,
+                total_replies: section.total_replies,
+                posts_7d: section.posts_7d,
+                posts_30d: section.posts_30d,
+                last_activity_at: section.last_activity_at,
+                status: Some(
+                    statuses
+                        .get(&section.section_id)
+                        .copied()
+                        .unwrap_or_default(),
+                ),
+            })
+            .collect(),
+        computed_at: match stats.computed_at.is_empty() {
+            true => None,
+            false => Some(stats.computed_at),
+        },
+    })
+}
+
+/// Sitewide BBS statistics. Served from the snapshot maintained by the
+/// periodic aggregation job, so numbers may lag by up to one interval.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getStats")]
+pub async fn get_stats(db: ReadConn) -> Result<Json<GetStatsOutput>, ApiError> {
+    match inner_get_stats(db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
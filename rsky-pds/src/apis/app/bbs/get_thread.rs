@@ -0,0 +1,48 @@
+use crate::apis::ApiError;
+use crate::hydration::Hydrator;
+use crate::pipethrough::{pipethrough, OverrideOpts, ProxyRequest};
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::GetThreadOutput;
+
+async fn inner_get_thread(
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<Option<GetThreadOutput>> {
+    let res = pipethrough(
+        &req,
+        None,
+        OverrideOpts {
+            aud: None,
+            lxm: None,
+        },
+    )
+    .await?;
+    let output: GetThreadOutput = serde_json::from_slice(res.buffer.as_slice())?;
+    hydrator.hydrate_thread(output).await
+}
+
+/// A thread with its replies from the BBS AppView, without what was taken
+/// down on this PDS or written by its inactive accounts.
+#[tracing::instrument(skip_all)]
+#[allow(unused_variables)]
+#[rocket::get("/xrpc/app.bbs.getThread?<uri>&<limit>&<cursor>")]
+pub async fn get_thread(
+    uri: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<Json<GetThreadOutput>, ApiError> {
+    match inner_get_thread(req, hydrator).await {
+        Ok(Some(res)) => Ok(Json(res)),
+        Ok(None) => Err(ApiError::BadRequest(
+            "NotFound".to_string(),
+            format!("Thread not found: {uri}"),
+        )),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(error.into())
+        }
+    }
+}
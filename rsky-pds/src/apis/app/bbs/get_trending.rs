@@ -0,0 +1,104 @@
+use crate::apis::ApiError;
+use crate::db::replica::ReadConn;
+use crate::hydration::Hydrator;
+use crate::models::BbsTrending;
+use anyhow::{bail, Result};
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::{
+    truncate_graphemes, AuthorView, GetTrendingOutput, ThreadView, PREVIEW_GRAPHEMES,
+};
+
+async fn inner_get_trending(
+    section: Option<i64>,
+    limit: u16,
+    cursor: Option<String>,
+    db: ReadConn,
+    hydrator: Hydrator,
+) -> Result<GetTrendingOutput> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::bbs_trending::dsl as TrendingSchema;
+
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let after_rank = match cursor {
+        Some(cursor) => cursor.parse::<i64>()?,
+        None => 0,
+    };
+    let rows: Vec<(BbsTrending, Option<String>)> = db
+        .run(move |conn| {
+            let mut builder = TrendingSchema::bbs_trending
+                .left_join(ActorSchema::actor.on(ActorSchema::did.eq(TrendingSchema::did)))
+                .filter(TrendingSchema::rank.gt(after_rank))
+                .into_boxed();
+            if let Some(section) = section {
+                builder = builder.filter(TrendingSchema::sectionId.eq(section));
+            }
+            builder
+                .order(TrendingSchema::rank.asc())
+                .limit(limit as i64)
+                .select((BbsTrending::as_select(), ActorSchema::handle.nullable()))
+                .load(conn)
+        })
+        .await?;
+
+    let cursor = match rows.len() == limit as usize {
+        true => rows.last().map(|(last, _)| last.rank.to_string()),
+        false => None,
+    };
+    let threads = rows
+        .into_iter()
+        .map(|(thread, handle)| {
+            // Like the AppView's listings, only the start of the post
+            let (text, truncated) = truncate_graphemes(&thread.text, PREVIEW_GRAPHEMES);
+            ThreadView {
+                uri: thread.uri,
+                cid: thread.cid,
+                author: AuthorView {
+                    did: thread.did,
+                    handle,
+                },
+                section_id: thread.section_id,
+                title: thread.title,
+                text,
+                reply_count: thread.reply_count,
+                score: thread.vote_score,
+                created_at: thread.created_at,
+                last_activity_at: thread.last_activity_at,
+                indexed_at: thread.computed_at,
+                truncated: truncated.then_some(true),
+                image: None,
+                external: None,
+                reactions: None,
+                embed: None,
+            }
+        })
+        .collect();
+    Ok(GetTrendingOutput {
+        threads: hydrator.hydrate_threads(threads).await?,
+        cursor,
+    })
+}
+
+/// Recent threads hottest first, optionally within one section. Served from
+/// the snapshot maintained by the periodic ranking job, so the order may lag
+/// by up to one interval and a cursor from before a re-rank can skip or
+/// repeat threads.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.getTrending?<section>&<limit>&<cursor>")]
+pub async fn get_trending(
+    section: Option<i64>,
+    limit: Option<u16>,
+    cursor: Option<String>,
+    db: ReadConn,
+    hydrator: Hydrator,
+) -> Result<Json<GetTrendingOutput>, ApiError> {
+    match inner_get_trending(section, limit.unwrap_or(50), cursor, db, hydrator).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::apis::app::bbs::draft_view;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::draft;
+use crate::db::DbConn;
+use anyhow::{bail, Result};
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::{DraftView, ListDraftsOutput};
+
+async fn inner_list_drafts(
+    did: String,
+    limit: u16,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<ListDraftsOutput> {
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let drafts = db
+        .run(move |conn| draft::list_drafts(conn, &did, limit as i64, cursor))
+        .await?
+        .into_iter()
+        .map(draft_view)
+        .collect::<Result<Vec<DraftView>>>()?;
+    let cursor = match drafts.len() == limit as usize {
+        true => drafts
+            .last()
+            .map(|last| format!("{}::{}", last.updated_at, last.id)),
+        false => None,
+    };
+    Ok(ListDraftsOutput { drafts, cursor })
+}
+
+/// The requesting account's drafts, most recently saved first.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/app.bbs.listDrafts?<limit>&<cursor>")]
+pub async fn list_drafts(
+    limit: Option<u16>,
+    cursor: Option<String>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<ListDraftsOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_list_drafts(did, limit.unwrap_or(50), cursor, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::models::BbsDraft;
+use anyhow::Result;
+use rsky_lexicon::app::bbs::DraftView;
+
+pub mod actor;
+pub mod delete_draft;
+pub mod dm;
+pub mod get_link_thumb;
+pub mod get_read_states;
+pub mod get_section_feed;
+pub mod get_stats;
+pub mod get_thread;
+pub mod get_trending;
+pub mod list_drafts;
+pub mod moderation;
+pub mod save_draft;
+pub mod search_posts;
+pub mod update_read_state;
+
+fn draft_view(draft: BbsDraft) -> Result<DraftView> {
+    Ok(DraftView {
+        id: draft.id.to_string(),
+        collection: draft.collection,
+        value: serde_json::from_str(&draft.value)?,
+        created_at: draft.created_at,
+        updated_at: draft.updated_at,
+    })
+}
@@ -0,0 +1 @@
+pub mod set_section_status;
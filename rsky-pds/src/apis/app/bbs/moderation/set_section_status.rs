@@ -0,0 +1,70 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::section;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::SharedSequencer;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::app::bbs::moderation::{SetSectionStatusInput, SetSectionStatusOutput};
+
+async fn inner_set_section_status(
+    did: String,
+    body: SetSectionStatusInput,
+    sequencer: &State<SharedSequencer>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<SetSectionStatusOutput, ApiError> {
+    let SetSectionStatusInput { section_id, status } = body;
+    let section_id = section_id as i64;
+    let is_moderator = cfg
+        .bbs
+        .section_moderators
+        .get(&section_id)
+        .is_some_and(|moderators| moderators.contains(&did));
+    if !is_moderator {
+        return Err(ApiError::BadRequest(
+            "InsufficientScope".to_string(),
+            format!("Not a moderator of section {section_id}"),
+        ));
+    }
+
+    let updated_by = did.clone();
+    let row = db
+        .run(move |conn| section::set_status(conn, section_id, status, &updated_by))
+        .await
+        .map_err(anyhow::Error::from)?;
+    // Appviews stop showing the composer without waiting for a reindex
+    let mut lock = sequencer.sequencer.write().await;
+    lock.sequence_section_evt(did, section_id, status).await?;
+    Ok(SetSectionStatusOutput {
+        section_id: section_id as usize,
+        status,
+        updated_at: row.updated_at,
+    })
+}
+
+/// Opens or archives a BBS section. Archived sections are read-only: the PDS
+/// refuses new posts and replies in them, and edits to the ones there.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/app.bbs.moderation.setSectionStatus",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn set_section_status(
+    body: Json<SetSectionStatusInput>,
+    auth: AccessStandard,
+    sequencer: &State<SharedSequencer>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<Json<SetSectionStatusOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_set_section_status(did, body.into_inner(), sequencer, cfg, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error:?}");
+            Err(error)
+        }
+    }
+}
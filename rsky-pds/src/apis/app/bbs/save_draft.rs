@@ -0,0 +1,79 @@
+use crate::apis::app::bbs::draft_view;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::draft::{self, DraftNotFoundError, TooManyDraftsError, MAX_DRAFT_BYTES};
+use crate::bbs::{POST_COLLECTION, REPLY_COLLECTION};
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::app::bbs::{DraftView, SaveDraftInput};
+
+async fn inner_save_draft(
+    did: String,
+    body: SaveDraftInput,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<DraftView, ApiError> {
+    let SaveDraftInput {
+        id,
+        collection,
+        value,
+    } = body;
+    if collection != POST_COLLECTION && collection != REPLY_COLLECTION {
+        return Err(ApiError::InvalidRequest(format!(
+            "Drafts are for {POST_COLLECTION} or {REPLY_COLLECTION}, not {collection}"
+        )));
+    }
+    // left unvalidated otherwise, a draft is unfinished by definition
+    if !value.is_object() {
+        return Err(ApiError::InvalidRequest(
+            "value must be an object".to_string(),
+        ));
+    }
+    let value = serde_json::to_string(&value).map_err(|_| ApiError::RuntimeError)?;
+    if value.len() > MAX_DRAFT_BYTES {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "Drafts can be at most {MAX_DRAFT_BYTES} bytes"
+        )));
+    }
+    let id = match id {
+        Some(id) => Some(
+            id.parse::<i64>()
+                .map_err(|_| anyhow::Error::from(DraftNotFoundError))?,
+        ),
+        None => None,
+    };
+
+    let max_drafts = cfg.bbs.max_drafts;
+    match db
+        .run(move |conn| draft::save_draft(conn, &did, id, &collection, value, max_drafts))
+        .await
+        .and_then(draft_view)
+    {
+        Ok(draft) => Ok(draft),
+        Err(error) if error.is::<DraftNotFoundError>() || error.is::<TooManyDraftsError>() => {
+            Err(ApiError::from(error))
+        }
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Saves an unfinished post or reply for the requesting account, so another
+/// of their devices can pick it up. Drafts are kept in the PDS database,
+/// never in the repo, so nothing is signed or relayed.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.saveDraft", format = "json", data = "<body>")]
+pub async fn save_draft(
+    body: Json<SaveDraftInput>,
+    auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<Json<DraftView>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let draft = inner_save_draft(did, body.into_inner(), cfg, db).await?;
+    Ok(Json(draft))
+}
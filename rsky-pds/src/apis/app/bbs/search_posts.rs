@@ -0,0 +1,47 @@
+use crate::apis::ApiError;
+use crate::hydration::Hydrator;
+use crate::pipethrough::{pipethrough, OverrideOpts, ProxyRequest};
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::app::bbs::SearchPostsOutput;
+
+async fn inner_search_posts(
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<SearchPostsOutput> {
+    let res = pipethrough(
+        &req,
+        None,
+        OverrideOpts {
+            aud: None,
+            lxm: None,
+        },
+    )
+    .await?;
+    let output: SearchPostsOutput = serde_json::from_slice(res.buffer.as_slice())?;
+    Ok(SearchPostsOutput {
+        threads: hydrator.hydrate_threads(output.threads).await?,
+        cursor: output.cursor,
+    })
+}
+
+/// Thread search from the BBS AppView, filtered like getSectionFeed.
+#[tracing::instrument(skip_all)]
+#[allow(unused_variables)]
+#[rocket::get("/xrpc/app.bbs.searchPosts?<q>&<section>&<limit>&<cursor>")]
+pub async fn search_posts(
+    q: String,
+    section: Option<i64>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    req: ProxyRequest<'_>,
+    hydrator: Hydrator,
+) -> Result<Json<SearchPostsOutput>, ApiError> {
+    match inner_search_posts(req, hydrator).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(error.into())
+        }
+    }
+}
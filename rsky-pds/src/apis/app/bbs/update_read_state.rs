@@ -0,0 +1,58 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::bbs::read_state::{self, ReadPosition};
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::repo::prepare::assert_input_valid;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::app::bbs::UpdateReadStateInput;
+
+async fn inner_update_read_state(
+    did: String,
+    body: UpdateReadStateInput,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    let body_value = serde_json::to_value(&body).map_err(|_| ApiError::RuntimeError)?;
+    if let Err(error) = assert_input_valid("app.bbs.updateReadState", &body_value) {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    let positions = body
+        .states
+        .into_iter()
+        .map(|state| ReadPosition {
+            thread: state.thread,
+            read_reply_count: state.read_reply_count,
+            last_read_at: format!("{}", state.last_read_at.format(RFC3339_VARIANT)),
+        })
+        .collect::<Vec<ReadPosition>>();
+
+    let max_read_states = cfg.bbs.max_read_states;
+    match db
+        .run(move |conn| read_state::update_read_states(conn, &did, positions, max_read_states))
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Records how far the requesting account has read some threads, so unread
+/// counts agree across their devices. Clients are expected to batch what was
+/// read over a while into one call rather than report every reply.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/app.bbs.updateReadState", format = "json", data = "<body>")]
+pub async fn update_read_state(
+    body: Json<UpdateReadStateInput>,
+    auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    inner_update_read_state(did, body.into_inner(), cfg, db).await
+}
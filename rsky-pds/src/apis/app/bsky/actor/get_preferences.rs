@@ -1,26 +1,21 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::db::DbConn;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::app::bsky::actor::{GetPreferencesOutput, RefPreferences};
 
 async fn inner_get_preferences(
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: AccessStandard,
     db: DbConn,
 ) -> Result<GetPreferencesOutput> {
     let auth = auth.access.credentials.unwrap();
     let requester = auth.did.unwrap().clone();
-    let actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
     let preferences: Vec<RefPreferences> = actor_store
         .pref
         .get_preferences(Some("app.bsky".to_string()), auth.scope.unwrap())
@@ -34,11 +29,11 @@ async fn inner_get_preferences(
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/app.bsky.actor.getPreferences")]
 pub async fn get_preferences(
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: AccessStandard,
     db: DbConn,
 ) -> Result<Json<GetPreferencesOutput>, ApiError> {
-    match inner_get_preferences(s3_config, auth, db).await {
+    match inner_get_preferences(blob_store, auth, db).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
@@ -1,4 +1,5 @@
 use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::config::ServerConfig;
@@ -9,7 +10,6 @@ use crate::read_after_write::viewer::LocalViewer;
 use crate::xrpc_server::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::State;
 use rsky_lexicon::app::bsky::actor::ProfileViewDetailed;
 
@@ -20,7 +20,7 @@ pub async fn inner_get_profile(
     _actor: String,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -37,7 +37,7 @@ pub async fn inner_get_profile(
                 requester,
                 res,
                 get_profile_munge,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
@@ -57,7 +57,7 @@ pub async fn get_profile(
     actor: String,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -70,7 +70,7 @@ pub async fn get_profile(
                 actor,
                 auth,
                 res,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
@@ -1,4 +1,5 @@
 use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::config::ServerConfig;
@@ -9,7 +10,6 @@ use crate::read_after_write::viewer::LocalViewer;
 use crate::xrpc_server::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::State;
 use rsky_lexicon::app::bsky::actor::{GetProfilesOutput, ProfileViewDetailed};
 
@@ -19,7 +19,7 @@ pub async fn inner_get_profiles(
     _actors: Vec<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -33,7 +33,7 @@ pub async fn inner_get_profiles(
         requester,
         res,
         get_profiles_munge,
-        s3_config,
+        blob_store,
         state_local_viewer,
         db,
         account_manager,
@@ -49,7 +49,7 @@ pub async fn get_profiles(
     actors: Vec<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -62,7 +62,7 @@ pub async fn get_profiles(
                 actors,
                 auth,
                 res,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
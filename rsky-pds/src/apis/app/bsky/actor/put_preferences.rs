@@ -1,28 +1,23 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::db::DbConn;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::app::bsky::actor::PutPreferencesInput;
 
 async fn inner_put_preferences(
     body: Json<PutPreferencesInput>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: AccessStandard,
     db: DbConn,
 ) -> Result<(), ApiError> {
     let PutPreferencesInput { preferences } = body.into_inner();
     let auth = auth.access.credentials.unwrap();
     let requester = auth.did.unwrap().clone();
-    let actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
     actor_store
         .pref
         .put_preferences(preferences, "app.bsky".to_string(), auth.scope.unwrap())
@@ -38,11 +33,11 @@ async fn inner_put_preferences(
 )]
 pub async fn put_preferences(
     body: Json<PutPreferencesInput>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: AccessStandard,
     db: DbConn,
 ) -> Result<(), ApiError> {
-    match inner_put_preferences(body, s3_config, auth, db).await {
+    match inner_put_preferences(body, blob_store, auth, db).await {
         Ok(_) => Ok(()),
         Err(error) => Err(error),
     }
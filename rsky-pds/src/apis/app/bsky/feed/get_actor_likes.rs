@@ -1,4 +1,5 @@
 use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::config::ServerConfig;
@@ -9,7 +10,6 @@ use crate::read_after_write::viewer::LocalViewer;
 use crate::xrpc_server::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::State;
 use rsky_lexicon::app::bsky::feed::{AuthorFeed, FeedViewPost, PostView};
 
@@ -21,7 +21,7 @@ pub async fn inner_get_actor_likes(
     _cursor: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -38,7 +38,7 @@ pub async fn inner_get_actor_likes(
                 requester,
                 res,
                 get_author_munge,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
@@ -58,7 +58,7 @@ pub async fn get_actor_likes(
     cursor: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -77,7 +77,7 @@ pub async fn get_actor_likes(
             cursor,
             auth,
             res,
-            s3_config,
+            blob_store,
             state_local_viewer,
             db,
             account_manager,
@@ -1,4 +1,5 @@
 use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
 use crate::config::ServerConfig;
@@ -9,7 +10,6 @@ use crate::read_after_write::viewer::LocalViewer;
 use crate::xrpc_server::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::form::validate::Contains;
 use rocket::State;
 use rsky_lexicon::app::bsky::feed::{AuthorFeed, FeedViewPost, PostView};
@@ -23,7 +23,7 @@ pub async fn inner_get_author_feed(
     _filter: Option<String>,
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -40,7 +40,7 @@ pub async fn inner_get_author_feed(
                 requester,
                 res,
                 get_author_munge,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
@@ -61,7 +61,7 @@ pub async fn get_author_feed(
     filter: Option<String>, // Combinations of post/repost types to include in response.
     auth: AccessStandard,
     res: HandlerPipeThrough,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -69,9 +69,8 @@ pub async fn get_author_feed(
 ) -> Result<ReadAfterWriteResponse<AuthorFeed>, ApiError> {
     if let Some(limit) = limit {
         if limit > 100 {
-            return Err(ApiError::BadRequest(
-                "invalid_limit".to_string(),
-                "invalid_limit".to_string(),
+            return Err(ApiError::InvalidRequest(
+                "limit can not be greater than 100".to_string(),
             ));
         }
     }
@@ -84,17 +83,16 @@ pub async fn get_author_feed(
         ]
         .contains(filter.as_str())
         {
-            return Err(ApiError::BadRequest(
-                "invalid filter".to_string(),
-                "invalid filter".to_string(),
-            ));
+            return Err(ApiError::InvalidRequest(format!(
+                "Unknown filter: {filter}"
+            )));
         }
     }
     match cfg.bsky_app_view {
         None => {
             return Err(ApiError::BadRequest(
-                "not found".to_string(),
-                "not found".to_string(),
+                "NotFound".to_string(),
+                "No AppView configured".to_string(),
             ));
         }
         Some(_) => match inner_get_author_feed(
@@ -104,7 +102,7 @@ pub async fn get_author_feed(
             filter,
             auth,
             res,
-            s3_config,
+            blob_store,
             state_local_viewer,
             db,
             account_manager,
@@ -1,5 +1,5 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
@@ -21,7 +21,6 @@ use atrium_api::app::bsky::feed::get_post_thread::{
 use atrium_api::client::AtpServiceClient;
 use atrium_api::types::LimitedU16;
 use atrium_xrpc_client::reqwest::ReqwestClientBuilder;
-use aws_sdk_s3::Config;
 use futures::stream::{self, StreamExt};
 use ipld_core::ipld::Ipld as AtriumIpld;
 use reqwest::header::HeaderMap;
@@ -50,7 +49,7 @@ pub async fn inner_get_post_thread(
     parentHeight: u16,
     auth: AccessStandard,
     res: Result<HandlerPipeThrough>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -67,7 +66,7 @@ pub async fn inner_get_post_thread(
                 requester,
                 res,
                 get_post_thread_munge,
-                s3_config,
+                blob_store,
                 state_local_viewer,
                 db,
                 account_manager,
@@ -88,7 +87,7 @@ pub async fn inner_get_post_thread(
                         Some(error) if error == "NotFound" => {
                             let actor_store = ActorStore::new(
                                 requester.clone(),
-                                S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
+                                blob_store.for_did(requester.clone()),
                                 db,
                             );
                             let local_viewer_lock = state_local_viewer.local_viewer.read().await;
@@ -132,7 +131,7 @@ pub async fn get_post_thread(
     parentHeight: Option<u16>, // How many levels of parent (and grandparent, etc.) post to include.
     auth: AccessStandard,
     res: Result<HandlerPipeThrough>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     cfg: &State<ServerConfig>,
     db: DbConn,
@@ -153,7 +152,7 @@ pub async fn get_post_thread(
             parentHeight,
             auth,
             res,
-            s3_config,
+            blob_store,
             state_local_viewer,
             cfg,
             db,
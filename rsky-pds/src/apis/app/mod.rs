@@ -1 +1,2 @@
+pub mod bbs;
 pub mod bsky;
@@ -0,0 +1,128 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::plc::web5_types::get_didoc_from_chain;
+use anyhow::Result;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::{ChainInconsistency, CheckChainConsistencyOutput};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+/// What's wrong between an account and its DID doc cell, empty if they agree.
+async fn check_account(handle: Option<&str>, ckb_addr: &str, public_url: &str) -> Vec<String> {
+    let didoc = match get_didoc_from_chain(ckb_addr).await {
+        Ok(didoc) => didoc,
+        Err(ApiError::CkbAddrNoCell | ApiError::CkbDidocCellNotFound) => {
+            return vec!["missingCell".to_string()]
+        }
+        Err(_) => return vec!["chainUnavailable".to_string()],
+    };
+    let mut problems = Vec::new();
+    let chain_handle = didoc
+        .also_known_as
+        .first()
+        .and_then(|aka| aka.strip_prefix("at://"));
+    if chain_handle != handle {
+        problems.push("handleMismatch".to_string());
+    }
+    let endpoint = didoc
+        .services
+        .get("atproto_pds")
+        .map(|service| service.endpoint.trim_end_matches('/'));
+    if endpoint != Some(public_url.trim_end_matches('/')) {
+        problems.push("pdsMismatch".to_string());
+    }
+    problems
+}
+
+async fn inner_check_chain_consistency(
+    did: Option<String>,
+    limit: i64,
+    cursor: Option<String>,
+    public_url: &str,
+    db: DbConn,
+) -> Result<CheckChainConsistencyOutput> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+
+    let rows = db
+        .run(move |conn| {
+            let mut builder = ActorSchema::actor
+                .filter(ActorSchema::ckbAddress.is_not_null())
+                .select((
+                    ActorSchema::did,
+                    ActorSchema::handle,
+                    ActorSchema::ckbAddress,
+                ))
+                .order(ActorSchema::did.asc())
+                .limit(limit)
+                .into_boxed();
+            if let Some(did) = did {
+                builder = builder.filter(ActorSchema::did.eq(did));
+            }
+            if let Some(cursor) = cursor {
+                builder = builder.filter(ActorSchema::did.gt(cursor));
+            }
+            builder.load::<(String, Option<String>, Option<String>)>(conn)
+        })
+        .await?;
+
+    let cursor = match rows.len() as i64 == limit {
+        true => rows.last().map(|(did, _, _)| did.clone()),
+        false => None,
+    };
+    let checked = rows.len();
+    let mut inconsistencies = Vec::new();
+    // One at a time, so a full sweep doesn't flood the CKB node
+    for (did, handle, ckb_address) in rows {
+        let Some(ckb_address) = ckb_address else {
+            continue;
+        };
+        let problems = check_account(handle.as_deref(), &ckb_address, public_url).await;
+        if !problems.is_empty() {
+            inconsistencies.push(ChainInconsistency {
+                did,
+                handle,
+                ckb_address,
+                problems,
+            });
+        }
+    }
+    Ok(CheckChainConsistencyOutput {
+        cursor,
+        checked,
+        inconsistencies,
+    })
+}
+
+/// Compares web5 accounts with their DID doc cells on chain: the cell has to
+/// exist, name the account's handle first and point at this PDS. Pages
+/// through accounts by DID.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.admin.checkChainConsistency?<did>&<limit>&<cursor>")]
+pub async fn check_chain_consistency(
+    did: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    cfg: &State<ServerConfig>,
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<CheckChainConsistencyOutput>, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::InvalidRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    let did = did.map(|did| did.to_lowercase());
+    match inner_check_chain_consistency(did, limit, cursor, &cfg.service.public_url, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::ActorStore;
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::{CompactRepoInput, CompactRepoOutput};
+
+async fn inner_compact_repo(
+    body: Json<CompactRepoInput>,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<CompactRepoOutput> {
+    let CompactRepoInput { did } = body.into_inner();
+
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+    let compaction = actor_store.compact_repo().await?;
+    tracing::info!(
+        "Compacted repo {did}: kept {} blocks, removed {} ({} bytes reclaimed)",
+        compaction.blocks_kept,
+        compaction.blocks_removed,
+        compaction.reclaimed_bytes
+    );
+    Ok(CompactRepoOutput {
+        blocks_kept: compaction.blocks_kept,
+        blocks_removed: compaction.blocks_removed,
+        reclaimed_bytes: compaction.reclaimed_bytes,
+    })
+}
+
+/// Removes blocks left behind by old commits from an actor's blockstore.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.compactRepo",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn compact_repo(
+    body: Json<CompactRepoInput>,
+    blob_store: &State<SharedBlobStore>,
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<CompactRepoOutput>, ApiError> {
+    match inner_compact_repo(body, blob_store, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::Moderator;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::labeler;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::{CreateLabelsInput, CreateLabelsOutput};
+
+/// Issues labels signed by this service, e.g. from the BBS moderation queue. They are
+/// served by queryLabels and streamed to subscribeLabels consumers.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.createLabels",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn create_labels(
+    body: Json<CreateLabelsInput>,
+    cfg: &State<ServerConfig>,
+    _auth: Moderator,
+    db: DbConn,
+) -> Result<Json<CreateLabelsOutput>, ApiError> {
+    let CreateLabelsInput { labels } = body.into_inner();
+    if labels.is_empty() {
+        return Err(ApiError::InvalidRequest(
+            "At least one label is required".to_string(),
+        ));
+    }
+    if let Some(error) = labels
+        .iter()
+        .find_map(|label| labeler::validate_label(label).err())
+    {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    match labeler::create_labels(cfg.service.did.clone(), labels, &db).await {
+        Ok(labels) => Ok(Json(CreateLabelsOutput { labels })),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::delete;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::DeleteWebhookInput;
+
+async fn inner_delete_webhook(body: DeleteWebhookInput, db: DbConn) -> Result<usize> {
+    use crate::schema::pds::webhook::dsl as WebhookSchema;
+
+    let DeleteWebhookInput { id } = body;
+    // dead letters go with it through the foreign key
+    Ok(db
+        .run(move |conn| {
+            delete(WebhookSchema::webhook)
+                .filter(WebhookSchema::id.eq(id))
+                .execute(conn)
+        })
+        .await?)
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.deleteWebhook",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn delete_webhook(
+    body: Json<DeleteWebhookInput>,
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<(), ApiError> {
+    match inner_delete_webhook(body.into_inner(), db).await {
+        Ok(0) => Err(ApiError::InvalidRequest("Webhook not found".to_string())),
+        Ok(_) => Ok(()),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::SharedSequencer;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::GetLatestSeqOutput;
+
+async fn inner_get_latest_seq(sequencer: &State<SharedSequencer>) -> Result<GetLatestSeqOutput> {
+    let seq = sequencer.sequencer.read().await.curr().await?;
+    Ok(GetLatestSeqOutput { seq })
+}
+
+/// Returns the most recent sequence number on the firehose, for operators checking how far a
+/// consumer has fallen behind.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.admin.getLatestSeq")]
+pub async fn get_latest_seq(
+    sequencer: &State<SharedSequencer>,
+    _auth: AdminToken,
+) -> Result<Json<GetLatestSeqOutput>, ApiError> {
+    match inner_get_latest_seq(sequencer).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::flags::SharedFlagStore;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::RuntimeFlags;
+
+/// Returns the runtime flags currently in effect.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.admin.getRuntimeFlags")]
+pub async fn get_runtime_flags(
+    flags: &State<SharedFlagStore>,
+    _auth: AdminToken,
+) -> Result<Json<RuntimeFlags>, ApiError> {
+    Ok(Json(flags.get()))
+}
@@ -1,11 +1,10 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::Moderator;
 use crate::db::DbConn;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use futures::try_join;
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
@@ -18,7 +17,7 @@ async fn inner_get_subject_status(
     did: Option<String>,
     uri: Option<String>,
     blob: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<SubjectStatus> {
@@ -27,11 +26,7 @@ async fn inner_get_subject_status(
         match did {
             None => bail!("Must provide a did to request blob state"),
             Some(did) => {
-                let actor_store = ActorStore::new(
-                    did.clone(),
-                    S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-                    db,
-                );
+                let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
 
                 let takedown = actor_store
                     .blob
@@ -46,6 +41,7 @@ async fn inner_get_subject_status(
                         }),
                         takedown: Some(takedown),
                         deactivated: None,
+                        suspended: None,
                     });
                 }
             }
@@ -57,7 +53,7 @@ async fn inner_get_subject_status(
         {
             let actor_store = ActorStore::new(
                 uri_hostname.to_string(),
-                S3BlobStore::new(uri_hostname.to_string(), s3_config.inner().clone()),
+                blob_store.for_did(uri_hostname.to_string()),
                 db,
             );
             let (takedown, cid) = try_join!(
@@ -72,6 +68,7 @@ async fn inner_get_subject_status(
                     }),
                     takedown: Some(takedown),
                     deactivated: None,
+                    suspended: None,
                 });
             }
         }
@@ -82,6 +79,7 @@ async fn inner_get_subject_status(
                 subject: Subject::RepoRef(RepoRef { did }),
                 takedown: Some(status.takedown),
                 deactivated: Some(status.deactivated),
+                suspended: Some(status.suspended),
             });
         }
     } else {
@@ -99,12 +97,12 @@ pub async fn get_subject_status(
     did: Option<String>,
     uri: Option<String>,
     blob: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     _auth: Moderator,
     account_manager: AccountManager,
 ) -> Result<Json<SubjectStatus>, ApiError> {
-    match inner_get_subject_status(did, uri, blob, s3_config, db, account_manager).await {
+    match inner_get_subject_status(did, uri, blob, blob_store, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
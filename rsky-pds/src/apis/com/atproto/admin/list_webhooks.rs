@@ -0,0 +1,63 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use crate::models::Webhook;
+use anyhow::Result;
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::{ListWebhooksOutput, WebhookView};
+use std::collections::HashMap;
+
+async fn inner_list_webhooks(db: DbConn) -> Result<ListWebhooksOutput> {
+    use crate::schema::pds::webhook::dsl as WebhookSchema;
+    use crate::schema::pds::webhook_dead_letter::dsl as DeadLetterSchema;
+
+    let (webhooks, dead_letters) = db
+        .run(|conn| {
+            let webhooks = WebhookSchema::webhook
+                .order(WebhookSchema::createdAt.asc())
+                .select(Webhook::as_select())
+                .load::<Webhook>(conn)?;
+            let dead_letters = DeadLetterSchema::webhook_dead_letter
+                .group_by(DeadLetterSchema::webhookId)
+                .select((DeadLetterSchema::webhookId, count_star()))
+                .load::<(String, i64)>(conn)?;
+            Ok::<_, diesel::result::Error>((webhooks, dead_letters))
+        })
+        .await?;
+    let dead_letters = dead_letters.into_iter().collect::<HashMap<String, i64>>();
+
+    Ok(ListWebhooksOutput {
+        webhooks: webhooks
+            .into_iter()
+            .map(|webhook| WebhookView {
+                dead_letters: dead_letters.get(&webhook.id).copied().unwrap_or(0),
+                id: webhook.id,
+                url: webhook.url,
+                collections: webhook.collections,
+                dids: webhook.dids,
+                cursor: webhook.cursor,
+                attempts: webhook.attempts,
+                next_attempt_at: webhook.next_attempt_at,
+                last_error: webhook.last_error,
+                created_at: webhook.created_at,
+            })
+            .collect(),
+    })
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.admin.listWebhooks")]
+pub async fn list_webhooks(
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<ListWebhooksOutput>, ApiError> {
+    match inner_list_webhooks(db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -1,12 +1,25 @@
+pub mod check_chain_consistency;
+pub mod compact_repo;
+pub mod create_labels;
 pub mod delete_account;
+pub mod delete_webhook;
 pub mod disable_account_invites;
 pub mod disable_invite_codes;
 pub mod enable_account_invites;
 pub mod get_account_info;
 pub mod get_invite_codes;
+pub mod get_latest_seq;
+pub mod get_runtime_flags;
 pub mod get_subject_status;
+pub mod list_webhooks;
+pub mod query_audit_log;
+pub mod register_webhook;
+pub mod replay_events;
+pub mod rotate_jwt_key;
 pub mod send_email;
 pub mod update_account_email;
 pub mod update_account_handle;
 pub mod update_account_password;
+pub mod update_runtime_flags;
 pub mod update_subject_status;
+pub mod whoami;
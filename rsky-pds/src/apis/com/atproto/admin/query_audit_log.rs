@@ -0,0 +1,139 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use crate::models::AuditLog;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::com::atproto::admin::{AuditLogEntry, QueryAuditLogOutput};
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+
+pub struct AuditLogQuery {
+    pub did: Option<String>,
+    pub action: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: i64,
+    pub cursor: Option<i64>,
+}
+
+/// Timestamps are stored in RFC3339_VARIANT, so bounds are normalized to it to
+/// compare as text.
+fn parse_bound(name: &str, value: Option<String>) -> Result<Option<String>, ApiError> {
+    value
+        .map(|value| match DateTime::parse_from_rfc3339(&value) {
+            Ok(dt) => Ok(format!(
+                "{}",
+                dt.with_timezone(&Utc).format(RFC3339_VARIANT)
+            )),
+            Err(_) => Err(ApiError::InvalidRequest(format!(
+                "{name} must be an RFC3339 datetime"
+            ))),
+        })
+        .transpose()
+}
+
+async fn inner_query_audit_log(query: AuditLogQuery, db: DbConn) -> Result<QueryAuditLogOutput> {
+    use crate::schema::pds::audit_log::dsl as AuditLogSchema;
+
+    let limit = query.limit;
+    let rows = db
+        .run(move |conn| {
+            let mut builder = AuditLogSchema::audit_log
+                .select(AuditLog::as_select())
+                .order(AuditLogSchema::id.desc())
+                .limit(limit)
+                .into_boxed();
+            if let Some(did) = query.did {
+                builder = builder.filter(
+                    AuditLogSchema::subjectDid
+                        .eq(did.clone())
+                        .or(AuditLogSchema::actorDid.eq(did)),
+                );
+            }
+            if let Some(action) = query.action {
+                builder = builder.filter(AuditLogSchema::action.eq(action));
+            }
+            if let Some(since) = query.since {
+                builder = builder.filter(AuditLogSchema::createdAt.ge(since));
+            }
+            if let Some(until) = query.until {
+                builder = builder.filter(AuditLogSchema::createdAt.lt(until));
+            }
+            if let Some(cursor) = query.cursor {
+                builder = builder.filter(AuditLogSchema::id.lt(cursor));
+            }
+            builder.load::<AuditLog>(conn)
+        })
+        .await?;
+
+    let cursor = match rows.len() as i64 == limit {
+        true => rows.last().map(|row| row.id.to_string()),
+        false => None,
+    };
+    Ok(QueryAuditLogOutput {
+        cursor,
+        entries: rows
+            .into_iter()
+            .map(|row| AuditLogEntry {
+                id: row.id,
+                action: row.action,
+                actor_did: row.actor_did,
+                subject_did: row.subject_did,
+                subject_uri: row.subject_uri,
+                subject_cid: row.subject_cid,
+                detail: row
+                    .detail
+                    .and_then(|detail| serde_json::from_str(&detail).ok()),
+                created_at: row.created_at,
+            })
+            .collect(),
+    })
+}
+
+/// Lists audit log entries newest first. `did` matches both the account acted
+/// on and the account that acted, `since` is inclusive and `until` exclusive.
+#[tracing::instrument(skip_all)]
+#[rocket::get(
+    "/xrpc/com.atproto.admin.queryAuditLog?<did>&<action>&<since>&<until>&<limit>&<cursor>"
+)]
+pub async fn query_audit_log(
+    did: Option<String>,
+    action: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<QueryAuditLogOutput>, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::InvalidRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    let cursor = match cursor.map(|cursor| cursor.parse::<i64>()).transpose() {
+        Ok(cursor) => cursor,
+        Err(_) => return Err(ApiError::InvalidRequest("Malformed cursor".to_string())),
+    };
+    let query = AuditLogQuery {
+        did: did.map(|did| did.to_lowercase()),
+        action,
+        since: parse_bound("since", since)?,
+        until: parse_bound("until", until)?,
+        limit,
+        cursor,
+    };
+    match inner_query_audit_log(query, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use crate::jetstream::JetstreamFilter;
+use crate::models::Webhook;
+use crate::SharedSequencer;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_common::get_random_str;
+use rsky_lexicon::com::atproto::admin::{RegisterWebhookInput, RegisterWebhookOutput};
+use url::Url;
+
+async fn inner_register_webhook(
+    body: RegisterWebhookInput,
+    sequencer: &State<SharedSequencer>,
+    db: DbConn,
+) -> Result<RegisterWebhookOutput> {
+    use crate::schema::pds::webhook::dsl as WebhookSchema;
+
+    let RegisterWebhookInput {
+        url,
+        collections,
+        dids,
+    } = body;
+    // only events sequenced from now on are delivered
+    let cursor = sequencer.sequencer.read().await.curr().await?.unwrap_or(0);
+    let webhook = Webhook {
+        id: get_random_str(),
+        url,
+        secret: get_random_str(),
+        collections,
+        dids,
+        cursor,
+        attempts: 0,
+        next_attempt_at: None,
+        last_error: None,
+        created_at: rsky_common::now(),
+    };
+    let output = RegisterWebhookOutput {
+        id: webhook.id.clone(),
+        secret: webhook.secret.clone(),
+    };
+    db.run(move |conn| {
+        insert_into(WebhookSchema::webhook)
+            .values(webhook)
+            .execute(conn)
+    })
+    .await?;
+    Ok(output)
+}
+
+/// Registers an endpoint that gets an HMAC signed POST for every repo event
+/// matching its collection and DID filters.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.registerWebhook",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn register_webhook(
+    body: Json<RegisterWebhookInput>,
+    sequencer: &State<SharedSequencer>,
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<RegisterWebhookOutput>, ApiError> {
+    let body = body.into_inner();
+    match Url::parse(&body.url) {
+        Ok(url) if url.scheme() == "https" && url.host().is_some() => (),
+        _ => {
+            return Err(ApiError::InvalidRequest(
+                "Webhook url must be an https url".to_string(),
+            ))
+        }
+    }
+    if let Err(error) = JetstreamFilter::new(body.collections.clone(), body.dids.clone()) {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    match inner_register_webhook(body, sequencer, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::sequencer::ReplayCommitsOpts;
+use crate::SharedSequencer;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::{ReplayEventsInput, ReplayEventsOutput};
+
+const MAX_LIMIT: i64 = 1000;
+
+async fn inner_replay_events(
+    body: ReplayEventsInput,
+    sequencer: &State<SharedSequencer>,
+) -> Result<ReplayEventsOutput> {
+    let ReplayEventsInput {
+        did,
+        from_seq,
+        to_seq,
+        limit,
+    } = body;
+    let limit = limit.unwrap_or(MAX_LIMIT);
+
+    let replayed = sequencer
+        .sequencer
+        .write()
+        .await
+        .replay_commits(ReplayCommitsOpts {
+            did: did.clone(),
+            from_seq,
+            to_seq,
+            limit,
+        })
+        .await?;
+    tracing::info!(
+        "Replayed {} commit events (did: {did:?}, from: {from_seq:?}, to: {to_seq:?})",
+        replayed.replayed.len()
+    );
+    Ok(ReplayEventsOutput {
+        replayed: replayed.replayed.len(),
+        cursor: replayed.cursor,
+    })
+}
+
+/// Re-emits commit events for a repo and/or a seq range onto the firehose, to
+/// recover downstream indexers after a bug or data loss on their side.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.replayEvents",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn replay_events(
+    body: Json<ReplayEventsInput>,
+    sequencer: &State<SharedSequencer>,
+    _auth: AdminToken,
+) -> Result<Json<ReplayEventsOutput>, ApiError> {
+    let body = body.into_inner();
+    if body.did.is_none() && body.from_seq.is_none() {
+        return Err(ApiError::InvalidRequest(
+            "Must provide a did or a fromSeq to replay from".to_string(),
+        ));
+    }
+    if let Some(limit) = body.limit {
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(ApiError::InvalidRequest(format!(
+                "limit must be between 1 and {MAX_LIMIT}"
+            )));
+        }
+    }
+    match inner_replay_events(body, sequencer).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::account_manager::helpers::jwt_keys::{load_stored_keys, rotate_stored_key, JWT_KEYS};
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::db::DbConn;
+use chrono::{DateTime, Utc};
+use rocket::serde::json::Json;
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::com::atproto::admin::{JwtKeyView, RotateJwtKeyOutput};
+use std::time::SystemTime;
+
+fn format_time(time: SystemTime) -> String {
+    format!("{}", DateTime::<Utc>::from(time).format(RFC3339_VARIANT))
+}
+
+/// Switches the key access and refresh tokens are signed with to a freshly
+/// generated one, stored in the database for every replica to load. It starts
+/// signing once they all have, and tokens signed with the previous key keep
+/// working for PDS_JWT_KEY_GRACE_PERIOD_SECS, so sessions move over as they
+/// refresh.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/com.atproto.admin.rotateJwtKey")]
+pub async fn rotate_jwt_key(
+    _auth: AdminToken,
+    db: DbConn,
+) -> Result<Json<RotateJwtKeyOutput>, ApiError> {
+    let rotated = db
+        .run(|conn| {
+            let next = rotate_stored_key(conn)?;
+            JWT_KEYS.load(load_stored_keys(conn)?);
+            Ok::<_, anyhow::Error>(next)
+        })
+        .await;
+    let next = match rotated {
+        Ok(next) => next,
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: failed to rotate JWT key: {error}");
+            return Err(ApiError::RuntimeError);
+        }
+    };
+    let current = JWT_KEYS.current();
+    let keys = JWT_KEYS
+        .keys()
+        .into_iter()
+        .map(|key| JwtKeyView {
+            current: key.kid == current.kid,
+            active_at: key.active_at.map(format_time),
+            retired_at: key.retired_at.map(format_time),
+            verifiable_until: JWT_KEYS.verifiable_until(&key).map(format_time),
+            kid: key.kid,
+        })
+        .collect();
+    Ok(Json(RotateJwtKeyOutput {
+        kid: next.kid,
+        keys,
+    }))
+}
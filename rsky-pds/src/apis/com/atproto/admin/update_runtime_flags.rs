@@ -0,0 +1,25 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use crate::flags::SharedFlagStore;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::admin::{RuntimeFlags, UpdateRuntimeFlagsInput};
+
+/// Changes runtime flags without a restart. Flags left out keep their value,
+/// and the flags in effect afterwards are returned.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.admin.updateRuntimeFlags",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn update_runtime_flags(
+    body: Json<UpdateRuntimeFlagsInput>,
+    flags: &State<SharedFlagStore>,
+    _auth: AdminToken,
+) -> Result<Json<RuntimeFlags>, ApiError> {
+    match flags.update(body.into_inner()) {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(ApiError::InvalidRequest(error.to_string())),
+    }
+}
@@ -1,23 +1,27 @@
+use crate::account_manager::helpers::audit::{AuditAction, AuditEvent};
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::Moderator;
 use crate::db::DbConn;
 use crate::SharedSequencer;
-use anyhow::Result;
-use aws_sdk_s3::Config;
+use anyhow::{bail, Result};
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_lexicon::com::atproto::admin::{Subject, SubjectStatus, UpdateSubjectStatusOutput};
+use rsky_lexicon::com::atproto::admin::{
+    StatusAttr, Subject, SubjectStatus, SuspensionAttr, UpdateSubjectStatusOutput,
+};
 use rsky_syntax::aturi::AtUri;
+use serde_json::json;
 use std::str::FromStr;
 
 async fn inner_update_subject_status(
     body: Json<SubjectStatus>,
+    actor_did: Option<String>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<UpdateSubjectStatusOutput> {
@@ -25,50 +29,133 @@ async fn inner_update_subject_status(
         subject,
         takedown,
         deactivated,
+        suspended,
     } = body.into_inner();
 
     if let Some(takedown) = &takedown {
+        let action = match takedown.applied {
+            true => AuditAction::Takedown,
+            false => AuditAction::ReverseTakedown,
+        };
+        let detail = json!({ "ref": takedown.r#ref });
         match &subject {
             Subject::RepoRef(subject) => {
                 account_manager
                     .takedown_account(&subject.did, takedown.clone())
                     .await?;
+                account_manager
+                    .record_audit_event(AuditEvent {
+                        detail: Some(detail),
+                        ..AuditEvent::new(action, actor_did.clone(), subject.did.clone())
+                    })
+                    .await?;
             }
             Subject::StrongRef(subject) => {
                 let subject_at_uri: AtUri = subject.uri.clone().try_into()?;
                 let actor_store = ActorStore::new(
                     subject_at_uri.get_hostname().to_string(),
-                    S3BlobStore::new(subject_at_uri.get_hostname().to_string(), s3_config.inner().clone()),
+                    blob_store.for_did(subject_at_uri.get_hostname().to_string()),
                     db,
                 );
                 actor_store
                     .record
                     .update_record_takedown_status(&subject_at_uri, takedown.clone())
                     .await?;
+                account_manager
+                    .record_audit_event(AuditEvent {
+                        subject_uri: Some(subject.uri.clone()),
+                        subject_cid: Some(subject.cid.clone()),
+                        detail: Some(detail),
+                        ..AuditEvent::new(
+                            action,
+                            actor_did.clone(),
+                            subject_at_uri.get_hostname().to_string(),
+                        )
+                    })
+                    .await?;
             }
             Subject::RepoBlobRef(subject) => {
                 let actor_store = ActorStore::new(
                     subject.did.clone(),
-                    S3BlobStore::new(subject.did.clone(), s3_config.inner().clone()),
+                    blob_store.for_did(subject.did.clone()),
                     db,
                 );
                 actor_store
                     .blob
                     .update_blob_takedown_status(Cid::from_str(&subject.cid)?, takedown.clone())
                     .await?;
+                account_manager
+                    .record_audit_event(AuditEvent {
+                        subject_uri: subject.record_uri.clone(),
+                        subject_cid: Some(subject.cid.clone()),
+                        detail: Some(detail),
+                        ..AuditEvent::new(action, actor_did.clone(), subject.did.clone())
+                    })
+                    .await?;
             }
         }
     }
 
+    let mut suspended_output = None;
+    if let Some(suspended) = suspended {
+        let Subject::RepoRef(subject) = &subject else {
+            bail!("Only accounts can be suspended");
+        };
+        let (action, expires_at) = if suspended.applied {
+            let Some(duration_in_hours) = suspended.duration_in_hours.filter(|hours| *hours > 0)
+            else {
+                bail!("durationInHours is required to suspend an account");
+            };
+            let expires_at = account_manager
+                .suspend_account(&subject.did, suspended.r#ref.clone(), duration_in_hours)
+                .await?;
+            (AuditAction::Suspend, Some(expires_at))
+        } else {
+            account_manager
+                .takedown_account(
+                    &subject.did,
+                    StatusAttr {
+                        applied: false,
+                        r#ref: None,
+                    },
+                )
+                .await?;
+            (AuditAction::Unsuspend, None)
+        };
+        account_manager
+            .record_audit_event(AuditEvent {
+                detail: Some(json!({
+                    "ref": suspended.r#ref,
+                    "durationInHours": suspended.duration_in_hours,
+                    "expiresAt": expires_at,
+                })),
+                ..AuditEvent::new(action, actor_did.clone(), subject.did.clone())
+            })
+            .await?;
+        suspended_output = Some(SuspensionAttr {
+            expires_at,
+            ..suspended
+        });
+    }
+
     if let Some(deactivated) = deactivated {
         if let Subject::RepoRef(subject) = &subject {
-            if deactivated.applied {
+            let action = if deactivated.applied {
                 account_manager
                     .deactivate_account(&subject.did, None)
                     .await?;
+                AuditAction::Deactivate
             } else {
                 account_manager.activate_account(&subject.did).await?;
-            }
+                AuditAction::Reactivate
+            };
+            account_manager
+                .record_audit_event(AuditEvent::new(
+                    action,
+                    actor_did.clone(),
+                    subject.did.clone(),
+                ))
+                .await?;
         }
     }
 
@@ -79,7 +166,11 @@ async fn inner_update_subject_status(
             .await?;
     }
 
-    Ok(UpdateSubjectStatusOutput { subject, takedown })
+    Ok(UpdateSubjectStatusOutput {
+        subject,
+        takedown,
+        suspended: suspended_output,
+    })
 }
 
 #[tracing::instrument(skip_all)]
@@ -91,12 +182,19 @@ async fn inner_update_subject_status(
 pub async fn update_subject_status(
     body: Json<SubjectStatus>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
-    _auth: Moderator,
+    auth: Moderator,
     account_manager: AccountManager,
 ) -> Result<Json<UpdateSubjectStatusOutput>, ApiError> {
-    match inner_update_subject_status(body, sequencer, s3_config, db, account_manager).await {
+    // Moderation services act under their own DID, the admin password has none
+    let actor_did = auth
+        .access
+        .credentials
+        .and_then(|credentials| credentials.iss);
+    match inner_update_subject_status(body, actor_did, sequencer, blob_store, db, account_manager)
+        .await
+    {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
@@ -0,0 +1,22 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AdminToken;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::admin::WhoamiOutput;
+
+/// Reports who an admin request was authenticated as, so an operator can
+/// check a service's JWTs are accepted before relying on them.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.admin.whoami")]
+pub async fn whoami(auth: AdminToken) -> Result<Json<WhoamiOutput>, ApiError> {
+    let did = auth
+        .access
+        .credentials
+        .and_then(|credentials| credentials.iss);
+    Ok(Json(WhoamiOutput {
+        auth_type: match did {
+            Some(_) => "service".to_string(),
+            None => "password".to_string(),
+        },
+        did,
+    }))
+}
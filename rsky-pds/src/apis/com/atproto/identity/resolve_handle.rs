@@ -1,11 +1,15 @@
 use crate::account_manager::helpers::account::ActorAccount;
 use crate::account_manager::AccountManager;
 use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::flags::SharedFlagStore;
+use crate::handle::{base_normalize_and_validate, is_service_domain};
+use crate::plc::web5_types::get_didoc_from_chain;
 use crate::{SharedIdResolver, APP_USER_AGENT};
-use anyhow::{bail, Result};
+use anyhow::Result;
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_common::env::{env_list, env_str};
+use rsky_common::env::env_str;
 use rsky_lexicon::com::atproto::identity::ResolveHandleOutput;
 
 async fn try_resolve_from_app_view(handle: &String) -> Result<Option<String>> {
@@ -36,57 +40,97 @@ async fn try_resolve_from_app_view(handle: &String) -> Result<Option<String>> {
     }
 }
 
-async fn inner_resolve_handle(
-    handle: String,
-    id_resolver: &State<SharedIdResolver>,
-    account_manager: AccountManager,
-) -> Result<ResolveHandleOutput> {
-    // @TODO: Implement normalizeAndEnsureValidHandle()
-    let mut did: Option<String> = None;
-    let user: Option<ActorAccount> = account_manager.get_account(&handle, None).await?;
-
-    match user {
-        Some(user) => did = Some(user.did),
-        None => {
-            let supported_handle = env_list("PDS_SERVICE_HANDLE_DOMAINS")
-                .iter()
-                .any(|host| handle.ends_with(host.as_str()) || handle == host[1..]);
-            // this should be in our DB & we couldn't find it, so fail
-            if supported_handle {
-                bail!("unable to resolve handle");
+/// Whether what's published about a local handle agrees with the DB. A web5
+/// account's DID doc cell has to name the handle once it's written, and a
+/// handle outside the service domains has to still point at the account
+/// through DNS or its well-known file.
+async fn is_consistent_local_handle(
+    handle: &str,
+    user: &ActorAccount,
+    cfg: &ServerConfig,
+    chain_strict: bool,
+    id_resolver: &SharedIdResolver,
+) -> Result<bool, ApiError> {
+    if let Some(ref ckb_addr) = user.ckb_address {
+        match get_didoc_from_chain(ckb_addr).await {
+            Ok(didoc) => {
+                let chain_handle = didoc
+                    .also_known_as
+                    .first()
+                    .and_then(|aka| aka.strip_prefix("at://"))
+                    .map(str::to_lowercase);
+                if chain_handle.as_deref() != Some(handle) {
+                    return Ok(false);
+                }
             }
+            // Nothing on chain yet, so the DB is all there is to go on
+            Err(ApiError::CkbAddrNoCell | ApiError::CkbDidocCellNotFound) => {}
+            Err(error) if !chain_strict => tracing::warn!(
+                "Chain strict mode is off, resolving {handle} from the DB: {error:?}"
+            ),
+            Err(error) => return Err(error),
         }
     }
-
-    // this is not someone on our server, but we help with resolving anyway
-    // @TODO: Weird error about Tokio received when this fails that leads to panic
-    if did.is_none() && env_str("PDS_BSKY_APP_VIEW_URL").is_some() {
-        did = try_resolve_from_app_view(&handle).await?;
-    }
-
-    if did.is_none() {
-        let mut lock = id_resolver.id_resolver.write().await;
-        did = lock.handle.resolve(&handle).await?;
+    if !is_service_domain(handle, &cfg.identity.service_handle_domains) {
+        let resolved = id_resolver
+            .resolve_handle_expecting(handle, &user.did)
+            .await;
+        return Ok(resolved.as_deref() == Some(user.did.as_str()));
     }
+    Ok(true)
+}
 
+async fn inner_resolve_handle(
+    handle: String,
+    cfg: &ServerConfig,
+    chain_strict: bool,
+    id_resolver: &SharedIdResolver,
+    account_manager: AccountManager,
+) -> Result<ResolveHandleOutput, ApiError> {
+    let handle = base_normalize_and_validate(&handle)
+        .map_err(|error| ApiError::InvalidRequest(error.message))?;
+    let did = match account_manager.get_account(&handle, None).await? {
+        Some(user) => {
+            match is_consistent_local_handle(&handle, &user, cfg, chain_strict, id_resolver).await?
+            {
+                true => Some(user.did),
+                false => None,
+            }
+        }
+        // this should be in our DB & we couldn't find it, so fail
+        None if is_service_domain(&handle, &cfg.identity.service_handle_domains) => None,
+        // this is not someone on our server, but we help with resolving anyway
+        None => match try_resolve_from_app_view(&handle).await? {
+            Some(did) => Some(did),
+            None => id_resolver.resolve_handle(&handle).await,
+        },
+    };
     match did {
-        None => bail!("unable to resolve handle"),
+        None => Err(ApiError::InvalidRequest(
+            "Unable to resolve handle".to_string(),
+        )),
         Some(did) => Ok(ResolveHandleOutput { did }),
     }
 }
 
+/// Resolves a handle to a DID. Handles of local accounts come from the DB,
+/// checked against the chain and DNS, and other handles are looked up through
+/// the app view or DNS and the well-known file, cached by the resolver.
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.identity.resolveHandle?<handle>")]
 pub async fn resolve_handle(
     handle: String,
+    cfg: &State<ServerConfig>,
+    flags: &State<SharedFlagStore>,
     id_resolver: &State<SharedIdResolver>,
     account_manager: AccountManager,
 ) -> Result<Json<ResolveHandleOutput>, ApiError> {
-    match inner_resolve_handle(handle, id_resolver, account_manager).await {
+    let chain_strict = flags.get().chain_strict;
+    match inner_resolve_handle(handle, cfg, chain_strict, id_resolver, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
-            tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            tracing::error!("@LOG: ERROR: {error:?}");
+            Err(error)
         }
     }
 }
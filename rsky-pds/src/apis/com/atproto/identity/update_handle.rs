@@ -1,17 +1,15 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::apis::com::atproto::server::get_keys_from_private_key_str;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardCheckTakedown;
 use crate::config::ServerConfig;
 use crate::handle::{normalize_and_validate_handle, HandleValidationContext, HandleValidationOpts};
-use crate::{plc, SharedIdResolver, SharedSequencer};
+use crate::identity::{DidSubject, SharedDidMethods};
+use crate::{SharedIdResolver, SharedSequencer};
 use anyhow::{bail, Result};
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_common::env::env_str;
 use rsky_lexicon::com::atproto::identity::UpdateHandleInput;
-use std::env;
 
 #[tracing::instrument(skip_all)]
 async fn inner_update_handle(
@@ -19,6 +17,7 @@ async fn inner_update_handle(
     sequencer: &State<SharedSequencer>,
     server_config: &State<ServerConfig>,
     id_resolver: &State<SharedIdResolver>,
+    did_methods: &State<SharedDidMethods>,
     auth: AccessStandardCheckTakedown,
     account_manager: AccountManager,
 ) -> Result<()> {
@@ -50,12 +49,25 @@ async fn inner_update_handle(
         Some(account) if account.did != requester => bail!("Handle already taken: {handle}"),
         Some(_) => (),
         None => {
-            let plc_url = env_str("PDS_DID_PLC_URL").unwrap_or("https://plc.directory".to_owned());
-            let plc_client = plc::Client::new(plc_url);
-            let private_key = env::var("PDS_PLC_ROTATION_KEY_K256_PRIVATE_KEY_HEX").unwrap();
-            let (signing_key, _) = get_keys_from_private_key_str(private_key)?;
-            plc_client
-                .update_handle(&requester, &signing_key, &handle)
+            let ckb_address = account_manager
+                .get_account(
+                    &requester,
+                    Some(AvailabilityFlags {
+                        include_deactivated: Some(true),
+                        include_taken_down: None,
+                    }),
+                )
+                .await?
+                .and_then(|account| account.ckb_address);
+            did_methods
+                .for_did(&requester)?
+                .update_handle(
+                    DidSubject {
+                        did: &requester,
+                        ckb_address: ckb_address.as_deref(),
+                    },
+                    &handle,
+                )
                 .await?;
             account_manager.update_handle(&requester, &handle).await?;
         }
@@ -89,6 +101,7 @@ pub async fn update_handle(
     sequencer: &State<SharedSequencer>,
     server_config: &State<ServerConfig>,
     id_resolver: &State<SharedIdResolver>,
+    did_methods: &State<SharedDidMethods>,
     auth: AccessStandardCheckTakedown,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
@@ -97,6 +110,7 @@ pub async fn update_handle(
         sequencer,
         server_config,
         id_resolver,
+        did_methods,
         auth,
         account_manager,
     )
@@ -0,0 +1,2 @@
+pub mod query_labels;
+pub mod subscribe_labels;
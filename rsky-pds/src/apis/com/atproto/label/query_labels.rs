@@ -0,0 +1,69 @@
+use crate::apis::ApiError;
+use crate::db::DbConn;
+use crate::labeler::{format_label, query_labels as labeler_query_labels, QueryLabelsOpts};
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::label::QueryLabelsOutput;
+
+async fn inner_query_labels(
+    uri_patterns: Vec<String>,
+    sources: Vec<String>,
+    limit: i64,
+    cursor: Option<i64>,
+    db: DbConn,
+) -> Result<QueryLabelsOutput> {
+    let rows = labeler_query_labels(
+        QueryLabelsOpts {
+            uri_patterns,
+            sources,
+            limit,
+            cursor,
+        },
+        &db,
+    )
+    .await?;
+    let cursor = match rows.last() {
+        Some(last) if rows.len() as i64 == limit => Some(last.seq.to_string()),
+        _ => None,
+    };
+    Ok(QueryLabelsOutput {
+        cursor,
+        labels: rows.into_iter().map(format_label).collect(),
+    })
+}
+
+/// Find labels relevant to the provided AT-URI patterns. Patterns are exact uris, or prefixes
+/// ending in `*`. Expired labels are not returned.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.label.queryLabels?<uriPatterns>&<sources>&<limit>&<cursor>")]
+#[allow(non_snake_case)]
+pub async fn query_labels(
+    uriPatterns: Vec<String>,
+    sources: Vec<String>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<Json<QueryLabelsOutput>, ApiError> {
+    if uriPatterns.is_empty() {
+        return Err(ApiError::InvalidRequest(
+            "uriPatterns is required".to_string(),
+        ));
+    }
+    let limit = limit.unwrap_or(50);
+    if !(1..=250).contains(&limit) {
+        return Err(ApiError::InvalidRequest(
+            "limit must be between 1 and 250".to_string(),
+        ));
+    }
+    let cursor = match cursor.map(|cursor| cursor.parse::<i64>()).transpose() {
+        Ok(cursor) => cursor,
+        Err(_) => return Err(ApiError::InvalidRequest("Malformed cursor".to_string())),
+    };
+    match inner_query_labels(uriPatterns, sources, limit, cursor, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::db::{DbConn, DbConnPool};
+use crate::labeler::{format_label, labels_after, latest_label_seq};
+use crate::models;
+use crate::xrpc_server::stream::frames::{ErrorFrame, Frame, MessageFrame, MessageFrameOpts};
+use crate::xrpc_server::stream::types::ErrorFrameBody;
+use anyhow::{anyhow, bail, Result};
+use futures::{pin_mut, StreamExt};
+use rocket::tokio::select;
+use rocket::{Orbit, Rocket, Shutdown};
+use rsky_lexicon::com::atproto::label::SubscribeLabels;
+use tokio::time::{interval, Duration as TokioDuration};
+use ws::Message;
+
+const POLL_BATCH_SIZE: i64 = 500;
+
+/// Reads labels on a pooled connection that's handed back right away, so an
+/// idle subscriber doesn't tie up a database connection between polls
+async fn poll_labels(pool: &Option<DbConnPool>, cursor: i64) -> Result<Vec<models::Label>> {
+    let db = get_conn(pool).await?;
+    db.run(move |conn| labels_after(conn, cursor, POLL_BATCH_SIZE))
+        .await
+}
+
+async fn get_latest_seq(pool: &Option<DbConnPool>) -> Result<Option<i64>> {
+    let db = get_conn(pool).await?;
+    db.run(latest_label_seq).await
+}
+
+async fn get_conn(pool: &Option<DbConnPool>) -> Result<DbConn> {
+    let Some(pool) = pool else {
+        bail!("The database pool isn't set up");
+    };
+    pool.get()
+        .await
+        .ok_or_else(|| anyhow!("Timed out waiting for a database connection"))
+}
+
+/// Subscribe to the stream of labels (and negations) issued by this service. Each label is
+/// sent as its own `#labels` frame carrying the label's seq, which clients pass back as
+/// `cursor` to resume. Without a cursor the stream starts at the next label issued.
+#[rocket::get("/xrpc/com.atproto.label.subscribeLabels?<cursor>")]
+pub async fn subscribe_labels(
+    cursor: Option<i64>,
+    mut shutdown: Shutdown,
+    rocket: &Rocket<Orbit>,
+    ws: ws::WebSocket,
+) -> ws::Stream!['static] {
+    let pool = DbConnPool::of(rocket);
+    ws::Stream! { ws =>
+        tracing::debug!("@LOG DEBUG: request to com.atproto.label.subscribeLabels; Cursor={cursor:?}");
+        let latest = match get_latest_seq(&pool).await {
+            Ok(latest) => latest.unwrap_or(0),
+            Err(_) => {
+                let error_frame = ErrorFrame::new(ErrorFrameBody {
+                    error: "CurrError".to_string(),
+                    message: Some("Failed to fetch current label.".to_string()),
+                });
+                yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                return;
+            }
+        };
+        let mut last_seq = match cursor {
+            Some(cursor) if cursor > latest => {
+                let error_frame = ErrorFrame::new(ErrorFrameBody {
+                    error: "FutureCursor".to_string(),
+                    message: Some("Cursor in the future.".to_string()),
+                });
+                yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                return;
+            },
+            Some(cursor) => cursor,
+            None => latest,
+        };
+
+        pin_mut!(ws);
+        let mut poll_interval = interval(TokioDuration::from_secs(1));
+        let mut ping_interval = interval(TokioDuration::from_secs(30));
+
+        loop {
+            select! {
+                _ = poll_interval.tick() => {
+                    let rows = match poll_labels(&pool, last_seq).await {
+                        Ok(rows) => rows,
+                        Err(_) => {
+                            let error_frame = ErrorFrame::new(ErrorFrameBody {
+                                error: "EventStreamError".to_string(),
+                                message: Some("Failed to fetch labels.".to_string()),
+                            });
+                            yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                            break;
+                        }
+                    };
+                    let mut failed = false;
+                    for row in rows {
+                        let seq = row.seq;
+                        let subscribe_labels_evt = SubscribeLabels {
+                            seq,
+                            labels: vec![format_label(row)],
+                        };
+                        let message_frame = MessageFrame::new(subscribe_labels_evt, Some(MessageFrameOpts { r#type: Some("#labels".to_string()) }));
+                        match message_frame.to_bytes() {
+                            Ok(binary) => yield Message::Binary(binary),
+                            Err(_) => {
+                                failed = true;
+                                break;
+                            }
+                        }
+                        last_seq = seq;
+                    }
+                    if failed {
+                        let error_frame = ErrorFrame::new(ErrorFrameBody {
+                            error: "SerializationError".to_string(),
+                            message: Some("Failed to serialize label to message frame.".to_string()),
+                        });
+                        yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                        break;
+                    }
+                },
+                message = ws.next() => {
+                    match message {
+                        Some(Ok(ws::Message::Close(close_frame))) => {
+                            tracing::info!("Received Close message: {:?}", close_frame);
+                            break;
+                        },
+                        Some(Ok(ws::Message::Ping(payload))) => {
+                            yield ws::Message::Pong(payload);
+                        },
+                        Some(Ok(_)) => (),
+                        Some(Err(err)) => {
+                            tracing::info!("WebSocket error: {:?}", err);
+                            break;
+                        },
+                        None => {
+                            tracing::info!("WebSocket closed.");
+                            break;
+                        }
+                    }
+                },
+                _ = ping_interval.tick() => {
+                    yield ws::Message::Ping(vec![]);
+                },
+                _ = &mut shutdown => break
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 pub mod admin;
 pub mod identity;
+pub mod label;
 pub mod repo;
 pub mod server;
 pub mod sync;
@@ -1,30 +1,37 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::bbs::automod::{apply_hits, check_writes, AutomodRejectedError};
+use crate::bbs::section::SectionArchivedError;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::flags::SharedFlagStore;
+use crate::rate_limit::RateLimit;
 use crate::repo::prepare::{
-    prepare_create, prepare_delete, prepare_update, PrepareCreateOpts, PrepareDeleteOpts,
-    PrepareUpdateOpts,
+    prepare_create, prepare_delete, prepare_update, record_from_json, validation_within,
+    CollectionNotPermittedError, PrepareCreateOpts, PrepareDeleteOpts, PrepareUpdateOpts,
 };
 use crate::SharedSequencer;
+use crate::shutdown::InFlightWrite;
+use crate::xrpc_server::body::{BoundedJson, WRITES_MAX_BYTES};
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use futures::stream::{self, StreamExt};
 use lexicon_cid::Cid;
-use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::repo::{ApplyWritesInput, ApplyWritesInputRefWrite};
+use rsky_lexicon::schema::ValidationError;
 use rsky_repo::types::PreparedWrite;
 use std::str::FromStr;
 
 async fn inner_apply_writes(
-    body: Json<ApplyWritesInput>,
+    body: BoundedJson<ApplyWritesInput, WRITES_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<()> {
@@ -50,7 +57,10 @@ async fn inner_apply_writes(
             bail!("Account is deactivated")
         }
         let did = account.did;
-        if did != auth.access.credentials.unwrap().did.unwrap() {
+        let credentials = auth.access.credentials.unwrap();
+        let collections = credentials.collections;
+        let collections = &collections;
+        if did != credentials.did.unwrap() {
             bail!("AuthRequiredError")
         }
         let did: &String = &did;
@@ -58,8 +68,8 @@ async fn inner_apply_writes(
             bail!("Too many writes. Max: 200")
         }
 
-        let writes: Vec<PreparedWrite> = stream::iter(tx.writes)
-            .then(|write| async move {
+        let writes: Vec<PreparedWrite> = stream::iter(tx.writes.into_iter().enumerate())
+            .then(|(index, write)| async move {
                 Ok::<PreparedWrite, anyhow::Error>(match write {
                     ApplyWritesInputRefWrite::Create(write) => PreparedWrite::Create(
                         prepare_create(PrepareCreateOpts {
@@ -67,10 +77,14 @@ async fn inner_apply_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
+                            record: record_from_json(write.value, validate)?,
                             validate,
+                            collections: collections.clone(),
                         })
-                        .await?,
+                        .await
+                        .map_err(|error| {
+                            validation_within(error, &format!("writes[{index}].value"))
+                        })?,
                     ),
                     ApplyWritesInputRefWrite::Update(write) => PreparedWrite::Update(
                         prepare_update(PrepareUpdateOpts {
@@ -78,10 +92,14 @@ async fn inner_apply_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
+                            record: record_from_json(write.value, validate)?,
                             validate,
+                            collections: collections.clone(),
                         })
-                        .await?,
+                        .await
+                        .map_err(|error| {
+                            validation_within(error, &format!("writes[{index}].value"))
+                        })?,
                     ),
                     ApplyWritesInputRefWrite::Delete(write) => {
                         PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
@@ -89,6 +107,7 @@ async fn inner_apply_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
+                            collections: collections.clone(),
                         })?)
                     }
                 })
@@ -104,11 +123,14 @@ async fn inner_apply_writes(
         };
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+            ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+        let _write_lock = actor_store.lock_writes().await?;
 
+        let hits = check_writes(&actor_store.record.db, &cfg.bbs, did, &writes).await?;
         let commit = actor_store
             .process_writes(writes.clone(), swap_commit_cid)
             .await?;
+        apply_hits(&actor_store.record.db, cfg.service.did.clone(), hits).await;
 
         let mut lock = sequencer.sequencer.write().await;
         lock.sequence_commit(did.clone(), commit.clone()).await?;
@@ -128,19 +150,34 @@ async fn inner_apply_writes(
 #[tracing::instrument(skip_all)]
 #[rocket::post("/xrpc/com.atproto.repo.applyWrites", format = "json", data = "<body>")]
 pub async fn apply_writes(
-    body: Json<ApplyWritesInput>,
+    body: BoundedJson<ApplyWritesInput, WRITES_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    _write: InFlightWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
+    flags.check_writable(body.writes.iter().map(|write| write.collection()))?;
     tracing::debug!("@LOG: debug apply_writes {body:#?}");
-    match inner_apply_writes(body, auth, sequencer, s3_config, db, account_manager).await {
+    match inner_apply_writes(body, auth, sequencer, blob_store, cfg, db, account_manager).await {
         Ok(()) => Ok(()),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            match error.downcast::<ValidationError>() {
+                Ok(error) => Err(ApiError::InvalidRecord(error)),
+                Err(error)
+                    if error.is::<CollectionNotPermittedError>()
+                        || error.is::<AutomodRejectedError>()
+                        || error.is::<SectionArchivedError>() =>
+                {
+                    Err(ApiError::from(error))
+                }
+                Err(_) => Err(ApiError::RuntimeError),
+            }
         }
     }
 }
@@ -1,27 +1,38 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::bbs::automod::{apply_hits, check_writes, AutomodRejectedError};
+use crate::bbs::section::SectionArchivedError;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
-use crate::repo::prepare::{prepare_create, prepare_delete, PrepareCreateOpts, PrepareDeleteOpts};
+use crate::flags::SharedFlagStore;
+use crate::rate_limit::RateLimit;
+use crate::repo::prepare::{
+    prepare_create, prepare_delete, record_from_json, validation_within,
+    CollectionNotPermittedError, PrepareCreateOpts, PrepareDeleteOpts,
+};
 use crate::SharedSequencer;
+use crate::shutdown::InFlightWrite;
+use crate::xrpc_server::body::{BoundedJson, RECORD_MAX_BYTES};
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::repo::{CreateRecordInput, CreateRecordOutput};
+use rsky_lexicon::schema::ValidationError;
 use rsky_repo::types::{PreparedDelete, PreparedWrite};
 use rsky_syntax::aturi::AtUri;
 use std::str::FromStr;
 
 async fn inner_create_record(
-    body: Json<CreateRecordInput>,
+    body: BoundedJson<CreateRecordInput, RECORD_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<CreateRecordOutput> {
@@ -47,7 +58,9 @@ async fn inner_create_record(
             bail!("Account is deactivated")
         }
         let did = account.did;
-        if did != auth.access.credentials.unwrap().did.unwrap() {
+        let credentials = auth.access.credentials.unwrap();
+        let collections = credentials.collections;
+        if did != credentials.did.unwrap() {
             bail!("AuthRequiredError")
         }
         let swap_commit_cid = match swap_commit {
@@ -57,15 +70,18 @@ async fn inner_create_record(
         let write = prepare_create(PrepareCreateOpts {
             did: did.clone(),
             collection: collection.clone(),
-            record: serde_json::from_value(record)?,
+            record: record_from_json(record, validate)?,
             rkey,
             validate,
             swap_cid: None,
+            collections: collections.clone(),
         })
-        .await?;
+        .await
+        .map_err(|error| validation_within(error, "record"))?;
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+            ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+        let _write_lock = actor_store.lock_writes().await?;
         let backlink_conflicts: Vec<AtUri> = match validate {
             Some(true) => {
                 let write_at_uri: AtUri = write.uri.clone().try_into()?;
@@ -85,6 +101,7 @@ async fn inner_create_record(
                     collection: at_uri.get_collection(),
                     rkey: at_uri.get_rkey(),
                     swap_cid: None,
+                    collections: collections.clone(),
                 })
             })
             .collect::<Result<Vec<PreparedDelete>>>()?;
@@ -92,9 +109,11 @@ async fn inner_create_record(
         for delete in backlink_deletions {
             writes.push(PreparedWrite::Delete(delete));
         }
+        let hits = check_writes(&actor_store.record.db, &cfg.bbs, &did, &writes).await?;
         let commit = actor_store
             .process_writes(writes.clone(), swap_commit_cid)
             .await?;
+        apply_hits(&actor_store.record.db, cfg.service.did.clone(), hits).await;
 
         let mut lock = sequencer.sequencer.write().await;
         lock.sequence_commit(did.clone(), commit.clone()).await?;
@@ -118,19 +137,34 @@ async fn inner_create_record(
     data = "<body>"
 )]
 pub async fn create_record(
-    body: Json<CreateRecordInput>,
+    body: BoundedJson<CreateRecordInput, RECORD_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    _write: InFlightWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<CreateRecordOutput>, ApiError> {
+    flags.check_writable([body.collection.as_str()])?;
     tracing::debug!("@LOG: debug create_record {body:#?}");
-    match inner_create_record(body, auth, sequencer, s3_config, db, account_manager).await {
+    match inner_create_record(body, auth, sequencer, blob_store, cfg, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            match error.downcast::<ValidationError>() {
+                Ok(error) => Err(ApiError::InvalidRecord(error)),
+                Err(error)
+                    if error.is::<CollectionNotPermittedError>()
+                        || error.is::<AutomodRejectedError>()
+                        || error.is::<SectionArchivedError>() =>
+                {
+                    Err(ApiError::from(error))
+                }
+                Err(_) => Err(ApiError::RuntimeError),
+            }
         }
     }
 }
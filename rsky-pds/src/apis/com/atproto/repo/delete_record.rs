@@ -1,14 +1,16 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
 use crate::db::DbConn;
-use crate::repo::prepare::{prepare_delete, PrepareDeleteOpts};
+use crate::flags::SharedFlagStore;
+use crate::rate_limit::RateLimit;
+use crate::repo::prepare::{prepare_delete, CollectionNotPermittedError, PrepareDeleteOpts};
 use crate::SharedSequencer;
+use crate::shutdown::InFlightWrite;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
 use rocket::State;
@@ -21,7 +23,7 @@ async fn inner_delete_record(
     body: Json<DeleteRecordInput>,
     auth: AccessStandardIncludeChecks,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<()> {
@@ -46,7 +48,9 @@ async fn inner_delete_record(
         Some(account) if account.deactivated_at.is_some() => bail!("Account is deactivated"),
         Some(account) => {
             let did = account.did;
-            if did != auth.access.credentials.unwrap().did.unwrap() {
+            let credentials = auth.access.credentials.unwrap();
+            let collections = credentials.collections;
+            if did != credentials.did.unwrap() {
                 bail!("AuthRequiredError")
             }
 
@@ -64,9 +68,11 @@ async fn inner_delete_record(
                 collection,
                 rkey,
                 swap_cid: swap_record_cid,
+                collections: collections.clone(),
             })?;
             let mut actor_store =
-                ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+                ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+            let _write_lock = actor_store.lock_writes().await?;
             let write_at_uri: AtUri = write.uri.clone().try_into()?;
             let record = actor_store
                 .record
@@ -101,13 +107,18 @@ async fn inner_delete_record(
 pub async fn delete_record(
     body: Json<DeleteRecordInput>,
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    _write: InFlightWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
-    match inner_delete_record(body, auth, sequencer, s3_config, db, account_manager).await {
+    flags.check_writable([body.collection.as_str()])?;
+    match inner_delete_record(body, auth, sequencer, blob_store, db, account_manager).await {
         Ok(()) => Ok(()),
+        Err(error) if error.is::<CollectionNotPermittedError>() => Err(ApiError::from(error)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
             Err(ApiError::RuntimeError)
@@ -1,11 +1,10 @@
-use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::db::DbConn;
+use crate::hydration::Hydrator;
 use crate::pipethrough::{pipethrough, OverrideOpts, ProxyRequest};
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::repo::GetRecordOutput;
@@ -17,26 +16,31 @@ async fn inner_get_record(
     collection: String,
     rkey: String,
     cid: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     req: ProxyRequest<'_>,
-    account_manager: AccountManager,
+    hydrator: Hydrator,
 ) -> Result<GetRecordOutput> {
-    let did = account_manager.get_did_for_actor(&repo, None).await?;
+    let did = hydrator.account_manager.get_did_for_actor(&repo, None).await?;
 
     // fetch from pds if available, if not then fetch from appview
     if let Some(did) = did {
+        if let Err(error) = hydrator.assert_repo_readable(&did).await {
+            bail!("Could not locate record in `{did}`: {error}");
+        }
         let uri = AtUri::make(did.clone(), Some(collection), Some(rkey))?;
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+            ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
 
         match actor_store.record.get_record(&uri, cid, None).await {
-            Ok(Some(record)) if record.takedown_ref.is_none() => Ok(GetRecordOutput {
-                uri: uri.to_string(),
-                cid: Some(record.cid),
-                value: serde_json::to_value(record.value)?,
-            }),
+            Ok(Some(record)) if hydrator.record_readable(record.takedown_ref.as_ref()) => {
+                Ok(GetRecordOutput {
+                    uri: uri.to_string(),
+                    cid: Some(record.cid),
+                    value: serde_json::to_value(record.value)?,
+                })
+            }
             _ => bail!("Could not locate record: `{uri}`"),
         }
     } else {
@@ -72,20 +76,20 @@ pub async fn get_record(
     collection: String,
     rkey: String,
     cid: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     req: ProxyRequest<'_>,
-    account_manager: AccountManager,
+    hydrator: Hydrator,
 ) -> Result<Json<GetRecordOutput>, ApiError> {
     match inner_get_record(
         repo,
         collection,
         rkey,
         cid,
-        s3_config,
+        blob_store,
         db,
         req,
-        account_manager,
+        hydrator,
     )
     .await
     {
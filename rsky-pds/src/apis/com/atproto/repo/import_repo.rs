@@ -1,26 +1,31 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessFullImport;
 use crate::db::DbConn;
+use crate::plc::web5_types::get_didoc_from_chain;
 use crate::repo::prepare::{
     prepare_create, prepare_delete, prepare_update, PrepareCreateOpts, PrepareDeleteOpts,
     PrepareUpdateOpts,
 };
-use aws_sdk_s3::Config;
+use crate::SharedSequencer;
 use futures::{stream, StreamExt};
 use lexicon_cid::Cid;
 use reqwest::header;
 use rocket::data::{FromData, Outcome, ToByteUnit};
 use rocket::http::Status;
 use rocket::{Data, Request, State};
+use rsky_common::cbor_to_struct;
 use rsky_common::env::env_int;
 use rsky_repo::block_map::BlockMap;
 use rsky_repo::car::{read_stream_car_with_root, CarWithRoot};
 use rsky_repo::parse::get_and_parse_record;
 use rsky_repo::repo::Repo;
 use rsky_repo::sync::consumer::{verify_diff, VerifyRepoInput};
-use rsky_repo::types::{PreparedWrite, RecordWriteDescript, VerifiedDiff};
+use rsky_repo::types::{Commit, PreparedWrite, RecordWriteDescript, VerifiedDiff};
+use rsky_repo::util::verify_commit_sig;
 use std::num::NonZeroU64;
 
 struct ImportRepoInput {
@@ -72,20 +77,64 @@ impl<'r> FromData<'r> for ImportRepoInput {
     }
 }
 
+/// Finds the key in the account's on-chain did doc that signed the imported
+/// root. web5 repos are signed by the user rather than the PDS, so this is the
+/// only key an imported commit can legitimately verify against.
+async fn find_root_signing_key(
+    did: &String,
+    ckb_addr: &str,
+    blocks: &BlockMap,
+    root: Cid,
+) -> Result<String, ApiError> {
+    let commit: Commit = match blocks.get(root) {
+        Some(bytes) => cbor_to_struct(bytes.clone())
+            .map_err(|_| ApiError::InvalidRequest("Root block is not a commit".to_string()))?,
+        None => {
+            return Err(ApiError::InvalidRequest(
+                "Root block is missing from the CAR".to_string(),
+            ))
+        }
+    };
+    if &commit.did != did {
+        return Err(ApiError::InvalidRequest(format!(
+            "Imported repo belongs to {}",
+            commit.did
+        )));
+    }
+    let didoc = get_didoc_from_chain(ckb_addr).await?;
+    didoc
+        .verification_methods
+        .into_values()
+        .find(|key| verify_commit_sig(commit.clone(), key).unwrap_or(false))
+        .ok_or(ApiError::InvalidRequest(
+            "Root commit is not signed by a key in the did doc".to_string(),
+        ))
+}
+
 #[tracing::instrument(skip_all)]
 #[rocket::post("/xrpc/com.atproto.repo.importRepo", data = "<import_repo_input>")]
 pub async fn import_repo(
     auth: AccessFullImport,
     import_repo_input: ImportRepoInput,
-    s3_config: &State<Config>,
+    sequencer: &State<SharedSequencer>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
+    account_manager: AccountManager,
 ) -> Result<(), ApiError> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
-    let mut actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
+    let account = account_manager
+        .get_account(
+            &requester,
+            Some(AvailabilityFlags {
+                include_deactivated: Some(true),
+                include_taken_down: None,
+            }),
+        )
+        .await?
+        .ok_or(ApiError::AccountNotFound)?;
+    let mut actor_store =
+        ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
+    let _write_lock = actor_store.lock_writes().await?;
 
     // Get current repo if it exists
     let curr_root: Option<Cid> = actor_store.get_repo_root().await;
@@ -100,6 +149,12 @@ pub async fn import_repo(
     // Get verified difference from current repo and imported repo
     let mut imported_blocks: BlockMap = car_with_root.blocks;
     let imported_root: Cid = car_with_root.root;
+    let signing_key = match account.ckb_address {
+        Some(ref ckb_addr) => Some(
+            find_root_signing_key(&requester, ckb_addr, &imported_blocks, imported_root).await?,
+        ),
+        None => None,
+    };
     let opts = VerifyRepoInput {
         ensure_leaves: Some(false),
     };
@@ -108,8 +163,8 @@ pub async fn import_repo(
         curr_repo,
         &mut imported_blocks,
         imported_root,
-        None,
-        None,
+        Some(&requester),
+        signing_key.as_ref(),
         Some(opts),
     )
     .await
@@ -117,15 +172,17 @@ pub async fn import_repo(
         Ok(res) => res,
         Err(error) => {
             tracing::error!("{:?}", error);
-            return Err(ApiError::RuntimeError);
+            return Err(ApiError::InvalidRequest(format!(
+                "Could not verify imported repo: {error}"
+            )));
         }
     };
 
     let commit_data = diff.commit;
     let prepared_writes: Vec<PreparedWrite> =
-        prepare_import_repo_writes(requester, diff.writes, &imported_blocks).await?;
+        prepare_import_repo_writes(requester.clone(), diff.writes, &imported_blocks).await?;
     match actor_store
-        .process_import_repo(commit_data, prepared_writes)
+        .process_import_repo(commit_data.clone(), prepared_writes)
         .await
     {
         Ok(_res) => {}
@@ -134,6 +191,14 @@ pub async fn import_repo(
             return Err(ApiError::RuntimeError);
         }
     }
+    account_manager
+        .update_repo_root(requester.clone(), commit_data.cid, commit_data.rev)
+        .await?;
+
+    // let relays pick up the imported repo as a whole rather than as a diff
+    let sync_data = actor_store.get_sync_event_data().await?;
+    let mut lock = sequencer.sequencer.write().await;
+    lock.sequence_sync_evt(requester, sync_data).await?;
 
     Ok(())
 }
@@ -159,6 +224,7 @@ async fn prepare_import_repo_writes(
                                 swap_cid: None,
                                 record: parsed_record.record,
                                 validate: Some(true),
+                                collections: None,
                             })
                             .await?,
                         )
@@ -173,6 +239,7 @@ async fn prepare_import_repo_writes(
                                 swap_cid: None,
                                 record: parsed_record.record,
                                 validate: Some(true),
+                                collections: None,
                             })
                             .await?,
                         )
@@ -183,6 +250,7 @@ async fn prepare_import_repo_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
+                            collections: None,
                         })?)
                     }
                 })
@@ -1,12 +1,9 @@
-use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
-use crate::actor_store::ActorStore;
+use crate::actor_store::record::{collection_prefix, list_records_for_collection};
+use crate::apis::com::atproto::repo::repo_unavailable;
 use crate::apis::ApiError;
-use crate::db::DbConn;
+use crate::hydration::Hydrator;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
-use rocket::State;
 use rsky_lexicon::com::atproto::repo::{ListRecordsOutput, Record};
 use rsky_syntax::aturi::AtUri;
 
@@ -14,7 +11,7 @@ use rsky_syntax::aturi::AtUri;
 async fn inner_list_records(
     // The handle or DID of the repo.
     repo: String,
-    // The NSID of the record type.
+    // The NSID of the record type, or a prefix such as `app.bbs.*`.
     collection: String,
     // The number of records to return.
     limit: u16,
@@ -25,45 +22,52 @@ async fn inner_list_records(
     rkeyEnd: Option<String>,
     // Flag to reverse the order of the returned records.
     reverse: bool,
-    s3_config: &State<Config>,
-    db: DbConn,
-    account_manager: AccountManager,
+    hydrator: Hydrator,
 ) -> Result<ListRecordsOutput> {
     if limit > 100 {
         bail!("Error: limit can not be greater than 100")
     }
-    let did = account_manager.get_did_for_actor(&repo, None).await?;
+    let is_prefix = collection_prefix(&collection)?.is_some();
+    let did = hydrator
+        .account_manager
+        .get_did_for_actor(&repo, None)
+        .await?;
     if let Some(did) = did {
-        let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
-
-        let records: Vec<Record> = actor_store
-            .record
-            .list_records_for_collection(
-                collection,
-                limit as i64,
-                reverse,
-                cursor,
-                rkeyStart,
-                rkeyEnd,
-                None,
-            )
-            .await?
-            .into_iter()
-            .map(|record| {
-                Ok(Record {
-                    uri: record.uri.clone(),
-                    cid: record.cid.clone(),
-                    value: serde_json::to_value(record)?,
-                })
+        hydrator.assert_repo_readable(&did).await?;
+        let records: Vec<Record> = list_records_for_collection(
+            &hydrator.db,
+            did,
+            collection,
+            limit as i64,
+            reverse,
+            cursor,
+            rkeyStart,
+            rkeyEnd,
+            Some(hydrator.include_taken_down),
+        )
+        .await?
+        .into_iter()
+        .map(|record| {
+            Ok(Record {
+                uri: record.uri.clone(),
+                cid: record.cid.clone(),
+                value: serde_json::to_value(record.value)?,
             })
-            .collect::<Result<Vec<Record>>>()?;
+        })
+        .collect::<Result<Vec<Record>>>()?;
 
         let last_record = records.last();
         let cursor: Option<String>;
         if let Some(last_record) = last_record {
             let last_at_uri: AtUri = last_record.uri.clone().try_into()?;
-            cursor = Some(last_at_uri.get_rkey());
+            cursor = match is_prefix {
+                true => Some(format!(
+                    "{}/{}",
+                    last_at_uri.get_rkey(),
+                    last_at_uri.get_collection()
+                )),
+                false => Some(last_at_uri.get_rkey()),
+            };
         } else {
             cursor = None;
         }
@@ -90,31 +94,20 @@ pub async fn list_records(
     rkeyEnd: Option<String>,
     // Flag to reverse the order of the returned records.
     reverse: Option<bool>,
-    s3_config: &State<Config>,
-    db: DbConn,
-    account_manager: AccountManager,
+    hydrator: Hydrator,
 ) -> Result<Json<ListRecordsOutput>, ApiError> {
     let limit = limit.unwrap_or(50);
     let reverse = reverse.unwrap_or(false);
 
     match inner_list_records(
-        repo,
-        collection,
-        limit,
-        cursor,
-        rkeyStart,
-        rkeyEnd,
-        reverse,
-        s3_config,
-        db,
-        account_manager,
+        repo, collection, limit, cursor, rkeyStart, rkeyEnd, reverse, hydrator,
     )
     .await
     {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            Err(repo_unavailable(&error).unwrap_or(ApiError::RuntimeError))
         }
     }
 }
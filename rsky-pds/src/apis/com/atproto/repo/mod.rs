@@ -1,7 +1,17 @@
 use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
 use crate::account_manager::AccountManager;
+use crate::apis::ApiError;
 use anyhow::{bail, Result};
 
+/// XRPC errors `assert_repo_availability` reports, as the `Name: ` prefix of
+/// its messages
+const REPO_UNAVAILABLE_ERRORS: [&str; 4] = [
+    "RepoNotFound",
+    "RepoTakendown",
+    "RepoSuspended",
+    "RepoDeactivated",
+];
+
 pub async fn assert_repo_availability(
     did: &String,
     is_admin_of_self: bool,
@@ -22,6 +32,9 @@ pub async fn assert_repo_availability(
             if is_admin_of_self {
                 return Ok(account);
             }
+            if account.takedown_ref.is_some() && account.suspended_until.is_some() {
+                bail!("RepoSuspended: Repo has been suspended: {did}");
+            }
             if account.takedown_ref.is_some() {
                 bail!("RepoTakendown: Repo has been takendown: {did}");
             }
@@ -33,6 +46,18 @@ pub async fn assert_repo_availability(
     }
 }
 
+/// The error to answer with when `error` says the repo can't be served, see
+/// `assert_repo_availability`
+pub fn repo_unavailable(error: &anyhow::Error) -> Option<ApiError> {
+    let message = error.to_string();
+    match message.split_once(": ") {
+        Some((name, reason)) if REPO_UNAVAILABLE_ERRORS.contains(&name) => {
+            Some(ApiError::BadRequest(name.to_string(), reason.to_string()))
+        }
+        _ => None,
+    }
+}
+
 pub mod apply_writes;
 pub mod create_record;
 pub mod delete_record;
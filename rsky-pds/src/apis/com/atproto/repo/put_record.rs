@@ -1,28 +1,39 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::bbs::automod::{apply_hits, check_writes, AutomodRejectedError};
+use crate::bbs::section::SectionArchivedError;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
-use crate::repo::prepare::{prepare_create, prepare_update, PrepareCreateOpts, PrepareUpdateOpts};
+use crate::flags::SharedFlagStore;
+use crate::rate_limit::RateLimit;
+use crate::repo::prepare::{
+    prepare_create, prepare_update, record_from_json, validation_within,
+    CollectionNotPermittedError, PrepareCreateOpts, PrepareUpdateOpts,
+};
 use crate::SharedSequencer;
+use crate::shutdown::InFlightWrite;
+use crate::xrpc_server::body::{BoundedJson, RECORD_MAX_BYTES};
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::repo::{PutRecordInput, PutRecordOutput};
+use rsky_lexicon::schema::ValidationError;
 use rsky_repo::types::{CommitDataWithOps, PreparedWrite};
 use rsky_syntax::aturi::AtUri;
 use std::str::FromStr;
 
 #[tracing::instrument(skip_all)]
 async fn inner_put_record(
-    body: Json<PutRecordInput>,
+    body: BoundedJson<PutRecordInput, RECORD_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<PutRecordOutput> {
@@ -49,7 +60,9 @@ async fn inner_put_record(
             bail!("Account is deactivated")
         }
         let did = account.did;
-        if did != auth.access.credentials.unwrap().did.unwrap() {
+        let credentials = auth.access.credentials.unwrap();
+        let collections = credentials.collections;
+        if did != credentials.did.unwrap() {
             bail!("AuthRequiredError")
         }
         let uri = AtUri::make(did.clone(), Some(collection.clone()), Some(rkey.clone()))?;
@@ -61,9 +74,11 @@ async fn inner_put_record(
             Some(swap_record) => Some(Cid::from_str(&swap_record)?),
             None => None,
         };
-        let (commit, write): (Option<CommitDataWithOps>, PreparedWrite) = {
+        let (commit, write, _write_lock): (Option<CommitDataWithOps>, PreparedWrite, _) = {
             let mut actor_store =
-                ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+                ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+            // returned with the commit, so it's held until the new root is recorded
+            let write_lock = actor_store.lock_writes().await?;
 
             let current = actor_store
                 .record
@@ -77,10 +92,12 @@ async fn inner_put_record(
                         collection,
                         rkey,
                         swap_cid: swap_record_cid,
-                        record: serde_json::from_value(record)?,
+                        record: record_from_json(record, validate)?,
                         validate,
+                        collections: collections.clone(),
                     })
-                    .await?,
+                    .await
+                    .map_err(|error| validation_within(error, "record"))?,
                 )
             } else {
                 PreparedWrite::Create(
@@ -89,20 +106,28 @@ async fn inner_put_record(
                         collection,
                         rkey: Some(rkey),
                         swap_cid: swap_record_cid,
-                        record: serde_json::from_value(record)?,
+                        record: record_from_json(record, validate)?,
                         validate,
+                        collections: collections.clone(),
                     })
-                    .await?,
+                    .await
+                    .map_err(|error| validation_within(error, "record"))?,
                 )
             };
 
             match current {
-                Some(current) if current.cid == write.cid().unwrap().to_string() => (None, write),
+                Some(current) if current.cid == write.cid().unwrap().to_string() => {
+                    (None, write, write_lock)
+                }
                 _ => {
+                    let writes = vec![write.clone()];
+                    let hits =
+                        check_writes(&actor_store.record.db, &cfg.bbs, &did, &writes).await?;
                     let commit = actor_store
-                        .process_writes(vec![write.clone()], swap_commit_cid)
+                        .process_writes(writes, swap_commit_cid)
                         .await?;
-                    (Some(commit), write)
+                    apply_hits(&actor_store.record.db, cfg.service.did.clone(), hits).await;
+                    (Some(commit), write, write_lock)
                 }
             }
         };
@@ -126,19 +151,34 @@ async fn inner_put_record(
 #[tracing::instrument(skip_all)]
 #[rocket::post("/xrpc/com.atproto.repo.putRecord", format = "json", data = "<body>")]
 pub async fn put_record(
-    body: Json<PutRecordInput>,
+    body: BoundedJson<PutRecordInput, RECORD_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    _write: InFlightWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<PutRecordOutput>, ApiError> {
+    flags.check_writable([body.collection.as_str()])?;
     tracing::debug!("@LOG: debug put_record {body:#?}");
-    match inner_put_record(body, auth, sequencer, s3_config, db, account_manager).await {
+    match inner_put_record(body, auth, sequencer, blob_store, cfg, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            match error.downcast::<ValidationError>() {
+                Ok(error) => Err(ApiError::InvalidRecord(error)),
+                Err(error)
+                    if error.is::<CollectionNotPermittedError>()
+                        || error.is::<AutomodRejectedError>()
+                        || error.is::<SectionArchivedError>() =>
+                {
+                    Err(ApiError::from(error))
+                }
+                Err(_) => Err(ApiError::RuntimeError),
+            }
         }
     }
 }
@@ -1,10 +1,12 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::{BlobStore, SharedBlobStore};
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::rate_limit::RateLimit;
 use anyhow::{Error, Result};
-use aws_sdk_s3::Config;
 use rocket::data::Data;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
@@ -42,16 +44,18 @@ async fn inner_upload_blob(
     auth: AccessStandardIncludeChecks,
     blob: Data<'_>,
     content_type: ContentType,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
+    account_manager: AccountManager,
 ) -> Result<BlobOutput> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
+    let quota = cfg.quota.account_storage_bytes;
+    account_manager
+        .assert_storage_available(&requester, 0, quota)
+        .await?;
 
-    let actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
 
     if !actor_store
         .blob
@@ -72,6 +76,9 @@ async fn inner_upload_blob(
         .blob
         .upload_blob_and_get_metadata(content_type.name, blob)
         .await?;
+    account_manager
+        .assert_storage_available(&requester, metadata.size, quota)
+        .await?;
     let blobref = actor_store.blob.track_untethered_blob(metadata).await?;
 
     // make the blob permanent if an associated record is already indexed
@@ -109,18 +116,32 @@ async fn inner_upload_blob(
 
 #[tracing::instrument(skip_all)]
 #[rocket::post("/xrpc/com.atproto.web5.uploadBlob", data = "<blob>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn upload_blob(
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
     blob: Data<'_>,
     content_type: ContentType,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
+    account_manager: AccountManager,
 ) -> Result<Json<BlobOutput>, ApiError> {
-    match inner_upload_blob(auth, blob, content_type, s3_config, db).await {
+    match inner_upload_blob(
+        auth,
+        blob,
+        content_type,
+        blob_store,
+        cfg,
+        db,
+        account_manager,
+    )
+    .await
+    {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("{error:?}");
-            Err(ApiError::RuntimeError)
+            Err(error.into())
         }
     }
 }
@@ -1,13 +1,13 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::server::assert_valid_did_documents_for_service;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessFull;
 use crate::db::DbConn;
+use crate::identity::SharedDidMethods;
 use crate::SharedSequencer;
-use aws_sdk_s3::Config;
 use rocket::State;
 use rsky_syntax::handle::INVALID_HANDLE;
 
@@ -15,12 +15,13 @@ use rsky_syntax::handle::INVALID_HANDLE;
 async fn inner_activate_account(
     auth: AccessFull,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
-    assert_valid_did_documents_for_service(requester.clone()).await?;
+    assert_valid_did_documents_for_service(requester.clone(), did_methods).await?;
 
     let account = account_manager
         .get_account(
@@ -35,11 +36,8 @@ async fn inner_activate_account(
     if let Some(account) = account {
         account_manager.activate_account(&requester).await?;
 
-        let mut actor_store = ActorStore::new(
-            requester.clone(),
-            S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-            db,
-        );
+        let mut actor_store =
+            ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
         let sync_data = actor_store.get_sync_event_data().await?;
 
         // @NOTE: we're over-emitting for now for backwards compatibility, can reduce this in the future
@@ -63,11 +61,21 @@ async fn inner_activate_account(
 pub async fn activate_account(
     auth: AccessFull,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
-    match inner_activate_account(auth, sequencer, s3_config, db, account_manager).await {
+    match inner_activate_account(
+        auth,
+        sequencer,
+        blob_store,
+        did_methods,
+        db,
+        account_manager,
+    )
+    .await
+    {
         Ok(_) => Ok(()),
         Err(error) => Err(error),
     }
@@ -1,12 +1,12 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::server::is_valid_did_doc_for_service;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessFull;
 use crate::db::DbConn;
+use crate::identity::SharedDidMethods;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use futures::try_join;
 use rocket::serde::json::Json;
 use rocket::State;
@@ -14,17 +14,15 @@ use rsky_lexicon::com::atproto::server::CheckAccountStatusOutput;
 
 async fn inner_check_account_status(
     auth: AccessFull,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<CheckAccountStatusOutput> {
     let requester = auth.access.credentials.unwrap().did.unwrap();
 
-    let mut actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
+    let mut actor_store =
+        ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
     let repo_root = {
         let storage_guard = actor_store.storage.read().await;
         storage_guard.get_root_detailed().await?
@@ -41,7 +39,7 @@ async fn inner_check_account_status(
 
     let (activated, valid_did) = try_join!(
         account_manager.is_account_activated(&requester),
-        is_valid_did_doc_for_service(requester.clone())
+        is_valid_did_doc_for_service(requester.clone(), did_methods)
     )?;
 
     Ok(CheckAccountStatusOutput {
@@ -61,11 +59,12 @@ async fn inner_check_account_status(
 #[rocket::get("/xrpc/com.atproto.server.checkAccountStatus")]
 pub async fn check_account_status(
     auth: AccessFull,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<CheckAccountStatusOutput>, ApiError> {
-    match inner_check_account_status(auth, s3_config, db, account_manager).await {
+    match inner_check_account_status(auth, blob_store, did_methods, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("Internal Error: {error}");
@@ -1,23 +1,23 @@
 use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::saga::CreateAccountSaga;
 use crate::account_manager::{AccountManager, CreateAccountOpts};
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::server::safe_resolve_did_doc;
 use crate::apis::ApiError;
 use crate::auth_verifier::UserDidAuthOptional;
 use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::flags::SharedFlagStore;
 use crate::handle::{normalize_and_validate_handle, HandleValidationContext, HandleValidationOpts};
-use crate::plc::operations::{create_op, CreateAtprotoOpInput};
-use crate::plc::types::{OpOrTombstone, Operation};
+use crate::identity::plc::PLC_METHOD;
+use crate::identity::{CreateDidOpts, DidMethods, PendingDid, SharedDidMethods};
 use crate::sequencer::events::sync_evt_data_from_commit;
+use crate::SharedIdResolver;
 use crate::SharedSequencer;
-use crate::{plc, SharedIdResolver};
-use aws_sdk_s3::Config;
 use email_address::*;
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_common::env::env_str;
 use rsky_crypto::utils::encode_did_key;
 use rsky_lexicon::com::atproto::server::{CreateAccountInput, CreateAccountOutput};
 use secp256k1::{Keypair, Secp256k1, SecretKey};
@@ -31,33 +31,21 @@ pub struct TransformedCreateAccountInput {
     pub invite_code: Option<String>,
     pub password: String,
     pub signing_key: Keypair,
-    pub plc_op: Option<Operation>,
+    pub pending_did: PendingDid,
     pub deactivated: bool,
 }
 
-//TODO: Potential for taking advantage of async better
-#[tracing::instrument(skip_all)]
-#[rocket::post(
-    "/xrpc/com.atproto.server.createAccount",
-    format = "json",
-    data = "<body>"
-)]
-pub async fn server_create_account(
-    body: Json<CreateAccountInput>,
-    auth: UserDidAuthOptional,
+/// Creates the repo, did and account and announces them, marking each step on
+/// the saga so the caller can undo them if a later step fails.
+async fn inner_create_account(
+    saga: &mut CreateAccountSaga,
+    input: TransformedCreateAccountInput,
+    actor_store: &mut ActorStore,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
-    cfg: &State<ServerConfig>,
     id_resolver: &State<SharedIdResolver>,
-    account_manager: AccountManager,
-    db: DbConn,
-) -> Result<Json<CreateAccountOutput>, ApiError> {
-    tracing::info!("Creating new user account");
-    let requester = match auth.access {
-        Some(access) if access.credentials.is_some() => access.credentials.unwrap().iss,
-        _ => None,
-    };
-    // @TODO: Evaluate if we need to validate for entryway PDS
+    did_methods: &DidMethods,
+    account_manager: &AccountManager,
+) -> Result<CreateAccountOutput, ApiError> {
     let TransformedCreateAccountInput {
         email,
         handle,
@@ -65,51 +53,32 @@ pub async fn server_create_account(
         invite_code,
         password,
         deactivated,
-        plc_op,
+        pending_did,
         signing_key,
-    } = validate_inputs_for_local_pds(
-        cfg,
-        id_resolver,
-        body.into_inner(),
-        requester,
-        &account_manager,
-    )
-    .await?;
+    } = input;
 
-    // Create new actor repo TODO: Proper rollback
-    let mut actor_store = ActorStore::new(
-        did.clone(),
-        S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-        db,
-    );
+    // Create new actor repo
+    saga.creating_repo();
     let commit = match actor_store.create_repo(signing_key, Vec::new()).await {
         Ok(commit) => commit,
         Err(error) => {
             tracing::error!("Failed to create repo\n{:?}", error);
-            actor_store.destroy().await?;
             return Err(ApiError::RuntimeError);
         }
     };
 
-    // Generate a real did with PLC
-    match plc_op {
-        None => {}
-        Some(op) => {
-            let plc_url = env_str("PDS_DID_PLC_URL").unwrap_or("https://plc.directory".to_owned());
-            let plc_client = plc::Client::new(plc_url);
-            match plc_client
-                .send_operation(&did, &OpOrTombstone::Operation(op))
-                .await
-            {
-                Ok(_) => {
-                    tracing::info!("Succesfully sent PLC Operation")
-                }
-                Err(_) => {
-                    tracing::error!("Failed to create did:plc");
-                    actor_store.destroy().await?;
-                    return Err(ApiError::RuntimeError);
-                }
-            }
+    // Publish the new did, e.g. send the genesis op to PLC
+    let published = match did_methods.for_did(&did) {
+        Ok(method) => method.publish(&pending_did).await,
+        Err(error) => Err(error),
+    };
+    match published {
+        Ok(_) => {
+            tracing::info!("Succesfully published {did}")
+        }
+        Err(error) => {
+            tracing::error!("Failed to publish {did}\n{error}");
+            return Err(ApiError::RuntimeError);
         }
     }
 
@@ -117,12 +86,12 @@ pub async fn server_create_account(
         Ok(res) => res,
         Err(error) => {
             tracing::error!("Error resolving DID Doc\n{error}");
-            actor_store.destroy().await?;
             return Err(ApiError::RuntimeError);
         }
     };
 
     // Create Account
+    saga.creating_account();
     let (access_jwt, refresh_jwt);
     match account_manager
         .create_account(CreateAccountOpts {
@@ -143,13 +112,13 @@ pub async fn server_create_account(
         }
         Err(error) => {
             tracing::error!("Error creating account\n{error}");
-            actor_store.destroy().await?;
             return Err(ApiError::RuntimeError);
         }
     }
 
     if !deactivated {
         let mut lock = sequencer.sequencer.write().await;
+        saga.sequencing();
         match lock
             .sequence_identity_evt(did.clone(), Some(handle.clone()))
             .await
@@ -224,25 +193,92 @@ pub async fn server_create_account(
         },
     }
 
-    Ok(Json(CreateAccountOutput {
+    Ok(CreateAccountOutput {
         access_jwt,
         refresh_jwt,
         handle,
         did,
         did_doc: converted_did_doc,
-    }))
+    })
+}
+
+//TODO: Potential for taking advantage of async better
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.server.createAccount",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn server_create_account(
+    body: Json<CreateAccountInput>,
+    auth: UserDidAuthOptional,
+    sequencer: &State<SharedSequencer>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    flags: &State<SharedFlagStore>,
+    id_resolver: &State<SharedIdResolver>,
+    did_methods: &State<SharedDidMethods>,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<Json<CreateAccountOutput>, ApiError> {
+    tracing::info!("Creating new user account");
+    let requester = match auth.access {
+        Some(access) if access.credentials.is_some() => access.credentials.unwrap().iss,
+        _ => None,
+    };
+    // @TODO: Evaluate if we need to validate for entryway PDS
+    let input = validate_inputs_for_local_pds(
+        cfg,
+        flags,
+        id_resolver,
+        did_methods,
+        body.into_inner(),
+        requester,
+        &account_manager,
+    )
+    .await?;
+    let did = input.did.clone();
+
+    let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+    let mut saga = match CreateAccountSaga::begin(did, &actor_store, &account_manager).await {
+        Ok(saga) => saga,
+        Err(error) => {
+            tracing::error!("Cannot create account\n{error}");
+            return Err(ApiError::InvalidRequest(error.to_string()));
+        }
+    };
+    match inner_create_account(
+        &mut saga,
+        input,
+        &mut actor_store,
+        sequencer,
+        id_resolver,
+        did_methods,
+        &account_manager,
+    )
+    .await
+    {
+        Ok(output) => Ok(Json(output)),
+        Err(error) => {
+            saga.compensate(&mut actor_store, &account_manager, sequencer)
+                .await;
+            Err(error)
+        }
+    }
 }
 
-/// Validates Create Account Parameters and builds PLC Operation if needed
+/// Validates Create Account Parameters and works out the DID, with its PLC
+/// genesis op if it's a new did:plc
 pub async fn validate_inputs_for_local_pds(
     cfg: &State<ServerConfig>,
+    flags: &State<SharedFlagStore>,
     id_resolver: &State<SharedIdResolver>,
+    did_methods: &DidMethods,
     input: CreateAccountInput,
     requester: Option<String>,
     account_manager: &AccountManager,
 ) -> Result<TransformedCreateAccountInput, ApiError> {
-    let did: String;
-    let plc_op;
+    let pending_did;
     let deactivated: bool;
     let email;
 
@@ -254,7 +290,7 @@ pub async fn validate_inputs_for_local_pds(
     }
 
     //Invite Code Validation
-    let invite_code = if cfg.invites.required && input.invite_code.is_none() {
+    let invite_code = if flags.get().invite_required && input.invite_code.is_none() {
         return Err(ApiError::InvalidInviteCode);
     } else {
         input.invite_code.clone()
@@ -319,14 +355,14 @@ pub async fn validate_inputs_for_local_pds(
                     "Missing auth to create account with did: {input_did}"
                 )));
             }
-            did = input_did;
-            plc_op = None;
+            pending_did = PendingDid {
+                did: input_did,
+                plc_op: None,
+            };
             deactivated = true;
         }
         None => {
-            let res = format_did_and_plc_op(input, signing_key).await?;
-            did = res.0;
-            plc_op = Some(res.1);
+            pending_did = create_plc_did(did_methods, input, signing_key).await?;
             deactivated = false;
         }
     };
@@ -334,53 +370,34 @@ pub async fn validate_inputs_for_local_pds(
     Ok(TransformedCreateAccountInput {
         email,
         handle,
-        did,
+        did: pending_did.did.clone(),
         invite_code,
         password,
         signing_key,
-        plc_op,
+        pending_did,
         deactivated,
     })
 }
 
 #[tracing::instrument(skip_all)]
-async fn format_did_and_plc_op(
+async fn create_plc_did(
+    did_methods: &DidMethods,
     input: CreateAccountInput,
     signing_key: Keypair,
-) -> Result<(String, Operation), ApiError> {
-    let mut rotation_keys: Vec<String> = Vec::new();
-
-    //Add user provided rotation key
-    if let Some(recovery_key) = &input.recovery_key {
-        rotation_keys.push(recovery_key.clone());
-    }
-
-    //Add PDS rotation key
-    let secp = Secp256k1::new();
-    let private_rotation_key = env::var("PDS_PLC_ROTATION_KEY_K256_PRIVATE_KEY_HEX").unwrap();
-    let private_secret_key =
-        SecretKey::from_slice(&hex::decode(private_rotation_key.as_bytes()).unwrap()).unwrap();
-    let rotation_keypair = Keypair::from_secret_key(&secp, &private_secret_key);
-    rotation_keys.push(encode_did_key(&rotation_keypair.public_key()));
-
-    //Build PLC Create Operation
-
-    let create_op_input = CreateAtprotoOpInput {
-        signing_key: encode_did_key(&signing_key.public_key()),
+) -> Result<PendingDid, ApiError> {
+    let opts = CreateDidOpts {
+        did: None,
         handle: input.handle,
-        pds: format!(
-            "https://{}",
-            env::var("PDS_HOSTNAME").unwrap_or("localhost".to_owned())
-        ),
-        rotation_keys,
+        signing_key: encode_did_key(&signing_key.public_key()),
+        recovery_key: input.recovery_key,
+        ckb_address: None,
     };
-    let response = match create_op(create_op_input, rotation_keypair.secret_key()).await {
-        Ok(res) => res,
-        Err(error) => {
-            tracing::error!("{error}");
-            return Err(ApiError::RuntimeError);
-        }
+    let created = match did_methods.get(PLC_METHOD) {
+        Ok(plc) => plc.create(opts).await,
+        Err(error) => Err(error),
     };
-
-    Ok(response)
+    created.map_err(|error| {
+        tracing::error!("{error}");
+        ApiError::RuntimeError
+    })
 }
@@ -1,6 +1,7 @@
 use crate::account_manager::AccountManager;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessFull;
+use crate::repo::prepare::validate_collection_pattern;
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::server::{CreateAppPasswordInput, CreateAppPasswordOutput};
 
@@ -15,9 +16,20 @@ pub async fn create_app_password(
     auth: AccessFull,
     account_manager: AccountManager,
 ) -> Result<Json<CreateAppPasswordOutput>, ApiError> {
-    let CreateAppPasswordInput { name } = body.into_inner();
+    let CreateAppPasswordInput { name, collections } = body.into_inner();
+    if let Some(ref collections) = collections {
+        for pattern in collections {
+            if let Err(error) = validate_collection_pattern(pattern) {
+                return Err(ApiError::InvalidRequest(error.to_string()));
+            }
+        }
+    }
     match account_manager
-        .create_app_password(auth.access.credentials.unwrap().did.unwrap(), name)
+        .create_app_password(
+            auth.access.credentials.unwrap().did.unwrap(),
+            name,
+            collections,
+        )
         .await
     {
         Ok(app_password) => Ok(Json(app_password)),
@@ -44,7 +44,7 @@ async fn inner_create_session(
         let mut app_password_name: Option<String> = None;
 
         let valid_account_pass = match account_manager
-            .verify_account_password(&user.did, &password)
+            .verify_login_password(&user, &password)
             .await
         {
             Ok(res) => res,
@@ -75,7 +75,7 @@ async fn inner_create_session(
         }
         let (access_jwt, refresh_jwt);
         match account_manager
-            .create_session(user.did.clone(), app_password_name)
+            .create_session(user.did.clone(), app_password_name, None)
             .await
         {
             Ok(res) => {
@@ -109,7 +109,6 @@ pub async fn create_session(
     body: Json<CreateSessionInput>,
     account_manager: AccountManager,
 ) -> Result<Json<CreateSessionOutput>, ApiError> {
-    // @TODO: Add rate limiting
     match inner_create_session(body, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error),
@@ -1,14 +1,12 @@
 use crate::account_manager::helpers::account::{AccountStatus, AvailabilityFlags};
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AdminToken;
 use crate::db::DbConn;
 use crate::models::models::EmailTokenPurpose;
-use crate::sequencer;
 use crate::SharedSequencer;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::server::DeleteAccountInput;
@@ -17,7 +15,7 @@ use rsky_lexicon::com::atproto::server::DeleteAccountInput;
 async fn inner_delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
@@ -47,14 +45,14 @@ async fn inner_delete_account(
             .await?;
 
         let mut actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+            ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
         actor_store.destroy().await?;
         account_manager.delete_account(&did).await?;
         let mut lock = sequencer.sequencer.write().await;
         let account_seq = lock
             .sequence_account_evt(did.clone(), AccountStatus::Deleted)
             .await?;
-        sequencer::delete_all_for_user(&did, Some(vec![account_seq])).await?;
+        lock.delete_all_for_user(&did, Some(vec![account_seq])).await?;
         Ok(())
     } else {
         tracing::error!("account not found");
@@ -71,12 +69,12 @@ async fn inner_delete_account(
 pub async fn delete_account(
     body: Json<DeleteAccountInput>,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     _auth: AdminToken,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
-    match inner_delete_account(body, sequencer, s3_config, db, account_manager).await {
+    match inner_delete_account(body, sequencer, blob_store, db, account_manager).await {
         Ok(_) => Ok(()),
         Err(error) => Err(error),
     }
@@ -8,7 +8,7 @@ pub async fn delete_session(
     auth: RevokeRefreshToken,
     account_manager: AccountManager,
 ) -> Result<(), ApiError> {
-    match account_manager.revoke_refresh_token(auth.id).await {
+    match account_manager.revoke_session(auth.id).await {
         Ok(_) => Ok(()),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
@@ -1,15 +1,19 @@
 use crate::apis::ApiError;
+use crate::flags::SharedFlagStore;
 use rocket::serde::json::Json;
-use rsky_common::env::{env_bool, env_list, env_str};
+use rocket::State;
+use rsky_common::env::{env_list, env_str};
 use rsky_lexicon::com::atproto::server::{
     DescribeServerOutput, DescribeServerRefContact, DescribeServerRefLinks,
 };
 
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.server.describeServer")]
-pub async fn describe_server() -> Result<Json<DescribeServerOutput>, ApiError> {
+pub async fn describe_server(
+    flags: &State<SharedFlagStore>,
+) -> Result<Json<DescribeServerOutput>, ApiError> {
     let available_user_domains = env_list("PDS_SERVICE_HANDLE_DOMAINS");
-    let invite_code_required = env_bool("PDS_INVITE_REQUIRED");
+    let invite_code_required = Some(flags.get().invite_required);
     let privacy_policy = env_str("PDS_PRIVACY_POLICY_URL");
     let terms_of_service = env_str("PDS_TERMS_OF_SERVICE_URL");
     let contact_email_address = env_str("PDS_CONTACT_EMAIL_ADDRESS");
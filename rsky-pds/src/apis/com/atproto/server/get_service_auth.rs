@@ -2,60 +2,84 @@ use crate::account_manager::helpers::auth::{create_service_jwt, ServiceJwtParams
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessFull;
 use crate::pipethrough::{PRIVILEGED_METHODS, PROTECTED_METHODS};
-use anyhow::{bail, Result};
-use chrono::offset::Utc as UtcOffset;
-use chrono::DateTime;
 use rocket::serde::json::Json;
-use rsky_common::time::{from_micros_to_utc, HOUR, MINUTE};
+use rsky_common::time::{HOUR, MINUTE, SECOND};
 use rsky_lexicon::com::atproto::server::GetServiceAuthOutput;
 use secp256k1::SecretKey;
 use std::env;
 use std::time::SystemTime;
 
+fn bad_expiration(message: &str) -> ApiError {
+    ApiError::BadRequest("BadExpiration".to_string(), message.to_string())
+}
+
+/// Checks a requested `exp` (Unix Epoch seconds) against the bounds allowed for the token.
+pub fn validate_exp(exp: u64, now: u64, has_lxm: bool) -> Result<(), ApiError> {
+    if exp < now {
+        return Err(bad_expiration("expiration is in past"));
+    }
+    let diff = exp - now;
+    if diff > (HOUR / SECOND) as u64 {
+        return Err(bad_expiration(
+            "cannot request a token with an expiration more than an hour in the future",
+        ));
+    }
+    if !has_lxm && diff > (MINUTE / SECOND) as u64 {
+        return Err(bad_expiration(
+            "cannot request a method-less token with an expiration more than a minute in the future",
+        ));
+    }
+    Ok(())
+}
+
 pub async fn inner_get_service_auth(
     aud: String,
     exp: Option<u64>,
     lxm: Option<String>,
     auth: AccessFull,
-) -> Result<String> {
+) -> Result<String, ApiError> {
     let credentials = auth.access.credentials.unwrap();
     let did = credentials.clone().did.unwrap();
-    // We just use the repo signing key
-    let private_key = env::var("PDS_REPO_SIGNING_KEY_K256_PRIVATE_KEY_HEX").unwrap();
-    let keypair = SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-    let exp = match exp {
-        None => None,
-        Some(exp) => Some(exp * 1000),
-    };
     if let Some(exp) = exp {
-        let system_time = SystemTime::now();
-        let now: DateTime<UtcOffset> = system_time.into();
-        let diff = from_micros_to_utc(exp as i64) - now;
-        if diff.num_milliseconds() < 0 {
-            bail!("BadExpiration: expiration is in past");
-        } else if diff.num_milliseconds() > HOUR as i64 {
-            bail!("BadExpiration: cannot request a token with an expiration more than an hour in the future");
-        } else if lxm.is_none() && diff.num_milliseconds() > MINUTE as i64 {
-            bail!("BadExpiration: cannot request a method-less token with an expiration more than a minute in the future");
-        }
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("timestamp in seconds since UNIX epoch")
+            .as_secs();
+        validate_exp(exp, now, lxm.is_some())?;
     }
     if let Some(ref lxm) = lxm {
         if PROTECTED_METHODS.contains(lxm.as_str()) {
-            bail!("cannot request a service auth token for the following protected method: {lxm}");
+            return Err(ApiError::InvalidRequest(format!(
+                "cannot request a service auth token for the following protected method: {lxm}"
+            )));
         }
         if credentials.is_privileged.unwrap_or(false) && PRIVILEGED_METHODS.contains(lxm.as_str()) {
-            bail!("insufficient access to request a service auth token for the following method: {lxm}");
+            return Err(ApiError::InvalidRequest(format!(
+                "insufficient access to request a service auth token for the following method: {lxm}"
+            )));
         }
     }
-    create_service_jwt(ServiceJwtParams {
+    // We just use the repo signing key, web5 accounts included: their chain DID doc
+    // keys are held by the user's wallet and never reach the PDS.
+    let private_key = env::var("PDS_REPO_SIGNING_KEY_K256_PRIVATE_KEY_HEX").unwrap();
+    let keypair = SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
+    match create_service_jwt(ServiceJwtParams {
         iss: did,
         aud,
-        exp: None,
+        exp,
         lxm,
         jti: None,
         keypair,
+        collections: credentials.collections,
     })
     .await
+    {
+        Ok(token) => Ok(token),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
 }
 
 /// Get a signed token on behalf of the requesting DID for the requested service.
@@ -71,11 +95,22 @@ pub async fn get_service_auth(
     lxm: Option<String>,
     auth: AccessFull,
 ) -> Result<Json<GetServiceAuthOutput>, ApiError> {
-    match inner_get_service_auth(aud, exp, lxm, auth).await {
-        Ok(token) => Ok(Json(GetServiceAuthOutput { token })),
-        Err(error) => {
-            tracing::error!("Internal Error: {error}");
-            Err(ApiError::RuntimeError)
-        }
+    let token = inner_get_service_auth(aud, exp, lxm, auth).await?;
+    Ok(Json(GetServiceAuthOutput { token }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn validate_exp_bounds() {
+        assert!(validate_exp(NOW + 60, NOW, false).is_ok());
+        assert!(validate_exp(NOW + 3600, NOW, true).is_ok());
+        assert!(validate_exp(NOW - 1, NOW, true).is_err());
+        assert!(validate_exp(NOW + 3601, NOW, true).is_err());
+        assert!(validate_exp(NOW + 61, NOW, false).is_err());
     }
 }
@@ -1,25 +1,132 @@
 use crate::account_manager::AccountManager;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandard;
+use crate::config::{IdentityConfig, ServerConfig};
+use crate::db::DbConn;
+use crate::mailer;
+use crate::plc::web5_types::{get_didoc_from_chain, Web5DocumentData};
+use diesel::prelude::*;
+use diesel::{insert_into, upsert::excluded};
 use rocket::serde::json::Json;
+use rocket::State;
 use rsky_lexicon::com::atproto::server::GetSessionOutput;
 use rsky_syntax::handle::INVALID_HANDLE;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("timestamp in millis since UNIX epoch")
+        .as_millis() as i64
+}
+
+/// The DID doc cell of a web5 account, cached in `did_doc` for the DID cache's
+/// stale TTL so getSession doesn't hit the chain every time. Identity events
+/// drop the cached copy. If the chain can't be read, a copy up to the max TTL
+/// old is used, and past that there is no doc at all.
+async fn get_web5_did_doc(
+    db: &DbConn,
+    did: &str,
+    ckb_addr: &str,
+    cfg: &IdentityConfig,
+) -> Option<Web5DocumentData> {
+    use crate::schema::pds::did_doc::dsl as DidDocSchema;
+
+    let key = did.to_string();
+    let cached = db
+        .run(move |conn| {
+            DidDocSchema::did_doc
+                .filter(DidDocSchema::did.eq(&key))
+                .select((DidDocSchema::doc, DidDocSchema::updatedAt))
+                .first::<(String, i64)>(conn)
+                .optional()
+        })
+        .await
+        .unwrap_or_else(|error| {
+            tracing::error!("@LOG: ERROR: failed to read cached DID doc: {error}");
+            None
+        })
+        .and_then(|(doc, updated_at)| {
+            let doc = serde_json::from_str::<Web5DocumentData>(&doc).ok()?;
+            Some((doc, now_millis() - updated_at))
+        });
+    if let Some((ref doc, age)) = cached {
+        if age < cfg.cache_state_ttl as i64 {
+            return Some(doc.clone());
+        }
+    }
+
+    match get_didoc_from_chain(ckb_addr).await {
+        Ok(doc) => {
+            let key = did.to_string();
+            let serialized = serde_json::to_string(&doc).ok()?;
+            let updated_at = now_millis();
+            let stored = db
+                .run(move |conn| {
+                    insert_into(DidDocSchema::did_doc)
+                        .values((
+                            DidDocSchema::did.eq(&key),
+                            DidDocSchema::doc.eq(&serialized),
+                            DidDocSchema::updatedAt.eq(updated_at),
+                        ))
+                        .on_conflict(DidDocSchema::did)
+                        .do_update()
+                        .set((
+                            DidDocSchema::doc.eq(excluded(DidDocSchema::doc)),
+                            DidDocSchema::updatedAt.eq(excluded(DidDocSchema::updatedAt)),
+                        ))
+                        .execute(conn)
+                })
+                .await;
+            if let Err(error) = stored {
+                tracing::error!("@LOG: ERROR: failed to cache DID doc: {error}");
+            }
+            Some(doc)
+        }
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: failed to fetch DID doc for {ckb_addr}: {error:?}");
+            match cached {
+                Some((doc, age)) if age < cfg.cache_max_ttl as i64 => Some(doc),
+                _ => None,
+            }
+        }
+    }
+}
 
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.server.getSession")]
 pub async fn get_session(
     auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<GetSessionOutput>, ApiError> {
-    let did = auth.access.credentials.unwrap().did.unwrap();
+    let credentials = auth.access.credentials.unwrap();
+    let did = credentials.did.unwrap();
     match account_manager.get_account(&did, None).await {
-        Ok(Some(user)) => Ok(Json(GetSessionOutput {
-            handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
-            did: user.did,
-            email: user.email,
-            did_doc: None,
-            email_confirmed: Some(user.email_confirmed_at.is_some()),
-        })),
+        Ok(Some(user)) => {
+            // Read from the DID doc since the wallet may have rotated the key
+            // since the session was created. Left out rather than guessed
+            // when the doc can't be read or names no atproto key.
+            let signing_key = match user.ckb_address {
+                Some(ref ckb_addr) => get_web5_did_doc(&db, &user.did, ckb_addr, &cfg.identity)
+                    .await
+                    .and_then(|doc| doc.verification_methods.get("atproto").cloned()),
+                None => None,
+            };
+            Ok(Json(GetSessionOutput {
+                handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
+                did: user.did,
+                email: user
+                    .email
+                    .filter(|email| !mailer::is_placeholder_email(email)),
+                did_doc: None,
+                email_confirmed: Some(user.email_confirmed_at.is_some()),
+                ckb_address: user.ckb_address,
+                signing_key,
+                collections: credentials.collections,
+            }))
+        }
         _ => Err(ApiError::AccountNotFound),
     }
 }
@@ -0,0 +1,41 @@
+use crate::account_manager::AccountManager;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::config::ServerConfig;
+use anyhow::Result;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::server::GetStorageUsageOutput;
+
+async fn inner_get_storage_usage(
+    auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    account_manager: AccountManager,
+) -> Result<GetStorageUsageOutput> {
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+    let usage = account_manager.get_storage_usage(&requester).await?;
+
+    Ok(GetStorageUsageOutput {
+        blob_bytes: usage.blob_bytes,
+        repo_bytes: usage.repo_bytes,
+        total_bytes: usage.total_bytes(),
+        quota_bytes: cfg.quota.account_storage_bytes.map(|quota| quota as i64),
+    })
+}
+
+/// Reports how much storage the requesting account uses across blobs and repo blocks.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.server.getStorageUsage")]
+pub async fn get_storage_usage(
+    auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    account_manager: AccountManager,
+) -> Result<Json<GetStorageUsageOutput>, ApiError> {
+    match inner_get_storage_usage(auth, cfg, account_manager).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{plc, SharedIdResolver};
+use crate::identity::plc::PLC_METHOD;
+use crate::identity::web::WEB_METHOD;
+use crate::identity::{DidMethods, DidSubject};
+use crate::SharedIdResolver;
 use anyhow::{bail, Result};
 use rand::{distributions::Alphanumeric, Rng};
 use rocket::form::validate::Contains;
@@ -88,35 +91,28 @@ pub fn get_keys_from_private_key_str(private_key: String) -> Result<(SecretKey,
     Ok((secret_key, public_key))
 }
 
-pub async fn is_valid_did_doc_for_service(did: String) -> Result<bool> {
-    match assert_valid_did_documents_for_service(did).await {
+pub async fn is_valid_did_doc_for_service(did: String, did_methods: &DidMethods) -> Result<bool> {
+    match assert_valid_did_documents_for_service(did, did_methods).await {
         Ok(()) => Ok(true),
         Err(_) => Ok(false),
     }
 }
 
-pub async fn assert_valid_did_documents_for_service(did: String) -> Result<()> {
-    if did.starts_with("did:plc") {
-        let plc_url = env_str("PDS_DID_PLC_URL").unwrap_or("https://plc.directory".to_owned());
-        let plc_client = plc::Client::new(plc_url);
-        let resolved = plc_client.get_document_data(&did).await?;
-        let pds_endpoint = match resolved.services.get("atproto_pds") {
-            Some(service) => Some(service.endpoint.clone()),
-            None => None,
-        };
-        let signing_key = match resolved.verification_methods.get("atproto") {
-            Some(key) => Some(key.clone()),
-            None => None,
-        };
-        assert_valid_doc_contents(AssertionContents {
-            pds_endpoint,
-            signing_key,
-            rotation_keys: Some(resolved.rotation_keys),
-        })
-        .await?;
-    } else {
-        bail!("Not yet supporting did:web")
+pub async fn assert_valid_did_documents_for_service(
+    did: String,
+    did_methods: &DidMethods,
+) -> Result<()> {
+    let method = did_methods.for_did(&did)?;
+    if method.name() != PLC_METHOD && method.name() != WEB_METHOD {
+        bail!("Not yet supporting did:{}", method.name())
     }
+    let resolved = method.resolve(DidSubject::new(&did)).await?;
+    assert_valid_doc_contents(AssertionContents {
+        pds_endpoint: resolved.pds_endpoint,
+        signing_key: resolved.signing_key,
+        rotation_keys: resolved.rotation_keys,
+    })
+    .await?;
     Ok(())
 }
 
@@ -183,6 +179,7 @@ pub mod describe_server;
 pub mod get_account_invite_codes;
 pub mod get_service_auth;
 pub mod get_session;
+pub mod get_storage_usage;
 pub mod list_app_passwords;
 pub mod refresh_session;
 pub mod request_account_delete;
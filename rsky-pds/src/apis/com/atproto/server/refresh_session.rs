@@ -2,6 +2,8 @@ use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
 use crate::apis::ApiError;
 use crate::auth_verifier::{Credentials, Refresh};
+use crate::db::DbConn;
+use crate::oauth::get_session;
 use anyhow::Result;
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::server::RefreshSessionOutput;
@@ -10,10 +12,17 @@ use rsky_syntax::handle::INVALID_HANDLE;
 async fn inner_refresh_session(
     auth: Refresh,
     account_manager: AccountManager,
+    db: DbConn,
 ) -> Result<RefreshSessionOutput, ApiError> {
     let Credentials { did, token_id, .. } = auth.access.credentials.unwrap();
     let did = did.unwrap();
     let token_id = token_id.unwrap();
+    // OAuth sessions are refreshed at /oauth/token, with a proof of their DPoP key
+    if get_session(token_id.clone(), &db).await?.is_some() {
+        return Err(ApiError::InvalidRequest(
+            "OAuth sessions can't be refreshed here".to_string(),
+        ));
+    }
     let user = account_manager
         .get_account(
             &did,
@@ -28,7 +37,9 @@ async fn inner_refresh_session(
         if user.takedown_ref.is_some() {
             return Err(ApiError::AccountTakendown);
         }
-        let rotated = account_manager.rotate_refresh_token(&token_id).await?;
+        let rotated = account_manager
+            .rotate_refresh_token(&token_id, None)
+            .await?;
         if let Some(rotated) = rotated {
             Ok(RefreshSessionOutput {
                 handle: user.handle.unwrap_or(INVALID_HANDLE.to_string()),
@@ -50,8 +61,9 @@ async fn inner_refresh_session(
 pub async fn refresh_session(
     auth: Refresh,
     account_manager: AccountManager,
+    db: DbConn,
 ) -> Result<Json<RefreshSessionOutput>, ApiError> {
-    match inner_refresh_session(auth, account_manager).await {
+    match inner_refresh_session(auth, account_manager, db).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error),
     }
@@ -1,28 +1,35 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::repo::assert_repo_availability;
 use crate::apis::ApiError;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
 use crate::db::DbConn;
+use crate::image::ImageVariant;
 use anyhow::Result;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::primitives::AggregatedBytes;
-use aws_sdk_s3::Config;
 use lexicon_cid::Cid;
 use rocket::http::Header;
 use rocket::{Responder, State};
+use rsky_repo::error::BlobError;
 use std::str::FromStr;
 
 #[derive(Responder)]
 #[response(status = 200)]
-pub struct BlobResponder(Vec<u8>, Header<'static>, Header<'static>, Header<'static>);
+pub struct BlobResponder(
+    pub Vec<u8>,
+    pub Header<'static>,
+    pub Header<'static>,
+    pub Header<'static>,
+);
 
 async fn inner_get_blob(
     did: String,
     cid: String,
-    s3_config: &State<Config>,
+    variant: Option<ImageVariant>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
@@ -35,26 +42,36 @@ async fn inner_get_blob(
     let _ = assert_repo_availability(&did, is_user_or_admin, &account_manager).await?;
 
     let cid = Cid::from_str(&cid)?;
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
 
-    let found = actor_store.blob.get_blob(cid).await?;
+    let found = match variant {
+        None => actor_store.blob.get_blob(cid).await?,
+        Some(variant) => actor_store.blob.get_blob_variant(cid, variant).await?,
+    };
     let buf: AggregatedBytes = found.stream.collect().await?;
     Ok((buf.to_vec(), found.mime_type))
 }
 
-/// Get a blob associated with a given account. Returns the full blob as originally uploaded.
+/// Get a blob associated with a given account. Returns the full blob as originally uploaded,
+/// or a processed rendition of an image when `variant` is `thumbnail` or `fullsize`.
 /// Does not require auth; implemented by PDS.
 #[tracing::instrument(skip_all)]
-#[rocket::get("/xrpc/com.atproto.sync.getBlob?<did>&<cid>")]
+#[rocket::get("/xrpc/com.atproto.sync.getBlob?<did>&<cid>&<variant>")]
 pub async fn get_blob(
     did: String,
     cid: String,
-    s3_config: &State<Config>,
+    variant: Option<String>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<BlobResponder, ApiError> {
-    match inner_get_blob(did, cid, s3_config, auth, db, account_manager).await {
+    let variant = match variant.map(|variant| ImageVariant::from_str(&variant)) {
+        None => None,
+        Some(Ok(variant)) => Some(variant),
+        Some(Err(error)) => return Err(ApiError::InvalidRequest(error.to_string())),
+    };
+    match inner_get_blob(did, cid, variant, blob_store, auth, db, account_manager).await {
         Ok(res) => {
             let (bytes, mime_type) = res;
             Ok(BlobResponder(
@@ -73,6 +90,10 @@ pub async fn get_blob(
                     tracing::error!("Error: {}", error);
                     Err(ApiError::BlobNotFound)
                 }
+                _ if matches!(error.downcast_ref(), Some(BlobError::BlobNotFoundError)) => {
+                    tracing::error!("Error: {}", error);
+                    Err(ApiError::BlobNotFound)
+                }
                 _ => {
                     tracing::error!("Error: {}", error);
                     Err(ApiError::RuntimeError)
@@ -1,5 +1,5 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::repo::assert_repo_availability;
 use crate::apis::ApiError;
@@ -7,7 +7,6 @@ use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
 use crate::db::DbConn;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use lexicon_cid::Cid;
 use rocket::{Responder, State};
 use rsky_repo::car::blocks_to_car_file;
@@ -21,7 +20,7 @@ pub struct BlockResponder(Vec<u8>);
 async fn inner_get_blocks(
     did: String,
     cids: Vec<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
@@ -38,7 +37,7 @@ async fn inner_get_blocks(
         .map(|c| Cid::from_str(&c).map_err(anyhow::Error::new))
         .collect::<Result<Vec<Cid>>>()?;
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
     let storage_guard = actor_store.storage.read().await;
     let got = storage_guard.get_blocks(cids).await?;
 
@@ -62,12 +61,12 @@ async fn inner_get_blocks(
 pub async fn get_blocks(
     did: String,
     cids: Vec<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<BlockResponder, ApiError> {
-    match inner_get_blocks(did, cids, s3_config, auth, db, account_manager).await {
+    match inner_get_blocks(did, cids, blob_store, auth, db, account_manager).await {
         Ok(res) => Ok(BlockResponder(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
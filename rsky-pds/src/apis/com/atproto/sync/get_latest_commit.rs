@@ -1,20 +1,19 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
-use crate::apis::com::atproto::repo::assert_repo_availability;
+use crate::apis::com::atproto::repo::{assert_repo_availability, repo_unavailable};
 use crate::apis::ApiError;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
 use crate::db::DbConn;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::sync::GetLatestCommitOutput;
 
 async fn inner_get_latest_commit(
     did: String,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
@@ -26,31 +25,34 @@ async fn inner_get_latest_commit(
     };
     let _ = assert_repo_availability(&did, is_user_or_admin, &account_manager).await?;
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
     let storage_guard = actor_store.storage.read().await;
     match storage_guard.get_root_detailed().await {
         Ok(res) => Ok(GetLatestCommitOutput {
             cid: res.cid.to_string(),
             rev: res.rev,
         }),
-        Err(_) => bail!("Could not find root for DID: {did}"),
+        Err(_) => bail!("RepoNotFound: Could not find root for DID: {did}"),
     }
 }
 
+/// Get the current commit CID & revision of the specified repo. Does not
+/// require auth. Unavailable repos answer RepoNotFound, RepoTakendown,
+/// RepoSuspended or RepoDeactivated, so a relay can tell them apart.
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.sync.getLatestCommit?<did>")]
 pub async fn get_latest_commit(
     did: String,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<GetLatestCommitOutput>, ApiError> {
-    match inner_get_latest_commit(did, s3_config, auth, db, account_manager).await {
+    match inner_get_latest_commit(did, blob_store, auth, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            Err(repo_unavailable(&error).unwrap_or(ApiError::RuntimeError))
         }
     }
 }
@@ -1,29 +1,46 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
-use crate::apis::com::atproto::repo::assert_repo_availability;
+use crate::apis::com::atproto::repo::{assert_repo_availability, repo_unavailable};
 use crate::apis::ApiError;
 use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
 use crate::db::DbConn;
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
-use rocket::{Responder, State};
+use rocket::http::ContentType;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket::State;
+use rsky_repo::storage::RepoRootError;
+use rsky_syntax::tid::{ensure_valid_tid, InvalidTidError};
+use tokio::io::DuplexStream;
 
-#[derive(Responder)]
-#[response(status = 200, content_type = "application/vnd.ipld.car")]
-pub struct BlockResponder(Vec<u8>);
+/// Streams the CAR to the client as the export is written, rather than
+/// buffering the whole repo first.
+pub struct CarStreamResponder(DuplexStream);
+
+impl<'r> Responder<'r, 'static> for CarStreamResponder {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::new("application", "vnd.ipld.car"))
+            .streamed_body(self.0)
+            .ok()
+    }
+}
 
 async fn get_car_stream(
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     did: String,
     since: Option<String>,
     db: DbConn,
-) -> Result<Vec<u8>> {
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+) -> Result<DuplexStream> {
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
     let storage_guard = actor_store.storage.read().await;
     match storage_guard.get_car_stream(since).await {
-        Err(_) => bail!("Could not find repo for DID: {did}"),
+        Err(error) if error.downcast_ref::<RepoRootError>().is_some() => {
+            bail!("RepoNotFound: Could not find repo for DID: {did}")
+        }
+        Err(error) => Err(error),
         Ok(carstream) => Ok(carstream),
     }
 }
@@ -31,37 +48,46 @@ async fn get_car_stream(
 async fn inner_get_repo(
     did: String,
     since: Option<String>, // The revision ('rev') of the repo to create a diff from.
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
-) -> Result<Vec<u8>> {
+) -> Result<DuplexStream> {
     let is_user_or_admin = if let Some(access) = auth.access {
         auth_verifier::is_user_or_admin(access, &did)
     } else {
         false
     };
     let _ = assert_repo_availability(&did, is_user_or_admin, &account_manager).await?;
-    get_car_stream(s3_config, did, since, db).await
+    if let Some(ref since) = since {
+        ensure_valid_tid(since.clone())?;
+    }
+    get_car_stream(blob_store, did, since, db).await
 }
 
 /// Download a repository export as CAR file. Optionally only a 'diff' since a previous revision.
 /// Does not require auth; implemented by PDS.
+/// The CAR is rooted at the latest commit, which for web5 repos carries the
+/// signature made by the account's own key, so mirrors can verify it as-is.
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.sync.getRepo?<did>&<since>")]
 pub async fn get_repo(
     did: String,
     since: Option<String>, // The revision ('rev') of the repo to create a diff from.
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
-) -> Result<BlockResponder, ApiError> {
-    match inner_get_repo(did, since, s3_config, auth, db, account_manager).await {
-        Ok(res) => Ok(BlockResponder(res)),
+) -> Result<CarStreamResponder, ApiError> {
+    match inner_get_repo(did, since, blob_store, auth, db, account_manager).await {
+        Ok(res) => Ok(CarStreamResponder(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            let message = error.to_string();
+            if error.downcast_ref::<InvalidTidError>().is_some() {
+                return Err(ApiError::InvalidRequest(format!("Invalid since: {message}")));
+            }
+            Err(repo_unavailable(&error).unwrap_or(ApiError::RuntimeError))
         }
     }
 }
@@ -1,21 +1,19 @@
-use crate::account_manager::helpers::account::{
-    format_account_status, AccountStatus, FormattedAccountStatus,
-};
+use crate::account_manager::helpers::account::{format_account_status, FormattedAccountStatus};
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
-use crate::apis::com::atproto::repo::assert_repo_availability;
+use crate::apis::com::atproto::repo::{assert_repo_availability, repo_unavailable};
+use crate::apis::com::atproto::sync::format_repo_status;
 use crate::apis::ApiError;
 use crate::db::DbConn;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
-use rsky_lexicon::com::atproto::sync::{GetRepoStatusOutput, RepoStatus};
+use rsky_lexicon::com::atproto::sync::GetRepoStatusOutput;
 
 async fn inner_get_repo(
     did: String,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<GetRepoStatusOutput> {
@@ -25,7 +23,7 @@ async fn inner_get_repo(
     let mut rev: Option<String> = None;
     if active {
         let actor_store =
-            ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+            ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
         let storage_guard = actor_store.storage.read().await;
         let root = storage_guard.get_root_detailed().await?;
         rev = Some(root.rev);
@@ -34,18 +32,7 @@ async fn inner_get_repo(
     Ok(GetRepoStatusOutput {
         did,
         active,
-        status: match status {
-            None => None,
-            Some(status) => match status {
-                AccountStatus::Active => None,
-                AccountStatus::Takendown => Some(RepoStatus::Takedown),
-                AccountStatus::Suspended => Some(RepoStatus::Suspended),
-                AccountStatus::Deleted => None,
-                AccountStatus::Deactivated => Some(RepoStatus::Deactivated),
-                AccountStatus::Desynchronized => Some(RepoStatus::Desynchronized),
-                AccountStatus::Throttled => Some(RepoStatus::Throttled),
-            },
-        },
+        status: format_repo_status(status),
         rev,
     })
 }
@@ -56,15 +43,15 @@ async fn inner_get_repo(
 #[rocket::get("/xrpc/com.atproto.sync.getRepoStatus?<did>")]
 pub async fn get_repo_status(
     did: String,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<GetRepoStatusOutput>, ApiError> {
-    match inner_get_repo(did, s3_config, db, account_manager).await {
+    match inner_get_repo(did, blob_store, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            Err(repo_unavailable(&error).unwrap_or(ApiError::RuntimeError))
         }
     }
 }
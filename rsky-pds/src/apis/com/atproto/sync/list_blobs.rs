@@ -1,5 +1,5 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::blob::ListBlobsOpts;
 use crate::actor_store::ActorStore;
 use crate::apis::com::atproto::repo::assert_repo_availability;
@@ -8,7 +8,6 @@ use crate::auth_verifier;
 use crate::auth_verifier::OptionalAccessOrAdminToken;
 use crate::db::DbConn;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::sync::ListBlobsOutput;
@@ -18,7 +17,7 @@ async fn inner_list_blobs(
     since: Option<String>, // Optional revision of the repo to list blobs since.
     limit: Option<u16>,
     cursor: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
@@ -30,7 +29,7 @@ async fn inner_list_blobs(
     };
     let _ = assert_repo_availability(&did, is_user_or_admin, &account_manager).await?;
 
-    let actor_store = ActorStore::new(did.clone(), S3BlobStore::new(did.clone(), s3_config.inner().clone()), db);
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
     let blob_cids = actor_store
         .blob
         .list_blobs(ListBlobsOpts {
@@ -59,7 +58,7 @@ pub async fn list_blobs(
     since: Option<String>, // Optional revision of the repo to list blobs since.
     limit: Option<u16>,
     cursor: Option<String>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     auth: OptionalAccessOrAdminToken,
     db: DbConn,
     account_manager: AccountManager,
@@ -69,7 +68,7 @@ pub async fn list_blobs(
         since,
         limit,
         cursor,
-        s3_config,
+        blob_store,
         auth,
         db,
         account_manager,
@@ -1,6 +1,7 @@
 use crate::account_manager::helpers::account::{
-    format_account_status, AccountStatus, ActorAccount, FormattedAccountStatus,
+    format_account_status, ActorAccount, FormattedAccountStatus,
 };
+use crate::apis::com::atproto::sync::format_repo_status;
 use crate::apis::ApiError;
 use crate::db::DbConn;
 use anyhow::{anyhow, bail, Result};
@@ -11,7 +12,7 @@ use diesel::QueryDsl;
 use rocket::serde::json::Json;
 use rsky_common::time::{from_millis_to_utc, from_str_to_millis};
 use rsky_common::RFC3339_VARIANT;
-use rsky_lexicon::com::atproto::sync::{ListReposOutput, RefRepo as LexiconRepo, RepoStatus};
+use rsky_lexicon::com::atproto::sync::{ListReposOutput, RefRepo as LexiconRepo};
 
 #[derive(Debug, Clone)]
 pub struct TimeDidResult {
@@ -136,6 +137,7 @@ impl TimeDidKeySet {
             Option<String>,
             Option<String>,
             Option<String>,
+            Option<String>,
         )>,
     > {
         let KeySetPaginateOpts {
@@ -160,6 +162,7 @@ impl TimeDidKeySet {
                 ActorSchema::deactivatedAt,
                 ActorSchema::takedownRef,
                 ActorSchema::ckbAddress,
+                ActorSchema::suspendedUntil,
             ))
             .limit(limit)
             .into_boxed();
@@ -208,6 +211,7 @@ impl TimeDidKeySet {
                     Option<String>,
                     Option<String>,
                     Option<String>,
+                    Option<String>,
                 )>(conn)
             })
             .await?;
@@ -253,24 +257,14 @@ async fn inner_list_repos(
                     email: None,
                     invites_disabled: None,
                     email_confirmed_at: None,
+                    suspended_until: row.7,
                 }));
             LexiconRepo {
                 did: row.0,
                 head: row.1,
                 rev: row.2,
                 active: Some(active),
-                status: match status {
-                    None => None,
-                    Some(status) => match status {
-                        AccountStatus::Active => None,
-                        AccountStatus::Takendown => Some(RepoStatus::Takedown),
-                        AccountStatus::Suspended => Some(RepoStatus::Suspended),
-                        AccountStatus::Deleted => None,
-                        AccountStatus::Deactivated => Some(RepoStatus::Deactivated),
-                        AccountStatus::Desynchronized => Some(RepoStatus::Desynchronized),
-                        AccountStatus::Throttled => Some(RepoStatus::Throttled),
-                    },
-                },
+                status: format_repo_status(status),
             }
         })
         .collect::<Vec<LexiconRepo>>();
@@ -280,6 +274,9 @@ async fn inner_list_repos(
     })
 }
 
+/// Enumerates all the DID, rev, and commit CID for all repos hosted by this
+/// service, deactivated and taken down ones included with `active: false`
+/// and their status. Does not require auth; implemented by PDS and Relay.
 #[tracing::instrument(skip_all)]
 #[rocket::get("/xrpc/com.atproto.sync.listRepos?<limit>&<cursor>")]
 pub async fn list_repos(
@@ -287,6 +284,13 @@ pub async fn list_repos(
     cursor: Option<String>,
     db: DbConn,
 ) -> Result<Json<ListReposOutput>, ApiError> {
+    if let Some(limit) = limit {
+        if !(1..=1000).contains(&limit) {
+            return Err(ApiError::InvalidRequest(
+                "limit must be between 1 and 1000".to_string(),
+            ));
+        }
+    }
     match inner_list_repos(limit, cursor, &db).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
@@ -0,0 +1,82 @@
+use crate::apis::ApiError;
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::sync::{ListReposByCollectionOutput, ListReposByCollectionRepo};
+use rsky_syntax::nsid::ensure_valid_nsid;
+
+const MAX_LIMIT: i64 = 2000;
+
+async fn inner_list_repos_by_collection(
+    collection: String,
+    limit: i64,
+    cursor: Option<String>,
+    db: &DbConn,
+) -> Result<ListReposByCollectionOutput> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    let dids = db
+        .run(move |conn| {
+            let mut builder = RecordSchema::record
+                .filter(RecordSchema::collection.eq(collection))
+                .filter(exists(
+                    ActorSchema::actor
+                        .filter(ActorSchema::did.eq(RecordSchema::did))
+                        .filter(ActorSchema::takedownRef.is_null())
+                        .filter(ActorSchema::deactivatedAt.is_null()),
+                ))
+                .select(RecordSchema::did)
+                .distinct()
+                .order(RecordSchema::did.asc())
+                .limit(limit)
+                .into_boxed();
+            if let Some(cursor) = cursor {
+                builder = builder.filter(RecordSchema::did.gt(cursor));
+            }
+            builder.load::<String>(conn)
+        })
+        .await?;
+
+    let cursor = match dids.len() as i64 == limit {
+        true => dids.last().cloned(),
+        false => None,
+    };
+    Ok(ListReposByCollectionOutput {
+        cursor,
+        repos: dids
+            .into_iter()
+            .map(|did| ListReposByCollectionRepo { did })
+            .collect(),
+    })
+}
+
+/// Enumerates all the DIDs which have records with the given collection NSID, so indexers can
+/// find the repos to backfill for e.g. `app.bbs.post`. Does not require auth.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.sync.listReposByCollection?<collection>&<limit>&<cursor>")]
+pub async fn list_repos_by_collection(
+    collection: String,
+    limit: Option<i64>,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<Json<ListReposByCollectionOutput>, ApiError> {
+    if let Err(error) = ensure_valid_nsid(&collection) {
+        return Err(ApiError::InvalidRequest(error.to_string()));
+    }
+    let limit = limit.unwrap_or(500);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::InvalidRequest(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    match inner_list_repos_by_collection(collection, limit, cursor, &db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
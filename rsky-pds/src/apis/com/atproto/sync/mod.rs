@@ -1,3 +1,19 @@
+use crate::account_manager::helpers::account::AccountStatus;
+use rsky_lexicon::com::atproto::sync::RepoStatus;
+
+/// The `status` sync endpoints report for an account, unset while it's active
+pub fn format_repo_status(status: Option<AccountStatus>) -> Option<RepoStatus> {
+    match status? {
+        AccountStatus::Active => None,
+        AccountStatus::Takendown => Some(RepoStatus::Takedown),
+        AccountStatus::Suspended => Some(RepoStatus::Suspended),
+        AccountStatus::Deleted => Some(RepoStatus::Deleted),
+        AccountStatus::Deactivated => Some(RepoStatus::Deactivated),
+        AccountStatus::Desynchronized => Some(RepoStatus::Desynchronized),
+        AccountStatus::Throttled => Some(RepoStatus::Throttled),
+    }
+}
+
 pub mod get_blob;
 pub mod get_blocks;
 pub mod get_latest_commit;
@@ -6,4 +22,5 @@ pub mod get_repo;
 pub mod get_repo_status;
 pub mod list_blobs;
 pub mod list_repos;
+pub mod list_repos_by_collection;
 pub mod subscribe_repos;
@@ -1,13 +1,18 @@
+use crate::auth_verifier::AdminToken;
 use crate::config::ServerConfig;
 use crate::crawlers::Crawlers;
 use crate::sequencer::events::{
     AccountEvt, CommitEvt, IdentityEvt, SeqEvt, SyncEvt, TypedAccountEvt, TypedCommitEvt,
     TypedIdentityEvt, TypedSyncEvt,
 };
-use crate::sequencer::outbox::{Outbox, OutboxOpts};
-use crate::sequencer::Sequencer;
+use crate::sequencer::outbox::{ConsumerTooSlowError, Outbox, OutboxEvt, OutboxOpts};
+use crate::sequencer::{
+    count_subscriber_cursors, get_subscriber_cursor, save_subscriber_cursor, Sequencer,
+};
+use crate::shutdown::close_for_shutdown;
 use crate::xrpc_server::stream::frames::{ErrorFrame, Frame, MessageFrame, MessageFrameOpts};
 use crate::xrpc_server::stream::types::ErrorFrameBody;
+use crate::SharedSequencer;
 use chrono::offset::Utc as UtcOffset;
 use chrono::{DateTime, Duration};
 use futures::{pin_mut, StreamExt};
@@ -17,13 +22,16 @@ use rsky_common::time::from_str_to_utc;
 use rsky_common::RFC3339_VARIANT;
 use rsky_lexicon::com::atproto::sync::{
     SubscribeReposAccount, SubscribeReposCommit, SubscribeReposCommitOperation,
-    SubscribeReposIdentity, SubscribeReposSync,
+    SubscribeReposIdentity, SubscribeReposInfo, SubscribeReposSync,
 };
 use serde_json::json;
 use std::time::SystemTime;
 use tokio::time::{interval, Duration as TokioDuration};
 use ws::Message;
 
+/// Most named subscribers that can have a cursor saved at once
+const MAX_SUBSCRIBER_CURSORS: i64 = 1000;
+
 fn get_backfill_limit(ms: u64) -> String {
     let system_time = SystemTime::now();
     let mut dt: DateTime<UtcOffset> = system_time.into();
@@ -31,31 +39,111 @@ fn get_backfill_limit(ms: u64) -> String {
     format!("{}", dt.format(RFC3339_VARIANT))
 }
 
+fn is_valid_subscriber_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Where a named subscriber starts, `cursor` if it passed one or else the seq
+/// saved for it. A new subscriber is only let in while there's room for its
+/// cursor.
+async fn get_start_cursor(id: &str, cursor: Option<i64>) -> Result<Option<i64>, ErrorFrameBody> {
+    let cursor_error = || ErrorFrameBody {
+        error: "CursorError".to_string(),
+        message: Some("Failed to fetch saved subscriber cursor.".to_string()),
+    };
+    let id = id.to_string();
+    match get_subscriber_cursor(&id).await {
+        Ok(Some(stored)) => Ok(cursor.or(Some(stored))),
+        Ok(None) => match count_subscriber_cursors().await {
+            Ok(count) if count < MAX_SUBSCRIBER_CURSORS => Ok(cursor),
+            Ok(_) => Err(ErrorFrameBody {
+                error: "TooManySubscribers".to_string(),
+                message: Some("No room to save another subscriber cursor.".to_string()),
+            }),
+            Err(_) => Err(cursor_error()),
+        },
+        Err(_) => Err(cursor_error()),
+    }
+}
+
+async fn save_cursor(subscriber: &Option<String>, cursor: Option<i64>) {
+    if let (Some(id), Some(cursor)) = (subscriber, cursor) {
+        if let Err(error) = save_subscriber_cursor(id, cursor).await {
+            tracing::warn!("Failed to save cursor {cursor} for subscriber {id}: {error}");
+        }
+    }
+}
+
 /// Repository event stream, aka Firehose endpoint. Outputs repo commits with diff data,
 /// and identity update events, for all repositories on the current server. See the atproto
 /// specifications for details around stream sequencing, repo versioning, CAR diff format, and more.
 /// Public and does not require auth; implemented by PDS and Relay.
-#[rocket::get("/xrpc/com.atproto.sync.subscribeRepos?<cursor>")]
+///
+/// A consumer can pass a `subscriber` id to have the last delivered seq saved server side, a
+/// later connection with the same id and no `cursor` resumes from there. Saving a cursor takes
+/// admin auth, and only so many subscribers can have one.
+#[rocket::get("/xrpc/com.atproto.sync.subscribeRepos?<cursor>&<subscriber>")]
 #[allow(unused_variables)]
 pub async fn subscribe_repos<'a>(
     cursor: Option<i64>,
+    subscriber: Option<String>,
+    admin: Option<AdminToken>,
     cfg: &'a State<ServerConfig>,
+    shared_sequencer: &'a State<SharedSequencer>,
     mut shutdown: Shutdown,
     ws: ws::WebSocket,
 ) -> ws::Stream!['a] {
     ws::Stream! { ws =>
+        let store = shared_sequencer.sequencer.read().await.store.clone();
         let sequencer_lock = Sequencer::new(
-            Crawlers::new(cfg.service.hostname.clone(), cfg.crawlers.clone()),
+            Crawlers::new(
+                cfg.service.hostname.clone(),
+                cfg.crawlers.clone(),
+                cfg.crawl_notify.clone(),
+            ),
+            store,
             None,
         );
         let mut outbox = Outbox::new(
             sequencer_lock.clone(),
             Some(OutboxOpts {
-                max_buffer_size: cfg.subscription.repo_backfill_limit_ms as usize,
+                max_buffer_size: cfg.subscription.max_buffer as usize,
+                backpressure: cfg.subscription.backpressure,
             })
         );
 
-        tracing::debug!("@LOG DEBUG: request to com.atproto.sync.subscribeRepos; Cursor={cursor:?}");
+        tracing::debug!("@LOG DEBUG: request to com.atproto.sync.subscribeRepos; Cursor={cursor:?}; Subscriber={subscriber:?}");
+        let cursor = match (cursor, &subscriber) {
+            (_, Some(_)) if admin.is_none() => {
+                let error_frame = ErrorFrame::new(ErrorFrameBody {
+                    error: "AuthRequired".to_string(),
+                    message: Some("Saving a subscriber cursor requires admin auth.".to_string()),
+                });
+                yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                return;
+            },
+            (_, Some(id)) if !is_valid_subscriber_id(id) => {
+                let error_frame = ErrorFrame::new(ErrorFrameBody {
+                    error: "InvalidRequest".to_string(),
+                    message: Some("Invalid subscriber id.".to_string()),
+                });
+                yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                return;
+            },
+            (cursor, Some(id)) => match get_start_cursor(id, cursor).await {
+                Ok(cursor) => cursor,
+                Err(body) => {
+                    let error_frame = ErrorFrame::new(body);
+                    yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
+                    return;
+                }
+            },
+            (cursor, None) => cursor,
+        };
         let backfill_time = get_backfill_limit(cfg.subscription.repo_backfill_limit_ms);
 
         let mut outbox_cursor: Option<i64> = None;
@@ -122,18 +210,48 @@ pub async fn subscribe_repos<'a>(
         // Initialize the ping interval
         let mut ping_interval = interval(TokioDuration::from_secs(30));
 
+        // Last seq sent to a named subscriber. Once events have been dropped this stops
+        // advancing, so the saved cursor stays before the gap and a reconnect backfills it.
+        let mut delivered: Option<i64> = None;
+        let mut dropped = false;
+        let mut unsaved: u64 = 0;
+
         loop {
             select! {
                 evt = event_stream.next() => {
                     let evt = match evt {
-                        Some(Ok(evt)) => evt,
+                        Some(Ok(OutboxEvt::Evt(evt))) => evt,
+                        Some(Ok(OutboxEvt::Dropped { count, last_seen })) => {
+                            if !dropped {
+                                dropped = true;
+                                delivered = Some(last_seen);
+                                save_cursor(&subscriber, delivered).await;
+                                unsaved = 0;
+                            }
+                            let subscribe_info_evt = SubscribeReposInfo {
+                                name: "ConsumerTooSlow".to_string(),
+                                message: Some(format!("Skipped {count} events after seq {last_seen}, reconnect with that cursor to backfill them.")),
+                            };
+                            let message_frame = MessageFrame::new(subscribe_info_evt, Some(MessageFrameOpts { r#type: Some("#info".to_string()) }));
+                            match message_frame.to_bytes() {
+                                Ok(binary) => {
+                                    yield Message::Binary(binary);
+                                },
+                                Err(_) => break
+                            }
+                            continue;
+                        },
                         Some(Err(err)) => {
+                            let error = match err.is::<ConsumerTooSlowError>() {
+                                true => "ConsumerTooSlow",
+                                false => "EventStreamError",
+                            };
                             let error_frame = ErrorFrame::new(ErrorFrameBody {
-                                error: "EventStreamError".to_string(),
+                                error: error.to_string(),
                                 message: Some(err.to_string()),
                             });
                             yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                            return;
+                            break;
                         },
                         None => {
                             let error_frame = ErrorFrame::new(ErrorFrameBody {
@@ -141,9 +259,10 @@ pub async fn subscribe_repos<'a>(
                                 message: Some("Failed to fetch event from stream.".to_string()),
                             });
                             yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                            return;
+                            break;
                         }
                     };
+                    let seq = evt.seq();
 
                     match evt {
                         SeqEvt::TypedCommitEvt(commit) => {
@@ -179,7 +298,7 @@ pub async fn subscribe_repos<'a>(
                                         message: Some("Failed to serialize event to message frame.".to_string()),
                                     });
                                     yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                                    return;
+                                    break;
                                 }
                             };
                             yield Message::Binary(binary);
@@ -202,7 +321,7 @@ pub async fn subscribe_repos<'a>(
                                         message: Some("Failed to serialize event to message frame.".to_string()),
                                     });
                                     yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                                    return;
+                                    break;
                                 }
                             };
                             yield Message::Binary(binary);
@@ -226,7 +345,7 @@ pub async fn subscribe_repos<'a>(
                                         message: Some("Failed to serialize event to message frame.".to_string()),
                                     });
                                     yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                                    return;
+                                    break;
                                 }
                             };
                             yield Message::Binary(binary);
@@ -250,11 +369,21 @@ pub async fn subscribe_repos<'a>(
                                         message: Some("Failed to serialize event to message frame.".to_string()),
                                     });
                                     yield Message::Binary(error_frame.to_bytes().expect("couldn't translate error to binary."));
-                                    return;
+                                    break;
                                 }
                             };
                             yield Message::Binary(binary);
                         }
+                        // BBS section changes go out on the jetstream only
+                        SeqEvt::TypedSectionEvt(_) => {}
+                    }
+                    if subscriber.is_some() && !dropped {
+                        delivered = Some(seq);
+                        unsaved += 1;
+                        if unsaved >= cfg.subscription.cursor_save_interval {
+                            save_cursor(&subscriber, delivered).await;
+                            unsaved = 0;
+                        }
                     }
                 }
                message = ws.next() => {
@@ -300,8 +429,14 @@ pub async fn subscribe_repos<'a>(
                     // Send a Ping message to the client
                     yield ws::Message::Ping(vec![]);
                 },
-                _ = &mut shutdown => break
+                _ = &mut shutdown => {
+                    yield close_for_shutdown();
+                    break
+                }
             }
         }
+        if unsaved > 0 {
+            save_cursor(&subscriber, delivered).await;
+        }
     }
 }
@@ -1,15 +1,19 @@
 use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::saga::CreateAccountSaga;
 use crate::account_manager::{AccountManager, CreateAccountOpts};
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
+use crate::apis::com::atproto::web5::signup;
 use crate::apis::ApiError;
 use crate::auth_verifier::UserDidAuthOptional;
 use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::identity::SharedDidMethods;
+use crate::mailer;
 use crate::plc::web5_types::{generate_random_string, get_didoc_from_chain};
+use crate::rate_limit::ClientIp;
 use crate::sequencer::events::sync_evt_data_from_commit;
 use crate::SharedSequencer;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::web5::{CreateAccountInput, CreateAccountOutput};
@@ -22,44 +26,21 @@ pub struct TransformedWeb5CreateAccountInput {
     pub invite_code: Option<String>,
 }
 
-//TODO: Potential for taking advantage of async better
-#[tracing::instrument(skip_all)]
-#[rocket::post(
-    "/xrpc/com.atproto.web5.createAccount",
-    format = "json",
-    data = "<body>"
-)]
-pub async fn create_account(
-    body: Json<CreateAccountInput>,
-    _auth: UserDidAuthOptional,
+/// Creates the repo and account and announces them, marking each step on the
+/// saga so the caller can undo them if a later step fails.
+async fn inner_create_account(
+    saga: &mut CreateAccountSaga,
+    input: CreateAccountInput,
+    actor_store: &mut ActorStore,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
-    _cfg: &State<ServerConfig>,
-    account_manager: AccountManager,
-    db: DbConn,
-) -> Result<Json<CreateAccountOutput>, ApiError> {
-    tracing::info!("Creating new user account");
-    // @TODO: Evaluate if we need to validate for entryway PDS
-    let input: CreateAccountInput = body.into_inner();
+    account_manager: &AccountManager,
+    email: Option<String>,
+) -> Result<(String, String), ApiError> {
     let did = input.root.did.clone();
     let handle = input.handle.clone();
 
-    match get_didoc_from_chain(&input.ckb_addr).await {
-        Ok(_) => {
-            return Err(ApiError::InvalidCkbError(format!(
-                "Already apply did, please change address."
-            )))
-        }
-        Err(ApiError::CkbDidocCellNotFound) => {},
-        Err(error) => return Err(error),
-    }
-
-    // Create new actor repo TODO: Proper rollback
-    let mut actor_store = ActorStore::new(
-        did.clone(),
-        S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-        db,
-    );
+    // Create new actor repo
+    saga.creating_repo();
     let commit = match actor_store
         .web5_create_repo(input.root, input.signing_key, Vec::new())
         .await
@@ -67,18 +48,20 @@ pub async fn create_account(
         Ok(commit) => commit,
         Err(error) => {
             tracing::error!("Failed to create repo\n{:?}", error);
-            actor_store.destroy().await?;
             return Err(ApiError::RuntimeError);
         }
     };
 
     // Create Account
+    saga.creating_account();
     let (access_jwt, refresh_jwt);
     match account_manager
         .create_account(CreateAccountOpts {
             did: did.clone(),
             handle: handle.clone(),
-            email: Some(format!("web5Mock{}@web5.com", generate_random_string(10))),
+            email: Some(
+                email.unwrap_or_else(|| mailer::placeholder_email(&generate_random_string(10))),
+            ),
             password: Some(generate_random_string(16)),
             repo_cid: commit.commit_data.cid,
             repo_rev: commit.commit_data.rev.clone(),
@@ -93,12 +76,12 @@ pub async fn create_account(
         }
         Err(error) => {
             tracing::error!("Error creating account\n{error}");
-            actor_store.destroy().await?;
             return Err(ApiError::RuntimeError);
         }
     }
 
     let mut lock = sequencer.sequencer.write().await;
+    saga.sequencing();
     match lock
         .sequence_identity_evt(did.clone(), Some(handle.clone()))
         .await
@@ -160,6 +143,103 @@ pub async fn create_account(
         }
     }
 
+    Ok((access_jwt, refresh_jwt))
+}
+
+//TODO: Potential for taking advantage of async better
+#[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.createAccount",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn create_account(
+    body: Json<CreateAccountInput>,
+    _auth: UserDidAuthOptional,
+    sequencer: &State<SharedSequencer>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    account_manager: AccountManager,
+    db: DbConn,
+    client_ip: ClientIp,
+) -> Result<Json<CreateAccountOutput>, ApiError> {
+    tracing::info!("Creating new user account");
+    // @TODO: Evaluate if we need to validate for entryway PDS
+    let mut input: CreateAccountInput = body.into_inner();
+    let did = input.root.did.clone();
+    if !did_methods.is_chain_did(&did) {
+        return Err(ApiError::InvalidRequest(format!(
+            "web5 accounts need a chain DID, got {did}"
+        )));
+    }
+    let handle = super::validate_handle(&input.handle, cfg)?;
+    input.handle = handle.clone();
+
+    let ip = client_ip.0.map(|ip| ip.to_string()).unwrap_or_default();
+    signup::check_ckb_address(&cfg.signup, &input.ckb_addr)?;
+    signup::check_ip_cap(&cfg.signup, &ip, &account_manager).await?;
+    signup::verify_captcha(&cfg.signup, input.captcha_token.as_deref(), &ip).await?;
+    let email = signup::check_email(input.email.as_deref(), &account_manager).await?;
+
+    // A handle claimed on chain first can only go to that chain address. Its
+    // DID doc being on chain already is then expected rather than a conflict.
+    let reservation = account_manager.get_handle_reservation(&handle).await?;
+    if let Some(ref reservation) = reservation {
+        if reservation.ckb_address != input.ckb_addr {
+            return Err(ApiError::HandleNotAvailable);
+        }
+    }
+    match get_didoc_from_chain(&input.ckb_addr).await {
+        Ok(_) if reservation.is_some() => {}
+        Ok(_) => {
+            return Err(ApiError::InvalidCkbError(format!(
+                "Already apply did, please change address."
+            )))
+        }
+        Err(ApiError::CkbDidocCellNotFound) => {},
+        Err(error) => return Err(error),
+    }
+
+    let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+    let mut saga =
+        match CreateAccountSaga::begin(did.clone(), &actor_store, &account_manager).await {
+            Ok(saga) => saga,
+            Err(error) => {
+                tracing::error!("Cannot create account\n{error}");
+                return Err(ApiError::InvalidRequest(error.to_string()));
+            }
+        };
+    let (access_jwt, refresh_jwt) = match inner_create_account(
+        &mut saga,
+        input,
+        &mut actor_store,
+        sequencer,
+        &account_manager,
+        email,
+    )
+    .await
+    {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            saga.compensate(&mut actor_store, &account_manager, sequencer)
+                .await;
+            return Err(error);
+        }
+    };
+
+    if reservation.is_some() {
+        if let Err(error) = account_manager.release_handle_reservation(&handle).await {
+            tracing::error!("Failed to release handle reservation\n{error}");
+        }
+    }
+
+    // Best-effort, a missed record only loosens the ip cap
+    if let Err(error) = account_manager.record_signup_ip(&did, &ip).await {
+        tracing::error!("Failed to record signup ip\n{error}");
+    }
+
     // let converted_did_doc;
     // match did_doc {
     //     None => converted_did_doc = None,
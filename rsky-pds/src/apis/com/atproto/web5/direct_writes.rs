@@ -1,18 +1,28 @@
-use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
+use crate::apis::com::atproto::web5::get_didoc_with_fallback;
+use crate::apis::com::atproto::web5::index_action::record_key_check_failure;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::bbs::automod::{apply_hits, check_writes};
+use crate::config::ServerConfig;
 use crate::db::DbConn;
-use crate::plc::web5_types::get_didoc_from_chain;
+use crate::flags::SharedFlagStore;
+use crate::identity::{DidMethods, SharedDidMethods};
+use crate::metrics;
+use crate::rate_limit::RateLimit;
 use crate::repo::prepare::{
-    prepare_create, prepare_delete, prepare_update, PrepareCreateOpts, PrepareDeleteOpts,
-    PrepareUpdateOpts,
+    prepare_create, prepare_delete, prepare_update, record_from_json, validation_within,
+    PrepareCreateOpts, PrepareDeleteOpts, PrepareUpdateOpts,
 };
-use crate::SharedSequencer;
+use crate::request_log::redacted;
+use crate::sequencer::Sequencer;
+use crate::shutdown::InFlightWrite;
+use crate::xrpc_server::body::{BoundedJson, WRITES_MAX_BYTES};
+use crate::{telemetry, SharedSequencer};
 use anyhow::{bail, Result};
-use aws_sdk_s3::Config;
 use futures::stream::{self, StreamExt};
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
@@ -20,195 +30,379 @@ use rocket::State;
 use rsky_lexicon::com::atproto::web5::{
     CommitMeta, DirectWritesInput, DirectWritesInputRefWrite, DirectWritesOutput,
     DirectWritesOutputRefWrite, RefWriteCreateResult, RefWriteDeleteResult, RefWriteUpdateResult,
+    SignedRoot,
 };
+use rsky_repo::error::SignedRootError;
 use rsky_repo::types::PreparedWrite;
 use std::str::FromStr;
+use std::time::Instant;
+use tokio::sync::RwLock;
 
-async fn inner_direct_writes(
-    body: Json<DirectWritesInput>,
-    auth: AccessStandardIncludeChecks,
-    sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
-    db: DbConn,
-    account_manager: AccountManager,
-) -> Result<DirectWritesOutput, ApiError> {
-    let tx: DirectWritesInput = body.into_inner();
-    let DirectWritesInput {
-        repo,
-        validate,
-        swap_commit,
-        writes,
-        signing_key,
-        ckb_addr,
-        root,
-    } = tx;
+const DIRECT_WRITES_LXM: &str = "com.atproto.web5.directWrites";
+/// Most writes one signed commit may carry
+const MAX_WRITES: usize = 200;
+
+/// Checks that `ckb_addr` and `signing_key` are the ones the account's DID doc
+/// on chain names. With `chain_strict` off, a chain that can't be reached is
+/// let through on the commit signature alone.
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_account_keys(
+    account: &ActorAccount,
+    ckb_addr: Option<String>,
+    signing_key: &String,
+    cfg: &ServerConfig,
+    did_methods: &DidMethods,
+    account_manager: &AccountManager,
+    chain_strict: bool,
+    lxm: &str,
+) -> Result<(), ApiError> {
     let ckb_addr = ckb_addr.ok_or(ApiError::CkbAddrNotFound)?;
+    if account.ckb_address != Some(ckb_addr.clone()) {
+        record_key_check_failure(account_manager, &account.did, lxm, "ckbAddress").await;
+        return Err(ApiError::InvalidRequest(
+            "Address is inconsistent with the original".to_string(),
+        ));
+    }
+
+    match get_didoc_with_fallback(&ckb_addr, account.handle.as_deref(), cfg, did_methods).await {
+        Ok(didoc) => {
+            if didoc.also_known_as.len() == 0 || !didoc.also_known_as[0].starts_with("at://") {
+                return Err(ApiError::IncompatibleDidDoc);
+            }
+            let handle = didoc.also_known_as[0][5..].to_string();
+            if account.handle.as_ref().ok_or(ApiError::InvalidHandle)? != &handle {
+                return Err(ApiError::InvalidHandle);
+            }
+            let doc_keys: Vec<String> = didoc.verification_methods.values().cloned().collect();
+            if !doc_keys.contains(signing_key) {
+                record_key_check_failure(account_manager, &account.did, lxm, "signingKey").await;
+                return Err(ApiError::InvalidRequest(
+                    "Signing key is inconsistent with the did doc".to_string(),
+                ));
+            }
+        }
+        // The cell is gone, not just unreachable, so the account can't be trusted
+        Err(error @ (ApiError::CkbAddrNoCell | ApiError::CkbDidocCellNotFound)) => {
+            return Err(error)
+        }
+        Err(error) if !chain_strict => tracing::warn!(
+            "Chain strict mode is off, accepting {lxm} for {} on its commit signature: {error:?}",
+            account.did
+        ),
+        Err(error) => return Err(error),
+    };
+
+    if account.deactivated_at.is_some() {
+        return Err(ApiError::InvalidRequest(
+            "Account is deactivated".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Looks up the repo a signed batch of writes is for and checks that the
+/// requester owns it and holds the keys its DID doc names. Returns the
+/// account's did and the collections the session may write to.
+#[allow(clippy::too_many_arguments)]
+pub async fn authorize_direct_writes(
+    repo: &String,
+    ckb_addr: Option<String>,
+    signing_key: &String,
+    auth: AccessStandardIncludeChecks,
+    cfg: &ServerConfig,
+    did_methods: &DidMethods,
+    account_manager: &AccountManager,
+    chain_strict: bool,
+    lxm: &str,
+) -> Result<(String, Option<Vec<String>>), ApiError> {
+    if ckb_addr.is_none() {
+        return Err(ApiError::CkbAddrNotFound);
+    }
     let account = account_manager
         .get_account(
-            &repo,
+            repo,
             Some(AvailabilityFlags {
                 include_deactivated: Some(true),
                 include_taken_down: None,
             }),
         )
         .await?;
+    let Some(account) = account else {
+        return Err(ApiError::InvalidRequest(format!(
+            "Could not find repo: `{repo}`"
+        )));
+    };
+    verify_account_keys(
+        &account,
+        ckb_addr,
+        signing_key,
+        cfg,
+        did_methods,
+        account_manager,
+        chain_strict,
+        lxm,
+    )
+    .await?;
 
-    if let Some(account) = account {
-        if account.ckb_address != Some(ckb_addr.clone()) {
-            return Err(ApiError::InvalidRequest(
-                "Address is inconsistent with the original".to_string(),
-            ));
-        }
+    let collections = auth
+        .access
+        .credentials
+        .as_ref()
+        .and_then(|credentials| credentials.collections.clone());
+    if account.did
+        != auth
+            .access
+            .credentials
+            .ok_or(ApiError::AuthRequiredError("".to_string()))?
+            .did
+            .ok_or(ApiError::InvalidRequest(
+                "Auth credentials require did ".to_string(),
+            ))?
+    {
+        return Err(ApiError::AuthRequiredError(
+            "Did is inconsistent with origin".to_string(),
+        ));
+    }
+    Ok((account.did, collections))
+}
 
-        match get_didoc_from_chain(&ckb_addr).await {
-            Ok(didoc) => {
-                if didoc.also_known_as.len() == 0 || !didoc.also_known_as[0].starts_with("at://") {
-                    return Err(ApiError::IncompatibleDidDoc);
-                }
-                let handle = didoc.also_known_as[0][5..].to_string();
-                if account.handle.ok_or(ApiError::InvalidHandle)? != handle {
-                    return Err(ApiError::InvalidHandle);
-                }
-                let doc_keys: Vec<String> = didoc.verification_methods.values().cloned().collect();
-                if !doc_keys.contains(&signing_key) {
-                    return Err(ApiError::InvalidRequest(
-                        "Signing key is inconsistent with the did doc".to_string(),
-                    ));
+/// Validates `writes` into the writes the commit was signed over
+pub async fn prepare_direct_writes(
+    did: &String,
+    writes: Vec<DirectWritesInputRefWrite>,
+    validate: Option<bool>,
+    collections: &Option<Vec<String>>,
+) -> Result<Vec<PreparedWrite>, ApiError> {
+    if writes.len() > MAX_WRITES {
+        return Err(ApiError::InvalidRequest(format!(
+            "Too many writes. Max: {MAX_WRITES}"
+        )));
+    }
+    let writes = stream::iter(writes.into_iter().enumerate())
+        .then(|(index, write)| async move {
+            Ok::<PreparedWrite, anyhow::Error>(match write {
+                DirectWritesInputRefWrite::Create(write) => PreparedWrite::Create(
+                    prepare_create(PrepareCreateOpts {
+                        did: did.clone(),
+                        collection: write.collection,
+                        rkey: write.rkey,
+                        swap_cid: None,
+                        record: record_from_json(write.value, validate)?,
+                        validate,
+                        collections: collections.clone(),
+                    })
+                    .await
+                    .map_err(|error| validation_within(error, &format!("writes[{index}].value")))?,
+                ),
+                DirectWritesInputRefWrite::Update(write) => PreparedWrite::Update(
+                    prepare_update(PrepareUpdateOpts {
+                        did: did.clone(),
+                        collection: write.collection,
+                        rkey: write.rkey,
+                        swap_cid: None,
+                        record: record_from_json(write.value, validate)?,
+                        validate,
+                        collections: collections.clone(),
+                    })
+                    .await
+                    .map_err(|error| validation_within(error, &format!("writes[{index}].value")))?,
+                ),
+                DirectWritesInputRefWrite::Delete(write) => {
+                    PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
+                        did: did.clone(),
+                        collection: write.collection,
+                        rkey: write.rkey,
+                        swap_cid: None,
+                        collections: collections.clone(),
+                    })?)
                 }
-            }
-            Err(error) => return Err(error),
-        };
-
-        if account.deactivated_at.is_some() {
-            return Err(ApiError::InvalidRequest(
-                "Account is deactivated".to_string(),
-            ));
-        }
-        let did = account.did;
-        if did
-            != auth
-                .access
-                .credentials
-                .ok_or(ApiError::AuthRequiredError("".to_string()))?
-                .did
-                .ok_or(ApiError::InvalidRequest(
-                    "Auth credentials require did ".to_string(),
-                ))?
-        {
-            return Err(ApiError::AuthRequiredError(
-                "Did is inconsistent with origin".to_string(),
-            ));
-        }
-        let did: &String = &did;
-        if writes.len() > 200 {
-            return Err(ApiError::InvalidRequest(
-                "Too many writes. Max: 200".to_string(),
-            ));
-        }
-
-        let writes: Vec<PreparedWrite> = stream::iter(writes)
-            .then(|write| async move {
-                Ok::<PreparedWrite, anyhow::Error>(match write {
-                    DirectWritesInputRefWrite::Create(write) => PreparedWrite::Create(
-                        prepare_create(PrepareCreateOpts {
-                            did: did.clone(),
-                            collection: write.collection,
-                            rkey: write.rkey,
-                            swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
-                            validate,
-                        })
-                        .await?,
-                    ),
-                    DirectWritesInputRefWrite::Update(write) => PreparedWrite::Update(
-                        prepare_update(PrepareUpdateOpts {
-                            did: did.clone(),
-                            collection: write.collection,
-                            rkey: write.rkey,
-                            swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
-                            validate,
-                        })
-                        .await?,
-                    ),
-                    DirectWritesInputRefWrite::Delete(write) => {
-                        PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
-                            did: did.clone(),
-                            collection: write.collection,
-                            rkey: write.rkey,
-                            swap_cid: None,
-                        })?)
-                    }
-                })
             })
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<PreparedWrite>, _>>()?;
-
-        let swap_commit_cid =
-            match swap_commit {
-                Some(swap_commit) => Some(Cid::from_str(&swap_commit).map_err(|_| {
-                    ApiError::InvalidRequest("Swap commit convert error".to_string())
-                })?),
-                None => None,
-            };
-
-        let mut actor_store = ActorStore::new(
-            did.clone(),
-            S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-            db,
-        );
-
-        let commit = actor_store
-            .verify_writes(writes.clone(), swap_commit_cid, signing_key, root)
-            .await?;
+        })
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<PreparedWrite>, _>>()?;
+    Ok(writes)
+}
+
+pub fn parse_swap_commit(swap_commit: Option<String>) -> Result<Option<Cid>, ApiError> {
+    match swap_commit {
+        Some(swap_commit) => Ok(Some(Cid::from_str(&swap_commit).map_err(|_| {
+            ApiError::InvalidRequest("Swap commit convert error".to_string())
+        })?)),
+        None => Ok(None),
+    }
+}
 
-        let mut lock = sequencer.sequencer.write().await;
-        lock.sequence_commit(did.clone(), commit.clone()).await?;
+/// Applies prepared `writes` with the commit the wallet signed over them, then
+/// sequences it and moves the repo root. The repo's head must still be the
+/// commit the signature builds on.
+#[allow(clippy::too_many_arguments)]
+pub async fn commit_direct_writes(
+    did: &String,
+    writes: Vec<PreparedWrite>,
+    validate: Option<bool>,
+    swap_commit: Option<Cid>,
+    signing_key: String,
+    root: SignedRoot,
+    sequencer: &RwLock<Sequencer>,
+    blob_store: &SharedBlobStore,
+    cfg: &ServerConfig,
+    db: DbConn,
+    account_manager: &AccountManager,
+) -> Result<DirectWritesOutput, ApiError> {
+    // deletes are always let through so an account over its quota can free up space
+    if writes
+        .iter()
+        .any(|write| !matches!(write, PreparedWrite::Delete(_)))
+    {
         account_manager
-            .update_repo_root(
-                did.to_string(),
-                commit.commit_data.cid.clone(),
-                commit.commit_data.rev.clone(),
-            )
+            .assert_storage_available(did, 0, cfg.quota.account_storage_bytes)
             .await?;
+    }
 
-        Ok(DirectWritesOutput {
-            commit: Some(CommitMeta {
-                cid: commit.commit_data.cid.to_string(),
-                rev: commit.commit_data.rev,
-            }),
-            results: Some(
-                writes
-                    .iter()
-                    .map(|write| write_to_output_result(write, validate))
-                    .collect(),
-            ),
-        })
-    } else {
-        Err(ApiError::InvalidRequest(format!(
-            "Could not find repo: `{repo}`"
-        )))
+    let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+
+    // held until the new root is recorded so a concurrent directWrites can't
+    // verify against the same head and fork the repo
+    let _write_lock = actor_store.lock_writes().await?;
+    let hits = check_writes(&actor_store.record.db, &cfg.bbs, did, &writes).await?;
+    let commit = actor_store
+        .verify_writes(writes.clone(), swap_commit, signing_key, root)
+        .await;
+    match commit {
+        Ok(_) => metrics::record_signature_verification(DIRECT_WRITES_LXM, true),
+        Err(ref error)
+            if matches!(
+                error.downcast_ref::<SignedRootError>(),
+                Some(SignedRootError::Signature)
+            ) =>
+        {
+            metrics::record_signature_verification(DIRECT_WRITES_LXM, false)
+        }
+        Err(_) => (),
     }
+    let commit = commit?;
+    apply_hits(&actor_store.record.db, cfg.service.did.clone(), hits).await;
+
+    let mut lock = sequencer.write().await;
+    lock.sequence_commit(did.clone(), commit.clone()).await?;
+    account_manager
+        .update_repo_root(
+            did.to_string(),
+            commit.commit_data.cid.clone(),
+            commit.commit_data.rev.clone(),
+        )
+        .await?;
+
+    Ok(DirectWritesOutput {
+        commit: Some(CommitMeta {
+            cid: commit.commit_data.cid.to_string(),
+            rev: commit.commit_data.rev,
+        }),
+        results: Some(
+            writes
+                .iter()
+                .map(|write| write_to_output_result(write, validate))
+                .collect(),
+        ),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn inner_direct_writes(
+    body: BoundedJson<DirectWritesInput, WRITES_MAX_BYTES>,
+    auth: AccessStandardIncludeChecks,
+    sequencer: &State<SharedSequencer>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    db: DbConn,
+    account_manager: AccountManager,
+    chain_strict: bool,
+) -> Result<DirectWritesOutput, ApiError> {
+    let tx: DirectWritesInput = body.into_inner();
+    let DirectWritesInput {
+        repo,
+        validate,
+        swap_commit,
+        writes,
+        signing_key,
+        ckb_addr,
+        root,
+    } = tx;
+    let (did, collections) = authorize_direct_writes(
+        &repo,
+        ckb_addr,
+        &signing_key,
+        auth,
+        cfg,
+        did_methods,
+        &account_manager,
+        chain_strict,
+        DIRECT_WRITES_LXM,
+    )
+    .await?;
+    let writes = prepare_direct_writes(&did, writes, validate, &collections).await?;
+    let swap_commit = parse_swap_commit(swap_commit)?;
+    commit_direct_writes(
+        &did,
+        writes,
+        validate,
+        swap_commit,
+        signing_key,
+        root,
+        &sequencer.sequencer,
+        blob_store,
+        cfg,
+        db,
+        &account_manager,
+    )
+    .await
 }
 
-#[tracing::instrument(skip_all)]
+#[tracing::instrument(skip_all, fields(
+    did = %telemetry::hashed(&body.repo),
+    ckb_addr = %body.ckb_addr.as_deref().map(telemetry::hashed).unwrap_or_default(),
+))]
 #[rocket::post(
     "/xrpc/com.atproto.web5.directWrites",
     format = "json",
     data = "<body>"
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn direct_writes(
-    body: Json<DirectWritesInput>,
+    body: BoundedJson<DirectWritesInput, WRITES_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    _write: InFlightWrite,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<DirectWritesOutput>, ApiError> {
-    tracing::debug!("@LOG: debug direct_writes {body:#?}");
-    match inner_direct_writes(body, auth, sequencer, s3_config, db, account_manager).await {
+    flags.check_writable(body.writes.iter().map(|write| write.collection()))?;
+    tracing::debug!("directWrites input: {}", redacted(&*body));
+    let started = Instant::now();
+    let res = inner_direct_writes(
+        body,
+        auth,
+        sequencer,
+        blob_store,
+        cfg,
+        did_methods,
+        db,
+        account_manager,
+        flags.get().chain_strict,
+    )
+    .await;
+    metrics::DIRECT_WRITES_DURATION
+        .with_label_values(&[if res.is_ok() { "ok" } else { "error" }])
+        .observe(started.elapsed().as_secs_f64());
+    match res {
         Ok(output) => Ok(Json(output)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error:?}");
@@ -0,0 +1,47 @@
+use crate::account_manager::AccountManager;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::web5::EmailNotificationPrefs;
+
+/// Reports which BBS activity the requesting account is emailed about.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.web5.getEmailNotificationPrefs")]
+pub async fn get_email_notification_prefs(
+    auth: AccessStandard,
+    account_manager: AccountManager,
+) -> Result<Json<EmailNotificationPrefs>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match account_manager.get_email_notification_prefs(&did).await {
+        Ok(prefs) => Ok(Json(prefs)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Opts the requesting account in or out of BBS activity emails.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.putEmailNotificationPrefs",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn put_email_notification_prefs(
+    body: Json<EmailNotificationPrefs>,
+    auth: AccessStandard,
+    account_manager: AccountManager,
+) -> Result<(), ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match account_manager
+        .put_email_notification_prefs(&did, body.into_inner())
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
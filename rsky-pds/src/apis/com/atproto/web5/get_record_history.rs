@@ -0,0 +1,109 @@
+use crate::actor_store::record::get_record_history as read_record_history;
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::hydration::Hydrator;
+use anyhow::{bail, Result};
+use diesel::*;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::web5::{GetRecordHistoryOutput, RecordVersion};
+use rsky_syntax::aturi::AtUri;
+
+#[allow(clippy::too_many_arguments)]
+async fn inner_get_record_history(
+    repo: String,
+    collection: String,
+    rkey: String,
+    limit: u16,
+    cursor: Option<String>,
+    cfg: &State<ServerConfig>,
+    hydrator: Hydrator,
+) -> Result<GetRecordHistoryOutput> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let before_seq = match cursor {
+        Some(cursor) => Some(cursor.parse::<i64>()?),
+        None => None,
+    };
+    let Some(did) = hydrator
+        .account_manager
+        .get_did_for_actor(&repo, None)
+        .await?
+    else {
+        bail!("Could not find repo: {repo}")
+    };
+    hydrator.assert_repo_readable(&did).await?;
+    let uri = AtUri::make(did, Some(collection), Some(rkey))?;
+
+    // a taken down record's earlier versions are withheld along with it
+    let record_uri = uri.to_string();
+    let takedown_ref = hydrator
+        .db
+        .run(move |conn| {
+            RecordSchema::record
+                .select(RecordSchema::takedownRef)
+                .filter(RecordSchema::uri.eq(record_uri))
+                .first::<Option<String>>(conn)
+                .optional()
+        })
+        .await?
+        .flatten();
+    if !hydrator.record_readable(takedown_ref.as_ref()) {
+        bail!("Could not locate record: `{uri}`")
+    }
+
+    let (versions, next_seq) = read_record_history(
+        &hydrator.db,
+        &uri,
+        limit as usize,
+        before_seq,
+        cfg.bbs.record_history_max_commits,
+    )
+    .await?;
+    let versions = versions
+        .into_iter()
+        .map(|version| {
+            Ok(RecordVersion {
+                action: version.action.to_string(),
+                cid: version.cid.map(|cid| cid.to_string()),
+                rev: version.rev,
+                value: version.value.map(serde_json::to_value).transpose()?,
+                created_at: version.sequenced_at,
+            })
+        })
+        .collect::<Result<Vec<RecordVersion>>>()?;
+    Ok(GetRecordHistoryOutput {
+        uri: uri.to_string(),
+        versions,
+        cursor: next_seq.map(|seq| seq.to_string()),
+    })
+}
+
+/// Earlier versions of a record, newest first, so BBS edit history and
+/// moderation review can show what changed without an edit lexicon.
+#[tracing::instrument(skip_all)]
+#[rocket::get(
+    "/xrpc/com.atproto.web5.getRecordHistory?<repo>&<collection>&<rkey>&<limit>&<cursor>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_record_history(
+    repo: String,
+    collection: String,
+    rkey: String,
+    limit: Option<u16>,
+    cursor: Option<String>,
+    cfg: &State<ServerConfig>,
+    hydrator: Hydrator,
+) -> Result<Json<GetRecordHistoryOutput>, ApiError> {
+    let limit = limit.unwrap_or(50);
+    match inner_get_record_history(repo, collection, rkey, limit, cursor, cfg, hydrator).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RecordNotFound)
+        }
+    }
+}
@@ -1,32 +1,76 @@
+use crate::account_manager::helpers::audit::{AuditAction, AuditEvent};
+use crate::account_manager::helpers::auth::{create_step_up_token, STEP_UP_TOKEN_EXPIRES_IN_SECS};
+use crate::account_manager::helpers::jwt_keys::JWT_KEYS;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
+use crate::apis::com::atproto::web5::get_didoc_with_fallback;
 use crate::apis::ApiError;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
-use crate::plc::web5_types::statement_check;
+use crate::identity::{DidMethods, SharedDidMethods};
+use crate::metrics;
+use crate::plc::web5_types::{decode_signed_bytes, statement_check};
+use crate::shutdown::InFlightWrite;
+use crate::xrpc_server::body::{BoundedJson, INDEX_ACTION_MAX_BYTES};
 use crate::{
-    account_manager::helpers::account::{AccountStatus, AvailabilityFlags},
-    plc::web5_types::{extract_timestamp, get_didoc_from_chain, timestamp_check},
+    account_manager::helpers::account::{AccountStatus, ActorAccount, AvailabilityFlags},
+    plc::web5_types::{extract_timestamp, timestamp_check},
 };
-use crate::{sequencer, SharedSequencer};
-use aws_sdk_s3::Config;
+use crate::{telemetry, SharedSequencer};
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::web5::{
     IndexActionInput, IndexActionInputRef, IndexActionOutput, IndexActionOutputRefResult,
-    RefCreateSessionResult, RefDeleteAccountIndex, RefDeleteAccountResult,
+    RefCreateSessionResult, RefDeleteAccountIndex, RefDeleteAccountResult, RefStepUpIndex,
+    RefStepUpResult,
 };
 use serde_json::json;
 use sha2::{Digest, Sha256};
+use std::env;
 
-#[tracing::instrument(skip_all)]
-async fn inner_index_action(
-    body: Json<IndexActionInput>,
-    account_manager: AccountManager,
-    sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
-    db: DbConn,
-) -> Result<IndexActionOutput, ApiError> {
+/// A web5 account whose wallet signature over an indexAction message checked out
+/// against the keys in its DID doc on chain, or its did:web document when
+/// falling back to it.
+pub struct VerifiedIndexAction {
+    pub user: ActorAccount,
+    /// None only for a deleteAccount whose DID doc cell is gone
+    pub did_doc: Option<serde_json::Value>,
+    pub handle: String,
+}
+
+const INDEX_ACTION_LXM: &str = "com.atproto.web5.indexAction";
+
+/// Whether `index` may go ahead without a DID doc to check the signing key
+/// against. Only deleting an account whose cell is gone can, signing in or
+/// stepping up with a key nothing vouches for can't.
+fn allowed_without_did_doc(index: &IndexActionInputRef) -> bool {
+    matches!(index, IndexActionInputRef::DeleteAccountIndex(_))
+}
+
+/// Audits a wallet address, key or signature that didn't match the account.
+pub async fn record_key_check_failure(
+    account_manager: &AccountManager,
+    did: &str,
+    lxm: &str,
+    reason: &str,
+) {
+    account_manager
+        .try_record_audit_event(AuditEvent {
+            detail: Some(json!({ "lxm": lxm, "reason": reason })),
+            ..AuditEvent::new(AuditAction::KeyCheckFailure, None, did.to_string())
+        })
+        .await;
+}
+
+/// Checks an indexAction message signed by the account's CKB wallet. Shared with
+/// the OAuth authorize UI, which signs web5 accounts in the same way.
+pub async fn verify_index_action(
+    input: &IndexActionInput,
+    account_manager: &AccountManager,
+    cfg: &ServerConfig,
+    did_methods: &DidMethods,
+) -> Result<VerifiedIndexAction, ApiError> {
     let IndexActionInput {
         did,
         message,
@@ -34,9 +78,9 @@ async fn inner_index_action(
         signed_bytes,
         ckb_addr,
         index,
-    } = body.into_inner();
+    } = input;
     let did = did.to_lowercase();
-    let ckb_addr = ckb_addr.ok_or(ApiError::CkbAddrNotFound)?;
+    let ckb_addr = ckb_addr.clone().ok_or(ApiError::CkbAddrNotFound)?;
 
     let user = account_manager
         .get_account(
@@ -47,120 +91,225 @@ async fn inner_index_action(
             }),
         )
         .await;
-    if let Ok(Some(user)) = user {
-        if user.ckb_address != Some(ckb_addr.clone()) {
-            return Err(ApiError::InvalidRequest(
-                "Address is inconsistent with the original".to_string(),
-            ));
+    let Ok(Some(user)) = user else {
+        return Err(ApiError::InvalidLogin);
+    };
+    // Taken down and suspended accounts may still delete themselves, but not sign in
+    if user.takedown_ref.is_some() && !matches!(index, IndexActionInputRef::DeleteAccountIndex(_)) {
+        return Err(ApiError::AccountTakendown);
+    }
+    if user.ckb_address != Some(ckb_addr.clone()) {
+        record_key_check_failure(account_manager, &did, INDEX_ACTION_LXM, "ckbAddress").await;
+        return Err(ApiError::InvalidRequest(
+            "Address is inconsistent with the original".to_string(),
+        ));
+    }
+
+    let didoc = get_didoc_with_fallback(&ckb_addr, user.handle.as_deref(), cfg, did_methods).await;
+    let (did_doc, handle) = match didoc {
+        Ok(didoc) => {
+            if didoc.also_known_as.len() == 0 || !didoc.also_known_as[0].starts_with("at://") {
+                return Err(ApiError::IncompatibleDidDoc);
+            }
+            let handle = didoc.also_known_as[0][5..].to_string();
+            if user.handle.clone().ok_or(ApiError::InvalidHandle)? != handle {
+                return Err(ApiError::InvalidHandle);
+            }
+            let doc_keys: Vec<String> = didoc.verification_methods.values().cloned().collect();
+            if !doc_keys.contains(signing_key) {
+                record_key_check_failure(account_manager, &did, INDEX_ACTION_LXM, "signingKey")
+                    .await;
+                return Err(ApiError::InvalidRequest(
+                    "Signing key is inconsistent with the did doc".to_string(),
+                ));
+            }
+            (Some(json!(didoc)), handle)
+        }
+        Err(ApiError::CkbDidocCellNotFound) if allowed_without_did_doc(index) => {
+            (None, "deleteHandle".to_string())
         }
+        Err(error) => return Err(error),
+    };
 
-        let (did_doc, handle) = match get_didoc_from_chain(&ckb_addr).await {
-            Ok(didoc) => {
-                if didoc.also_known_as.len() == 0 || !didoc.also_known_as[0].starts_with("at://") {
-                    return Err(ApiError::IncompatibleDidDoc);
-                }
-                let handle = didoc.also_known_as[0][5..].to_string();
-                if user.handle.ok_or(ApiError::InvalidHandle)? != handle {
-                    return Err(ApiError::InvalidHandle);
+    if !timestamp_check(extract_timestamp(message)?)? {
+        return Err(ApiError::InvalidRequest("Sign message timeout".to_string()));
+    }
+    let hash = Sha256::digest(message);
+    if !statement_check(message, index)? {
+        return Err(ApiError::InvalidRequest(
+            "Message statement check error".to_string(),
+        ));
+    }
+    let sig = decode_signed_bytes(signed_bytes)
+        .map_err(|error| ApiError::InvalidRequest(error.to_string()))?;
+    let valid = rsky_crypto::verify::verify_signature(signing_key, hash.as_ref(), &sig, None)?;
+    metrics::record_signature_verification(INDEX_ACTION_LXM, valid);
+    if !valid {
+        tracing::error!("web5 create session verify signature failed");
+        record_key_check_failure(account_manager, &did, INDEX_ACTION_LXM, "signature").await;
+        return Err(ApiError::RuntimeError);
+    }
+    Ok(VerifiedIndexAction {
+        user,
+        did_doc,
+        handle,
+    })
+}
+
+#[tracing::instrument(skip_all, fields(
+    did = %telemetry::hashed(&body.did),
+    ckb_addr = %body.ckb_addr.as_deref().map(telemetry::hashed).unwrap_or_default(),
+))]
+#[allow(clippy::too_many_arguments)]
+async fn inner_index_action(
+    body: BoundedJson<IndexActionInput, INDEX_ACTION_MAX_BYTES>,
+    _write: InFlightWrite,
+    account_manager: AccountManager,
+    sequencer: &State<SharedSequencer>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    db: DbConn,
+) -> Result<IndexActionOutput, ApiError> {
+    let input = body.into_inner();
+    let VerifiedIndexAction {
+        user,
+        did_doc,
+        handle,
+    } = verify_index_action(&input, &account_manager, cfg, did_methods).await?;
+    let did = input.did.to_lowercase();
+    match input.index {
+        IndexActionInputRef::CreateSessionIndex(_) => {
+            let (access_jwt, refresh_jwt);
+            match account_manager
+                .create_session(user.did.clone(), None, None)
+                .await
+            {
+                Ok(res) => {
+                    (access_jwt, refresh_jwt) = res;
                 }
-                let doc_keys: Vec<String> = didoc.verification_methods.values().cloned().collect();
-                if !doc_keys.contains(&signing_key) {
-                    return Err(ApiError::InvalidRequest(
-                        "Signing key is inconsistent with the did doc".to_string(),
-                    ));
+                Err(e) => {
+                    tracing::error!("{e:?}");
+                    return Err(ApiError::RuntimeError);
                 }
-                (Some(json!(didoc)), handle)
             }
-            Err(ApiError::CkbDidocCellNotFound) => (None, "deleteHandle".to_string()),
-            Err(error) => return Err(error),
-        };
-
-        if !timestamp_check(extract_timestamp(&message)?)? {
-            return Err(ApiError::InvalidRequest("Sign message timeout".to_string()));
-        }
-        let hash = Sha256::digest(&message);
-        let sig = if signed_bytes.starts_with("0x") || signed_bytes.starts_with("0X") {
-            &signed_bytes[2..]
-        } else {
-            &signed_bytes
-        };
-        if !statement_check(&message, &index)? {
-            return Err(ApiError::InvalidRequest(
-                "Message statement check error".to_string(),
-            ));
+            account_manager
+                .try_record_audit_event(AuditEvent::new(
+                    AuditAction::CreateSession,
+                    Some(did.clone()),
+                    did.clone(),
+                ))
+                .await;
+            let ref_csr = RefCreateSessionResult {
+                did,
+                did_doc,
+                handle,
+                email: user.email,
+                email_confirmed: Some(user.email_confirmed_at.is_some()),
+                access_jwt,
+                refresh_jwt,
+            };
+            Ok(IndexActionOutput {
+                result: IndexActionOutputRefResult::CreateSessionResult(ref_csr),
+            })
         }
-        if !rsky_crypto::verify::verify_signature(
-            &signing_key,
-            hash.as_ref(),
-            &hex::decode(sig).map_err(|error| {
-                ApiError::InvalidRequest(format!("Signature decode error {error}"))
-            })?,
-            None,
-        )? {
-            tracing::error!("web5 create session verify signature failed");
-            return Err(ApiError::RuntimeError);
+        IndexActionInputRef::DeleteAccountIndex(_) => {
+            let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+            actor_store.destroy().await?;
+            account_manager.delete_account(&did).await?;
+            let mut lock = sequencer.sequencer.write().await;
+            let account_seq = lock
+                .sequence_account_evt(did.clone(), AccountStatus::Deleted)
+                .await?;
+            lock.delete_all_for_user(&did, Some(vec![account_seq]))
+                .await?;
+            account_manager
+                .try_record_audit_event(AuditEvent::new(
+                    AuditAction::DeleteAccount,
+                    Some(did.clone()),
+                    did.clone(),
+                ))
+                .await;
+            Ok(IndexActionOutput {
+                result: IndexActionOutputRefResult::DeleteAccountResult(RefDeleteAccountResult {}),
+            })
         }
-        match index {
-            IndexActionInputRef::CreateSessionIndex(_) => {
-                let (access_jwt, refresh_jwt);
-                match account_manager.create_session(user.did.clone(), None).await {
-                    Ok(res) => {
-                        (access_jwt, refresh_jwt) = res;
-                    }
-                    Err(e) => {
-                        tracing::error!("{e:?}");
-                        return Err(ApiError::RuntimeError);
-                    }
+        IndexActionInputRef::StepUpIndex(RefStepUpIndex { lxm }) => {
+            let step_up_token = match create_step_up_token(
+                user.did,
+                lxm.clone(),
+                env::var("PDS_SERVICE_DID").unwrap(),
+                JWT_KEYS.current(),
+            ) {
+                Ok(token) => token,
+                Err(e) => {
+                    tracing::error!("{e:?}");
+                    return Err(ApiError::RuntimeError);
                 }
-                let ref_csr = RefCreateSessionResult {
-                    did,
-                    did_doc,
-                    handle,
-                    email: user.email,
-                    email_confirmed: Some(user.email_confirmed_at.is_some()),
-                    access_jwt,
-                    refresh_jwt,
-                };
-                Ok(IndexActionOutput {
-                    result: IndexActionOutputRefResult::CreateSessionResult(ref_csr),
-                })
-            }
-            IndexActionInputRef::DeleteAccountIndex(_) => {
-                let mut actor_store = ActorStore::new(
-                    did.clone(),
-                    S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-                    db,
-                );
-                actor_store.destroy().await?;
-                account_manager.delete_account(&did).await?;
-                let mut lock = sequencer.sequencer.write().await;
-                let account_seq = lock
-                    .sequence_account_evt(did.clone(), AccountStatus::Deleted)
-                    .await?;
-                sequencer::delete_all_for_user(&did, Some(vec![account_seq])).await?;
-                Ok(IndexActionOutput {
-                    result: IndexActionOutputRefResult::DeleteAccountResult(
-                        RefDeleteAccountResult {},
-                    ),
+            };
+            account_manager
+                .try_record_audit_event(AuditEvent {
+                    detail: Some(json!({ "lxm": lxm })),
+                    ..AuditEvent::new(AuditAction::StepUp, Some(did.clone()), did.clone())
                 })
-            }
+                .await;
+            Ok(IndexActionOutput {
+                result: IndexActionOutputRefResult::StepUpResult(RefStepUpResult {
+                    step_up_token,
+                    lxm,
+                    expires_in: STEP_UP_TOKEN_EXPIRES_IN_SECS,
+                }),
+            })
         }
-    } else {
-        Err(ApiError::InvalidLogin)
     }
 }
 
 #[rocket::post("/xrpc/com.atproto.web5.indexAction", format = "json", data = "<body>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn index_action(
-    body: Json<IndexActionInput>,
+    body: BoundedJson<IndexActionInput, INDEX_ACTION_MAX_BYTES>,
+    write: InFlightWrite,
     account_manager: AccountManager,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
     db: DbConn,
 ) -> Result<Json<IndexActionOutput>, ApiError> {
-    // @TODO: Add rate limiting
-    match inner_index_action(body, account_manager, sequencer, s3_config, db).await {
+    match inner_index_action(
+        body,
+        write,
+        account_manager,
+        sequencer,
+        blob_store,
+        cfg,
+        did_methods,
+        db,
+    )
+    .await
+    {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_lexicon::com::atproto::web5::RefCreateSessionIndex;
+
+    #[test]
+    fn only_account_deletion_goes_ahead_without_a_did_doc() {
+        assert!(allowed_without_did_doc(
+            &IndexActionInputRef::DeleteAccountIndex(RefDeleteAccountIndex {})
+        ));
+        assert!(!allowed_without_did_doc(
+            &IndexActionInputRef::CreateSessionIndex(RefCreateSessionIndex {})
+        ));
+        assert!(!allowed_without_did_doc(&IndexActionInputRef::StepUpIndex(
+            RefStepUpIndex {
+                lxm: "com.atproto.web5.setPassword".to_string(),
+            }
+        )));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::record::tombstone::purge_at;
+use crate::actor_store::ActorStore;
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessStandard;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use anyhow::{bail, Result};
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::web5::{ListTombstonesOutput, Tombstone};
+use rsky_repo::util::cbor_to_lex_record;
+
+async fn inner_list_tombstones(
+    did: String,
+    limit: u16,
+    cursor: Option<String>,
+    cfg: &State<ServerConfig>,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<ListTombstonesOutput> {
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did), db);
+    let tombstones = actor_store
+        .record
+        .list_tombstones(limit as i64, cursor)
+        .await?
+        .into_iter()
+        .map(|tombstone| {
+            Ok(Tombstone {
+                purge_at: purge_at(&tombstone.deleted_at, cfg.tombstones.retention_ms)?,
+                uri: tombstone.uri,
+                cid: tombstone.cid,
+                value: serde_json::to_value(cbor_to_lex_record(tombstone.content)?)?,
+                deleted_at: tombstone.deleted_at,
+            })
+        })
+        .collect::<Result<Vec<Tombstone>>>()?;
+    let cursor = match tombstones.len() == limit as usize {
+        true => tombstones
+            .last()
+            .map(|last| format!("{}::{}", last.deleted_at, last.uri)),
+        false => None,
+    };
+    Ok(ListTombstonesOutput { tombstones, cursor })
+}
+
+/// Records the requesting account deleted through directWrites that the PDS
+/// still holds, so a client can offer to undo a delete by creating the
+/// record again.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.web5.listTombstones?<limit>&<cursor>")]
+pub async fn list_tombstones(
+    limit: Option<u16>,
+    cursor: Option<String>,
+    auth: AccessStandard,
+    cfg: &State<ServerConfig>,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<Json<ListTombstonesOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_list_tombstones(did, limit.unwrap_or(50), cursor, cfg, blob_store, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
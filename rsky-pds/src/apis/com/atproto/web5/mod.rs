@@ -1,16 +1,231 @@
-use std::env;
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::handle::errors::{Error, ErrorKind, Result};
+use crate::handle::reserved::is_reserved_handle;
+use crate::identity::web::WEB_METHOD;
+use crate::identity::{DidData, DidMethods, DidSubject};
+use crate::plc::web5_types::{get_didoc_from_chain, Service, Web5DocumentData};
+use crate::telemetry;
 
-pub fn validate_handle(handle: &str) -> bool {
-    let suffix: String = env::var("PDS_HOSTNAME").unwrap_or("localhost".to_owned());
-    let s_slice: &str = &suffix[..]; // take a full slice of the string
-    handle.ends_with(s_slice)
-    // Need to check suffix here and need to make sure handle doesn't include "." after trumming it
+/// Shortest and longest label a web5 handle can have
+const HANDLE_LABEL_MIN_LEN: usize = 3;
+const HANDLE_LABEL_MAX_LEN: usize = 18;
+
+/// Checks a handle has the `<label>.<PDS_HOSTNAME>` shape web5 accounts are
+/// given and returns it normalized: lowercased, with internationalized labels
+/// in their punycode form. The label is letters, digits and inner hyphens,
+/// and can't be on the built-in or configured reserved lists.
+pub fn validate_handle(handle: &str, cfg: &ServerConfig) -> Result<String> {
+    check_handle(
+        handle,
+        &cfg.service.hostname,
+        &cfg.identity.reserved_handles,
+    )
+}
+
+fn check_handle(handle: &str, hostname: &str, reserved_handles: &[String]) -> Result<String> {
+    let handle = match url::Host::parse(handle.trim()) {
+        Ok(url::Host::Domain(handle)) => handle,
+        _ => return Err(Error::new(ErrorKind::InvalidHandle, "Invalid handle")),
+    };
+    let hostname = hostname.to_lowercase();
+    let label = match handle
+        .strip_suffix(hostname.as_str())
+        .and_then(|front| front.strip_suffix('.'))
+    {
+        Some(label) if !label.contains('.') => label,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::UnsupportedDomain,
+                &format!("Handle must be a single label under {hostname}"),
+            ))
+        }
+    };
+    if label.len() < HANDLE_LABEL_MIN_LEN {
+        return Err(Error::new(ErrorKind::InvalidHandle, "Handle too short"));
+    }
+    if label.len() > HANDLE_LABEL_MAX_LEN {
+        return Err(Error::new(ErrorKind::InvalidHandle, "Handle too long"));
+    }
+    if !label
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        || label.starts_with('-')
+        || label.ends_with('-')
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidHandle,
+            "Invalid characters in handle",
+        ));
+    }
+    if is_reserved_handle(label) || reserved_handles.iter().any(|reserved| reserved == label) {
+        return Err(Error::new(ErrorKind::HandleNotAvailable, "Reserved handle"));
+    }
+    Ok(handle)
+}
+
+/// Reads the DID doc cell bound to `ckb_addr`. When the cell is missing or the
+/// chain can't be reached and `PDS_DID_WEB_FALLBACK` is on, the account's
+/// `did:web:<handle>` document stands in for it, so accounts that also host
+/// one stay usable through a chain outage. The chain error is returned if
+/// there's no such document either.
+pub async fn get_didoc_with_fallback(
+    ckb_addr: &str,
+    handle: Option<&str>,
+    cfg: &ServerConfig,
+    did_methods: &DidMethods,
+) -> std::result::Result<Web5DocumentData, ApiError> {
+    let error = match get_didoc_from_chain(ckb_addr).await {
+        Ok(didoc) => return Ok(didoc),
+        Err(
+            error @ (ApiError::CkbAddrNoCell
+            | ApiError::CkbDidocCellNotFound
+            | ApiError::InvalidCkbError(_)),
+        ) => error,
+        Err(error) => return Err(error),
+    };
+    let Some(handle) = handle.filter(|_| cfg.identity.did_web_fallback) else {
+        return Err(error);
+    };
+    let did = format!("did:web:{handle}");
+    let resolved = match did_methods.get(WEB_METHOD) {
+        Ok(method) => method.resolve(DidSubject::new(&did)).await,
+        Err(error) => Err(error),
+    };
+    match resolved {
+        Ok(data) => {
+            tracing::warn!(
+                "DID doc cell for {} unavailable ({error:?}), falling back to did:web",
+                telemetry::hashed(ckb_addr)
+            );
+            Ok(web5_doc_from_did_data(data))
+        }
+        Err(fallback_error) => {
+            tracing::debug!("did:web fallback for {handle} failed: {fallback_error}");
+            Err(error)
+        }
+    }
+}
+
+fn web5_doc_from_did_data(data: DidData) -> Web5DocumentData {
+    Web5DocumentData {
+        verification_methods: data
+            .signing_key
+            .into_iter()
+            .map(|key| ("atproto".to_string(), key))
+            .collect(),
+        also_known_as: data.also_known_as,
+        services: data
+            .pds_endpoint
+            .into_iter()
+            .map(|endpoint| {
+                (
+                    "atproto_pds".to_string(),
+                    Service {
+                        r#type: "AtprotoPersonalDataServer".to_string(),
+                        endpoint,
+                    },
+                )
+            })
+            .collect(),
+    }
 }
 
 pub mod create_account;
+pub mod email_notification_prefs;
+pub mod get_record_history;
+pub mod list_tombstones;
 pub mod index_action;
 pub mod direct_writes;
 pub mod pre_create_account;
 pub mod pre_direct_writes;
 pub mod upload_blob;
 pub mod pre_index_action;
+pub mod rebind_address;
+pub mod reserve_handle;
+pub mod scheduled_writes;
+pub mod set_password;
+pub mod signup;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOSTNAME: &str = "pds.example.com";
+
+    fn check(handle: &str) -> std::result::Result<String, String> {
+        check_handle(handle, HOSTNAME, &["blocked".to_string()]).map_err(|error| match error.kind {
+            ErrorKind::InvalidHandle => "invalid".to_string(),
+            ErrorKind::HandleNotAvailable => "reserved".to_string(),
+            ErrorKind::UnsupportedDomain => "domain".to_string(),
+            ErrorKind::InternalError => "internal".to_string(),
+        })
+    }
+
+    #[test]
+    fn validates_handles() {
+        let cases: [(&str, std::result::Result<&str, &str>); 21] = [
+            ("alice.pds.example.com", Ok("alice.pds.example.com")),
+            ("Alice.PDS.Example.com", Ok("alice.pds.example.com")),
+            (" bob-99.pds.example.com ", Ok("bob-99.pds.example.com")),
+            ("abc.pds.example.com", Ok("abc.pds.example.com")),
+            (
+                "abcdefghijklmnopqr.pds.example.com",
+                Ok("abcdefghijklmnopqr.pds.example.com"),
+            ),
+            (
+                "bücher.pds.example.com",
+                Ok("xn--bcher-kva.pds.example.com"),
+            ),
+            // only the configured hostname, with one label in front of it
+            ("evilpds.example.com", Err("domain")),
+            ("alicepds.example.com", Err("domain")),
+            ("alice.evil.pds.example.com", Err("domain")),
+            ("alice.example.com", Err("domain")),
+            ("pds.example.com", Err("domain")),
+            ("alice.pds.example.com.", Err("domain")),
+            ("127.0.0.1", Err("invalid")),
+            // label rules
+            ("ab.pds.example.com", Err("invalid")),
+            ("abcdefghijklmnopqrs.pds.example.com", Err("invalid")),
+            ("evil_pds.pds.example.com", Err("invalid")),
+            ("-alice.pds.example.com", Err("invalid")),
+            ("alice-.pds.example.com", Err("invalid")),
+            ("al ice.pds.example.com", Err("invalid")),
+            // reserved, built in and from config
+            ("admin.pds.example.com", Err("reserved")),
+            ("blocked.pds.example.com", Err("reserved")),
+        ];
+        for (handle, expected) in cases {
+            assert_eq!(
+                check(handle),
+                expected.map(str::to_string).map_err(str::to_string),
+                "{handle}"
+            );
+        }
+    }
+
+    #[test]
+    fn converts_did_web_data_to_a_web5_doc() {
+        let doc = web5_doc_from_did_data(DidData {
+            also_known_as: vec!["at://alice.pds.example.com".to_string()],
+            signing_key: Some("did:key:zQ3sh".to_string()),
+            pds_endpoint: Some("https://pds.example.com".to_string()),
+            rotation_keys: None,
+        });
+        assert_eq!(doc.also_known_as, vec!["at://alice.pds.example.com"]);
+        assert_eq!(doc.verification_methods["atproto"], "did:key:zQ3sh");
+        assert_eq!(
+            doc.services["atproto_pds"].endpoint,
+            "https://pds.example.com"
+        );
+        assert_eq!(
+            doc.services["atproto_pds"].r#type,
+            "AtprotoPersonalDataServer"
+        );
+
+        let doc = web5_doc_from_did_data(DidData::default());
+        assert!(doc.verification_methods.is_empty());
+        assert!(doc.services.is_empty());
+    }
+}
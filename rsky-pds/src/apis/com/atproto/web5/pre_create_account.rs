@@ -1,14 +1,15 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::UserDidAuthOptional;
 use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::flags::SharedFlagStore;
 use crate::handle::{normalize_and_validate_handle, HandleValidationContext, HandleValidationOpts};
+use crate::rate_limit::ClientIp;
 use crate::SharedIdResolver;
 use crate::SharedSequencer;
-use aws_sdk_s3::Config;
 use rocket::serde::json::Json;
 use rocket::State;
 use rsky_lexicon::com::atproto::web5::{PreCreateAccountInput, PreCreateAccountOutput};
@@ -22,6 +23,7 @@ pub struct TransformedWeb5CreateAccountInput {
 
 //TODO: Potential for taking advantage of async better
 #[tracing::instrument(skip_all)]
+#[allow(clippy::too_many_arguments)]
 #[rocket::post(
     "/xrpc/com.atproto.web5.preCreateAccount",
     format = "json",
@@ -31,20 +33,31 @@ pub async fn pre_create_account(
     body: Json<PreCreateAccountInput>,
     _auth: UserDidAuthOptional,
     _sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     cfg: &State<ServerConfig>,
+    flags: &State<SharedFlagStore>,
     id_resolver: &State<SharedIdResolver>,
     account_manager: AccountManager,
     db: DbConn,
+    client_ip: ClientIp,
 ) -> Result<Json<PreCreateAccountOutput>, ApiError> {
     tracing::info!("PreCreating new user account");
+    // Fail early, createAccount checks again before anything is persisted
+    let ip = client_ip.0.map(|ip| ip.to_string()).unwrap_or_default();
+    super::signup::check_ip_cap(&cfg.signup, &ip, &account_manager).await?;
     // @TODO: Evaluate if we need to validate for entryway PDS
     let TransformedWeb5CreateAccountInput {
         handle: _,
         did,
         invite_code: _,
-    } = match validate_inputs_for_local_pds(cfg, id_resolver, body.into_inner(), &account_manager)
-        .await
+    } = match validate_inputs_for_local_pds(
+        cfg,
+        flags,
+        id_resolver,
+        body.into_inner(),
+        &account_manager,
+    )
+    .await
     {
         Ok(input) => input,
         Err(error) => {
@@ -53,17 +66,12 @@ pub async fn pre_create_account(
         }
     };
 
-    // Create new actor repo TODO: Proper rollback
-    let mut actor_store = ActorStore::new(
-        did.clone(),
-        S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-        db,
-    );
+    // Only builds the unsigned commit, nothing is persisted until createAccount
+    let actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
     match actor_store.pre_create_repo(Vec::new()).await {
         Ok(commit) => Ok(Json(commit)),
         Err(error) => {
             tracing::error!("Failed to create repo\n{:?}", error);
-            actor_store.destroy().await?;
             Err(ApiError::RuntimeError)
         }
     }
@@ -72,20 +80,28 @@ pub async fn pre_create_account(
 /// Validates Create Account Parameters and builds PLC Operation if needed
 pub async fn validate_inputs_for_local_pds(
     cfg: &State<ServerConfig>,
+    flags: &State<SharedFlagStore>,
     id_resolver: &State<SharedIdResolver>,
     input: PreCreateAccountInput,
     account_manager: &AccountManager,
 ) -> Result<TransformedWeb5CreateAccountInput, ApiError> {
     //Invite Code Validation
-    let invite_code = if cfg.invites.required && input.invite_code.is_none() {
+    let invite_code = if flags.get().invite_required && input.invite_code.is_none() {
         return Err(ApiError::InvalidInviteCode);
     } else {
         input.invite_code
     };
 
     // Normalize and Ensure Valid Handle
+    let handle = super::validate_handle(&input.handle, cfg)?;
+    // A handle claimed on chain first is held for that DID doc's key
+    if let Some(reservation) = account_manager.get_handle_reservation(&handle).await? {
+        if input.signing_key.as_deref() != Some(reservation.signing_key.as_str()) {
+            return Err(ApiError::HandleNotAvailable);
+        }
+    }
     let opts = HandleValidationOpts {
-        handle: input.handle,
+        handle,
         did: Some(input.did.clone()),
         allow_reserved: None,
     };
@@ -94,9 +110,6 @@ pub async fn validate_inputs_for_local_pds(
         id_resolver,
     };
     let handle = normalize_and_validate_handle(opts, validation_ctx).await?;
-    if !super::validate_handle(&handle) {
-        return Err(ApiError::InvalidHandle);
-    };
 
     // Check Handle is still available
     let handle_accnt = account_manager.get_account(&handle, None).await?;
@@ -1,17 +1,21 @@
 use crate::account_manager::helpers::account::AvailabilityFlags;
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::bbs::automod::check_writes;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::flags::SharedFlagStore;
 use crate::repo::prepare::{
-    prepare_create, prepare_delete, prepare_update, PrepareCreateOpts, PrepareDeleteOpts,
-    PrepareUpdateOpts,
+    prepare_create, prepare_delete, prepare_update, record_from_json, validation_within,
+    PrepareCreateOpts, PrepareDeleteOpts, PrepareUpdateOpts,
 };
+use crate::request_log::redacted;
 use crate::SharedSequencer;
+use crate::xrpc_server::body::{BoundedJson, WRITES_MAX_BYTES};
 use anyhow::bail;
-use aws_sdk_s3::Config;
 use futures::stream::{self, StreamExt};
 use lexicon_cid::Cid;
 use rocket::serde::json::Json;
@@ -23,10 +27,11 @@ use rsky_repo::types::PreparedWrite;
 use std::str::FromStr;
 
 async fn inner_pre_writes(
-    body: Json<PreDirectWritesInput>,
+    body: BoundedJson<PreDirectWritesInput, WRITES_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
     _sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<PreDirectWritesOutput, ApiError> {
@@ -53,6 +58,12 @@ async fn inner_pre_writes(
                 "Account is deactivated".to_string(),
             ));
         }
+        let collections = auth
+            .access
+            .credentials
+            .as_ref()
+            .and_then(|credentials| credentials.collections.clone());
+        let collections = &collections;
         let did = account.did;
         if did
             != auth
@@ -75,8 +86,8 @@ async fn inner_pre_writes(
             ));
         }
 
-        let writes: Vec<PreparedWrite> = stream::iter(tx.writes)
-            .then(|write| async move {
+        let writes: Vec<PreparedWrite> = stream::iter(tx.writes.into_iter().enumerate())
+            .then(|(index, write)| async move {
                 Ok::<PreparedWrite, anyhow::Error>(match write {
                     PreDirectWritesInputRefWrite::Create(write) => PreparedWrite::Create(
                         prepare_create(PrepareCreateOpts {
@@ -84,10 +95,14 @@ async fn inner_pre_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
+                            record: record_from_json(write.value, validate)?,
                             validate,
+                            collections: collections.clone(),
                         })
-                        .await?,
+                        .await
+                        .map_err(|error| {
+                            validation_within(error, &format!("writes[{index}].value"))
+                        })?,
                     ),
                     PreDirectWritesInputRefWrite::Update(write) => PreparedWrite::Update(
                         prepare_update(PrepareUpdateOpts {
@@ -95,10 +110,14 @@ async fn inner_pre_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
-                            record: serde_json::from_value(write.value)?,
+                            record: record_from_json(write.value, validate)?,
                             validate,
+                            collections: collections.clone(),
                         })
-                        .await?,
+                        .await
+                        .map_err(|error| {
+                            validation_within(error, &format!("writes[{index}].value"))
+                        })?,
                     ),
                     PreDirectWritesInputRefWrite::Delete(write) => {
                         PreparedWrite::Delete(prepare_delete(PrepareDeleteOpts {
@@ -106,6 +125,7 @@ async fn inner_pre_writes(
                             collection: write.collection,
                             rkey: write.rkey,
                             swap_cid: None,
+                            collections: collections.clone(),
                         })?)
                     }
                 })
@@ -123,12 +143,11 @@ async fn inner_pre_writes(
             None => None,
         };
 
-        let mut actor_store = ActorStore::new(
-            did.clone(),
-            S3BlobStore::new(did.clone(), s3_config.inner().clone()),
-            db,
-        );
+        let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
 
+        // Refuse what section rules reject before the client signs it. Holds
+        // and labels are applied once the signed commit comes back.
+        check_writes(&actor_store.record.db, &cfg.bbs, did, &writes).await?;
         let commit = actor_store
             .generate_commit(writes.clone(), swap_commit_cid)
             .await?;
@@ -160,15 +179,18 @@ async fn inner_pre_writes(
     data = "<body>"
 )]
 pub async fn pre_direct_writes(
-    body: Json<PreDirectWritesInput>,
+    body: BoundedJson<PreDirectWritesInput, WRITES_MAX_BYTES>,
     auth: AccessStandardIncludeChecks,
     sequencer: &State<SharedSequencer>,
-    s3_config: &State<Config>,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
     account_manager: AccountManager,
 ) -> Result<Json<PreDirectWritesOutput>, ApiError> {
-    tracing::debug!("@LOG: debug apply_writes {body:#?}");
-    match inner_pre_writes(body, auth, sequencer, s3_config, db, account_manager).await {
+    flags.check_writable(body.writes.iter().map(|write| write.collection()))?;
+    tracing::debug!("preDirectWrites input: {}", redacted(&*body));
+    match inner_pre_writes(body, auth, sequencer, blob_store, cfg, db, account_manager).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error:?}");
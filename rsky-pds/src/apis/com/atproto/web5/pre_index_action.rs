@@ -1,5 +1,6 @@
 use crate::apis::ApiError;
 use crate::plc::web5_types::{generate_challenge, get_didoc_from_chain};
+use crate::xrpc_server::body::{BoundedJson, INDEX_ACTION_MAX_BYTES};
 use rocket::serde::json::Json;
 use rsky_lexicon::com::atproto::web5::{
     PreIndexActionInput, PreIndexActionInputRef, PreIndexActionOutput, RefDeleteAccountIndex,
@@ -7,7 +8,7 @@ use rsky_lexicon::com::atproto::web5::{
 
 #[tracing::instrument(skip_all)]
 async fn inner_pre_index_action(
-    body: Json<PreIndexActionInput>,
+    body: BoundedJson<PreIndexActionInput, INDEX_ACTION_MAX_BYTES>,
 ) -> Result<PreIndexActionOutput, ApiError> {
     let PreIndexActionInput {
         did,
@@ -47,9 +48,8 @@ async fn inner_pre_index_action(
     data = "<body>"
 )]
 pub async fn pre_index_action(
-    body: Json<PreIndexActionInput>,
+    body: BoundedJson<PreIndexActionInput, INDEX_ACTION_MAX_BYTES>,
 ) -> Result<Json<PreIndexActionOutput>, ApiError> {
-    // @TODO: Add rate limiting
     match inner_pre_index_action(body).await {
         Ok(res) => Ok(Json(res)),
         Err(error) => Err(error),
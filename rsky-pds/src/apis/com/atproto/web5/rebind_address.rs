@@ -0,0 +1,197 @@
+use crate::account_manager::helpers::account::{AccountHelperError, AvailabilityFlags};
+use crate::account_manager::helpers::audit::{AuditAction, AuditEvent};
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::ActorStore;
+use crate::apis::com::atproto::web5::index_action::record_key_check_failure;
+use crate::apis::com::atproto::web5::signup;
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::metrics;
+use crate::plc::web5_types::{extract_timestamp, timestamp_check, verify_ckb_message_signature};
+use crate::telemetry;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_common::cbor_to_struct;
+use rsky_lexicon::com::atproto::web5::{RebindAddressInput, RebindAddressOutput};
+use rsky_repo::storage::readable_blockstore::ReadableBlockstore;
+use rsky_repo::storage::types::RepoStorage;
+use rsky_repo::types::Commit;
+use rsky_repo::util::verify_commit_sig;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+const REBIND_ADDRESS_LXM: &str = "com.atproto.web5.rebindAddress";
+
+fn decode_signature(signed_bytes: &str) -> Result<Vec<u8>, ApiError> {
+    let sig = if signed_bytes.starts_with("0x") || signed_bytes.starts_with("0X") {
+        &signed_bytes[2..]
+    } else {
+        signed_bytes
+    };
+    hex::decode(sig)
+        .map_err(|error| ApiError::InvalidRequest(format!("Signature decode error {error}")))
+}
+
+/// Whether `signing_key` signed the repo's current commit, i.e. is the key the
+/// repo is actually kept with rather than one merely listed somewhere.
+async fn is_repo_signing_key(
+    did: &str,
+    signing_key: &String,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<bool, ApiError> {
+    let actor_store = ActorStore::new(did.to_string(), blob_store.for_did(did.to_string()), db);
+    let storage_guard = actor_store.storage.read().await;
+    let repo_not_found = || {
+        ApiError::BadRequest(
+            "RepoNotFound".to_string(),
+            format!("Could not find repo for DID: {did}"),
+        )
+    };
+    let Some(root) = storage_guard.get_root().await else {
+        return Err(repo_not_found());
+    };
+    let Some(commit_bytes) = storage_guard.get_bytes(&root).await? else {
+        return Err(repo_not_found());
+    };
+    let commit: Commit = cbor_to_struct(commit_bytes)?;
+    Ok(verify_commit_sig(commit, signing_key).unwrap_or(false))
+}
+
+#[tracing::instrument(skip_all, fields(
+    did = %telemetry::hashed(&input.did),
+    ckb_addr = %telemetry::hashed(&input.ckb_addr),
+))]
+async fn inner_rebind_address(
+    input: RebindAddressInput,
+    cfg: &State<ServerConfig>,
+    account_manager: &AccountManager,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<RebindAddressOutput, ApiError> {
+    let did = input.did.to_lowercase();
+    let user = account_manager
+        .get_account(
+            &did,
+            Some(AvailabilityFlags {
+                include_deactivated: Some(true),
+                include_taken_down: Some(true),
+            }),
+        )
+        .await?
+        .ok_or(ApiError::AccountNotFound)?;
+    if user.takedown_ref.is_some() {
+        return Err(ApiError::AccountTakendown);
+    }
+    let old_ckb_addr = user.ckb_address.ok_or(ApiError::CkbAddrNotFound)?;
+    if old_ckb_addr == input.ckb_addr {
+        return Err(ApiError::InvalidRequest(
+            "Account is already bound to this address".to_string(),
+        ));
+    }
+    signup::check_ckb_address(&cfg.signup, &input.ckb_addr)?;
+
+    let message = &input.message;
+    if !timestamp_check(extract_timestamp(message)?)? {
+        return Err(ApiError::InvalidRequest("Sign message timeout".to_string()));
+    }
+    let address_line = format!("Address: {}", input.ckb_addr);
+    if !message
+        .lines()
+        .any(|line| line.contains(&input.statement()))
+        || !message.lines().any(|line| line.trim() == address_line)
+    {
+        return Err(ApiError::InvalidRequest(
+            "Message statement check error".to_string(),
+        ));
+    }
+
+    // The wallet is gone, so the repo signing key vouches for the account
+    if !is_repo_signing_key(&did, &input.signing_key, blob_store, db).await? {
+        record_key_check_failure(account_manager, &did, REBIND_ADDRESS_LXM, "signingKey").await;
+        return Err(ApiError::InvalidRequest(
+            "Signing key is not the repo signing key".to_string(),
+        ));
+    }
+    let hash = Sha256::digest(message);
+    let valid = rsky_crypto::verify::verify_signature(
+        &input.signing_key,
+        hash.as_ref(),
+        &decode_signature(&input.signed_bytes)?,
+        None,
+    )?;
+    metrics::record_signature_verification(REBIND_ADDRESS_LXM, valid);
+    if !valid {
+        record_key_check_failure(account_manager, &did, REBIND_ADDRESS_LXM, "signature").await;
+        return Err(ApiError::InvalidRequest(
+            "Signing key signature is invalid".to_string(),
+        ));
+    }
+    // and the new address proves it's held by the same person
+    let valid = verify_ckb_message_signature(
+        &input.ckb_addr,
+        message,
+        &decode_signature(&input.ckb_signed_bytes)?,
+    )
+    .map_err(|error| ApiError::InvalidCkbError(error.to_string()))?;
+    metrics::record_signature_verification(REBIND_ADDRESS_LXM, valid);
+    if !valid {
+        record_key_check_failure(account_manager, &did, REBIND_ADDRESS_LXM, "ckbSignature").await;
+        return Err(ApiError::InvalidRequest(
+            "Address signature is invalid".to_string(),
+        ));
+    }
+
+    match account_manager
+        .rebind_ckb_address(&did, &old_ckb_addr, &input.ckb_addr)
+        .await
+    {
+        Ok(()) => {}
+        Err(error) if error.downcast_ref::<AccountHelperError>().is_some() => {
+            return Err(ApiError::InvalidRequest(
+                "Address is already bound to an account".to_string(),
+            ))
+        }
+        Err(error) => return Err(error.into()),
+    }
+    account_manager
+        .try_record_audit_event(AuditEvent {
+            detail: Some(json!({
+                "oldCkbAddress": old_ckb_addr,
+                "newCkbAddress": input.ckb_addr,
+            })),
+            ..AuditEvent::new(AuditAction::RebindAddress, Some(did.clone()), did.clone())
+        })
+        .await;
+    Ok(RebindAddressOutput {
+        did,
+        ckb_address: input.ckb_addr,
+    })
+}
+
+/// Binds a web5 account to a new CKB address for users who lost the wallet
+/// but kept their signing key. The message must carry the new address and be
+/// signed by both the repo signing key and the new address. All sessions are
+/// revoked, as the old wallet may be in someone else's hands.
+#[rocket::post(
+    "/xrpc/com.atproto.web5.rebindAddress",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn rebind_address(
+    body: Json<RebindAddressInput>,
+    cfg: &State<ServerConfig>,
+    account_manager: AccountManager,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<Json<RebindAddressOutput>, ApiError> {
+    match inner_rebind_address(body.into_inner(), cfg, &account_manager, blob_store, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: failed to rebind address: {error:?}");
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::account_manager::AccountManager;
+use crate::apis::com::atproto::web5::{signup, validate_handle};
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::plc::web5_types::get_didoc_from_chain;
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::web5::{ReserveHandleInput, ReserveHandleOutput};
+
+async fn inner_reserve_handle(
+    input: ReserveHandleInput,
+    cfg: &State<ServerConfig>,
+    account_manager: &AccountManager,
+) -> Result<ReserveHandleOutput, ApiError> {
+    signup::check_ckb_address(&cfg.signup, &input.ckb_addr)?;
+    // the chain is the source of truth, so nothing in the request is trusted
+    // beyond the address to look up
+    let did_doc = get_didoc_from_chain(&input.ckb_addr).await?;
+    let signing_key = did_doc.verification_methods.get("atproto").ok_or_else(|| {
+        ApiError::InvalidRequest("DID doc has no atproto verification method".to_string())
+    })?;
+    let handle = did_doc
+        .also_known_as
+        .iter()
+        .filter_map(|aka| aka.strip_prefix("at://"))
+        .find_map(|handle| validate_handle(handle, cfg).ok())
+        .ok_or_else(|| {
+            ApiError::InvalidRequest("DID doc claims no handle on this PDS".to_string())
+        })?;
+    if account_manager.get_account(&handle, None).await?.is_some() {
+        return Err(ApiError::HandleNotAvailable);
+    }
+    match account_manager
+        .reserve_handle(
+            &handle,
+            &input.ckb_addr,
+            signing_key,
+            cfg.identity.handle_reservation_window,
+        )
+        .await?
+    {
+        Some(reservation) => Ok(ReserveHandleOutput {
+            handle,
+            expires_at: reservation.expires_at,
+        }),
+        None => Err(ApiError::HandleNotAvailable),
+    }
+}
+
+/// Holds the handle claimed by the DID doc on chain at `ckbAddr` for that
+/// address's key holder, so nobody else can sign up with it first. Needs no
+/// auth: the claim is read from the chain, not taken from the caller.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.reserveHandle",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn reserve_handle(
+    body: Json<ReserveHandleInput>,
+    cfg: &State<ServerConfig>,
+    account_manager: AccountManager,
+) -> Result<Json<ReserveHandleOutput>, ApiError> {
+    match inner_reserve_handle(body.into_inner(), cfg, &account_manager).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: failed to reserve handle: {error:?}");
+            Err(error)
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::ActorStore;
+use crate::apis::com::atproto::web5::direct_writes::{
+    authorize_direct_writes, parse_swap_commit, prepare_direct_writes,
+};
+use crate::apis::ApiError;
+use crate::auth_verifier::{AccessStandard, AccessStandardIncludeChecks};
+use crate::bbs::automod::check_writes;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::flags::SharedFlagStore;
+use crate::identity::SharedDidMethods;
+use crate::rate_limit::RateLimit;
+use crate::request_log::redacted;
+use crate::scheduled_writes::{self, SCHEDULED_WRITES_LXM};
+use crate::telemetry;
+use crate::xrpc_server::body::{BoundedJson, WRITES_MAX_BYTES};
+use anyhow::{bail, Result};
+use chrono::{Duration, Utc};
+use rocket::serde::json::Json;
+use rocket::State;
+use rsky_lexicon::com::atproto::web5::{
+    CancelScheduledWriteInput, ListScheduledWritesOutput, ScheduleDirectWritesInput, ScheduledWrite,
+};
+
+#[allow(clippy::too_many_arguments)]
+async fn inner_schedule_direct_writes(
+    body: ScheduleDirectWritesInput,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    db: DbConn,
+    account_manager: AccountManager,
+    chain_strict: bool,
+) -> Result<ScheduledWrite, ApiError> {
+    let ScheduleDirectWritesInput {
+        writes: input,
+        publish_at,
+    } = body;
+    let now = Utc::now();
+    if publish_at <= now {
+        return Err(ApiError::InvalidRequest(
+            "publishAt must be in the future".to_string(),
+        ));
+    }
+    if publish_at > now + Duration::milliseconds(cfg.scheduled_writes.max_horizon_ms as i64) {
+        return Err(ApiError::InvalidRequest(format!(
+            "publishAt can be at most {} hours ahead",
+            cfg.scheduled_writes.max_horizon_ms / (60 * 60 * 1000)
+        )));
+    }
+    let (did, collections) = authorize_direct_writes(
+        &input.repo,
+        input.ckb_addr.clone(),
+        &input.signing_key,
+        auth,
+        cfg,
+        did_methods,
+        &account_manager,
+        chain_strict,
+        SCHEDULED_WRITES_LXM,
+    )
+    .await?;
+    let writes =
+        prepare_direct_writes(&did, input.writes.clone(), input.validate, &collections).await?;
+    let swap_commit = parse_swap_commit(input.swap_commit.clone())?;
+
+    let mut actor_store = ActorStore::new(did.clone(), blob_store.for_did(did.clone()), db);
+    // a post automod or an archived section would refuse is turned away now
+    // rather than at publishAt
+    check_writes(&actor_store.record.db, &cfg.bbs, &did, &writes).await?;
+    // a bad signature or stale swap is caught up front, nothing is applied yet
+    let write_lock = actor_store.lock_writes().await?;
+    actor_store
+        .verify_commit(
+            writes,
+            swap_commit,
+            input.signing_key.clone(),
+            input.root.clone(),
+        )
+        .await?;
+    drop(write_lock);
+
+    let max_pending = cfg.scheduled_writes.max_pending;
+    let publish_at = scheduled_writes::format_publish_at(&publish_at);
+    let row = actor_store
+        .record
+        .db
+        .run(move |conn| {
+            if scheduled_writes::count_pending(conn, &did)? >= max_pending {
+                return Ok(None);
+            }
+            scheduled_writes::insert(conn, &did, &input, publish_at).map(Some)
+        })
+        .await?
+        .ok_or_else(|| {
+            ApiError::QuotaExceeded(format!(
+                "At most {max_pending} writes can be scheduled at once"
+            ))
+        })?;
+    Ok(scheduled_writes::to_view(row)?)
+}
+
+/// Takes a batch of writes signed just as for directWrites and applies it at
+/// `publishAt`, e.g. for a moderator to line up an announcement. The
+/// signature is checked now. Since the commit builds on the repo's current
+/// head, any other write to the repo before then fails the scheduled one and
+/// the client has to sign it again.
+#[tracing::instrument(skip_all, fields(
+    did = %telemetry::hashed(&body.writes.repo),
+    ckb_addr = %body.writes.ckb_addr.as_deref().map(telemetry::hashed).unwrap_or_default(),
+))]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.scheduleDirectWrites",
+    format = "json",
+    data = "<body>"
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn schedule_direct_writes(
+    body: BoundedJson<ScheduleDirectWritesInput, WRITES_MAX_BYTES>,
+    auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
+    flags: &State<SharedFlagStore>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<Json<ScheduledWrite>, ApiError> {
+    flags.check_writable(body.writes.writes.iter().map(|write| write.collection()))?;
+    tracing::debug!("scheduleDirectWrites input: {}", redacted(&*body));
+    match inner_schedule_direct_writes(
+        body.into_inner(),
+        auth,
+        blob_store,
+        cfg,
+        did_methods,
+        db,
+        account_manager,
+        flags.get().chain_strict,
+    )
+    .await
+    {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error:?}");
+            Err(error)
+        }
+    }
+}
+
+async fn inner_list_scheduled_writes(
+    did: String,
+    limit: u16,
+    cursor: Option<String>,
+    db: DbConn,
+) -> Result<ListScheduledWritesOutput> {
+    if limit == 0 || limit > 100 {
+        bail!("Error: limit must be between 1 and 100")
+    }
+    let scheduled = db
+        .run(move |conn| scheduled_writes::list(conn, &did, limit as i64, cursor))
+        .await?
+        .into_iter()
+        .map(scheduled_writes::to_view)
+        .collect::<Result<Vec<ScheduledWrite>>>()?;
+    let cursor = match scheduled.len() == limit as usize {
+        true => scheduled
+            .last()
+            .map(|last| format!("{}::{}", last.publish_at, last.id)),
+        false => None,
+    };
+    Ok(ListScheduledWritesOutput { scheduled, cursor })
+}
+
+/// The requesting account's scheduled writes, soonest first, with how the
+/// ones already due went.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/com.atproto.web5.listScheduledWrites?<limit>&<cursor>")]
+pub async fn list_scheduled_writes(
+    limit: Option<u16>,
+    cursor: Option<String>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<ListScheduledWritesOutput>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    match inner_list_scheduled_writes(did, limit.unwrap_or(50), cursor, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+/// Drops one of the requesting account's scheduled writes before it's
+/// published.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.cancelScheduledWrite",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn cancel_scheduled_write(
+    body: Json<CancelScheduledWriteInput>,
+    auth: AccessStandard,
+    db: DbConn,
+) -> Result<Json<ScheduledWrite>, ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let Ok(id) = body.id.parse::<i64>() else {
+        return Err(ApiError::InvalidRequest(format!(
+            "Unknown scheduled write `{}`",
+            body.id
+        )));
+    };
+    let res = db
+        .run(move |conn| scheduled_writes::cancel(conn, &did, id))
+        .await
+        .and_then(scheduled_writes::to_view);
+    match res {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(error.into())
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::helpers::audit::{AuditAction, AuditEvent};
+use crate::account_manager::{AccountManager, SetPasswordOpts};
+use crate::apis::ApiError;
+use crate::auth_verifier::AccessFull;
+use rocket::serde::json::Json;
+use rsky_lexicon::com::atproto::web5::SetPasswordInput;
+use serde_json::json;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+async fn inner_set_password(
+    body: Json<SetPasswordInput>,
+    auth: AccessFull,
+    account_manager: AccountManager,
+) -> Result<(), ApiError> {
+    let did = auth.access.credentials.unwrap().did.unwrap();
+    let SetPasswordInput {
+        password,
+        password_login,
+    } = body.into_inner();
+    let account = account_manager
+        .get_account(
+            &did,
+            Some(AvailabilityFlags {
+                include_deactivated: Some(true),
+                include_taken_down: None,
+            }),
+        )
+        .await?
+        .ok_or(ApiError::AccountNotFound)?;
+    if account.ckb_address.is_none() {
+        return Err(ApiError::InvalidRequest(
+            "Only web5 accounts set their password this way, use com.atproto.server.requestPasswordReset".to_string(),
+        ));
+    }
+    if let Some(ref password) = password {
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(ApiError::InvalidPassword);
+        }
+    }
+    // The password the account was created with was never shown to anyone
+    if password_login && password.is_none() && !account_manager.password_login_enabled(&did).await?
+    {
+        return Err(ApiError::InvalidRequest(
+            "A password is required to turn on password login".to_string(),
+        ));
+    }
+    let changed_password = password.is_some();
+    account_manager
+        .set_password(SetPasswordOpts {
+            did: did.clone(),
+            password,
+            password_login,
+        })
+        .await?;
+    account_manager
+        .try_record_audit_event(AuditEvent {
+            detail: Some(json!({
+                "passwordChanged": changed_password,
+                "passwordLogin": password_login,
+            })),
+            ..AuditEvent::new(AuditAction::SetPassword, Some(did.clone()), did)
+        })
+        .await;
+    Ok(())
+}
+
+/// Lets a web5 account, which normally signs in with its wallet, set a
+/// password to sign in with through createSession when the wallet isn't at
+/// hand. Needs a step-up token, so the wallet still has to approve it.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/xrpc/com.atproto.web5.setPassword", format = "json", data = "<body>")]
+pub async fn set_password(
+    body: Json<SetPasswordInput>,
+    auth: AccessFull,
+    account_manager: AccountManager,
+) -> Result<(), ApiError> {
+    match inner_set_password(body, auth, account_manager).await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: {error}");
+            Err(error)
+        }
+    }
+}
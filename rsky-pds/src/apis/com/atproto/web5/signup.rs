@@ -0,0 +1,135 @@
+use crate::account_manager::AccountManager;
+use crate::apis::ApiError;
+use crate::config::{CaptchaConfig, SignupConfig};
+use crate::mailer;
+use email_address::EmailAddress;
+use std::time::Duration;
+
+const CAPTCHA_VERIFY_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Deserialize)]
+struct CaptchaVerifyResponse {
+    success: bool,
+    #[serde(rename = "error-codes", default)]
+    error_codes: Vec<String>,
+}
+
+pub fn check_ckb_address(cfg: &SignupConfig, ckb_addr: &str) -> Result<(), ApiError> {
+    if cfg
+        .denied_ckb_addresses
+        .iter()
+        .any(|denied| denied == ckb_addr)
+    {
+        return Err(ApiError::InvalidCkbError(
+            "This address may not create accounts".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the optional email given at signup and checks no account has it,
+/// returning it lowercased.
+pub async fn check_email(
+    email: Option<&str>,
+    account_manager: &AccountManager,
+) -> Result<Option<String>, ApiError> {
+    let Some(email) = email else {
+        return Ok(None);
+    };
+    let email = email.to_lowercase();
+    if !EmailAddress::is_valid(&email)
+        || !mailchecker::is_valid(&email)
+        || mailer::is_placeholder_email(&email)
+    {
+        return Err(ApiError::InvalidEmail);
+    }
+    if account_manager
+        .get_account_by_email(&email, None)
+        .await?
+        .is_some()
+    {
+        return Err(ApiError::EmailNotAvailable);
+    }
+    Ok(Some(email))
+}
+
+pub async fn check_ip_cap(
+    cfg: &SignupConfig,
+    ip: &str,
+    account_manager: &AccountManager,
+) -> Result<(), ApiError> {
+    let Some(cap) = cfg.daily_ip_cap else {
+        return Ok(());
+    };
+    let count = account_manager.count_daily_signups_from_ip(ip).await?;
+    if count as u64 >= cap {
+        tracing::warn!("Signup cap reached for {ip}");
+        return Err(ApiError::RateLimitExceeded);
+    }
+    Ok(())
+}
+
+pub async fn verify_captcha(
+    cfg: &SignupConfig,
+    token: Option<&str>,
+    ip: &str,
+) -> Result<(), ApiError> {
+    let Some(CaptchaConfig { provider, secret }) = &cfg.captcha else {
+        return Ok(());
+    };
+    let Some(token) = token else {
+        return Err(ApiError::InvalidRequest(
+            "A captcha token is required to create an account".to_string(),
+        ));
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(CAPTCHA_VERIFY_TIMEOUT_SECS))
+        .build()
+        .map_err(|error| {
+            tracing::error!("@LOG: ERROR: {error}");
+            ApiError::RuntimeError
+        })?;
+    let res = client
+        .post(provider.verify_url())
+        .form(&[
+            ("secret", secret.as_str()),
+            ("response", token),
+            ("remoteip", ip),
+        ])
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    let verified = match res {
+        Ok(res) => res.json::<CaptchaVerifyResponse>().await,
+        Err(error) => Err(error),
+    };
+    match verified {
+        Ok(CaptchaVerifyResponse { success: true, .. }) => Ok(()),
+        Ok(CaptchaVerifyResponse { error_codes, .. }) => {
+            tracing::info!("Captcha rejected: {error_codes:?}");
+            Err(ApiError::InvalidRequest(
+                "Captcha verification failed".to_string(),
+            ))
+        }
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: captcha verification failed: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denies_listed_ckb_addresses() {
+        let cfg = SignupConfig {
+            daily_ip_cap: None,
+            captcha: None,
+            denied_ckb_addresses: vec!["ckt1denied".to_string()],
+        };
+        assert!(check_ckb_address(&cfg, "ckt1denied").is_err());
+        assert!(check_ckb_address(&cfg, "ckt1allowed").is_ok());
+    }
+}
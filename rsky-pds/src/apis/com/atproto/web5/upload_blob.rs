@@ -1,18 +1,34 @@
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::account_manager::helpers::usage::StorageQuotaError;
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::{BlobStore, SharedBlobStore};
+use crate::actor_store::blob::BlobMetadata;
 use crate::actor_store::ActorStore;
 use crate::apis::ApiError;
 use crate::auth_verifier::AccessStandardIncludeChecks;
+use crate::config::ServerConfig;
 use crate::db::DbConn;
+use crate::models::BlobUpload;
+use crate::rate_limit::RateLimit;
 use anyhow::{Error, Result};
-use aws_sdk_s3::Config;
-use rocket::data::Data;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use lexicon_cid::Cid;
+use rocket::data::{Data, ToByteUnit};
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
 use rocket::serde::json::Json;
 use rocket::{Request, State};
-use rsky_common::BadContentTypeError;
+use rsky_common::ipld::sha256_to_cid;
+use rsky_common::time::DAY;
+use rsky_common::{get_random_str, now, BadContentTypeError, RFC3339_VARIANT};
 use rsky_lexicon::com::atproto::repo::{Blob, BlobOutput};
+use rsky_lexicon::com::atproto::web5::{
+    CreateUploadInput, FinalizeUploadInput, UploadStatusOutput,
+};
 use rsky_repo::types::{BlobConstraint, PreparedBlobRef};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
 
 #[derive(Clone)]
 pub struct ContentType {
@@ -37,21 +53,7 @@ impl<'r> FromRequest<'r> for ContentType {
     }
 }
 
-async fn inner_upload_blob(
-    auth: AccessStandardIncludeChecks,
-    blob: Data<'_>,
-    content_type: ContentType,
-    s3_config: &State<Config>,
-    db: DbConn,
-) -> Result<BlobOutput> {
-    let requester = auth.access.credentials.unwrap().did.unwrap();
-
-    let actor_store = ActorStore::new(
-        requester.clone(),
-        S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-        db,
-    );
-
+async fn ensure_bucket(actor_store: &ActorStore) -> Result<()> {
     if !actor_store
         .blob
         .blobstore
@@ -66,11 +68,38 @@ async fn inner_upload_blob(
             .await
             .map_err(|e| Error::msg(format!("{:?}", e)))?;
     }
+    Ok(())
+}
+
+async fn inner_upload_blob(
+    auth: AccessStandardIncludeChecks,
+    blob: Data<'_>,
+    content_type: ContentType,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<BlobOutput> {
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+    let quota = cfg.quota.account_storage_bytes;
+    account_manager
+        .assert_storage_available(&requester, 0, quota)
+        .await?;
+
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
+    ensure_bucket(&actor_store).await?;
 
     let metadata = actor_store
         .blob
         .upload_blob_and_get_metadata(content_type.name, blob)
         .await?;
+    account_manager
+        .assert_storage_available(&requester, metadata.size, quota)
+        .await?;
+    persist_blob(&actor_store, metadata).await
+}
+
+async fn persist_blob(actor_store: &ActorStore, metadata: BlobMetadata) -> Result<BlobOutput> {
     let blobref = actor_store.blob.track_untethered_blob(metadata).await?;
 
     // make the blob permanent if an associated record is already indexed
@@ -107,18 +136,535 @@ async fn inner_upload_blob(
 
 #[tracing::instrument(skip_all)]
 #[rocket::post("/xrpc/com.atproto.repo.uploadBlob", data = "<blob>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn upload_blob(
     auth: AccessStandardIncludeChecks,
+    _rate_limit: RateLimit,
     blob: Data<'_>,
     content_type: ContentType,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
     db: DbConn,
+    account_manager: AccountManager,
 ) -> Result<Json<BlobOutput>, ApiError> {
-    match inner_upload_blob(auth, blob, content_type, s3_config, db).await {
+    match inner_upload_blob(
+        auth,
+        blob,
+        content_type,
+        blob_store,
+        cfg,
+        db,
+        account_manager,
+    )
+    .await
+    {
         Ok(res) => Ok(Json(res)),
-        Err(error) => {
-            tracing::error!("{error:?}");
-            Err(ApiError::RuntimeError)
+        Err(error) => Err(to_api_error(error)),
+    }
+}
+
+/// Largest blob accepted through the resumable flow, matching uploadBlob
+const MAX_RESUMABLE_UPLOAD_SIZE: i64 = 100 * 1024 * 1024;
+/// Largest chunk accepted by a single appendUpload call
+const MAX_UPLOAD_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+/// How long an unfinished upload is kept around for the client to resume
+const UPLOAD_TTL_MS: i64 = DAY as i64;
+/// Unfinished uploads a single account may have open at once
+const MAX_OPEN_UPLOADS_PER_DID: i64 = 5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ResumableUploadError {
+    #[error("Upload not found or expired")]
+    UploadNotFound,
+    #[error("Expected chunk at offset {0}")]
+    InvalidOffset(i64),
+    #[error("Blob size must be between 1 and {MAX_RESUMABLE_UPLOAD_SIZE} bytes")]
+    InvalidSize,
+    #[error(
+        "Chunk exceeds the declared blob size or the {MAX_UPLOAD_CHUNK_SIZE} byte chunk limit"
+    )]
+    ChunkTooLarge,
+    #[error("Upload incomplete, received {0} of {1} bytes")]
+    IncompleteUpload(i64, i64),
+    #[error("Uploaded blob has cid {0} which does not match the expected cid")]
+    CidMismatch(String),
+    #[error("Invalid cid: {0}")]
+    InvalidCid(String),
+    #[error("At most {MAX_OPEN_UPLOADS_PER_DID} uploads can be in progress at once")]
+    TooManyUploads,
+}
+
+impl From<&ResumableUploadError> for ApiError {
+    fn from(error: &ResumableUploadError) -> Self {
+        let name = match error {
+            ResumableUploadError::UploadNotFound => "UploadNotFound",
+            ResumableUploadError::InvalidOffset(_) => "InvalidOffset",
+            ResumableUploadError::InvalidSize | ResumableUploadError::ChunkTooLarge => {
+                "BlobTooLarge"
+            }
+            ResumableUploadError::IncompleteUpload(_, _) => "IncompleteUpload",
+            ResumableUploadError::CidMismatch(_) | ResumableUploadError::InvalidCid(_) => {
+                "InvalidCid"
+            }
+            ResumableUploadError::TooManyUploads => "TooManyUploads",
+        };
+        ApiError::BadRequest(name.to_string(), error.to_string())
+    }
+}
+
+fn to_api_error(error: Error) -> ApiError {
+    #[cfg(feature = "image-processing")]
+    if let Some(invalid) = error.downcast_ref::<crate::image::processing::InvalidImageError>() {
+        return ApiError::BadRequest("InvalidImage".to_string(), invalid.to_string());
+    }
+    if let Some(quota_error) = error.downcast_ref::<StorageQuotaError>() {
+        return ApiError::QuotaExceeded(quota_error.to_string());
+    }
+    match error.downcast_ref::<ResumableUploadError>() {
+        Some(upload_error) => upload_error.into(),
+        None => {
+            tracing::error!("@LOG: ERROR: {error}");
+            ApiError::RuntimeError
         }
     }
 }
+
+fn upload_status(id: String, offset: i32, size: i32, expires_at: String) -> UploadStatusOutput {
+    UploadStatusOutput {
+        upload_id: id,
+        offset: offset as i64,
+        size: size as i64,
+        expires_at,
+    }
+}
+
+/// Temp key of the `index`th chunk of an upload in the blobstore
+fn chunk_key(upload_id: &str, index: i32) -> String {
+    format!("{upload_id}.{index}")
+}
+
+fn chunk_keys(upload_id: &str, chunk_count: i32) -> Vec<String> {
+    (0..chunk_count)
+        .map(|index| chunk_key(upload_id, index))
+        .collect()
+}
+
+/// Reassembles a staged upload, hashing the chunks as they're read back
+async fn read_staged(
+    blob_store: &dyn BlobStore,
+    upload_id: &str,
+    chunk_count: i32,
+    size: i32,
+) -> Result<(Vec<u8>, Cid)> {
+    let mut hasher = Sha256::new();
+    let mut bytes = Vec::with_capacity(size.max(0) as usize);
+    for key in chunk_keys(upload_id, chunk_count) {
+        let chunk = blob_store.get_temp_bytes(key).await?;
+        hasher.update(&chunk);
+        bytes.extend_from_slice(&chunk);
+    }
+    if bytes.len() != size as usize {
+        return Err(ResumableUploadError::IncompleteUpload(bytes.len() as i64, size as i64).into());
+    }
+    Ok((bytes, sha256_to_cid(hasher.finalize().to_vec())))
+}
+
+async fn delete_staged(blob_store: &dyn BlobStore, upload_id: &str, chunk_count: i32) {
+    if let Err(error) = blob_store
+        .delete_temp_many(chunk_keys(upload_id, chunk_count))
+        .await
+    {
+        tracing::warn!("Failed to delete staged chunks of upload {upload_id}: {error}");
+    }
+}
+
+fn parse_expected_cid(cid: Option<String>) -> Result<Option<Cid>> {
+    match cid {
+        None => Ok(None),
+        Some(cid) => match Cid::from_str(&cid) {
+            Ok(cid) => Ok(Some(cid)),
+            Err(_) => Err(ResumableUploadError::InvalidCid(cid).into()),
+        },
+    }
+}
+
+async fn inner_create_upload(
+    body: Json<CreateUploadInput>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<UploadStatusOutput> {
+    use crate::schema::pds::blob_upload::dsl as BlobUploadSchema;
+
+    let CreateUploadInput {
+        mime_type,
+        size,
+        cid,
+    } = body.into_inner();
+    if size <= 0 || size > MAX_RESUMABLE_UPLOAD_SIZE {
+        return Err(ResumableUploadError::InvalidSize.into());
+    }
+    let expected_cid = parse_expected_cid(cid)?;
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+    // fail before the client spends time sending chunks that can't be stored
+    account_manager
+        .assert_storage_available(&requester, size, cfg.quota.account_storage_bytes)
+        .await?;
+    let created_at = now();
+    let expires_at = format!(
+        "{}",
+        (Utc::now() + ChronoDuration::milliseconds(UPLOAD_TTL_MS)).format(RFC3339_VARIANT)
+    );
+    let upload = BlobUpload {
+        id: get_random_str(),
+        did: requester,
+        mime_type,
+        size: size as i32,
+        bytes_received: 0,
+        expected_cid: expected_cid.map(|cid| cid.to_string()),
+        created_at: created_at.clone(),
+        expires_at,
+        chunk_count: 0,
+    };
+    let status = upload_status(upload.id.clone(), 0, upload.size, upload.expires_at.clone());
+    let expired = db
+        .run(move |conn| {
+            conn.transaction(|conn| {
+                // abandoned uploads are cleaned up lazily whenever a new one starts
+                let expired = delete(BlobUploadSchema::blob_upload)
+                    .filter(BlobUploadSchema::expiresAt.lt(&created_at))
+                    .returning((
+                        BlobUploadSchema::id,
+                        BlobUploadSchema::did,
+                        BlobUploadSchema::chunkCount,
+                    ))
+                    .get_results::<(String, String, i32)>(conn)?;
+                let open = BlobUploadSchema::blob_upload
+                    .filter(BlobUploadSchema::did.eq(&upload.did))
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if open >= MAX_OPEN_UPLOADS_PER_DID {
+                    return Err(ResumableUploadError::TooManyUploads.into());
+                }
+                insert_into(BlobUploadSchema::blob_upload)
+                    .values(&upload)
+                    .execute(conn)?;
+                Ok::<_, Error>(expired)
+            })
+        })
+        .await?;
+    for (id, did, chunk_count) in expired {
+        delete_staged(blob_store.for_did(did).as_ref(), &id, chunk_count).await;
+    }
+    Ok(status)
+}
+
+async fn get_upload_status(
+    db: &DbConn,
+    did: String,
+    upload_id: String,
+) -> Result<UploadStatusOutput> {
+    use crate::schema::pds::blob_upload::dsl as BlobUploadSchema;
+
+    let found = db
+        .run(move |conn| {
+            BlobUploadSchema::blob_upload
+                .filter(BlobUploadSchema::id.eq(&upload_id))
+                .filter(BlobUploadSchema::did.eq(&did))
+                .filter(BlobUploadSchema::expiresAt.gt(now()))
+                .select((
+                    BlobUploadSchema::id,
+                    BlobUploadSchema::bytesReceived,
+                    BlobUploadSchema::size,
+                    BlobUploadSchema::expiresAt,
+                ))
+                .first::<(String, i32, i32, String)>(conn)
+                .optional()
+        })
+        .await?;
+    match found {
+        Some((id, offset, size, expires_at)) => Ok(upload_status(id, offset, size, expires_at)),
+        None => Err(ResumableUploadError::UploadNotFound.into()),
+    }
+}
+
+async fn inner_append_upload(
+    upload_id: String,
+    offset: i64,
+    chunk: Data<'_>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<UploadStatusOutput> {
+    use crate::schema::pds::blob_upload::dsl as BlobUploadSchema;
+
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+    let chunk = chunk
+        .open(MAX_UPLOAD_CHUNK_SIZE.bytes())
+        .into_bytes()
+        .await?;
+    if !chunk.is_complete() {
+        return Err(ResumableUploadError::ChunkTooLarge.into());
+    }
+    let chunk = chunk.into_inner();
+    let chunk_len = chunk.len() as i32;
+
+    let did = requester.clone();
+    let id = upload_id.clone();
+    // Claims the chunk's place before staging it. Only applies if the chunk
+    // starts exactly where the last one ended, so a retried or out of order
+    // chunk can never corrupt the upload
+    let claimed = db
+        .run(move |conn| {
+            update(BlobUploadSchema::blob_upload)
+                .filter(BlobUploadSchema::id.eq(&id))
+                .filter(BlobUploadSchema::did.eq(&did))
+                .filter(BlobUploadSchema::expiresAt.gt(now()))
+                .filter(BlobUploadSchema::bytesReceived.eq(offset as i32))
+                .filter((BlobUploadSchema::bytesReceived + chunk_len).le(BlobUploadSchema::size))
+                .set((
+                    BlobUploadSchema::bytesReceived.eq(BlobUploadSchema::bytesReceived + chunk_len),
+                    BlobUploadSchema::chunkCount.eq(BlobUploadSchema::chunkCount + 1),
+                ))
+                .returning((
+                    BlobUploadSchema::bytesReceived,
+                    BlobUploadSchema::size,
+                    BlobUploadSchema::expiresAt,
+                    BlobUploadSchema::chunkCount,
+                ))
+                .get_result::<(i32, i32, String, i32)>(conn)
+                .optional()
+        })
+        .await?;
+    let (received, size, expires_at, chunk_count) = match claimed {
+        Some(claimed) => claimed,
+        None => {
+            let status = get_upload_status(&db, requester, upload_id).await?;
+            return if status.offset != offset {
+                Err(ResumableUploadError::InvalidOffset(status.offset).into())
+            } else {
+                Err(ResumableUploadError::ChunkTooLarge.into())
+            };
+        }
+    };
+
+    let staged = blob_store
+        .for_did(requester.clone())
+        .put_temp_with_key(chunk_key(&upload_id, chunk_count - 1), chunk)
+        .await;
+    if let Err(error) = staged {
+        // hand the offset back so the client can retry the same chunk
+        let id = upload_id.clone();
+        db.run(move |conn| {
+            update(BlobUploadSchema::blob_upload)
+                .filter(BlobUploadSchema::id.eq(&id))
+                .filter(BlobUploadSchema::did.eq(&requester))
+                .filter(BlobUploadSchema::bytesReceived.eq(received))
+                .filter(BlobUploadSchema::chunkCount.eq(chunk_count))
+                .set((
+                    BlobUploadSchema::bytesReceived.eq(offset as i32),
+                    BlobUploadSchema::chunkCount.eq(chunk_count - 1),
+                ))
+                .execute(conn)
+        })
+        .await?;
+        return Err(error);
+    }
+    Ok(upload_status(upload_id, received, size, expires_at))
+}
+
+async fn inner_finalize_upload(
+    body: Json<FinalizeUploadInput>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<BlobOutput> {
+    use crate::schema::pds::blob_upload::dsl as BlobUploadSchema;
+
+    let FinalizeUploadInput { upload_id, cid } = body.into_inner();
+    let requested_cid = parse_expected_cid(cid)?;
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+
+    let did = requester.clone();
+    let id = upload_id.clone();
+    let upload = db
+        .run(move |conn| {
+            BlobUploadSchema::blob_upload
+                .filter(BlobUploadSchema::id.eq(&id))
+                .filter(BlobUploadSchema::did.eq(&did))
+                .filter(BlobUploadSchema::expiresAt.gt(now()))
+                .select(BlobUpload::as_select())
+                .first(conn)
+                .optional()
+        })
+        .await?;
+    let upload = match upload {
+        Some(upload) => upload,
+        None => return Err(ResumableUploadError::UploadNotFound.into()),
+    };
+    if upload.bytes_received != upload.size {
+        return Err(ResumableUploadError::IncompleteUpload(
+            upload.bytes_received as i64,
+            upload.size as i64,
+        )
+        .into());
+    }
+
+    let staging = blob_store.for_did(requester.clone());
+    let (bytes, cid) = read_staged(
+        staging.as_ref(),
+        &upload.id,
+        upload.chunk_count,
+        upload.size,
+    )
+    .await?;
+    let expected = match requested_cid {
+        Some(requested_cid) => Some(requested_cid),
+        None => parse_expected_cid(upload.expected_cid)?,
+    };
+    if let Some(expected) = expected {
+        if expected != cid {
+            return Err(ResumableUploadError::CidMismatch(cid.to_string()).into());
+        }
+    }
+    account_manager
+        .assert_storage_available(
+            &requester,
+            upload.size as i64,
+            cfg.quota.account_storage_bytes,
+        )
+        .await?;
+
+    let actor_store = ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
+    ensure_bucket(&actor_store).await?;
+    let metadata = actor_store
+        .blob
+        .upload_bytes_and_get_metadata(upload.mime_type, bytes)
+        .await?;
+    let output = persist_blob(&actor_store, metadata).await?;
+
+    actor_store
+        .blob
+        .db
+        .run(move |conn| {
+            delete(BlobUploadSchema::blob_upload)
+                .filter(BlobUploadSchema::id.eq(&upload_id))
+                .execute(conn)
+        })
+        .await?;
+    delete_staged(staging.as_ref(), &upload.id, upload.chunk_count).await;
+    Ok(output)
+}
+
+/// Starts a resumable upload for clients on unreliable connections. The blob
+/// is then sent in chunks with appendUpload and completed with finalizeUpload.
+/// Chunks are staged in the blobstore's temp area until then, and an account
+/// can only have a few uploads open at a time.
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.createUpload",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn create_upload(
+    body: Json<CreateUploadInput>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<Json<UploadStatusOutput>, ApiError> {
+    match inner_create_upload(body, auth, blob_store, cfg, db, account_manager).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(to_api_error(error)),
+    }
+}
+
+/// Appends a chunk starting at `offset`. On a mismatch the error carries the
+/// offset the server expects, which is also available from getUpload.
+#[tracing::instrument(skip_all)]
+#[allow(non_snake_case)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.appendUpload?<uploadId>&<offset>",
+    data = "<chunk>"
+)]
+pub async fn append_upload(
+    uploadId: String,
+    offset: i64,
+    chunk: Data<'_>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    db: DbConn,
+) -> Result<Json<UploadStatusOutput>, ApiError> {
+    match inner_append_upload(uploadId, offset, chunk, auth, blob_store, db).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(to_api_error(error)),
+    }
+}
+
+#[tracing::instrument(skip_all)]
+#[allow(non_snake_case)]
+#[rocket::get("/xrpc/com.atproto.web5.getUpload?<uploadId>")]
+pub async fn get_upload(
+    uploadId: String,
+    auth: AccessStandardIncludeChecks,
+    db: DbConn,
+) -> Result<Json<UploadStatusOutput>, ApiError> {
+    let requester = auth.access.credentials.unwrap().did.unwrap();
+    match get_upload_status(&db, requester, uploadId).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(to_api_error(error)),
+    }
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::post(
+    "/xrpc/com.atproto.web5.finalizeUpload",
+    format = "json",
+    data = "<body>"
+)]
+pub async fn finalize_upload(
+    body: Json<FinalizeUploadInput>,
+    auth: AccessStandardIncludeChecks,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+    account_manager: AccountManager,
+) -> Result<Json<BlobOutput>, ApiError> {
+    match inner_finalize_upload(body, auth, blob_store, cfg, db, account_manager).await {
+        Ok(res) => Ok(Json(res)),
+        Err(error) => Err(to_api_error(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor_store::memory::MemoryBlobStore;
+
+    #[tokio::test]
+    async fn reassembles_staged_chunks_in_order() {
+        let blob_store = MemoryBlobStore::creator()("did:web5:alice".to_string());
+        blob_store
+            .put_temp_with_key(chunk_key("upload", 1), b" world".to_vec())
+            .await
+            .unwrap();
+        blob_store
+            .put_temp_with_key(chunk_key("upload", 0), b"hello".to_vec())
+            .await
+            .unwrap();
+
+        let (bytes, cid) = read_staged(blob_store.as_ref(), "upload", 2, 11)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"hello world".to_vec());
+        assert_eq!(cid, sha256_to_cid(Sha256::digest(b"hello world").to_vec()));
+
+        let short = read_staged(blob_store.as_ref(), "upload", 1, 11).await;
+        assert!(short.is_err());
+    }
+}
@@ -1,12 +1,24 @@
+use crate::account_manager::helpers::usage::StorageQuotaError;
+use crate::actor_store::ConcurrentWriteError;
 use crate::auth_verifier::AccessStandard;
+use crate::bbs::automod::AutomodRejectedError;
+use crate::bbs::dm::ConvoNotFoundError;
+use crate::bbs::draft::{DraftNotFoundError, TooManyDraftsError};
+use crate::bbs::section::SectionArchivedError;
 use crate::handle;
 use crate::handle::errors::ErrorKind;
 use crate::pipethrough::{pipethrough_procedure, pipethrough_procedure_post, ProxyRequest};
+use crate::repo::prepare::CollectionNotPermittedError;
+use crate::scheduled_writes::ScheduledWriteNotPendingError;
+use crate::xrpc_server::types::{InvalidRequestError, XRPCError};
 use anyhow::{Error, Result};
 use rocket::http::{ContentType, Header, Status};
 use rocket::request::FromParam;
 use rocket::serde::json::Json;
 use rocket::{response, Data, Request, Responder};
+use rsky_lexicon::error::{ErrorCode, XrpcErrorBody};
+use rsky_lexicon::schema::ValidationError;
+use rsky_repo::error::SignedRootError;
 
 #[derive(Responder)]
 #[response(status = 200)]
@@ -57,7 +69,7 @@ pub async fn bsky_api_get_forwarder(
         }
         Err(error) => {
             tracing::error!("@LOG: ERROR: {error}");
-            Err(ApiError::RuntimeError)
+            Err(error.into())
         }
     }
 }
@@ -93,6 +105,8 @@ pub enum ApiError {
     InvalidLogin,
     AccountTakendown,
     InvalidRequest(String),
+    /// A record that doesn't match its lexicon, with the path of the field that didn't
+    InvalidRecord(ValidationError),
     ExpiredToken,
     InvalidToken,
     RecordNotFound,
@@ -115,412 +129,202 @@ pub enum ApiError {
     AuthRequiredError(String),
     InvalidCkbError(String),
     InvalidS3Error(String),
+    QuotaExceeded(String),
+    /// The request body is over its route's size or nesting limit
+    PayloadTooLarge(String),
+    RateLimitExceeded,
+    /// The PDS is draining for a restart
+    ServiceUnavailable,
+    /// An error response from a service the request was proxied to, passed on
+    /// with its status
+    Upstream(Status, String, String),
 }
 
-#[derive(Serialize)]
-pub struct ErrorBody {
-    error: String,
-    message: String,
+impl ApiError {
+    /// The `error` name clients see, see [`ErrorCode`]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::RuntimeError => ErrorCode::InternalServerError,
+            ApiError::InvalidLogin => ErrorCode::InvalidLogin,
+            ApiError::AccountTakendown => ErrorCode::AccountTakendown,
+            ApiError::InvalidRequest(_) => ErrorCode::InvalidRequest,
+            ApiError::InvalidRecord(_) => ErrorCode::InvalidRecord,
+            ApiError::ExpiredToken => ErrorCode::ExpiredToken,
+            ApiError::InvalidToken => ErrorCode::InvalidToken,
+            ApiError::RecordNotFound => ErrorCode::RecordNotFound,
+            ApiError::InvalidHandle => ErrorCode::InvalidHandle,
+            ApiError::InvalidEmail => ErrorCode::InvalidEmail,
+            ApiError::InvalidPassword => ErrorCode::InvalidPassword,
+            ApiError::InvalidInviteCode => ErrorCode::InvalidInviteCode,
+            ApiError::HandleNotAvailable => ErrorCode::HandleNotAvailable,
+            ApiError::EmailNotAvailable => ErrorCode::EmailNotAvailable,
+            ApiError::UnsupportedDomain => ErrorCode::UnsupportedDomain,
+            ApiError::UnresolvableDid => ErrorCode::UnresolvableDid,
+            ApiError::IncompatibleDidDoc => ErrorCode::IncompatibleDidDoc,
+            ApiError::WellKnownNotFound => ErrorCode::WellKnownNotFound,
+            ApiError::AccountNotFound => ErrorCode::AccountNotFound,
+            ApiError::BlobNotFound => ErrorCode::BlobNotFound,
+            ApiError::CkbAddrNotFound => ErrorCode::CkbAddrNotFound,
+            ApiError::CkbDidocCellNotFound => ErrorCode::CkbDidocCellNotFound,
+            ApiError::CkbAddrNoCell => ErrorCode::CkbAddrNoCell,
+            ApiError::BadRequest(error, _) => ErrorCode::from(error.as_str()),
+            ApiError::AuthRequiredError(_) => ErrorCode::AuthRequiredError,
+            ApiError::InvalidCkbError(_) => ErrorCode::InvalidCkbAddr,
+            ApiError::InvalidS3Error(_) => ErrorCode::InvalidS3Error,
+            ApiError::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+            ApiError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+            ApiError::RateLimitExceeded => ErrorCode::RateLimitExceeded,
+            ApiError::ServiceUnavailable => ErrorCode::ServiceUnavailable,
+            ApiError::Upstream(_, error, _) => ErrorCode::from(error.as_str()),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            ApiError::RuntimeError => "Something went wrong".to_string(),
+            ApiError::InvalidLogin => "Invalid identifier or password".to_string(),
+            ApiError::AccountTakendown => "Account has been taken down".to_string(),
+            ApiError::InvalidRecord(error) => error.to_string(),
+            ApiError::ExpiredToken => "Token is expired".to_string(),
+            ApiError::InvalidToken => "Token is invalid".to_string(),
+            ApiError::RecordNotFound => "Record could not be found".to_string(),
+            ApiError::InvalidHandle => "Handle is invalid".to_string(),
+            ApiError::InvalidEmail => "Invalid email".to_string(),
+            ApiError::InvalidPassword => "Invalid Password".to_string(),
+            ApiError::InvalidInviteCode => "Invalid invite code".to_string(),
+            ApiError::HandleNotAvailable => "Handle not available".to_string(),
+            ApiError::EmailNotAvailable => "Email not available".to_string(),
+            ApiError::UnsupportedDomain => "Unsupported domain".to_string(),
+            ApiError::UnresolvableDid => "Unresolved Did".to_string(),
+            ApiError::IncompatibleDidDoc => "IncompatibleDidDoc".to_string(),
+            ApiError::WellKnownNotFound => "User not found".to_string(),
+            ApiError::AccountNotFound => "Account could not be found".to_string(),
+            ApiError::BlobNotFound => "Blob could not be found".to_string(),
+            ApiError::CkbAddrNotFound => "Ckb address could not be found".to_string(),
+            ApiError::CkbDidocCellNotFound => "Liv ckb did doc could not be found".to_string(),
+            ApiError::CkbAddrNoCell => "No live cell be found".to_string(),
+            ApiError::RateLimitExceeded => "Rate Limit Exceeded".to_string(),
+            ApiError::ServiceUnavailable => "Server is shutting down, retry shortly".to_string(),
+            ApiError::InvalidRequest(message)
+            | ApiError::BadRequest(_, message)
+            | ApiError::AuthRequiredError(message)
+            | ApiError::InvalidCkbError(message)
+            | ApiError::InvalidS3Error(message)
+            | ApiError::QuotaExceeded(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::Upstream(_, _, message) => message.clone(),
+        }
+    }
+
+    /// For responses Rocket produced itself, without a route or guard saying why
+    pub fn from_status(status: Status) -> Self {
+        match status.code {
+            401 => ApiError::AuthRequiredError("Authentication Required".to_string()),
+            404 => ApiError::BadRequest("NotFound".to_string(), "Not Found".to_string()),
+            400 | 422 => ApiError::InvalidRequest(status.reason_lossy().to_string()),
+            413 => ApiError::PayloadTooLarge(status.reason_lossy().to_string()),
+            429 => ApiError::RateLimitExceeded,
+            503 => ApiError::ServiceUnavailable,
+            _ => ApiError::RuntimeError,
+        }
+    }
 }
 
+/// Every error goes out as `{ "error": <code>, "message": ... }`, with the
+/// status that goes with the code so clients see the same for both.
 impl<'r, 'o: 'r> ::rocket::response::Responder<'r, 'o> for ApiError {
-    fn respond_to(self, __req: &'r Request<'_>) -> response::Result<'o> {
-        match self {
-            ApiError::RuntimeError => {
-                let body = Json(ErrorBody {
-                    error: "InternalServerError".to_string(),
-                    message: "Something went wrong".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 500u16 });
-                Ok(res)
-            }
-            ApiError::InvalidLogin => {
-                let body = Json(ErrorBody {
-                    error: "InvalidLogin".to_string(),
-                    message: "Invalid identifier or password".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(::rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::AccountTakendown => {
-                let body = Json(ErrorBody {
-                    error: "AccountTakendown".to_string(),
-                    message: "Account has been taken down".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(::rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidRequest(message) => {
-                let body = Json(ErrorBody {
-                    error: "InvalidRequest".to_string(),
-                    message,
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::ExpiredToken => {
-                let body = Json(ErrorBody {
-                    error: "ExpiredToken".to_string(),
-                    message: "Token is expired".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidToken => {
-                let body = Json(ErrorBody {
-                    error: "InvalidToken".to_string(),
-                    message: "Token is invalid".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidHandle => {
-                let body = Json(ErrorBody {
-                    error: "InvalidHandle".to_string(),
-                    message: "Handle is invalid".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidEmail => {
-                let body = Json(ErrorBody {
-                    error: "InvalidEmail".to_string(),
-                    message: "Invalid email".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidPassword => {
-                let body = Json(ErrorBody {
-                    error: "InvalidPassword".to_string(),
-                    message: "Invalid Password".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::InvalidInviteCode => {
-                let body = Json(ErrorBody {
-                    error: "InvalidInviteCode".to_string(),
-                    message: "Invalid invite code".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::HandleNotAvailable => {
-                let body = Json(ErrorBody {
-                    error: "HandleNotAvailable".to_string(),
-                    message: "Handle not available".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::EmailNotAvailable => {
-                let body = Json(ErrorBody {
-                    error: "EmailNotAvailable".to_string(),
-                    message: "Email not available".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::UnsupportedDomain => {
-                let body = Json(ErrorBody {
-                    error: "UnsupportedDomain".to_string(),
-                    message: "Unsupported domain".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::UnresolvableDid => {
-                let body = Json(ErrorBody {
-                    error: "UnresolvableDid".to_string(),
-                    message: "Unresolved Did".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::IncompatibleDidDoc => {
-                let body = Json(ErrorBody {
-                    error: "IncompatibleDidDoc".to_string(),
-                    message: "IncompatibleDidDoc".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::AccountNotFound => {
-                let body = Json(ErrorBody {
-                    error: "AccountNotFound".to_string(),
-                    message: "Account could not be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::BlobNotFound => {
-                let body = Json(ErrorBody {
-                    error: "BlobNotFound".to_string(),
-                    message: "Blob could not be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::WellKnownNotFound => {
-                let body = Json(ErrorBody {
-                    error: "WellKnownNotFound".to_string(),
-                    message: "User not found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(::rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 404u16 });
-                Ok(res)
-            }
-            ApiError::CkbAddrNotFound => {
-                let body = Json(ErrorBody {
-                    error: "CkbAddrNotFound".to_string(),
-                    message: "Ckb address could not be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::CkbDidocCellNotFound => {
-                let body = Json(ErrorBody {
-                    error: "CkbDidocCellNotFound".to_string(),
-                    message: "Liv ckb did doc could not be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::CkbAddrNoCell => {
-                let body = Json(ErrorBody {
-                    error: "CkbAddrNoCell".to_string(),
-                    message: "No live cell be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::BadRequest(error, message) => {
-                let body = Json(ErrorBody { error, message });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
-            }
-            ApiError::AuthRequiredError(message) => {
-                let body = Json(ErrorBody {
-                    error: "AuthRequiredError".to_string(),
-                    message,
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(::rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 401u16 });
-                Ok(res)
-            }
-            ApiError::RecordNotFound => {
-                let body = Json(ErrorBody {
-                    error: "RecordNotFound".to_string(),
-                    message: "Record could not be found".to_string(),
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 404u16 });
-                Ok(res)
-            }
-            ApiError::InvalidCkbError(message) => {
-                let body = Json(ErrorBody {
-                    error: "InvalidCkbAddr".to_string(),
-                    message,
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let error = self.code();
+        let status = match self {
+            ApiError::Upstream(status, _, _) => status,
+            _ => Status::new(error.http_status()),
+        };
+        let body = Json(XrpcErrorBody {
+            message: Some(self.message()),
+            path: match self {
+                ApiError::InvalidRecord(error) => Some(error.path),
+                _ => None,
+            },
+            error,
+        });
+        let mut res =
+            <Json<XrpcErrorBody> as ::rocket::response::Responder>::respond_to(body, req)?;
+        res.set_header(ContentType::JSON);
+        res.set_status(status);
+        Ok(res)
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(value: Error) -> Self {
+        if let Some(error) = value.downcast_ref::<ValidationError>() {
+            return ApiError::InvalidRecord(error.clone());
+        }
+        if let Some(error) = value.downcast_ref::<SignedRootError>() {
+            return ApiError::BadRequest("InvalidSignedRoot".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<ConcurrentWriteError>() {
+            return ApiError::BadRequest("ConcurrentWriteError".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<CollectionNotPermittedError>() {
+            return ApiError::BadRequest("InsufficientScope".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<AutomodRejectedError>() {
+            return ApiError::BadRequest("RejectedByAutomod".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<SectionArchivedError>() {
+            return ApiError::BadRequest("SectionArchived".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<ConvoNotFoundError>() {
+            return ApiError::BadRequest("NotFound".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<DraftNotFoundError>() {
+            return ApiError::BadRequest("NotFound".to_string(), error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<TooManyDraftsError>() {
+            return ApiError::QuotaExceeded(error.to_string());
+        }
+        if let Some(error) = value.downcast_ref::<ScheduledWriteNotPendingError>() {
+            return ApiError::InvalidRequest(error.to_string());
+        }
+        // Errors from proxying to another service are the client's to handle
+        match value.downcast_ref::<InvalidRequestError>() {
+            Some(InvalidRequestError::XRPCError(XRPCError::FailedResponse {
+                status,
+                error,
+                message,
+                ..
+            })) => {
+                return ApiError::Upstream(
+                    upstream_status(status),
+                    error.clone().unwrap_or("UpstreamError".to_string()),
+                    message.clone().unwrap_or_default(),
+                )
             }
-            ApiError::InvalidS3Error(message) => {
-                let body = Json(ErrorBody {
-                    error: "InvalidS3Error".to_string(),
-                    message,
-                });
-                let mut res =
-                    <Json<ErrorBody> as ::rocket::response::Responder>::respond_to(body, __req)?;
-                res.set_header(ContentType(rocket::http::MediaType::const_new(
-                    "application",
-                    "json",
-                    &[],
-                )));
-                res.set_status(Status { code: 400u16 });
-                Ok(res)
+            Some(InvalidRequestError::XRPCError(XRPCError::UpstreamFailure)) => {
+                return ApiError::RuntimeError
             }
+            Some(InvalidRequestError::AuthError(_)) => return ApiError::InvalidToken,
+            Some(error) => return ApiError::InvalidRequest(error.to_string()),
+            None => (),
+        }
+        match value.downcast_ref::<StorageQuotaError>() {
+            Some(error) => ApiError::QuotaExceeded(error.to_string()),
+            None => ApiError::RuntimeError,
         }
     }
 }
 
-impl From<Error> for ApiError {
-    fn from(_value: Error) -> Self {
-        ApiError::RuntimeError
-    }
+/// The status of a failed upstream response, from how reqwest prints it
+/// (`404 Not Found`). Anything that isn't an error status is a bad gateway.
+fn upstream_status(status: &str) -> Status {
+    status
+        .split_whitespace()
+        .next()
+        .and_then(|code| code.parse::<u16>().ok())
+        .filter(|code| (400..600).contains(code))
+        .map(Status::new)
+        .unwrap_or(Status::BadGateway)
 }
 
 impl From<handle::errors::Error> for ApiError {
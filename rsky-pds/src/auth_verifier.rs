@@ -1,27 +1,66 @@
 use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
-use crate::account_manager::helpers::auth::CustomClaimObj;
+use crate::account_manager::helpers::auth::{verify_step_up_token, CustomClaimObj};
+use crate::account_manager::helpers::jwt_keys::{JwtKeyRing, JWT_KEYS};
 use crate::account_manager::AccountManager;
 use crate::apis::ApiError;
-use crate::xrpc_server::auth::{verify_jwt as verify_service_jwt_server, ServiceJwtPayload};
+use crate::config::ServerConfig;
+use crate::oauth::{
+    dpop_token_from_req, is_dpop_token, is_oauth_access_token, verify_dpop_bound_token,
+};
+use crate::xrpc_server::auth::{
+    check_claims, parse_payload, verify_jwt as verify_service_jwt_server, ServiceJwtPayload,
+};
 use crate::SharedIdResolver;
 use anyhow::{bail, Result};
 use base64::{engine::general_purpose::STANDARD as base64pad, Engine as _};
 use jwt_simple::claims::Audiences;
 use jwt_simple::prelude::*;
+use lazy_static::lazy_static;
+use lru::LruCache;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket::State;
-use rsky_common::env::env_str;
+use rsky_common::env::{env_list, env_str};
 use rsky_common::get_verification_material;
+use rsky_crypto::utils::encode_did_key;
 use rsky_identity::did::atproto_data::get_did_key_from_multibase;
 use rsky_identity::types::DidDocument;
 use secp256k1::{Keypair, Secp256k1, SecretKey};
 use std::env;
+use std::num::NonZeroUsize;
 use std::str;
+use std::sync::Mutex;
+use std::time::Instant;
 use thiserror::Error;
 
 const INFINITY: u64 = u64::MAX;
 
+/// DID of the access token a request was authenticated with, for guards that run
+/// after the auth guard such as the rate limiter.
+pub struct AuthenticatedDid(pub Option<String>);
+
+/// Header carrying the token from `com.atproto.web5.indexAction#stepUp`
+pub const STEP_UP_HEADER: &str = "Web5-Step-Up";
+
+lazy_static! {
+    /// Methods that need a fresh wallet signature on top of the access token for
+    /// web5 accounts. A comma separated PDS_STEP_UP_METHODS adds to these, it
+    /// can't take any away.
+    pub static ref STEP_UP_METHODS: HashSet<String> = [
+        "com.atproto.identity.updateHandle",
+        "com.atproto.identity.requestPlcOperationSignature",
+        "com.atproto.identity.signPlcOperation",
+        "com.atproto.server.requestAccountDelete",
+        "com.atproto.server.deactivateAccount",
+        "com.atproto.server.updateEmail",
+        "com.atproto.web5.setPassword",
+    ]
+    .into_iter()
+    .map(String::from)
+    .chain(env_list("PDS_STEP_UP_METHODS"))
+    .collect();
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum AuthScope {
     Access,
@@ -70,6 +109,8 @@ pub struct Credentials {
     pub aud: Option<String>,
     pub iss: Option<String>,
     pub is_privileged: Option<bool>,
+    /// Collections an app password session may write to, unset when unrestricted
+    pub collections: Option<Vec<String>>,
 }
 
 #[derive(Clone)]
@@ -95,6 +136,7 @@ pub struct AuthVerifierDids {
 pub struct ServiceJwtOpts {
     pub aud: Option<String>,
     pub iss: Option<Vec<String>>,
+    pub lxm: Option<String>,
 }
 
 pub struct ValidateAccessTokenOpts {
@@ -120,6 +162,9 @@ pub struct JwtPayload {
     pub exp: Option<Duration>,
     pub iat: Option<Duration>,
     pub jti: Option<String>,
+    pub collections: Option<Vec<String>>,
+    /// Thumbprint of the DPoP key an OAuth access token is bound to
+    pub dpop_jkt: Option<String>,
 }
 
 #[derive(Error, Debug)]
@@ -138,6 +183,8 @@ pub enum AuthError {
     AccountTakedown(String),
     #[error("AccountDeactivated: `{0}`")]
     AccountDeactivated(String),
+    #[error("StepUpRequired: `{0}`")]
+    StepUpRequired(String),
     #[error("InternalServerError: `{0}`")]
     InternalServerError(String),
 }
@@ -193,6 +240,7 @@ impl<'r> FromRequest<'r> for Refresh {
                     aud: None,
                     iss: None,
                     is_privileged: None,
+                    collections: None,
                 }),
                 artifacts: Some(token),
             },
@@ -220,6 +268,10 @@ pub async fn access_check<'r>(
                 Status::BadRequest,
                 AuthError::AccountTakedown(error.to_string()),
             )),
+            Some(AuthError::StepUpRequired(error)) => Outcome::Error((
+                Status::Unauthorized,
+                AuthError::StepUpRequired(error.to_string()),
+            )),
             _ => Outcome::Error((Status::BadRequest, AuthError::BadJwt(error.to_string()))),
         },
     }
@@ -465,6 +517,7 @@ impl<'r> FromRequest<'r> for UserDidAuth {
             ServiceJwtOpts {
                 aud: Some(env::var("PDS_SERVICE_DID").unwrap()),
                 iss: None,
+                lxm: None,
             },
         )
         .await
@@ -480,6 +533,7 @@ impl<'r> FromRequest<'r> for UserDidAuth {
                         aud: Some(payload.aud),
                         iss: Some(payload.iss),
                         is_privileged: None,
+                        collections: None,
                     }),
                     artifacts: None,
                 },
@@ -542,6 +596,7 @@ impl<'r> FromRequest<'r> for ModService {
                         mod_service_did.clone(),
                         format!("{mod_service_did}#atproto_labeler"),
                     ]),
+                    lxm: None,
                 },
             )
             .await
@@ -568,6 +623,7 @@ impl<'r> FromRequest<'r> for ModService {
                             aud: Some(payload.aud),
                             iss: Some(payload.iss),
                             is_privileged: None,
+                            collections: None,
                         }),
                         artifacts: None,
                     },
@@ -595,7 +651,7 @@ impl<'r> FromRequest<'r> for Moderator {
     type Error = AuthError;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        if is_bearer_token(req) {
+        if is_bearer_token(req) && !is_admin_service_token(req) {
             match ModService::from_request(req).await {
                 Outcome::Success(output) => Outcome::Success(Moderator {
                     access: output.access,
@@ -621,6 +677,9 @@ impl<'r> FromRequest<'r> for Moderator {
     }
 }
 
+/// Admin access, either a service JWT from one of `PDS_ADMIN_SERVICE_DIDS` or,
+/// while `PDS_ADMIN_PASSWORD_AUTH` allows it, basic auth with the admin
+/// password.
 pub struct AdminToken {
     pub access: AccessOutput,
 }
@@ -630,6 +689,44 @@ impl<'r> FromRequest<'r> for AdminToken {
     type Error = AuthError;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(cfg) = req.rocket().state::<ServerConfig>() else {
+            return Outcome::Error((
+                Status::InternalServerError,
+                AuthError::InternalServerError("Server config unavailable".to_string()),
+            ));
+        };
+        if is_bearer_token(req) {
+            return match verify_admin_service_jwt(req, cfg).await {
+                Ok(payload) => Outcome::Success(AdminToken {
+                    access: AccessOutput {
+                        credentials: Some(Credentials {
+                            r#type: "admin_service".to_string(),
+                            did: None,
+                            scope: None,
+                            audience: None,
+                            token_id: None,
+                            aud: Some(payload.aud),
+                            iss: Some(payload.iss),
+                            is_privileged: None,
+                            collections: None,
+                        }),
+                        artifacts: None,
+                    },
+                }),
+                Err(error) => {
+                    let error = AuthError::BadJwt(error.to_string());
+                    req.local_cache(|| Some(ApiError::InvalidRequest(error.to_string())));
+                    Outcome::Error((Status::BadRequest, error))
+                }
+            };
+        }
+        if !cfg.admin_auth.password_enabled {
+            let error = AuthError::AuthRequired(
+                "Admin password auth is disabled, use a service JWT".to_string(),
+            );
+            req.local_cache(|| Some(ApiError::InvalidRequest(error.to_string())));
+            return Outcome::Error((Status::BadRequest, error));
+        }
         let auth_header: &str = req.headers().get_one("Authorization").unwrap_or("");
         match parse_basic_auth(auth_header) {
             None => Outcome::Error((
@@ -639,7 +736,7 @@ impl<'r> FromRequest<'r> for AdminToken {
             Some(parsed) => {
                 let BasicAuth { username, password } = parsed;
 
-                if username != "admin" || password != env::var("PDS_ADMIN_PASS").unwrap() {
+                if username != "admin" || Some(password) != env_str("PDS_ADMIN_PASS") {
                     let error = AuthError::AuthRequired("BadAuth".to_string());
                     req.local_cache(|| Some(ApiError::InvalidRequest(error.to_string())));
                     Outcome::Error((Status::BadRequest, error))
@@ -655,6 +752,7 @@ impl<'r> FromRequest<'r> for AdminToken {
                                 aud: None,
                                 iss: None,
                                 is_privileged: None,
+                                collections: None,
                             }),
                             artifacts: None,
                         },
@@ -675,7 +773,7 @@ impl<'r> FromRequest<'r> for OptionalAccessOrAdminToken {
     type Error = AuthError;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        if is_bearer_token(req) {
+        if (is_bearer_token(req) && !is_admin_service_token(req)) || is_dpop_token(req) {
             match AccessFull::from_request(req).await {
                 Outcome::Success(output) => Outcome::Success(OptionalAccessOrAdminToken {
                     access: Some(output.access),
@@ -686,7 +784,7 @@ impl<'r> FromRequest<'r> for OptionalAccessOrAdminToken {
                 }
                 _ => panic!("Unexpected outcome during OptionalAccessOrAdminToken"),
             }
-        } else if is_basic_token(req) {
+        } else if is_admin_request(req) {
             match AdminToken::from_request(req).await {
                 Outcome::Success(output) => Outcome::Success(OptionalAccessOrAdminToken {
                     access: Some(output.access),
@@ -716,7 +814,7 @@ pub async fn validate_bearer_access_token<'r>(
         scope,
         token,
         audience,
-        ..
+        payload,
     } = validate_bearer_token(request, scopes, Some(options)).await?;
     let is_privileged = vec![AuthScope::Access, AuthScope::AppPassPrivileged].contains(&scope);
     Ok(AccessOutput {
@@ -729,6 +827,7 @@ pub async fn validate_bearer_access_token<'r>(
             aud: None,
             iss: None,
             is_privileged: Some(is_privileged),
+            collections: payload.collections,
         }),
         artifacts: Some(token),
     })
@@ -739,14 +838,24 @@ pub async fn validate_bearer_token<'r>(
     scopes: Vec<AuthScope>,
     verify_options: Option<VerificationOptions>,
 ) -> Result<ValidatedBearer> {
-    let token = bearer_token_from_req(request)?;
+    let (token, dpop_bound) = match dpop_token_from_req(request) {
+        Some(token) => {
+            verify_dpop_bound_token(request, &token).await?;
+            (Some(token), true)
+        }
+        None => (bearer_token_from_req(request)?, false),
+    };
     if let Some(token) = token {
-        let secp = Secp256k1::new();
-        let private_key = env::var("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX").unwrap();
-        let secret_key =
-            SecretKey::from_slice(&hex::decode(private_key.as_bytes()).unwrap()).unwrap();
-        let jwt_key = Keypair::from_secret_key(&secp, &secret_key);
-        let payload = verify_jwt(token.clone(), jwt_key, verify_options).await?;
+        let payload = verify_jwt(token.clone(), &JWT_KEYS, verify_options).await?;
+        // An OAuth access token is only good with a proof of its DPoP key.
+        // Tokens issued before they carried cnf.jkt are known by their session.
+        if !dpop_bound
+            && (payload.dpop_jkt.is_some()
+                || (payload.scope == AuthScope::AppPass
+                    && is_oauth_access_token(request, &token).await?))
+        {
+            bail!("DPoP-bound token used as a bearer token")
+        }
         let JwtPayload {
             sub, aud, scope, ..
         } = payload.clone();
@@ -781,7 +890,6 @@ pub async fn validate_bearer_token<'r>(
     }
 }
 
-// @TODO: Implement DPop/OAuth
 pub async fn validate_access_token<'r>(
     request: &'r Request<'_>,
     scopes: Vec<AuthScope>,
@@ -796,7 +904,7 @@ pub async fn validate_access_token<'r>(
         scope,
         token,
         audience,
-        ..
+        payload,
     } = validate_bearer_token(request, scopes, Some(options)).await?;
     let ValidateAccessTokenOpts {
         check_takedown,
@@ -825,6 +933,15 @@ pub async fn validate_access_token<'r>(
             )))
         }
     };
+    // Sessions share their id with the refresh token, so logged out sessions
+    // stop working straight away rather than when the access token expires
+    if let Some(jti) = payload.jti {
+        if account_manager.is_session_revoked(jti).await? {
+            bail!("Token has been revoked")
+        }
+    }
+    check_step_up(request, &account_manager, &did).await?;
+    request.local_cache(|| AuthenticatedDid(Some(did.clone())));
     if check_takedown || check_deactivated {
         let found: ActorAccount = match account_manager
             .get_account(
@@ -864,59 +981,236 @@ pub async fn validate_access_token<'r>(
             aud: None,
             iss: None,
             is_privileged: None,
+            collections: payload.collections,
         }),
         artifacts: Some(token),
     })
 }
 
+/// Enforces STEP_UP_METHODS: web5 accounts calling one of them must also present a
+/// step-up token for that method. Other accounts have no wallet to sign with.
+async fn check_step_up<'r>(
+    request: &'r Request<'_>,
+    account_manager: &AccountManager,
+    did: &str,
+) -> Result<()> {
+    let path = request.uri().path();
+    let Some(lxm) = path.as_str().strip_prefix("/xrpc/") else {
+        return Ok(());
+    };
+    if !STEP_UP_METHODS.contains(lxm) {
+        return Ok(());
+    }
+    let is_web5 = match account_manager
+        .get_account(
+            did,
+            Some(AvailabilityFlags {
+                include_taken_down: Some(true),
+                include_deactivated: Some(true),
+            }),
+        )
+        .await?
+    {
+        Some(account) => account.ckb_address.is_some(),
+        None => false,
+    };
+    if !is_web5 {
+        return Ok(());
+    }
+    let Some(token) = request.headers().get_one(STEP_UP_HEADER) else {
+        return Err(anyhow::Error::new(AuthError::StepUpRequired(format!(
+            "{lxm} requires a wallet signature, see com.atproto.web5.indexAction#stepUp"
+        ))));
+    };
+    verify_step_up_token(token, did, lxm, env::var("PDS_SERVICE_DID")?, &JWT_KEYS)
+        .map_err(|error| anyhow::Error::new(AuthError::StepUpRequired(error.to_string())))
+}
+
 pub async fn verify_service_jwt<'r>(
     request: &'r Request<'_>,
     id_resolver: &State<SharedIdResolver>,
     opts: ServiceJwtOpts,
 ) -> Result<VerifiedServiceJwt> {
+    let jwt_str = match bearer_token_from_req(request)? {
+        None => bail!("MissingJwt: missing jwt"),
+        Some(jwt_str) => jwt_str,
+    };
+    let unverified = match jwt_str.split(".").nth(1).map(parse_payload) {
+        Some(Ok(unverified)) => unverified,
+        _ => bail!("BadJwt: poorly formatted jwt"),
+    };
+    // Nothing is looked up for the unverified iss until the token is at least
+    // addressed to us, unexpired and for this method
+    check_claims(&unverified, opts.aud.as_deref(), opts.lxm.as_deref())?;
+    if let Some(ref opts_iss) = opts.iss {
+        if !opts_iss.contains(&unverified.iss) {
+            bail!("UntrustedIss: Untrusted issuer");
+        }
+    }
+    // Web5 accounts hosted here have no PLC/web DID doc to resolve, so their tokens are
+    // checked against the PDS repo signing key that getServiceAuth mints them with.
+    let web5_signing_key = get_web5_signing_key(request, &unverified.iss).await?;
     let get_signing_key = |iss: String, force_refresh: bool| -> Result<String> {
-        match &opts.iss {
-            Some(opts_iss) if opts_iss.contains(&iss) => bail!("UntrustedIss: Untrusted issuer"),
-            _ => (),
-        }
-        let parts = iss.split("#").collect::<Vec<&str>>();
-        if let (Some(did), Some(service_id)) = (parts.get(0), parts.get(1)) {
-            let (did, service_id) = (did.to_string(), *service_id);
-            let key_id = if service_id == "atproto_labeler" {
-                "atproto_label"
-            } else {
-                "atproto"
-            };
-            let mut lock = futures::executor::block_on(id_resolver.id_resolver.write());
-            let did_doc: Result<DidDocument> =
-                futures::executor::block_on(lock.did.ensure_resolve(&did, Some(force_refresh)));
-            let did_doc: DidDocument = match did_doc {
-                Err(err) => bail!("could not resolve iss did: `{err}`"),
-                Ok(res) => res,
-            };
-            match get_verification_material(&did_doc, &key_id.to_string()) {
+        if let Some(ref signing_key) = web5_signing_key {
+            return Ok(signing_key.clone());
+        }
+        let mut parts = iss.split("#");
+        let did = parts.next().unwrap_or_default().to_string();
+        let key_id = match parts.next() {
+            Some("atproto_labeler") => "atproto_label",
+            _ => "atproto",
+        };
+        let mut lock = futures::executor::block_on(id_resolver.id_resolver.write());
+        let did_doc: Result<DidDocument> =
+            futures::executor::block_on(lock.did.ensure_resolve(&did, Some(force_refresh)));
+        let did_doc: DidDocument = match did_doc {
+            Err(err) => bail!("could not resolve iss did: `{err}`"),
+            Ok(res) => res,
+        };
+        match get_verification_material(&did_doc, &key_id.to_string()) {
+            None => bail!("missing or bad key in did doc"),
+            Some(parsed_key) => match get_did_key_from_multibase(parsed_key)? {
                 None => bail!("missing or bad key in did doc"),
-                Some(parsed_key) => match get_did_key_from_multibase(parsed_key)? {
-                    None => bail!("missing or bad key in did doc"),
-                    Some(did_key) => Ok(did_key),
-                },
-            }
-        } else {
-            bail!("could not resolve iss did")
+                Some(did_key) => Ok(did_key),
+            },
         }
     };
 
-    match bearer_token_from_req(request)? {
+    let payload: ServiceJwtPayload =
+        verify_service_jwt_server(jwt_str, opts.aud, opts.lxm, get_signing_key).await?;
+    Ok(VerifiedServiceJwt {
+        iss: payload.iss,
+        aud: payload.aud,
+    })
+}
+
+/// Checks a service JWT from one of the admin services, addressed to this PDS
+/// and bound to the method being called. With `PDS_ADMIN_SERVICE_KEYS` set the
+/// token must be signed by one of those keys, otherwise by the key in the
+/// service's DID doc.
+async fn verify_admin_service_jwt<'r>(
+    request: &'r Request<'_>,
+    cfg: &ServerConfig,
+) -> Result<VerifiedServiceJwt> {
+    let admin = &cfg.admin_auth;
+    if admin.service_dids.is_empty() {
+        bail!("UntrustedIss: no admin services are configured");
+    }
+    let aud = Some(cfg.service.did.clone());
+    let lxm = request
+        .uri()
+        .path()
+        .as_str()
+        .strip_prefix("/xrpc/")
+        .map(|nsid| nsid.to_string());
+    if admin.service_keys.is_empty() {
+        let id_resolver = match request.guard::<&State<SharedIdResolver>>().await {
+            Outcome::Success(id_resolver) => id_resolver,
+            _ => bail!("could not load identity resolver"),
+        };
+        return verify_service_jwt(
+            request,
+            id_resolver,
+            ServiceJwtOpts {
+                aud,
+                iss: Some(admin.service_dids.clone()),
+                lxm,
+            },
+        )
+        .await;
+    }
+
+    let jwt_str = match bearer_token_from_req(request)? {
         None => bail!("MissingJwt: missing jwt"),
-        Some(jwt_str) => {
-            let payload: ServiceJwtPayload =
-                verify_service_jwt_server(jwt_str, opts.aud, get_signing_key).await?;
-            Ok(VerifiedServiceJwt {
-                iss: payload.iss,
-                aud: payload.aud,
-            })
+        Some(jwt_str) => jwt_str,
+    };
+    let mut last_error = None;
+    for key in admin.service_keys.iter() {
+        let get_signing_key = |iss: String, _force_refresh: bool| -> Result<String> {
+            if !admin.service_dids.contains(&iss) {
+                bail!("UntrustedIss: Untrusted issuer");
+            }
+            Ok(key.clone())
+        };
+        match verify_service_jwt_server(jwt_str.clone(), aud.clone(), lxm.clone(), get_signing_key)
+            .await
+        {
+            Ok(payload) => {
+                return Ok(VerifiedServiceJwt {
+                    iss: payload.iss,
+                    aud: payload.aud,
+                })
+            }
+            Err(error) => last_error = Some(error),
         }
     }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("BadJwtSignature: no admin service keys")))
+}
+
+/// Issuers kept by [`get_web5_signing_key`]
+const WEB5_ISSUER_CACHE_CAPACITY: usize = 10_000;
+/// Whether a DID is a web5 account is settled when the account is created, so
+/// this mostly bounds how long a deleted account's issuer is remembered
+const WEB5_ISSUER_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(300);
+
+lazy_static! {
+    /// The verification key resolved for each service JWT issuer, `None` for
+    /// issuers that aren't web5 accounts hosted here
+    static ref WEB5_ISSUER_CACHE: Mutex<LruCache<String, (Option<String>, Instant)>> =
+        Mutex::new(LruCache::new(
+            NonZeroUsize::new(WEB5_ISSUER_CACHE_CAPACITY).expect("non-zero capacity")
+        ));
+}
+
+/// Returns the PDS repo signing key as a did:key when `iss` is a web5 account hosted here.
+async fn get_web5_signing_key<'r>(request: &'r Request<'_>, iss: &str) -> Result<Option<String>> {
+    let did = iss.split("#").next().unwrap_or_default();
+    {
+        let mut cache = WEB5_ISSUER_CACHE
+            .lock()
+            .expect("web5 issuer cache poisoned");
+        if let Some((signing_key, cached_at)) = cache.get(did) {
+            if cached_at.elapsed() < WEB5_ISSUER_CACHE_TTL {
+                return Ok(signing_key.clone());
+            }
+        }
+    }
+    let signing_key = resolve_web5_signing_key(request, did).await?;
+    WEB5_ISSUER_CACHE
+        .lock()
+        .expect("web5 issuer cache poisoned")
+        .put(did.to_string(), (signing_key.clone(), Instant::now()));
+    Ok(signing_key)
+}
+
+async fn resolve_web5_signing_key<'r>(
+    request: &'r Request<'_>,
+    did: &str,
+) -> Result<Option<String>> {
+    let account_manager = match request.guard::<AccountManager>().await {
+        Outcome::Success(account_manager) => account_manager,
+        _ => bail!("could not load account manager"),
+    };
+    let is_web5 = match account_manager
+        .get_account(
+            did,
+            Some(AvailabilityFlags {
+                include_taken_down: Some(true),
+                include_deactivated: Some(true),
+            }),
+        )
+        .await?
+    {
+        Some(account) => account.ckb_address.is_some(),
+        None => false,
+    };
+    if !is_web5 {
+        return Ok(None);
+    }
+    let private_key = env::var("PDS_REPO_SIGNING_KEY_K256_PRIVATE_KEY_HEX")?;
+    let secret_key = SecretKey::from_slice(&hex::decode(private_key.as_bytes())?)?;
+    let keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret_key);
+    Ok(Some(encode_did_key(&keypair.public_key())))
 }
 
 pub fn is_user_or_admin(auth: AccessOutput, did: &String) -> bool {
@@ -947,6 +1241,26 @@ pub fn is_basic_token(request: &Request) -> bool {
     }
 }
 
+/// Whether the request carries a service JWT issued by one of the admin
+/// services. Only the issuer is looked at, [`AdminToken`] verifies the token.
+pub fn is_admin_service_token(request: &Request) -> bool {
+    let Some(cfg) = request.rocket().state::<ServerConfig>() else {
+        return false;
+    };
+    match bearer_token_from_req(request) {
+        Ok(Some(jwt_str)) => match jwt_str.split(".").nth(1).map(parse_payload) {
+            Some(Ok(unverified)) => cfg.admin_auth.service_dids.contains(&unverified.iss),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether the request asks for admin access, which [`AdminToken`] decides
+pub fn is_admin_request(request: &Request) -> bool {
+    is_basic_token(request) || is_admin_service_token(request)
+}
+
 pub fn bearer_token_from_req(request: &Request) -> Result<Option<String>> {
     match request.headers().get_one("authorization") {
         Some(header) if !header.starts_with("Bearer ") => Ok(None),
@@ -958,14 +1272,14 @@ pub fn bearer_token_from_req(request: &Request) -> Result<Option<String>> {
     }
 }
 
+/// Verifies an access or refresh token with the key its `kid` names, which may
+/// be one rotated out within the grace period.
 pub async fn verify_jwt(
     jwt: String,
-    jwt_key: Keypair,
+    jwt_keys: &JwtKeyRing,
     verify_options: Option<VerificationOptions>,
 ) -> Result<JwtPayload> {
-    let key = ES256kKeyPair::from_bytes(jwt_key.secret_bytes().as_slice())?;
-    let public_key = key.public_key();
-    let claims = public_key.verify_token::<CustomClaimObj>(&jwt, verify_options)?;
+    let claims = jwt_keys.verify::<CustomClaimObj>(&jwt, verify_options)?;
 
     Ok(JwtPayload {
         scope: AuthScope::from_str(&claims.custom.scope)?,
@@ -974,6 +1288,8 @@ pub async fn verify_jwt(
         exp: claims.expires_at,
         iat: claims.issued_at,
         jti: claims.jwt_id,
+        collections: claims.custom.collections,
+        dpop_jkt: claims.custom.cnf.map(|cnf| cnf.jkt),
     })
 }
 
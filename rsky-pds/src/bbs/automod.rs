@@ -0,0 +1,550 @@
+use crate::bbs::reaction::assert_allowed_emoji;
+use crate::bbs::section::{self, SectionArchivedError};
+use crate::bbs::{
+    find_root_posts, post_links, POST_COLLECTION, REACTION_COLLECTION, REPLY_COLLECTION,
+    RULE_COLLECTION,
+};
+use crate::config::BbsConfig;
+use crate::db::DbConn;
+use crate::labeler::insert_labels;
+use crate::models::BbsReview;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::insert_into;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use regex::Regex;
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::app::bbs::automod::{Rule, RuleAction};
+use rsky_lexicon::app::bbs::SectionStatus;
+use rsky_lexicon::com::atproto::admin::CreateLabel;
+use rsky_lexicon::schema::ValidationError;
+use rsky_repo::types::{PreparedWrite, RepoRecord};
+use rsky_syntax::aturi::AtUri;
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+/// Label the `label` action applies when the rule doesn't name one
+const DEFAULT_LABEL: &str = "automod";
+/// Source of the review entries posts held by a rule get
+const REVIEW_SOURCE: &str = "automod";
+
+/// A section's rules refused a post or reply. The message is the rule's
+/// reason, for the author to see.
+#[derive(thiserror::Error, Debug)]
+#[error("{0}")]
+pub struct AutomodRejectedError(pub String);
+
+/// A rule as it's enforced, with its keywords lowercased and its patterns
+/// compiled
+pub struct SectionRule {
+    pub uri: String,
+    pub rule: Rule,
+    keywords: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl SectionRule {
+    /// `None` for a rule with a pattern that doesn't compile, which the PDS
+    /// refuses to write unless validation was skipped.
+    pub fn new(uri: String, rule: Rule) -> Option<Self> {
+        let keywords = rule
+            .keywords
+            .iter()
+            .flatten()
+            .map(|keyword| keyword.trim().to_lowercase())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        let patterns = match rule
+            .patterns
+            .iter()
+            .flatten()
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<Regex>, regex::Error>>()
+        {
+            Ok(patterns) => patterns,
+            Err(error) => {
+                tracing::warn!("Skipping automoderation rule {uri}: {error}");
+                return None;
+            }
+        };
+        Some(SectionRule {
+            uri,
+            rule,
+            keywords,
+            patterns,
+        })
+    }
+
+    /// Why the post trips the rule, `None` when it doesn't
+    pub fn trips(&self, post: &PostFacts) -> Option<String> {
+        let text = post.text.to_lowercase();
+        if let Some(keyword) = self.keywords.iter().find(|keyword| text.contains(*keyword)) {
+            return Some(format!("contains \"{keyword}\""));
+        }
+        if let Some(pattern) = self
+            .patterns
+            .iter()
+            .find(|pattern| pattern.is_match(&post.text))
+        {
+            return Some(format!("matches /{pattern}/"));
+        }
+        if let Some(hours) = self.rule.min_account_age_hours {
+            if post.account_age_hours < hours as i64 {
+                return Some(format!("account is younger than {hours} hours"));
+            }
+        }
+        if let Some(max) = self.rule.max_links {
+            if post.links.len() > max {
+                return Some(format!("more than {max} links"));
+            }
+        }
+        if let Some(ref domains) = self.rule.allowed_domains {
+            if let Some(link) = post.links.iter().find(|link| !link_allowed(link, domains)) {
+                return Some(format!("links to {link}"));
+            }
+        }
+        if let Some(max) = self.rule.max_posts_per_hour {
+            if post.recent_posts >= max as i64 {
+                return Some(format!("more than {max} posts within an hour"));
+            }
+        }
+        None
+    }
+}
+
+/// What the rules look at in a post or reply
+#[derive(Debug, Clone, Default)]
+pub struct PostFacts {
+    /// Title and text
+    pub text: String,
+    pub links: Vec<String>,
+    pub account_age_hours: i64,
+    /// Posts and replies by the author within the past hour, this one aside
+    pub recent_posts: i64,
+}
+
+/// A rule a post tripped
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trip {
+    pub action: RuleAction,
+    pub label: Option<String>,
+    pub reason: String,
+}
+
+/// The rules `post` trips, in the order they're given
+pub fn evaluate(rules: &[SectionRule], post: &PostFacts) -> Vec<Trip> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let why = rule.trips(post)?;
+            Some(Trip {
+                action: rule.rule.action,
+                label: rule.rule.label.clone(),
+                reason: rule
+                    .rule
+                    .reason
+                    .clone()
+                    .unwrap_or_else(|| format!("Tripped a section rule: {why}")),
+            })
+        })
+        .collect()
+}
+
+/// A hold or label a write tripped, applied with [`apply_hits`] once it commits
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub trip: Trip,
+}
+
+/// Runs the rules of each post's or reply's section over `writes` before
+/// they commit.
+///
+/// Errs with [`SectionArchivedError`] when one of them is written into an
+/// archived section, moderators included, and with [`AutomodRejectedError`]
+/// when any rule rejects one of them. Otherwise returns the holds and labels
+/// they tripped. Replies are checked against the section of their root post,
+/// and skipped when the root isn't hosted here. Moderators aren't held to
+/// their own section's rules.
+///
+/// Reactions aren't up to sections, but their emoji has to be on the PDS's
+/// allowlist, or this errs with a `ValidationError`.
+pub async fn check_writes(
+    db: &DbConn,
+    bbs: &BbsConfig,
+    did: &str,
+    writes: &[PreparedWrite],
+) -> Result<Vec<Hit>> {
+    let mut posts: Vec<(String, String, Value)> = Vec::new();
+    for write in writes {
+        let (PreparedWrite::Create(write) | PreparedWrite::Update(write)) = write else {
+            continue;
+        };
+        let collection = AtUri::new(write.uri.clone(), None)?.get_collection();
+        if collection == REACTION_COLLECTION {
+            assert_allowed_emoji(&bbs.reaction_emojis, &serde_json::to_value(&write.record)?)?;
+        } else if collection == POST_COLLECTION || collection == REPLY_COLLECTION {
+            posts.push((
+                write.uri.clone(),
+                write.cid.to_string(),
+                serde_json::to_value(&write.record)?,
+            ));
+        }
+    }
+    if posts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let section_moderators = bbs.section_moderators.clone();
+    let did = did.to_string();
+    db.run(move |conn| {
+        let mut hits = Vec::new();
+        for (uri, cid, record) in posts {
+            let Some(section) = section_of(conn, &record)? else {
+                continue;
+            };
+            if section::status(conn, section)? == SectionStatus::Archived {
+                return Err(SectionArchivedError(section).into());
+            }
+            let Some(moderators) = section_moderators.get(&section) else {
+                continue;
+            };
+            if moderators.contains(&did) {
+                continue;
+            }
+            let rules = load_rules(conn, moderators, section)?;
+            if rules.is_empty() {
+                continue;
+            }
+            let trips = evaluate(&rules, &post_facts(conn, &did, &record)?);
+            if let Some(trip) = trips.iter().find(|trip| trip.action == RuleAction::Reject) {
+                return Err(AutomodRejectedError(trip.reason.clone()).into());
+            }
+            hits.extend(trips.into_iter().map(|trip| Hit {
+                uri: uri.clone(),
+                cid: cid.clone(),
+                did: did.clone(),
+                trip,
+            }));
+        }
+        Ok(hits)
+    })
+    .await
+}
+
+/// Labels and holds what [`check_writes`] found, once the writes have
+/// committed and before they're sequenced. Labels are issued by
+/// `labeler_did`; held posts go to `bbs_review` and stay hidden until a
+/// moderator resolves them, held again if an edit trips a rule after that.
+/// The writes can't be taken back by then, so failures are only logged.
+pub async fn apply_hits(db: &DbConn, labeler_did: String, hits: Vec<Hit>) {
+    use crate::schema::pds::bbs_review::dsl as ReviewSchema;
+
+    if hits.is_empty() {
+        return;
+    }
+    let res = db
+        .run(move |conn| {
+            for hit in hits {
+                match hit.trip.action {
+                    RuleAction::Label => {
+                        let label = CreateLabel {
+                            uri: hit.uri,
+                            cid: Some(hit.cid),
+                            val: hit.trip.label.unwrap_or_else(|| DEFAULT_LABEL.to_string()),
+                            neg: None,
+                            exp: None,
+                        };
+                        insert_labels(conn, labeler_did.clone(), vec![label])?;
+                    }
+                    RuleAction::Hold => {
+                        let created_at = rsky_common::now();
+                        insert_into(ReviewSchema::bbs_review)
+                            .values(BbsReview {
+                                uri: hit.uri,
+                                cid: hit.cid.clone(),
+                                did: hit.did,
+                                source: REVIEW_SOURCE.to_string(),
+                                reason: hit.trip.reason.clone(),
+                                created_at: created_at.clone(),
+                                resolved_at: None,
+                                held: true,
+                            })
+                            .on_conflict(ReviewSchema::uri)
+                            .do_update()
+                            .set((
+                                ReviewSchema::cid.eq(hit.cid),
+                                ReviewSchema::source.eq(REVIEW_SOURCE),
+                                ReviewSchema::reason.eq(hit.trip.reason),
+                                ReviewSchema::createdAt.eq(created_at),
+                                ReviewSchema::resolvedAt.eq(None::<String>),
+                                ReviewSchema::held.eq(true),
+                            ))
+                            .execute(conn)?;
+                    }
+                    RuleAction::Reject => (),
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await;
+    if let Err(error) = res {
+        tracing::error!("@LOG: ERROR: failed to apply automoderation: {error}");
+    }
+}
+
+/// Refuses rules with a pattern that isn't a valid regular expression, so
+/// moderators learn about it instead of the rule never being enforced.
+pub fn assert_valid_rule(record: &RepoRecord) -> Result<()> {
+    let record = serde_json::to_value(record)?;
+    let patterns = record.get("patterns").and_then(Value::as_array);
+    for (index, pattern) in patterns.into_iter().flatten().enumerate() {
+        if let Some(Err(_)) = pattern.as_str().map(Regex::new) {
+            return Err(ValidationError {
+                path: format!("patterns[{index}]"),
+                message: "isn't a valid regular expression".to_string(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RootPost {
+    section_id: Option<i64>,
+}
+
+/// The section a post declares, or for a reply the section of its root post
+/// when that's hosted here
+fn section_of(conn: &mut PgConnection, record: &Value) -> Result<Option<i64>> {
+    if let Some(section) = record.get("sectionId").and_then(Value::as_i64) {
+        return Ok(Some(section));
+    }
+    let Some(root) = record.get("root").and_then(Value::as_str) else {
+        return Ok(None);
+    };
+    let roots = find_root_posts(conn, &[root.to_string()])?;
+    Ok(roots
+        .get(root)
+        .and_then(|(_, content)| serde_ipld_dagcbor::from_slice::<RootPost>(content).ok())
+        .and_then(|root| root.section_id))
+}
+
+/// The rules `moderators` published for `section`
+fn load_rules(
+    conn: &mut PgConnection,
+    moderators: &[String],
+    section: i64,
+) -> Result<Vec<SectionRule>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let rules = RecordSchema::record
+        .inner_join(
+            RepoBlockSchema::repo_block.on(RepoBlockSchema::cid
+                .eq(RecordSchema::cid)
+                .and(RepoBlockSchema::did.eq(RecordSchema::did))),
+        )
+        .filter(RecordSchema::collection.eq(RULE_COLLECTION))
+        .filter(RecordSchema::did.eq_any(moderators))
+        .filter(RecordSchema::takedownRef.is_null())
+        .order(RecordSchema::uri.asc())
+        .select((RecordSchema::uri, RepoBlockSchema::content))
+        .load::<(String, Vec<u8>)>(conn)?;
+    Ok(rules
+        .into_iter()
+        .filter_map(|(uri, content)| {
+            let rule = serde_ipld_dagcbor::from_slice::<Rule>(&content).ok()?;
+            match rule.section_id as i64 == section {
+                true => SectionRule::new(uri, rule),
+                false => None,
+            }
+        })
+        .collect())
+}
+
+fn post_facts(conn: &mut PgConnection, did: &str, record: &Value) -> Result<PostFacts> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    let now = Utc::now();
+    let since = format!(
+        "{}",
+        (now - ChronoDuration::hours(1)).format(RFC3339_VARIANT)
+    );
+    let created_at = ActorSchema::actor
+        .filter(ActorSchema::did.eq(did))
+        .select(ActorSchema::createdAt)
+        .first::<String>(conn)
+        .optional()?;
+    let account_age_hours = created_at
+        .and_then(|created_at| DateTime::parse_from_rfc3339(&created_at).ok())
+        .map_or(0, |created_at| {
+            (now - created_at.with_timezone(&Utc)).num_hours()
+        });
+    let recent_posts = RecordSchema::record
+        .filter(RecordSchema::did.eq(did))
+        .filter(RecordSchema::collection.eq_any(vec![POST_COLLECTION, REPLY_COLLECTION]))
+        .filter(RecordSchema::indexedAt.ge(since))
+        .count()
+        .get_result::<i64>(conn)?;
+    let text = ["title", "text"]
+        .iter()
+        .filter_map(|field| record.get(field).and_then(Value::as_str))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    Ok(PostFacts {
+        links: post_links(&text, record),
+        text,
+        account_age_hours,
+        recent_posts,
+    })
+}
+
+/// Whether `link` points at one of `domains` or a subdomain of one. Bare
+/// `www.` links are read as https.
+fn link_allowed(link: &str, domains: &[String]) -> bool {
+    let url = match link.to_lowercase().starts_with("www.") {
+        true => Url::parse(&format!("https://{link}")),
+        false => Url::parse(link),
+    };
+    let Some(host) = url
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+    else {
+        return false;
+    };
+    let host = host.trim_end_matches('.');
+    domains.iter().any(|domain| {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        host == domain || host.ends_with(&format!(".{domain}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: RuleAction) -> Rule {
+        Rule {
+            created_at: Utc::now(),
+            section_id: 1,
+            action,
+            label: None,
+            reason: None,
+            keywords: None,
+            patterns: None,
+            min_account_age_hours: None,
+            max_links: None,
+            allowed_domains: None,
+            max_posts_per_hour: None,
+        }
+    }
+
+    fn enforced(rule: Rule) -> SectionRule {
+        SectionRule::new("at://did:web5:mod/app.bbs.automod.rule/1".to_string(), rule).unwrap()
+    }
+
+    fn post(text: &str) -> PostFacts {
+        PostFacts {
+            text: text.to_string(),
+            links: Vec::new(),
+            account_age_hours: 24 * 365,
+            recent_posts: 0,
+        }
+    }
+
+    #[test]
+    fn matches_keywords_and_patterns() {
+        let rules = [enforced(Rule {
+            keywords: Some(vec!["Free Airdrop".to_string()]),
+            patterns: Some(vec![r"t\.me/\w+".to_string()]),
+            ..rule(RuleAction::Reject)
+        })];
+        assert_eq!(
+            evaluate(&rules, &post("Claim your FREE airdrop today")).len(),
+            1
+        );
+        assert_eq!(evaluate(&rules, &post("join t.me/pumpgroup")).len(), 1);
+        assert!(evaluate(&rules, &post("An ordinary question about CKB")).is_empty());
+
+        let invalid = Rule {
+            patterns: Some(vec!["(unclosed".to_string()]),
+            ..rule(RuleAction::Reject)
+        };
+        assert!(SectionRule::new("at://x".to_string(), invalid).is_none());
+        assert!(evaluate(&[enforced(rule(RuleAction::Reject))], &post("anything")).is_empty());
+    }
+
+    #[test]
+    fn holds_new_and_busy_accounts() {
+        let rules = [enforced(Rule {
+            min_account_age_hours: Some(24),
+            max_posts_per_hour: Some(5),
+            reason: Some("Held for a moderator".to_string()),
+            ..rule(RuleAction::Hold)
+        })];
+        let fresh = PostFacts {
+            account_age_hours: 3,
+            ..post("hello")
+        };
+        assert_eq!(
+            evaluate(&rules, &fresh),
+            vec![Trip {
+                action: RuleAction::Hold,
+                label: None,
+                reason: "Held for a moderator".to_string(),
+            }]
+        );
+        let busy = PostFacts {
+            recent_posts: 5,
+            ..post("hello")
+        };
+        assert_eq!(evaluate(&rules, &busy).len(), 1);
+        let regular = PostFacts {
+            recent_posts: 4,
+            ..post("hello")
+        };
+        assert!(evaluate(&rules, &regular).is_empty());
+    }
+
+    #[test]
+    fn restricts_links() {
+        let rules = [enforced(Rule {
+            max_links: Some(2),
+            allowed_domains: Some(vec!["github.com".to_string()]),
+            label: Some("offsite".to_string()),
+            ..rule(RuleAction::Label)
+        })];
+        let links = |links: &[&str]| PostFacts {
+            links: links.iter().map(|link| link.to_string()).collect(),
+            ..post("see")
+        };
+        assert!(evaluate(&rules, &links(&["https://github.com/web5fans"])).is_empty());
+        assert!(evaluate(&rules, &links(&["https://gist.github.com/x"])).is_empty());
+        assert_eq!(
+            evaluate(&rules, &links(&["https://notgithub.com"])).len(),
+            1
+        );
+        assert_eq!(evaluate(&rules, &links(&["www.example.com"])).len(), 1);
+        let tripped = evaluate(
+            &rules,
+            &links(&[
+                "https://github.com/a",
+                "https://github.com/b",
+                "https://github.com/c",
+            ]),
+        );
+        assert_eq!(tripped[0].label.as_deref(), Some("offsite"));
+        assert_eq!(
+            tripped[0].reason,
+            "Tripped a section rule: more than 2 links"
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::ActorStore;
+use crate::bbs::POST_COLLECTION;
+use crate::config::ServerConfig;
+use crate::db::{establish_connection_for_jobs, DbConnPool};
+use crate::jetstream::{to_jetstream_evts, JetstreamCommit, JetstreamFilter, JetstreamKind};
+use crate::repo::prepare::{
+    prepare_create, prepare_delete, record_from_json, PrepareCreateOpts, PrepareDeleteOpts,
+};
+use crate::sequencer::events::CommitEvtOpAction;
+use crate::sequencer::{
+    get_subscriber_cursor, save_subscriber_cursor, RequestSeqRangeOpts, Sequencer,
+};
+use crate::shutdown::SharedShutdown;
+use crate::SharedSequencer;
+use anyhow::{anyhow, Result};
+use chrono::SecondsFormat;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Orbit, Rocket};
+use rsky_lexicon::app::bbs::actor::{CrosspostPref, CROSSPOST_PREF};
+use rsky_lexicon::app::bbs::{truncate_graphemes, Post};
+use rsky_lexicon::app::bsky::richtext::{ByteSlice, Facet, Features, Link};
+use rsky_repo::types::PreparedWrite;
+use rsky_syntax::aturi::AtUri;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Subscriber cursor the bridge keeps its place in repo_seq with
+const CURSOR_ID: &str = "bsky-bridge";
+const MIRROR_COLLECTION: &str = "app.bsky.feed.post";
+/// Longest app.bsky.feed.post text, in graphemes and in bytes
+const MAX_MIRROR_GRAPHEMES: usize = 300;
+const MAX_MIRROR_BYTES: usize = 3000;
+const ELLIPSIS: &str = "…";
+
+/// Mirrors new BBS posts of accounts that turned on
+/// `app.bbs.actor.defs#crosspostPref` to app.bsky.feed.post records, so they
+/// show up on Bluesky. A mirror is the post's title and the start of its
+/// text, with a link back to the thread. Replies aren't mirrored, nor are
+/// edits, and deleting a post deletes its mirror.
+///
+/// Mirrors are committed like createRecord writes, signed with the PDS's repo
+/// key, so accounts whose commits are signed client-side, as web5 accounts'
+/// are, are never mirrored, and app.bbs.actor.putPreferences won't let them
+/// turn it on. Tails repo_seq from its own cursor, starting
+/// from the head the first time so old posts aren't mirrored. Mirroring is
+/// best-effort, a failed mirror is logged and not retried.
+pub struct CrosspostBridge {
+    pub pool: DbConnPool,
+    pub sequencer: RwLock<Sequencer>,
+    pub blob_store: SharedBlobStore,
+    pub shutdown: SharedShutdown,
+    pub interval_ms: u64,
+    pub batch_size: i64,
+    pub thread_url: String,
+}
+
+impl CrosspostBridge {
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: bsky bridge run failed: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let id = CURSOR_ID.to_string();
+        let sequencer = self.sequencer.read().await;
+        let Some(mut cursor) = get_subscriber_cursor(&id).await? else {
+            let head = sequencer.curr().await?.unwrap_or(0);
+            return save_subscriber_cursor(&id, head).await;
+        };
+        let filter = JetstreamFilter::new(vec![POST_COLLECTION.to_string()], Vec::new())?;
+        let evts = sequencer
+            .request_seq_range(RequestSeqRangeOpts {
+                earliest_seq: Some(cursor),
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(self.batch_size),
+            })
+            .await?;
+        drop(sequencer);
+        if evts.is_empty() {
+            return Ok(());
+        }
+
+        let conn = &mut establish_connection_for_jobs()?;
+        for evt in evts {
+            // the rest of the batch is picked up after a restart
+            let Some(_write) = self.shutdown.begin_write() else {
+                break;
+            };
+            let seq = evt.seq();
+            for jetstream_evt in to_jetstream_evts(evt, &filter).await? {
+                if let JetstreamKind::Commit { commit } = jetstream_evt.kind {
+                    self.bridge(conn, &jetstream_evt.did, commit).await?;
+                }
+            }
+            cursor = seq;
+        }
+        save_subscriber_cursor(&id, cursor).await
+    }
+
+    async fn bridge(
+        &self,
+        conn: &mut PgConnection,
+        did: &str,
+        commit: JetstreamCommit,
+    ) -> Result<()> {
+        let uri = format!("at://{did}/{}/{}", commit.collection, commit.rkey);
+        match (commit.operation, commit.record) {
+            (CommitEvtOpAction::Create, Some(record)) => {
+                if !crosspost_enabled(conn, did)? {
+                    return Ok(());
+                }
+                let post: Post = match serde_json::from_value(record) {
+                    Ok(post) => post,
+                    Err(error) => {
+                        tracing::warn!("Not mirroring {uri}, it isn't a post: {error}");
+                        return Ok(());
+                    }
+                };
+                // another replica, or a replay of the same events, got to it first
+                if !claim(conn, did, &uri)? {
+                    return Ok(());
+                }
+                match self.mirror(did, &uri, post).await {
+                    Ok(Some(mirror_uri)) => set_mirror_uri(conn, &uri, mirror_uri)?,
+                    Ok(None) => {
+                        release(conn, &uri)?;
+                    }
+                    Err(error) => {
+                        tracing::warn!("Failed to mirror {uri} to bsky: {error:?}");
+                        release(conn, &uri)?;
+                    }
+                }
+            }
+            (CommitEvtOpAction::Delete, _) => {
+                let Some(mirror_uri) = release(conn, &uri)? else {
+                    return Ok(());
+                };
+                if let Err(error) = self.unmirror(did, &mirror_uri).await {
+                    tracing::warn!("Failed to delete bsky mirror {mirror_uri} of {uri}: {error:?}");
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Writes the mirror of `post`, unless the account was deactivated or
+    /// taken down since, or signs its own commits. Returns the mirror's uri.
+    async fn mirror(&self, did: &str, uri: &str, post: Post) -> Result<Option<String>> {
+        let account_manager = self.account_manager().await?;
+        match account_manager.get_account(did, None).await? {
+            Some(account) if account.ckb_address.is_none() => (),
+            Some(_) => {
+                tracing::warn!("Not mirroring {uri}, {did} signs its own commits");
+                return Ok(None);
+            }
+            None => return Ok(None),
+        }
+        let link = thread_link(&self.thread_url, uri);
+        let (text, facet) = mirror_text(&post.title, &post.text, &link);
+        let mut record = json!({
+            "$type": MIRROR_COLLECTION,
+            "text": text,
+            "facets": [facet],
+            "createdAt": post.created_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        });
+        if let Some(langs) = post.langs {
+            record["langs"] = json!(langs);
+        }
+        let write = prepare_create(PrepareCreateOpts {
+            did: did.to_string(),
+            collection: MIRROR_COLLECTION.to_string(),
+            record: record_from_json(record, None)?,
+            rkey: None,
+            validate: None,
+            swap_cid: None,
+            collections: None,
+        })
+        .await?;
+        let mirror_uri = write.uri.clone();
+        self.commit(did, account_manager, PreparedWrite::Create(write))
+            .await?;
+        Ok(Some(mirror_uri))
+    }
+
+    /// Deletes the mirror at `mirror_uri` if it's still around, the account
+    /// may have deleted it on Bluesky already
+    async fn unmirror(&self, did: &str, mirror_uri: &str) -> Result<()> {
+        let at_uri: AtUri = mirror_uri.to_string().try_into()?;
+        let db = self
+            .pool
+            .get()
+            .await
+            .ok_or_else(|| anyhow!("No database connection"))?;
+        let mut actor_store = ActorStore::new(
+            did.to_string(),
+            self.blob_store.for_did(did.to_string()),
+            db,
+        );
+        if actor_store
+            .record
+            .get_record(&at_uri, None, None)
+            .await?
+            .is_none()
+        {
+            return Ok(());
+        }
+        let write = prepare_delete(PrepareDeleteOpts {
+            did: did.to_string(),
+            collection: at_uri.get_collection(),
+            rkey: at_uri.get_rkey(),
+            swap_cid: None,
+            collections: None,
+        })?;
+        let account_manager = self.account_manager().await?;
+        self.commit(did, account_manager, PreparedWrite::Delete(write))
+            .await
+    }
+
+    async fn account_manager(&self) -> Result<AccountManager> {
+        let db = self
+            .pool
+            .get()
+            .await
+            .ok_or_else(|| anyhow!("No database connection"))?;
+        Ok(AccountManager::new(Arc::new(db)))
+    }
+
+    /// Commits `write` to `did`'s repo the way createRecord and deleteRecord do
+    async fn commit(
+        &self,
+        did: &str,
+        account_manager: AccountManager,
+        write: PreparedWrite,
+    ) -> Result<()> {
+        let db = self
+            .pool
+            .get()
+            .await
+            .ok_or_else(|| anyhow!("No database connection"))?;
+        let mut actor_store = ActorStore::new(
+            did.to_string(),
+            self.blob_store.for_did(did.to_string()),
+            db,
+        );
+        // held until the new root is recorded so the account's own writes
+        // can't build on the same head
+        let _write_lock = actor_store.lock_writes().await?;
+        let commit = actor_store.process_writes(vec![write], None).await?;
+        let mut lock = self.sequencer.write().await;
+        lock.sequence_commit(did.to_string(), commit.clone())
+            .await?;
+        account_manager
+            .update_repo_root(
+                did.to_string(),
+                commit.commit_data.cid,
+                commit.commit_data.rev,
+            )
+            .await
+    }
+}
+
+/// Whether `did` turned on `app.bbs.actor.defs#crosspostPref`
+fn crosspost_enabled(conn: &mut PgConnection, did: &str) -> Result<bool> {
+    use crate::schema::pds::account_pref::dsl as AccountPrefSchema;
+
+    let value: Option<Option<String>> = AccountPrefSchema::account_pref
+        .filter(AccountPrefSchema::did.eq(did))
+        .filter(AccountPrefSchema::name.eq(CROSSPOST_PREF))
+        .select(AccountPrefSchema::valueJson)
+        .first(conn)
+        .optional()?;
+    Ok(value
+        .flatten()
+        .and_then(|value| serde_json::from_str::<CrosspostPref>(&value).ok())
+        .is_some_and(|pref| pref.enabled))
+}
+
+/// Claims `uri` for this replica to mirror. False if it's been claimed before.
+fn claim(conn: &mut PgConnection, did: &str, uri: &str) -> Result<bool> {
+    use crate::schema::pds::bbs_crosspost::dsl as CrosspostSchema;
+
+    let claimed = insert_into(CrosspostSchema::bbs_crosspost)
+        .values((
+            CrosspostSchema::uri.eq(uri),
+            CrosspostSchema::did.eq(did),
+            CrosspostSchema::createdAt.eq(rsky_common::now()),
+        ))
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(claimed > 0)
+}
+
+fn set_mirror_uri(conn: &mut PgConnection, uri: &str, mirror_uri: String) -> Result<()> {
+    use crate::schema::pds::bbs_crosspost::dsl as CrosspostSchema;
+
+    update(CrosspostSchema::bbs_crosspost.find(uri))
+        .set(CrosspostSchema::mirrorUri.eq(Some(mirror_uri)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Forgets `uri` was mirrored, returning its mirror's uri if it had one
+fn release(conn: &mut PgConnection, uri: &str) -> Result<Option<String>> {
+    use crate::schema::pds::bbs_crosspost::dsl as CrosspostSchema;
+
+    Ok(delete(CrosspostSchema::bbs_crosspost.find(uri))
+        .returning(CrosspostSchema::mirrorUri)
+        .get_result::<Option<String>>(conn)
+        .optional()?
+        .flatten())
+}
+
+/// `thread_url` with the post's AT URI filled in
+fn thread_link(thread_url: &str, uri: &str) -> String {
+    let uri: String = url::form_urlencoded::byte_serialize(uri.as_bytes()).collect();
+    thread_url.replace("{uri}", &uri)
+}
+
+/// The mirror's text: `title`, then `text`, cut short with an ellipsis where
+/// they'd run past Bluesky's limit, and `link` on a line of its own. Also
+/// returns the facet making `link` a link.
+fn mirror_text(title: &str, text: &str, link: &str) -> (String, Facet) {
+    let body = match text.trim() {
+        "" => title.trim().to_string(),
+        text => format!("{}\n\n{text}", title.trim()),
+    };
+    // the link is percent-encoded, so each of its chars is a grapheme
+    let separator = "\n\n";
+    let max_graphemes = MAX_MIRROR_GRAPHEMES.saturating_sub(link.chars().count() + 2);
+    let max_bytes = MAX_MIRROR_BYTES.saturating_sub(link.len() + separator.len());
+    let (mut body, truncated) = truncate_graphemes(&body, max_graphemes);
+    if truncated || body.len() > max_bytes {
+        // room for the ellipsis
+        body = truncate_graphemes(&body, max_graphemes.saturating_sub(1)).0;
+        let mut end = max_bytes.saturating_sub(ELLIPSIS.len()).min(body.len());
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+        body = format!("{}{ELLIPSIS}", body.trim_end());
+    }
+
+    let text = format!("{body}{separator}{link}");
+    let facet = Facet {
+        index: ByteSlice {
+            byte_start: text.len() - link.len(),
+            byte_end: text.len(),
+        },
+        features: vec![Features::Link(Link {
+            uri: link.to_string(),
+        })],
+    };
+    (text, facet)
+}
+
+/// Starts the [`CrosspostBridge`] once Rocket has set up the database pool
+/// and managed state it writes with, if it's enabled.
+pub struct CrosspostBridgeFairing;
+
+#[rocket::async_trait]
+impl Fairing for CrosspostBridgeFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Mirror BBS posts to bsky",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(cfg) = rocket.state::<ServerConfig>() else {
+            return;
+        };
+        // check_config refuses a bridge without a thread url
+        let (true, Some(thread_url)) = (cfg.bridge.enabled, cfg.bridge.thread_url.clone()) else {
+            return;
+        };
+        let (Some(pool), Some(sequencer), Some(blob_store), Some(shutdown)) = (
+            DbConnPool::of(rocket),
+            rocket.state::<SharedSequencer>(),
+            rocket.state::<SharedBlobStore>(),
+            rocket.state::<SharedShutdown>(),
+        ) else {
+            tracing::error!("@LOG: ERROR: bsky bridge can't start, missing state");
+            return;
+        };
+        let bridge = CrosspostBridge {
+            pool,
+            sequencer: RwLock::new(sequencer.sequencer.read().await.clone()),
+            blob_store: blob_store.clone(),
+            shutdown: shutdown.clone(),
+            interval_ms: cfg.bridge.interval_ms.max(1000),
+            batch_size: cfg.bridge.batch_size.max(1),
+            thread_url,
+        };
+        tokio::spawn(async move { bridge.start().await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK: &str = "https://bbs.example.com/t/at%3A%2F%2Fdid%3Aweb5%3Aalice%2Fapp.bbs.post%2F1";
+
+    #[test]
+    fn links_back_to_the_thread() {
+        assert_eq!(
+            thread_link(
+                "https://bbs.example.com/t/{uri}",
+                "at://did:web5:alice/app.bbs.post/1"
+            ),
+            LINK
+        );
+    }
+
+    #[test]
+    fn mirrors_short_posts_whole() {
+        let (text, facet) = mirror_text("Hello", "First post!", LINK);
+        assert_eq!(text, format!("Hello\n\nFirst post!\n\n{LINK}"));
+        assert_eq!(&text[facet.index.byte_start..facet.index.byte_end], LINK);
+        assert_eq!(
+            facet.features,
+            vec![Features::Link(Link {
+                uri: LINK.to_string()
+            })]
+        );
+
+        let (text, _) = mirror_text("Just a title", "  ", LINK);
+        assert_eq!(text, format!("Just a title\n\n{LINK}"));
+    }
+
+    #[test]
+    fn cuts_long_posts_short_to_fit_the_link() {
+        let (text, facet) = mirror_text("Über", &"ä".repeat(1000), LINK);
+        assert_eq!(text.chars().count(), MAX_MIRROR_GRAPHEMES);
+        assert!(text.starts_with("Über\n\nää"));
+        assert!(text.ends_with(&format!("ä{ELLIPSIS}\n\n{LINK}")));
+        assert_eq!(&text[facet.index.byte_start..facet.index.byte_end], LINK);
+    }
+}
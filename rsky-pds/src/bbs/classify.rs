@@ -0,0 +1,433 @@
+use crate::bbs::{post_links, POST_COLLECTION, REPLY_COLLECTION};
+use crate::config::ClassifierConfig;
+use crate::db::establish_connection_for_jobs;
+use crate::jetstream::{to_jetstream_evts, JetstreamCommit, JetstreamFilter, JetstreamKind};
+use crate::labeler::insert_labels;
+use crate::models::BbsReview;
+use crate::sequencer::events::CommitEvtOpAction;
+use crate::sequencer::{
+    get_subscriber_cursor, save_subscriber_cursor, RequestSeqRangeOpts, Sequencer,
+};
+use crate::APP_USER_AGENT;
+use anyhow::{bail, Result};
+use diesel::insert_into;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rsky_lexicon::com::atproto::admin::CreateLabel;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Subscriber cursor the screener keeps its place in repo_seq with
+const CURSOR_ID: &str = "content-classifier";
+pub const SPAM_LABEL: &str = "spam";
+/// Fewer links than this are never spam by density alone, so a short post
+/// sharing one link isn't labeled
+const MIN_SPAM_LINKS: usize = 3;
+/// Texts shorter than this, like "thanks!", are expected to repeat
+const MIN_DUPLICATE_CHARS: usize = 20;
+
+/// What classifiers are shown of a BBS post or reply
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Content {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub collection: String,
+    pub text: String,
+    pub record: Value,
+}
+
+/// What a classifier wants done with a post or reply. Empty when it's fine.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    /// Label values this service labels the record with, e.g. `spam`
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Why the record should be held for a moderator, if it should
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<String>,
+}
+
+/// Looks at BBS posts and replies after they're committed and decides
+/// whether to label them or hold them for review.
+#[rocket::async_trait]
+pub trait ContentClassifier: Send + Sync {
+    /// Names the classifier in the review queue
+    fn name(&self) -> &'static str;
+
+    async fn classify(&self, content: &Content) -> Result<Verdict>;
+}
+
+/// Catches the cheap and obvious: posts that are mostly links, and the same
+/// text posted over and over, by one account or many.
+///
+/// Recent texts are remembered in process, so replicas each count their own
+/// duplicates and a restart forgets them.
+pub struct HeuristicClassifier {
+    pub max_link_density: f64,
+    pub max_duplicates: usize,
+    pub duplicate_window: usize,
+    recent: Mutex<RecentTexts>,
+}
+
+#[derive(Default)]
+struct RecentTexts {
+    order: VecDeque<u64>,
+    counts: HashMap<u64, usize>,
+}
+
+impl HeuristicClassifier {
+    pub fn new(cfg: &ClassifierConfig) -> Self {
+        HeuristicClassifier {
+            max_link_density: cfg.max_link_density,
+            max_duplicates: cfg.max_duplicates.max(1),
+            duplicate_window: cfg.duplicate_window.max(1),
+            recent: Mutex::new(RecentTexts::default()),
+        }
+    }
+
+    /// Remembers `text` and returns how often it was seen within the window,
+    /// this time included
+    fn seen(&self, text: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut recent = self.recent.lock().expect("recent texts poisoned");
+        recent.order.push_back(hash);
+        *recent.counts.entry(hash).or_insert(0) += 1;
+        while recent.order.len() > self.duplicate_window {
+            if let Some(oldest) = recent.order.pop_front() {
+                if let Some(count) = recent.counts.get_mut(&oldest) {
+                    *count -= 1;
+                    if *count == 0 {
+                        recent.counts.remove(&oldest);
+                    }
+                }
+            }
+        }
+        recent.counts.get(&hash).copied().unwrap_or(0)
+    }
+}
+
+#[rocket::async_trait]
+impl ContentClassifier for HeuristicClassifier {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    async fn classify(&self, content: &Content) -> Result<Verdict> {
+        let mut verdict = Verdict::default();
+
+        let words = content.text.split_whitespace().count().max(1);
+        let links = post_links(&content.text, &content.record).len();
+        let density = links as f64 * 100.0 / words as f64;
+        if links >= MIN_SPAM_LINKS && density > self.max_link_density {
+            verdict.labels.push(SPAM_LABEL.to_string());
+        }
+
+        let normalized = normalize(&content.text);
+        if normalized.chars().count() >= MIN_DUPLICATE_CHARS {
+            let seen = self.seen(&normalized);
+            if seen > self.max_duplicates {
+                verdict.review = Some(format!("Same text posted {seen} times recently"));
+            }
+        }
+        Ok(verdict)
+    }
+}
+
+/// Asks an external service for a verdict. The content is POSTed as JSON and
+/// the service responds with a [`Verdict`].
+pub struct HttpClassifier {
+    pub url: String,
+    pub client: reqwest::Client,
+}
+
+impl HttpClassifier {
+    pub fn new(url: String, cfg: &ClassifierConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .timeout(Duration::from_millis(cfg.callout_timeout_ms))
+            .build()?;
+        Ok(HttpClassifier { url, client })
+    }
+}
+
+#[rocket::async_trait]
+impl ContentClassifier for HttpClassifier {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    async fn classify(&self, content: &Content) -> Result<Verdict> {
+        let res = self.client.post(&self.url).json(content).send().await?;
+        if !res.status().is_success() {
+            bail!("Classifier responded with {}", res.status());
+        }
+        Ok(res.json::<Verdict>().await?)
+    }
+}
+
+/// Runs new and edited BBS posts and replies through the classifiers.
+///
+/// Tails repo_seq from its own cursor, starting from the head the first time,
+/// so a write is committed and sequenced before anything looks at it and a
+/// slow or failing classifier never holds one up. Labels are issued by this
+/// service and served like the ones moderators create; records held for
+/// review go to `bbs_review`. A classifier that errors is logged and skipped
+/// for that record, it isn't retried.
+pub struct ContentScreener {
+    pub sequencer: Sequencer,
+    pub classifiers: Vec<Box<dyn ContentClassifier>>,
+    /// This service's DID, which the labels are issued by
+    pub labeler_did: String,
+    pub interval_ms: u64,
+    pub batch_size: i64,
+}
+
+impl ContentScreener {
+    pub fn new(sequencer: Sequencer, labeler_did: String, cfg: &ClassifierConfig) -> Result<Self> {
+        let mut classifiers: Vec<Box<dyn ContentClassifier>> =
+            vec![Box::new(HeuristicClassifier::new(cfg))];
+        if let Some(ref url) = cfg.callout_url {
+            classifiers.push(Box::new(HttpClassifier::new(url.clone(), cfg)?));
+        }
+        Ok(ContentScreener {
+            sequencer,
+            classifiers,
+            labeler_did,
+            interval_ms: cfg.interval_ms.max(1000),
+            batch_size: cfg.batch_size.max(1),
+        })
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: content classification run failed: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let id = CURSOR_ID.to_string();
+        let Some(mut cursor) = get_subscriber_cursor(&id).await? else {
+            let head = self.sequencer.curr().await?.unwrap_or(0);
+            return save_subscriber_cursor(&id, head).await;
+        };
+        let filter = JetstreamFilter::new(
+            vec![POST_COLLECTION.to_string(), REPLY_COLLECTION.to_string()],
+            Vec::new(),
+        )?;
+        let evts = self
+            .sequencer
+            .request_seq_range(RequestSeqRangeOpts {
+                earliest_seq: Some(cursor),
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(self.batch_size),
+            })
+            .await?;
+        if evts.is_empty() {
+            return Ok(());
+        }
+
+        let conn = &mut establish_connection_for_jobs()?;
+        for evt in evts {
+            cursor = evt.seq();
+            for jetstream_evt in to_jetstream_evts(evt, &filter).await? {
+                if let JetstreamKind::Commit { commit } = jetstream_evt.kind {
+                    if let Some(content) = to_content(&jetstream_evt.did, commit) {
+                        self.screen(conn, content).await?;
+                    }
+                }
+            }
+        }
+        save_subscriber_cursor(&id, cursor).await
+    }
+
+    async fn screen(&self, conn: &mut PgConnection, content: Content) -> Result<()> {
+        for classifier in self.classifiers.iter() {
+            let verdict = match classifier.classify(&content).await {
+                Ok(verdict) => verdict,
+                Err(error) => {
+                    tracing::warn!(
+                        "Classifier {} failed on {}: {error}",
+                        classifier.name(),
+                        content.uri
+                    );
+                    continue;
+                }
+            };
+            apply_verdict(
+                conn,
+                &self.labeler_did,
+                classifier.name(),
+                &content,
+                verdict,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Labels the record and queues it for review as the verdict says
+fn apply_verdict(
+    conn: &mut PgConnection,
+    labeler_did: &str,
+    source: &str,
+    content: &Content,
+    verdict: Verdict,
+) -> Result<()> {
+    use crate::schema::pds::bbs_review::dsl as ReviewSchema;
+
+    if !verdict.labels.is_empty() {
+        let labels = verdict
+            .labels
+            .into_iter()
+            .map(|val| CreateLabel {
+                uri: content.uri.clone(),
+                cid: Some(content.cid.clone()),
+                val,
+                neg: None,
+                exp: None,
+            })
+            .collect();
+        insert_labels(conn, labeler_did.to_string(), labels)?;
+    }
+    if let Some(reason) = verdict.review {
+        insert_into(ReviewSchema::bbs_review)
+            .values(BbsReview {
+                uri: content.uri.clone(),
+                cid: content.cid.clone(),
+                did: content.did.clone(),
+                source: source.to_string(),
+                reason,
+                created_at: rsky_common::now(),
+                resolved_at: None,
+                held: false,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// The content of a created or updated post or reply, `None` for deletes
+fn to_content(did: &str, commit: JetstreamCommit) -> Option<Content> {
+    let record = match (commit.operation, commit.record) {
+        (CommitEvtOpAction::Create | CommitEvtOpAction::Update, Some(record)) => record,
+        _ => return None,
+    };
+    let text = ["title", "text"]
+        .iter()
+        .filter_map(|field| record.get(field).and_then(Value::as_str))
+        .collect::<Vec<&str>>()
+        .join("\n");
+    Some(Content {
+        uri: format!("at://{did}/{}/{}", commit.collection, commit.rkey),
+        cid: commit.cid?,
+        did: did.to_string(),
+        collection: commit.collection,
+        text,
+        record,
+    })
+}
+
+/// Lowercased with whitespace collapsed, so trivially varied copies match
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> ClassifierConfig {
+        ClassifierConfig {
+            enabled: true,
+            interval_ms: 1000,
+            batch_size: 100,
+            max_link_density: 20.0,
+            max_duplicates: 2,
+            duplicate_window: 3,
+            callout_url: None,
+            callout_timeout_ms: 1000,
+        }
+    }
+
+    fn content(text: &str) -> Content {
+        Content {
+            uri: "at://did:web5:alice/app.bbs.post/3l".to_string(),
+            cid: "bafyreidfayvfuwqa7qlnopdjiqrxzs6blmoeu4rujcjtnci5beludirz2a".to_string(),
+            did: "did:web5:alice".to_string(),
+            collection: POST_COLLECTION.to_string(),
+            text: text.to_string(),
+            record: serde_json::json!({ "text": text }),
+        }
+    }
+
+    #[tokio::test]
+    async fn labels_posts_that_are_mostly_links() {
+        let classifier = HeuristicClassifier::new(&cfg());
+        let verdict = classifier
+            .classify(&content(
+                "cheap https://a.example https://b.example https://c.example",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(verdict.labels, vec![SPAM_LABEL.to_string()]);
+
+        // One link in a short post is just sharing a link
+        let verdict = classifier
+            .classify(&content("have a look https://a.example"))
+            .await
+            .unwrap();
+        assert!(verdict.labels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn holds_repeated_text_for_review() {
+        let classifier = HeuristicClassifier::new(&cfg());
+        let spam = "Buy followers now, best prices on the whole BBS";
+        for _ in 0..2 {
+            let verdict = classifier.classify(&content(spam)).await.unwrap();
+            assert_eq!(verdict.review, None);
+        }
+        let verdict = classifier
+            .classify(&content(&spam.to_uppercase()))
+            .await
+            .unwrap();
+        assert!(verdict.review.is_some());
+
+        // Repeats that fell out of the window are forgotten
+        for text in [
+            "first unrelated post text",
+            "second unrelated post text",
+            "third unrelated post text",
+        ] {
+            classifier.classify(&content(text)).await.unwrap();
+        }
+        let verdict = classifier.classify(&content(spam)).await.unwrap();
+        assert_eq!(verdict.review, None);
+    }
+
+    #[tokio::test]
+    async fn short_texts_are_never_duplicates() {
+        let classifier = HeuristicClassifier::new(&cfg());
+        for _ in 0..5 {
+            let verdict = classifier.classify(&content("thanks!")).await.unwrap();
+            assert_eq!(verdict, Verdict::default());
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use crate::models::{BbsDmConvo, BbsDmMessage};
+use anyhow::{bail, Result};
+use diesel::dsl::max;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{insert_into, update};
+use rsky_common::now;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+
+/// The conversation doesn't exist or the requester isn't one of its members,
+/// which callers can't tell apart
+#[derive(thiserror::Error, Debug)]
+#[error("Conversation not found")]
+pub struct ConvoNotFoundError;
+
+/// A conversation as one of its members sees it
+#[derive(Debug, Clone)]
+pub struct ConvoSummary {
+    pub convo: BbsDmConvo,
+    pub members: Vec<String>,
+    pub last_message: Option<BbsDmMessage>,
+    pub unread_count: i64,
+}
+
+/// The same set of members always lands in the same conversation, whoever
+/// writes first and in whatever order they're listed.
+pub fn convo_id_for(members: &[String]) -> String {
+    let members: BTreeSet<&str> = members.iter().map(String::as_str).collect();
+    let joined = members.into_iter().collect::<Vec<&str>>().join("\n");
+    hex::encode(Sha256::digest(joined.as_bytes()))
+}
+
+fn assert_member(conn: &mut PgConnection, convo_id: &str, did: &str) -> Result<()> {
+    use crate::schema::pds::bbs_dm_member::dsl as MemberSchema;
+
+    let found = MemberSchema::bbs_dm_member
+        .filter(MemberSchema::convoId.eq(convo_id))
+        .filter(MemberSchema::did.eq(did))
+        .select(MemberSchema::did)
+        .first::<String>(conn)
+        .optional()?;
+    match found {
+        Some(_) => Ok(()),
+        None => Err(ConvoNotFoundError.into()),
+    }
+}
+
+/// Posts `text` into `convo_id`, or into the conversation between the sender
+/// and `members`, starting it if needed. The sender has read their own message.
+pub fn send_message(
+    conn: &mut PgConnection,
+    sender: &str,
+    convo_id: Option<String>,
+    members: Option<Vec<String>>,
+    text: String,
+) -> Result<BbsDmMessage> {
+    use crate::schema::pds::bbs_dm_convo::dsl as ConvoSchema;
+    use crate::schema::pds::bbs_dm_member::dsl as MemberSchema;
+    use crate::schema::pds::bbs_dm_message::dsl as MessageSchema;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let created_at = now();
+        let convo_id = match (convo_id, members) {
+            (Some(convo_id), _) => {
+                assert_member(conn, &convo_id, sender)?;
+                convo_id
+            }
+            (None, Some(mut members)) => {
+                members.push(sender.to_string());
+                let convo_id = convo_id_for(&members);
+                insert_into(ConvoSchema::bbs_dm_convo)
+                    .values((
+                        ConvoSchema::id.eq(&convo_id),
+                        ConvoSchema::createdAt.eq(&created_at),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+                let rows: Vec<_> = members
+                    .iter()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(|did| {
+                        (
+                            MemberSchema::convoId.eq(&convo_id),
+                            MemberSchema::did.eq(did),
+                        )
+                    })
+                    .collect();
+                insert_into(MemberSchema::bbs_dm_member)
+                    .values(rows)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+                convo_id
+            }
+            (None, None) => bail!("Either convoId or members is required"),
+        };
+
+        let message = insert_into(MessageSchema::bbs_dm_message)
+            .values((
+                MessageSchema::convoId.eq(&convo_id),
+                MessageSchema::senderDid.eq(sender),
+                MessageSchema::text.eq(text),
+                MessageSchema::createdAt.eq(&created_at),
+            ))
+            .returning(BbsDmMessage::as_returning())
+            .get_result(conn)?;
+        update(ConvoSchema::bbs_dm_convo)
+            .filter(ConvoSchema::id.eq(&convo_id))
+            .set(ConvoSchema::lastMessageAt.eq(&created_at))
+            .execute(conn)?;
+        update(MemberSchema::bbs_dm_member)
+            .filter(MemberSchema::convoId.eq(&convo_id))
+            .filter(MemberSchema::did.eq(sender))
+            .set(MemberSchema::lastReadId.eq(message.id))
+            .execute(conn)?;
+        Ok(message)
+    })
+}
+
+/// Messages in a conversation `did` is a member of, newest first, older than
+/// the message id `cursor`.
+pub fn list_messages(
+    conn: &mut PgConnection,
+    did: &str,
+    convo_id: &str,
+    limit: i64,
+    cursor: Option<i64>,
+) -> Result<Vec<BbsDmMessage>> {
+    use crate::schema::pds::bbs_dm_message::dsl as MessageSchema;
+
+    assert_member(conn, convo_id, did)?;
+    let mut builder = MessageSchema::bbs_dm_message
+        .filter(MessageSchema::convoId.eq(convo_id))
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        builder = builder.filter(MessageSchema::id.lt(cursor));
+    }
+    Ok(builder
+        .order(MessageSchema::id.desc())
+        .limit(limit)
+        .select(BbsDmMessage::as_select())
+        .load(conn)?)
+}
+
+/// The conversations `did` is in, most recently active first. `cursor` is
+/// `lastMessageAt::id` of the last conversation on the previous page.
+pub fn list_convos(
+    conn: &mut PgConnection,
+    did: &str,
+    limit: i64,
+    cursor: Option<String>,
+) -> Result<Vec<ConvoSummary>> {
+    use crate::schema::pds::bbs_dm_convo::dsl as ConvoSchema;
+    use crate::schema::pds::bbs_dm_member::dsl as MemberSchema;
+    use crate::schema::pds::bbs_dm_message::dsl as MessageSchema;
+
+    let mut builder = ConvoSchema::bbs_dm_convo
+        .inner_join(MemberSchema::bbs_dm_member.on(MemberSchema::convoId.eq(ConvoSchema::id)))
+        .filter(MemberSchema::did.eq(did))
+        .filter(ConvoSchema::lastMessageAt.is_not_null())
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        let Some((last_message_at, id)) = cursor.split_once("::") else {
+            bail!("Malformed cursor");
+        };
+        builder = builder.filter(
+            ConvoSchema::lastMessageAt
+                .lt(last_message_at.to_string())
+                .or(ConvoSchema::lastMessageAt
+                    .eq(last_message_at.to_string())
+                    .and(ConvoSchema::id.lt(id.to_string()))),
+        );
+    }
+    let rows: Vec<(BbsDmConvo, Option<i64>)> = builder
+        .order((ConvoSchema::lastMessageAt.desc(), ConvoSchema::id.desc()))
+        .limit(limit)
+        .select((BbsDmConvo::as_select(), MemberSchema::lastReadId))
+        .load(conn)?;
+
+    rows.into_iter()
+        .map(|(convo, last_read_id)| {
+            let members = MemberSchema::bbs_dm_member
+                .filter(MemberSchema::convoId.eq(&convo.id))
+                .order(MemberSchema::did.asc())
+                .select(MemberSchema::did)
+                .load::<String>(conn)?;
+            let last_message = MessageSchema::bbs_dm_message
+                .filter(MessageSchema::convoId.eq(&convo.id))
+                .order(MessageSchema::id.desc())
+                .select(BbsDmMessage::as_select())
+                .first(conn)
+                .optional()?;
+            let unread_count = MessageSchema::bbs_dm_message
+                .filter(MessageSchema::convoId.eq(&convo.id))
+                .filter(MessageSchema::senderDid.ne(did))
+                .filter(MessageSchema::id.gt(last_read_id.unwrap_or(0)))
+                .count()
+                .get_result(conn)?;
+            Ok(ConvoSummary {
+                convo,
+                members,
+                last_message,
+                unread_count,
+            })
+        })
+        .collect()
+}
+
+/// Moves `did`'s read marker up to `message_id`, or to the conversation's
+/// newest message. The marker never moves back.
+pub fn mark_read(
+    conn: &mut PgConnection,
+    did: &str,
+    convo_id: &str,
+    message_id: Option<i64>,
+) -> Result<()> {
+    use crate::schema::pds::bbs_dm_member::dsl as MemberSchema;
+    use crate::schema::pds::bbs_dm_message::dsl as MessageSchema;
+
+    assert_member(conn, convo_id, did)?;
+    let latest = MessageSchema::bbs_dm_message
+        .filter(MessageSchema::convoId.eq(convo_id))
+        .select(max(MessageSchema::id))
+        .first::<Option<i64>>(conn)?;
+    let Some(latest) = latest else {
+        return Ok(());
+    };
+    let read_up_to = match message_id {
+        Some(message_id) => message_id.min(latest),
+        None => latest,
+    };
+    update(MemberSchema::bbs_dm_member)
+        .filter(MemberSchema::convoId.eq(convo_id))
+        .filter(MemberSchema::did.eq(did))
+        .filter(
+            MemberSchema::lastReadId
+                .is_null()
+                .or(MemberSchema::lastReadId.lt(read_up_to)),
+        )
+        .set(MemberSchema::lastReadId.eq(read_up_to))
+        .execute(conn)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convo_ids_ignore_member_order_and_repeats() {
+        let alice = "did:web5:alice".to_string();
+        let bob = "did:web5:bob".to_string();
+        let carol = "did:web5:carol".to_string();
+        assert_eq!(
+            convo_id_for(&[alice.clone(), bob.clone()]),
+            convo_id_for(&[bob.clone(), alice.clone(), bob.clone()])
+        );
+        assert_ne!(
+            convo_id_for(&[alice.clone(), bob.clone()]),
+            convo_id_for(&[alice, bob, carol])
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::BbsDraft;
+use anyhow::{anyhow, Result};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use rsky_common::now;
+
+/// Largest draft value, as JSON, generously above the longest post
+pub const MAX_DRAFT_BYTES: usize = 200_000;
+
+/// The draft doesn't exist or belongs to another account, which callers
+/// can't tell apart
+#[derive(thiserror::Error, Debug)]
+#[error("Draft not found")]
+pub struct DraftNotFoundError;
+
+/// The account already keeps as many drafts as the PDS allows
+#[derive(thiserror::Error, Debug)]
+#[error("At most {0} drafts can be kept, delete one first")]
+pub struct TooManyDraftsError(pub i64);
+
+/// Saves `value` as a new draft of `did`'s, or over their draft `id`
+pub fn save_draft(
+    conn: &mut PgConnection,
+    did: &str,
+    id: Option<i64>,
+    collection: &str,
+    value: String,
+    max_drafts: i64,
+) -> Result<BbsDraft> {
+    use crate::schema::pds::bbs_draft::dsl as DraftSchema;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let saved_at = now();
+        match id {
+            Some(id) => update(DraftSchema::bbs_draft.find(id))
+                .filter(DraftSchema::did.eq(did))
+                .set((
+                    DraftSchema::collection.eq(collection),
+                    DraftSchema::value.eq(value),
+                    DraftSchema::updatedAt.eq(&saved_at),
+                ))
+                .returning(BbsDraft::as_returning())
+                .get_result(conn)
+                .optional()?
+                .ok_or_else(|| DraftNotFoundError.into()),
+            None => {
+                let drafts: i64 = DraftSchema::bbs_draft
+                    .filter(DraftSchema::did.eq(did))
+                    .count()
+                    .get_result(conn)?;
+                if drafts >= max_drafts {
+                    return Err(TooManyDraftsError(max_drafts).into());
+                }
+                Ok(insert_into(DraftSchema::bbs_draft)
+                    .values((
+                        DraftSchema::did.eq(did),
+                        DraftSchema::collection.eq(collection),
+                        DraftSchema::value.eq(value),
+                        DraftSchema::createdAt.eq(&saved_at),
+                        DraftSchema::updatedAt.eq(&saved_at),
+                    ))
+                    .returning(BbsDraft::as_returning())
+                    .get_result(conn)?)
+            }
+        }
+    })
+}
+
+/// `did`'s drafts, most recently saved first. `cursor` is `<updatedAt>::<id>`
+/// of the last draft of the previous page.
+pub fn list_drafts(
+    conn: &mut PgConnection,
+    did: &str,
+    limit: i64,
+    cursor: Option<String>,
+) -> Result<Vec<BbsDraft>> {
+    use crate::schema::pds::bbs_draft::dsl as DraftSchema;
+
+    let mut query = DraftSchema::bbs_draft
+        .filter(DraftSchema::did.eq(did))
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        let (updated_at, id) = parse_cursor(&cursor)?;
+        query = query.filter(
+            DraftSchema::updatedAt
+                .lt(updated_at.clone())
+                .or(DraftSchema::updatedAt
+                    .eq(updated_at)
+                    .and(DraftSchema::id.lt(id))),
+        );
+    }
+    Ok(query
+        .order((DraftSchema::updatedAt.desc(), DraftSchema::id.desc()))
+        .limit(limit)
+        .select(BbsDraft::as_select())
+        .load(conn)?)
+}
+
+/// Deletes `did`'s draft `id`
+pub fn delete_draft(conn: &mut PgConnection, did: &str, id: i64) -> Result<()> {
+    use crate::schema::pds::bbs_draft::dsl as DraftSchema;
+
+    let deleted = delete(DraftSchema::bbs_draft.find(id))
+        .filter(DraftSchema::did.eq(did))
+        .execute(conn)?;
+    match deleted {
+        0 => Err(DraftNotFoundError.into()),
+        _ => Ok(()),
+    }
+}
+
+fn parse_cursor(cursor: &str) -> Result<(String, i64)> {
+    cursor
+        .split_once("::")
+        .and_then(|(updated_at, id)| Some((updated_at.to_string(), id.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Malformed cursor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cursors_of_listed_drafts() {
+        assert_eq!(
+            parse_cursor("2025-01-02T00:00:00.000Z::7").unwrap(),
+            ("2025-01-02T00:00:00.000Z".to_string(), 7)
+        );
+        assert!(parse_cursor("2025-01-02T00:00:00.000Z").is_err());
+        assert!(parse_cursor("2025-01-02T00:00:00.000Z::seven").is_err());
+    }
+}
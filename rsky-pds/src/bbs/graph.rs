@@ -0,0 +1,91 @@
+use crate::bbs::{BLOCK_COLLECTION, MUTE_COLLECTION};
+use crate::models::BbsGraph;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rsky_repo::storage::Ipld;
+use rsky_repo::types::{Lex, RepoRecord};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+
+/// The account a block or mute record is about, `None` for any other record.
+pub fn relation_subject(collection: &str, record: &RepoRecord) -> Option<String> {
+    if collection != BLOCK_COLLECTION && collection != MUTE_COLLECTION {
+        return None;
+    }
+    match record.get("subject") {
+        Some(Lex::Ipld(Ipld::Json(JsonValue::String(subject)))) => Some(subject.clone()),
+        _ => None,
+    }
+}
+
+/// Indexes a block or mute, replacing the row of an earlier version of the
+/// record so an update pointing at someone else moves the relationship.
+pub fn index_relation(conn: &mut PgConnection, relation: BbsGraph) -> Result<()> {
+    use crate::schema::pds::bbs_graph::dsl as GraphSchema;
+
+    insert_into(GraphSchema::bbs_graph)
+        .values(&relation)
+        .on_conflict(GraphSchema::uri)
+        .do_update()
+        .set(GraphSchema::subjectDid.eq(&relation.subject_did))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Which of `authors` the viewer must not see: accounts the viewer blocks or
+/// mutes, and accounts that block the viewer. Only relationships written on
+/// this PDS are known, blocks from accounts hosted elsewhere are left to the
+/// AppView.
+pub fn hidden_authors(
+    conn: &mut PgConnection,
+    viewer: &str,
+    authors: Vec<String>,
+) -> Result<HashSet<String>> {
+    use crate::schema::pds::bbs_graph::dsl as GraphSchema;
+
+    if authors.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let mut hidden = GraphSchema::bbs_graph
+        .filter(GraphSchema::creatorDid.eq(viewer))
+        .filter(GraphSchema::subjectDid.eq_any(&authors))
+        .select(GraphSchema::subjectDid)
+        .load::<String>(conn)?
+        .into_iter()
+        .collect::<HashSet<String>>();
+    hidden.extend(
+        GraphSchema::bbs_graph
+            .filter(GraphSchema::subjectDid.eq(viewer))
+            .filter(GraphSchema::collection.eq(BLOCK_COLLECTION))
+            .filter(GraphSchema::creatorDid.eq_any(&authors))
+            .select(GraphSchema::creatorDid)
+            .load::<String>(conn)?,
+    );
+    Ok(hidden)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_blocks_and_mutes_have_a_subject() {
+        let mut record = RepoRecord::new();
+        record.insert(
+            "subject".to_string(),
+            Lex::Ipld(Ipld::Json(JsonValue::String("did:web5:bob".to_string()))),
+        );
+        assert_eq!(
+            relation_subject(BLOCK_COLLECTION, &record),
+            Some("did:web5:bob".to_string())
+        );
+        assert_eq!(
+            relation_subject(MUTE_COLLECTION, &record),
+            Some("did:web5:bob".to_string())
+        );
+        assert_eq!(relation_subject("app.bbs.vote", &record), None);
+        assert_eq!(relation_subject(BLOCK_COLLECTION, &RepoRecord::new()), None);
+    }
+}
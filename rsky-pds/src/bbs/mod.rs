@@ -0,0 +1,202 @@
+pub mod automod;
+pub mod bridge;
+pub mod classify;
+pub mod dm;
+pub mod draft;
+pub mod graph;
+pub mod reaction;
+pub mod read_state;
+pub mod section;
+pub mod stats;
+pub mod trending;
+pub mod unfurl;
+pub mod watch;
+
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const POST_COLLECTION: &str = "app.bbs.post";
+pub const REPLY_COLLECTION: &str = "app.bbs.reply";
+pub const VOTE_COLLECTION: &str = "app.bbs.vote";
+/// Emoji reactions, restricted to [`crate::config::BbsConfig::reaction_emojis`]
+pub const REACTION_COLLECTION: &str = "app.bbs.reaction";
+pub const BLOCK_COLLECTION: &str = "app.bbs.graph.block";
+pub const MUTE_COLLECTION: &str = "app.bbs.graph.mute";
+/// Watches or mutes a thread for reply notifications, see [`watch`]
+pub const WATCH_COLLECTION: &str = "app.bbs.watchThread";
+/// Published by section moderators, see [`automod`]
+pub const RULE_COLLECTION: &str = "app.bbs.automod.rule";
+/// Envelope for posts and replies in private sections, see [`rsky_lexicon::app::bbs::EncryptedPost`]
+pub const ENCRYPTED_POST_COLLECTION: &str = "app.bbs.encryptedPost";
+/// Largest ciphertext blob an encrypted post may reference
+pub const MAX_CIPHERTEXT_BYTES: usize = 1_000_000;
+
+/// Values keyed by thread, looked up by however a reply or vote names the
+/// thread's root post: by its cid, or by its uri, which is accepted as well.
+#[derive(Debug, Clone)]
+pub struct ThreadKeys<T> {
+    keys: HashMap<String, T>,
+}
+
+impl<T> Default for ThreadKeys<T> {
+    fn default() -> Self {
+        ThreadKeys {
+            keys: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Clone> ThreadKeys<T> {
+    pub fn insert(&mut self, uri: &str, cid: &str, value: T) {
+        self.keys.insert(uri.to_string(), value.clone());
+        self.keys.insert(cid.to_string(), value);
+    }
+
+    pub fn get(&self, root: &str) -> Option<&T> {
+        self.keys.get(root)
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplyRoot {
+    root: Option<String>,
+}
+
+/// The root post an `app.bbs.reply` names, from the reply's DAG-CBOR block
+pub fn reply_root(content: &[u8]) -> Option<String> {
+    serde_ipld_dagcbor::from_slice::<ReplyRoot>(content)
+        .ok()?
+        .root
+}
+
+/// A record of one of the app.bbs collections along with its DAG-CBOR block
+#[derive(Debug, Clone)]
+pub struct IndexedRecord {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub indexed_at: String,
+    pub content: Vec<u8>,
+}
+
+/// The records of `collection` indexed at or after `since`, so background
+/// jobs only decode what's new or in their window. Records taken down, or of
+/// a taken down account, are left out unless `include_taken_down` is set.
+pub fn load_indexed_since(
+    conn: &mut PgConnection,
+    collection: &'static str,
+    since: &str,
+    include_taken_down: bool,
+) -> Result<Vec<IndexedRecord>> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record::dsl as RecordSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let mut query = RecordSchema::record
+        .inner_join(
+            RepoBlockSchema::repo_block.on(RepoBlockSchema::cid
+                .eq(RecordSchema::cid)
+                .and(RepoBlockSchema::did.eq(RecordSchema::did))),
+        )
+        .inner_join(ActorSchema::actor.on(ActorSchema::did.eq(RecordSchema::did)))
+        .filter(RecordSchema::collection.eq(collection))
+        .filter(RecordSchema::indexedAt.ge(since.to_string()))
+        .select((
+            RecordSchema::uri,
+            RecordSchema::cid,
+            RecordSchema::did,
+            RecordSchema::indexedAt,
+            RepoBlockSchema::content,
+        ))
+        .into_boxed();
+    if !include_taken_down {
+        query = query
+            .filter(RecordSchema::takedownRef.is_null())
+            .filter(ActorSchema::takedownRef.is_null());
+    }
+    Ok(query
+        .load::<(String, String, String, String, Vec<u8>)>(conn)?
+        .into_iter()
+        .map(|(uri, cid, did, indexed_at, content)| IndexedRecord {
+            uri,
+            cid,
+            did,
+            indexed_at,
+            content,
+        })
+        .collect())
+}
+
+/// The posts hosted here that `roots` name, as their uri and DAG-CBOR
+/// block, keyed the way the roots name them
+pub fn find_root_posts(
+    conn: &mut PgConnection,
+    roots: &[String],
+) -> Result<ThreadKeys<(String, Vec<u8>)>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+    use crate::schema::pds::repo_block::dsl as RepoBlockSchema;
+
+    let mut posts = ThreadKeys::default();
+    // Stay well under postgres' limit on bind parameters per statement
+    for chunk in roots.chunks(1000) {
+        let found = RecordSchema::record
+            .inner_join(
+                RepoBlockSchema::repo_block.on(RepoBlockSchema::cid
+                    .eq(RecordSchema::cid)
+                    .and(RepoBlockSchema::did.eq(RecordSchema::did))),
+            )
+            .filter(RecordSchema::collection.eq(POST_COLLECTION))
+            .filter(
+                RecordSchema::uri
+                    .eq_any(chunk)
+                    .or(RecordSchema::cid.eq_any(chunk)),
+            )
+            .select((
+                RecordSchema::uri,
+                RecordSchema::cid,
+                RepoBlockSchema::content,
+            ))
+            .load::<(String, String, Vec<u8>)>(conn)?;
+        for (uri, cid, content) in found {
+            posts.insert(&uri, &cid, (uri.clone(), content));
+        }
+    }
+    Ok(posts)
+}
+
+/// The links in a post: its link facets, or the URLs written out in `text`
+/// when there are more of those, as clients that skip facets leave them bare.
+pub fn post_links(text: &str, record: &Value) -> Vec<String> {
+    let in_text = text
+        .split_whitespace()
+        .filter(|word| {
+            let word = word.to_lowercase();
+            word.starts_with("http://") || word.starts_with("https://") || word.starts_with("www.")
+        })
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    let in_facets = record
+        .get("facets")
+        .and_then(Value::as_array)
+        .map(|facets| {
+            facets
+                .iter()
+                .filter_map(|facet| facet.get("features").and_then(Value::as_array))
+                .flatten()
+                .filter(|feature| {
+                    feature.get("$type").and_then(Value::as_str)
+                        == Some("app.bsky.richtext.facet#link")
+                })
+                .filter_map(|feature| feature.get("uri").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        })
+        .unwrap_or_default();
+    match in_text.len() > in_facets.len() {
+        true => in_text,
+        false => in_facets,
+    }
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use rsky_lexicon::schema::ValidationError;
+use serde_json::Value;
+
+/// Refuses a reaction whose emoji isn't on `allowlist`, which the BBS AppView
+/// would leave out of the counts anyway.
+pub fn assert_allowed_emoji(allowlist: &[String], record: &Value) -> Result<()> {
+    let emoji = record
+        .get("emoji")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if allowlist.iter().any(|allowed| allowed == emoji) {
+        return Ok(());
+    }
+    Err(ValidationError {
+        path: "emoji".to_string(),
+        message: format!("must be one of {}", allowlist.join(", ")),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_allowlisted_emojis_are_accepted() {
+        let allowlist = vec!["heart".to_string(), "+1".to_string()];
+        let reaction = |emoji: &str| {
+            json!({
+                "$type": "app.bbs.reaction",
+                "subject": "at://did:web5:alice/app.bbs.post/1",
+                "emoji": emoji,
+            })
+        };
+        assert!(assert_allowed_emoji(&allowlist, &reaction("heart")).is_ok());
+        assert!(assert_allowed_emoji(&allowlist, &reaction("+1")).is_ok());
+        let error = assert_allowed_emoji(&allowlist, &reaction("Heart")).unwrap_err();
+        assert_eq!(error.downcast::<ValidationError>().unwrap().path, "emoji");
+        assert!(assert_allowed_emoji(&allowlist, &json!({})).is_err());
+    }
+}
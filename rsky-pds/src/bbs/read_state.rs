@@ -0,0 +1,255 @@
+use crate::db::establish_connection_for_jobs;
+use crate::models::BbsReadState;
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel::{delete, insert_into};
+use rsky_common::{now, RFC3339_VARIANT};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Most stale read positions compacted per statement
+const COMPACT_BATCH_SIZE: i64 = 1000;
+
+/// How far an account reports having read a thread
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadPosition {
+    pub thread: String,
+    pub read_reply_count: i64,
+    /// `createdAt` of the newest reply read, formatted as [`RFC3339_VARIANT`]
+    pub last_read_at: String,
+}
+
+/// The furthest of `kept` and `reported` in each of their fields, or `None`
+/// when `reported` adds nothing to what's kept. A device that fell behind
+/// can't move the position back.
+fn furthest(kept: Option<&ReadPosition>, reported: ReadPosition) -> Option<ReadPosition> {
+    let Some(kept) = kept else {
+        return Some(reported);
+    };
+    if reported.read_reply_count <= kept.read_reply_count
+        && reported.last_read_at <= kept.last_read_at
+    {
+        return None;
+    }
+    Some(ReadPosition {
+        thread: reported.thread,
+        read_reply_count: reported.read_reply_count.max(kept.read_reply_count),
+        last_read_at: reported.last_read_at.max(kept.last_read_at.clone()),
+    })
+}
+
+/// Keeps a batch of `did`'s read positions in one statement, each only
+/// where it's ahead of the one already kept. Past `max_read_states`, the
+/// threads `did` read least recently are dropped.
+pub fn update_read_states(
+    conn: &mut PgConnection,
+    did: &str,
+    positions: Vec<ReadPosition>,
+    max_read_states: i64,
+) -> Result<()> {
+    use crate::schema::pds::bbs_read_state::dsl as ReadStateSchema;
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        let threads = positions
+            .iter()
+            .map(|position| position.thread.clone())
+            .collect::<Vec<String>>();
+        let mut kept: HashMap<String, ReadPosition> = ReadStateSchema::bbs_read_state
+            .filter(ReadStateSchema::did.eq(did))
+            .filter(ReadStateSchema::thread.eq_any(&threads))
+            .select(BbsReadState::as_select())
+            .for_update()
+            .load(conn)?
+            .into_iter()
+            .map(|state| {
+                let position = ReadPosition {
+                    thread: state.thread.clone(),
+                    read_reply_count: state.read_reply_count,
+                    last_read_at: state.last_read_at,
+                };
+                (state.thread, position)
+            })
+            .collect();
+        // a thread reported twice in the batch ends up at the further position
+        let mut ahead: BTreeMap<String, ReadPosition> = BTreeMap::new();
+        for position in positions {
+            let thread = position.thread.clone();
+            if let Some(position) = furthest(kept.get(&thread), position) {
+                ahead.insert(thread.clone(), position.clone());
+                kept.insert(thread, position);
+            }
+        }
+        if ahead.is_empty() {
+            return Ok(());
+        }
+
+        let updated_at = now();
+        insert_into(ReadStateSchema::bbs_read_state)
+            .values(
+                ahead
+                    .into_values()
+                    .map(|position| {
+                        (
+                            ReadStateSchema::did.eq(did),
+                            ReadStateSchema::thread.eq(position.thread),
+                            ReadStateSchema::readReplyCount.eq(position.read_reply_count),
+                            ReadStateSchema::lastReadAt.eq(position.last_read_at),
+                            ReadStateSchema::updatedAt.eq(updated_at.clone()),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .on_conflict((ReadStateSchema::did, ReadStateSchema::thread))
+            .do_update()
+            .set((
+                ReadStateSchema::readReplyCount.eq(excluded(ReadStateSchema::readReplyCount)),
+                ReadStateSchema::lastReadAt.eq(excluded(ReadStateSchema::lastReadAt)),
+                ReadStateSchema::updatedAt.eq(excluded(ReadStateSchema::updatedAt)),
+            ))
+            .execute(conn)?;
+
+        let dropped: Vec<String> = ReadStateSchema::bbs_read_state
+            .filter(ReadStateSchema::did.eq(did))
+            .order((
+                ReadStateSchema::updatedAt.desc(),
+                ReadStateSchema::thread.desc(),
+            ))
+            .offset(max_read_states)
+            .select(ReadStateSchema::thread)
+            .load(conn)?;
+        if !dropped.is_empty() {
+            delete(ReadStateSchema::bbs_read_state)
+                .filter(ReadStateSchema::did.eq(did))
+                .filter(ReadStateSchema::thread.eq_any(dropped))
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// `did`'s read positions in `threads`, leaving out the ones they haven't read
+pub fn get_read_states(
+    conn: &mut PgConnection,
+    did: &str,
+    threads: Vec<String>,
+) -> Result<Vec<BbsReadState>> {
+    use crate::schema::pds::bbs_read_state::dsl as ReadStateSchema;
+
+    Ok(ReadStateSchema::bbs_read_state
+        .filter(ReadStateSchema::did.eq(did))
+        .filter(ReadStateSchema::thread.eq_any(threads))
+        .select(BbsReadState::as_select())
+        .load(conn)?)
+}
+
+/// Periodically drops read positions no device has moved for `max_age_ms`,
+/// so threads read once long ago don't keep a row per reader forever. A
+/// client shows a compacted thread as never read.
+#[derive(Debug, Clone)]
+pub struct ReadStateCompactor {
+    pub max_age_ms: u64,
+    pub interval_ms: u64,
+}
+
+impl ReadStateCompactor {
+    pub fn new(max_age_ms: u64, interval_ms: u64) -> Self {
+        ReadStateCompactor {
+            max_age_ms,
+            interval_ms: interval_ms.max(1000),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            let compactor = self.clone();
+            let res = tokio::task::spawn_blocking(move || {
+                let conn = &mut establish_connection_for_jobs()?;
+                compactor.run(conn)
+            })
+            .await;
+            match res {
+                Ok(Ok(0)) => (),
+                Ok(Ok(compacted)) => tracing::info!("Compacted {compacted} bbs read states"),
+                Ok(Err(error)) => {
+                    tracing::error!("@LOG: ERROR: failed to compact bbs read states: {error}")
+                }
+                Err(error) => {
+                    tracing::error!("@LOG: ERROR: bbs read state compaction panicked: {error}")
+                }
+            }
+        }
+    }
+
+    /// Deletes read positions older than the cutoff, a batch at a time so a
+    /// large backlog doesn't hold one long transaction. Returns how many.
+    pub fn run(&self, conn: &mut PgConnection) -> Result<usize> {
+        use crate::schema::pds::bbs_read_state::dsl as ReadStateSchema;
+
+        let cutoff = format!(
+            "{}",
+            (Utc::now() - ChronoDuration::milliseconds(self.max_age_ms as i64))
+                .format(RFC3339_VARIANT)
+        );
+        let mut compacted = 0;
+        loop {
+            let stale: Vec<(String, String)> = ReadStateSchema::bbs_read_state
+                .filter(ReadStateSchema::updatedAt.lt(&cutoff))
+                .select((ReadStateSchema::did, ReadStateSchema::thread))
+                .limit(COMPACT_BATCH_SIZE)
+                .load(conn)?;
+            if stale.is_empty() {
+                return Ok(compacted);
+            }
+            let (dids, threads): (Vec<String>, Vec<String>) = stale.into_iter().unzip();
+            // pairs across the two lists that weren't loaded are just as
+            // stale, so matching them too is harmless
+            compacted += delete(ReadStateSchema::bbs_read_state)
+                .filter(ReadStateSchema::updatedAt.lt(&cutoff))
+                .filter(ReadStateSchema::did.eq_any(dids))
+                .filter(ReadStateSchema::thread.eq_any(threads))
+                .execute(conn)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(read_reply_count: i64, last_read_at: &str) -> ReadPosition {
+        ReadPosition {
+            thread: "at://did:web5:alice/app.bbs.post/1".to_string(),
+            read_reply_count,
+            last_read_at: last_read_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn read_positions_only_move_forward() {
+        let kept = position(5, "2025-01-02T00:00:00.000Z");
+        assert_eq!(
+            furthest(None, kept.clone()),
+            Some(kept.clone()),
+            "the first position is kept as reported"
+        );
+        assert_eq!(
+            furthest(Some(&kept), position(3, "2025-01-01T00:00:00.000Z")),
+            None
+        );
+        assert_eq!(furthest(Some(&kept), kept.clone()), None);
+        assert_eq!(
+            furthest(Some(&kept), position(8, "2025-01-03T00:00:00.000Z")),
+            Some(position(8, "2025-01-03T00:00:00.000Z"))
+        );
+        // a reply deleted since keeps the count down while the time moves on
+        assert_eq!(
+            furthest(Some(&kept), position(4, "2025-01-03T00:00:00.000Z")),
+            Some(position(5, "2025-01-03T00:00:00.000Z"))
+        );
+    }
+}
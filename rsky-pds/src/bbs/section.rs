@@ -0,0 +1,67 @@
+use crate::models::BbsSection;
+use diesel::insert_into;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rsky_lexicon::app::bbs::SectionStatus;
+use std::collections::HashMap;
+
+/// A post or reply was written into an archived section
+#[derive(thiserror::Error, Debug)]
+#[error("Section {0} is archived and takes no new posts or replies")]
+pub struct SectionArchivedError(pub i64);
+
+/// The status a moderator last gave `section`, open when none did
+pub fn status(conn: &mut PgConnection, section: i64) -> QueryResult<SectionStatus> {
+    use crate::schema::pds::bbs_section::dsl as SectionSchema;
+
+    let status = SectionSchema::bbs_section
+        .filter(SectionSchema::sectionId.eq(section))
+        .select(SectionSchema::status)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(parse_status(status.as_deref()))
+}
+
+/// Every section a moderator gave a status, by section id
+pub fn statuses(conn: &mut PgConnection) -> QueryResult<HashMap<i64, SectionStatus>> {
+    use crate::schema::pds::bbs_section::dsl as SectionSchema;
+
+    let sections = SectionSchema::bbs_section
+        .select(BbsSection::as_select())
+        .load(conn)?;
+    Ok(sections
+        .into_iter()
+        .map(|section| (section.section_id, parse_status(Some(&section.status))))
+        .collect())
+}
+
+/// Records `status` for `section`, set by the moderator `did`
+pub fn set_status(
+    conn: &mut PgConnection,
+    section: i64,
+    status: SectionStatus,
+    did: &str,
+) -> QueryResult<BbsSection> {
+    use crate::schema::pds::bbs_section::dsl as SectionSchema;
+
+    let row = BbsSection {
+        section_id: section,
+        status: status.as_str().to_string(),
+        updated_at: rsky_common::now(),
+        updated_by: did.to_string(),
+    };
+    insert_into(SectionSchema::bbs_section)
+        .values(&row)
+        .on_conflict(SectionSchema::sectionId)
+        .do_update()
+        .set(&row)
+        .execute(conn)?;
+    Ok(row)
+}
+
+/// Unknown statuses, e.g. from a newer PDS sharing the database, read as open
+fn parse_status(status: Option<&str>) -> SectionStatus {
+    status
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_default()
+}
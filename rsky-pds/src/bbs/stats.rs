@@ -0,0 +1,294 @@
+use crate::bbs::{
+    find_root_posts, load_indexed_since, reply_root, IndexedRecord, POST_COLLECTION,
+    REPLY_COLLECTION,
+};
+use crate::db::establish_connection_for_jobs;
+use crate::models::{BbsSectionStats, BbsStats, BbsThreadIndex};
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::dsl::{count_distinct, count_star, max};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Text};
+use diesel::upsert::excluded;
+use diesel::{delete, insert_into, sql_query};
+use rsky_common::RFC3339_VARIANT;
+use std::time::Duration;
+
+/// The sitewide totals live in a single row.
+pub const STATS_ROW_ID: i32 = 1;
+/// How far before the newest record already indexed the next run looks
+/// again, for writes that committed late with an earlier `indexedAt`
+const REINDEX_OVERLAP_MS: i64 = 60 * 1000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostSection {
+    section_id: Option<u64>,
+}
+
+/// One section's row of [`SECTION_STATS_QUERY`]
+#[derive(Debug, QueryableByName)]
+struct SectionCounts {
+    #[diesel(sql_type = BigInt)]
+    section_id: i64,
+    #[diesel(sql_type = BigInt)]
+    total_posts: i64,
+    #[diesel(sql_type = BigInt)]
+    total_replies: i64,
+    #[diesel(sql_type = BigInt)]
+    posts_7d: i64,
+    #[diesel(sql_type = BigInt)]
+    posts_30d: i64,
+    #[diesel(sql_type = Nullable<Text>)]
+    last_activity_at: Option<String>,
+}
+
+/// Sums each section's posts and the replies to them from `bbs_thread_index`,
+/// leaving out what's taken down along with replies to taken down posts.
+/// Binds the post and reply collections, then the 7 and 30 day cutoffs.
+const SECTION_STATS_QUERY: &str = r#"
+SELECT p."sectionId" AS section_id,
+    count(*) FILTER (WHERE t.collection = $1) AS total_posts,
+    count(*) FILTER (WHERE t.collection = $2) AS total_replies,
+    count(*) FILTER (WHERE t.collection = $1 AND r."indexedAt" >= $3) AS posts_7d,
+    count(*) FILTER (WHERE t.collection = $1 AND r."indexedAt" >= $4) AS posts_30d,
+    max(r."indexedAt") AS last_activity_at
+FROM pds.bbs_thread_index t
+JOIN pds.record r ON r.uri = t.uri AND r."takedownRef" IS NULL
+JOIN pds.actor a ON a.did = t.did AND a."takedownRef" IS NULL
+JOIN pds.bbs_thread_index p ON p.uri = t.thread AND p."sectionId" IS NOT NULL
+JOIN pds.record pr ON pr.uri = p.uri AND pr."takedownRef" IS NULL
+JOIN pds.actor pa ON pa.did = p.did AND pa."takedownRef" IS NULL
+GROUP BY p."sectionId"
+ORDER BY p."sectionId"
+"#;
+
+/// Periodically recomputes the BBS statistics so app.bbs.getStats only has to
+/// read two small tables instead of scanning every record on each request.
+#[derive(Debug, Clone)]
+pub struct StatsAggregator {
+    pub interval_ms: u64,
+}
+
+impl StatsAggregator {
+    pub fn new(interval_ms: u64) -> Self {
+        StatsAggregator {
+            interval_ms: interval_ms.max(1000),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            let res = tokio::task::spawn_blocking(|| {
+                let conn = &mut establish_connection_for_jobs()?;
+                aggregate_stats(conn)
+            })
+            .await;
+            match res {
+                Ok(Ok(stats)) => tracing::debug!(
+                    "Aggregated bbs stats: {} posts, {} replies",
+                    stats.total_posts,
+                    stats.total_replies
+                ),
+                Ok(Err(error)) => {
+                    tracing::error!("@LOG: ERROR: failed to aggregate bbs stats: {error}")
+                }
+                Err(error) => tracing::error!("@LOG: ERROR: bbs stats task panicked: {error}"),
+            }
+        }
+    }
+}
+
+fn format_cutoff(days: i64) -> String {
+    format!(
+        "{}",
+        (Utc::now() - ChronoDuration::days(days)).format(RFC3339_VARIANT)
+    )
+}
+
+fn count_active_users(conn: &mut PgConnection, since: &String) -> Result<i64> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    Ok(RecordSchema::record
+        .inner_join(ActorSchema::actor.on(ActorSchema::did.eq(RecordSchema::did)))
+        .filter(RecordSchema::collection.eq_any(vec![POST_COLLECTION, REPLY_COLLECTION]))
+        .filter(RecordSchema::takedownRef.is_null())
+        .filter(ActorSchema::takedownRef.is_null())
+        .filter(RecordSchema::indexedAt.ge(since))
+        .select(count_distinct(RecordSchema::did))
+        .get_result::<i64>(conn)?)
+}
+
+fn count_collection(conn: &mut PgConnection, collection: &'static str) -> Result<i64> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    Ok(RecordSchema::record
+        .inner_join(ActorSchema::actor.on(ActorSchema::did.eq(RecordSchema::did)))
+        .filter(RecordSchema::collection.eq(collection))
+        .filter(RecordSchema::takedownRef.is_null())
+        .filter(ActorSchema::takedownRef.is_null())
+        .select(count_star())
+        .get_result::<i64>(conn)?)
+}
+
+/// Where the next run of [`index_threads`] starts: a little before the
+/// newest record already indexed, or the beginning the first time
+fn reindex_from(latest: Option<String>) -> String {
+    latest
+        .as_deref()
+        .and_then(|latest| DateTime::parse_from_rfc3339(latest).ok())
+        .map(|latest| {
+            format!(
+                "{}",
+                (latest.with_timezone(&Utc) - ChronoDuration::milliseconds(REINDEX_OVERLAP_MS))
+                    .format(RFC3339_VARIANT)
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Brings `bbs_thread_index` up to date with the posts and replies written
+/// or edited since the last run, decoding only those. Deleted records are
+/// dropped from it as they're deleted, taken down ones are kept and left
+/// out when summing. Returns how many records were indexed.
+pub fn index_threads(conn: &mut PgConnection) -> Result<usize> {
+    use crate::schema::pds::bbs_thread_index::dsl as ThreadIndexSchema;
+
+    let latest: Option<String> = ThreadIndexSchema::bbs_thread_index
+        .select(max(ThreadIndexSchema::indexedAt))
+        .first(conn)?;
+    let since = reindex_from(latest);
+
+    let mut rows: Vec<BbsThreadIndex> = Vec::new();
+    // taken down records are indexed too, they're only left out when summing
+    for post in load_indexed_since(conn, POST_COLLECTION, &since, true)? {
+        let section_id = match serde_ipld_dagcbor::from_slice::<PostSection>(&post.content) {
+            Ok(PostSection { section_id }) => section_id.map(|section_id| section_id as i64),
+            Err(_) => None,
+        };
+        rows.push(BbsThreadIndex {
+            uri: post.uri.clone(),
+            did: post.did,
+            collection: POST_COLLECTION.to_string(),
+            thread: post.uri,
+            section_id,
+            indexed_at: post.indexed_at,
+        });
+    }
+    let replies = load_indexed_since(conn, REPLY_COLLECTION, &since, true)?
+        .into_iter()
+        .filter_map(|reply| Some((reply_root(&reply.content)?, reply)))
+        .collect::<Vec<(String, IndexedRecord)>>();
+    let roots = replies
+        .iter()
+        .map(|(root, _)| root.clone())
+        .collect::<Vec<String>>();
+    let root_posts = find_root_posts(conn, &roots)?;
+    for (root, reply) in replies {
+        let thread = match root_posts.get(&root) {
+            Some((post_uri, _)) => post_uri.clone(),
+            None => root,
+        };
+        rows.push(BbsThreadIndex {
+            uri: reply.uri,
+            did: reply.did,
+            collection: REPLY_COLLECTION.to_string(),
+            thread,
+            section_id: None,
+            indexed_at: reply.indexed_at,
+        });
+    }
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        // Stay well under postgres' limit on bind parameters per statement
+        for chunk in rows.chunks(1000) {
+            insert_into(ThreadIndexSchema::bbs_thread_index)
+                .values(chunk)
+                .on_conflict(ThreadIndexSchema::uri)
+                .do_update()
+                .set((
+                    ThreadIndexSchema::thread.eq(excluded(ThreadIndexSchema::thread)),
+                    ThreadIndexSchema::sectionId.eq(excluded(ThreadIndexSchema::sectionId)),
+                    ThreadIndexSchema::indexedAt.eq(excluded(ThreadIndexSchema::indexedAt)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(())
+    })?;
+    Ok(rows.len())
+}
+
+/// Recomputes sitewide and per-section statistics and replaces the stored
+/// snapshot. Only records indexed since the last run are decoded, the rest
+/// is summed in SQL.
+pub fn aggregate_stats(conn: &mut PgConnection) -> Result<BbsStats> {
+    use crate::schema::pds::bbs_section_stats::dsl as SectionStatsSchema;
+    use crate::schema::pds::bbs_stats::dsl as StatsSchema;
+
+    let computed_at = rsky_common::now();
+    let cutoff_7d = format_cutoff(7);
+    let cutoff_30d = format_cutoff(30);
+
+    index_threads(conn)?;
+    let section_rows: Vec<BbsSectionStats> = sql_query(SECTION_STATS_QUERY)
+        .bind::<Text, _>(POST_COLLECTION)
+        .bind::<Text, _>(REPLY_COLLECTION)
+        .bind::<Text, _>(&cutoff_7d)
+        .bind::<Text, _>(&cutoff_30d)
+        .load::<SectionCounts>(conn)?
+        .into_iter()
+        .map(|counts| BbsSectionStats {
+            section_id: counts.section_id,
+            total_posts: counts.total_posts,
+            total_replies: counts.total_replies,
+            posts_7d: counts.posts_7d,
+            posts_30d: counts.posts_30d,
+            last_activity_at: counts.last_activity_at,
+            computed_at: computed_at.clone(),
+        })
+        .collect();
+
+    let stats = BbsStats {
+        id: STATS_ROW_ID,
+        total_posts: count_collection(conn, POST_COLLECTION)?,
+        total_replies: count_collection(conn, REPLY_COLLECTION)?,
+        active_users_7d: count_active_users(conn, &cutoff_7d)?,
+        active_users_30d: count_active_users(conn, &cutoff_30d)?,
+        computed_at,
+    };
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(SectionStatsSchema::bbs_section_stats).execute(conn)?;
+        insert_into(SectionStatsSchema::bbs_section_stats)
+            .values(&section_rows)
+            .execute(conn)?;
+        insert_into(StatsSchema::bbs_stats)
+            .values(&stats)
+            .on_conflict(StatsSchema::id)
+            .do_update()
+            .set(&stats)
+            .execute(conn)?;
+        Ok(())
+    })?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reindexes_a_little_before_the_newest_indexed_record() {
+        assert_eq!(reindex_from(None), "");
+        assert_eq!(
+            reindex_from(Some("2025-01-02T00:00:30.000Z".to_string())),
+            "2025-01-01T23:59:30.000Z"
+        );
+        // every indexedAt is at or after the empty string
+        assert!("2025-01-01T00:00:00.000Z" >= reindex_from(None).as_str());
+    }
+}
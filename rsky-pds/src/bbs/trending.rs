@@ -0,0 +1,320 @@
+use crate::bbs::{
+    load_indexed_since, reply_root, IndexedRecord, ThreadKeys, POST_COLLECTION, REPLY_COLLECTION,
+    VOTE_COLLECTION,
+};
+use crate::db::establish_connection_for_jobs;
+use crate::models::BbsTrending;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+use rsky_common::RFC3339_VARIANT;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Only threads started within this many days are ranked
+pub const TRENDING_WINDOW_DAYS: i64 = 7;
+/// How much a reply counts for compared to an up vote
+const REPLY_WEIGHT: f64 = 2.0;
+/// How fast a thread sinks as it ages, higher sinks faster
+const GRAVITY: f64 = 1.5;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrendingPost {
+    section_id: Option<u64>,
+    title: Option<String>,
+    text: Option<String>,
+    created_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoteSubject {
+    subject: Option<String>,
+    value: Option<i64>,
+}
+
+/// Periodically ranks recent threads by hot score into `bbs_trending`, so
+/// app.bbs.getTrending is a single indexed read instead of every client
+/// ranking full section feeds.
+#[derive(Debug, Clone)]
+pub struct TrendingRanker {
+    pub interval_ms: u64,
+}
+
+impl TrendingRanker {
+    pub fn new(interval_ms: u64) -> Self {
+        TrendingRanker {
+            interval_ms: interval_ms.max(1000),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            let res = tokio::task::spawn_blocking(|| {
+                let conn = &mut establish_connection_for_jobs()?;
+                rank_threads(conn)
+            })
+            .await;
+            match res {
+                Ok(Ok(ranked)) => tracing::debug!("Ranked {ranked} trending bbs threads"),
+                Ok(Err(error)) => {
+                    tracing::error!("@LOG: ERROR: failed to rank trending bbs threads: {error}")
+                }
+                Err(error) => tracing::error!("@LOG: ERROR: bbs trending task panicked: {error}"),
+            }
+        }
+    }
+}
+
+/// Votes and weighted replies, decayed by the thread's age so new activity
+/// beats old totals.
+pub fn hot_score(vote_score: i64, reply_count: i64, age_hours: f64) -> f64 {
+    let points = vote_score as f64 + REPLY_WEIGHT * reply_count as f64;
+    (points + 1.0) / (age_hours.max(0.0) + 2.0).powf(GRAVITY)
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Ranks the threads started within the window and replaces the snapshot.
+/// Returns how many threads were ranked.
+pub fn rank_threads(conn: &mut PgConnection) -> Result<usize> {
+    use crate::schema::pds::bbs_trending::dsl as TrendingSchema;
+
+    let now = Utc::now();
+    // A thread started in the window was indexed in it, and so was anything
+    // replying to or voting on it
+    let cutoff = format!(
+        "{}",
+        (now - ChronoDuration::days(TRENDING_WINDOW_DAYS)).format(RFC3339_VARIANT)
+    );
+    let threads = rank(
+        now,
+        load_indexed_since(conn, POST_COLLECTION, &cutoff, false)?,
+        load_indexed_since(conn, REPLY_COLLECTION, &cutoff, false)?,
+        load_indexed_since(conn, VOTE_COLLECTION, &cutoff, false)?,
+    );
+
+    conn.transaction::<_, anyhow::Error, _>(|conn| {
+        delete(TrendingSchema::bbs_trending).execute(conn)?;
+        // Stay well under postgres' limit on bind parameters per statement
+        for chunk in threads.chunks(1000) {
+            insert_into(TrendingSchema::bbs_trending)
+                .values(chunk)
+                .execute(conn)?;
+        }
+        Ok(())
+    })?;
+    Ok(threads.len())
+}
+
+/// Scores the posts started within the window by the replies and votes on
+/// them, ordered by rank.
+fn rank(
+    now: DateTime<Utc>,
+    posts: Vec<IndexedRecord>,
+    replies: Vec<IndexedRecord>,
+    votes: Vec<IndexedRecord>,
+) -> Vec<BbsTrending> {
+    let computed_at = format!("{}", now.format(RFC3339_VARIANT));
+    let cutoff = now - ChronoDuration::days(TRENDING_WINDOW_DAYS);
+
+    let mut threads: Vec<BbsTrending> = Vec::new();
+    let mut thread_keys: ThreadKeys<usize> = ThreadKeys::default();
+    for record in posts {
+        let Ok(post) = serde_ipld_dagcbor::from_slice::<TrendingPost>(&record.content) else {
+            continue;
+        };
+        let (Some(section_id), Some(indexed)) = (post.section_id, parse_time(&record.indexed_at))
+        else {
+            continue;
+        };
+        // The declared time can't be trusted to be in the past, the PDS's can
+        let created = post
+            .created_at
+            .as_deref()
+            .and_then(parse_time)
+            .map_or(indexed, |created| created.min(indexed));
+        if created < cutoff {
+            continue;
+        }
+        let created_at = format!("{}", created.format(RFC3339_VARIANT));
+        thread_keys.insert(&record.uri, &record.cid, threads.len());
+        threads.push(BbsTrending {
+            uri: record.uri,
+            cid: record.cid,
+            did: record.did,
+            section_id: section_id as i64,
+            title: post.title.unwrap_or_default(),
+            text: post.text.unwrap_or_default(),
+            rank: 0,
+            hot_score: 0.0,
+            vote_score: 0,
+            reply_count: 0,
+            created_at: created_at.clone(),
+            last_activity_at: created_at,
+            computed_at: computed_at.clone(),
+        });
+    }
+    for reply in replies {
+        let thread = reply_root(&reply.content).and_then(|root| thread_keys.get(&root).copied());
+        if let Some(thread) = thread.map(|index| &mut threads[index]) {
+            thread.reply_count += 1;
+            if reply.indexed_at > thread.last_activity_at {
+                thread.last_activity_at = reply.indexed_at;
+            }
+        }
+    }
+    // Only the latest vote of an account on a thread counts, like the AppView
+    let mut latest_votes: HashMap<(String, usize), (String, i64)> = HashMap::new();
+    for vote in votes {
+        if let Ok(VoteSubject {
+            subject: Some(subject),
+            value: Some(value @ (-1 | 1)),
+        }) = serde_ipld_dagcbor::from_slice::<VoteSubject>(&vote.content)
+        {
+            let Some(&index) = thread_keys.get(&subject) else {
+                continue;
+            };
+            match latest_votes.get(&(vote.did.clone(), index)) {
+                Some((latest, _)) if *latest >= vote.indexed_at => (),
+                _ => {
+                    latest_votes.insert((vote.did, index), (vote.indexed_at, value));
+                }
+            }
+        }
+    }
+    for ((_, index), (_, value)) in latest_votes {
+        threads[index].vote_score += value;
+    }
+
+    for thread in threads.iter_mut() {
+        let age = parse_time(&thread.created_at)
+            .map_or(0.0, |created| (now - created).num_seconds() as f64 / 3600.0);
+        thread.hot_score = hot_score(thread.vote_score, thread.reply_count, age);
+    }
+    threads.sort_by(|a, b| {
+        b.hot_score
+            .total_cmp(&a.hot_score)
+            .then_with(|| b.uri.cmp(&a.uri))
+    });
+    for (index, thread) in threads.iter_mut().enumerate() {
+        thread.rank = index as i64 + 1;
+    }
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn newer_threads_outrank_older_ones_with_the_same_activity() {
+        assert!(hot_score(5, 2, 1.0) > hot_score(5, 2, 24.0));
+        assert!(hot_score(10, 0, 3.0) > hot_score(1, 0, 3.0));
+        assert!(hot_score(0, 3, 3.0) > hot_score(3, 0, 3.0));
+        assert_eq!(hot_score(0, 0, -5.0), hot_score(0, 0, 0.0));
+    }
+
+    fn record(uri: &str, cid: &str, indexed_at: &str, content: serde_json::Value) -> IndexedRecord {
+        IndexedRecord {
+            uri: uri.to_string(),
+            cid: cid.to_string(),
+            did: uri
+                .trim_start_matches("at://")
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            indexed_at: indexed_at.to_string(),
+            content: serde_ipld_dagcbor::to_vec(&content).unwrap(),
+        }
+    }
+
+    #[test]
+    fn counts_the_latest_vote_and_replies_naming_the_root_either_way() {
+        let now = parse_time("2025-01-08T00:00:00.000Z").unwrap();
+        let root_uri = "at://did:web5:alice/app.bbs.post/1";
+        let root_cid = "bafyroot";
+        let posts = vec![
+            record(
+                root_uri,
+                root_cid,
+                "2025-01-07T22:00:00.000Z",
+                json!({ "sectionId": 1, "title": "Hi", "text": "hello" }),
+            ),
+            record(
+                "at://did:web5:alice/app.bbs.post/2",
+                "bafyquiet",
+                "2025-01-07T22:00:00.000Z",
+                json!({ "sectionId": 1, "title": "Quiet", "text": "..." }),
+            ),
+        ];
+        let replies = vec![
+            record(
+                "at://did:web5:bob/app.bbs.reply/1",
+                "bafyreply1",
+                "2025-01-07T23:00:00.000Z",
+                json!({ "root": root_cid }),
+            ),
+            record(
+                "at://did:web5:carol/app.bbs.reply/1",
+                "bafyreply2",
+                "2025-01-07T23:30:00.000Z",
+                json!({ "root": root_uri }),
+            ),
+            record(
+                "at://did:web5:carol/app.bbs.reply/2",
+                "bafyreply3",
+                "2025-01-07T23:45:00.000Z",
+                json!({ "root": "at://did:web5:dave/app.bbs.post/9" }),
+            ),
+        ];
+        // bob changed his mind, and his latest vote is listed first
+        let votes = vec![
+            record(
+                "at://did:web5:bob/app.bbs.vote/2",
+                "bafyvote2",
+                "2025-01-07T23:20:00.000Z",
+                json!({ "subject": root_uri, "value": -1 }),
+            ),
+            record(
+                "at://did:web5:bob/app.bbs.vote/1",
+                "bafyvote1",
+                "2025-01-07T23:10:00.000Z",
+                json!({ "subject": root_cid, "value": 1 }),
+            ),
+            record(
+                "at://did:web5:carol/app.bbs.vote/1",
+                "bafyvote3",
+                "2025-01-07T23:15:00.000Z",
+                json!({ "subject": root_cid, "value": 1 }),
+            ),
+            record(
+                "at://did:web5:dave/app.bbs.vote/1",
+                "bafyvote4",
+                "2025-01-07T23:15:00.000Z",
+                json!({ "subject": root_uri, "value": 1 }),
+            ),
+        ];
+
+        let threads = rank(now, posts, replies, votes);
+        assert_eq!(threads.len(), 2);
+        let root = &threads[0];
+        assert_eq!(root.uri, root_uri);
+        assert_eq!(root.rank, 1);
+        assert_eq!(root.reply_count, 2);
+        assert_eq!(root.vote_score, 1);
+        assert_eq!(root.last_activity_at, "2025-01-07T23:30:00.000Z");
+        assert_eq!(threads[1].reply_count, 0);
+        assert_eq!(threads[1].vote_score, 0);
+    }
+}
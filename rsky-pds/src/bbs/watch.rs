@@ -0,0 +1,89 @@
+use crate::bbs::WATCH_COLLECTION;
+use crate::models::BbsThreadWatch;
+use anyhow::Result;
+use diesel::insert_into;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use rsky_repo::types::RepoRecord;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The thread a watchThread record is about and whether it mutes it, `None`
+/// for any other record.
+pub fn watched_thread(collection: &str, record: &RepoRecord) -> Option<(String, bool)> {
+    if collection != WATCH_COLLECTION {
+        return None;
+    }
+    let record = serde_json::to_value(record).ok()?;
+    let thread = record.get("subject")?.as_str()?.to_string();
+    let muted = record
+        .get("muted")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    Some((thread, muted))
+}
+
+/// Indexes a watch, replacing the row of an earlier version of the record so
+/// an update can switch between watching and muting, or move to another thread.
+pub fn index_watch(conn: &mut PgConnection, watch: BbsThreadWatch) -> Result<()> {
+    use crate::schema::pds::bbs_thread_watch::dsl as WatchSchema;
+
+    insert_into(WatchSchema::bbs_thread_watch)
+        .values(&watch)
+        .on_conflict(WatchSchema::uri)
+        .do_update()
+        .set((
+            WatchSchema::thread.eq(&watch.thread),
+            WatchSchema::muted.eq(watch.muted),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// The accounts on this PDS that watch or mute `thread`, with `true` for the
+/// ones that mute it. Muting wins for an account with records doing both.
+pub fn thread_watchers(conn: &mut PgConnection, thread: &str) -> Result<HashMap<String, bool>> {
+    use crate::schema::pds::bbs_thread_watch::dsl as WatchSchema;
+
+    let mut watchers: HashMap<String, bool> = HashMap::new();
+    for (did, muted) in WatchSchema::bbs_thread_watch
+        .filter(WatchSchema::thread.eq(thread))
+        .select((WatchSchema::did, WatchSchema::muted))
+        .load::<(String, bool)>(conn)?
+    {
+        *watchers.entry(did).or_default() |= muted;
+    }
+    Ok(watchers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_repo::storage::Ipld;
+    use rsky_repo::types::Lex;
+
+    #[test]
+    fn only_watch_records_watch_a_thread() {
+        let thread = "at://did:web5:alice/app.bbs.post/1".to_string();
+        let mut record = RepoRecord::new();
+        record.insert(
+            "subject".to_string(),
+            Lex::Ipld(Ipld::Json(Value::String(thread.clone()))),
+        );
+        assert_eq!(
+            watched_thread(WATCH_COLLECTION, &record),
+            Some((thread.clone(), false))
+        );
+        assert_eq!(watched_thread("app.bbs.vote", &record), None);
+
+        record.insert(
+            "muted".to_string(),
+            Lex::Ipld(Ipld::Json(Value::Bool(true))),
+        );
+        assert_eq!(
+            watched_thread(WATCH_COLLECTION, &record),
+            Some((thread, true))
+        );
+        assert_eq!(watched_thread(WATCH_COLLECTION, &RepoRecord::new()), None);
+    }
+}
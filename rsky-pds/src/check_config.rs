@@ -0,0 +1,466 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::config::{
+    env_to_cfg, BlobstoreConfig, EmailProviderConfig, RepoLockConfig, SequencerConfig, ServerConfig,
+};
+use crate::labeler;
+use crate::plc::web5_types::{check_ckb_rpc, CKB_EXPLORER_URL, CKB_RPC_URL};
+use crate::readiness::{check, check_blobstore};
+use anyhow::{anyhow, bail, Result};
+use diesel::{Connection, PgConnection};
+use rsky_common::env::{env_list, env_str};
+use secp256k1::SecretKey;
+use std::panic;
+use url::Url;
+
+const REDACTED: &str = "redacted";
+const KEY_HINT: &str = "generate one with `openssl ecparam --name secp256k1 --genkey --noout --outform DER | tail -c +8 | head -c 32 | xxd -p -c 32`";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The PDS can't serve requests correctly, startup is refused
+    Error,
+    /// Likely a mistake, but the PDS still works
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigProblem {
+    pub severity: Severity,
+    /// The env var to fix
+    pub var: &'static str,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    fn error(var: &'static str, message: impl Into<String>) -> Self {
+        ConfigProblem {
+            severity: Severity::Error,
+            var,
+            message: message.into(),
+        }
+    }
+
+    fn warning(var: &'static str, message: impl Into<String>) -> Self {
+        ConfigProblem {
+            severity: Severity::Warning,
+            var,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn has_errors(problems: &[ConfigProblem]) -> bool {
+    problems
+        .iter()
+        .any(|problem| problem.severity == Severity::Error)
+}
+
+/// Checks a hex encoded secp256k1 private key, as used for the JWT, repo
+/// signing and PLC rotation keys.
+fn check_key(value: &str) -> Result<()> {
+    let bytes = hex::decode(value).map_err(|_| anyhow!("isn't hex encoded"))?;
+    SecretKey::from_slice(&bytes).map_err(|_| anyhow!("isn't a valid secp256k1 private key"))?;
+    Ok(())
+}
+
+fn check_url(value: &str) -> Result<()> {
+    match Url::parse(value) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+        Ok(url) => bail!("has scheme `{}`, expected http or https", url.scheme()),
+        Err(error) => bail!("isn't a url: {error}"),
+    }
+}
+
+/// Masks the password of a url, e.g. in DATABASE_URL or an smtp url.
+fn redact_url(value: &str) -> String {
+    match Url::parse(value) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some(REDACTED));
+            url.to_string()
+        }
+        Ok(url) => url.to_string(),
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+fn validate_keys(problems: &mut Vec<ConfigProblem>) {
+    let keys = [
+        ("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX", Severity::Error),
+        ("PDS_REPO_SIGNING_KEY_K256_PRIVATE_KEY_HEX", Severity::Error),
+        // Only used for did:plc operations, which web5 accounts don't need
+        (
+            "PDS_PLC_ROTATION_KEY_K256_PRIVATE_KEY_HEX",
+            Severity::Warning,
+        ),
+        // Labels can't be issued without it
+        (labeler::SIGNING_KEY_VAR, Severity::Warning),
+    ];
+    for (var, severity) in keys {
+        let message = match env_str(var) {
+            None => format!("is unset, {KEY_HINT}"),
+            Some(value) => match check_key(&value) {
+                Ok(()) => continue,
+                Err(error) => format!("{error}, {KEY_HINT}"),
+            },
+        };
+        problems.push(ConfigProblem {
+            severity,
+            var,
+            message,
+        });
+    }
+    for (i, value) in env_list("PDS_JWT_PREVIOUS_KEYS_K256_PRIVATE_KEY_HEX")
+        .iter()
+        .enumerate()
+    {
+        if value.trim().is_empty() {
+            continue;
+        }
+        if let Err(error) = check_key(value.trim()) {
+            problems.push(ConfigProblem::error(
+                "PDS_JWT_PREVIOUS_KEYS_K256_PRIVATE_KEY_HEX",
+                format!("entry {} {error}", i + 1),
+            ));
+        }
+    }
+}
+
+/// Finds settings the PDS would otherwise only trip over mid-request, like a
+/// missing signing key or a malformed service url.
+pub fn validate(cfg: &ServerConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+
+    let hostname = &cfg.service.hostname;
+    if hostname.is_empty() || hostname.contains(['/', ':']) {
+        problems.push(ConfigProblem::error(
+            "PDS_HOSTNAME",
+            format!("`{hostname}` must be a bare hostname like pds.example.com, without scheme, port or path"),
+        ));
+    } else if hostname == "localhost" && !cfg.service.dev_mode {
+        problems.push(ConfigProblem::warning(
+            "PDS_HOSTNAME",
+            "is unset or localhost outside of PDS_DEV_MODE, the PDS won't be reachable by relays or AppViews",
+        ));
+    }
+    if cfg.rate_limits.enabled
+        && cfg.service.trusted_proxy_header.is_none()
+        && !cfg.service.dev_mode
+    {
+        problems.push(ConfigProblem::warning(
+            "PDS_TRUSTED_PROXY_HEADER",
+            "is unset, behind a reverse proxy every request is rate limited as the proxy's ip",
+        ));
+    }
+    if !cfg.service.did.starts_with("did:") {
+        problems.push(ConfigProblem::error(
+            "PDS_SERVICE_DID",
+            format!("`{}` isn't a DID", cfg.service.did),
+        ));
+    }
+
+    validate_keys(&mut problems);
+    if cfg.admin_auth.password_enabled && env_str("PDS_ADMIN_PASS").is_none() {
+        problems.push(ConfigProblem::error(
+            "PDS_ADMIN_PASS",
+            "is unset, admin endpoints can't be authenticated",
+        ));
+    }
+    for did in cfg.admin_auth.service_dids.iter() {
+        if !did.starts_with("did:") {
+            problems.push(ConfigProblem::error(
+                "PDS_ADMIN_SERVICE_DIDS",
+                format!("`{did}` isn't a DID"),
+            ));
+        }
+    }
+    for key in cfg.admin_auth.service_keys.iter() {
+        if !key.starts_with("did:key:") {
+            problems.push(ConfigProblem::error(
+                "PDS_ADMIN_SERVICE_KEYS",
+                format!("`{key}` isn't a did:key"),
+            ));
+        }
+    }
+    if !cfg.admin_auth.password_enabled && cfg.admin_auth.service_dids.is_empty() {
+        problems.push(ConfigProblem::error(
+            "PDS_ADMIN_PASSWORD_AUTH",
+            "is off but PDS_ADMIN_SERVICE_DIDS is empty, admin endpoints can't be authenticated",
+        ));
+    }
+    match env_str("DATABASE_URL") {
+        None => problems.push(ConfigProblem::error("DATABASE_URL", "is unset")),
+        Some(url) if !(url.starts_with("postgres://") || url.starts_with("postgresql://")) => {
+            problems.push(ConfigProblem::error(
+                "DATABASE_URL",
+                format!("`{}` must be a postgres:// url", redact_url(&url)),
+            ))
+        }
+        Some(_) => (),
+    }
+    if let Some(ref replica) = cfg.database.replica {
+        if !(replica.url.starts_with("postgres://") || replica.url.starts_with("postgresql://")) {
+            problems.push(ConfigProblem::error(
+                "PDS_DB_REPLICA_URL",
+                format!("`{}` must be a postgres:// url", redact_url(&replica.url)),
+            ));
+        }
+    }
+
+    if let SequencerConfig::Redis { ref url, .. } = cfg.sequencer {
+        if cfg!(not(feature = "sequencer-redis")) {
+            problems.push(ConfigProblem::error(
+                "PDS_SEQUENCER_REDIS_URL",
+                "is set but this build doesn't have the sequencer-redis feature",
+            ));
+        } else if !(url.starts_with("redis://") || url.starts_with("rediss://")) {
+            problems.push(ConfigProblem::error(
+                "PDS_SEQUENCER_REDIS_URL",
+                format!("`{}` must be a redis:// url", redact_url(url)),
+            ));
+        }
+    }
+
+    if let RepoLockConfig::Redis { ref url } = cfg.cluster.repo_locks {
+        if cfg!(not(feature = "cluster-redis")) {
+            problems.push(ConfigProblem::error(
+                "PDS_CLUSTER_REPO_LOCKS",
+                "is redis but this build doesn't have the cluster-redis feature",
+            ));
+        } else if !(url.starts_with("redis://") || url.starts_with("rediss://")) {
+            problems.push(ConfigProblem::error(
+                "PDS_CLUSTER_REDIS_URL",
+                format!("`{}` must be a redis:// url", redact_url(url)),
+            ));
+        }
+    }
+
+    if let BlobstoreConfig::S3(ref s3) = cfg.blobstore {
+        if !cfg.service.dev_mode && (s3.access_key_id == "test" || s3.secret_access_key == "test") {
+            problems.push(ConfigProblem::warning(
+                "AWS_ACCESS_KEY_ID",
+                "S3 credentials fall back to test/test, set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or PDS_BLOBSTORE_DISK_LOCATION",
+            ));
+        }
+    }
+
+    let mut urls = vec![
+        ("PDS_DID_PLC_URL", cfg.identity.plc_url.as_str()),
+        ("PDS_OAUTH_ISSUER", cfg.oauth.issuer.as_str()),
+    ];
+    let services = [
+        ("PDS_MOD_SERVICE_URL", &cfg.mod_service),
+        ("PDS_REPORT_SERVICE_URL", &cfg.report_service),
+        ("PDS_BSKY_APP_VIEW_URL", &cfg.bsky_app_view),
+        ("PDS_BBS_APP_VIEW_URL", &cfg.bbs_app_view),
+    ];
+    for &(var, service) in services.iter() {
+        if let Some(service) = service {
+            urls.push((var, service.url.as_str()));
+        }
+    }
+    for crawler in cfg.crawlers.iter() {
+        urls.push(("PDS_CRAWLERS", crawler.as_str()));
+    }
+    for (var, url) in urls {
+        if let Err(error) = check_url(url) {
+            problems.push(ConfigProblem::error(var, format!("`{url}` {error}")));
+        }
+    }
+    for &(var, service) in services.iter() {
+        if let Some(service) = service {
+            if !service.did.starts_with("did:") {
+                problems.push(ConfigProblem::error(
+                    var,
+                    format!("service DID `{}` isn't a DID", service.did),
+                ));
+            }
+        }
+    }
+
+    if cfg.bridge.enabled {
+        match cfg.bridge.thread_url {
+            None => problems.push(ConfigProblem::error(
+                "PDS_BSKY_BRIDGE_THREAD_URL",
+                "is unset but PDS_BSKY_BRIDGE_ENABLED is on, mirrors would have nothing to link back to",
+            )),
+            Some(ref url) if !url.contains("{uri}") => problems.push(ConfigProblem::error(
+                "PDS_BSKY_BRIDGE_THREAD_URL",
+                format!("`{url}` must contain `{{uri}}` where the post's AT URI goes"),
+            )),
+            Some(ref url) => {
+                if let Err(error) = check_url(url) {
+                    problems.push(ConfigProblem::error(
+                        "PDS_BSKY_BRIDGE_THREAD_URL",
+                        format!("`{url}` {error}"),
+                    ));
+                }
+            }
+        }
+    }
+
+    for origin in cfg.cors.allowed_origins.iter() {
+        if origin == "*" {
+            continue;
+        }
+        // Browsers send the bare origin, so anything else would never match
+        match Url::parse(origin) {
+            Ok(url) if url.origin().ascii_serialization().eq_ignore_ascii_case(origin) => (),
+            _ => problems.push(ConfigProblem::error(
+                "PDS_CORS_ALLOWED_ORIGINS",
+                format!("`{origin}` must be `*` or an origin like https://bbs.example.com, without a path or default port"),
+            )),
+        }
+    }
+
+    problems
+}
+
+/// The config with every secret masked, safe to print or paste in an issue.
+fn redacted(cfg: &ServerConfig) -> ServerConfig {
+    let mut cfg = cfg.clone();
+    if let BlobstoreConfig::S3(ref mut s3) = cfg.blobstore {
+        s3.secret_access_key = REDACTED.to_string();
+    }
+    cfg.rate_limits.redis_url = cfg.rate_limits.redis_url.as_deref().map(redact_url);
+    cfg.classifier.callout_url = cfg.classifier.callout_url.as_deref().map(redact_url);
+    if let Some(ref mut replica) = cfg.database.replica {
+        replica.url = redact_url(&replica.url);
+    }
+    if let SequencerConfig::Redis { ref mut url, .. } = cfg.sequencer {
+        *url = redact_url(url);
+    }
+    if let RepoLockConfig::Redis { ref mut url } = cfg.cluster.repo_locks {
+        *url = redact_url(url);
+    }
+    if cfg.rate_limits.bypass_key.is_some() {
+        cfg.rate_limits.bypass_key = Some(REDACTED.to_string());
+    }
+    if let Some(ref mut captcha) = cfg.signup.captcha {
+        captcha.secret = REDACTED.to_string();
+    }
+    match cfg.email.provider {
+        Some(EmailProviderConfig::Mailgun {
+            ref mut api_key, ..
+        }) => *api_key = REDACTED.to_string(),
+        Some(EmailProviderConfig::Smtp { ref mut url }) => *url = redact_url(url),
+        _ => (),
+    }
+    if cfg.metrics.bearer_token.is_some() {
+        cfg.metrics.bearer_token = Some(REDACTED.to_string());
+    }
+    cfg
+}
+
+async fn check_postgres() -> Result<()> {
+    let Some(url) = env_str("DATABASE_URL") else {
+        bail!("DATABASE_URL is unset");
+    };
+    // Only the connection error is reported, its context would leak the url
+    tokio::task::spawn_blocking(move || PgConnection::establish(&url).map(|_| ()))
+        .await?
+        .map_err(|error| anyhow!("{error}"))
+}
+
+/// Loads the config the way startup does, turning its panics on missing or
+/// malformed values into an error.
+fn load_config() -> Result<ServerConfig> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let res = panic::catch_unwind(env_to_cfg);
+    panic::set_hook(hook);
+    res.map_err(|panic| {
+        let message = match panic.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .unwrap_or_default(),
+        };
+        anyhow!("{message}")
+    })
+}
+
+pub fn print_problems(problems: &[ConfigProblem]) {
+    for problem in problems {
+        let label = match problem.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        eprintln!("{label}: {} {}", problem.var, problem.message);
+    }
+}
+
+/// `rsky-pds --check-config`: prints the effective config with secrets
+/// redacted, the problems `validate` finds and whether each backend can be
+/// reached. Returns the process exit code.
+pub async fn run() -> i32 {
+    let cfg = match load_config() {
+        Ok(cfg) => cfg,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return 1;
+        }
+    };
+    println!("Effective configuration:\n{:#?}", redacted(&cfg));
+    println!(
+        "DATABASE_URL: {}",
+        env_str("DATABASE_URL")
+            .as_deref()
+            .map(redact_url)
+            .unwrap_or("<unset>".to_string())
+    );
+    println!("CKB RPC: {}", *CKB_RPC_URL);
+    println!("CKB explorer: {}", *CKB_EXPLORER_URL);
+
+    let problems = validate(&cfg);
+    print_problems(&problems);
+
+    let blob_store = SharedBlobStore::new(&cfg.blobstore);
+    let (postgres, blobstore, ckb_rpc) = tokio::join!(
+        check(check_postgres()),
+        check(check_blobstore(&blob_store, cfg.service.did.clone())),
+        check(check_ckb_rpc()),
+    );
+    let mut reachable = true;
+    for (name, status) in [
+        ("postgres", postgres),
+        ("blobstore", blobstore),
+        ("ckbRpc", ckb_rpc),
+    ] {
+        match status.error {
+            None => println!("{name}: ok ({}ms)", status.latency_ms),
+            Some(error) => {
+                reachable = false;
+                eprintln!("{name}: unreachable ({error})");
+            }
+        }
+    }
+
+    match has_errors(&problems) || !reachable {
+        true => 1,
+        false => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_url_passwords() {
+        assert_eq!(
+            redact_url("postgres://pds:hunter2@db:5432/pds"),
+            "postgres://pds:redacted@db:5432/pds"
+        );
+        assert_eq!(redact_url("redis://cache:6379"), "redis://cache:6379");
+        assert_eq!(redact_url("not a url"), REDACTED);
+    }
+
+    #[test]
+    fn checks_private_keys() {
+        let key = "7b4f6c1e0d2a3b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9011223344";
+        assert!(check_key(key).is_ok());
+        assert!(check_key("not hex").is_err());
+        assert!(check_key(&"00".repeat(32)).is_err());
+    }
+}
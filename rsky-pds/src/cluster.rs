@@ -0,0 +1,143 @@
+use crate::db::establish_connection_for_jobs;
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::sql_types::{Bool, Integer, Text};
+use diesel::{sql_query, QueryableByName, RunQueryDsl};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Cookie, Header, SameSite, Status};
+use rocket::{Request, Response};
+use std::future::Future;
+use std::time::Duration;
+
+/// Names the replica that answered a request
+pub const NODE_HEADER: &str = "X-Pds-Node";
+/// Set on firehose connections, for load balancers doing cookie affinity
+pub const NODE_COOKIE: &str = "pds_node";
+
+/// First key of the advisory locks background jobs are elected with, so they
+/// can't collide with other advisory locks on the same database
+const JOB_LOCK_CLASS: i32 = 0x6a6f6273;
+/// How often a replica that isn't running a job checks whether it can take
+/// over, and the leader checks it still holds the job
+const ELECTION_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Labels every response with the replica that served it. Firehose and
+/// jetstream upgrades also get a `pds_node` cookie, so a load balancer can
+/// send a subscriber that reconnects back to the same replica instead of
+/// reshuffling long-lived connections on every blip. Any replica can resume a
+/// cursor, since they all read the same sequencer store, so losing the
+/// replica only costs the subscriber a reconnect.
+pub struct ClusterNodeFairing(pub String);
+
+#[rocket::async_trait]
+impl Fairing for ClusterNodeFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cluster node id",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _req: &'r Request<'_>, res: &mut Response<'r>) {
+        res.set_header(Header::new(NODE_HEADER, self.0.clone()));
+        if res.status() == Status::SwitchingProtocols {
+            let cookie = Cookie::build((NODE_COOKIE, self.0.clone()))
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .build();
+            res.adjoin_header(Header::new("Set-Cookie", cookie.to_string()));
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct Locked {
+    #[diesel(sql_type = Bool)]
+    locked: bool,
+}
+
+/// Runs a background job on one replica at a time. Whichever replica takes the
+/// job's advisory lock first runs it, the rest wait to take over if its
+/// session ends, so a replica going down hands the job to another instead of
+/// every replica running it at once. `start` is called again each time this
+/// replica is elected.
+pub async fn run_elected<F, Fut>(job: &'static str, start: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut ticker = tokio::time::interval(ELECTION_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let conn = match try_lead(job).await {
+            Ok(Some(conn)) => conn,
+            Ok(None) => continue,
+            Err(error) => {
+                tracing::error!("@LOG: ERROR: failed to run the election for {job}: {error}");
+                continue;
+            }
+        };
+        tracing::info!("Running {job} on this replica");
+        tokio::select! {
+            _ = start() => return,
+            error = hold(conn) => {
+                tracing::error!("@LOG: ERROR: lost the lock on {job}, stopping it: {error}");
+            }
+        }
+    }
+}
+
+/// Runs a job that only has to happen once per deploy, such as asking relays
+/// to crawl, on whichever replica gets to it first
+pub async fn run_once_elected<Fut>(job: &'static str, start: Fut)
+where
+    Fut: Future<Output = ()>,
+{
+    match try_lead(job).await {
+        Ok(Some(conn)) => {
+            start.await;
+            drop(conn);
+        }
+        Ok(None) => tracing::info!("Another replica is running {job}"),
+        Err(error) => tracing::error!("@LOG: ERROR: failed to run the election for {job}: {error}"),
+    }
+}
+
+/// Locks `job` for as long as `conn`'s session lasts, unless another session
+/// already has it
+pub fn try_lock_job(conn: &mut PgConnection, job: &str) -> Result<bool> {
+    let locked = sql_query("SELECT pg_try_advisory_lock($1, hashtext($2)) AS locked")
+        .bind::<Integer, _>(JOB_LOCK_CLASS)
+        .bind::<Text, _>(job)
+        .get_result::<Locked>(conn)?;
+    Ok(locked.locked)
+}
+
+/// The connection holding the lock on `job`, if no other replica has it.
+/// Closing the connection releases the lock.
+async fn try_lead(job: &'static str) -> Result<Option<PgConnection>> {
+    tokio::task::spawn_blocking(move || {
+        let mut conn = establish_connection_for_jobs()?;
+        Ok(try_lock_job(&mut conn, job)?.then_some(conn))
+    })
+    .await?
+}
+
+/// Keeps the session holding a job's lock alive, returning once it's gone
+async fn hold(mut conn: PgConnection) -> anyhow::Error {
+    let mut ticker = tokio::time::interval(ELECTION_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let alive = tokio::task::spawn_blocking(move || {
+            sql_query("SELECT 1").execute(&mut conn)?;
+            Ok::<_, anyhow::Error>(conn)
+        })
+        .await;
+        conn = match alive {
+            Ok(Ok(conn)) => conn,
+            Ok(Err(error)) => return error,
+            Err(error) => return error.into(),
+        };
+    }
+}
@@ -15,6 +15,7 @@ pub async fn service_auth_headers(did: &str, aud: &str, lxm: &str) -> Result<Hea
         lxm: Some(lxm.to_owned()),
         jti: None,
         keypair,
+        collections: None,
     })
     .await
 }
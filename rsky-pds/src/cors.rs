@@ -0,0 +1,148 @@
+use crate::config::CorsConfig;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method, Status};
+use rocket::{Request, Response};
+
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE, OPTIONS";
+/// Response headers browser clients read: the DPoP nonce, the auth challenge
+/// and the rate limit status
+const EXPOSED_HEADERS: &str =
+    "DPoP-Nonce, WWW-Authenticate, RateLimit-Limit, RateLimit-Remaining, RateLimit-Reset, RateLimit-Policy";
+
+/// Lets browser clients, like the BBS web client, call the XRPC endpoints
+/// from the origins in `PDS_CORS_ALLOWED_ORIGINS`.
+///
+/// A listed origin is echoed back with credentials allowed, and responses
+/// carry `Vary: Origin` so caches don't hand one origin's answer to another.
+/// With `*` any other origin may call the PDS too, but without credentials,
+/// which browsers refuse to combine with a wildcard. Preflights echo the
+/// requested headers instead of answering `*`, since a wildcard doesn't cover
+/// `Authorization`.
+///
+/// WebSocket upgrades are left alone: browsers don't apply CORS to them, and
+/// the firehose and jetstream are public anyway.
+pub struct CorsFairing(pub CorsConfig);
+
+impl CorsFairing {
+    fn any_origin(&self) -> bool {
+        self.0.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+
+    /// The `Access-Control-Allow-Origin` a request from `origin` gets, if any
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        let origin = origin.trim_end_matches('/');
+        if self
+            .0
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        {
+            Some(origin.to_string())
+        } else if self.any_origin() {
+            Some("*".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+fn is_websocket_upgrade(req: &Request<'_>) -> bool {
+    req.headers()
+        .get_one("Upgrade")
+        .map_or(false, |upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+#[rocket::async_trait]
+impl Fairing for CorsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add CORS headers to responses",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        if is_websocket_upgrade(req) {
+            return;
+        }
+        let preflight = req.method() == Method::Options
+            && req.headers().contains("Access-Control-Request-Method");
+        let explicit_origins = self.0.allowed_origins.iter().any(|allowed| allowed != "*");
+        if explicit_origins {
+            res.adjoin_header(Header::new("Vary", "Origin"));
+        }
+        if preflight {
+            res.adjoin_header(Header::new(
+                "Vary",
+                "Access-Control-Request-Method, Access-Control-Request-Headers",
+            ));
+        }
+
+        let Some(origin) = req.headers().get_one("Origin") else {
+            return;
+        };
+        let Some(allow_origin) = self.allow_origin(origin) else {
+            return;
+        };
+        if allow_origin != "*" {
+            res.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+        res.set_header(Header::new("Access-Control-Allow-Origin", allow_origin));
+        res.set_header(Header::new(
+            "Access-Control-Expose-Headers",
+            EXPOSED_HEADERS,
+        ));
+
+        if preflight {
+            res.set_header(Header::new("Access-Control-Allow-Methods", ALLOWED_METHODS));
+            if let Some(headers) = req.headers().get_one("Access-Control-Request-Headers") {
+                res.set_header(Header::new(
+                    "Access-Control-Allow-Headers",
+                    headers.to_string(),
+                ));
+            }
+            res.set_header(Header::new(
+                "Access-Control-Max-Age",
+                self.0.max_age_secs.to_string(),
+            ));
+            if res.status() == Status::Ok {
+                res.set_status(Status::NoContent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fairing(origins: &[&str]) -> CorsFairing {
+        CorsFairing(CorsConfig {
+            allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            max_age_secs: 600,
+        })
+    }
+
+    #[test]
+    fn echoes_listed_origins_only() {
+        let cors = fairing(&["https://bbs.example.com"]);
+        assert_eq!(
+            cors.allow_origin("https://BBS.example.com/"),
+            Some("https://BBS.example.com".to_string())
+        );
+        assert_eq!(cors.allow_origin("https://evil.example.com"), None);
+    }
+
+    #[test]
+    fn wildcard_allows_any_origin_without_echoing_it() {
+        let cors = fairing(&["https://bbs.example.com", "*"]);
+        assert_eq!(
+            cors.allow_origin("https://bbs.example.com"),
+            Some("https://bbs.example.com".to_string())
+        );
+        assert_eq!(
+            cors.allow_origin("https://other.example.com"),
+            Some("*".to_string())
+        );
+    }
+}
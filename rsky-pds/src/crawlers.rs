@@ -1,16 +1,24 @@
+use crate::config::CrawlNotifyConfig;
 use crate::APP_USER_AGENT;
-use anyhow::Result;
-use futures::stream::{self, StreamExt};
+use anyhow::{bail, Result};
+use chrono::Utc;
+use futures::future::join_all;
+use rsky_common::tid::TID;
 use rsky_common::time::MINUTE;
-use std::time::SystemTime;
+use std::time::Duration;
 
-const NOTIFY_THRESHOLD: i32 = 20 * MINUTE; // 20 minutes;
+const NOTIFY_THRESHOLD: i64 = 20 * MINUTE as i64; // 20 minutes, in ms
+const REQUEST_CRAWL_TIMEOUT_SECS: u64 = 10;
+/// Longest wait between two retries of a requestCrawl
+const MAX_RETRY_BACKOFF_MS: u64 = 5 * MINUTE as u64;
 
 #[derive(Debug, Clone)]
 pub struct Crawlers {
     pub hostname: String,
     pub crawlers: Vec<String>,
-    pub last_notified: usize,
+    pub notify: CrawlNotifyConfig,
+    /// When crawlers were last asked to crawl, in ms since the epoch
+    pub last_notified: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -18,45 +26,178 @@ pub struct CrawlerRequest {
     pub hostname: String,
 }
 
+/// A relay turned the request down, so asking again won't help
+#[derive(thiserror::Error, Debug)]
+#[error("{service} rejected the crawl request with {status}: {body}")]
+pub struct CrawlRejectedError {
+    pub service: String,
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+/// Asks the relay at `service` to subscribe to the PDS at `hostname`.
+pub async fn request_crawl(service: &str, hostname: &str) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .timeout(Duration::from_secs(REQUEST_CRAWL_TIMEOUT_SECS))
+        .build()?;
+    let res = client
+        .post(format!(
+            "{}/xrpc/com.atproto.sync.requestCrawl",
+            service.trim_end_matches('/')
+        ))
+        .json(&CrawlerRequest {
+            hostname: hostname.to_string(),
+        })
+        .send()
+        .await?;
+    let status = res.status();
+    if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(CrawlRejectedError {
+            service: service.to_string(),
+            status,
+            body: res.text().await.unwrap_or_default(),
+        }
+        .into());
+    }
+    if !status.is_success() {
+        bail!("{service} answered {status}: {}", res.text().await?);
+    }
+    Ok(())
+}
+
+/// How long to wait before retry number `attempt`, counting from 0
+fn retry_backoff(base_ms: u64, attempt: u32) -> Duration {
+    let backoff_ms = base_ms.saturating_mul(2u64.saturating_pow(attempt));
+    Duration::from_millis(backoff_ms.min(MAX_RETRY_BACKOFF_MS))
+}
+
 impl Crawlers {
-    pub fn new(hostname: String, crawlers: Vec<String>) -> Self {
+    pub fn new(hostname: String, crawlers: Vec<String>, notify: CrawlNotifyConfig) -> Self {
         Crawlers {
             hostname,
             crawlers,
+            notify,
             last_notified: 0,
         }
     }
 
+    /// Reminds the crawlers this PDS has new events, at most every 20 minutes.
+    /// The requests are sent in the background, so a relay that's down never
+    /// holds up or fails the write that was just sequenced.
     pub async fn notify_of_update(&mut self) -> Result<()> {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .expect("timestamp in micros since UNIX epoch")
-            .as_micros() as usize;
-        if now - self.last_notified < NOTIFY_THRESHOLD as usize {
+        if Utc::now().timestamp_millis() - self.last_notified < NOTIFY_THRESHOLD {
             return Ok(());
         }
-        let _ = stream::iter(self.crawlers.clone())
-            .then(|service: String| async move {
-                let client = reqwest::Client::builder()
-                    .user_agent(APP_USER_AGENT)
-                    .build()?;
-                let record = CrawlerRequest {
-                    hostname: service.clone(),
-                };
-                Ok::<reqwest::Response, anyhow::Error>(
-                    client
-                        .post(format!("{}/xrpc/com.atproto.sync.requestCrawl", service))
-                        .json(&record)
-                        .send()
-                        .await?,
-                )
+        self.notify_now();
+        Ok(())
+    }
+
+    /// Asks the crawlers to crawl in the background without waiting out the
+    /// 20 minutes since the last time, for an account that just woke up.
+    pub fn notify_now(&mut self) {
+        self.last_notified = Utc::now().timestamp_millis();
+        let crawlers = self.clone();
+        tokio::spawn(async move {
+            crawlers.request_crawl().await;
+        });
+    }
+
+    /// Whether a repo whose previous commit was `since` had gone quiet long
+    /// enough that relays may have stopped watching for it. A repo's first
+    /// commit counts, it's an account becoming active.
+    pub fn is_idle(&self, since: Option<&str>) -> bool {
+        let Some(since) = since else {
+            return true;
+        };
+        if !TID::is_canonical(since) {
+            return false;
+        }
+        let now_us = Utc::now().timestamp_micros().max(0) as usize;
+        now_us.saturating_sub(TID(since.to_string()).timestamp())
+            > self.notify.idle_account_ms as usize * 1000
+    }
+
+    /// Asks every crawler to crawl this PDS now, retrying each with backoff,
+    /// and returns how each finally answered. Failures are logged as well.
+    pub async fn request_crawl(&self) -> Vec<(String, Result<()>)> {
+        let results = join_all(
+            self.crawlers
+                .iter()
+                .map(|service| self.request_crawl_with_retries(service)),
+        )
+        .await;
+        self.crawlers
+            .iter()
+            .cloned()
+            .zip(results)
+            .inspect(|(service, result)| {
+                if let Err(error) = result {
+                    tracing::warn!("Failed to request a crawl from {service}: {error}");
+                }
             })
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect()
+    }
 
-        self.last_notified = now;
-        Ok(())
+    async fn request_crawl_with_retries(&self, service: &str) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match request_crawl(service, &self.hostname).await {
+                Ok(()) => return Ok(()),
+                Err(error)
+                    if attempt < self.notify.max_retries
+                        && error.downcast_ref::<CrawlRejectedError>().is_none() =>
+                {
+                    let backoff = retry_backoff(self.notify.retry_backoff_ms, attempt);
+                    tracing::debug!(
+                        "requestCrawl to {service} failed, retrying in {}ms: {error}",
+                        backoff.as_millis()
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crawlers(idle_account_ms: u64) -> Crawlers {
+        Crawlers::new(
+            "pds.example.com".to_string(),
+            vec![],
+            CrawlNotifyConfig {
+                idle_account_ms,
+                max_retries: 0,
+                retry_backoff_ms: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(retry_backoff(1000, 0), Duration::from_millis(1000));
+        assert_eq!(retry_backoff(1000, 3), Duration::from_millis(8000));
+        assert_eq!(
+            retry_backoff(1000, 40),
+            Duration::from_millis(MAX_RETRY_BACKOFF_MS)
+        );
+    }
+
+    #[test]
+    fn repos_are_idle_after_a_quiet_spell_or_before_their_first_commit() {
+        let crawlers = crawlers(60_000);
+        let now_us = Utc::now().timestamp_micros() as usize;
+        let recent = TID::from_time(now_us - 1_000_000, 0).to_string();
+        let old = TID::from_time(now_us - 120_000_000, 0).to_string();
+
+        assert!(crawlers.is_idle(None));
+        assert!(!crawlers.is_idle(Some(&recent)));
+        assert!(crawlers.is_idle(Some(&old)));
+        assert!(!crawlers.is_idle(Some("not-a-tid")));
     }
 }
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, bail, Result};
+use diesel::migration::MigrationSource;
+use diesel::pg::{Pg, PgConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+/// Every migration under `migrations/`, compiled into the binary so a deploy
+/// can't run against a schema it doesn't know about.
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaStatus {
+    /// Version of the last applied migration, unset on an empty database
+    pub current: Option<String>,
+    /// Version of the last migration this binary embeds
+    pub latest: Option<String>,
+    /// Names of the embedded migrations not applied yet, oldest first
+    pub pending: Vec<String>,
+}
+
+/// Applies every pending migration, returning the versions applied.
+pub fn run_pending(conn: &mut PgConnection) -> Result<Vec<String>> {
+    let applied = conn
+        .run_pending_migrations(MIGRATIONS)
+        .map_err(|error| anyhow!("failed to run migrations: {error}"))?;
+    Ok(applied.iter().map(|version| version.to_string()).collect())
+}
+
+/// Reverts the last applied migration, returning its version.
+pub fn revert_last(conn: &mut PgConnection) -> Result<String> {
+    let reverted = conn
+        .revert_last_migration(MIGRATIONS)
+        .map_err(|error| anyhow!("failed to revert migration: {error}"))?;
+    Ok(reverted.to_string())
+}
+
+pub fn schema_status(conn: &mut PgConnection) -> Result<SchemaStatus> {
+    let current = conn
+        .applied_migrations()
+        .map_err(|error| anyhow!("failed to read applied migrations: {error}"))?
+        .into_iter()
+        .max()
+        .map(|version| version.to_string());
+    let latest = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+        .map_err(|error| anyhow!("failed to read embedded migrations: {error}"))?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .max();
+    let pending = conn
+        .pending_migrations(MIGRATIONS)
+        .map_err(|error| anyhow!("failed to read pending migrations: {error}"))?
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect();
+    Ok(SchemaStatus {
+        current,
+        latest,
+        pending,
+    })
+}
+
+/// Fails while the database is behind the migrations this binary embeds.
+pub fn check_schema_version(conn: &mut PgConnection) -> Result<()> {
+    let status = schema_status(conn)?;
+    if !status.pending.is_empty() {
+        bail!(
+            "Schema is at {}, {} migrations pending up to {}",
+            status.current.as_deref().unwrap_or("none"),
+            status.pending.len(),
+            status.latest.as_deref().unwrap_or("none")
+        );
+    }
+    Ok(())
+}
+
+/// `rsky-pds migrate [up|down|status]`, returning the process exit code.
+pub fn run(command: Option<&str>) -> i32 {
+    let res = super::establish_connection_for_jobs().and_then(|mut conn| match command {
+        None | Some("up") => {
+            let applied = run_pending(&mut conn)?;
+            match applied.is_empty() {
+                true => println!("Schema is up to date"),
+                false => applied
+                    .iter()
+                    .for_each(|version| println!("Applied {version}")),
+            }
+            Ok(())
+        }
+        Some("down") => {
+            println!("Reverted {}", revert_last(&mut conn)?);
+            Ok(())
+        }
+        Some("status") => {
+            let status = schema_status(&mut conn)?;
+            println!(
+                "Schema version: {}",
+                status.current.as_deref().unwrap_or("none")
+            );
+            println!(
+                "Latest version: {}",
+                status.latest.as_deref().unwrap_or("none")
+            );
+            status
+                .pending
+                .iter()
+                .for_each(|name| println!("Pending: {name}"));
+            Ok(())
+        }
+        Some(command) => bail!("unknown migrate command `{command}`, expected up, down or status"),
+    });
+    match res {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {error}");
+            1
+        }
+    }
+}
@@ -1,8 +1,12 @@
+pub mod migrations;
+pub mod replica;
+
 use anyhow::Result;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use dotenvy::dotenv;
-use rocket_sync_db_pools::database;
+use rocket::{Phase, Rocket};
+use rocket_sync_db_pools::{database, ConnectionPool};
 use std::env;
 use std::fmt::{Debug, Formatter};
 
@@ -15,6 +19,22 @@ impl Debug for DbConn {
     }
 }
 
+/// Hands out `DbConn`s outside of a request, for background work that goes
+/// through the same stores request handlers use
+#[derive(Clone)]
+pub struct DbConnPool(ConnectionPool<DbConn, PgConnection>);
+
+impl DbConnPool {
+    /// The pool `DbConn::fairing()` set up, once Rocket has ignited
+    pub fn of<P: Phase>(rocket: &Rocket<P>) -> Option<Self> {
+        DbConn::pool(rocket).cloned().map(DbConnPool)
+    }
+
+    pub async fn get(&self) -> Option<DbConn> {
+        self.0.get().await.map(DbConn)
+    }
+}
+
 #[tracing::instrument(skip_all)]
 pub fn establish_connection_for_sequencer() -> Result<PgConnection> {
     dotenv().ok();
@@ -27,3 +47,16 @@ pub fn establish_connection_for_sequencer() -> Result<PgConnection> {
 
     Ok(result)
 }
+
+#[tracing::instrument(skip_all)]
+pub fn establish_connection_for_jobs() -> Result<PgConnection> {
+    dotenv().ok();
+    tracing::debug!("Establishing database connection for background job");
+    let database_url = env::var("DATABASE_URL").unwrap_or("".into());
+    let result = PgConnection::establish(&database_url).map_err(|error| {
+        let context = format!("Error connecting to {database_url:?}");
+        anyhow::Error::new(error).context(context)
+    })?;
+
+    Ok(result)
+}
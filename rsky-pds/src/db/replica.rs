@@ -0,0 +1,184 @@
+use crate::config::DatabaseConfig;
+use crate::db::DbConn;
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, State};
+use rocket_sync_db_pools::database;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Replication lag in milliseconds, 0 once the replica has replayed all the
+/// WAL it received. An idle primary doesn't advance the replay timestamp, so
+/// it alone would report a caught up replica as lagging.
+const REPLICATION_LAG_SQL: &str = "CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+     ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) * 1000, 0) END::bigint";
+
+#[database("pg_replica")]
+pub struct ReplicaConn(PgConnection);
+
+fn replication_lag_ms(conn: &mut PgConnection) -> Result<i64> {
+    Ok(diesel::select(diesel::dsl::sql::<BigInt>(REPLICATION_LAG_SQL)).get_result(conn)?)
+}
+
+/// Tracks how far the read replica is behind the primary, so reads only go to
+/// it while it's close enough that clients won't notice.
+pub struct ReplicaMonitor {
+    url: Option<String>,
+    max_lag_ms: u64,
+    interval_ms: u64,
+    /// Last measured lag, -1 until measured or while the replica is down
+    lag_ms: AtomicI64,
+    usable: AtomicBool,
+}
+
+pub type SharedReplicaMonitor = Arc<ReplicaMonitor>;
+
+impl ReplicaMonitor {
+    pub fn new(cfg: &DatabaseConfig) -> Self {
+        ReplicaMonitor {
+            url: cfg.replica.as_ref().map(|replica| replica.url.clone()),
+            max_lag_ms: cfg.replica.as_ref().map_or(0, |replica| replica.max_lag_ms),
+            interval_ms: cfg
+                .replica
+                .as_ref()
+                .map_or(1000, |replica| replica.lag_check_interval_ms.max(100)),
+            lag_ms: AtomicI64::new(-1),
+            usable: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether reads can go to the replica right now.
+    pub fn is_usable(&self) -> bool {
+        self.usable.load(Ordering::SeqCst)
+    }
+
+    pub fn lag_ms(&self) -> Option<u64> {
+        match self.lag_ms.load(Ordering::SeqCst) {
+            lag_ms if lag_ms < 0 => None,
+            lag_ms => Some(lag_ms as u64),
+        }
+    }
+
+    fn record_lag(&self, lag_ms: Option<i64>) {
+        self.lag_ms.store(lag_ms.unwrap_or(-1), Ordering::SeqCst);
+        let usable = matches!(lag_ms, Some(lag_ms) if lag_ms as u64 <= self.max_lag_ms);
+        if self.usable.swap(usable, Ordering::SeqCst) != usable {
+            match usable {
+                true => tracing::info!("Read replica caught up, serving reads from it"),
+                false => tracing::warn!(
+                    "Read replica lag {lag_ms:?}ms is over {}ms, serving reads from the primary",
+                    self.max_lag_ms
+                ),
+            }
+        }
+    }
+
+    pub async fn start(&self) {
+        let Some(ref url) = self.url else {
+            return;
+        };
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        let mut replica: Option<PgConnection> = None;
+        loop {
+            ticker.tick().await;
+            let url = url.clone();
+            let conn = replica.take();
+            let res = tokio::task::spawn_blocking(move || {
+                let mut conn = match conn {
+                    Some(conn) => conn,
+                    None => PgConnection::establish(&url)?,
+                };
+                let lag_ms = replication_lag_ms(&mut conn)?;
+                Ok::<_, anyhow::Error>((conn, lag_ms))
+            })
+            .await;
+            match res {
+                Ok(Ok((conn, lag_ms))) => {
+                    replica = Some(conn);
+                    self.record_lag(Some(lag_ms));
+                }
+                Ok(Err(error)) => {
+                    tracing::error!("@LOG: ERROR: failed to check replica lag: {error}");
+                    self.record_lag(None);
+                }
+                Err(error) => {
+                    tracing::error!("@LOG: ERROR: replica lag task panicked: {error}");
+                    self.record_lag(None);
+                }
+            }
+        }
+    }
+}
+
+/// Connection for read-only routes: the replica while it's within the lag
+/// threshold, otherwise the primary. Never use it where the caller expects to
+/// read its own writes.
+pub enum ReadConn {
+    Primary(DbConn),
+    Replica(ReplicaConn),
+}
+
+impl ReadConn {
+    pub async fn run<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut PgConnection) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        match self {
+            ReadConn::Primary(db) => db.run(f).await,
+            ReadConn::Replica(db) => db.run(f).await,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ReadConn {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let use_replica = match req.guard::<&State<SharedReplicaMonitor>>().await {
+            Outcome::Success(monitor) => monitor.is_usable(),
+            _ => false,
+        };
+        if use_replica {
+            if let Outcome::Success(db) = req.guard::<ReplicaConn>().await {
+                return Outcome::Success(ReadConn::Replica(db));
+            }
+            tracing::warn!("No replica connection available, reading from the primary");
+        }
+        req.guard::<DbConn>().await.map(ReadConn::Primary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ReplicaConfig;
+
+    #[test]
+    fn falls_back_when_replica_lags() {
+        let monitor = ReplicaMonitor::new(&DatabaseConfig {
+            migrate_on_startup: false,
+            pool_size: 20,
+            pool_timeout_secs: 30,
+            replica: Some(ReplicaConfig {
+                url: "postgres://replica/pds".to_string(),
+                pool_size: 10,
+                max_lag_ms: 5000,
+                lag_check_interval_ms: 1000,
+            }),
+        });
+        assert!(!monitor.is_usable());
+        monitor.record_lag(Some(200));
+        assert!(monitor.is_usable());
+        monitor.record_lag(Some(6000));
+        assert!(!monitor.is_usable());
+        monitor.record_lag(None);
+        assert!(!monitor.is_usable());
+        assert_eq!(monitor.lag_ms(), None);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::apis::ApiError;
+use crate::config::{apply_rate_limit_overrides, RateLimitPolicy, ServerConfig};
+use crate::rate_limit::SharedRateLimiter;
+use anyhow::Result;
+use rsky_lexicon::com::atproto::admin::{RuntimeFlags, UpdateRuntimeFlagsInput};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+const BBS_COLLECTION_PREFIX: &str = "app.bbs.";
+
+/// Flags operators can flip while the PDS runs, through the admin endpoints or
+/// `PDS_FLAGS_FILE`, without a restart dropping firehose subscribers.
+pub struct FlagStore {
+    flags: RwLock<RuntimeFlags>,
+    /// Policies from the env config, which rate limit overrides apply over
+    rate_limit_policies: Vec<RateLimitPolicy>,
+    rate_limiter: SharedRateLimiter,
+}
+
+pub type SharedFlagStore = Arc<FlagStore>;
+
+impl FlagStore {
+    pub fn new(cfg: &ServerConfig, rate_limiter: SharedRateLimiter) -> Self {
+        FlagStore {
+            flags: RwLock::new(RuntimeFlags {
+                invite_required: cfg.invites.required,
+                bbs_writes_frozen: false,
+                chain_strict: true,
+                rate_limits_enabled: cfg.rate_limits.enabled,
+                rate_limit_overrides: Vec::new(),
+            }),
+            rate_limit_policies: cfg.rate_limits.policies.clone(),
+            rate_limiter,
+        }
+    }
+
+    pub fn get(&self) -> RuntimeFlags {
+        self.flags.read().expect("runtime flags poisoned").clone()
+    }
+
+    /// Applies the flags set in `update`, leaving the rest as they are.
+    pub fn update(&self, update: UpdateRuntimeFlagsInput) -> Result<RuntimeFlags> {
+        let mut current = self.flags.write().expect("runtime flags poisoned");
+        let mut flags = current.clone();
+        if let Some(invite_required) = update.invite_required {
+            flags.invite_required = invite_required;
+        }
+        if let Some(bbs_writes_frozen) = update.bbs_writes_frozen {
+            flags.bbs_writes_frozen = bbs_writes_frozen;
+        }
+        if let Some(chain_strict) = update.chain_strict {
+            flags.chain_strict = chain_strict;
+        }
+        if let Some(rate_limits_enabled) = update.rate_limits_enabled {
+            flags.rate_limits_enabled = rate_limits_enabled;
+        }
+        if let Some(rate_limit_overrides) = update.rate_limit_overrides {
+            flags.rate_limit_overrides = rate_limit_overrides;
+        }
+        let policies = apply_rate_limit_overrides(
+            self.rate_limit_policies.clone(),
+            &flags.rate_limit_overrides,
+        )?;
+        self.rate_limiter
+            .reload(flags.rate_limits_enabled, policies);
+        if *current != flags {
+            tracing::info!("Runtime flags changed to {flags:?}");
+        }
+        *current = flags.clone();
+        Ok(flags)
+    }
+
+    /// Rejects writes to app.bbs.* collections while BBS writes are frozen.
+    pub fn check_writable<'a>(
+        &self,
+        collections: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), ApiError> {
+        if !self.get().bbs_writes_frozen {
+            return Ok(());
+        }
+        match collections
+            .into_iter()
+            .find(|collection| collection.starts_with(BBS_COLLECTION_PREFIX))
+        {
+            Some(collection) => Err(ApiError::BadRequest(
+                "WritesFrozen".to_string(),
+                format!("Writes to {collection} are temporarily disabled"),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reapplies `PDS_FLAGS_FILE` whenever it's modified. The file holds the same
+/// JSON as `com.atproto.admin.updateRuntimeFlags`.
+pub struct FlagsWatcher {
+    flags: SharedFlagStore,
+    path: PathBuf,
+    interval_ms: u64,
+}
+
+impl FlagsWatcher {
+    pub fn new(flags: SharedFlagStore, path: String, interval_ms: u64) -> Self {
+        FlagsWatcher {
+            flags,
+            path: PathBuf::from(path),
+            interval_ms,
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        let mut last_modified = None;
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run(&mut last_modified).await {
+                tracing::error!(
+                    "@LOG: ERROR: failed to load flags from {}: {error}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+
+    pub async fn run(&self, last_modified: &mut Option<SystemTime>) -> Result<()> {
+        let modified = tokio::fs::metadata(&self.path).await?.modified()?;
+        if *last_modified == Some(modified) {
+            return Ok(());
+        }
+        // Marked as seen even if it's invalid, so a bad edit is reported once
+        *last_modified = Some(modified);
+        let contents = tokio::fs::read(&self.path).await?;
+        let update: UpdateRuntimeFlagsInput = serde_json::from_slice(&contents)?;
+        self.flags.update(update)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::env_to_cfg;
+    use crate::rate_limit::RateLimiter;
+
+    async fn flag_store() -> FlagStore {
+        let cfg = env_to_cfg();
+        let rate_limiter = RateLimiter::new(cfg.rate_limits.clone()).await.unwrap();
+        FlagStore::new(&cfg, Arc::new(rate_limiter))
+    }
+
+    #[tokio::test]
+    async fn updates_only_given_flags() {
+        let store = flag_store().await;
+        let before = store.get();
+        let after = store
+            .update(UpdateRuntimeFlagsInput {
+                bbs_writes_frozen: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(after.bbs_writes_frozen);
+        assert_eq!(after.invite_required, before.invite_required);
+        assert_eq!(after.chain_strict, before.chain_strict);
+        assert!(store
+            .check_writable(["app.bbs.post", "app.bsky.feed.post"])
+            .is_err());
+        assert!(store.check_writable(["app.bsky.feed.post"]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_rate_limit_policies() {
+        let store = flag_store().await;
+        let res = store.update(UpdateRuntimeFlagsInput {
+            rate_limits_enabled: Some(true),
+            rate_limit_overrides: Some(vec!["nope:1:1000".to_string()]),
+            ..Default::default()
+        });
+        assert!(res.is_err());
+        assert!(store.get().rate_limit_overrides.is_empty());
+    }
+}
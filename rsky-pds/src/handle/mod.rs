@@ -57,10 +57,14 @@ pub async fn normalize_and_validate_handle(
         }
 
         // Verify resolution of a non-service domain
-        let mut lock = ctx.id_resolver.id_resolver.write().await;
-        match lock.handle.resolve(&handle).await.unwrap() {
+        let did = opts.did.unwrap();
+        match ctx
+            .id_resolver
+            .resolve_handle_expecting(&handle, &did)
+            .await
+        {
             Some(resolved_did) => {
-                if resolved_did != opts.did.unwrap() {
+                if resolved_did != did {
                     return Err(Error::new(
                         ErrorKind::InvalidHandle,
                         "External handle did not resolve to DID",
@@ -79,14 +83,14 @@ pub async fn normalize_and_validate_handle(
     Ok(handle)
 }
 
-fn base_normalize_and_validate(handle: &str) -> Result<String> {
+pub(crate) fn base_normalize_and_validate(handle: &str) -> Result<String> {
     match normalize_and_ensure_valid_handle(handle) {
         Ok(normalized) => Ok(normalized),
         Err(e) => Err(Error::new(ErrorKind::InvalidHandle, &e.to_string())),
     }
 }
 
-fn is_service_domain(handle: &str, available_user_domains: &[String]) -> bool {
+pub(crate) fn is_service_domain(handle: &str, available_user_domains: &[String]) -> bool {
     available_user_domains
         .iter()
         .any(|domain| handle.ends_with(domain))
@@ -129,3 +133,4 @@ fn ensure_handle_service_constraints(
 pub mod errors;
 pub mod explicit_slurs;
 pub mod reserved;
+pub mod resolver;
@@ -0,0 +1,125 @@
+use crate::SharedIdResolver;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// External handles kept in memory
+const HANDLE_CACHE_CAPACITY: usize = 10_000;
+
+struct CachedHandle {
+    did: Option<String>,
+    cached_at: Instant,
+}
+
+/// The DID each external handle was last found to point at, or that it
+/// pointed nowhere, so resolveHandle doesn't hit DNS for every request.
+pub struct HandleCache {
+    ttl: Duration,
+    entries: Mutex<LruCache<String, CachedHandle>>,
+}
+
+impl HandleCache {
+    pub fn new(ttl: Duration) -> Self {
+        HandleCache {
+            ttl,
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(HANDLE_CACHE_CAPACITY).expect("non-zero capacity"),
+            )),
+        }
+    }
+
+    /// `None` when the handle isn't cached or its entry has expired.
+    pub fn get(&self, handle: &str) -> Option<Option<String>> {
+        let mut entries = self.entries.lock().expect("handle cache poisoned");
+        match entries.get(handle) {
+            Some(cached) if cached.cached_at.elapsed() < self.ttl => Some(cached.did.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn put(&self, handle: &str, did: Option<String>) {
+        self.entries.lock().expect("handle cache poisoned").put(
+            handle.to_string(),
+            CachedHandle {
+                did,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+}
+
+impl SharedIdResolver {
+    /// Looks up the DID `handle` claims in its `_atproto` DNS TXT record, then
+    /// at `https://<handle>/.well-known/atproto-did`, then through the backup
+    /// name servers. Skips the cache.
+    pub async fn lookup_handle(&self, handle: &str) -> Option<String> {
+        let handle = handle.to_string();
+        // Cloned so the lock isn't held while waiting on the network
+        let resolver = self.id_resolver.read().await.handle.clone();
+        match resolver.resolve_dns(&handle).await {
+            Ok(Some(did)) => return Some(did),
+            Ok(None) => {}
+            Err(error) => tracing::debug!("DNS lookup for {handle} failed: {error}"),
+        }
+        match resolver.resolve_http(&handle).await {
+            Ok(Some(did)) => return Some(did),
+            Ok(None) => {}
+            Err(error) => tracing::debug!("well-known lookup for {handle} failed: {error}"),
+        }
+        let mut lock = self.id_resolver.write().await;
+        lock.handle
+            .resolve_backup_dns(&handle)
+            .await
+            .unwrap_or_else(|error| {
+                tracing::debug!("backup DNS lookup for {handle} failed: {error}");
+                None
+            })
+    }
+
+    /// The DID `handle` resolves to, cached for `PDS_HANDLE_CACHE_TTL`.
+    pub async fn resolve_handle(&self, handle: &str) -> Option<String> {
+        if let Some(did) = self.handle_cache.get(handle) {
+            return did;
+        }
+        let did = self.lookup_handle(handle).await;
+        self.handle_cache.put(handle, did.clone());
+        did
+    }
+
+    /// Like `resolve_handle`, but a cached DID other than `did` is looked up
+    /// again, so a user who just pointed their domain here doesn't have to
+    /// wait out the cache.
+    pub async fn resolve_handle_expecting(&self, handle: &str, did: &str) -> Option<String> {
+        match self.handle_cache.get(handle) {
+            Some(Some(cached)) if cached == did => Some(cached),
+            _ => {
+                let resolved = self.lookup_handle(handle).await;
+                self.handle_cache.put(handle, resolved.clone());
+                resolved
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_handles_until_they_expire() {
+        let cache = HandleCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get("alice.example.com"), None);
+        cache.put("alice.example.com", Some("did:plc:alice".to_string()));
+        cache.put("nobody.example.com", None);
+        assert_eq!(
+            cache.get("alice.example.com"),
+            Some(Some("did:plc:alice".to_string()))
+        );
+        assert_eq!(cache.get("nobody.example.com"), Some(None));
+
+        let cache = HandleCache::new(Duration::ZERO);
+        cache.put("alice.example.com", Some("did:plc:alice".to_string()));
+        assert_eq!(cache.get("alice.example.com"), None);
+    }
+}
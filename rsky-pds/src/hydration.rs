@@ -0,0 +1,390 @@
+use crate::account_manager::helpers::account::{ActorAccount, AvailabilityFlags};
+use crate::account_manager::AccountManager;
+use crate::apis::com::atproto::repo::assert_repo_availability;
+use crate::auth_verifier::{
+    is_admin_request, is_bearer_token, AccessStandard, AdminToken, AuthError,
+};
+use crate::bbs::graph::hidden_authors;
+use crate::bbs::unfurl::link_cards;
+use crate::config::{ServerConfig, UnfurlConfig};
+use crate::db::replica::ReadConn;
+use crate::oauth::is_dpop_token;
+use anyhow::Result;
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use rsky_lexicon::app::bbs::embed::{View as EmbedView, ViewBlocked, ViewNotFound, ViewUnion};
+use rsky_lexicon::app::bbs::{GetThreadOutput, ReplyView, ThreadView};
+use rsky_lexicon::app::bsky::embed::external::ViewExternal;
+use std::collections::{HashMap, HashSet};
+
+/// Decides what a read may return from account status, record takedowns and
+/// the viewer's blocks and mutes, so endpoints share one set of checks
+/// instead of each repeating their own. Admin requests bypass it, since
+/// moderators need to review what they took down.
+pub struct Hydrator {
+    /// Set for admins, who also see taken down and deactivated content
+    pub include_taken_down: bool,
+    /// The signed in account reading, whose blocks and mutes apply
+    pub viewer: Option<String>,
+    pub account_manager: AccountManager,
+    pub db: ReadConn,
+    /// Set when link cards are enabled, see [`crate::bbs::unfurl`]
+    pub unfurl: Option<UnfurlConfig>,
+    /// Where this PDS is reached, link card thumbs are served from here
+    pub public_url: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Hydrator {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let include_taken_down = match is_admin_request(req) {
+            true => match AdminToken::from_request(req).await {
+                Outcome::Success(_) => true,
+                Outcome::Error(err) => return Outcome::Error(err),
+                Outcome::Forward(status) => return Outcome::Forward(status),
+            },
+            false => false,
+        };
+        let viewer = match !include_taken_down && (is_bearer_token(req) || is_dpop_token(req)) {
+            true => match AccessStandard::from_request(req).await {
+                Outcome::Success(auth) => auth.access.credentials.and_then(|creds| creds.did),
+                Outcome::Error(err) => return Outcome::Error(err),
+                Outcome::Forward(status) => return Outcome::Forward(status),
+            },
+            false => None,
+        };
+        let account_manager = match req.guard::<AccountManager>().await {
+            Outcome::Success(account_manager) => account_manager,
+            _ => {
+                return Outcome::Error((
+                    Status::InternalServerError,
+                    AuthError::AuthRequired("No account manager".to_string()),
+                ))
+            }
+        };
+        let Some(cfg) = req.rocket().state::<ServerConfig>() else {
+            return Outcome::Error((
+                Status::InternalServerError,
+                AuthError::AuthRequired("No server config".to_string()),
+            ));
+        };
+        let db = match req.guard::<ReadConn>().await {
+            Outcome::Success(db) => db,
+            _ => {
+                return Outcome::Error((
+                    Status::ServiceUnavailable,
+                    AuthError::AuthRequired("No database connection".to_string()),
+                ))
+            }
+        };
+        Outcome::Success(Hydrator {
+            include_taken_down,
+            viewer,
+            account_manager,
+            db,
+            unfurl: cfg.unfurl.enabled.then(|| cfg.unfurl.clone()),
+            public_url: cfg.service.public_url.clone(),
+        })
+    }
+}
+
+impl Hydrator {
+    /// The account behind a repo, unless it's taken down, suspended or
+    /// deactivated. See `assert_repo_availability` for the errors.
+    pub async fn assert_repo_readable(&self, did: &String) -> Result<ActorAccount> {
+        assert_repo_availability(did, self.include_taken_down, &self.account_manager).await
+    }
+
+    /// Whether a record with this takedown may be returned
+    pub fn record_readable(&self, takedown_ref: Option<&String>) -> bool {
+        self.include_taken_down || takedown_ref.is_none()
+    }
+
+    /// Which of the `(uri, author)` pairs must be withheld: records taken down
+    /// on this PDS or held for review by a section's rules, and records by
+    /// accounts hosted here that aren't active. Authors hosted elsewhere are
+    /// left to their own PDS and the AppView.
+    pub async fn hidden_uris(&self, records: Vec<(String, String)>) -> Result<HashSet<String>> {
+        use crate::schema::pds::bbs_review::dsl as ReviewSchema;
+        use crate::schema::pds::record::dsl as RecordSchema;
+
+        if self.include_taken_down || records.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut authors = records
+            .iter()
+            .map(|(_, author)| author.clone())
+            .collect::<Vec<String>>();
+        authors.sort();
+        authors.dedup();
+        let inactive = self
+            .account_manager
+            .get_accounts(
+                &authors,
+                Some(AvailabilityFlags {
+                    include_taken_down: Some(true),
+                    include_deactivated: Some(true),
+                }),
+            )
+            .await?
+            .into_values()
+            .filter(|account| !account_readable(account))
+            .map(|account| account.did)
+            .collect::<HashSet<String>>();
+
+        let uris = records
+            .iter()
+            .map(|(uri, _)| uri.clone())
+            .collect::<Vec<String>>();
+        let withheld = self
+            .db
+            .run(move |conn| {
+                let mut hidden = RecordSchema::record
+                    .filter(RecordSchema::uri.eq_any(&uris))
+                    .filter(RecordSchema::takedownRef.is_not_null())
+                    .select(RecordSchema::uri)
+                    .load::<String>(conn)?;
+                hidden.extend(
+                    ReviewSchema::bbs_review
+                        .filter(ReviewSchema::uri.eq_any(&uris))
+                        .filter(ReviewSchema::held.eq(true))
+                        .filter(ReviewSchema::resolvedAt.is_null())
+                        .select(ReviewSchema::uri)
+                        .load::<String>(conn)?,
+                );
+                Ok::<_, diesel::result::Error>(hidden)
+            })
+            .await?
+            .into_iter()
+            .collect::<HashSet<String>>();
+
+        Ok(records
+            .into_iter()
+            .filter(|(uri, author)| withheld.contains(uri) || inactive.contains(author))
+            .map(|(uri, _)| uri)
+            .collect())
+    }
+
+    /// Which of `authors` the viewer blocks or mutes, or is blocked by. Empty
+    /// for signed out readers.
+    pub async fn silenced_authors(&self, authors: Vec<String>) -> Result<HashSet<String>> {
+        let Some(viewer) = self.viewer.clone() else {
+            return Ok(HashSet::new());
+        };
+        self.db
+            .run(move |conn| hidden_authors(conn, &viewer, authors))
+            .await
+    }
+
+    /// The link cards unfurled for the posts among `uris`, keyed by uri.
+    /// Empty when link cards aren't enabled.
+    pub async fn link_cards(&self, uris: Vec<String>) -> Result<HashMap<String, ViewExternal>> {
+        let Some(unfurl) = self.unfurl.clone() else {
+            return Ok(HashMap::new());
+        };
+        let public_url = self.public_url.clone();
+        self.db
+            .run(move |conn| link_cards(conn, uris, &public_url, &unfurl.denylist))
+            .await
+    }
+
+    /// Drops the threads that must be withheld or are by silenced authors,
+    /// keeping the order, and attaches link cards to the rest. Their quotes
+    /// are screened the same way, see [`screen_quote`].
+    pub async fn hydrate_threads(&self, threads: Vec<ThreadView>) -> Result<Vec<ThreadView>> {
+        let quoted = threads
+            .iter()
+            .filter_map(|thread| quoted_record(&thread.embed))
+            .collect::<Vec<(String, String)>>();
+        let hidden = self
+            .hidden_uris(
+                threads
+                    .iter()
+                    .map(|thread| (thread.uri.clone(), thread.author.did.clone()))
+                    .chain(quoted.iter().cloned())
+                    .collect(),
+            )
+            .await?;
+        let silenced = self
+            .silenced_authors(
+                threads
+                    .iter()
+                    .map(|thread| thread.author.did.clone())
+                    .chain(quoted.into_iter().map(|(_, author)| author))
+                    .collect(),
+            )
+            .await?;
+        let mut cards = self
+            .link_cards(threads.iter().map(|thread| thread.uri.clone()).collect())
+            .await?;
+        Ok(threads
+            .into_iter()
+            .filter(|thread| {
+                !hidden.contains(&thread.uri) && !silenced.contains(&thread.author.did)
+            })
+            .map(|thread| ThreadView {
+                external: cards.remove(&thread.uri).or(thread.external.clone()),
+                embed: screen_quote(thread.embed.clone(), &hidden, &silenced),
+                ..thread
+            })
+            .collect())
+    }
+
+    /// The thread without the replies that must be withheld or are by
+    /// silenced authors, or `None` when the thread itself must be withheld.
+    /// A thread by a silenced author is still served, the viewer asked for it.
+    /// The thread gets its link card like in listings, and quotes are
+    /// screened like replies.
+    pub async fn hydrate_thread(&self, output: GetThreadOutput) -> Result<Option<GetThreadOutput>> {
+        let quoted = std::iter::once(&output.thread.embed)
+            .chain(output.replies.iter().map(|reply| &reply.embed))
+            .filter_map(quoted_record)
+            .collect::<Vec<(String, String)>>();
+        let mut records = vec![(output.thread.uri.clone(), output.thread.author.did.clone())];
+        records.extend(
+            output
+                .replies
+                .iter()
+                .map(|reply| (reply.uri.clone(), reply.author.did.clone())),
+        );
+        records.extend(quoted.iter().cloned());
+        let hidden = self.hidden_uris(records).await?;
+        if hidden.contains(&output.thread.uri) {
+            return Ok(None);
+        }
+        let silenced = self
+            .silenced_authors(
+                output
+                    .replies
+                    .iter()
+                    .map(|reply| reply.author.did.clone())
+                    .chain(quoted.into_iter().map(|(_, author)| author))
+                    .collect(),
+            )
+            .await?;
+        let mut cards = self.link_cards(vec![output.thread.uri.clone()]).await?;
+        let thread = ThreadView {
+            external: cards
+                .remove(&output.thread.uri)
+                .or(output.thread.external.clone()),
+            embed: screen_quote(output.thread.embed.clone(), &hidden, &silenced),
+            ..output.thread
+        };
+        Ok(Some(GetThreadOutput {
+            thread,
+            replies: output
+                .replies
+                .into_iter()
+                .filter(|reply| {
+                    !hidden.contains(&reply.uri) && !silenced.contains(&reply.author.did)
+                })
+                .map(|reply| ReplyView {
+                    embed: screen_quote(reply.embed.clone(), &hidden, &silenced),
+                    ..reply
+                })
+                .collect(),
+            ..output
+        }))
+    }
+}
+
+/// Active accounts are the only ones whose content is served
+fn account_readable(account: &ActorAccount) -> bool {
+    account.takedown_ref.is_none() && account.deactivated_at.is_none()
+}
+
+/// The `(uri, author)` of the post or reply quoted, if the AppView found it
+fn quoted_record(embed: &Option<EmbedView>) -> Option<(String, String)> {
+    match embed.as_ref().map(|embed| &embed.record) {
+        Some(ViewUnion::ViewRecord(quote)) => Some((quote.uri.clone(), quote.author.did.clone())),
+        _ => None,
+    }
+}
+
+/// The quote as the viewer may see it: `viewNotFound` when it must be
+/// withheld, `viewBlocked` when its author is silenced
+fn screen_quote(
+    embed: Option<EmbedView>,
+    hidden: &HashSet<String>,
+    silenced: &HashSet<String>,
+) -> Option<EmbedView> {
+    let EmbedView { record } = embed?;
+    let record = match record {
+        ViewUnion::ViewRecord(quote) if hidden.contains(&quote.uri) => {
+            ViewUnion::ViewNotFound(ViewNotFound {
+                uri: quote.uri,
+                not_found: true,
+            })
+        }
+        ViewUnion::ViewRecord(quote) if silenced.contains(&quote.author.did) => {
+            ViewUnion::ViewBlocked(ViewBlocked {
+                uri: quote.uri,
+                blocked: true,
+                author: quote.author,
+            })
+        }
+        record => record,
+    };
+    Some(EmbedView { record })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_lexicon::app::bbs::embed::ViewRecord;
+    use rsky_lexicon::app::bbs::AuthorView;
+
+    fn quote(uri: &str, did: &str) -> Option<EmbedView> {
+        Some(EmbedView {
+            record: ViewUnion::ViewRecord(ViewRecord {
+                uri: uri.to_string(),
+                cid: "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm".to_string(),
+                author: AuthorView {
+                    did: did.to_string(),
+                    handle: None,
+                },
+                thread: uri.to_string(),
+                title: Some("Hello".to_string()),
+                text: "hello".to_string(),
+                truncated: None,
+                created_at: "2025-01-01T00:00:00.000Z".to_string(),
+                indexed_at: "2025-01-01T00:00:00.000Z".to_string(),
+            }),
+        })
+    }
+
+    #[test]
+    fn screens_quotes_the_viewer_may_not_see() {
+        let taken_down = "at://did:web5:alice/app.bbs.post/1";
+        let hidden = HashSet::from([taken_down.to_string()]);
+        let silenced = HashSet::from(["did:web5:bob".to_string()]);
+        assert_eq!(screen_quote(None, &hidden, &silenced), None);
+        assert_eq!(
+            screen_quote(quote(taken_down, "did:web5:alice"), &hidden, &silenced)
+                .map(|embed| embed.record),
+            Some(ViewUnion::ViewNotFound(ViewNotFound {
+                uri: taken_down.to_string(),
+                not_found: true,
+            }))
+        );
+        let by_bob = "at://did:web5:bob/app.bbs.reply/2";
+        assert_eq!(
+            screen_quote(quote(by_bob, "did:web5:bob"), &hidden, &silenced)
+                .map(|embed| embed.record),
+            Some(ViewUnion::ViewBlocked(ViewBlocked {
+                uri: by_bob.to_string(),
+                blocked: true,
+                author: AuthorView {
+                    did: "did:web5:bob".to_string(),
+                    handle: None,
+                },
+            }))
+        );
+        let readable = quote("at://did:web5:carol/app.bbs.post/3", "did:web5:carol");
+        assert_eq!(screen_quote(readable.clone(), &hidden, &silenced), readable);
+    }
+}
@@ -0,0 +1,53 @@
+use crate::identity::{CreateDidOpts, DidData, DidMethod, DidSubject, PendingDid};
+use crate::plc::web5_types::get_didoc_from_chain;
+use anyhow::{anyhow, bail, Result};
+
+pub const CKB_METHOD: &str = "ckb";
+
+/// Web5 chain DIDs. The document lives in a DID cell on CKB that only the
+/// wallet can write, so the PDS reads it through the account's address.
+pub struct CkbMethod;
+
+#[rocket::async_trait]
+impl DidMethod for CkbMethod {
+    fn name(&self) -> &'static str {
+        CKB_METHOD
+    }
+
+    async fn create(&self, opts: CreateDidOpts) -> Result<PendingDid> {
+        // The wallet writes the DID cell itself once the account exists
+        match (opts.did, opts.ckb_address) {
+            (Some(did), Some(_)) => Ok(PendingDid { did, plc_op: None }),
+            _ => bail!("A chain DID needs the DID and the CKB address it's bound to"),
+        }
+    }
+
+    async fn publish(&self, _pending: &PendingDid) -> Result<()> {
+        Ok(())
+    }
+
+    async fn update_handle(&self, subject: DidSubject<'_>, handle: &str) -> Result<()> {
+        if self.resolve(subject).await?.handle() != Some(handle) {
+            bail!("Update the DID doc on chain to at://{handle} first")
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, subject: DidSubject<'_>) -> Result<DidData> {
+        let Some(ckb_address) = subject.ckb_address else {
+            bail!("No CKB address bound to {}", subject.did)
+        };
+        let doc = get_didoc_from_chain(ckb_address)
+            .await
+            .map_err(|error| anyhow!("Failed to fetch DID doc from chain: {error:?}"))?;
+        Ok(DidData {
+            signing_key: doc.verification_methods.get("atproto").cloned(),
+            pds_endpoint: doc
+                .services
+                .get("atproto_pds")
+                .map(|service| service.endpoint.clone()),
+            also_known_as: doc.also_known_as,
+            rotation_keys: None,
+        })
+    }
+}
@@ -0,0 +1,193 @@
+use crate::config::ServerConfig;
+use crate::plc::types::Operation;
+use anyhow::{bail, Result};
+use rsky_identity::errors::Error;
+use secp256k1::SecretKey;
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub mod ckb;
+pub mod plc;
+pub mod web;
+
+/// What a DID document says about an account, in the same shape whichever
+/// method it's published with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DidData {
+    pub also_known_as: Vec<String>,
+    /// `did:key` of the atproto signing key
+    pub signing_key: Option<String>,
+    pub pds_endpoint: Option<String>,
+    /// Keys allowed to change the document, for methods that have them
+    pub rotation_keys: Option<Vec<String>>,
+}
+
+impl DidData {
+    pub fn handle(&self) -> Option<&str> {
+        self.also_known_as
+            .iter()
+            .find_map(|aka| aka.strip_prefix("at://"))
+    }
+}
+
+/// An account's DID along with what the PDS knows about where its document is.
+#[derive(Debug, Clone, Copy)]
+pub struct DidSubject<'a> {
+    pub did: &'a str,
+    /// Address the DID cell of a chain DID is looked up by
+    pub ckb_address: Option<&'a str>,
+}
+
+impl<'a> DidSubject<'a> {
+    pub fn new(did: &'a str) -> Self {
+        DidSubject {
+            did,
+            ckb_address: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateDidOpts {
+    /// The DID to use, for methods where the owner publishes the document
+    pub did: Option<String>,
+    pub handle: String,
+    /// `did:key` the repo will be signed with
+    pub signing_key: String,
+    /// Extra rotation key supplied by the user
+    pub recovery_key: Option<String>,
+    pub ckb_address: Option<String>,
+}
+
+/// A DID worked out before its account exists. It's only published once the
+/// repo has been created, so a failed signup leaves nothing in a directory.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PendingDid {
+    pub did: String,
+    /// Genesis operation still to be sent to the PLC directory
+    pub plc_op: Option<Operation>,
+}
+
+/// A way of creating, updating and resolving DIDs for accounts on this PDS.
+#[rocket::async_trait]
+pub trait DidMethod: Send + Sync {
+    /// Method as it appears in the DID, e.g. `plc` for `did:plc:...`
+    fn name(&self) -> &'static str;
+
+    async fn create(&self, opts: CreateDidOpts) -> Result<PendingDid>;
+
+    /// Makes a DID from `create` resolvable.
+    async fn publish(&self, pending: &PendingDid) -> Result<()>;
+
+    /// Points the document at `handle`, or checks that its owner already has
+    /// for methods the PDS can't write to.
+    async fn update_handle(&self, subject: DidSubject<'_>, handle: &str) -> Result<()>;
+
+    async fn resolve(&self, subject: DidSubject<'_>) -> Result<DidData>;
+}
+
+/// Splits the method out of a `did:<method>:<id>` DID.
+pub fn method_name(did: &str) -> Result<&str> {
+    match did.split(':').collect::<Vec<&str>>()[..] {
+        ["did", method, ref id @ ..]
+            if !method.is_empty() && !id.is_empty() && id.iter().all(|part| !part.is_empty()) =>
+        {
+            Ok(method)
+        }
+        _ => bail!(Error::PoorlyFormattedDidError(did.to_string())),
+    }
+}
+
+/// The DID methods this PDS hosts accounts under, so classic atproto accounts
+/// and web5 chain accounts can live side by side.
+pub struct DidMethods {
+    methods: BTreeMap<&'static str, Arc<dyn DidMethod>>,
+}
+
+pub type SharedDidMethods = Arc<DidMethods>;
+
+impl DidMethods {
+    pub fn empty() -> Self {
+        DidMethods {
+            methods: BTreeMap::new(),
+        }
+    }
+
+    pub fn new(cfg: &ServerConfig) -> Self {
+        let rotation_key = env::var("PDS_PLC_ROTATION_KEY_K256_PRIVATE_KEY_HEX")
+            .ok()
+            .and_then(|key| hex::decode(key).ok())
+            .and_then(|key| SecretKey::from_slice(&key).ok());
+        let timeout = Duration::from_millis(cfg.identity.resolver_timeout);
+        let ckb = Arc::new(ckb::CkbMethod);
+        let mut methods = DidMethods::empty();
+        methods.register(Arc::new(plc::PlcMethod::new(
+            cfg.identity.plc_url.clone(),
+            rotation_key,
+            cfg.service.public_url.clone(),
+        )));
+        methods.register(Arc::new(web::WebMethod::new(timeout)));
+        methods.register(ckb.clone());
+        // Chain DIDs are also written did:web5
+        methods.methods.insert("web5", ckb);
+        methods
+    }
+
+    pub fn register(&mut self, method: Arc<dyn DidMethod>) {
+        self.methods.insert(method.name(), method);
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn DidMethod> {
+        match self.methods.get(name) {
+            Some(method) => Ok(method.as_ref()),
+            None => bail!(Error::UnsupportedDidMethodError(name.to_string())),
+        }
+    }
+
+    pub fn for_did(&self, did: &str) -> Result<&dyn DidMethod> {
+        match self.methods.get(method_name(did)?) {
+            Some(method) => Ok(method.as_ref()),
+            None => bail!(Error::UnsupportedDidMethodError(did.to_string())),
+        }
+    }
+
+    /// Whether `did` is one whose document lives on chain.
+    pub fn is_chain_did(&self, did: &str) -> bool {
+        matches!(self.for_did(did), Ok(method) if method.name() == ckb::CKB_METHOD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_method_names() {
+        assert_eq!(method_name("did:plc:abc").unwrap(), "plc");
+        assert_eq!(method_name("did:web:example.com").unwrap(), "web");
+        assert_eq!(method_name("did:ckb:abc").unwrap(), "ckb");
+        assert!(method_name("did:plc").is_err());
+        assert!(method_name("did::abc").is_err());
+        assert!(method_name("did:web:").is_err());
+        assert!(method_name("plc:abc:def").is_err());
+    }
+
+    #[test]
+    fn picks_the_method_for_a_did() {
+        let mut methods = DidMethods::empty();
+        let ckb = Arc::new(ckb::CkbMethod);
+        methods.register(Arc::new(web::WebMethod::new(Duration::from_secs(1))));
+        methods.register(ckb.clone());
+        methods.methods.insert("web5", ckb);
+        assert_eq!(
+            methods.for_did("did:web:example.com").unwrap().name(),
+            "web"
+        );
+        assert_eq!(methods.for_did("did:ckb:abc").unwrap().name(), "ckb");
+        assert!(methods.is_chain_did("did:web5:abc"));
+        assert!(!methods.is_chain_did("did:web:example.com"));
+        assert!(methods.for_did("did:plc:abc").is_err());
+    }
+}
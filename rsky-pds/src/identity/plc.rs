@@ -0,0 +1,98 @@
+use crate::identity::{CreateDidOpts, DidData, DidMethod, DidSubject, PendingDid};
+use crate::plc;
+use crate::plc::operations::{create_op, CreateAtprotoOpInput};
+use crate::plc::types::OpOrTombstone;
+use anyhow::{bail, Result};
+use rsky_crypto::utils::encode_did_key;
+use secp256k1::{Secp256k1, SecretKey};
+
+pub const PLC_METHOD: &str = "plc";
+
+/// did:plc, registered with the PLC directory and rotated by the PDS key.
+pub struct PlcMethod {
+    client: plc::Client,
+    /// Unset on web5-only deployments, which can still resolve did:plc
+    rotation_key: Option<SecretKey>,
+    pds_endpoint: String,
+}
+
+impl PlcMethod {
+    pub fn new(plc_url: String, rotation_key: Option<SecretKey>, pds_endpoint: String) -> Self {
+        PlcMethod {
+            client: plc::Client::new(plc_url),
+            rotation_key,
+            pds_endpoint,
+        }
+    }
+
+    fn rotation_key(&self) -> Result<&SecretKey> {
+        match self.rotation_key {
+            Some(ref rotation_key) => Ok(rotation_key),
+            None => bail!("PDS_PLC_ROTATION_KEY_K256_PRIVATE_KEY_HEX is not configured"),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl DidMethod for PlcMethod {
+    fn name(&self) -> &'static str {
+        PLC_METHOD
+    }
+
+    async fn create(&self, opts: CreateDidOpts) -> Result<PendingDid> {
+        // An account moving in keeps the DID it already has
+        if let Some(did) = opts.did {
+            return Ok(PendingDid { did, plc_op: None });
+        }
+        let rotation_key = self.rotation_key()?;
+        let mut rotation_keys: Vec<String> = opts.recovery_key.into_iter().collect();
+        rotation_keys.push(encode_did_key(&rotation_key.public_key(&Secp256k1::new())));
+        let (did, op) = create_op(
+            CreateAtprotoOpInput {
+                signing_key: opts.signing_key,
+                handle: opts.handle,
+                pds: self.pds_endpoint.clone(),
+                rotation_keys,
+            },
+            *rotation_key,
+        )
+        .await?;
+        Ok(PendingDid {
+            did,
+            plc_op: Some(op),
+        })
+    }
+
+    async fn publish(&self, pending: &PendingDid) -> Result<()> {
+        match pending.plc_op {
+            Some(ref op) => {
+                self.client
+                    .send_operation(&pending.did, &OpOrTombstone::Operation(op.clone()))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn update_handle(&self, subject: DidSubject<'_>, handle: &str) -> Result<()> {
+        self.client
+            .update_handle(&subject.did.to_string(), self.rotation_key()?, handle)
+            .await
+    }
+
+    async fn resolve(&self, subject: DidSubject<'_>) -> Result<DidData> {
+        let data = self
+            .client
+            .get_document_data(&subject.did.to_string())
+            .await?;
+        Ok(DidData {
+            also_known_as: data.also_known_as,
+            signing_key: data.verification_methods.get("atproto").cloned(),
+            pds_endpoint: data
+                .services
+                .get("atproto_pds")
+                .map(|service| service.endpoint.clone()),
+            rotation_keys: Some(data.rotation_keys),
+        })
+    }
+}
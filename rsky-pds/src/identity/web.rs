@@ -0,0 +1,74 @@
+use crate::identity::{CreateDidOpts, DidData, DidMethod, DidSubject, PendingDid};
+use anyhow::{bail, Result};
+use rsky_common::{get_service_endpoint, get_verification_material, GetServiceEndpointOpts};
+use rsky_identity::did::atproto_data::get_did_key_from_multibase;
+use rsky_identity::did::web_resolver::DidWebResolver;
+use rsky_identity::errors::Error;
+use rsky_identity::types::DidDocument;
+use std::time::Duration;
+
+pub const WEB_METHOD: &str = "web";
+
+/// did:web, whose document is served by its owner at
+/// `https://<host>/.well-known/did.json`. The PDS only ever reads it.
+pub struct WebMethod {
+    resolver: DidWebResolver,
+}
+
+impl WebMethod {
+    pub fn new(timeout: Duration) -> Self {
+        WebMethod {
+            resolver: DidWebResolver::new(timeout, None),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl DidMethod for WebMethod {
+    fn name(&self) -> &'static str {
+        WEB_METHOD
+    }
+
+    async fn create(&self, opts: CreateDidOpts) -> Result<PendingDid> {
+        match opts.did {
+            Some(did) => Ok(PendingDid { did, plc_op: None }),
+            None => bail!("A did:web must be created by hosting its document first"),
+        }
+    }
+
+    async fn publish(&self, _pending: &PendingDid) -> Result<()> {
+        Ok(())
+    }
+
+    async fn update_handle(&self, subject: DidSubject<'_>, handle: &str) -> Result<()> {
+        if self.resolve(subject).await?.handle() != Some(handle) {
+            bail!("Add at://{handle} to alsoKnownAs in the did:web document first")
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, subject: DidSubject<'_>) -> Result<DidData> {
+        let did = subject.did.to_string();
+        let Some(doc) = self.resolver.resolve_no_check(did.clone()).await? else {
+            bail!(Error::DidNotFoundError(did))
+        };
+        let doc: DidDocument = serde_json::from_value(doc.clone())
+            .map_err(|_| Error::PoorlyFormattedDidDocumentError(doc))?;
+        let signing_key = match get_verification_material(&doc, "atproto") {
+            Some(key) => get_did_key_from_multibase(key)?,
+            None => None,
+        };
+        Ok(DidData {
+            also_known_as: doc.also_known_as.clone().unwrap_or_default(),
+            signing_key,
+            pds_endpoint: get_service_endpoint(
+                doc,
+                GetServiceEndpointOpts {
+                    id: "#atproto_pds".to_string(),
+                    r#type: Some("AtprotoPersonalDataServer".to_string()),
+                },
+            ),
+            rotation_keys: None,
+        })
+    }
+}
@@ -38,3 +38,44 @@ pub async fn maybe_get_info(bytes: Vec<u8>) -> Result<Option<ImageInfo>> {
         Err(_) => Ok(None),
     }
 }
+
+/// Processed renditions of an image blob, served through getBlob's `variant` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageVariant {
+    Thumbnail,
+    Fullsize,
+}
+
+impl ImageVariant {
+    pub const ALL: [ImageVariant; 2] = [ImageVariant::Thumbnail, ImageVariant::Fullsize];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageVariant::Thumbnail => "thumbnail",
+            ImageVariant::Fullsize => "fullsize",
+        }
+    }
+
+    /// Longest edge of the rendition in pixels, smaller images aren't upscaled
+    pub fn max_dimension(&self) -> u32 {
+        match self {
+            ImageVariant::Thumbnail => 640,
+            ImageVariant::Fullsize => 2000,
+        }
+    }
+}
+
+impl std::str::FromStr for ImageVariant {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "thumbnail" => Ok(ImageVariant::Thumbnail),
+            "fullsize" => Ok(ImageVariant::Fullsize),
+            _ => anyhow::bail!("Unknown image variant: {s}"),
+        }
+    }
+}
+
+#[cfg(feature = "image-processing")]
+pub mod processing;
@@ -0,0 +1,289 @@
+use crate::image::ImageVariant;
+use anyhow::Result;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{guess_format, DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use std::io::Cursor;
+
+const JPEG_QUALITY: u8 = 85;
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+const XMP_APP1_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+#[derive(thiserror::Error, Debug)]
+#[error("Image could not be decoded")]
+pub struct InvalidImageError;
+
+pub struct ProcessedImage {
+    /// The upload with its metadata removed, this is what gets stored and hashed
+    pub bytes: Vec<u8>,
+    pub derivatives: Vec<(ImageVariant, Vec<u8>)>,
+}
+
+/// Validates an uploaded image, strips its EXIF/XMP metadata and renders the
+/// derivatives listed in `ImageVariant`. Returns `None` for blobs that aren't
+/// a still image format we process.
+pub async fn process_image(bytes: Vec<u8>) -> Result<Option<ProcessedImage>> {
+    tokio::task::spawn_blocking(move || process_image_sync(bytes)).await?
+}
+
+fn process_image_sync(bytes: Vec<u8>) -> Result<Option<ProcessedImage>> {
+    let format = match guess_format(&bytes) {
+        Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+        _ => return Ok(None),
+    };
+    let img = ImageReader::with_format(Cursor::new(&bytes), format)
+        .decode()
+        .map_err(|_| InvalidImageError)?;
+
+    let (stripped, orientation) = match format {
+        ImageFormat::Jpeg => strip_jpeg_metadata(&bytes).ok_or(InvalidImageError)?,
+        ImageFormat::Png => (strip_png_metadata(&bytes).ok_or(InvalidImageError)?, None),
+        _ => (strip_webp_metadata(&bytes).ok_or(InvalidImageError)?, None),
+    };
+    let oriented = apply_orientation(img, orientation.unwrap_or(1));
+    // Dropping the orientation tag would show the image rotated, so bake it in
+    let bytes = match orientation {
+        Some(orientation) if orientation != 1 => encode(&oriented)?,
+        _ => stripped,
+    };
+
+    let (width, height) = oriented.dimensions();
+    let mut derivatives = Vec::with_capacity(ImageVariant::ALL.len());
+    for variant in ImageVariant::ALL {
+        let max = variant.max_dimension();
+        let derived = if width > max || height > max {
+            encode(&oriented.resize(max, max, FilterType::Lanczos3))?
+        } else {
+            encode(&oriented)?
+        };
+        derivatives.push((variant, derived));
+    }
+    Ok(Some(ProcessedImage { bytes, derivatives }))
+}
+
+fn encode(img: &DynamicImage) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    if img.color().has_alpha() {
+        img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)?;
+    } else {
+        JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY).encode_image(&img.to_rgb8())?;
+    }
+    Ok(buf)
+}
+
+fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Removes the APP1 Exif and XMP segments from a JPEG without re-encoding it,
+/// returning the EXIF orientation if there was one.
+pub fn strip_jpeg_metadata(bytes: &[u8]) -> Option<(Vec<u8>, Option<u16>)> {
+    if bytes.len() < 4 || bytes[0..2] != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[0..2]);
+    let mut orientation = None;
+    let mut i = 2;
+    while i + 1 < bytes.len() {
+        if bytes[i] != 0xFF {
+            return None;
+        }
+        let marker = bytes[i + 1];
+        match marker {
+            // fill byte
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            // start of scan or end of image, everything after is image data
+            0xDA | 0xD9 => {
+                out.extend_from_slice(&bytes[i..]);
+                return Some((out, orientation));
+            }
+            // standalone markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                out.extend_from_slice(&bytes[i..i + 2]);
+                i += 2;
+                continue;
+            }
+            _ => (),
+        }
+        let len = u16::from_be_bytes([*bytes.get(i + 2)?, *bytes.get(i + 3)?]) as usize;
+        let end = i + 2 + len;
+        if len < 2 || end > bytes.len() {
+            return None;
+        }
+        let payload = &bytes[i + 4..end];
+        if marker == 0xE1 && payload.starts_with(b"Exif\0\0") {
+            orientation = parse_exif_orientation(&payload[6..]);
+        } else if !(marker == 0xE1 && payload.starts_with(XMP_APP1_PREFIX)) {
+            out.extend_from_slice(&bytes[i..end]);
+        }
+        i = end;
+    }
+    None
+}
+
+/// Reads the orientation tag out of the first IFD of a TIFF-structured EXIF block
+pub fn parse_exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        let raw = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(match little_endian {
+            true => u16::from_le_bytes(raw),
+            false => u16::from_be_bytes(raw),
+        })
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        let raw: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(match little_endian {
+            true => u32::from_le_bytes(raw),
+            false => u32::from_be_bytes(raw),
+        })
+    };
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|&entry| read_u16(entry) == Some(EXIF_ORIENTATION_TAG))
+        .and_then(|entry| read_u16(entry + 8))
+}
+
+/// Drops the eXIf and text chunks from a PNG, which is where cameras and
+/// editors put EXIF/XMP data
+pub fn strip_png_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    if bytes.get(0..8)? != SIGNATURE {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&SIGNATURE);
+    let mut i = 8;
+    while i < bytes.len() {
+        let len = u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?) as usize;
+        // length, type, data and crc
+        let end = i + 12 + len;
+        if end > bytes.len() {
+            return None;
+        }
+        match &bytes[i + 4..i + 8] {
+            b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" => (),
+            _ => out.extend_from_slice(&bytes[i..end]),
+        }
+        i = end;
+    }
+    Some(out)
+}
+
+/// Drops the EXIF and XMP chunks from a WebP container and clears the
+/// matching flags in the VP8X header
+pub fn strip_webp_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    const VP8X_EXIF_FLAG: u8 = 0x08;
+    const VP8X_XMP_FLAG: u8 = 0x04;
+    if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[0..12]);
+    let mut i = 12;
+    while i < bytes.len() {
+        let len = u32::from_le_bytes(bytes.get(i + 4..i + 8)?.try_into().ok()?) as usize;
+        // chunks are padded to an even length
+        let end = i + 8 + len + (len & 1);
+        if end > bytes.len() {
+            return None;
+        }
+        match &bytes[i..i + 4] {
+            b"EXIF" | b"XMP " => (),
+            b"VP8X" => {
+                let start = out.len();
+                out.extend_from_slice(&bytes[i..end]);
+                *out.get_mut(start + 8)? &= !(VP8X_EXIF_FLAG | VP8X_XMP_FLAG);
+            }
+            _ => out.extend_from_slice(&bytes[i..end]),
+        }
+        i = end;
+    }
+    let riff_size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exif_app1(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&EXIF_ORIENTATION_TAG.to_be_bytes());
+        tiff.extend_from_slice(&3u16.to_be_bytes());
+        tiff.extend_from_slice(&1u32.to_be_bytes());
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        let mut payload = b"Exif\0\0".to_vec();
+        payload.extend_from_slice(&tiff);
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(&payload);
+        segment
+    }
+
+    fn sample_jpeg() -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(4, 2);
+        encode(&img).unwrap()
+    }
+
+    #[test]
+    fn strips_exif_and_reads_orientation() {
+        let jpeg = sample_jpeg();
+        let mut with_exif = jpeg[0..2].to_vec();
+        with_exif.extend_from_slice(&exif_app1(6));
+        with_exif.extend_from_slice(&jpeg[2..]);
+
+        let (stripped, orientation) = strip_jpeg_metadata(&with_exif).unwrap();
+        assert_eq!(orientation, Some(6));
+        assert_eq!(stripped, jpeg);
+    }
+
+    #[test]
+    fn rotates_when_orientation_is_set() {
+        let jpeg = sample_jpeg();
+        let mut with_exif = jpeg[0..2].to_vec();
+        with_exif.extend_from_slice(&exif_app1(6));
+        with_exif.extend_from_slice(&jpeg[2..]);
+
+        let processed = process_image_sync(with_exif).unwrap().unwrap();
+        let img = image::load_from_memory(&processed.bytes).unwrap();
+        assert_eq!(img.dimensions(), (2, 4));
+        assert_eq!(processed.derivatives.len(), ImageVariant::ALL.len());
+    }
+
+    #[test]
+    fn rejects_undecodable_images() {
+        let mut jpeg = sample_jpeg();
+        jpeg.truncate(20);
+        assert!(process_image_sync(jpeg).is_err());
+    }
+
+    #[test]
+    fn ignores_non_images() {
+        assert!(process_image_sync(b"hello world".to_vec())
+            .unwrap()
+            .is_none());
+    }
+}
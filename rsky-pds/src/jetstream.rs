@@ -0,0 +1,480 @@
+use crate::config::ServerConfig;
+use crate::crawlers::Crawlers;
+use crate::sequencer::events::{
+    AccountEvt, CommitEvt, CommitEvtOpAction, IdentityEvt, SectionEvt, SeqEvt, TypedAccountEvt,
+    TypedCommitEvt, TypedIdentityEvt, TypedSectionEvt,
+};
+use crate::sequencer::outbox::{ConsumerTooSlowError, Outbox, OutboxEvt, OutboxOpts};
+use crate::sequencer::Sequencer;
+use crate::shutdown::close_for_shutdown;
+use crate::SharedSequencer;
+use anyhow::{bail, Result};
+use futures::{pin_mut, StreamExt};
+use lexicon_cid::Cid;
+use rocket::tokio::select;
+use rocket::{Shutdown, State};
+use rsky_common::time::from_str_to_utc;
+use rsky_lexicon::app::bbs::SectionStatus;
+use rsky_lexicon::com::atproto::sync::AccountStatus;
+use rsky_repo::block_map::BlockMap;
+use rsky_repo::car::read_car;
+use rsky_repo::util::cbor_to_lex_record;
+use serde_json::json;
+use std::collections::HashSet;
+use tokio::time::{interval, Duration};
+use ws::Message;
+
+const MAX_WANTED_COLLECTIONS: usize = 100;
+const MAX_WANTED_DIDS: usize = 10_000;
+/// Section status changes are delivered as if they were records in this
+/// collection, so `app.bbs.*` subscribers get them
+pub const SECTION_COLLECTION: &str = "app.bbs.section";
+
+/// Which events a Jetstream subscriber asked for. Empty lists match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JetstreamFilter {
+    collections: HashSet<String>,
+    /// From `app.bbs.*` style entries, stored with the trailing dot
+    collection_prefixes: Vec<String>,
+    dids: HashSet<String>,
+}
+
+impl JetstreamFilter {
+    pub fn new(wanted_collections: Vec<String>, wanted_dids: Vec<String>) -> Result<Self> {
+        if wanted_collections.len() > MAX_WANTED_COLLECTIONS {
+            bail!("Too many wantedCollections, the limit is {MAX_WANTED_COLLECTIONS}");
+        }
+        if wanted_dids.len() > MAX_WANTED_DIDS {
+            bail!("Too many wantedDids, the limit is {MAX_WANTED_DIDS}");
+        }
+        let mut filter = JetstreamFilter::default();
+        for collection in wanted_collections {
+            if let Some(prefix) = collection.strip_suffix(".*") {
+                if !prefix.is_empty() && !prefix.contains('*') {
+                    filter.collection_prefixes.push(format!("{prefix}."));
+                    continue;
+                }
+            }
+            if collection.is_empty() || collection.contains('*') {
+                bail!("Invalid wantedCollections entry: {collection}");
+            }
+            filter.collections.insert(collection);
+        }
+        for did in wanted_dids {
+            if !did.starts_with("did:") {
+                bail!("Invalid wantedDids entry: {did}");
+            }
+            filter.dids.insert(did);
+        }
+        Ok(filter)
+    }
+
+    pub fn wants_did(&self, did: &str) -> bool {
+        self.dids.is_empty() || self.dids.contains(did)
+    }
+
+    pub fn wants_collection(&self, collection: &str) -> bool {
+        (self.collections.is_empty() && self.collection_prefixes.is_empty())
+            || self.collections.contains(collection)
+            || self
+                .collection_prefixes
+                .iter()
+                .any(|prefix| collection.starts_with(prefix))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JetstreamEvt {
+    pub did: String,
+    pub time_us: i64,
+    /// Firehose seq of the event, pass it back as `cursor` to resume
+    pub seq: i64,
+    #[serde(flatten)]
+    pub kind: JetstreamKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum JetstreamKind {
+    Commit { commit: JetstreamCommit },
+    Identity { identity: JetstreamIdentity },
+    Account { account: JetstreamAccount },
+    Section { section: JetstreamSection },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JetstreamCommit {
+    pub rev: String,
+    pub operation: CommitEvtOpAction,
+    pub collection: String,
+    pub rkey: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JetstreamIdentity {
+    pub did: String,
+    pub handle: Option<String>,
+    pub seq: i64,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JetstreamAccount {
+    pub did: String,
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<AccountStatus>,
+    pub seq: i64,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JetstreamSection {
+    pub section_id: i64,
+    pub status: SectionStatus,
+    pub seq: i64,
+    pub time: String,
+}
+
+/// Turns a sequenced event into the Jetstream events a subscriber wants, one
+/// per matching commit op with the record decoded to JSON.
+pub async fn to_jetstream_evts(evt: SeqEvt, filter: &JetstreamFilter) -> Result<Vec<JetstreamEvt>> {
+    match evt {
+        SeqEvt::TypedCommitEvt(TypedCommitEvt { seq, time, evt, .. }) => {
+            let CommitEvt {
+                repo,
+                rev,
+                blocks,
+                ops,
+                ..
+            } = evt;
+            if !filter.wants_did(&repo) {
+                return Ok(vec![]);
+            }
+            let time_us = from_str_to_utc(&time).timestamp_micros();
+            let mut block_map: Option<BlockMap> = None;
+            let mut evts = Vec::new();
+            for op in ops {
+                let (collection, rkey) = match op.path.split_once('/') {
+                    Some((collection, rkey)) => (collection.to_string(), rkey.to_string()),
+                    None => continue,
+                };
+                if !filter.wants_collection(&collection) {
+                    continue;
+                }
+                let record = match (&op.action, op.cid) {
+                    (CommitEvtOpAction::Delete, _) | (_, None) => None,
+                    (_, Some(cid)) => {
+                        // tooBig commits are sequenced without their blocks
+                        if block_map.is_none() && !blocks.is_empty() {
+                            block_map = Some(read_car(blocks.clone()).await?.blocks);
+                        }
+                        decode_record(block_map.as_ref(), &cid)?
+                    }
+                };
+                evts.push(JetstreamEvt {
+                    did: repo.clone(),
+                    time_us,
+                    seq,
+                    kind: JetstreamKind::Commit {
+                        commit: JetstreamCommit {
+                            rev: rev.clone(),
+                            operation: op.action,
+                            collection,
+                            rkey,
+                            record,
+                            cid: op.cid.map(|cid| cid.to_string()),
+                        },
+                    },
+                });
+            }
+            Ok(evts)
+        }
+        SeqEvt::TypedIdentityEvt(TypedIdentityEvt { seq, time, evt, .. }) => {
+            let IdentityEvt { did, handle } = evt;
+            if !filter.wants_did(&did) {
+                return Ok(vec![]);
+            }
+            Ok(vec![JetstreamEvt {
+                did: did.clone(),
+                time_us: from_str_to_utc(&time).timestamp_micros(),
+                seq,
+                kind: JetstreamKind::Identity {
+                    identity: JetstreamIdentity {
+                        did,
+                        handle,
+                        seq,
+                        time,
+                    },
+                },
+            }])
+        }
+        SeqEvt::TypedAccountEvt(TypedAccountEvt { seq, time, evt, .. }) => {
+            let AccountEvt {
+                did,
+                active,
+                status,
+            } = evt;
+            if !filter.wants_did(&did) {
+                return Ok(vec![]);
+            }
+            Ok(vec![JetstreamEvt {
+                did: did.clone(),
+                time_us: from_str_to_utc(&time).timestamp_micros(),
+                seq,
+                kind: JetstreamKind::Account {
+                    account: JetstreamAccount {
+                        did,
+                        active,
+                        status,
+                        seq,
+                        time,
+                    },
+                },
+            }])
+        }
+        SeqEvt::TypedSectionEvt(TypedSectionEvt { seq, time, evt, .. }) => {
+            let SectionEvt {
+                did,
+                section_id,
+                status,
+            } = evt;
+            // not filtered by did, the moderator who made the change isn't what
+            // subscribers are interested in
+            if !filter.wants_collection(SECTION_COLLECTION) {
+                return Ok(vec![]);
+            }
+            Ok(vec![JetstreamEvt {
+                did,
+                time_us: from_str_to_utc(&time).timestamp_micros(),
+                seq,
+                kind: JetstreamKind::Section {
+                    section: JetstreamSection {
+                        section_id,
+                        status,
+                        seq,
+                        time,
+                    },
+                },
+            }])
+        }
+        // sync events only carry a commit block, there's nothing to decode for consumers
+        SeqEvt::TypedSyncEvt(_) => Ok(vec![]),
+    }
+}
+
+fn decode_record(blocks: Option<&BlockMap>, cid: &Cid) -> Result<Option<serde_json::Value>> {
+    match blocks.and_then(|blocks| blocks.get(*cid)) {
+        None => Ok(None),
+        Some(bytes) => {
+            let record = cbor_to_lex_record(bytes.clone())?;
+            Ok(Some(serde_json::to_value(record)?))
+        }
+    }
+}
+
+fn error_message(error: &str, message: String) -> Message {
+    Message::Text(json!({ "error": error, "message": message }).to_string())
+}
+
+/// Jetstream-style event stream: the same events as subscribeRepos, but as
+/// JSON text frames with records already decoded, filtered by collection
+/// (exact NSIDs or `app.bbs.*` prefixes) and repo DID. `cursor` is a firehose
+/// seq rather than a timestamp.
+#[rocket::get("/subscribe?<wantedCollections>&<wantedDids>&<cursor>")]
+#[allow(non_snake_case)]
+pub async fn subscribe<'a>(
+    wantedCollections: Vec<String>,
+    wantedDids: Vec<String>,
+    cursor: Option<i64>,
+    cfg: &'a State<ServerConfig>,
+    shared_sequencer: &'a State<SharedSequencer>,
+    mut shutdown: Shutdown,
+    ws: ws::WebSocket,
+) -> ws::Stream!['a] {
+    ws::Stream! { ws =>
+        let filter = match JetstreamFilter::new(wantedCollections, wantedDids) {
+            Ok(filter) => filter,
+            Err(error) => {
+                yield error_message("InvalidRequest", error.to_string());
+                return;
+            }
+        };
+        let store = shared_sequencer.sequencer.read().await.store.clone();
+        let sequencer = Sequencer::new(
+            Crawlers::new(
+                cfg.service.hostname.clone(),
+                cfg.crawlers.clone(),
+                cfg.crawl_notify.clone(),
+            ),
+            store,
+            None,
+        );
+        if let Some(cursor) = cursor {
+            match sequencer.curr().await {
+                Ok(curr) if cursor > curr.unwrap_or(0) => {
+                    yield error_message("FutureCursor", "Cursor in the future.".to_string());
+                    return;
+                },
+                Ok(_) => (),
+                Err(_) => {
+                    yield error_message("CurrError", "Failed to fetch current event.".to_string());
+                    return;
+                }
+            }
+        }
+        let mut outbox = Outbox::new(
+            sequencer,
+            Some(OutboxOpts {
+                max_buffer_size: cfg.subscription.max_buffer as usize,
+                backpressure: cfg.subscription.backpressure,
+            })
+        );
+
+        let event_stream = outbox.events(cursor).await;
+        pin_mut!(ws);
+        pin_mut!(event_stream);
+
+        let mut ping_interval = interval(Duration::from_secs(30));
+
+        loop {
+            select! {
+                evt = event_stream.next() => {
+                    let evt = match evt {
+                        Some(Ok(OutboxEvt::Evt(evt))) => evt,
+                        Some(Ok(OutboxEvt::Dropped { count, last_seen })) => {
+                            yield Message::Text(json!({
+                                "kind": "info",
+                                "info": {
+                                    "name": "ConsumerTooSlow",
+                                    "message": format!("Skipped {count} events after seq {last_seen}, reconnect with that cursor to backfill them."),
+                                }
+                            }).to_string());
+                            continue;
+                        },
+                        Some(Err(err)) => {
+                            let error = match err.is::<ConsumerTooSlowError>() {
+                                true => "ConsumerTooSlow",
+                                false => "EventStreamError",
+                            };
+                            yield error_message(error, err.to_string());
+                            break;
+                        },
+                        None => {
+                            yield error_message("EventStreamError", "Failed to fetch event from stream.".to_string());
+                            break;
+                        }
+                    };
+                    let seq = evt.seq();
+                    match to_jetstream_evts(evt, &filter).await {
+                        Ok(evts) => {
+                            for evt in evts {
+                                match serde_json::to_string(&evt) {
+                                    Ok(text) => {
+                                        yield Message::Text(text);
+                                    },
+                                    Err(error) => tracing::warn!("Jetstream: failed to serialize event {seq}: {error}")
+                                }
+                            }
+                        },
+                        Err(error) => tracing::warn!("Jetstream: failed to decode event {seq}: {error}")
+                    }
+                },
+                message = ws.next() => {
+                    match message {
+                        Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(ws::Message::Ping(payload))) => {
+                            yield ws::Message::Pong(payload);
+                        },
+                        Some(Ok(_)) => (),
+                    }
+                },
+                _ = ping_interval.tick() => {
+                    yield ws::Message::Ping(vec![]);
+                },
+                _ = &mut shutdown => {
+                    yield close_for_shutdown();
+                    break
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_collection_prefixes() {
+        let filter = JetstreamFilter::new(
+            vec!["app.bbs.*".to_string(), "app.bsky.feed.post".to_string()],
+            vec![],
+        )
+        .unwrap();
+        assert!(filter.wants_collection("app.bbs.post"));
+        assert!(filter.wants_collection("app.bbs.section.comment"));
+        assert!(filter.wants_collection("app.bsky.feed.post"));
+        assert!(!filter.wants_collection("app.bbsx.post"));
+        assert!(!filter.wants_collection("app.bsky.feed.like"));
+        assert!(filter.wants_did("did:web:anyone"));
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = JetstreamFilter::new(vec![], vec![]).unwrap();
+        assert!(filter.wants_collection("app.bbs.post"));
+        assert!(filter.wants_did("did:ckb:abc"));
+    }
+
+    #[test]
+    fn filters_dids() {
+        let filter = JetstreamFilter::new(vec![], vec!["did:ckb:abc".to_string()]).unwrap();
+        assert!(filter.wants_did("did:ckb:abc"));
+        assert!(!filter.wants_did("did:ckb:def"));
+    }
+
+    #[tokio::test]
+    async fn section_events_follow_the_collection_filter() {
+        let evt = SeqEvt::TypedSectionEvt(TypedSectionEvt {
+            r#type: "section".to_string(),
+            seq: 7,
+            time: "2025-01-01T00:00:00.000Z".to_string(),
+            evt: SectionEvt {
+                did: "did:ckb:mod".to_string(),
+                section_id: 3,
+                status: SectionStatus::Archived,
+            },
+        });
+        let bbs = JetstreamFilter::new(
+            vec!["app.bbs.*".to_string()],
+            vec!["did:ckb:abc".to_string()],
+        )
+        .unwrap();
+        let evts = to_jetstream_evts(evt.clone(), &bbs).await.unwrap();
+        assert_eq!(evts.len(), 1);
+        let json = serde_json::to_value(&evts[0]).unwrap();
+        assert_eq!(json["kind"], "section");
+        assert_eq!(json["section"]["sectionId"], 3);
+        assert_eq!(json["section"]["status"], "archived");
+
+        let posts_only =
+            JetstreamFilter::new(vec!["app.bsky.feed.post".to_string()], vec![]).unwrap();
+        assert!(to_jetstream_evts(evt, &posts_only)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        assert!(JetstreamFilter::new(vec!["*".to_string()], vec![]).is_err());
+        assert!(JetstreamFilter::new(vec!["app.*.post".to_string()], vec![]).is_err());
+        assert!(JetstreamFilter::new(vec![], vec!["abc".to_string()]).is_err());
+    }
+}
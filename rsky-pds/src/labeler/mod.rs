@@ -0,0 +1,276 @@
+use crate::db::DbConn;
+use crate::models;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use rsky_common::sign::sign_without_indexmap;
+use rsky_common::time::from_str_to_utc;
+use rsky_common::RFC3339_VARIANT;
+use rsky_crypto::utils::encode_did_key;
+use rsky_lexicon::com::atproto::admin::CreateLabel;
+use rsky_lexicon::com::atproto::label::Label;
+use secp256k1::{Keypair, Secp256k1, SecretKey};
+use std::env;
+
+pub const LABEL_VERSION: u8 = 1;
+/// Labels are signed with a key of their own rather than the repo key, so
+/// either can be rotated without the other. Consumers find it as the
+/// `#atproto_label` verification method of the service DID.
+pub const SIGNING_KEY_VAR: &str = "PDS_LABELER_SIGNING_KEY_K256_PRIVATE_KEY_HEX";
+const MAX_VAL_LENGTH: usize = 128;
+
+/// The label fields covered by the signature. Fields are declared in DAG-CBOR
+/// key order and `neg` is only present when true, matching other labelers.
+#[derive(Debug, Serialize)]
+struct UnsignedLabel<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cid: Option<&'a String>,
+    cts: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    neg: Option<bool>,
+    src: &'a String,
+    uri: &'a String,
+    val: &'a String,
+    ver: u8,
+}
+
+fn signing_key() -> Result<SecretKey> {
+    let private_key =
+        env::var(SIGNING_KEY_VAR).with_context(|| format!("{SIGNING_KEY_VAR} is not set"))?;
+    Ok(SecretKey::from_slice(&hex::decode(
+        private_key.as_bytes(),
+    )?)?)
+}
+
+/// The label signing key as a did:key, for the service's DID document
+pub fn public_signing_key() -> Result<String> {
+    let keypair = Keypair::from_secret_key(&Secp256k1::new(), &signing_key()?);
+    Ok(encode_did_key(&keypair.public_key()))
+}
+
+/// Timestamps are stored with millisecond precision, truncate before signing
+/// so the label reads back exactly as it was signed.
+fn now_millis() -> DateTime<Utc> {
+    from_str_to_utc(&rsky_common::now())
+}
+
+fn sign_label(key: &SecretKey, unsigned: &UnsignedLabel) -> Result<Vec<u8>> {
+    Ok(sign_without_indexmap(unsigned, key)?.to_vec())
+}
+
+pub fn format_label(row: models::Label) -> Label {
+    Label {
+        ver: Some(LABEL_VERSION),
+        src: row.src,
+        uri: row.uri,
+        cid: row.cid,
+        val: row.val,
+        neg: row.neg.then_some(true),
+        cts: from_str_to_utc(&row.cts),
+        exp: row.exp.map(|exp| from_str_to_utc(&exp)),
+        sig: Some(row.sig),
+    }
+}
+
+pub fn validate_label(label: &CreateLabel) -> Result<()> {
+    if !label.uri.starts_with("at://") && !label.uri.starts_with("did:") {
+        bail!("Label subject must be an at:// uri or a did: {}", label.uri);
+    }
+    if label.val.is_empty() || label.val.len() > MAX_VAL_LENGTH {
+        bail!("Label value must be 1 to {MAX_VAL_LENGTH} characters");
+    }
+    Ok(())
+}
+
+/// Signs and stores labels issued by `src` (this service's DID), e.g. from
+/// the BBS moderation queue. Subscribers of subscribeLabels pick them up on
+/// their next poll.
+pub async fn create_labels(
+    src: String,
+    labels: Vec<CreateLabel>,
+    db: &DbConn,
+) -> Result<Vec<Label>> {
+    db.run(move |conn| insert_labels(conn, src, labels)).await
+}
+
+/// [`create_labels`] on a connection of its own, for background jobs
+pub fn insert_labels(
+    conn: &mut PgConnection,
+    src: String,
+    labels: Vec<CreateLabel>,
+) -> Result<Vec<Label>> {
+    use crate::schema::pds::label::dsl as LabelSchema;
+
+    for label in labels.iter() {
+        validate_label(label)?;
+    }
+    let key = signing_key()?;
+    let cts = now_millis();
+    let mut rows = Vec::with_capacity(labels.len());
+    for label in labels {
+        let CreateLabel {
+            uri,
+            cid,
+            val,
+            neg,
+            exp,
+        } = label;
+        let neg = neg.unwrap_or(false);
+        let exp = exp.map(|exp| format!("{}", exp.format(RFC3339_VARIANT)));
+        let sig = sign_label(
+            &key,
+            &UnsignedLabel {
+                cid: cid.as_ref(),
+                cts,
+                exp: exp.as_ref().map(from_str_to_utc),
+                neg: neg.then_some(true),
+                src: &src,
+                uri: &uri,
+                val: &val,
+                ver: LABEL_VERSION,
+            },
+        )?;
+        rows.push((
+            LabelSchema::src.eq(src.clone()),
+            LabelSchema::uri.eq(uri),
+            LabelSchema::cid.eq(cid),
+            LabelSchema::val.eq(val),
+            LabelSchema::neg.eq(neg),
+            LabelSchema::cts.eq(format!("{}", cts.format(RFC3339_VARIANT))),
+            LabelSchema::exp.eq(exp),
+            LabelSchema::sig.eq(sig),
+        ));
+    }
+    let created = insert_into(LabelSchema::label)
+        .values(rows)
+        .returning(models::Label::as_returning())
+        .get_results::<models::Label>(conn)?;
+    Ok(created.into_iter().map(format_label).collect())
+}
+
+pub struct QueryLabelsOpts {
+    /// Exact uris, or prefixes ending in `*`
+    pub uri_patterns: Vec<String>,
+    pub sources: Vec<String>,
+    pub limit: i64,
+    pub cursor: Option<i64>,
+}
+
+pub async fn query_labels(opts: QueryLabelsOpts, db: &DbConn) -> Result<Vec<models::Label>> {
+    use crate::schema::pds::label::dsl as LabelSchema;
+
+    let QueryLabelsOpts {
+        uri_patterns,
+        sources,
+        limit,
+        cursor,
+    } = opts;
+    let now = rsky_common::now();
+    Ok(db
+        .run(move |conn| {
+            let mut builder = LabelSchema::label
+                .select(models::Label::as_select())
+                .filter(LabelSchema::exp.is_null().or(LabelSchema::exp.gt(now)))
+                .order(LabelSchema::seq.asc())
+                .limit(limit)
+                .into_boxed();
+            if !uri_patterns.iter().any(|pattern| pattern == "*") {
+                let mut uris: Box<dyn BoxableExpression<LabelSchema::label, Pg, SqlType = Bool>> =
+                    Box::new(LabelSchema::uri.eq_any(Vec::<String>::new()));
+                for pattern in uri_patterns {
+                    uris = match pattern.strip_suffix('*') {
+                        Some(prefix) => {
+                            let prefix = prefix.replace('%', "\\%").replace('_', "\\_");
+                            Box::new(uris.or(LabelSchema::uri.like(format!("{prefix}%"))))
+                        }
+                        None => Box::new(uris.or(LabelSchema::uri.eq(pattern))),
+                    };
+                }
+                builder = builder.filter(uris);
+            }
+            if !sources.is_empty() {
+                builder = builder.filter(LabelSchema::src.eq_any(sources));
+            }
+            if let Some(cursor) = cursor {
+                builder = builder.filter(LabelSchema::seq.gt(cursor));
+            }
+            builder.load::<models::Label>(conn)
+        })
+        .await?)
+}
+
+/// Labels issued after `cursor`, for subscribeLabels. Negations and expired
+/// labels are included, consumers apply them in order.
+pub fn labels_after(
+    conn: &mut PgConnection,
+    cursor: i64,
+    limit: i64,
+) -> Result<Vec<models::Label>> {
+    use crate::schema::pds::label::dsl as LabelSchema;
+
+    Ok(LabelSchema::label
+        .filter(LabelSchema::seq.gt(cursor))
+        .order(LabelSchema::seq.asc())
+        .limit(limit)
+        .select(models::Label::as_select())
+        .load::<models::Label>(conn)?)
+}
+
+pub fn latest_label_seq(conn: &mut PgConnection) -> Result<Option<i64>> {
+    use crate::schema::pds::label::dsl as LabelSchema;
+
+    Ok(LabelSchema::label
+        .select(diesel::dsl::max(LabelSchema::seq))
+        .first::<Option<i64>>(conn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipld_core::ipld::Ipld;
+
+    #[test]
+    fn unsigned_label_is_canonical_dag_cbor() {
+        let cid = "bafyreidfayvfuwqa7qlnopdjiqrxzs6blmoeu4rujcjtnci5beludirz2a".to_string();
+        let src = "did:web:pds.example".to_string();
+        let uri = "at://did:ckb:abc/app.bbs.post/3l".to_string();
+        let val = "spam".to_string();
+        let unsigned = UnsignedLabel {
+            cid: Some(&cid),
+            cts: now_millis(),
+            exp: Some(now_millis()),
+            neg: Some(true),
+            src: &src,
+            uri: &uri,
+            val: &val,
+            ver: LABEL_VERSION,
+        };
+        // decoding sorts the map, so re-encoding only matches if the struct
+        // was already in canonical key order
+        let bytes = serde_ipld_dagcbor::to_vec(&unsigned).unwrap();
+        let decoded: Ipld = serde_ipld_dagcbor::from_slice(&bytes).unwrap();
+        assert_eq!(serde_ipld_dagcbor::to_vec(&decoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn rejects_invalid_subjects() {
+        let label = CreateLabel {
+            uri: "https://example.com".to_string(),
+            cid: None,
+            val: "spam".to_string(),
+            neg: None,
+            exp: None,
+        };
+        assert!(validate_label(&label).is_err());
+        assert!(validate_label(&CreateLabel {
+            uri: "did:ckb:abc".to_string(),
+            ..label
+        })
+        .is_ok());
+    }
+}
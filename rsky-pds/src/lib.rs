@@ -8,32 +8,77 @@ use crate::sequencer::Sequencer;
 use atrium_xrpc_client::reqwest::ReqwestClient;
 use event_emitter_rs::EventEmitter;
 use lazy_static::lazy_static;
+pub mod account_events;
 pub mod account_manager;
 pub mod actor_store;
 pub mod apis;
 pub mod auth_verifier;
+pub mod bbs;
+pub mod check_config;
+pub mod cluster;
 pub mod config;
 pub mod context;
+pub mod cors;
 pub mod crawlers;
 pub mod db;
+pub mod flags;
 pub mod handle;
+pub mod hydration;
+pub mod identity;
 pub mod image;
+pub mod jetstream;
+pub mod labeler;
 pub mod lexicon;
 pub mod mailer;
+pub mod metrics;
 pub mod models;
+pub mod oauth;
 pub mod pipethrough;
 pub mod plc;
+pub mod rate_limit;
 pub mod read_after_write;
+pub mod readiness;
 pub mod repo;
+pub mod request_log;
+pub mod scheduled_writes;
 pub mod schema;
 pub mod sequencer;
+pub mod shutdown;
+pub mod telemetry;
+pub mod webhooks;
 pub mod well_known;
 pub mod xrpc_server;
+use crate::account_events::AccountEventDispatcher;
+use crate::account_manager::helpers::jwt_keys::{reload_jwt_keys, JwtKeyReloader};
+use crate::account_manager::suspension::SuspensionReaper;
 use crate::account_manager::{AccountManager, SharedAccountManager};
-use crate::config::env_to_cfg;
+use crate::actor_store::blob::gc::BlobGarbageCollector;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::actor_store::record::tombstone::TombstonePurger;
+use crate::actor_store::write_lock::REPO_WRITE_LOCKS;
+use crate::bbs::bridge::CrosspostBridgeFairing;
+use crate::bbs::classify::ContentScreener;
+use crate::bbs::read_state::ReadStateCompactor;
+use crate::bbs::stats::StatsAggregator;
+use crate::bbs::trending::TrendingRanker;
+use crate::bbs::unfurl::LinkUnfurler;
+use crate::cluster::{run_elected, run_once_elected, ClusterNodeFairing};
+use crate::config::{env_to_cfg, BlobstoreConfig};
+use crate::cors::CorsFairing;
 use crate::crawlers::Crawlers;
+use crate::db::replica::{ReplicaConn, ReplicaMonitor};
 use crate::db::DbConn;
+use crate::flags::{FlagStore, FlagsWatcher};
+use crate::handle::resolver::HandleCache;
+use crate::identity::{DidMethods, SharedDidMethods};
+use crate::mailer::notifications::EmailNotifier;
+use crate::metrics::MetricsFairing;
 use crate::models::{ErrorCode, ErrorMessageResponse, ServerVersion};
+use crate::rate_limit::{RateLimitFairing, RateLimiter};
+use crate::request_log::RequestLogFairing;
+use crate::scheduled_writes::ScheduledWritesFairing;
+use crate::shutdown::{ShutdownCoordinator, ShutdownFairing};
+use crate::webhooks::WebhookDispatcher;
 use diesel::prelude::*;
 use rocket::{catch, catchers, get, options, routes, Build, Rocket};
 
@@ -51,6 +96,7 @@ pub struct SharedSequencer {
 
 pub struct SharedIdResolver {
     pub id_resolver: RwLock<IdResolver>,
+    pub handle_cache: HandleCache,
 }
 
 pub struct SharedLocalViewer {
@@ -74,25 +120,23 @@ use atrium_xrpc_client::reqwest::ReqwestClientBuilder;
 use diesel::sql_types::Int4;
 use dotenvy::dotenv;
 use rocket::data::{Limits, ToByteUnit};
-use rocket::fairing::{Fairing, Info, Kind};
 use rocket::figment::{
     util::map,
     value::{Map, Value},
 };
-use rocket::http::Header;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::shield::{NoSniff, Shield};
-use rocket::{Request, Response};
+use rocket::Request;
 use rsky_common::env::env_list;
+use rsky_common::time::MINUTE;
 use rsky_identity::types::{DidCache, IdentityResolverOpts};
 use rsky_identity::IdResolver;
 use std::env;
+use std::sync::Arc;
 use tokio::sync::RwLock;
 
-pub struct CORS;
-
 #[get("/")]
 async fn index() -> &'static str {
     r#"
@@ -156,10 +200,10 @@ async fn health(
 
 #[tracing::instrument(skip_all)]
 #[catch(default)]
-async fn default_catcher(_status: Status, request: &Request<'_>) -> ApiError {
+async fn default_catcher(status: Status, request: &Request<'_>) -> ApiError {
     let api_error: &Option<ApiError> = request.local_cache(|| None);
     match api_error {
-        None => ApiError::RuntimeError,
+        None => ApiError::from_status(status),
         Some(error) => error.clone(),
     }
 }
@@ -170,77 +214,166 @@ async fn all_options() {
     /* Intentionally left empty */
 }
 
-#[rocket::async_trait]
-impl Fairing for CORS {
-    fn info(&self) -> Info {
-        Info {
-            name: "Add CORS headers to responses",
-            kind: Kind::Response,
-        }
-    }
-
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "POST, GET, PATCH, OPTIONS, DELETE",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
-    }
-}
-
 pub struct RocketConfig {
     pub db_url: String,
+    /// Overrides the blob store backend from the environment, e.g. for tests
+    pub blobstore: Option<BlobstoreConfig>,
 }
 
 pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
     dotenv().ok();
 
-    let db_url = if let Some(cfg) = cfg {
-        cfg.db_url
+    let (db_url, blobstore_override) = if let Some(cfg) = cfg {
+        (cfg.db_url, cfg.blobstore)
     } else {
-        env::var("DATABASE_URL").unwrap_or("".into())
+        (env::var("DATABASE_URL").unwrap_or("".into()), None)
     };
 
+    let mut cfg = env_to_cfg();
+    if let Some(blobstore) = blobstore_override {
+        cfg.blobstore = blobstore;
+    }
+
     let db: Map<_, Value> = map! {
         "url" => db_url.into(),
-        "pool_size" => 20.into(),
-        "timeout" => 30.into(),
+        "pool_size" => cfg.database.pool_size.into(),
+        "timeout" => cfg.database.pool_timeout_secs.into(),
     };
-
+    let mut databases = map!["pg_db" => db];
+    if let Some(ref replica) = cfg.database.replica {
+        let replica_db: Map<_, Value> = map! {
+            "url" => replica.url.clone().into(),
+            "pool_size" => replica.pool_size.into(),
+            "timeout" => cfg.database.pool_timeout_secs.into(),
+        };
+        databases.insert("pg_replica", replica_db);
+    }
     let figment = rocket::Config::figment()
-        .merge(("databases", map!["pg_db" => db]))
-        .merge(("limits", Limits::default().limit("file", 100.mebibytes())));
-    let cfg = env_to_cfg();
+        .merge(("databases", databases))
+        .merge(("limits", Limits::default().limit("file", 100.mebibytes())))
+        // Leave connections open for as long as writes are allowed to drain
+        .merge((
+            "shutdown.grace",
+            cfg.shutdown.drain_timeout_ms.div_ceil(1000).max(2),
+        ));
+    // Rocket takes the client ip from X-Real-IP by default, which any client can send
+    let figment = match cfg.service.trusted_proxy_header {
+        Some(ref header) => figment.merge(("ip_header", header.clone())),
+        None => figment.merge(("ip_header", false)),
+    };
 
+    metrics::init();
+
+    REPO_WRITE_LOCKS
+        .use_cluster_locks(&cfg.cluster)
+        .await
+        .expect("Failed to set up repo write locks");
+    let sequencer_store = sequencer::store::open(&cfg.sequencer)
+        .await
+        .expect("Failed to open sequencer store");
     let sequencer = SharedSequencer {
         sequencer: RwLock::new(Sequencer::new(
-            Crawlers::new(cfg.service.hostname.clone(), cfg.crawlers.clone()),
+            Crawlers::new(
+                cfg.service.hostname.clone(),
+                cfg.crawlers.clone(),
+                cfg.crawl_notify.clone(),
+            ),
+            sequencer_store,
             None,
         )),
     };
     let mut background_sequencer = sequencer.sequencer.write().await.clone();
     tokio::spawn(async move { background_sequencer.start().await });
+    // so relays find a new PDS before its first write
+    let crawlers = Crawlers::new(
+        cfg.service.hostname.clone(),
+        cfg.crawlers.clone(),
+        cfg.crawl_notify.clone(),
+    );
+    tokio::spawn(async move {
+        run_once_elected("crawl-request", async {
+            crawlers.request_crawl().await;
+        })
+        .await
+    });
+
+    let stats_aggregator = StatsAggregator::new(cfg.bbs.stats_interval_ms);
+    tokio::spawn(async move { run_elected("bbs-stats", || stats_aggregator.start()).await });
+    let trending_ranker = TrendingRanker::new(cfg.bbs.trending_interval_ms);
+    tokio::spawn(async move { run_elected("bbs-trending", || trending_ranker.start()).await });
+    let read_state_compactor = ReadStateCompactor::new(
+        cfg.bbs.read_state_max_age_ms,
+        cfg.bbs.read_state_compact_interval_ms,
+    );
+    tokio::spawn(
+        async move { run_elected("bbs-read-state", || read_state_compactor.start()).await },
+    );
+    if cfg.unfurl.enabled {
+        let link_unfurler = LinkUnfurler::new(&cfg.unfurl);
+        tokio::spawn(async move { run_elected("link-unfurler", || link_unfurler.start()).await });
+    }
 
-    let credentials = aws_sdk_s3::config::Credentials::new(
-        env::var("AWS_ACCESS_KEY_ID").unwrap_or("test".to_owned()), // Access Key ID
-        env::var("AWS_SECRET_ACCESS_KEY").unwrap_or("test".to_owned()), // Secret Access Key
-        None,                                                       // Session Token
-        None,                                                       // Expires
-        "localstack",
+    let blob_store = SharedBlobStore::new(&cfg.blobstore);
+    if cfg.blob_gc.enabled {
+        let blob_gc = BlobGarbageCollector::new(
+            blob_store.clone(),
+            cfg.blob_gc.interval_ms,
+            cfg.blob_gc.grace_period_ms,
+            cfg.blob_gc.min_orphan_age_ms,
+            cfg.blob_gc.batch_size,
+        );
+        tokio::spawn(async move { run_elected("blob-gc", || blob_gc.start()).await });
+    }
+
+    let tombstone_purger = TombstonePurger::new(
+        cfg.tombstones.retention_ms,
+        cfg.tombstones.purge_interval_ms,
+        cfg.tombstones.batch_size,
     );
+    tokio::spawn(async move { run_elected("tombstone-purger", || tombstone_purger.start()).await });
+
+    let suspension_reaper =
+        SuspensionReaper::new(sequencer.sequencer.read().await.clone(), MINUTE as u64);
+    tokio::spawn(async move {
+        run_elected("suspension-reaper", || {
+            let mut reaper = suspension_reaper.clone();
+            async move { reaper.start().await }
+        })
+        .await
+    });
+
+    mailer::init(&cfg.email)
+        .await
+        .expect("Failed to set up email provider");
+    if cfg.email.notifications.enabled {
+        let notifier = EmailNotifier::new(
+            sequencer.sequencer.read().await.clone(),
+            &cfg.email.notifications,
+        );
+        tokio::spawn(async move { run_elected("email-notifier", || notifier.start()).await });
+    }
+
+    if cfg.classifier.enabled {
+        let screener = ContentScreener::new(
+            sequencer.sequencer.read().await.clone(),
+            cfg.service.did.clone(),
+            &cfg.classifier,
+        )
+        .expect("Failed to build classifier http client");
+        tokio::spawn(async move { run_elected("content-screener", || screener.start()).await });
+    }
+
+    if cfg.webhooks.enabled {
+        let webhooks =
+            WebhookDispatcher::new(sequencer.sequencer.read().await.clone(), &cfg.webhooks)
+                .expect("Failed to build webhook http client");
+        tokio::spawn(async move { run_elected("webhooks", || webhooks.start()).await });
+    }
 
-    let aws_config = aws_sdk_s3::config::Builder::new()
-        .endpoint_url(env::var("AWS_ENDPOINT").unwrap_or("localhost".to_owned()))
-        .region(aws_config::Region::new(
-            env::var("AWS_DEFAULT_REGION").unwrap_or("us-east-1".to_owned()),
-        ))
-        .credentials_provider(credentials)
-        .behavior_version_latest()
-        .force_path_style(true)
-        .build();
+    let account_events = AccountEventDispatcher::new(&cfg.account_events);
+    tokio::spawn(async move { account_events.start().await });
 
+    let did_methods: SharedDidMethods = Arc::new(DidMethods::new(&cfg));
     let id_resolver = SharedIdResolver {
         id_resolver: RwLock::new(IdResolver::new(IdentityResolverOpts {
             timeout: None,
@@ -250,6 +383,9 @@ pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
             did_cache: Some(DidCache::new(None, None)),
             backup_nameservers: Some(env_list("PDS_HANDLE_BACKUP_NAMESERVERS")),
         })),
+        handle_cache: HandleCache::new(std::time::Duration::from_millis(
+            cfg.identity.handle_cache_ttl,
+        )),
     };
 
     // Keeping unused for other config purposes for now.
@@ -293,33 +429,76 @@ pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
         account_manager: RwLock::new(AccountManager::creator()),
     };
 
+    let rate_limiter = Arc::new(
+        RateLimiter::new(cfg.rate_limits.clone())
+            .await
+            .expect("Failed to set up rate limiter"),
+    );
+
+    // Keys rotated in on any replica, before anything is signed with the env's
+    if let Err(error) = reload_jwt_keys().await {
+        tracing::error!("@LOG: ERROR: failed to load JWT keys: {error}");
+    }
+    tokio::spawn(async move { JwtKeyReloader.start().await });
+
+    let flags = Arc::new(FlagStore::new(&cfg, rate_limiter.clone()));
+    if let Some(ref file) = cfg.flags.file {
+        let watcher = FlagsWatcher::new(flags.clone(), file.clone(), cfg.flags.poll_interval_ms);
+        tokio::spawn(async move { watcher.start().await });
+    }
+
+    let shutdown = Arc::new(ShutdownCoordinator::new(cfg.shutdown.drain_timeout_ms));
+
+    let replica_monitor = Arc::new(ReplicaMonitor::new(&cfg.database));
+    let has_replica = cfg.database.replica.is_some();
+    if has_replica {
+        let replica_monitor = replica_monitor.clone();
+        tokio::spawn(async move { replica_monitor.start().await });
+    }
+
     let shield = Shield::default().enable(NoSniff::Enable);
 
-    rocket::custom(figment)
+    let rocket = rocket::custom(figment)
         .mount(
             "/",
             routes![
                 index,
                 robots,
                 health,
+                readiness::ready,
+                com::atproto::admin::check_chain_consistency::check_chain_consistency,
+                com::atproto::admin::compact_repo::compact_repo,
+                com::atproto::admin::create_labels::create_labels,
                 com::atproto::admin::delete_account::delete_account,
+                com::atproto::admin::delete_webhook::delete_webhook,
                 com::atproto::admin::disable_account_invites::disable_account_invites,
                 com::atproto::admin::disable_invite_codes::disable_invite_codes,
                 com::atproto::admin::enable_account_invites::enable_account_invites,
                 com::atproto::admin::get_account_info::get_account_info,
                 com::atproto::admin::get_invite_codes::get_invite_codes,
+                com::atproto::admin::get_latest_seq::get_latest_seq,
+                com::atproto::admin::get_runtime_flags::get_runtime_flags,
                 com::atproto::admin::get_subject_status::get_subject_status,
+                com::atproto::admin::list_webhooks::list_webhooks,
+                com::atproto::admin::query_audit_log::query_audit_log,
+                com::atproto::admin::register_webhook::register_webhook,
+                com::atproto::admin::replay_events::replay_events,
+                com::atproto::admin::rotate_jwt_key::rotate_jwt_key,
                 com::atproto::admin::send_email::send_email,
                 com::atproto::admin::update_account_password::update_account_password,
                 com::atproto::admin::update_account_email::update_account_email,
                 com::atproto::admin::update_account_handle::update_account_handle,
+                com::atproto::admin::update_runtime_flags::update_runtime_flags,
                 com::atproto::admin::update_subject_status::update_subject_status,
+                com::atproto::admin::whoami::whoami,
                 com::atproto::identity::resolve_handle::resolve_handle,
                 com::atproto::identity::update_handle::update_handle,
                 com::atproto::identity::sign_plc_operation::sign_plc_operation,
                 com::atproto::identity::get_recommended_did_credentials::get_recommended_did_credentials,
                 com::atproto::identity::request_plc_operation_signature::request_plc_operation_signature,
                 com::atproto::identity::submit_plc_operation::submit_plc_operation,
+                com::atproto::label::query_labels::query_labels,
+                com::atproto::label::subscribe_labels::subscribe_labels,
                 com::atproto::repo::apply_writes::apply_writes,
                 com::atproto::repo::create_record::create_record,
                 com::atproto::repo::delete_record::delete_record,
@@ -341,6 +520,7 @@ pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
                 com::atproto::server::delete_session::delete_session,
                 com::atproto::server::describe_server::describe_server,
                 com::atproto::server::check_account_status::check_account_status,
+                com::atproto::server::get_storage_usage::get_storage_usage,
                 com::atproto::server::activate_account::activate_account,
                 com::atproto::server::get_service_auth::get_service_auth,
                 com::atproto::server::get_account_invite_codes::get_account_invite_codes,
@@ -363,14 +543,47 @@ pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
                 com::atproto::sync::get_repo_status::get_repo_status,
                 com::atproto::sync::list_blobs::list_blobs,
                 com::atproto::sync::list_repos::list_repos,
+                com::atproto::sync::list_repos_by_collection::list_repos_by_collection,
                 com::atproto::sync::subscribe_repos::subscribe_repos,
                 com::atproto::web5::pre_direct_writes::pre_direct_writes,
                 com::atproto::web5::direct_writes::direct_writes,
                 com::atproto::web5::pre_create_account::pre_create_account,
                 com::atproto::web5::create_account::create_account,
+                com::atproto::web5::email_notification_prefs::get_email_notification_prefs,
+                com::atproto::web5::email_notification_prefs::put_email_notification_prefs,
+                com::atproto::web5::get_record_history::get_record_history,
                 com::atproto::web5::index_action::index_action,
+                com::atproto::web5::list_tombstones::list_tombstones,
                 com::atproto::web5::pre_index_action::pre_index_action,
+                com::atproto::web5::rebind_address::rebind_address,
+                com::atproto::web5::reserve_handle::reserve_handle,
+                com::atproto::web5::scheduled_writes::schedule_direct_writes,
+                com::atproto::web5::scheduled_writes::list_scheduled_writes,
+                com::atproto::web5::scheduled_writes::cancel_scheduled_write,
+                com::atproto::web5::set_password::set_password,
                 com::atproto::web5::upload_blob::upload_blob,
+                com::atproto::web5::upload_blob::create_upload,
+                com::atproto::web5::upload_blob::append_upload,
+                com::atproto::web5::upload_blob::get_upload,
+                com::atproto::web5::upload_blob::finalize_upload,
+                app::bbs::actor::get_preferences::get_preferences,
+                app::bbs::actor::put_preferences::put_preferences,
+                app::bbs::delete_draft::delete_draft,
+                app::bbs::dm::list_convos::list_convos,
+                app::bbs::dm::list_messages::list_messages,
+                app::bbs::dm::mark_read::mark_read,
+                app::bbs::dm::send_message::send_message,
+                app::bbs::get_link_thumb::get_link_thumb,
+                app::bbs::get_read_states::get_read_states,
+                app::bbs::get_section_feed::get_section_feed,
+                app::bbs::get_stats::get_stats,
+                app::bbs::get_thread::get_thread,
+                app::bbs::get_trending::get_trending,
+                app::bbs::list_drafts::list_drafts,
+                app::bbs::moderation::set_section_status::set_section_status,
+                app::bbs::save_draft::save_draft,
+                app::bbs::search_posts::search_posts,
+                app::bbs::update_read_state::update_read_state,
                 app::bsky::actor::get_preferences::get_preferences,
                 app::bsky::actor::get_profile::get_profile,
                 app::bsky::actor::get_profiles::get_profiles,
@@ -384,18 +597,45 @@ pub async fn build_rocket(cfg: Option<RocketConfig>) -> Rocket<Build> {
                 bsky_api_get_forwarder,
                 bsky_api_post_forwarder,
                 well_known::well_known,
+                well_known::did_document,
+                oauth::routes::authorization_server_metadata,
+                oauth::routes::protected_resource_metadata,
+                oauth::routes::par,
+                oauth::routes::authorize,
+                oauth::routes::sign_in,
+                oauth::routes::web5_sign_in,
+                oauth::routes::token,
+                oauth::routes::revoke,
+                jetstream::subscribe,
+                metrics::metrics,
                 all_options
             ],
         )
         .register("/", catchers![default_catcher])
-        .attach(CORS)
+        .attach(CorsFairing(cfg.cors.clone()))
+        .attach(MetricsFairing)
+        .attach(RequestLogFairing)
+        .attach(RateLimitFairing(rate_limiter.clone()))
+        .attach(ShutdownFairing(shutdown.clone()))
+        .attach(ClusterNodeFairing(cfg.cluster.node_id.clone()))
         .attach(DbConn::fairing())
+        .attach(ScheduledWritesFairing)
+        .attach(CrosspostBridgeFairing)
         .attach(shield)
         .manage(sequencer)
-        .manage(aws_config)
+        .manage(blob_store)
         .manage(id_resolver)
+        .manage(did_methods)
         .manage(cfg)
         .manage(local_viewer)
         .manage(app_view_agent)
         .manage(account_manager)
+        .manage(rate_limiter)
+        .manage(shutdown)
+        .manage(flags)
+        .manage(replica_monitor);
+    match has_replica {
+        true => rocket.attach(ReplicaConn::fairing()),
+        false => rocket,
+    }
 }
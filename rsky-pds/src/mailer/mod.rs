@@ -1,11 +1,21 @@
 pub mod moderation;
+pub mod notifications;
+pub mod provider;
+pub mod templates;
 
-extern crate mailgun_rs;
-
-use anyhow::Result;
-use mailgun_rs::{EmailAddress, Mailgun, MailgunRegion, Message};
+use crate::config::EmailConfig;
+use anyhow::{bail, Result};
+use provider::EmailProvider;
 use std::collections::HashMap;
-use std::env;
+use std::sync::OnceLock;
+
+/// Web5 accounts created without an email are given an address like
+/// `web5Mock{random}@web5.com`, since account.email can't be null. Nothing is
+/// ever sent to them.
+const PLACEHOLDER_EMAIL_PREFIX: &str = "web5mock";
+const PLACEHOLDER_EMAIL_DOMAIN: &str = "@web5.com";
+
+static PROVIDER: OnceLock<Box<dyn EmailProvider>> = OnceLock::new();
 
 pub struct MailOpts {
     pub to: String,
@@ -25,37 +35,44 @@ pub struct TokenParam {
     pub token: String,
 }
 
-pub async fn send_template(opts: MailOpts) -> Result<()> {
-    let MailOpts {
-        to,
-        subject,
-        template,
-        template_vars,
-    } = opts;
+/// A BBS post or reply an account is notified about.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActivityParams {
+    /// Handle of the author, or their DID if they have none
+    pub author: String,
+    pub text: String,
+    pub uri: String,
+}
 
-    let recipient = EmailAddress::address(&to);
-    let message = Message {
-        to: vec![recipient],
-        subject,
-        template,
-        template_vars,
-        ..Default::default()
-    };
-
-    let client = Mailgun {
-        api_key: env::var("PDS_MAILGUN_API_KEY").unwrap(),
-        domain: env::var("PDS_MAILGUN_DOMAIN").unwrap(),
-        message,
-    };
-    let sender = EmailAddress::name_address(
-        &env::var("PDS_EMAIL_FROM_NAME").unwrap(),
-        &env::var("PDS_EMAIL_FROM_ADDRESS").unwrap(),
-    );
-
-    client.async_send(MailgunRegion::US, &sender).await?;
+pub fn placeholder_email(suffix: &str) -> String {
+    format!("web5Mock{suffix}{PLACEHOLDER_EMAIL_DOMAIN}")
+}
+
+pub fn is_placeholder_email(email: &str) -> bool {
+    let email = email.to_lowercase();
+    email.starts_with(PLACEHOLDER_EMAIL_PREFIX) && email.ends_with(PLACEHOLDER_EMAIL_DOMAIN)
+}
+
+/// Sets up the provider mail is sent through. Sending fails until this has
+/// run with a provider configured.
+pub async fn init(cfg: &EmailConfig) -> Result<()> {
+    if let Some(provider) = provider::build(cfg).await? {
+        // Already set if rocket is built more than once in a process, e.g. in tests
+        let _ = PROVIDER.set(provider);
+    }
     Ok(())
 }
 
+pub async fn send_template(opts: MailOpts) -> Result<()> {
+    if is_placeholder_email(&opts.to) {
+        bail!("Account does not have an email address");
+    }
+    match PROVIDER.get() {
+        Some(provider) => provider.send(&opts).await,
+        None => bail!("No email provider is configured"),
+    }
+}
+
 pub async fn send_reset_password(to: String, params: IdentifierAndTokenParams) -> Result<()> {
     let mut template_vars = HashMap::new();
     template_vars.insert("identifier".to_string(), params.identifier);
@@ -116,3 +133,41 @@ pub async fn send_plc_operation(to: String, params: TokenParam) -> Result<()> {
     })
     .await
 }
+
+pub async fn send_reply_notification(to: String, params: ActivityParams) -> Result<()> {
+    send_template(MailOpts {
+        to,
+        subject: format!("{} replied to you", params.author),
+        template: "bbs reply".to_string(),
+        template_vars: activity_vars(params),
+    })
+    .await
+}
+
+pub async fn send_watched_reply_notification(to: String, params: ActivityParams) -> Result<()> {
+    send_template(MailOpts {
+        to,
+        subject: format!("{} replied in a thread you watch", params.author),
+        template: "bbs watched reply".to_string(),
+        template_vars: activity_vars(params),
+    })
+    .await
+}
+
+pub async fn send_mention_notification(to: String, params: ActivityParams) -> Result<()> {
+    send_template(MailOpts {
+        to,
+        subject: format!("{} mentioned you", params.author),
+        template: "bbs mention".to_string(),
+        template_vars: activity_vars(params),
+    })
+    .await
+}
+
+fn activity_vars(params: ActivityParams) -> HashMap<String, String> {
+    let mut template_vars = HashMap::new();
+    template_vars.insert("author".to_string(), params.author);
+    template_vars.insert("text".to_string(), params.text);
+    template_vars.insert("uri".to_string(), params.uri);
+    template_vars
+}
@@ -0,0 +1,329 @@
+use super::{
+    is_placeholder_email, send_mention_notification, send_reply_notification,
+    send_watched_reply_notification, ActivityParams,
+};
+use crate::bbs::graph::hidden_authors;
+use crate::bbs::watch::thread_watchers;
+use crate::bbs::{POST_COLLECTION, REPLY_COLLECTION};
+use crate::cluster::try_lock_job;
+use crate::config::EmailNotificationsConfig;
+use crate::db::establish_connection_for_jobs;
+use crate::jetstream::{to_jetstream_evts, JetstreamCommit, JetstreamFilter, JetstreamKind};
+use crate::models::EmailNotificationPref;
+use crate::sequencer::events::CommitEvtOpAction;
+use crate::sequencer::{
+    get_subscriber_cursor, save_subscriber_cursor, RequestSeqRangeOpts, Sequencer,
+};
+use anyhow::Result;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// Subscriber cursor the notifier keeps its place in repo_seq with, also the
+/// lock a run holds so no two replicas read and save it at once
+const CURSOR_ID: &str = "email-notifications";
+const MENTION_FEATURE: &str = "app.bsky.richtext.facet#mention";
+/// Longest excerpt of a post quoted in a notification, in characters
+const EXCERPT_CHARS: usize = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Activity {
+    Reply,
+    Mention,
+    /// A reply in a thread the account watches
+    WatchedReply,
+}
+
+/// Emails accounts about replies to their BBS posts and replies, about posts
+/// that mention them, and about replies in threads they watch with an
+/// app.bbs.watchThread record. Muting a thread that way stops its replies from
+/// being mailed, replies to the account's own posts included, but not
+/// mentions.
+///
+/// Tails repo_seq from its own cursor, starting from the head the first time
+/// so nobody is mailed about old activity. Only confirmed addresses are
+/// mailed, only for activity the account hasn't opted out of, and never about
+/// authors it blocks, mutes or is blocked by. Delivery is best-effort, a
+/// failed email is logged and not retried.
+pub struct EmailNotifier {
+    pub sequencer: Sequencer,
+    pub interval_ms: u64,
+    pub batch_size: i64,
+}
+
+impl EmailNotifier {
+    pub fn new(sequencer: Sequencer, cfg: &EmailNotificationsConfig) -> Self {
+        EmailNotifier {
+            sequencer,
+            interval_ms: cfg.interval_ms.max(1000),
+            batch_size: cfg.batch_size.max(1),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: email notification run failed: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let id = CURSOR_ID.to_string();
+        // released when the connection closes at the end of the run
+        let conn = &mut establish_connection_for_jobs()?;
+        if !try_lock_job(conn, CURSOR_ID)? {
+            return Ok(());
+        }
+        let Some(mut cursor) = get_subscriber_cursor(&id).await? else {
+            let head = self.sequencer.curr().await?.unwrap_or(0);
+            return save_subscriber_cursor(&id, head).await;
+        };
+        let filter = JetstreamFilter::new(
+            vec![POST_COLLECTION.to_string(), REPLY_COLLECTION.to_string()],
+            Vec::new(),
+        )?;
+        let evts = self
+            .sequencer
+            .request_seq_range(RequestSeqRangeOpts {
+                earliest_seq: Some(cursor),
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(self.batch_size),
+            })
+            .await?;
+        if evts.is_empty() {
+            return Ok(());
+        }
+
+        for evt in evts {
+            cursor = evt.seq();
+            for jetstream_evt in to_jetstream_evts(evt, &filter).await? {
+                if let JetstreamKind::Commit { commit } = jetstream_evt.kind {
+                    self.notify(conn, &jetstream_evt.did, commit).await?;
+                }
+            }
+        }
+        save_subscriber_cursor(&id, cursor).await
+    }
+
+    async fn notify(
+        &self,
+        conn: &mut PgConnection,
+        author_did: &str,
+        commit: JetstreamCommit,
+    ) -> Result<()> {
+        let record = match (commit.operation, commit.record) {
+            (CommitEvtOpAction::Create, Some(record)) => record,
+            _ => return Ok(()),
+        };
+        let params = ActivityParams {
+            author: get_handle(conn, author_did)?.unwrap_or(author_did.to_string()),
+            text: excerpt(
+                record
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default(),
+            ),
+            uri: format!("at://{author_did}/{}/{}", commit.collection, commit.rkey),
+        };
+
+        // Nobody is notified about their own activity, or twice about the same record
+        let mut notified = HashSet::from([author_did.to_string()]);
+        let mut watchers = HashMap::new();
+        if commit.collection == REPLY_COLLECTION {
+            let root = record.get("root").and_then(Value::as_str);
+            if let Some(thread) = root.map(|root| get_thread_uri(conn, root)) {
+                if let Some(thread) = thread? {
+                    watchers = thread_watchers(conn, &thread)?;
+                }
+            }
+            let parent = record.get("parent").and_then(Value::as_str);
+            if let Some(owner) = parent.map(|parent| get_record_owner(conn, parent)) {
+                if let Some(owner) = owner? {
+                    let muted = watchers.get(&owner) == Some(&true);
+                    if !muted
+                        && notified.insert(owner.clone())
+                        && !silences(conn, &owner, author_did)?
+                    {
+                        send(conn, &owner, Activity::Reply, params.clone()).await?;
+                    }
+                }
+            }
+        }
+        for did in mentioned_dids(&record) {
+            if notified.insert(did.clone()) && !silences(conn, &did, author_did)? {
+                send(conn, &did, Activity::Mention, params.clone()).await?;
+            }
+        }
+        for (did, muted) in watchers {
+            if !muted && notified.insert(did.clone()) && !silences(conn, &did, author_did)? {
+                send(conn, &did, Activity::WatchedReply, params.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `did` blocks or mutes the author, or is blocked by them, in which
+/// case they aren't told about the author's activity
+fn silences(conn: &mut PgConnection, did: &str, author_did: &str) -> Result<bool> {
+    Ok(!hidden_authors(conn, did, vec![author_did.to_string()])?.is_empty())
+}
+
+async fn send(
+    conn: &mut PgConnection,
+    did: &str,
+    activity: Activity,
+    params: ActivityParams,
+) -> Result<()> {
+    let Some(email) = get_notifiable_email(conn, did, activity)? else {
+        return Ok(());
+    };
+    let uri = params.uri.clone();
+    let res = match activity {
+        Activity::Reply => send_reply_notification(email, params).await,
+        Activity::Mention => send_mention_notification(email, params).await,
+        Activity::WatchedReply => send_watched_reply_notification(email, params).await,
+    };
+    if let Err(error) = res {
+        tracing::warn!("Failed to email {did} about {uri}: {error}");
+    }
+    Ok(())
+}
+
+/// The confirmed address of a local account that wants to hear about `activity`
+fn get_notifiable_email(
+    conn: &mut PgConnection,
+    did: &str,
+    activity: Activity,
+) -> Result<Option<String>> {
+    use crate::schema::pds::account::dsl as AccountSchema;
+    use crate::schema::pds::email_notification_pref::dsl as EmailPrefSchema;
+
+    let account = AccountSchema::account
+        .find(did)
+        .select((AccountSchema::email, AccountSchema::emailConfirmedAt))
+        .first::<(String, Option<String>)>(conn)
+        .optional()?;
+    let email = match account {
+        Some((email, Some(_))) if !is_placeholder_email(&email) => email,
+        _ => return Ok(None),
+    };
+    let pref = EmailPrefSchema::email_notification_pref
+        .find(did)
+        .select(EmailNotificationPref::as_select())
+        .first(conn)
+        .optional()?;
+    let wanted = match (pref, activity) {
+        (None, _) => true,
+        (Some(pref), Activity::Reply | Activity::WatchedReply) => pref.replies,
+        (Some(pref), Activity::Mention) => pref.mentions,
+    };
+    Ok(if wanted { Some(email) } else { None })
+}
+
+fn get_handle(conn: &mut PgConnection, did: &str) -> Result<Option<String>> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+
+    Ok(ActorSchema::actor
+        .find(did)
+        .select(ActorSchema::handle)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten())
+}
+
+/// Replies reference their parent by cid, but accept the uri as well
+fn get_record_owner(conn: &mut PgConnection, parent: &str) -> Result<Option<String>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    Ok(RecordSchema::record
+        .filter(
+            RecordSchema::uri
+                .eq(parent)
+                .or(RecordSchema::cid.eq(parent)),
+        )
+        .select(RecordSchema::did)
+        .first::<String>(conn)
+        .optional()?)
+}
+
+/// The uri of the thread a reply's root references. Roots given by cid are
+/// only known when the post is hosted here.
+fn get_thread_uri(conn: &mut PgConnection, root: &str) -> Result<Option<String>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+
+    if root.starts_with("at://") {
+        return Ok(Some(root.to_string()));
+    }
+    Ok(RecordSchema::record
+        .filter(RecordSchema::cid.eq(root))
+        .filter(RecordSchema::collection.eq(POST_COLLECTION))
+        .select(RecordSchema::uri)
+        .first::<String>(conn)
+        .optional()?)
+}
+
+fn mentioned_dids(record: &Value) -> Vec<String> {
+    let facets = record.get("facets").and_then(Value::as_array);
+    let mut dids = Vec::new();
+    for feature in facets
+        .into_iter()
+        .flatten()
+        .filter_map(|facet| facet.get("features").and_then(Value::as_array))
+        .flatten()
+    {
+        if feature.get("$type").and_then(Value::as_str) != Some(MENTION_FEATURE) {
+            continue;
+        }
+        if let Some(did) = feature.get("did").and_then(Value::as_str) {
+            dids.push(did.to_string());
+        }
+    }
+    dids
+}
+
+fn excerpt(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text.to_string();
+    }
+    let mut excerpt: String = text.chars().take(EXCERPT_CHARS).collect();
+    excerpt.push('…');
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_mentioned_dids() {
+        let record = json!({
+            "text": "hi @alice.test, see https://example.com",
+            "facets": [
+                {
+                    "index": { "byteStart": 3, "byteEnd": 14 },
+                    "features": [{
+                        "$type": "app.bsky.richtext.facet#mention",
+                        "did": "did:web:alice.test"
+                    }]
+                },
+                {
+                    "index": { "byteStart": 20, "byteEnd": 39 },
+                    "features": [{
+                        "$type": "app.bsky.richtext.facet#link",
+                        "uri": "https://example.com"
+                    }]
+                }
+            ]
+        });
+        assert_eq!(mentioned_dids(&record), vec!["did:web:alice.test"]);
+        assert!(mentioned_dids(&json!({ "text": "no facets" })).is_empty());
+    }
+}
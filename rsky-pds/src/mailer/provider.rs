@@ -0,0 +1,146 @@
+use super::MailOpts;
+use crate::config::{EmailConfig, EmailProviderConfig};
+use anyhow::Result;
+use mailgun_rs::{EmailAddress, Mailgun, MailgunRegion, Message};
+
+/// Delivers account mail. Mailgun renders the stored template of the same
+/// name, the others render the bodies in `templates`.
+#[rocket::async_trait]
+pub trait EmailProvider: Send + Sync {
+    async fn send(&self, opts: &MailOpts) -> Result<()>;
+}
+
+pub async fn build(cfg: &EmailConfig) -> Result<Option<Box<dyn EmailProvider>>> {
+    let provider: Box<dyn EmailProvider> = match cfg.provider {
+        None => return Ok(None),
+        Some(EmailProviderConfig::Mailgun {
+            ref api_key,
+            ref domain,
+        }) => Box::new(MailgunProvider {
+            api_key: api_key.clone(),
+            domain: domain.clone(),
+            from_name: cfg.from_name.clone(),
+            from_address: cfg.from_address.clone(),
+        }),
+        #[cfg(feature = "email-smtp")]
+        Some(EmailProviderConfig::Smtp { ref url }) => {
+            Box::new(SmtpProvider::new(url, &cfg.from_name, &cfg.from_address)?)
+        }
+        #[cfg(not(feature = "email-smtp"))]
+        Some(EmailProviderConfig::Smtp { .. }) => anyhow::bail!(
+            "PDS_EMAIL_PROVIDER is smtp but rsky-pds was built without the email-smtp feature"
+        ),
+        #[cfg(feature = "email-ses")]
+        Some(EmailProviderConfig::Ses) => {
+            Box::new(SesProvider::new(&cfg.from_name, &cfg.from_address).await)
+        }
+        #[cfg(not(feature = "email-ses"))]
+        Some(EmailProviderConfig::Ses) => anyhow::bail!(
+            "PDS_EMAIL_PROVIDER is ses but rsky-pds was built without the email-ses feature"
+        ),
+    };
+    Ok(Some(provider))
+}
+
+pub struct MailgunProvider {
+    api_key: String,
+    domain: String,
+    from_name: String,
+    from_address: String,
+}
+
+#[rocket::async_trait]
+impl EmailProvider for MailgunProvider {
+    async fn send(&self, opts: &MailOpts) -> Result<()> {
+        let message = Message {
+            to: vec![EmailAddress::address(&opts.to)],
+            subject: opts.subject.clone(),
+            template: opts.template.clone(),
+            template_vars: opts.template_vars.clone(),
+            ..Default::default()
+        };
+        let client = Mailgun {
+            api_key: self.api_key.clone(),
+            domain: self.domain.clone(),
+            message,
+        };
+        let sender = EmailAddress::name_address(&self.from_name, &self.from_address);
+        client.async_send(MailgunRegion::US, &sender).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "email-smtp")]
+pub struct SmtpProvider {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+}
+
+#[cfg(feature = "email-smtp")]
+impl SmtpProvider {
+    pub fn new(url: &str, from_name: &str, from_address: &str) -> Result<Self> {
+        Ok(SmtpProvider {
+            transport: lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::from_url(url)?.build(),
+            from: lettre::message::Mailbox::new(Some(from_name.to_string()), from_address.parse()?),
+        })
+    }
+}
+
+#[cfg(feature = "email-smtp")]
+#[rocket::async_trait]
+impl EmailProvider for SmtpProvider {
+    async fn send(&self, opts: &MailOpts) -> Result<()> {
+        use lettre::message::header::ContentType;
+        use lettre::AsyncTransport;
+
+        let body = super::templates::render(&opts.template, &opts.template_vars)?;
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(opts.to.parse()?)
+            .subject(&opts.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "email-ses")]
+pub struct SesProvider {
+    client: aws_sdk_sesv2::Client,
+    from: String,
+}
+
+#[cfg(feature = "email-ses")]
+impl SesProvider {
+    pub async fn new(from_name: &str, from_address: &str) -> Self {
+        let aws_config = aws_config::load_from_env().await;
+        SesProvider {
+            client: aws_sdk_sesv2::Client::new(&aws_config),
+            from: format!("{from_name} <{from_address}>"),
+        }
+    }
+}
+
+#[cfg(feature = "email-ses")]
+#[rocket::async_trait]
+impl EmailProvider for SesProvider {
+    async fn send(&self, opts: &MailOpts) -> Result<()> {
+        use aws_sdk_sesv2::types::{Body, Content, Destination, EmailContent, Message};
+
+        let content = |data: String| Content::builder().data(data).charset("UTF-8").build();
+        let body = super::templates::render(&opts.template, &opts.template_vars)?;
+        let message = Message::builder()
+            .subject(content(opts.subject.clone())?)
+            .body(Body::builder().text(content(body)?).build())
+            .build();
+        self.client
+            .send_email()
+            .from_email_address(&self.from)
+            .destination(Destination::builder().to_addresses(&opts.to).build())
+            .content(EmailContent::builder().simple(message).build())
+            .send()
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Plain text bodies of the account mail, by template name. `{{name}}` is
+/// replaced with the template variable of that name.
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "reset password",
+        "We received a request to reset the password for {{identifier}}.\n\n\
+         Enter this code in your app to choose a new password:\n\n{{token}}\n\n\
+         If you didn't request this, you can ignore this email.\n",
+    ),
+    (
+        "delete account",
+        "We received a request to delete your account.\n\n\
+         Enter this code in your app to confirm:\n\n{{token}}\n\n\
+         If you didn't request this, you can ignore this email and your account will be kept.\n",
+    ),
+    (
+        "confirm email",
+        "Enter this code in your app to confirm your email address:\n\n{{token}}\n",
+    ),
+    (
+        "email update",
+        "We received a request to change the email address of your account.\n\n\
+         Enter this code in your app to confirm:\n\n{{token}}\n",
+    ),
+    (
+        "plc operation",
+        "We received a request to update the identity of your account.\n\n\
+         Enter this code in your app to confirm:\n\n{{token}}\n\n\
+         If you didn't request this, change your password right away.\n",
+    ),
+    (
+        "bbs reply",
+        "{{author}} replied to you:\n\n{{text}}\n\n{{uri}}\n\n\
+         You can turn off reply emails in your notification settings.\n",
+    ),
+    (
+        "bbs watched reply",
+        "{{author}} replied in a thread you watch:\n\n{{text}}\n\n{{uri}}\n\n\
+         You can stop watching the thread in your app, or turn off reply emails in your \
+         notification settings.\n",
+    ),
+    (
+        "bbs mention",
+        "{{author}} mentioned you:\n\n{{text}}\n\n{{uri}}\n\n\
+         You can turn off mention emails in your notification settings.\n",
+    ),
+];
+
+pub fn render(template: &str, template_vars: &HashMap<String, String>) -> Result<String> {
+    let Some((_, body)) = TEMPLATES.iter().find(|(name, _)| *name == template) else {
+        bail!("Unknown email template: {template}");
+    };
+    let mut rendered = body.to_string();
+    for (name, value) in template_vars {
+        rendered = rendered.replace(&format!("{{{{{name}}}}}"), value);
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_template_vars() {
+        let mut template_vars = HashMap::new();
+        template_vars.insert("token".to_string(), "ABCDE-12345".to_string());
+        let rendered = render("confirm email", &template_vars).unwrap();
+        assert!(rendered.contains("ABCDE-12345"));
+        assert!(!rendered.contains("{{token}}"));
+        assert!(render("welcome", &template_vars).is_err());
+    }
+}
@@ -1,8 +1,76 @@
-use rsky_pds::build_rocket;
+use rsky_pds::config::env_to_cfg;
+use rsky_pds::crawlers::Crawlers;
+use rsky_pds::db::{establish_connection_for_jobs, migrations};
+use rsky_pds::{build_rocket, check_config, sequencer, telemetry};
+use std::process;
+
+/// `rsky-pds request-crawl [relay...]` asks the given relays, or PDS_CRAWLERS
+/// if none are given, to crawl this PDS. Useful after adding a relay, which
+/// otherwise only hears from the PDS on its next write.
+async fn request_crawl(relays: &[String]) -> i32 {
+    let cfg = env_to_cfg();
+    let relays = match relays.is_empty() {
+        true => cfg.crawlers,
+        false => relays.to_vec(),
+    };
+    if relays.is_empty() {
+        eprintln!("error: no relays given and PDS_CRAWLERS is unset");
+        return 1;
+    }
+    let results = Crawlers::new(cfg.service.hostname, relays, cfg.crawl_notify)
+        .request_crawl()
+        .await;
+    let mut code = 0;
+    for (relay, result) in results {
+        match result {
+            Ok(()) => println!("{relay}: crawl requested"),
+            Err(error) => {
+                eprintln!("{relay}: error: {error}");
+                code = 1;
+            }
+        }
+    }
+    code
+}
 
 #[rocket::main]
 async fn main() {
-    let subscriber = tracing_subscriber::FmtSubscriber::new();
-    tracing::subscriber::set_global_default(subscriber).unwrap();
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("migrate") {
+        process::exit(migrations::run(args.get(2).map(String::as_str)));
+    }
+    if args.get(1).map(String::as_str) == Some("request-crawl") {
+        process::exit(request_crawl(&args[2..]).await);
+    }
+    if args.get(1).map(String::as_str) == Some("migrate-sequencer") {
+        process::exit(
+            sequencer::store::run_migration(
+                args.get(2).map(String::as_str),
+                args.get(3).map(String::as_str),
+            )
+            .await,
+        );
+    }
+    if args.iter().any(|arg| arg == "--check-config") {
+        process::exit(check_config::run().await);
+    }
+    let cfg = env_to_cfg();
+    let problems = check_config::validate(&cfg);
+    check_config::print_problems(&problems);
+    if check_config::has_errors(&problems) {
+        eprintln!("Refusing to start, run with --check-config for a full report");
+        process::exit(1);
+    }
+    telemetry::init().expect("Failed to set up tracing");
+    if cfg.database.migrate_on_startup {
+        let applied = establish_connection_for_jobs()
+            .and_then(|mut conn| migrations::run_pending(&mut conn))
+            .expect("Failed to run migrations");
+        for version in applied {
+            tracing::info!("Applied migration {version}");
+        }
+    }
     let _ = build_rocket(None).await.launch().await;
+    telemetry::shutdown();
 }
@@ -0,0 +1,227 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::bearer_token_from_req;
+use crate::config::ServerConfig;
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response};
+use std::time::Instant;
+
+/// Route label for requests that didn't match a route
+const UNMATCHED: &str = "unmatched";
+
+lazy_static! {
+    pub static ref REGISTRY: Registry =
+        Registry::new_custom(Some("pds".to_string()), None).expect("valid metrics registry");
+    pub static ref XRPC_REQUESTS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "xrpc_requests_total",
+                "Requests by route and response status"
+            ),
+            &["route", "status"],
+        )
+        .expect("valid metric")
+    );
+    pub static ref XRPC_REQUEST_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new("xrpc_request_duration_seconds", "Request latency by route"),
+            &["route"],
+        )
+        .expect("valid metric")
+    );
+    pub static ref CHAIN_LOOKUPS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "web5_chain_lookups_total",
+                "DID doc lookups on CKB by result: found, not_found or error"
+            ),
+            &["result"],
+        )
+        .expect("valid metric")
+    );
+    pub static ref CHAIN_LOOKUP_DURATION: Histogram = register(
+        Histogram::with_opts(HistogramOpts::new(
+            "web5_chain_lookup_duration_seconds",
+            "Latency of DID doc lookups on CKB"
+        ))
+        .expect("valid metric")
+    );
+    pub static ref SIGNATURE_VERIFICATIONS: IntCounterVec = register(
+        IntCounterVec::new(
+            Opts::new(
+                "web5_signature_verifications_total",
+                "Wallet signature checks by method and result: valid or invalid"
+            ),
+            &["method", "result"],
+        )
+        .expect("valid metric")
+    );
+    pub static ref DIRECT_WRITES_DURATION: HistogramVec = register(
+        HistogramVec::new(
+            HistogramOpts::new(
+                "web5_direct_writes_duration_seconds",
+                "directWrites latency by result: ok or error"
+            ),
+            &["result"],
+        )
+        .expect("valid metric")
+    );
+    pub static ref SEQUENCER_LAST_SEQ: IntGauge = register(
+        IntGauge::new("sequencer_last_seq", "Last seq emitted by the sequencer")
+            .expect("valid metric")
+    );
+    pub static ref SEQUENCER_LAG: Gauge = register(
+        Gauge::new(
+            "sequencer_lag_seconds",
+            "Time between an event being sequenced and emitted to subscribers"
+        )
+        .expect("valid metric")
+    );
+    pub static ref BLOB_UPLOAD_BYTES: IntCounter = register(
+        IntCounter::new("blob_upload_bytes_total", "Bytes of blobs uploaded")
+            .expect("valid metric")
+    );
+}
+
+fn register<T: Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric registered once");
+    metric
+}
+
+/// Registers every metric up front so all of them are scraped from the start,
+/// not only the ones touched since boot.
+pub fn init() {
+    lazy_static::initialize(&XRPC_REQUESTS);
+    lazy_static::initialize(&XRPC_REQUEST_DURATION);
+    lazy_static::initialize(&CHAIN_LOOKUPS);
+    lazy_static::initialize(&CHAIN_LOOKUP_DURATION);
+    lazy_static::initialize(&SIGNATURE_VERIFICATIONS);
+    lazy_static::initialize(&DIRECT_WRITES_DURATION);
+    lazy_static::initialize(&SEQUENCER_LAST_SEQ);
+    lazy_static::initialize(&SEQUENCER_LAG);
+    lazy_static::initialize(&BLOB_UPLOAD_BYTES);
+}
+
+pub fn record_signature_verification(method: &str, valid: bool) {
+    let result = if valid { "valid" } else { "invalid" };
+    SIGNATURE_VERIFICATIONS
+        .with_label_values(&[method, result])
+        .inc();
+}
+
+pub fn record_chain_lookup<T>(started: Instant, res: &Result<T, ApiError>) {
+    CHAIN_LOOKUP_DURATION.observe(started.elapsed().as_secs_f64());
+    let result = match res {
+        Ok(_) => "found",
+        Err(ApiError::CkbAddrNoCell | ApiError::CkbDidocCellNotFound) => "not_found",
+        Err(_) => "error",
+    };
+    CHAIN_LOOKUPS.with_label_values(&[result]).inc();
+}
+
+struct RequestStart(Instant);
+
+/// Counts and times every routed request. Routes are labeled by their path
+/// pattern, e.g. `/xrpc/<nsid>` for proxied methods, so the label set stays
+/// bounded.
+pub struct MetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for MetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Record request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let started = req.local_cache(|| RequestStart(Instant::now()));
+        let route = match req.route() {
+            Some(route) => route.uri.path().to_string(),
+            None => UNMATCHED.to_string(),
+        };
+        XRPC_REQUESTS
+            .with_label_values(&[&route, &res.status().code.to_string()])
+            .inc();
+        XRPC_REQUEST_DURATION
+            .with_label_values(&[&route])
+            .observe(started.0.elapsed().as_secs_f64());
+    }
+}
+
+/// A scrape of `/metrics`, allowed when metrics are enabled and the configured
+/// bearer token, if any, was sent.
+pub struct MetricsScraper;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsScraper {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let cfg = req
+            .rocket()
+            .state::<ServerConfig>()
+            .expect("server config is managed");
+        if !cfg.metrics.enabled {
+            return Outcome::Forward(Status::NotFound);
+        }
+        let Some(ref expected) = cfg.metrics.bearer_token else {
+            return Outcome::Success(MetricsScraper);
+        };
+        match bearer_token_from_req(req) {
+            Ok(Some(token)) if token == *expected => Outcome::Success(MetricsScraper),
+            _ => {
+                let error = ApiError::AuthRequiredError("Invalid metrics token".to_string());
+                req.local_cache(|| Some(error.clone()));
+                Outcome::Error((Status::Unauthorized, error))
+            }
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::get("/metrics")]
+pub async fn metrics(_scraper: MetricsScraper) -> Result<(ContentType, String), ApiError> {
+    let mut buffer = Vec::new();
+    match TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        Ok(()) => Ok((
+            ContentType::Plain,
+            String::from_utf8_lossy(&buffer).into_owned(),
+        )),
+        Err(error) => {
+            tracing::error!("@LOG: ERROR: failed to encode metrics: {error}");
+            Err(ApiError::RuntimeError)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_chain_lookup_results() {
+        let count = |result: &str| CHAIN_LOOKUPS.with_label_values(&[result]).get();
+        let (found, not_found, error) = (count("found"), count("not_found"), count("error"));
+        record_chain_lookup(Instant::now(), &Ok::<(), ApiError>(()));
+        record_chain_lookup::<()>(Instant::now(), &Err(ApiError::CkbDidocCellNotFound));
+        record_chain_lookup::<()>(Instant::now(), &Err(ApiError::RuntimeError));
+        assert_eq!(count("found"), found + 1);
+        assert_eq!(count("not_found"), not_found + 1);
+        assert_eq!(count("error"), error + 1);
+    }
+}
@@ -1,23 +1,56 @@
 pub mod models;
 pub use self::models::Account;
+pub use self::models::AccountEventOutbox;
 pub use self::models::AccountPref;
 pub use self::models::Actor;
 pub use self::models::AppPassword;
+pub use self::models::AuditLog;
 pub use self::models::Backlink;
+pub use self::models::BbsCrosspost;
+pub use self::models::BbsDmConvo;
+pub use self::models::BbsDmMember;
+pub use self::models::BbsDmMessage;
+pub use self::models::BbsDraft;
+pub use self::models::BbsGraph;
+pub use self::models::BbsLinkCard;
+pub use self::models::BbsPostLink;
+pub use self::models::BbsReadState;
+pub use self::models::BbsReview;
+pub use self::models::BbsSection;
+pub use self::models::BbsSectionStats;
+pub use self::models::BbsStats;
+pub use self::models::BbsThreadIndex;
+pub use self::models::BbsThreadWatch;
+pub use self::models::BbsTrending;
 pub use self::models::Blob;
+pub use self::models::BlobGc;
+pub use self::models::BlobUpload;
 pub use self::models::DidDoc;
+pub use self::models::EmailNotificationPref;
 pub use self::models::EmailToken;
 pub use self::models::InviteCode;
 pub use self::models::InviteCodeUse;
+pub use self::models::Label;
+pub use self::models::OAuthRequest;
+pub use self::models::OAuthSession;
 pub use self::models::Record;
 pub use self::models::RecordBlob;
+pub use self::models::RecordTombstone;
 pub use self::models::RefreshToken;
 pub use self::models::RepoBlock;
 pub use self::models::RepoRoot;
 pub use self::models::RepoSeq;
+pub use self::models::RevokedSession;
+pub use self::models::ScheduledWrite;
+pub use self::models::SignupIp;
+pub use self::models::SubscriberCursor;
+pub use self::models::Webhook;
+pub use self::models::WebhookDeadLetter;
 pub mod error_code;
 pub use self::error_code::ErrorCode;
 pub mod error_message_response;
 pub use self::error_message_response::ErrorMessageResponse;
+pub mod readiness;
+pub use self::readiness::{DependencyStatus, Readiness};
 pub mod server_version;
 pub use self::server_version::ServerVersion;
@@ -35,6 +35,31 @@ pub struct Account {
     #[diesel(column_name = emailConfirmedAt)]
     #[serde(rename = "emailConfirmedAt")]
     pub email_confirmed_at: Option<String>,
+    /// Whether a web5 account may also sign in with its password
+    #[diesel(column_name = passwordLogin)]
+    #[serde(rename = "passwordLogin")]
+    pub password_login: bool,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::account_event_outbox)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AccountEventOutbox {
+    pub id: i64,
+    pub handler: String,
+    pub did: String,
+    pub event: String,
+    pub attempts: i32,
+    #[diesel(column_name = nextAttemptAt)]
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: String,
+    #[diesel(column_name = lastError)]
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
 }
 
 #[derive(
@@ -95,6 +120,34 @@ pub struct AppPassword {
     #[diesel(column_name = createdAt)]
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    /// Comma separated collections the password may write to, see
+    /// [`crate::repo::prepare::collection_permitted`]
+    pub collections: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::audit_log)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditLog {
+    pub id: i64,
+    pub action: String,
+    #[diesel(column_name = actorDid)]
+    #[serde(rename = "actorDid")]
+    pub actor_did: Option<String>,
+    #[diesel(column_name = subjectDid)]
+    #[serde(rename = "subjectDid")]
+    pub subject_did: String,
+    #[diesel(column_name = subjectUri)]
+    #[serde(rename = "subjectUri")]
+    pub subject_uri: Option<String>,
+    #[diesel(column_name = subjectCid)]
+    #[serde(rename = "subjectCid")]
+    pub subject_cid: Option<String>,
+    pub detail: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
 }
 
 #[derive(
@@ -120,6 +173,370 @@ pub struct Backlink {
     pub link_to: String,
 }
 
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::bbs_draft)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsDraft {
+    pub id: i64,
+    pub did: String,
+    pub collection: String,
+    /// The record as composed so far, as JSON
+    pub value: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(did, thread))]
+#[diesel(table_name = crate::schema::pds::bbs_read_state)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsReadState {
+    pub did: String,
+    /// Uri of the thread's root post
+    pub thread: String,
+    #[diesel(column_name = readReplyCount)]
+    #[serde(rename = "readReplyCount")]
+    pub read_reply_count: i64,
+    /// `createdAt` of the newest reply read
+    #[diesel(column_name = lastReadAt)]
+    #[serde(rename = "lastReadAt")]
+    pub last_read_at: String,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::bbs_dm_convo)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsDmConvo {
+    pub id: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = lastMessageAt)]
+    #[serde(rename = "lastMessageAt")]
+    pub last_message_at: Option<String>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(convo_id, did))]
+#[diesel(table_name = crate::schema::pds::bbs_dm_member)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsDmMember {
+    #[diesel(column_name = convoId)]
+    #[serde(rename = "convoId")]
+    pub convo_id: String,
+    pub did: String,
+    /// Newest message the member has read, unset until they read any
+    #[diesel(column_name = lastReadId)]
+    #[serde(rename = "lastReadId")]
+    pub last_read_id: Option<i64>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::bbs_dm_message)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsDmMessage {
+    pub id: i64,
+    #[diesel(column_name = convoId)]
+    #[serde(rename = "convoId")]
+    pub convo_id: String,
+    #[diesel(column_name = senderDid)]
+    #[serde(rename = "senderDid")]
+    pub sender_did: String,
+    pub text: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+/// A block or mute from an `app.bbs.graph.*` record written on this PDS
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_graph)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsGraph {
+    pub uri: String,
+    pub collection: String,
+    #[diesel(column_name = creatorDid)]
+    #[serde(rename = "creatorDid")]
+    pub creator_did: String,
+    #[diesel(column_name = subjectDid)]
+    #[serde(rename = "subjectDid")]
+    pub subject_did: String,
+}
+
+/// A BBS post or reply on this PDS and the thread it's in, see
+/// [`crate::bbs::stats::index_threads`]
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_thread_index)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsThreadIndex {
+    pub uri: String,
+    pub did: String,
+    pub collection: String,
+    /// Uri of the thread's root post, or the root as a reply names it when
+    /// the post isn't hosted here
+    pub thread: String,
+    #[diesel(column_name = sectionId)]
+    #[serde(rename = "sectionId")]
+    pub section_id: Option<i64>,
+    #[diesel(column_name = indexedAt)]
+    #[serde(rename = "indexedAt")]
+    pub indexed_at: String,
+}
+
+/// A thread watched or muted with an `app.bbs.watchThread` record written on
+/// this PDS
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_thread_watch)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsThreadWatch {
+    pub uri: String,
+    pub did: String,
+    /// Uri of the thread's root post
+    pub thread: String,
+    pub muted: bool,
+}
+
+/// An `app.bbs.post` written on this PDS that the bridge mirrored to
+/// `app.bsky.feed.post`
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_crosspost)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsCrosspost {
+    pub uri: String,
+    pub did: String,
+    /// Uri of the mirror, unset until it's written
+    #[diesel(column_name = mirrorUri)]
+    #[serde(rename = "mirrorUri")]
+    pub mirror_uri: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+/// The URL an `app.bbs.post` written on this PDS unfurls into a card
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_post_link)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsPostLink {
+    pub uri: String,
+    pub url: String,
+}
+
+/// A BBS post or reply held for moderator review, see [`crate::bbs::classify`]
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::bbs_review)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsReview {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub source: String,
+    pub reason: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = resolvedAt)]
+    #[serde(rename = "resolvedAt")]
+    pub resolved_at: Option<String>,
+    /// Kept out of reads until resolved, see [`crate::bbs::automod`]
+    pub held: bool,
+}
+
+/// OpenGraph metadata unfurled from a linked URL, see [`crate::bbs::unfurl`]
+#[derive(
+    Queryable, Identifiable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize,
+)]
+#[diesel(primary_key(url))]
+#[diesel(table_name = crate::schema::pds::bbs_link_card)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsLinkCard {
+    pub url: String,
+    /// `ready` once fetched, `failed` otherwise
+    pub status: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[diesel(column_name = thumbCid)]
+    #[serde(rename = "thumbCid")]
+    pub thumb_cid: Option<String>,
+    /// JPEG thumbnail of the page's og:image
+    #[serde(skip)]
+    pub thumb: Option<Vec<u8>>,
+    pub attempts: i32,
+    /// When a failed fetch is retried, `None` once it won't be
+    #[diesel(column_name = nextAttemptAt)]
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: Option<String>,
+    #[diesel(column_name = fetchedAt)]
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    AsChangeset,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(section_id))]
+#[diesel(table_name = crate::schema::pds::bbs_section_stats)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsSectionStats {
+    #[diesel(column_name = sectionId)]
+    #[serde(rename = "sectionId")]
+    pub section_id: i64,
+    #[diesel(column_name = totalPosts)]
+    #[serde(rename = "totalPosts")]
+    pub total_posts: i64,
+    #[diesel(column_name = totalReplies)]
+    #[serde(rename = "totalReplies")]
+    pub total_replies: i64,
+    #[diesel(column_name = posts7d)]
+    #[serde(rename = "posts7d")]
+    pub posts_7d: i64,
+    #[diesel(column_name = posts30d)]
+    #[serde(rename = "posts30d")]
+    pub posts_30d: i64,
+    #[diesel(column_name = lastActivityAt)]
+    #[serde(rename = "lastActivityAt")]
+    pub last_activity_at: Option<String>,
+    #[diesel(column_name = computedAt)]
+    #[serde(rename = "computedAt")]
+    pub computed_at: String,
+}
+
+/// A moderator-set BBS section status, see
+/// [`crate::apis::app::bbs::moderation::set_section_status`]
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    AsChangeset,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(section_id))]
+#[diesel(table_name = crate::schema::pds::bbs_section)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsSection {
+    #[diesel(column_name = sectionId)]
+    #[serde(rename = "sectionId")]
+    pub section_id: i64,
+    pub status: String,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+    #[diesel(column_name = updatedBy)]
+    #[serde(rename = "updatedBy")]
+    pub updated_by: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    AsChangeset,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(table_name = crate::schema::pds::bbs_stats)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsStats {
+    pub id: i32,
+    #[diesel(column_name = totalPosts)]
+    #[serde(rename = "totalPosts")]
+    pub total_posts: i64,
+    #[diesel(column_name = totalReplies)]
+    #[serde(rename = "totalReplies")]
+    pub total_replies: i64,
+    #[diesel(column_name = activeUsers7d)]
+    #[serde(rename = "activeUsers7d")]
+    pub active_users_7d: i64,
+    #[diesel(column_name = activeUsers30d)]
+    #[serde(rename = "activeUsers30d")]
+    pub active_users_30d: i64,
+    #[diesel(column_name = computedAt)]
+    #[serde(rename = "computedAt")]
+    pub computed_at: String,
+}
+
+/// A thread in the trending snapshot, see `crate::bbs::trending`
+#[derive(Queryable, Insertable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::pds::bbs_trending)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BbsTrending {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    #[diesel(column_name = sectionId)]
+    #[serde(rename = "sectionId")]
+    pub section_id: i64,
+    pub title: String,
+    pub text: String,
+    pub rank: i64,
+    #[diesel(column_name = hotScore)]
+    #[serde(rename = "hotScore")]
+    pub hot_score: f64,
+    #[diesel(column_name = voteScore)]
+    #[serde(rename = "voteScore")]
+    pub vote_score: i64,
+    #[diesel(column_name = replyCount)]
+    #[serde(rename = "replyCount")]
+    pub reply_count: i64,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = lastActivityAt)]
+    #[serde(rename = "lastActivityAt")]
+    pub last_activity_at: String,
+    #[diesel(column_name = computedAt)]
+    #[serde(rename = "computedAt")]
+    pub computed_at: String,
+}
+
 #[derive(
     Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Default, Serialize, Deserialize,
 )]
@@ -147,6 +564,69 @@ pub struct Blob {
     pub takedown_ref: Option<String>,
 }
 
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(cid, did))]
+#[diesel(table_name = crate::schema::pds::blob_gc)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BlobGc {
+    pub cid: String,
+    pub did: String,
+    pub size: i32,
+    #[diesel(column_name = quarantinedAt)]
+    #[serde(rename = "quarantinedAt")]
+    pub quarantined_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::blob_upload)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct BlobUpload {
+    pub id: String,
+    pub did: String,
+    #[diesel(column_name = mimeType)]
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    pub size: i32,
+    #[diesel(column_name = bytesReceived)]
+    #[serde(rename = "bytesReceived")]
+    pub bytes_received: i32,
+    #[diesel(column_name = expectedCid)]
+    #[serde(rename = "expectedCid")]
+    pub expected_cid: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+    #[diesel(column_name = chunkCount)]
+    #[serde(rename = "chunkCount")]
+    pub chunk_count: i32,
+}
+
 #[derive(
     Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Default, Serialize, Deserialize,
 )]
@@ -219,6 +699,19 @@ where
     }
 }
 
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(did))]
+#[diesel(table_name = crate::schema::pds::email_notification_pref)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct EmailNotificationPref {
+    pub did: String,
+    pub replies: bool,
+    pub mentions: bool,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
 #[derive(
     Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Default, Serialize, Deserialize,
 )]
@@ -357,6 +850,38 @@ pub struct RefreshToken {
     #[diesel(column_name = appPasswordName)]
     #[serde(rename = "appPasswordName")]
     pub app_password_name: Option<String>,
+    #[diesel(column_name = familyId)]
+    #[serde(rename = "familyId")]
+    pub family_id: Option<String>,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(uri))]
+#[diesel(table_name = crate::schema::pds::record_tombstone)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RecordTombstone {
+    pub uri: String,
+    pub did: String,
+    pub cid: String,
+    #[diesel(sql_type = Bytea)]
+    pub content: Vec<u8>,
+    #[diesel(column_name = repoRev)]
+    #[serde(rename = "repoRev")]
+    pub repo_rev: String,
+    #[diesel(column_name = deletedAt)]
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: String,
 }
 
 #[derive(
@@ -399,6 +924,12 @@ pub struct RepoRoot {
     #[diesel(column_name = indexedAt)]
     #[serde(rename = "indexedAt")]
     pub indexed_at: String,
+    #[diesel(column_name = issuedRev)]
+    #[serde(rename = "issuedRev")]
+    pub issued_rev: Option<String>,
+    #[diesel(column_name = issuedBase)]
+    #[serde(rename = "issuedBase")]
+    pub issued_base: Option<String>,
 }
 
 #[derive(
@@ -444,3 +975,238 @@ impl RepoSeq {
         }
     }
 }
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::scheduled_write)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ScheduledWrite {
+    pub id: i64,
+    pub did: String,
+    pub input: String,
+    #[diesel(column_name = publishAt)]
+    #[serde(rename = "publishAt")]
+    pub publish_at: String,
+    pub status: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = claimedUntil)]
+    #[serde(rename = "claimedUntil")]
+    pub claimed_until: Option<String>,
+    #[diesel(column_name = commitCid)]
+    #[serde(rename = "commitCid")]
+    pub commit_cid: Option<String>,
+    #[diesel(column_name = commitRev)]
+    #[serde(rename = "commitRev")]
+    pub commit_rev: Option<String>,
+    pub error: Option<String>,
+    #[diesel(column_name = finishedAt)]
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<String>,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::subscriber_cursor)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubscriberCursor {
+    pub id: String,
+    pub cursor: i64,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Insertable,
+    Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::webhook)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    pub collections: Vec<String>,
+    pub dids: Vec<String>,
+    pub cursor: i64,
+    pub attempts: i32,
+    #[diesel(column_name = nextAttemptAt)]
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: Option<String>,
+    #[diesel(column_name = lastError)]
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::webhook_dead_letter)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDeadLetter {
+    pub id: i64,
+    #[diesel(column_name = webhookId)]
+    #[serde(rename = "webhookId")]
+    pub webhook_id: String,
+    pub seq: i64,
+    pub payload: String,
+    pub attempts: i32,
+    #[diesel(column_name = lastError)]
+    #[serde(rename = "lastError")]
+    pub last_error: String,
+    #[diesel(column_name = failedAt)]
+    #[serde(rename = "failedAt")]
+    pub failed_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(seq))]
+#[diesel(table_name = crate::schema::pds::label)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Label {
+    pub seq: i64,
+    pub src: String,
+    pub uri: String,
+    pub cid: Option<String>,
+    pub val: String,
+    pub neg: bool,
+    pub cts: String,
+    pub exp: Option<String>,
+    pub sig: Vec<u8>,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::oauth_request)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthRequest {
+    pub id: String,
+    #[diesel(column_name = clientId)]
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[diesel(column_name = redirectUri)]
+    #[serde(rename = "redirectUri")]
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    #[diesel(column_name = codeChallenge)]
+    #[serde(rename = "codeChallenge")]
+    pub code_challenge: String,
+    #[diesel(column_name = loginHint)]
+    #[serde(rename = "loginHint")]
+    pub login_hint: Option<String>,
+    #[diesel(column_name = dpopJkt)]
+    #[serde(rename = "dpopJkt")]
+    pub dpop_jkt: String,
+    pub did: Option<String>,
+    pub code: Option<String>,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(refreshTokenId))]
+#[diesel(table_name = crate::schema::pds::oauth_session)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OAuthSession {
+    #[diesel(column_name = refreshTokenId)]
+    #[serde(rename = "refreshTokenId")]
+    pub refresh_token_id: String,
+    pub did: String,
+    #[diesel(column_name = clientId)]
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub scope: String,
+    #[diesel(column_name = dpopJkt)]
+    #[serde(rename = "dpopJkt")]
+    pub dpop_jkt: String,
+    #[diesel(column_name = accessTokenHash)]
+    #[serde(rename = "accessTokenHash")]
+    pub access_token_hash: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[diesel(column_name = updatedAt)]
+    #[serde(rename = "updatedAt")]
+    pub updated_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(id))]
+#[diesel(table_name = crate::schema::pds::revoked_session)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RevokedSession {
+    pub id: String,
+    pub did: String,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
+
+#[derive(
+    Queryable,
+    Identifiable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    Clone,
+    Debug,
+    PartialEq,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(primary_key(handle))]
+#[diesel(table_name = crate::schema::pds::handle_reservation)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct HandleReservation {
+    pub handle: String,
+    #[diesel(column_name = ckbAddress)]
+    #[serde(rename = "ckbAddress")]
+    pub ckb_address: String,
+    #[diesel(column_name = signingKey)]
+    #[serde(rename = "signingKey")]
+    pub signing_key: String,
+    #[diesel(column_name = reservedAt)]
+    #[serde(rename = "reservedAt")]
+    pub reserved_at: String,
+    #[diesel(column_name = expiresAt)]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
+
+#[derive(Queryable, Identifiable, Selectable, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[diesel(primary_key(did))]
+#[diesel(table_name = crate::schema::pds::signup_ip)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SignupIp {
+    pub did: String,
+    pub ip: String,
+    #[diesel(column_name = createdAt)]
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
@@ -0,0 +1,17 @@
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct Readiness {
+    /// Whether every dependency check passed
+    pub ready: bool,
+    pub checks: BTreeMap<String, DependencyStatus>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyStatus {
+    pub ok: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
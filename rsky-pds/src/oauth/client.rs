@@ -0,0 +1,184 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+use url::Url;
+
+const LOOPBACK_CLIENT_ID: &str = "http://localhost";
+const METADATA_FETCH_TIMEOUT_SECS: u64 = 5;
+
+/// OAuth client metadata document, published by the client at its `client_id` url.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClientMetadata {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub client_uri: Option<String>,
+    pub logo_uri: Option<String>,
+    pub redirect_uris: Vec<String>,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<String>,
+    #[serde(default = "default_response_types")]
+    pub response_types: Vec<String>,
+    pub scope: Option<String>,
+    pub token_endpoint_auth_method: Option<String>,
+    #[serde(default)]
+    pub dpop_bound_access_tokens: bool,
+}
+
+fn default_grant_types() -> Vec<String> {
+    vec!["authorization_code".to_string()]
+}
+
+fn default_response_types() -> Vec<String> {
+    vec!["code".to_string()]
+}
+
+/// Loopback clients have no metadata document: redirect uris and scope are taken
+/// from the client_id's query, falling back to the loopback addresses.
+fn loopback_client(client_id: &str) -> Result<ClientMetadata> {
+    let url = Url::parse(client_id)?;
+    if url.path() != "/" || url.port().is_some() {
+        bail!("Loopback client_id must not have a path or port");
+    }
+    let mut redirect_uris = vec![];
+    let mut scope = None;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "redirect_uri" => redirect_uris.push(value.to_string()),
+            "scope" => scope = Some(value.to_string()),
+            _ => (),
+        }
+    }
+    if redirect_uris.is_empty() {
+        redirect_uris = vec!["http://127.0.0.1/".to_string(), "http://[::1]/".to_string()];
+    }
+    Ok(ClientMetadata {
+        client_id: client_id.to_string(),
+        client_name: Some("Loopback client".to_string()),
+        client_uri: None,
+        logo_uri: None,
+        redirect_uris,
+        grant_types: vec![
+            "authorization_code".to_string(),
+            "refresh_token".to_string(),
+        ],
+        response_types: default_response_types(),
+        scope: Some(scope.unwrap_or("atproto".to_string())),
+        token_endpoint_auth_method: Some("none".to_string()),
+        dpop_bound_access_tokens: true,
+    })
+}
+
+pub fn validate_client_metadata(client_id: &str, metadata: &ClientMetadata) -> Result<()> {
+    if metadata.client_id != client_id {
+        bail!("client_id does not match the client metadata document");
+    }
+    if metadata.redirect_uris.is_empty() {
+        bail!("Client metadata must declare redirect_uris");
+    }
+    if !metadata.dpop_bound_access_tokens {
+        bail!("Client metadata must set dpop_bound_access_tokens");
+    }
+    if !metadata
+        .grant_types
+        .contains(&"authorization_code".to_string())
+    {
+        bail!("Client must support the authorization_code grant");
+    }
+    if !metadata.response_types.contains(&"code".to_string()) {
+        bail!("Client must support the code response type");
+    }
+    // Only public clients for now, confidential clients need private_key_jwt
+    match metadata.token_endpoint_auth_method.as_deref() {
+        None | Some("none") => (),
+        Some(method) => bail!("Unsupported token_endpoint_auth_method: {method}"),
+    }
+    match metadata.scope {
+        Some(ref scope) if scope.split(' ').any(|s| s == "atproto") => Ok(()),
+        _ => bail!("Client metadata scope must include atproto"),
+    }
+}
+
+/// Every requested scope must be one the client declared.
+pub fn check_scope(metadata: &ClientMetadata, scope: &str) -> Result<()> {
+    let allowed = metadata.scope.clone().unwrap_or_default();
+    let allowed = allowed.split(' ').collect::<Vec<&str>>();
+    if !scope.split(' ').any(|s| s == "atproto") {
+        bail!("Requested scope must include atproto");
+    }
+    match scope.split(' ').find(|s| !allowed.contains(s)) {
+        Some(unknown) => bail!("Scope was not declared by the client: {unknown}"),
+        None => Ok(()),
+    }
+}
+
+pub fn check_redirect_uri(metadata: &ClientMetadata, redirect_uri: &str) -> Result<()> {
+    if metadata.redirect_uris.iter().any(|uri| uri == redirect_uri) {
+        return Ok(());
+    }
+    // Loopback redirects may use any port (RFC 8252)
+    if metadata.client_id.starts_with(LOOPBACK_CLIENT_ID) {
+        if let Ok(mut requested) = Url::parse(redirect_uri) {
+            let _ = requested.set_port(None);
+            if metadata
+                .redirect_uris
+                .iter()
+                .any(|uri| Url::parse(uri).ok().as_ref() == Some(&requested))
+            {
+                return Ok(());
+            }
+        }
+    }
+    bail!("redirect_uri was not declared by the client")
+}
+
+/// Resolves and validates the metadata for `client_id`.
+pub async fn resolve_client(client_id: &str, allow_loopback: bool) -> Result<ClientMetadata> {
+    if client_id.starts_with(LOOPBACK_CLIENT_ID) {
+        if !allow_loopback {
+            bail!("Loopback clients are not allowed on this server");
+        }
+        return loopback_client(client_id);
+    }
+    let url = Url::parse(client_id)?;
+    if url.scheme() != "https" || url.fragment().is_some() {
+        bail!("client_id must be an https url");
+    }
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(METADATA_FETCH_TIMEOUT_SECS))
+        .build()?;
+    let res = client
+        .get(url)
+        .header("Accept", "application/json")
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        bail!("Failed to fetch client metadata: {}", res.status());
+    }
+    let metadata = res.json::<ClientMetadata>().await?;
+    validate_client_metadata(client_id, &metadata)?;
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_client_from_query() {
+        let metadata = loopback_client(
+            "http://localhost?redirect_uri=http%3A%2F%2F127.0.0.1%2Fcallback&scope=atproto%20transition%3Ageneric",
+        )
+        .unwrap();
+        assert_eq!(metadata.redirect_uris, vec!["http://127.0.0.1/callback"]);
+        assert!(check_scope(&metadata, "atproto transition:generic").is_ok());
+        assert!(check_redirect_uri(&metadata, "http://127.0.0.1:8080/callback").is_ok());
+        assert!(check_redirect_uri(&metadata, "http://127.0.0.1/other").is_err());
+    }
+
+    #[test]
+    fn rejects_undeclared_scope() {
+        let metadata = loopback_client("http://localhost").unwrap();
+        assert!(check_scope(&metadata, "atproto").is_ok());
+        assert!(check_scope(&metadata, "atproto transition:generic").is_err());
+        assert!(check_scope(&metadata, "transition:generic").is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use rsky_common::env::env_str;
+use rsky_crypto::types::VerifyOptions;
+use rsky_crypto::{p256, secp256k1};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use thiserror::Error;
+use url::Url;
+
+/// Proofs issued further than this from the server's clock are rejected
+const PROOF_MAX_AGE_SECS: u64 = 60;
+/// Server nonces rotate this often, the previous one stays valid for a rotation
+const NONCE_ROTATION_SECS: u64 = 180;
+
+lazy_static! {
+    /// Proof jtis seen within the last PROOF_MAX_AGE_SECS, to reject replays
+    static ref SEEN_JTIS: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DpopError {
+    #[error("use_dpop_nonce")]
+    UseDpopNonce,
+    #[error("{0}")]
+    InvalidProof(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub y: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DpopHeader {
+    typ: String,
+    alg: String,
+    jwk: Jwk,
+}
+
+#[derive(Debug, Deserialize)]
+struct DpopClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: u64,
+    ath: Option<String>,
+    nonce: Option<String>,
+}
+
+pub struct VerifyProofOpts<'a> {
+    pub htm: &'a str,
+    pub htu: &'a str,
+    /// The access token the proof is presented with, checked against `ath`
+    pub access_token: Option<&'a str>,
+    pub require_nonce: bool,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in seconds since UNIX epoch")
+        .as_secs()
+}
+
+fn invalid(message: &str) -> DpopError {
+    DpopError::InvalidProof(message.to_string())
+}
+
+fn b64url(bytes: &[u8]) -> String {
+    base64_url::encode(bytes).replace("=", "")
+}
+
+/// SHA-256 of the access token, as carried in a proof's `ath` claim
+pub fn access_token_hash(token: &str) -> String {
+    b64url(Sha256::digest(token).as_slice())
+}
+
+/// JWK SHA-256 thumbprint (RFC 7638), which access tokens are bound to
+pub fn jwk_thumbprint(jwk: &Jwk) -> String {
+    let canonical = format!(
+        r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#,
+        jwk.crv, jwk.kty, jwk.x, jwk.y
+    );
+    b64url(Sha256::digest(canonical).as_slice())
+}
+
+fn nonce_secret() -> String {
+    env_str("PDS_DPOP_SECRET")
+        .or_else(|| env_str("PDS_JWT_KEY_K256_PRIVATE_KEY_HEX"))
+        .unwrap_or_default()
+}
+
+fn nonce_for(counter: u64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(nonce_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(&counter.to_be_bytes());
+    b64url(&mac.finalize().into_bytes()[..16])
+}
+
+/// The nonce clients are told to use in their next proof via the DPoP-Nonce header
+pub fn current_nonce() -> String {
+    nonce_for(now() / NONCE_ROTATION_SECS)
+}
+
+pub fn is_valid_nonce(nonce: &str) -> bool {
+    let counter = now() / NONCE_ROTATION_SECS;
+    nonce == nonce_for(counter) || nonce == nonce_for(counter.saturating_sub(1))
+}
+
+/// Compares `htu` ignoring query and fragment, as RFC 9449 requires
+fn same_htu(htu: &str, expected: &str) -> bool {
+    match (Url::parse(htu), Url::parse(expected)) {
+        (Ok(mut htu), Ok(mut expected)) => {
+            htu.set_query(None);
+            htu.set_fragment(None);
+            expected.set_query(None);
+            expected.set_fragment(None);
+            htu == expected
+        }
+        _ => false,
+    }
+}
+
+fn verify_signature(
+    header: &DpopHeader,
+    signing_input: &[u8],
+    sig: &[u8],
+) -> Result<bool, DpopError> {
+    let Jwk { kty, crv, x, y } = &header.jwk;
+    let x = base64_url::decode(x).map_err(|_| invalid("Invalid DPoP key"))?;
+    let y = base64_url::decode(y).map_err(|_| invalid("Invalid DPoP key"))?;
+    if kty != "EC" || x.len() != 32 || y.len() != 32 {
+        return Err(invalid("Unsupported DPoP key"));
+    }
+    let point = [vec![0x04], x, y].concat();
+    let opts = Some(VerifyOptions {
+        allow_malleable_sig: Some(true),
+    });
+    let verified = match (header.alg.as_str(), crv.as_str()) {
+        ("ES256", "P-256") => p256::operations::verify_sig(&point, signing_input, sig, opts),
+        ("ES256K", "secp256k1") => secp256k1::operations::verify_sig(
+            &point,
+            Sha256::digest(signing_input).as_slice(),
+            sig,
+            opts,
+        ),
+        _ => return Err(invalid("Unsupported DPoP proof algorithm")),
+    };
+    verified.map_err(|_| invalid("Invalid DPoP proof signature"))
+}
+
+fn check_replay(jti: &str, now: u64) -> Result<(), DpopError> {
+    let mut seen = SEEN_JTIS.lock().expect("DPoP jti cache poisoned");
+    seen.retain(|_, expires_at| *expires_at > now);
+    if seen.contains_key(jti) {
+        return Err(invalid("DPoP proof replayed"));
+    }
+    seen.insert(jti.to_string(), now + 2 * PROOF_MAX_AGE_SECS);
+    Ok(())
+}
+
+/// Verifies a DPoP proof JWT and returns the thumbprint of the key it was signed with.
+pub fn verify_proof(proof: &str, opts: VerifyProofOpts) -> Result<String, DpopError> {
+    let parts = proof.split(".").collect::<Vec<&str>>();
+    let [header_b64, claims_b64, sig_b64] = parts[..] else {
+        return Err(invalid("Malformed DPoP proof"));
+    };
+    let header: DpopHeader = base64_url::decode(header_b64)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| invalid("Malformed DPoP proof header"))?;
+    let claims: DpopClaims = base64_url::decode(claims_b64)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| invalid("Malformed DPoP proof payload"))?;
+    if header.typ != "dpop+jwt" {
+        return Err(invalid("Invalid DPoP proof type"));
+    }
+    if !claims.htm.eq_ignore_ascii_case(opts.htm) {
+        return Err(invalid("DPoP htm mismatch"));
+    }
+    if !same_htu(&claims.htu, opts.htu) {
+        return Err(invalid("DPoP htu mismatch"));
+    }
+    let now = now();
+    if claims.iat.abs_diff(now) > PROOF_MAX_AGE_SECS {
+        return Err(invalid("DPoP proof expired"));
+    }
+    if let Some(access_token) = opts.access_token {
+        if claims.ath.as_deref() != Some(access_token_hash(access_token).as_str()) {
+            return Err(invalid("DPoP ath mismatch"));
+        }
+    }
+    match claims.nonce {
+        Some(ref nonce) if is_valid_nonce(nonce) => (),
+        _ if opts.require_nonce => return Err(DpopError::UseDpopNonce),
+        _ => (),
+    }
+    let sig = base64_url::decode(sig_b64).map_err(|_| invalid("Malformed DPoP proof"))?;
+    let signing_input = format!("{header_b64}.{claims_b64}");
+    if !verify_signature(&header, signing_input.as_bytes(), &sig)? {
+        return Err(invalid("Invalid DPoP proof signature"));
+    }
+    check_replay(&claims.jti, now)?;
+    Ok(jwk_thumbprint(&header.jwk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::secp256k1::{Message, Secp256k1, SecretKey};
+    use serde_json::json;
+
+    const HTU: &str = "https://pds.example.com/oauth/token";
+
+    fn proof(jti: &str, htm: &str, nonce: Option<String>) -> (String, String) {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let point = secret_key.public_key(&secp).serialize_uncompressed();
+        let jwk = Jwk {
+            kty: "EC".to_string(),
+            crv: "secp256k1".to_string(),
+            x: b64url(&point[1..33]),
+            y: b64url(&point[33..]),
+        };
+        let header = json!({ "typ": "dpop+jwt", "alg": "ES256K", "jwk": jwk });
+        let claims = json!({ "jti": jti, "htm": htm, "htu": HTU, "iat": now(), "nonce": nonce });
+        let signing_input = format!(
+            "{}.{}",
+            b64url(header.to_string().as_bytes()),
+            b64url(claims.to_string().as_bytes())
+        );
+        let digest = Sha256::digest(signing_input.as_bytes());
+        let message = Message::from_digest_slice(digest.as_slice()).unwrap();
+        let sig = secp.sign_ecdsa(&message, &secret_key).serialize_compact();
+        (
+            format!("{signing_input}.{}", b64url(&sig)),
+            jwk_thumbprint(&jwk),
+        )
+    }
+
+    fn opts(htm: &str) -> VerifyProofOpts {
+        VerifyProofOpts {
+            htm,
+            htu: HTU,
+            access_token: None,
+            require_nonce: true,
+        }
+    }
+
+    #[test]
+    fn verifies_proof_and_rejects_replay() {
+        let (jwt, jkt) = proof("jti-verify", "POST", Some(current_nonce()));
+        assert_eq!(verify_proof(&jwt, opts("POST")), Ok(jkt));
+        assert_eq!(
+            verify_proof(&jwt, opts("POST")),
+            Err(invalid("DPoP proof replayed"))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_method_and_missing_nonce() {
+        let (jwt, _) = proof("jti-method", "GET", Some(current_nonce()));
+        assert_eq!(
+            verify_proof(&jwt, opts("POST")),
+            Err(invalid("DPoP htm mismatch"))
+        );
+        let (jwt, _) = proof("jti-nonce", "POST", None);
+        assert_eq!(
+            verify_proof(&jwt, opts("POST")),
+            Err(DpopError::UseDpopNonce)
+        );
+    }
+
+    #[test]
+    fn htu_ignores_query() {
+        assert!(same_htu(&format!("{HTU}?a=b"), HTU));
+        assert!(!same_htu("https://other.example.com/oauth/token", HTU));
+    }
+}
@@ -0,0 +1,434 @@
+//! OAuth 2.0 authorization server for atproto clients: pushed authorization
+//! requests, DPoP-bound tokens and an authorize UI that signs in password and
+//! web5 (CKB wallet) accounts. Issued tokens are regular account_manager
+//! sessions, so they refresh and revoke like any other session.
+
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::models;
+use crate::oauth::dpop::{access_token_hash, verify_proof, DpopError, VerifyProofOpts};
+use anyhow::{bail, Result};
+use diesel::prelude::*;
+use diesel::{delete, insert_into, update};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use rsky_common::get_random_str;
+use rsky_common::time::from_millis_to_str;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::time::SystemTime;
+
+pub mod client;
+pub mod dpop;
+pub mod routes;
+
+pub const REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+pub const SCOPES_SUPPORTED: [&str; 2] = ["atproto", "transition:generic"];
+/// OAuth sessions are app-password scoped, named after the client they were granted to
+pub const APP_PASSWORD_NAME_PREFIX: &str = "oauth:";
+
+/// Errors in the RFC 6749 `{ error, error_description }` shape. Every OAuth
+/// response carries a fresh DPoP-Nonce header.
+#[derive(Debug, PartialEq)]
+pub enum OAuthError {
+    InvalidRequest(String),
+    InvalidClient(String),
+    InvalidGrant(String),
+    InvalidScope(String),
+    InvalidDpopProof(String),
+    UseDpopNonce,
+    AccessDenied(String),
+    ServerError,
+}
+
+impl From<DpopError> for OAuthError {
+    fn from(error: DpopError) -> Self {
+        match error {
+            DpopError::UseDpopNonce => OAuthError::UseDpopNonce,
+            DpopError::InvalidProof(message) => OAuthError::InvalidDpopProof(message),
+        }
+    }
+}
+
+impl From<anyhow::Error> for OAuthError {
+    fn from(error: anyhow::Error) -> Self {
+        tracing::error!("@LOG: ERROR: {error}");
+        OAuthError::ServerError
+    }
+}
+
+impl OAuthError {
+    fn parts(&self) -> (Status, &str, String) {
+        match self {
+            OAuthError::InvalidRequest(message) => {
+                (Status::BadRequest, "invalid_request", message.clone())
+            }
+            OAuthError::InvalidClient(message) => {
+                (Status::BadRequest, "invalid_client", message.clone())
+            }
+            OAuthError::InvalidGrant(message) => {
+                (Status::BadRequest, "invalid_grant", message.clone())
+            }
+            OAuthError::InvalidScope(message) => {
+                (Status::BadRequest, "invalid_scope", message.clone())
+            }
+            OAuthError::InvalidDpopProof(message) => {
+                (Status::BadRequest, "invalid_dpop_proof", message.clone())
+            }
+            OAuthError::UseDpopNonce => (
+                Status::BadRequest,
+                "use_dpop_nonce",
+                "Authorization server requires nonce in DPoP proof".to_string(),
+            ),
+            OAuthError::AccessDenied(message) => {
+                (Status::Forbidden, "access_denied", message.clone())
+            }
+            OAuthError::ServerError => (
+                Status::InternalServerError,
+                "server_error",
+                "Something went wrong".to_string(),
+            ),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for OAuthError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let (status, error, error_description) = self.parts();
+        let body = json!({ "error": error, "error_description": error_description });
+        OAuthJson(status, body).respond_to(req)
+    }
+}
+
+/// A JSON OAuth response with the current DPoP-Nonce attached.
+pub struct OAuthJson(pub Status, pub serde_json::Value);
+
+impl<'r> Responder<'r, 'static> for OAuthJson {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        let bytes = serde_json::to_vec(&self.1).unwrap();
+        Response::build()
+            .status(self.0)
+            .header(ContentType::JSON)
+            .header(Header::new("DPoP-Nonce", dpop::current_nonce()))
+            .header(Header::new("Cache-Control", "no-store"))
+            .sized_body(bytes.len(), Cursor::new(bytes))
+            .ok()
+    }
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in millis since UNIX epoch")
+        .as_millis() as i64
+}
+
+/// PKCE S256: the challenge is the unpadded base64url SHA-256 of the verifier
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    let hash = Sha256::digest(code_verifier);
+    base64_url::encode(hash.as_slice()).replace("=", "") == code_challenge
+}
+
+pub fn request_uri(id: &str) -> String {
+    format!("{REQUEST_URI_PREFIX}{id}")
+}
+
+pub fn request_id_from_uri(request_uri: &str) -> Option<&str> {
+    request_uri.strip_prefix(REQUEST_URI_PREFIX)
+}
+
+pub struct CreateRequestOpts {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub login_hint: Option<String>,
+    pub dpop_jkt: String,
+    pub expires_in_ms: u64,
+}
+
+pub async fn create_request(opts: CreateRequestOpts, db: &DbConn) -> Result<String> {
+    use crate::schema::pds::oauth_request::dsl as RequestSchema;
+
+    let id = format!("req-{}", get_random_str());
+    let expires_at = from_millis_to_str(now_millis() + opts.expires_in_ms as i64);
+    let row = (
+        RequestSchema::id.eq(id.clone()),
+        RequestSchema::clientId.eq(opts.client_id),
+        RequestSchema::redirectUri.eq(opts.redirect_uri),
+        RequestSchema::scope.eq(opts.scope),
+        RequestSchema::state.eq(opts.state),
+        RequestSchema::codeChallenge.eq(opts.code_challenge),
+        RequestSchema::loginHint.eq(opts.login_hint),
+        RequestSchema::dpopJkt.eq(opts.dpop_jkt),
+        RequestSchema::expiresAt.eq(expires_at),
+    );
+    db.run(move |conn| {
+        insert_into(RequestSchema::oauth_request)
+            .values(row)
+            .execute(conn)
+    })
+    .await?;
+    Ok(id)
+}
+
+/// An unexpired request that hasn't been exchanged yet.
+pub async fn get_request(id: String, db: &DbConn) -> Result<Option<models::OAuthRequest>> {
+    use crate::schema::pds::oauth_request::dsl as RequestSchema;
+
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        RequestSchema::oauth_request
+            .filter(RequestSchema::id.eq(id))
+            .filter(RequestSchema::expiresAt.gt(now))
+            .select(models::OAuthRequest::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(Into::into)
+}
+
+/// Records that `did` approved the request and returns the authorization code.
+pub async fn authorize_request(id: String, did: String, db: &DbConn) -> Result<String> {
+    use crate::schema::pds::oauth_request::dsl as RequestSchema;
+
+    let code = format!("cod-{}", get_random_str());
+    let now = rsky_common::now();
+    let code_clone = code.clone();
+    let updated = db
+        .run(move |conn| {
+            update(RequestSchema::oauth_request)
+                .filter(RequestSchema::id.eq(id))
+                .filter(RequestSchema::code.is_null())
+                .filter(RequestSchema::expiresAt.gt(now))
+                .set((
+                    RequestSchema::did.eq(did),
+                    RequestSchema::code.eq(code_clone),
+                ))
+                .execute(conn)
+        })
+        .await?;
+    if updated == 0 {
+        bail!("Authorization request expired or already used");
+    }
+    Ok(code)
+}
+
+/// Removes and returns the request an authorization code was issued for. Codes
+/// are single use, a second exchange finds nothing.
+pub async fn consume_code(code: String, db: &DbConn) -> Result<Option<models::OAuthRequest>> {
+    use crate::schema::pds::oauth_request::dsl as RequestSchema;
+
+    let now = rsky_common::now();
+    let request = db
+        .run(move |conn| {
+            delete(RequestSchema::oauth_request)
+                .filter(RequestSchema::code.eq(code))
+                .returning(models::OAuthRequest::as_returning())
+                .get_result(conn)
+                .optional()
+        })
+        .await?;
+    Ok(request.filter(|request| request.expires_at > now && request.did.is_some()))
+}
+
+pub async fn delete_expired_requests(db: &DbConn) -> Result<()> {
+    use crate::schema::pds::oauth_request::dsl as RequestSchema;
+
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        delete(RequestSchema::oauth_request)
+            .filter(RequestSchema::expiresAt.le(now))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+pub struct CreateSessionOpts {
+    pub refresh_token_id: String,
+    pub did: String,
+    pub client_id: String,
+    pub scope: String,
+    pub dpop_jkt: String,
+    pub access_token: String,
+}
+
+pub async fn create_session(opts: CreateSessionOpts, db: &DbConn) -> Result<()> {
+    use crate::schema::pds::oauth_session::dsl as SessionSchema;
+
+    let now = rsky_common::now();
+    let row = (
+        SessionSchema::refreshTokenId.eq(opts.refresh_token_id),
+        SessionSchema::did.eq(opts.did),
+        SessionSchema::clientId.eq(opts.client_id),
+        SessionSchema::scope.eq(opts.scope),
+        SessionSchema::dpopJkt.eq(opts.dpop_jkt),
+        SessionSchema::accessTokenHash.eq(access_token_hash(&opts.access_token)),
+        SessionSchema::createdAt.eq(now.clone()),
+        SessionSchema::updatedAt.eq(now),
+    );
+    db.run(move |conn| {
+        insert_into(SessionSchema::oauth_session)
+            .values(row)
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+pub async fn get_session(
+    refresh_token_id: String,
+    db: &DbConn,
+) -> Result<Option<models::OAuthSession>> {
+    use crate::schema::pds::oauth_session::dsl as SessionSchema;
+
+    db.run(move |conn| {
+        SessionSchema::oauth_session
+            .filter(SessionSchema::refreshTokenId.eq(refresh_token_id))
+            .select(models::OAuthSession::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(Into::into)
+}
+
+pub async fn get_session_by_access_token(
+    access_token: &str,
+    db: &DbConn,
+) -> Result<Option<models::OAuthSession>> {
+    use crate::schema::pds::oauth_session::dsl as SessionSchema;
+
+    let hash = access_token_hash(access_token);
+    db.run(move |conn| {
+        SessionSchema::oauth_session
+            .filter(SessionSchema::accessTokenHash.eq(hash))
+            .select(models::OAuthSession::as_select())
+            .first(conn)
+            .optional()
+    })
+    .await
+    .map_err(Into::into)
+}
+
+/// Moves a session onto the refresh token and access token it was rotated to.
+pub async fn rotate_session(
+    refresh_token_id: String,
+    next_refresh_token_id: String,
+    access_token: &str,
+    db: &DbConn,
+) -> Result<()> {
+    use crate::schema::pds::oauth_session::dsl as SessionSchema;
+
+    let hash = access_token_hash(access_token);
+    let now = rsky_common::now();
+    db.run(move |conn| {
+        update(SessionSchema::oauth_session)
+            .filter(SessionSchema::refreshTokenId.eq(refresh_token_id))
+            .set((
+                SessionSchema::refreshTokenId.eq(next_refresh_token_id),
+                SessionSchema::accessTokenHash.eq(hash),
+                SessionSchema::updatedAt.eq(now),
+            ))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_session(refresh_token_id: String, db: &DbConn) -> Result<()> {
+    use crate::schema::pds::oauth_session::dsl as SessionSchema;
+
+    db.run(move |conn| {
+        delete(SessionSchema::oauth_session)
+            .filter(SessionSchema::refreshTokenId.eq(refresh_token_id))
+            .execute(conn)
+    })
+    .await?;
+    Ok(())
+}
+
+pub fn is_dpop_token(request: &Request) -> bool {
+    match request.headers().get_one("Authorization") {
+        None => false,
+        Some(auth_header) => auth_header.starts_with("DPoP "),
+    }
+}
+
+pub fn dpop_token_from_req(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|header| header.strip_prefix("DPoP "))
+        .map(|token| token.to_string())
+}
+
+/// Whether `token` is the access token of an OAuth session
+pub async fn is_oauth_access_token<'r>(request: &'r Request<'_>, token: &str) -> Result<bool> {
+    let db = match request.guard::<DbConn>().await {
+        Outcome::Success(db) => db,
+        _ => bail!("Database unavailable"),
+    };
+    Ok(get_session_by_access_token(token, &db).await?.is_some())
+}
+
+/// Checks the DPoP proof sent alongside an OAuth access token on a resource
+/// request, and that the proof key is the one the token was issued to.
+pub async fn verify_dpop_bound_token<'r>(request: &'r Request<'_>, token: &str) -> Result<()> {
+    let Some(proof) = request.headers().get_one("DPoP") else {
+        bail!("Missing DPoP proof");
+    };
+    let Some(cfg) = request.rocket().state::<ServerConfig>() else {
+        bail!("Server config unavailable");
+    };
+    let htu = format!("{}{}", cfg.service.public_url, request.uri().path());
+    let jkt = verify_proof(
+        proof,
+        VerifyProofOpts {
+            htm: request.method().as_str(),
+            htu: &htu,
+            access_token: Some(token),
+            require_nonce: false,
+        },
+    )?;
+    let db = match request.guard::<DbConn>().await {
+        Outcome::Success(db) => db,
+        _ => bail!("Database unavailable"),
+    };
+    match get_session_by_access_token(token, &db).await? {
+        Some(session) if session.dpop_jkt == jkt => Ok(()),
+        Some(_) => bail!("DPoP key does not match the access token"),
+        None => bail!("OAuth session not found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_s256() {
+        // RFC 7636 appendix B
+        assert!(verify_pkce(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        ));
+        assert!(!verify_pkce(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk",
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cN"
+        ));
+    }
+
+    #[test]
+    fn request_uri_round_trip() {
+        let uri = request_uri("req-abc");
+        assert_eq!(uri, "urn:ietf:params:oauth:request_uri:req-abc");
+        assert_eq!(request_id_from_uri(&uri), Some("req-abc"));
+        assert_eq!(request_id_from_uri("req-abc"), None);
+    }
+}
@@ -0,0 +1,558 @@
+use crate::account_manager::helpers::auth::{decode_refresh_token, ACCESS_TOKEN_EXPIRES_IN_SECS};
+use crate::account_manager::helpers::jwt_keys::JWT_KEYS;
+use crate::account_manager::AccountManager;
+use crate::apis::com::atproto::web5::index_action::{verify_index_action, VerifiedIndexAction};
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::db::DbConn;
+use crate::identity::SharedDidMethods;
+use crate::oauth::client::{check_redirect_uri, check_scope, resolve_client};
+use crate::oauth::dpop::{verify_proof, VerifyProofOpts};
+use crate::oauth::{
+    authorize_request, consume_code, create_request, create_session, delete_expired_requests,
+    delete_session, get_request, get_session, get_session_by_access_token, request_id_from_uri,
+    request_uri, rotate_session, verify_pkce, CreateRequestOpts, CreateSessionOpts, OAuthError,
+    OAuthJson, APP_PASSWORD_NAME_PREFIX, SCOPES_SUPPORTED,
+};
+use anyhow::Result;
+use rocket::form::Form;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect;
+use rocket::serde::json::Json;
+use rocket::{FromForm, State};
+use rsky_lexicon::com::atproto::web5::{IndexActionInput, IndexActionInputRef};
+use serde_json::json;
+use url::Url;
+
+/// The `DPoP` proof header, if the client sent one.
+pub struct DpopProofHeader(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DpopProofHeader {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(DpopProofHeader(
+            req.headers().get_one("DPoP").map(|proof| proof.to_string()),
+        ))
+    }
+}
+
+/// Verifies the DPoP proof sent to one of the authorization server's endpoints.
+fn verify_endpoint_proof(
+    proof: DpopProofHeader,
+    cfg: &ServerConfig,
+    path: &str,
+) -> Result<String, OAuthError> {
+    let Some(proof) = proof.0 else {
+        return Err(OAuthError::InvalidDpopProof(
+            "Missing DPoP proof".to_string(),
+        ));
+    };
+    let htu = format!("{}{path}", cfg.oauth.issuer);
+    Ok(verify_proof(
+        &proof,
+        VerifyProofOpts {
+            htm: "POST",
+            htu: &htu,
+            access_token: None,
+            require_nonce: true,
+        },
+    )?)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn redirect_with_code(
+    request: &crate::models::OAuthRequest,
+    code: &str,
+    issuer: &str,
+) -> Result<String, OAuthError> {
+    let mut url = Url::parse(&request.redirect_uri)
+        .map_err(|_| OAuthError::InvalidRequest("Invalid redirect_uri".to_string()))?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("code", code);
+        if let Some(ref state) = request.state {
+            query.append_pair("state", state);
+        }
+        query.append_pair("iss", issuer);
+    }
+    Ok(url.to_string())
+}
+
+fn token_response(
+    access_token: String,
+    refresh_token: String,
+    scope: String,
+    did: String,
+) -> OAuthJson {
+    OAuthJson(
+        Status::Ok,
+        json!({
+            "access_token": access_token,
+            "token_type": "DPoP",
+            "expires_in": ACCESS_TOKEN_EXPIRES_IN_SECS,
+            "refresh_token": refresh_token,
+            "scope": scope,
+            "sub": did,
+        }),
+    )
+}
+
+#[rocket::get("/.well-known/oauth-authorization-server")]
+pub async fn authorization_server_metadata(cfg: &State<ServerConfig>) -> OAuthJson {
+    let issuer = &cfg.oauth.issuer;
+    OAuthJson(
+        Status::Ok,
+        json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/oauth/authorize"),
+            "token_endpoint": format!("{issuer}/oauth/token"),
+            "pushed_authorization_request_endpoint": format!("{issuer}/oauth/par"),
+            "revocation_endpoint": format!("{issuer}/oauth/revoke"),
+            "require_pushed_authorization_requests": true,
+            "response_types_supported": ["code"],
+            "response_modes_supported": ["query"],
+            "grant_types_supported": ["authorization_code", "refresh_token"],
+            "code_challenge_methods_supported": ["S256"],
+            "scopes_supported": SCOPES_SUPPORTED,
+            "subject_types_supported": ["public"],
+            "token_endpoint_auth_methods_supported": ["none"],
+            "dpop_signing_alg_values_supported": ["ES256", "ES256K"],
+            "authorization_response_iss_parameter_supported": true,
+            "client_id_metadata_document_supported": true,
+        }),
+    )
+}
+
+#[rocket::get("/.well-known/oauth-protected-resource")]
+pub async fn protected_resource_metadata(cfg: &State<ServerConfig>) -> OAuthJson {
+    OAuthJson(
+        Status::Ok,
+        json!({
+            "resource": cfg.service.public_url,
+            "authorization_servers": [cfg.oauth.issuer],
+            "scopes_supported": SCOPES_SUPPORTED,
+            "bearer_methods_supported": ["header"],
+        }),
+    )
+}
+
+#[derive(FromForm)]
+pub struct ParRequest {
+    client_id: String,
+    response_type: String,
+    redirect_uri: String,
+    scope: String,
+    state: Option<String>,
+    code_challenge: String,
+    code_challenge_method: String,
+    login_hint: Option<String>,
+}
+
+/// Pushed authorization request (RFC 9126), the only way to start a flow here.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/oauth/par", data = "<body>")]
+pub async fn par(
+    body: Form<ParRequest>,
+    proof: DpopProofHeader,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let dpop_jkt = verify_endpoint_proof(proof, cfg, "/oauth/par")?;
+    let body = body.into_inner();
+    if body.response_type != "code" {
+        return Err(OAuthError::InvalidRequest(
+            "response_type must be code".to_string(),
+        ));
+    }
+    if body.code_challenge_method != "S256" {
+        return Err(OAuthError::InvalidRequest(
+            "code_challenge_method must be S256".to_string(),
+        ));
+    }
+    let client = resolve_client(&body.client_id, cfg.oauth.allow_loopback_clients)
+        .await
+        .map_err(|error| OAuthError::InvalidClient(error.to_string()))?;
+    check_redirect_uri(&client, &body.redirect_uri)
+        .map_err(|error| OAuthError::InvalidRequest(error.to_string()))?;
+    check_scope(&client, &body.scope)
+        .map_err(|error| OAuthError::InvalidScope(error.to_string()))?;
+
+    // take the chance to tidy expired requests, best-effort
+    if let Err(error) = delete_expired_requests(&db).await {
+        tracing::warn!("failed to delete expired oauth requests: {error}");
+    }
+    let id = create_request(
+        CreateRequestOpts {
+            client_id: body.client_id,
+            redirect_uri: body.redirect_uri,
+            scope: body.scope,
+            state: body.state,
+            code_challenge: body.code_challenge,
+            login_hint: body.login_hint,
+            dpop_jkt,
+            expires_in_ms: cfg.oauth.request_expires_in_ms,
+        },
+        &db,
+    )
+    .await?;
+    Ok(OAuthJson(
+        Status::Created,
+        json!({
+            "request_uri": request_uri(&id),
+            "expires_in": cfg.oauth.request_expires_in_ms / 1000,
+        }),
+    ))
+}
+
+/// Sign-in page the client sends the user to. Password accounts use the form;
+/// web5 accounts sign a login challenge with their CKB wallet, which the page
+/// hands to `window.web5SignIn` once the wallet returns the signature.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/oauth/authorize?<client_id>&<request_uri>")]
+pub async fn authorize(
+    client_id: String,
+    request_uri: String,
+    db: DbConn,
+) -> Result<RawHtml<String>, OAuthError> {
+    let request = match request_id_from_uri(&request_uri) {
+        Some(id) => get_request(id.to_string(), &db).await?,
+        None => None,
+    };
+    let request = match request {
+        Some(request) if request.client_id == client_id && request.code.is_none() => request,
+        _ => {
+            return Err(OAuthError::InvalidRequest(
+                "Unknown or expired request_uri".to_string(),
+            ))
+        }
+    };
+    let client_id = escape_html(&request.client_id);
+    let request_uri = escape_html(&request_uri);
+    let login_hint = escape_html(&request.login_hint.unwrap_or_default());
+    Ok(RawHtml(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <title>Sign in</title>
+</head>
+<body>
+  <main>
+    <h1>Sign in</h1>
+    <p><strong>{client_id}</strong> is asking to access your account.</p>
+    <form method="post" action="/oauth/authorize/sign-in">
+      <input type="hidden" name="request_uri" value="{request_uri}">
+      <label>Handle or email <input name="identifier" value="{login_hint}" required></label>
+      <label>Password <input name="password" type="password" required></label>
+      <button type="submit">Sign in</button>
+    </form>
+    <p id="web5-error" hidden></p>
+  </main>
+  <script>
+    window.web5SignIn = async function (signed) {{
+      const res = await fetch("/oauth/authorize/web5-sign-in", {{
+        method: "POST",
+        headers: {{ "Content-Type": "application/json" }},
+        body: JSON.stringify(Object.assign({{ requestUri: "{request_uri}" }}, signed)),
+      }});
+      const body = await res.json();
+      if (res.ok) {{
+        window.location.assign(body.redirectUri);
+      }} else {{
+        const error = document.getElementById("web5-error");
+        error.textContent = body.error_description || body.message;
+        error.hidden = false;
+      }}
+    }};
+  </script>
+</body>
+</html>"#
+    )))
+}
+
+#[derive(FromForm)]
+pub struct SignInForm {
+    request_uri: String,
+    identifier: String,
+    password: String,
+}
+
+async fn approve_request(
+    request_uri: &str,
+    did: String,
+    cfg: &ServerConfig,
+    db: &DbConn,
+) -> Result<String, OAuthError> {
+    let request = match request_id_from_uri(request_uri) {
+        Some(id) => get_request(id.to_string(), db).await?,
+        None => None,
+    };
+    let Some(request) = request else {
+        return Err(OAuthError::InvalidRequest(
+            "Unknown or expired request_uri".to_string(),
+        ));
+    };
+    let code = authorize_request(request.id.clone(), did, db)
+        .await
+        .map_err(|error| OAuthError::InvalidRequest(error.to_string()))?;
+    redirect_with_code(&request, &code, &cfg.oauth.issuer)
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::post("/oauth/authorize/sign-in", data = "<body>")]
+pub async fn sign_in(
+    body: Form<SignInForm>,
+    account_manager: AccountManager,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<Redirect, OAuthError> {
+    let SignInForm {
+        request_uri,
+        identifier,
+        password,
+    } = body.into_inner();
+    let identifier = identifier.to_lowercase();
+    let user = if identifier.contains('@') {
+        account_manager
+            .get_account_by_email(&identifier, None)
+            .await?
+    } else {
+        account_manager.get_account(&identifier, None).await?
+    };
+    let Some(user) = user else {
+        return Err(OAuthError::AccessDenied(
+            "Invalid identifier or password".to_string(),
+        ));
+    };
+    if !account_manager
+        .verify_login_password(&user, &password)
+        .await?
+    {
+        return Err(OAuthError::AccessDenied(
+            "Invalid identifier or password".to_string(),
+        ));
+    }
+    let location = approve_request(&request_uri, user.did, cfg, &db).await?;
+    Ok(Redirect::to(location))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Web5SignInInput {
+    request_uri: String,
+    #[serde(flatten)]
+    action: IndexActionInput,
+}
+
+/// Signs a web5 account in with a wallet-signed indexAction#createSession message.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/oauth/authorize/web5-sign-in", format = "json", data = "<body>")]
+pub async fn web5_sign_in(
+    body: Json<Web5SignInInput>,
+    account_manager: AccountManager,
+    cfg: &State<ServerConfig>,
+    did_methods: &State<SharedDidMethods>,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let Web5SignInInput {
+        request_uri,
+        action,
+    } = body.into_inner();
+    if !matches!(action.index, IndexActionInputRef::CreateSessionIndex(_)) {
+        return Err(OAuthError::InvalidRequest(
+            "Expected a createSession index".to_string(),
+        ));
+    }
+    let VerifiedIndexAction { user, .. } =
+        match verify_index_action(&action, &account_manager, cfg, did_methods).await {
+            Ok(verified) => verified,
+            Err(ApiError::RuntimeError) => return Err(OAuthError::ServerError),
+            Err(error) => {
+                tracing::info!("web5 oauth sign-in rejected: {error:?}");
+                return Err(OAuthError::AccessDenied(
+                    "Wallet signature could not be verified".to_string(),
+                ));
+            }
+        };
+    let location = approve_request(&request_uri, user.did, cfg, &db).await?;
+    Ok(OAuthJson(Status::Ok, json!({ "redirectUri": location })))
+}
+
+#[derive(FromForm)]
+pub struct TokenRequest {
+    grant_type: String,
+    client_id: String,
+    code: Option<String>,
+    code_verifier: Option<String>,
+    redirect_uri: Option<String>,
+    refresh_token: Option<String>,
+}
+
+async fn authorization_code_grant(
+    body: TokenRequest,
+    dpop_jkt: String,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let (Some(code), Some(code_verifier)) = (body.code, body.code_verifier) else {
+        return Err(OAuthError::InvalidRequest(
+            "code and code_verifier are required".to_string(),
+        ));
+    };
+    let Some(request) = consume_code(code, &db).await? else {
+        return Err(OAuthError::InvalidGrant(
+            "Invalid or expired code".to_string(),
+        ));
+    };
+    if request.client_id != body.client_id {
+        return Err(OAuthError::InvalidGrant(
+            "Code was issued to another client".to_string(),
+        ));
+    }
+    if body.redirect_uri.as_ref() != Some(&request.redirect_uri) {
+        return Err(OAuthError::InvalidGrant(
+            "redirect_uri does not match the authorization request".to_string(),
+        ));
+    }
+    if request.dpop_jkt != dpop_jkt {
+        return Err(OAuthError::InvalidGrant(
+            "DPoP key does not match the authorization request".to_string(),
+        ));
+    }
+    if !verify_pkce(&code_verifier, &request.code_challenge) {
+        return Err(OAuthError::InvalidGrant(
+            "Invalid code_verifier".to_string(),
+        ));
+    }
+    let did = request.did.expect("consumed codes are authorized");
+    let (access_token, refresh_token) = account_manager
+        .create_session(
+            did.clone(),
+            Some(format!("{APP_PASSWORD_NAME_PREFIX}{}", request.client_id)),
+            Some(dpop_jkt.clone()),
+        )
+        .await?;
+    let refresh_payload = decode_refresh_token(refresh_token.clone(), &JWT_KEYS)?;
+    create_session(
+        CreateSessionOpts {
+            refresh_token_id: refresh_payload.jti,
+            did: did.clone(),
+            client_id: request.client_id,
+            scope: request.scope.clone(),
+            dpop_jkt,
+            access_token: access_token.clone(),
+        },
+        &db,
+    )
+    .await?;
+    Ok(token_response(
+        access_token,
+        refresh_token,
+        request.scope,
+        did,
+    ))
+}
+
+async fn refresh_token_grant(
+    body: TokenRequest,
+    dpop_jkt: String,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let Some(refresh_token) = body.refresh_token else {
+        return Err(OAuthError::InvalidRequest(
+            "refresh_token is required".to_string(),
+        ));
+    };
+    let invalid = || OAuthError::InvalidGrant("Invalid refresh token".to_string());
+    let refresh_payload = decode_refresh_token(refresh_token, &JWT_KEYS).map_err(|_| invalid())?;
+    let session = match get_session(refresh_payload.jti.clone(), &db).await? {
+        Some(session) if session.client_id == body.client_id => session,
+        _ => return Err(invalid()),
+    };
+    if session.dpop_jkt != dpop_jkt {
+        return Err(OAuthError::InvalidGrant(
+            "DPoP key does not match the session".to_string(),
+        ));
+    }
+    let Some((access_token, refresh_token)) = account_manager
+        .rotate_refresh_token(&refresh_payload.jti, Some(dpop_jkt))
+        .await?
+    else {
+        delete_session(refresh_payload.jti, &db).await?;
+        return Err(invalid());
+    };
+    let next_payload = decode_refresh_token(refresh_token.clone(), &JWT_KEYS)?;
+    rotate_session(refresh_payload.jti, next_payload.jti, &access_token, &db).await?;
+    Ok(token_response(
+        access_token,
+        refresh_token,
+        session.scope,
+        session.did,
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+#[rocket::post("/oauth/token", data = "<body>")]
+pub async fn token(
+    body: Form<TokenRequest>,
+    proof: DpopProofHeader,
+    account_manager: AccountManager,
+    cfg: &State<ServerConfig>,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let dpop_jkt = verify_endpoint_proof(proof, cfg, "/oauth/token")?;
+    let body = body.into_inner();
+    match body.grant_type.as_str() {
+        "authorization_code" => authorization_code_grant(body, dpop_jkt, account_manager, db).await,
+        "refresh_token" => refresh_token_grant(body, dpop_jkt, account_manager, db).await,
+        grant_type => Err(OAuthError::InvalidRequest(format!(
+            "Unsupported grant_type: {grant_type}"
+        ))),
+    }
+}
+
+#[derive(FromForm)]
+pub struct RevokeRequest {
+    token: String,
+}
+
+/// Token revocation (RFC 7009). Revoking either token of a session ends it, and
+/// unknown tokens are not an error.
+#[tracing::instrument(skip_all)]
+#[rocket::post("/oauth/revoke", data = "<body>")]
+pub async fn revoke(
+    body: Form<RevokeRequest>,
+    account_manager: AccountManager,
+    db: DbConn,
+) -> Result<OAuthJson, OAuthError> {
+    let token = body.into_inner().token;
+    let refresh_token_id = match decode_refresh_token(token.clone(), &JWT_KEYS) {
+        Ok(payload) => Some(payload.jti),
+        Err(_) => get_session_by_access_token(&token, &db)
+            .await?
+            .map(|session| session.refresh_token_id),
+    };
+    if let Some(refresh_token_id) = refresh_token_id {
+        if get_session(refresh_token_id.clone(), &db).await?.is_some() {
+            account_manager
+                .revoke_session(refresh_token_id.clone())
+                .await?;
+            delete_session(refresh_token_id, &db).await?;
+        }
+    }
+    Ok(OAuthJson(Status::Ok, json!({})))
+}
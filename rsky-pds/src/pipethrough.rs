@@ -228,6 +228,8 @@ pub async fn pipethrough_procedure_post<'r>(
 // Request setup/formatting
 // -------------------
 
+const BBS_NSID_PREFIX: &str = "app.bbs.";
+
 const REQ_HEADERS_TO_FORWARD: [&str; 4] = [
     "accept-language",
     "content-type",
@@ -240,8 +242,12 @@ pub async fn format_url_and_aud<'r>(
     req: &'r ProxyRequest<'_>,
     aud_override: Option<String>,
 ) -> Result<UrlAndAud> {
-    let proxy_to = parse_proxy_header(req).await?;
     let nsid = parse_req_nsid(req);
+    // account management methods must be called on the PDS directly
+    if PROTECTED_METHODS.contains(nsid.as_str()) {
+        bail!(InvalidRequestError::ProtectedMethod(nsid));
+    }
+    let proxy_to = parse_proxy_header(req).await?;
     let default_proxy = default_service(req, &nsid).await;
     let service_url = match proxy_to {
         Some(ref proxy_to) => {
@@ -565,6 +571,7 @@ pub async fn default_service<'r>(req: &'r ProxyRequest<'_>, nsid: &str) -> Optio
         Ok(Ids::ToolsOzoneModerationQueryStatuses) => cfg.mod_service.clone(),
         Ok(Ids::ToolsOzoneModerationSearchRepos) => cfg.mod_service.clone(),
         Ok(Ids::ComAtprotoModerationCreateReport) => cfg.report_service.clone(),
+        _ if nsid.starts_with(BBS_NSID_PREFIX) => cfg.bbs_app_view.clone(),
         _ => cfg.bsky_app_view.clone(),
     }
 }
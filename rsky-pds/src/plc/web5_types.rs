@@ -1,16 +1,26 @@
 use crate::apis::ApiError;
-use crate::plc::cell_data::{DidWeb5Data, DidWeb5DataUnion};
-use anyhow::{bail, Result};
+use crate::metrics;
+use crate::plc::cell_data::{DidWeb5DataReader, DidWeb5DataUnionReader};
+use crate::telemetry;
+use anyhow::{anyhow, bail, Result};
+use ckb_hash::blake2b_256;
 use ckb_jsonrpc_types::{OutPoint, Uint32};
+use ckb_sdk::constants::SIGHASH_TYPE_HASH;
 use ckb_sdk::{Address, CkbRpcAsyncClient};
+use ckb_types::core::ScriptHashType;
+use ckb_types::prelude::Unpack;
 use ckb_types::{packed::Script, H256};
-use molecule::prelude::Entity;
+use lazy_static::lazy_static;
+use molecule::prelude::Reader;
 use rand::{distributions::Alphanumeric, Rng};
 use rsky_lexicon::com::atproto::web5::{IndexActionInputRef, PreIndexActionInputRef};
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, SECP256K1};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{collections::BTreeMap, str::FromStr};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{collections::BTreeMap, env, str::FromStr};
+use tracing::Instrument;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Service {
@@ -139,17 +149,45 @@ impl OpOrTombstone {
     }
 }
 
+lazy_static! {
+    /// CKB node the live cells holding DID docs are read from
+    pub static ref CKB_RPC_URL: String =
+        env::var("PDS_CKB_RPC_URL").unwrap_or_else(|_| "https://testnet.ckb.dev/".to_string());
+    /// CKB explorer API the DID cell of an address is looked up through
+    pub static ref CKB_EXPLORER_URL: String = env::var("PDS_CKB_EXPLORER_URL")
+        .unwrap_or_else(|_| "http://testnet-api.explorer.nervos.org".to_string());
+}
+
+/// Whether the CKB node DID docs are read from is reachable
+pub async fn check_ckb_rpc() -> Result<()> {
+    CkbRpcAsyncClient::new(&CKB_RPC_URL)
+        .get_tip_block_number()
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(ckb_addr = %telemetry::hashed(ckb_addr)))]
 pub async fn get_didoc_from_chain(ckb_addr: &str) -> Result<Web5DocumentData, ApiError> {
+    let started = Instant::now();
+    let res = fetch_didoc_from_chain(ckb_addr).await;
+    metrics::record_chain_lookup(started, &res);
+    res
+}
+
+async fn fetch_didoc_from_chain(ckb_addr: &str) -> Result<Web5DocumentData, ApiError> {
     let addr = Address::from_str(ckb_addr)
         .map_err(|_| ApiError::InvalidCkbError(format!("Address format invalid")))?;
     let script: Script = (&addr).into();
     let address_hash = "0x".to_string() + &hex::encode(script.calc_script_hash().raw_data());
-    let query_url = format!("http://testnet-api.explorer.nervos.org/api/v2/scripts/referring_cells?code_hash=0x510150477b10d6ab551a509b71265f3164e9fd4137fcb5a4322f49f03092c7c5&hash_type=type&sort=created_time.asc&address_hash={}&restrict=false&page=1&page_size=1", address_hash);
+    let query_url = format!("{}/api/v2/scripts/referring_cells?code_hash=0x510150477b10d6ab551a509b71265f3164e9fd4137fcb5a4322f49f03092c7c5&hash_type=type&sort=created_time.asc&address_hash={}&restrict=false&page=1&page_size=1", CKB_EXPLORER_URL.trim_end_matches('/'), address_hash);
     let client = reqwest::Client::new();
 
+    let span = tracing::info_span!("ckb.explorer.referring_cells");
     let response = client
         .get(query_url)
+        .headers(span.in_scope(telemetry::trace_headers))
         .send()
+        .instrument(span)
         .await
         .map_err(|_| ApiError::InvalidCkbError(format!("CKB Testnet")))?;
     let data = response
@@ -180,29 +218,19 @@ pub async fn get_didoc_from_chain(ckb_addr: &str) -> Result<Web5DocumentData, Ap
             ApiError::InvalidCkbError(format!("CKB Testnet Response Convert cell_index"))
         })? as u32;
 
-        let client = CkbRpcAsyncClient::new("https://testnet.ckb.dev/");
+        let client = CkbRpcAsyncClient::new(&CKB_RPC_URL);
         let tx_hash = H256::from_str(&tx_hash_str[2..])
             .map_err(|_| ApiError::InvalidCkbError(format!("CKB Testnet Response Convert Hash")))?;
         let index = Uint32::from(cell_index);
         let cell = client
             .get_live_cell(OutPoint { tx_hash, index }, true)
+            .instrument(tracing::info_span!("ckb.rpc.get_live_cell"))
             .await
             .map_err(|_| ApiError::InvalidCkbError(format!("CKB get_live_cell")))?;
         if let Some(cell) = cell.cell {
             if let Some(cell_data) = cell.data {
-                let bytes = cell_data.content.as_bytes();
-                let did_data = DidWeb5Data::from_slice(bytes).map_err(|_| {
-                    ApiError::InvalidCkbError("DidWeb5Data convert failed".to_string())
-                })?;
-                let DidWeb5DataUnion::DidWeb5DataV1(did_data_v1) = did_data.to_enum();
-                let did_doc = did_data_v1.document();
-                Ok(
-                    serde_ipld_dagcbor::from_slice(&did_doc.raw_data()).map_err(|_| {
-                        ApiError::InvalidCkbError(
-                            "Web5DocumentData dog cbor decode failed".to_string(),
-                        )
-                    })?,
-                )
+                decode_did_document(cell_data.content.as_bytes())
+                    .map_err(|error| ApiError::InvalidCkbError(error.to_string()))
             } else {
                 return Err(ApiError::InvalidCkbError("Cell data not found".to_string()));
             }
@@ -214,6 +242,18 @@ pub async fn get_didoc_from_chain(ckb_addr: &str) -> Result<Web5DocumentData, Ap
     }
 }
 
+/// Decodes the DID doc held in a DID cell's data. The molecule structure is
+/// verified and read in place, and the DAG-CBOR document decoded straight from
+/// the cell bytes, so nothing is copied and corrupt cell data is an error
+/// rather than a panic.
+pub fn decode_did_document(cell_data: &[u8]) -> Result<Web5DocumentData> {
+    let did_data = DidWeb5DataReader::from_slice(cell_data)
+        .map_err(|error| anyhow!("DidWeb5Data convert failed: {error}"))?;
+    let DidWeb5DataUnionReader::DidWeb5DataV1(did_data_v1) = did_data.to_enum();
+    serde_ipld_dagcbor::from_slice(did_data_v1.document().raw_data())
+        .map_err(|error| anyhow!("Web5DocumentData dag cbor decode failed: {error}"))
+}
+
 pub fn generate_random_string(length: usize) -> String {
     rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -253,6 +293,16 @@ pub fn statement_check(message: &str, index: &IndexActionInputRef) -> Result<boo
     bail!("Statement check error")
 }
 
+/// Decodes the hex `signedBytes` a wallet returns for an indexAction message,
+/// with or without a `0x` prefix.
+pub fn decode_signed_bytes(signed_bytes: &str) -> Result<Vec<u8>> {
+    let sig = signed_bytes
+        .strip_prefix("0x")
+        .or_else(|| signed_bytes.strip_prefix("0X"))
+        .unwrap_or(signed_bytes);
+    hex::decode(sig).map_err(|error| anyhow!("Signature decode error {error}"))
+}
+
 pub fn generate_challenge(
     domain: String,
     ckb_addr: String,
@@ -272,3 +322,134 @@ pub fn generate_challenge(
         index.statement(),
     ))
 }
+
+/// Prefix CKB wallets put in front of a message before hashing it for signing
+const CKB_MESSAGE_PREFIX: &str = "Nervos Message:";
+
+/// Checks `signature`, a 65 byte recoverable secp256k1 signature as CKB wallets
+/// produce over `blake2b("Nervos Message:" + message)`, was made by the key
+/// behind `ckb_addr`. Only default secp256k1-blake160 sighash addresses can
+/// sign this way.
+pub fn verify_ckb_message_signature(
+    ckb_addr: &str,
+    message: &str,
+    signature: &[u8],
+) -> Result<bool> {
+    let addr = Address::from_str(ckb_addr).map_err(|_| anyhow!("Address format invalid"))?;
+    let script: Script = (&addr).into();
+    if script.code_hash().unpack() != SIGHASH_TYPE_HASH
+        || ScriptHashType::try_from(script.hash_type()).ok() != Some(ScriptHashType::Type)
+    {
+        bail!("Only secp256k1-blake160 sighash addresses can sign messages");
+    }
+    if signature.len() != 65 {
+        bail!("Signature must be 65 bytes");
+    }
+    let recovery_id = RecoveryId::from_i32(signature[64] as i32)?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)?;
+    let hash = blake2b_256(format!("{CKB_MESSAGE_PREFIX}{message}"));
+    let pubkey = SECP256K1.recover_ecdsa(&Message::from_digest(hash), &signature)?;
+    let pubkey_hash = blake2b_256(pubkey.serialize());
+    Ok(script.args().raw_data().as_ref() == &pubkey_hash[..20])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plc::cell_data::{Bytes, DidWeb5Data, DidWeb5DataV1};
+    use molecule::prelude::{Builder, Entity};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn cell_data() -> Vec<u8> {
+        let document = Web5DocumentData {
+            verification_methods: BTreeMap::from([(
+                "atproto".to_string(),
+                "did:key:zQ3shXjHeiBuRCKmM36cuYnm7YEMzhGnCmCyW92sRJ9pribSF".to_string(),
+            )]),
+            also_known_as: vec!["at://alice.test".to_string()],
+            services: BTreeMap::from([(
+                "atproto_pds".to_string(),
+                Service {
+                    r#type: "AtprotoPersonalDataServer".to_string(),
+                    endpoint: "https://pds.test".to_string(),
+                },
+            )]),
+        };
+        let document = serde_ipld_dagcbor::to_vec(&document).unwrap();
+        let did_data: DidWeb5Data = DidWeb5DataV1::new_builder()
+            .document(Bytes::from(document))
+            .build()
+            .into();
+        did_data.as_slice().to_vec()
+    }
+
+    #[test]
+    fn decodes_did_cell() {
+        let document = decode_did_document(&cell_data()).unwrap();
+        assert_eq!(document.also_known_as, vec!["at://alice.test".to_string()]);
+        assert_eq!(
+            document.services["atproto_pds"].endpoint,
+            "https://pds.test".to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_cells_without_panicking() {
+        let valid = cell_data();
+        for len in 0..valid.len() {
+            assert!(decode_did_document(&valid[..len]).is_err());
+        }
+        let mut rng = StdRng::seed_from_u64(1841);
+        for _ in 0..10_000 {
+            let mut corrupt = valid.clone();
+            for _ in 0..rng.gen_range(1..4) {
+                let at = rng.gen_range(0..corrupt.len());
+                corrupt[at] = rng.gen();
+            }
+            let _ = decode_did_document(&corrupt);
+        }
+        for _ in 0..10_000 {
+            let len = rng.gen_range(0..256);
+            let random: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let _ = decode_did_document(&random);
+        }
+    }
+
+    #[test]
+    fn verifies_ckb_message_signatures() {
+        let sign = |secret_key: &secp256k1::SecretKey, message: &str| {
+            let hash = blake2b_256(format!("{CKB_MESSAGE_PREFIX}{message}"));
+            let (recovery_id, sig) = SECP256K1
+                .sign_ecdsa_recoverable(&Message::from_digest(hash), secret_key)
+                .serialize_compact();
+            let mut signature = sig.to_vec();
+            signature.push(recovery_id.to_i32() as u8);
+            signature
+        };
+        let (secret_key, public_key) = SECP256K1.generate_keypair(&mut StdRng::seed_from_u64(1845));
+        let pubkey_hash = blake2b_256(public_key.serialize());
+        let payload = ckb_sdk::AddressPayload::new_short(
+            ckb_sdk::CodeHashIndex::Sighash,
+            ckb_types::H160::from_slice(&pubkey_hash[..20]).unwrap(),
+        );
+        let ckb_addr = Address::new(ckb_sdk::NetworkType::Testnet, payload, true).to_string();
+
+        let signature = sign(&secret_key, "rebind");
+        assert!(verify_ckb_message_signature(&ckb_addr, "rebind", &signature).unwrap());
+        assert!(!verify_ckb_message_signature(&ckb_addr, "rebind twice", &signature).unwrap());
+        let (other_key, _) = SECP256K1.generate_keypair(&mut StdRng::seed_from_u64(1846));
+        let signature = sign(&other_key, "rebind");
+        assert!(!verify_ckb_message_signature(&ckb_addr, "rebind", &signature).unwrap());
+        assert!(verify_ckb_message_signature(&ckb_addr, "rebind", &signature[..64]).is_err());
+    }
+
+    #[test]
+    fn decodes_signed_bytes() {
+        assert_eq!(decode_signed_bytes("0xdead").unwrap(), vec![0xde, 0xad]);
+        assert_eq!(decode_signed_bytes("0XBEEF").unwrap(), vec![0xbe, 0xef]);
+        assert_eq!(decode_signed_bytes("beef").unwrap(), vec![0xbe, 0xef]);
+        assert!(decode_signed_bytes("0x").unwrap().is_empty());
+        assert!(decode_signed_bytes("0xabc").is_err());
+        assert!(decode_signed_bytes("é0").is_err());
+    }
+}
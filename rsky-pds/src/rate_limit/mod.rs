@@ -0,0 +1,335 @@
+use crate::apis::ApiError;
+use crate::auth_verifier::AuthenticatedDid;
+use crate::config::{
+    RateLimitKey, RateLimitPolicy, RateLimitsConfig, ServerConfig, OAUTH_SIGN_IN_PATHS,
+};
+use anyhow::Result;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{Header, Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, Response, State};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use store::{Consumed, MemoryStore, RateLimitStore};
+
+pub mod store;
+
+const BYPASS_HEADER: &str = "x-ratelimit-bypass";
+/// Unrouted path rate limited requests are sent to, so the catcher answers them.
+/// Kept outside /xrpc, where the proxy routes would pick it up.
+const RATE_LIMITED_PATH: &str = "/_rateLimited";
+
+/// The most restrictive limit a request was counted against, reported in the
+/// `RateLimit-*` response headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitStatus {
+    pub policy: String,
+    pub limit: u64,
+    pub remaining: u64,
+    pub window_ms: u64,
+    pub reset_in_ms: u64,
+}
+
+impl RateLimitStatus {
+    fn new(policy: &RateLimitPolicy, consumed: Consumed) -> Self {
+        RateLimitStatus {
+            policy: policy.name.clone(),
+            limit: policy.points,
+            remaining: policy.points.saturating_sub(consumed.points),
+            window_ms: policy.duration_ms,
+            reset_in_ms: consumed.reset_in_ms,
+        }
+    }
+}
+
+#[derive(Default)]
+struct RequestRateLimit(Mutex<Option<RateLimitStatus>>);
+
+impl RequestRateLimit {
+    fn record(&self, status: RateLimitStatus) {
+        let mut current = self.0.lock().expect("rate limit status poisoned");
+        match *current {
+            Some(ref existing) if existing.remaining <= status.remaining => (),
+            _ => *current = Some(status),
+        }
+    }
+}
+
+/// Policies in effect, swapped out when runtime flags change them
+struct ActivePolicies {
+    enabled: bool,
+    policies: Vec<RateLimitPolicy>,
+}
+
+pub struct RateLimiter {
+    cfg: RateLimitsConfig,
+    active: RwLock<Arc<ActivePolicies>>,
+    store: Box<dyn RateLimitStore>,
+}
+
+pub type SharedRateLimiter = Arc<RateLimiter>;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in milliseconds since UNIX epoch")
+        .as_millis() as u64
+}
+
+/// The xrpc method a request calls, or the OAuth sign-in endpoint's path,
+/// which policies name like a method
+fn nsid_from_request(req: &Request<'_>) -> Option<String> {
+    if req.method() == Method::Options {
+        return None;
+    }
+    let path = req.uri().path().as_str();
+    match path.strip_prefix("/xrpc/") {
+        Some(nsid) => Some(nsid.to_string()),
+        None if OAUTH_SIGN_IN_PATHS.contains(&path) => Some(path.to_string()),
+        None => None,
+    }
+}
+
+/// The client's ip, from the trusted proxy header when one is configured and
+/// the connection's peer otherwise. None if the proxy didn't send one.
+pub fn resolve_client_ip(req: &Request<'_>) -> Option<IpAddr> {
+    let header = req
+        .rocket()
+        .state::<ServerConfig>()
+        .and_then(|cfg| cfg.service.trusted_proxy_header.as_deref());
+    match header {
+        Some(header) => req
+            .headers()
+            .get_one(header)
+            .and_then(|ip| ip.trim().parse().ok()),
+        None => req.remote().map(|remote| remote.ip()),
+    }
+}
+
+fn client_ip(req: &Request<'_>) -> String {
+    resolve_client_ip(req)
+        .map(|ip| ip.to_string())
+        .unwrap_or("unknown".to_string())
+}
+
+impl RateLimiter {
+    pub async fn new(cfg: RateLimitsConfig) -> Result<Self> {
+        let store: Box<dyn RateLimitStore> = match cfg.redis_url {
+            #[cfg(feature = "rate-limit-redis")]
+            Some(ref url) => Box::new(store::RedisStore::new(url).await?),
+            #[cfg(not(feature = "rate-limit-redis"))]
+            Some(_) => anyhow::bail!(
+                "PDS_RATE_LIMITS_REDIS_URL is set but rsky-pds was built without the rate-limit-redis feature"
+            ),
+            None => Box::new(MemoryStore::default()),
+        };
+        let active = RwLock::new(Arc::new(ActivePolicies {
+            enabled: cfg.enabled,
+            policies: cfg.policies.clone(),
+        }));
+        Ok(RateLimiter { cfg, active, store })
+    }
+
+    /// Replaces the policies requests are counted against from now on.
+    pub fn reload(&self, enabled: bool, policies: Vec<RateLimitPolicy>) {
+        *self.active.write().expect("rate limit policies poisoned") =
+            Arc::new(ActivePolicies { enabled, policies });
+    }
+
+    fn is_bypassed(&self, req: &Request<'_>) -> bool {
+        if let (Some(key), Some(given)) =
+            (&self.cfg.bypass_key, req.headers().get_one(BYPASS_HEADER))
+        {
+            if key == given {
+                return true;
+            }
+        }
+        self.cfg.bypass_ips.contains(&client_ip(req))
+    }
+
+    /// Consumes a point from each `key` policy that applies to `nsid`, returning
+    /// false once any of them is exhausted.
+    pub async fn consume(
+        &self,
+        req: &Request<'_>,
+        key: RateLimitKey,
+        nsid: &str,
+        subject: &str,
+    ) -> Result<bool> {
+        let active = self
+            .active
+            .read()
+            .expect("rate limit policies poisoned")
+            .clone();
+        if !active.enabled || self.is_bypassed(req) {
+            return Ok(true);
+        }
+        let now_ms = now_ms();
+        let mut allowed = true;
+        for policy in active.policies.iter() {
+            if policy.key != key || !policy.applies_to(nsid) {
+                continue;
+            }
+            let store_key = format!("rl:{}:{subject}", policy.name);
+            let consumed = self
+                .store
+                .consume(&store_key, 1, policy.duration_ms, now_ms)
+                .await?;
+            allowed &= consumed.points <= policy.points;
+            req.local_cache(RequestRateLimit::default)
+                .record(RateLimitStatus::new(policy, consumed));
+        }
+        Ok(allowed)
+    }
+}
+
+fn rate_limited(req: &Request<'_>) -> ApiError {
+    let error = ApiError::RateLimitExceeded;
+    req.local_cache(|| Some(error.clone()));
+    error
+}
+
+/// Applies the ip keyed policies before routing and adds the `RateLimit-*` headers.
+pub struct RateLimitFairing(pub SharedRateLimiter);
+
+#[rocket::async_trait]
+impl Fairing for RateLimitFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit xrpc and OAuth sign-in requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let Some(nsid) = nsid_from_request(req) else {
+            return;
+        };
+        let ip = client_ip(req);
+        match self.0.consume(req, RateLimitKey::Ip, &nsid, &ip).await {
+            Ok(true) => (),
+            Ok(false) => {
+                rate_limited(req);
+                req.set_uri(Origin::parse(RATE_LIMITED_PATH).expect("valid rate limited path"));
+            }
+            // Fail open, an unavailable counter store shouldn't take the PDS down
+            Err(error) => tracing::error!("@LOG: ERROR: rate limiter failed: {error}"),
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let status = req.local_cache(RequestRateLimit::default);
+        let status = status.0.lock().expect("rate limit status poisoned").clone();
+        if let Some(status) = status {
+            res.set_header(Header::new("RateLimit-Limit", status.limit.to_string()));
+            res.set_header(Header::new(
+                "RateLimit-Remaining",
+                status.remaining.to_string(),
+            ));
+            res.set_header(Header::new(
+                "RateLimit-Reset",
+                status.reset_in_ms.div_ceil(1000).to_string(),
+            ));
+            res.set_header(Header::new(
+                "RateLimit-Policy",
+                format!("{};w={}", status.limit, status.window_ms / 1000),
+            ));
+        }
+    }
+}
+
+/// The client's ip as the rate limiter sees it, see [`resolve_client_ip`]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientIp {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientIp(resolve_client_ip(req)))
+    }
+}
+
+/// Applies the account keyed policies of a route. Goes after the route's auth
+/// guard, which records the authenticated DID.
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(nsid) = nsid_from_request(req) else {
+            return Outcome::Success(RateLimit);
+        };
+        let limiter = match req.guard::<&State<SharedRateLimiter>>().await {
+            Outcome::Success(limiter) => limiter,
+            _ => return Outcome::Success(RateLimit),
+        };
+        let subject = match req.local_cache(|| AuthenticatedDid(None)) {
+            AuthenticatedDid(Some(did)) => did.clone(),
+            AuthenticatedDid(None) => client_ip(req),
+        };
+        match limiter
+            .consume(req, RateLimitKey::Did, &nsid, &subject)
+            .await
+        {
+            Ok(true) => Outcome::Success(RateLimit),
+            Ok(false) => Outcome::Error((Status::TooManyRequests, rate_limited(req))),
+            Err(error) => {
+                tracing::error!("@LOG: ERROR: rate limiter failed: {error}");
+                Outcome::Success(RateLimit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_store_resets_after_window() {
+        let store = MemoryStore::default();
+        let first = store.consume("rl:test:ip", 1, 1000, 10_000).await.unwrap();
+        assert_eq!(first.points, 1);
+        assert_eq!(first.reset_in_ms, 1000);
+        let second = store.consume("rl:test:ip", 1, 1000, 10_500).await.unwrap();
+        assert_eq!(second.points, 2);
+        assert_eq!(second.reset_in_ms, 500);
+        let reset = store.consume("rl:test:ip", 1, 1000, 11_000).await.unwrap();
+        assert_eq!(reset.points, 1);
+    }
+
+    #[test]
+    fn oauth_sign_in_is_limited_like_create_session() {
+        let policies = crate::config::default_rate_limit_policies();
+        let auth_short = policies
+            .iter()
+            .find(|policy| policy.name == "auth-short")
+            .unwrap();
+        assert!(auth_short.applies_to("com.atproto.server.createSession"));
+        assert!(auth_short.applies_to("/oauth/authorize/sign-in"));
+        assert!(auth_short.applies_to("/oauth/authorize/web5-sign-in"));
+        assert!(!auth_short.applies_to("/oauth/authorize"));
+    }
+
+    #[test]
+    fn keeps_most_restrictive_status() {
+        let status = |policy: &str, remaining| RateLimitStatus {
+            policy: policy.to_string(),
+            limit: 10,
+            remaining,
+            window_ms: 1000,
+            reset_in_ms: 1000,
+        };
+        let state = RequestRateLimit::default();
+        state.record(status("global", 9));
+        state.record(status("auth-short", 2));
+        state.record(status("auth-daily", 5));
+        let recorded = state.0.lock().unwrap().clone().unwrap();
+        assert_eq!(recorded.policy, "auth-short");
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Expired windows are swept once the in-process store tracks this many keys
+const MEMORY_SWEEP_THRESHOLD: usize = 10_000;
+
+/// Points consumed in the current window of a key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Consumed {
+    pub points: u64,
+    /// Milliseconds until the window resets
+    pub reset_in_ms: u64,
+}
+
+/// Fixed window counters the rate limiter consumes points from.
+#[rocket::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn consume(
+        &self,
+        key: &str,
+        points: u64,
+        duration_ms: u64,
+        now_ms: u64,
+    ) -> Result<Consumed>;
+}
+
+struct Window {
+    reset_at_ms: u64,
+    points: u64,
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+#[rocket::async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn consume(
+        &self,
+        key: &str,
+        points: u64,
+        duration_ms: u64,
+        now_ms: u64,
+    ) -> Result<Consumed> {
+        let mut windows = self.windows.lock().expect("rate limit store poisoned");
+        if windows.len() > MEMORY_SWEEP_THRESHOLD {
+            windows.retain(|_, window| window.reset_at_ms > now_ms);
+        }
+        let window = windows.entry(key.to_string()).or_insert(Window {
+            reset_at_ms: now_ms + duration_ms,
+            points: 0,
+        });
+        if window.reset_at_ms <= now_ms {
+            window.reset_at_ms = now_ms + duration_ms;
+            window.points = 0;
+        }
+        window.points += points;
+        Ok(Consumed {
+            points: window.points,
+            reset_in_ms: window.reset_at_ms - now_ms,
+        })
+    }
+}
+
+#[cfg(feature = "rate-limit-redis")]
+pub struct RedisStore {
+    conn: redis::aio::ConnectionManager,
+}
+
+#[cfg(feature = "rate-limit-redis")]
+impl RedisStore {
+    pub async fn new(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(RedisStore {
+            conn: redis::aio::ConnectionManager::new(client).await?,
+        })
+    }
+}
+
+#[cfg(feature = "rate-limit-redis")]
+#[rocket::async_trait]
+impl RateLimitStore for RedisStore {
+    async fn consume(
+        &self,
+        key: &str,
+        points: u64,
+        duration_ms: u64,
+        _now_ms: u64,
+    ) -> Result<Consumed> {
+        let mut conn = self.conn.clone();
+        // The window starts with whichever instance sets the key first
+        let (consumed, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(key)
+            .arg(0)
+            .arg("PX")
+            .arg(duration_ms)
+            .arg("NX")
+            .ignore()
+            .cmd("INCRBY")
+            .arg(key)
+            .arg(points)
+            .cmd("PTTL")
+            .arg(key)
+            .query_async(&mut conn)
+            .await?;
+        Ok(Consumed {
+            points: consumed,
+            reset_in_ms: ttl.max(0) as u64,
+        })
+    }
+}
@@ -1,5 +1,5 @@
 use crate::account_manager::AccountManager;
-use crate::actor_store::aws::s3::S3BlobStore;
+use crate::actor_store::blob::store::SharedBlobStore;
 use crate::actor_store::ActorStore;
 use crate::db::DbConn;
 use crate::pipethrough::parse_res;
@@ -8,7 +8,6 @@ use crate::read_after_write::viewer::{get_records_since_rev, LocalViewer};
 use crate::xrpc_server::types::HandlerPipeThrough;
 use crate::SharedLocalViewer;
 use anyhow::Result;
-use aws_sdk_s3::Config;
 use chrono::offset::Utc as UtcOffset;
 use chrono::DateTime;
 use rocket::http::Status;
@@ -111,7 +110,7 @@ pub async fn handle_read_after_write<T: DeserializeOwned + serde::Serialize>(
     requester: String,
     res: HandlerPipeThrough,
     munge: MungeFn<T>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -121,7 +120,7 @@ pub async fn handle_read_after_write<T: DeserializeOwned + serde::Serialize>(
         requester.clone(),
         res.clone(),
         munge,
-        s3_config,
+        blob_store,
         state_local_viewer,
         db,
         account_manager,
@@ -145,7 +144,7 @@ pub async fn read_after_write_internal<T: DeserializeOwned + serde::Serialize>(
     requester: String,
     res: HandlerPipeThrough,
     munge: MungeFn<T>,
-    s3_config: &State<Config>,
+    blob_store: &State<SharedBlobStore>,
     state_local_viewer: &State<SharedLocalViewer>,
     db: DbConn,
     account_manager: AccountManager,
@@ -155,11 +154,8 @@ pub async fn read_after_write_internal<T: DeserializeOwned + serde::Serialize>(
     match rev {
         None => Ok(ReadAfterWriteResponse::HandlerPipeThrough(res)),
         Some(rev) => {
-            let actor_store = ActorStore::new(
-                requester.clone(),
-                S3BlobStore::new(requester.clone(), s3_config.inner().clone()),
-                db,
-            );
+            let actor_store =
+                ActorStore::new(requester.clone(), blob_store.for_did(requester.clone()), db);
             let local = get_records_since_rev(&actor_store, rev).await?;
             if local.count <= 0 {
                 return Ok(ReadAfterWriteResponse::HandlerPipeThrough(res));
@@ -154,6 +154,7 @@ impl LocalViewer {
                     lxm: Some(lxm.to_owned()),
                     jti: None,
                     keypair,
+                    collections: None,
                 })
                 .await
             }
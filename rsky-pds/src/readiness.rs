@@ -0,0 +1,111 @@
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::config::ServerConfig;
+use crate::db::{migrations, DbConn};
+use crate::models::{DependencyStatus, Readiness};
+use crate::plc::web5_types::check_ckb_rpc;
+use crate::sequencer;
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::sql_types::Int4;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+/// How long a dependency gets to answer before it's reported down
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// The sequencer polls at least every second, so this long without a poll
+/// means it's stuck
+const SEQUENCER_STALE_AFTER_MS: i64 = 30_000;
+
+pub(crate) async fn check(dependency: impl Future<Output = Result<()>>) -> DependencyStatus {
+    let started = Instant::now();
+    let res = match timeout(CHECK_TIMEOUT, dependency).await {
+        Ok(res) => res,
+        Err(_) => Err(anyhow!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    DependencyStatus {
+        ok: res.is_ok(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        error: res.err().map(|error| error.to_string()),
+    }
+}
+
+async fn check_postgres(db: Option<&DbConn>) -> Result<()> {
+    let Some(db) = db else {
+        bail!("No database connection available");
+    };
+    db.run(|conn| diesel::select(diesel::dsl::sql::<Int4>("1")).execute(conn))
+        .await?;
+    Ok(())
+}
+
+/// Whether every migration this binary embeds has been applied
+async fn check_schema(db: Option<&DbConn>) -> Result<()> {
+    let Some(db) = db else {
+        bail!("No database connection available");
+    };
+    db.run(migrations::check_schema_version).await
+}
+
+pub(crate) async fn check_blobstore(
+    blob_store: &SharedBlobStore,
+    service_did: String,
+) -> Result<()> {
+    blob_store
+        .for_did(service_did)
+        .bucket_exists()
+        .await
+        .map_err(|error| anyhow!("{error:?}"))?;
+    Ok(())
+}
+
+fn check_sequencer(now_ms: i64) -> Result<()> {
+    match sequencer::last_polled_at() {
+        None => bail!("Sequencer hasn't polled yet"),
+        Some(polled_at) if now_ms - polled_at > SEQUENCER_STALE_AFTER_MS => {
+            bail!("Sequencer last polled {}ms ago", now_ms - polled_at)
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Readiness probe, unlike `/xrpc/_health` it checks every backend the PDS
+/// needs to serve web5 accounts and answers 503 if any of them is down.
+#[tracing::instrument(skip_all)]
+#[rocket::get("/xrpc/_ready")]
+pub async fn ready(
+    db: Option<DbConn>,
+    blob_store: &State<SharedBlobStore>,
+    cfg: &State<ServerConfig>,
+) -> status::Custom<Json<Readiness>> {
+    let (postgres, schema, blobstore, ckb_rpc, sequencer) = tokio::join!(
+        check(check_postgres(db.as_ref())),
+        check(check_schema(db.as_ref())),
+        check(check_blobstore(blob_store, cfg.service.did.clone())),
+        check(check_ckb_rpc()),
+        check(async { check_sequencer(Utc::now().timestamp_millis()) }),
+    );
+    let checks = BTreeMap::from([
+        ("postgres".to_string(), postgres),
+        ("schema".to_string(), schema),
+        ("blobstore".to_string(), blobstore),
+        ("ckbRpc".to_string(), ckb_rpc),
+        ("sequencer".to_string(), sequencer),
+    ]);
+    let ready = checks.values().all(|status| status.ok);
+    for (name, status) in checks.iter().filter(|(_, status)| !status.ok) {
+        tracing::warn!("Readiness check {name} failed: {:?}", status.error);
+    }
+    let status = if ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    status::Custom(status, Json(Readiness { ready, checks }))
+}
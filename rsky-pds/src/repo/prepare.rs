@@ -1,18 +1,25 @@
+use crate::bbs::automod::assert_valid_rule;
+use crate::bbs::{ENCRYPTED_POST_COLLECTION, MAX_CIPHERTEXT_BYTES, RULE_COLLECTION};
 use crate::lexicon::LEXICONS;
 use anyhow::bail;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine as _;
 use lazy_static::lazy_static;
 use lexicon_cid::Cid;
 use rsky_common::ipld::cid_for_cbor;
 use rsky_common::tid::Ticker;
 use rsky_lexicon::blob_refs::{BlobRef, JsonBlobRef};
+use rsky_lexicon::schema::{lexicons, ValidationError, Validator};
 use rsky_repo::storage::Ipld;
 use rsky_repo::types::{
     BlobConstraint, Ids, Lex, PreparedBlobRef, PreparedCreateOrUpdate, PreparedDelete, RepoRecord,
     WriteOpAction,
 };
-use rsky_repo::util::{cbor_to_lex, lex_to_ipld};
+use rsky_repo::util::{cbor_to_lex_record, record_to_block};
 use rsky_syntax::aturi::AtUri;
+use rsky_syntax::nsid::ensure_valid_nsid;
 use serde_json::{json, Value as JsonValue};
+use std::cell::RefCell;
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct FoundBlobRef {
@@ -27,6 +34,8 @@ pub struct PrepareCreateOpts {
     pub swap_cid: Option<Cid>,
     pub record: RepoRecord,
     pub validate: Option<bool>,
+    /// Collections the token may write to, see [`collection_permitted`]
+    pub collections: Option<Vec<String>>,
 }
 
 pub struct PrepareUpdateOpts {
@@ -36,6 +45,8 @@ pub struct PrepareUpdateOpts {
     pub swap_cid: Option<Cid>,
     pub record: RepoRecord,
     pub validate: Option<bool>,
+    /// Collections the token may write to, see [`collection_permitted`]
+    pub collections: Option<Vec<String>>,
 }
 
 pub struct PrepareDeleteOpts {
@@ -43,9 +54,74 @@ pub struct PrepareDeleteOpts {
     pub collection: String,
     pub rkey: String,
     pub swap_cid: Option<Cid>,
+    pub collections: Option<Vec<String>>,
 }
 
-pub fn blobs_for_write(record: RepoRecord, validate: bool) -> anyhow::Result<Vec<PreparedBlobRef>> {
+/// A write outside the collections an app password or token was scoped to
+#[derive(thiserror::Error, Debug)]
+#[error("This token may not write to {collection}")]
+pub struct CollectionNotPermittedError {
+    pub collection: String,
+}
+
+/// Whether a token scoped to `patterns` may write to `collection`. Patterns
+/// are NSIDs, or prefixes like `app.bbs.*` covering every NSID under them.
+pub fn collection_permitted(collection: &str, patterns: &[String]) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix(".*") {
+            Some(prefix) => collection
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('.')),
+            None => collection == pattern,
+        })
+}
+
+/// Checks a pattern a token is being scoped to when it's created.
+pub fn validate_collection_pattern(pattern: &str) -> anyhow::Result<()> {
+    match pattern.strip_suffix(".*") {
+        // A prefix needs at least the authority's two segments
+        Some(prefix) => {
+            let segments = prefix.split('.').collect::<Vec<_>>();
+            if segments.len() < 2
+                || segments.iter().any(|segment| {
+                    segment.is_empty()
+                        || !segment
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
+            {
+                bail!("Invalid collection pattern: {pattern}")
+            }
+        }
+        None => {
+            if ensure_valid_nsid(pattern).is_err() {
+                bail!("Invalid collection: {pattern}")
+            }
+        }
+    }
+    Ok(())
+}
+
+fn assert_collection_permitted(
+    collection: &str,
+    collections: &Option<Vec<String>>,
+) -> anyhow::Result<()> {
+    match collections {
+        Some(patterns) if !collection_permitted(collection, patterns) => {
+            Err(CollectionNotPermittedError {
+                collection: collection.to_string(),
+            }
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn blobs_for_write(
+    record: &RepoRecord,
+    validate: bool,
+) -> anyhow::Result<Vec<PreparedBlobRef>> {
     let refs = find_blob_refs(Lex::Map(record.clone()), None, None);
     let record_type = match record.get("$type") {
         Some(Lex::Ipld(Ipld::String(t))) => Some(t),
@@ -124,16 +200,38 @@ pub fn find_blob_refs(val: Lex, path: Option<Vec<String>>, layer: Option<u8>) ->
             },
         },
         Lex::Ipld(_) => vec![],
-        Lex::Map(map) => map
-            .into_iter()
-            .flat_map(|(key, item)| {
-                find_blob_refs(
-                    item,
-                    Some([path.as_slice(), [key].as_slice()].concat()),
-                    Some(layer + 1),
-                )
-            })
-            .collect::<Vec<FoundBlobRef>>(),
+        Lex::Map(map) => match decoded_blob_ref(&map) {
+            Some(blob) => vec![FoundBlobRef { r#ref: blob, path }],
+            None => map
+                .into_iter()
+                .flat_map(|(key, item)| {
+                    find_blob_refs(
+                        item,
+                        Some([path.as_slice(), [key].as_slice()].concat()),
+                        Some(layer + 1),
+                    )
+                })
+                .collect::<Vec<FoundBlobRef>>(),
+        },
+    }
+}
+
+/// A blob ref as it comes out of a decoded DAG-CBOR record, with its `ref` a
+/// CID link rather than `{"$link": ...}` JSON.
+fn decoded_blob_ref(map: &RepoRecord) -> Option<BlobRef> {
+    match (
+        map.get("$type"),
+        map.get("ref"),
+        map.get("mimeType"),
+        map.get("size"),
+    ) {
+        (
+            Some(Lex::Ipld(Ipld::String(r#type))),
+            Some(Lex::Ipld(Ipld::Link(cid))),
+            Some(Lex::Ipld(Ipld::String(mime_type))),
+            Some(Lex::Ipld(Ipld::Json(JsonValue::Number(size)))),
+        ) if r#type == "blob" => Some(BlobRef::new(*cid, mime_type.clone(), size.as_i64()?, None)),
+        _ => None,
     }
 }
 
@@ -144,6 +242,31 @@ pub fn assert_valid_record(record: &RepoRecord) -> anyhow::Result<()> {
     }
 }
 
+/// Checks the record against the collection's lexicon, for the collections
+/// rsky-lexicon has one for. Failures are a `ValidationError` naming the
+/// field, relative to the record.
+pub fn assert_lexicon_valid(collection: &str, record: &RepoRecord) -> anyhow::Result<()> {
+    if RECORD_VALIDATOR.has_record(collection) {
+        RECORD_VALIDATOR.validate_record(collection, &serde_json::to_value(record)?)?;
+    }
+    Ok(())
+}
+
+/// Checks the body of a local procedure, like `app.bbs.dm.sendMessage`,
+/// against its lexicon's input schema.
+pub fn assert_input_valid(nsid: &str, input: &JsonValue) -> Result<(), ValidationError> {
+    RECORD_VALIDATOR.validate_input(nsid, input)
+}
+
+/// Places a validation failure from `assert_lexicon_valid` under `prefix`, the
+/// record's path in the request body
+pub fn validation_within(error: anyhow::Error, prefix: &str) -> anyhow::Error {
+    match error.downcast::<ValidationError>() {
+        Ok(error) => error.within(prefix).into(),
+        Err(error) => error,
+    }
+}
+
 pub fn set_collection_name(
     collection: &String,
     mut record: RepoRecord,
@@ -163,12 +286,49 @@ pub fn set_collection_name(
     Ok(record)
 }
 
-pub async fn cid_for_safe_record(record: RepoRecord) -> anyhow::Result<Cid> {
-    let lex = lex_to_ipld(Lex::Map(record));
-    let block = serde_ipld_dagcbor::to_vec(&lex)?;
-    // Confirm whether Block properly transforms between lex and cbor
-    let _ = cbor_to_lex(block)?;
-    cid_for_cbor(&lex)
+thread_local! {
+    /// Encoding buffer shared by the writes prepared on this thread, so a
+    /// batch of writes doesn't allocate one per record
+    static RECORD_BUF: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+/// CID of the record's block, encoded in one pass the same way the repo
+/// stores it. A record that encodes is one the commit can hold, so there's
+/// no decoding it back to check.
+pub fn cid_for_safe_record(record: &RepoRecord) -> anyhow::Result<Cid> {
+    RECORD_BUF.with(|buf| record_to_block(record, &mut buf.borrow_mut()))
+}
+
+/// Reads a record from a write's JSON value. Records with a `$type` this PDS
+/// has no lexicon for can instead be sent as their DAG-CBOR block, as
+/// `{"$bytes": "<base64>"}`, so the block the repo stores is byte for byte the
+/// one the client hashed. Those skip validation, so they need validate=false,
+/// and must already be canonical DAG-CBOR as the repo re-encodes what it reads.
+pub fn record_from_json(value: JsonValue, validate: Option<bool>) -> anyhow::Result<RepoRecord> {
+    let raw = value
+        .as_object()
+        .filter(|object| object.len() == 1)
+        .and_then(|object| object.get("$bytes"))
+        .and_then(JsonValue::as_str)
+        .map(str::to_string);
+    let Some(encoded) = raw else {
+        return Ok(serde_json::from_value(value)?);
+    };
+    if validate != Some(false) {
+        bail!("Records sent as DAG-CBOR bytes can't be validated, set validate to false")
+    }
+    let bytes = STANDARD_NO_PAD.decode(encoded.trim_end_matches('='))?;
+    let record = cbor_to_lex_record(bytes.clone())?;
+    // set_collection_name would otherwise add one and change the block
+    assert_valid_record(&record)?;
+    RECORD_BUF.with(|buf| {
+        let mut buf = buf.borrow_mut();
+        record_to_block(&record, &mut buf)?;
+        if *buf != bytes {
+            bail!("Record bytes aren't canonical DAG-CBOR")
+        }
+        Ok(record)
+    })
 }
 
 pub async fn prepare_create(opts: PrepareCreateOpts) -> anyhow::Result<PreparedCreateOrUpdate> {
@@ -180,11 +340,16 @@ pub async fn prepare_create(opts: PrepareCreateOpts) -> anyhow::Result<PreparedC
         validate,
         ..
     } = opts;
+    assert_collection_permitted(&collection, &opts.collections)?;
     let validate = validate.unwrap_or(true);
 
     let record = set_collection_name(&collection, opts.record, validate)?;
     if validate {
         assert_valid_record(&record)?;
+        assert_lexicon_valid(&collection, &record)?;
+        if collection == RULE_COLLECTION {
+            assert_valid_rule(&record)?;
+        }
     }
 
     // assert_no_explicit_slurs(rkey, record).await?;
@@ -194,10 +359,10 @@ pub async fn prepare_create(opts: PrepareCreateOpts) -> anyhow::Result<PreparedC
     Ok(PreparedCreateOrUpdate {
         action: WriteOpAction::Create,
         uri: uri.to_string(),
-        cid: cid_for_safe_record(record.clone()).await?,
+        cid: cid_for_safe_record(&record)?,
         swap_cid,
-        record: record.clone(),
-        blobs: blobs_for_write(record, validate)?,
+        blobs: blobs_for_write(&record, validate)?,
+        record,
     })
 }
 
@@ -210,21 +375,26 @@ pub async fn prepare_update(opts: PrepareUpdateOpts) -> anyhow::Result<PreparedC
         validate,
         ..
     } = opts;
+    assert_collection_permitted(&collection, &opts.collections)?;
     let validate = validate.unwrap_or(true);
 
     let record = set_collection_name(&collection, opts.record, validate)?;
     if validate {
         assert_valid_record(&record)?;
+        assert_lexicon_valid(&collection, &record)?;
+        if collection == RULE_COLLECTION {
+            assert_valid_rule(&record)?;
+        }
     }
     // assert_no_explicit_slurs(rkey, record).await?;
     let uri = AtUri::make(did, Some(collection), Some(rkey))?;
     Ok(PreparedCreateOrUpdate {
         action: WriteOpAction::Update,
         uri: uri.to_string(),
-        cid: cid_for_safe_record(record.clone()).await?,
+        cid: cid_for_safe_record(&record)?,
         swap_cid,
-        record: record.clone(),
-        blobs: blobs_for_write(record, validate)?,
+        blobs: blobs_for_write(&record, validate)?,
+        record,
     })
 }
 
@@ -234,7 +404,9 @@ pub fn prepare_delete(opts: PrepareDeleteOpts) -> anyhow::Result<PreparedDelete>
         collection,
         rkey,
         swap_cid,
+        collections,
     } = opts;
+    assert_collection_permitted(&collection, &collections)?;
     let uri = AtUri::make(did, Some(collection), Some(rkey))?;
     Ok(PreparedDelete {
         action: WriteOpAction::Delete,
@@ -244,6 +416,7 @@ pub fn prepare_delete(opts: PrepareDeleteOpts) -> anyhow::Result<PreparedDelete>
 }
 
 lazy_static! {
+    static ref RECORD_VALIDATOR: Validator = Validator::new(lexicons());
     static ref CONSTRAINTS: JsonValue = {
         json!({
             Ids::AppBskyActorProfile.as_str(): {
@@ -261,7 +434,190 @@ lazy_static! {
                 "embed/external/thumb": LEXICONS.app_bsky_embed_external.defs.external.properties.thumb,
                 "embed/media/images/image": LEXICONS.app_bsky_embed_images.defs.image.properties.image,
                 "embed/media/external/thumb": LEXICONS.app_bsky_embed_external.defs.external.properties.thumb
+            },
+            // Ciphertext is opaque, and whatever type uploadBlob sniffed from it is as good as any
+            ENCRYPTED_POST_COLLECTION: {
+                "ciphertext": { "type": "blob", "accept": ["*/*"], "maxSize": MAX_CIPHERTEXT_BYTES }
             }
         })
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_common::ipld::cid_for_cbor_bytes;
+    use std::collections::BTreeMap;
+
+    fn raw(bytes: &[u8]) -> JsonValue {
+        json!({ "$bytes": STANDARD_NO_PAD.encode(bytes) })
+    }
+
+    fn photo() -> Vec<u8> {
+        let blob = Ipld::Map(BTreeMap::from([
+            ("$type".to_string(), Ipld::String("blob".to_string())),
+            (
+                "ref".to_string(),
+                Ipld::Link(cid_for_cbor_bytes(b"image").unwrap()),
+            ),
+            (
+                "mimeType".to_string(),
+                Ipld::String("image/png".to_string()),
+            ),
+            ("size".to_string(), Ipld::Json(json!(1234))),
+        ]));
+        serde_ipld_dagcbor::to_vec(&Ipld::Map(BTreeMap::from([
+            (
+                "$type".to_string(),
+                Ipld::String("com.example.photo".to_string()),
+            ),
+            ("image".to_string(), blob),
+            ("score".to_string(), Ipld::Json(json!(-3))),
+        ])))
+        .unwrap()
+    }
+
+    #[test]
+    fn scoped_tokens_only_write_matching_collections() {
+        let patterns = vec!["app.bbs.*".to_string(), "com.example.bot".to_string()];
+        assert!(collection_permitted("app.bbs.post", &patterns));
+        assert!(collection_permitted("app.bbs.section.meta", &patterns));
+        assert!(collection_permitted("com.example.bot", &patterns));
+        assert!(!collection_permitted("app.bbsx.post", &patterns));
+        assert!(!collection_permitted("app.bsky.feed.post", &patterns));
+        assert!(!collection_permitted("com.example.bot2", &patterns));
+        assert!(prepare_delete(PrepareDeleteOpts {
+            did: "did:ckb:alice".to_string(),
+            collection: "app.bsky.feed.post".to_string(),
+            rkey: "3l3qo2vuowo2b".to_string(),
+            swap_cid: None,
+            collections: Some(patterns),
+        })
+        .unwrap_err()
+        .is::<CollectionNotPermittedError>());
+    }
+
+    #[test]
+    fn validates_collection_patterns() {
+        assert!(validate_collection_pattern("app.bbs.*").is_ok());
+        assert!(validate_collection_pattern("app.bbs.post").is_ok());
+        // A prefix has to name at least a domain authority
+        assert!(validate_collection_pattern("app.*").is_err());
+        assert!(validate_collection_pattern("*").is_err());
+        assert!(validate_collection_pattern("app.*.post").is_err());
+        assert!(validate_collection_pattern("app..*").is_err());
+    }
+
+    #[test]
+    fn stores_raw_records_byte_for_byte() {
+        let bytes = photo();
+        let record = record_from_json(raw(&bytes), Some(false)).unwrap();
+        assert_eq!(
+            cid_for_safe_record(&record).unwrap(),
+            cid_for_cbor_bytes(&bytes).unwrap()
+        );
+        let blobs = blobs_for_write(&record, false).unwrap();
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(blobs[0].cid, cid_for_cbor_bytes(b"image").unwrap());
+        assert_eq!(blobs[0].mime_type, "image/png");
+    }
+
+    #[test]
+    fn raw_records_skip_validation_only() {
+        let bytes = photo();
+        assert!(record_from_json(raw(&bytes), None).is_err());
+        assert!(record_from_json(raw(&bytes), Some(true)).is_err());
+    }
+
+    #[test]
+    fn rejects_raw_records_that_would_change() {
+        // {"text": "hi", "$type": "a.b.c"} with its keys out of DAG-CBOR order
+        let mut unsorted = vec![0xa2, 0x65];
+        unsorted.extend_from_slice(b"$type");
+        unsorted.push(0x65);
+        unsorted.extend_from_slice(b"a.b.c");
+        unsorted.push(0x64);
+        unsorted.extend_from_slice(b"text");
+        unsorted.push(0x62);
+        unsorted.extend_from_slice(b"hi");
+        assert!(record_from_json(raw(&unsorted), Some(false)).is_err());
+
+        let untyped = serde_ipld_dagcbor::to_vec(&Ipld::Map(BTreeMap::from([(
+            "text".to_string(),
+            Ipld::String("hi".to_string()),
+        )])))
+        .unwrap();
+        assert!(record_from_json(raw(&untyped), Some(false)).is_err());
+    }
+
+    #[test]
+    fn reads_json_records_as_before() {
+        let value = json!({ "$bytes": "aGk", "text": "hi" });
+        let record = record_from_json(value.clone(), Some(false)).unwrap();
+        assert_eq!(record, serde_json::from_value::<RepoRecord>(value).unwrap());
+    }
+
+    #[test]
+    fn names_the_field_a_record_fails_on() {
+        let mut post = json!({
+            "$type": "app.bbs.post",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "text": "hello",
+            "sectionId": 2,
+            "title": "Hello",
+        });
+        let record = record_from_json(post.clone(), None).unwrap();
+        assert_lexicon_valid("app.bbs.post", &record).unwrap();
+
+        post["title"] = json!("x".repeat(129));
+        let record = record_from_json(post, None).unwrap();
+        let error = assert_lexicon_valid("app.bbs.post", &record).unwrap_err();
+        let error = validation_within(error, "writes[3].value");
+        assert_eq!(
+            error.to_string(),
+            "writes[3].value.title: exceeds 128 graphemes"
+        );
+        assert_eq!(
+            error.downcast_ref::<ValidationError>().unwrap().path,
+            "writes[3].value.title"
+        );
+
+        // Collections without a lexicon here are left to the AppView
+        let other = record_from_json(json!({ "$type": "com.example.note" }), None).unwrap();
+        assert_lexicon_valid("com.example.note", &other).unwrap();
+    }
+
+    #[test]
+    fn checks_the_envelope_of_encrypted_posts_but_not_the_ciphertext() {
+        let mut envelope = json!({
+            "$type": "app.bbs.encryptedPost",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "sectionId": 7,
+            "algorithm": "xchacha20-poly1305",
+            "keyId": "2",
+            "nonce": "AAECAwQFBgcICQoLDA0ODxAREhMUFRYX",
+            "recipients": [{
+                "did": "did:web5:alice",
+                "keyRef": "did:web5:alice#atproto",
+                "wrappedKey": "c2VjdGlvbiBrZXk",
+            }],
+            "ciphertext": {
+                "$type": "blob",
+                "ref": { "$link": cid_for_cbor_bytes(b"sealed").unwrap().to_string() },
+                "mimeType": "application/octet-stream",
+                "size": 2048,
+            },
+        });
+        let record = record_from_json(envelope.clone(), None).unwrap();
+        assert_lexicon_valid(ENCRYPTED_POST_COLLECTION, &record).unwrap();
+        let constraint: crate::lexicon::lexicons::Image2 =
+            serde_json::from_value(CONSTRAINTS[ENCRYPTED_POST_COLLECTION]["ciphertext"].clone())
+                .unwrap();
+        assert_eq!(constraint.accept, vec!["*/*".to_string()]);
+        assert_eq!(constraint.max_size, MAX_CIPHERTEXT_BYTES as i64);
+
+        envelope.as_object_mut().unwrap().remove("keyId");
+        let record = record_from_json(envelope, None).unwrap();
+        assert!(assert_lexicon_valid(ENCRYPTED_POST_COLLECTION, &record).is_err());
+    }
+}
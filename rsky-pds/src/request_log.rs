@@ -0,0 +1,162 @@
+use crate::auth_verifier::AuthenticatedDid;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Cursor;
+use std::time::Instant;
+
+/// Tracing target of the per request lines, to filter or route them apart
+/// from the rest of the logs
+pub const REQUEST_LOG_TARGET: &str = "rsky_pds::request";
+const REDACTED: &str = "[redacted]";
+/// Keys whose values are never logged, compared lowercased without `_`
+const SECRET_KEYS: [&str; 10] = [
+    "password",
+    "newpassword",
+    "accessjwt",
+    "refreshjwt",
+    "token",
+    "authorization",
+    "secret",
+    "signedbytes",
+    "sig",
+    "signature",
+];
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.replace('_', "").to_lowercase();
+    SECRET_KEYS.contains(&key.as_str())
+}
+
+/// JWTs are three base64url segments, the first an encoded `{"` header
+fn is_jwt(value: &str) -> bool {
+    value.starts_with("eyJ") && value.split('.').count() == 3
+}
+
+/// Masks passwords, JWTs and signatures anywhere in `value`.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret_key(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::String(string) if is_jwt(string) => *string = REDACTED.to_string(),
+        _ => (),
+    }
+}
+
+/// JSON of `value` with secrets masked, for logging request and response
+/// bodies.
+pub fn redacted<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(error) => format!("<unserializable: {error}>"),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestLogLine<'a> {
+    method: &'a str,
+    path: &'a str,
+    /// Path pattern of the matched route, unset when nothing matched
+    route: Option<String>,
+    did: Option<&'a str>,
+    status: u16,
+    latency_ms: u64,
+    /// `error` of an XRPC error body
+    error: Option<String>,
+}
+
+struct RequestStarted(Instant);
+
+/// Reads the error code out of a JSON error body, putting the body back.
+async fn error_code(res: &mut Response<'_>) -> Option<String> {
+    if !res
+        .content_type()
+        .is_some_and(|content_type| content_type.is_json())
+    {
+        return None;
+    }
+    let body = res.body_mut().to_bytes().await.ok()?;
+    let code = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(|error| error.to_string()));
+    res.set_sized_body(body.len(), Cursor::new(body));
+    code
+}
+
+/// Logs one JSON line per request. Only the DID of the access token is
+/// recorded about the caller, never headers, query strings or bodies.
+pub struct RequestLogFairing;
+
+#[rocket::async_trait]
+impl Fairing for RequestLogFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Log requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStarted(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let started = req.local_cache(|| RequestStarted(Instant::now()));
+        let error = match res.status().code >= 400 {
+            true => error_code(res).await,
+            false => None,
+        };
+        let line = RequestLogLine {
+            method: req.method().as_str(),
+            path: req.uri().path().as_str(),
+            route: req.route().map(|route| route.uri.path().to_string()),
+            did: req.local_cache(|| AuthenticatedDid(None)).0.as_deref(),
+            status: res.status().code,
+            latency_ms: started.0.elapsed().as_millis() as u64,
+            error,
+        };
+        match serde_json::to_string(&line) {
+            Ok(line) => tracing::info!(target: REQUEST_LOG_TARGET, "{line}"),
+            Err(error) => tracing::error!("@LOG: ERROR: failed to serialize request log: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_secrets() {
+        let mut value = json!({
+            "identifier": "alice.test",
+            "password": "hunter2",
+            "root": {"did": "did:web5:alice", "signed_bytes": "0xabc"},
+            "session": [{"accessJwt": "a"}, "eyJhbGciOiJFUzI1NksifQ.eyJzdWIiOiJ4In0.c2ln"],
+        });
+        redact(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "identifier": "alice.test",
+                "password": REDACTED,
+                "root": {"did": "did:web5:alice", "signed_bytes": REDACTED},
+                "session": [{"accessJwt": REDACTED}, REDACTED],
+            })
+        );
+    }
+}
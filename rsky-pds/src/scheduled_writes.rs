@@ -0,0 +1,428 @@
+use crate::account_manager::helpers::account::AvailabilityFlags;
+use crate::account_manager::AccountManager;
+use crate::actor_store::blob::store::SharedBlobStore;
+use crate::apis::com::atproto::web5::direct_writes::{
+    commit_direct_writes, parse_swap_commit, prepare_direct_writes, verify_account_keys,
+};
+use crate::apis::ApiError;
+use crate::config::ServerConfig;
+use crate::db::{establish_connection_for_jobs, DbConnPool};
+use crate::flags::SharedFlagStore;
+use crate::identity::SharedDidMethods;
+use crate::models::ScheduledWrite as ScheduledWriteRow;
+use crate::sequencer::Sequencer;
+use crate::shutdown::SharedShutdown;
+use crate::SharedSequencer;
+use anyhow::{anyhow, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{insert_into, update};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Orbit, Rocket};
+use rsky_common::RFC3339_VARIANT;
+use rsky_lexicon::com::atproto::web5::{
+    CommitMeta, DirectWritesInput, DirectWritesOutput, ScheduledWrite, ScheduledWriteStatus,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+pub const SCHEDULED_WRITES_LXM: &str = "com.atproto.web5.scheduleDirectWrites";
+/// How long a claimed write is left to its replica before another one may
+/// pick it up, in case the first died while publishing it
+const CLAIM_LEASE_MS: i64 = 5 * 60 * 1000;
+/// Most due writes one replica claims per run
+const CLAIM_BATCH_SIZE: i64 = 20;
+
+/// A scheduled write could not be found, or is past the point it can be canceled
+#[derive(thiserror::Error, Debug)]
+#[error("Scheduled write {0} is not pending")]
+pub struct ScheduledWriteNotPendingError(pub i64);
+
+pub fn to_view(row: ScheduledWriteRow) -> Result<ScheduledWrite> {
+    let commit = match (row.commit_cid, row.commit_rev) {
+        (Some(cid), Some(rev)) => Some(CommitMeta { cid, rev }),
+        _ => None,
+    };
+    Ok(ScheduledWrite {
+        id: row.id.to_string(),
+        publish_at: row.publish_at,
+        status: row.status.parse()?,
+        created_at: row.created_at,
+        commit,
+        error: row.error,
+    })
+}
+
+/// `publish_at` in the format timestamps are stored and compared in
+pub fn format_publish_at(publish_at: &chrono::DateTime<Utc>) -> String {
+    format!("{}", publish_at.format(RFC3339_VARIANT))
+}
+
+/// Writes not yet published or canceled that `did` has scheduled
+pub fn count_pending(conn: &mut PgConnection, did: &str) -> QueryResult<i64> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    ScheduledSchema::scheduled_write
+        .filter(ScheduledSchema::did.eq(did))
+        .filter(ScheduledSchema::status.eq(ScheduledWriteStatus::Pending.as_str()))
+        .count()
+        .get_result(conn)
+}
+
+/// Holds `input` until `publish_at`
+pub fn insert(
+    conn: &mut PgConnection,
+    did: &str,
+    input: &DirectWritesInput,
+    publish_at: String,
+) -> Result<ScheduledWriteRow> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    Ok(insert_into(ScheduledSchema::scheduled_write)
+        .values((
+            ScheduledSchema::did.eq(did),
+            ScheduledSchema::input.eq(serde_json::to_string(input)?),
+            ScheduledSchema::publishAt.eq(publish_at),
+            ScheduledSchema::status.eq(ScheduledWriteStatus::Pending.as_str()),
+            ScheduledSchema::createdAt.eq(rsky_common::now()),
+        ))
+        .returning(ScheduledWriteRow::as_returning())
+        .get_result(conn)?)
+}
+
+/// `did`'s scheduled writes, soonest first. `cursor` is `<publishAt>::<id>`
+/// of the last write of the previous page.
+pub fn list(
+    conn: &mut PgConnection,
+    did: &str,
+    limit: i64,
+    cursor: Option<String>,
+) -> Result<Vec<ScheduledWriteRow>> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    let mut query = ScheduledSchema::scheduled_write
+        .filter(ScheduledSchema::did.eq(did))
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        let (publish_at, id) = cursor
+            .split_once("::")
+            .and_then(|(publish_at, id)| Some((publish_at.to_string(), id.parse::<i64>().ok()?)))
+            .ok_or_else(|| anyhow!("Malformed cursor"))?;
+        query = query.filter(
+            ScheduledSchema::publishAt
+                .gt(publish_at.clone())
+                .or(ScheduledSchema::publishAt
+                    .eq(publish_at)
+                    .and(ScheduledSchema::id.gt(id))),
+        );
+    }
+    Ok(query
+        .order((ScheduledSchema::publishAt.asc(), ScheduledSchema::id.asc()))
+        .limit(limit)
+        .select(ScheduledWriteRow::as_select())
+        .load(conn)?)
+}
+
+/// Cancels `did`'s write `id` unless a replica is publishing it right now
+pub fn cancel(conn: &mut PgConnection, did: &str, id: i64) -> Result<ScheduledWriteRow> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    let now = rsky_common::now();
+    update(ScheduledSchema::scheduled_write.find(id))
+        .filter(ScheduledSchema::did.eq(did))
+        .filter(ScheduledSchema::status.eq(ScheduledWriteStatus::Pending.as_str()))
+        .filter(
+            ScheduledSchema::claimedUntil
+                .is_null()
+                .or(ScheduledSchema::claimedUntil.le(&now)),
+        )
+        .set((
+            ScheduledSchema::status.eq(ScheduledWriteStatus::Canceled.as_str()),
+            ScheduledSchema::finishedAt.eq(&now),
+        ))
+        .returning(ScheduledWriteRow::as_returning())
+        .get_result(conn)
+        .optional()?
+        .ok_or_else(|| ScheduledWriteNotPendingError(id).into())
+}
+
+/// Applies scheduled writes once their `publishAt` passes, the same way
+/// directWrites would have: the account and its keys are checked again, and
+/// the commit must still build on the repo's head, so a write made to the
+/// repo after scheduling fails the scheduled one. Runs on every replica, each
+/// claiming different due writes.
+pub struct ScheduledWritesPublisher {
+    pub pool: DbConnPool,
+    pub sequencer: RwLock<Sequencer>,
+    pub blob_store: SharedBlobStore,
+    pub cfg: ServerConfig,
+    pub did_methods: SharedDidMethods,
+    pub flags: SharedFlagStore,
+    pub shutdown: SharedShutdown,
+}
+
+impl ScheduledWritesPublisher {
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(
+            self.cfg.scheduled_writes.interval_ms.max(1000),
+        ));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: failed to publish scheduled writes: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+        let conn = &mut establish_connection_for_jobs()?;
+        for row in claim(conn)? {
+            // left for another replica once its lease runs out if the PDS is draining
+            let Some(_write) = self.shutdown.begin_write() else {
+                return Ok(());
+            };
+            let (id, did) = (row.id, row.did.clone());
+            let finished = match self.publish(row).await.map(|output| output.commit) {
+                Ok(Some(commit)) => {
+                    tracing::info!("Published scheduled write {id} for {did} at {}", commit.rev);
+                    Finished::Published(commit)
+                }
+                Ok(None) => {
+                    tracing::warn!("Scheduled write {id} for {did} made no commit");
+                    Finished::Failed("No commit".to_string())
+                }
+                Err(error) => {
+                    tracing::warn!("Scheduled write {id} for {did} failed: {error:?}");
+                    Finished::Failed(error.message())
+                }
+            };
+            // the write has already been applied, so if recording that fails on this
+            // connection it's tried again on a fresh one rather than left to be republished
+            if let Err(error) = finish(conn, id, &finished) {
+                tracing::warn!("Retrying finishing scheduled write {id} for {did}: {error}");
+                finish(&mut establish_connection_for_jobs()?, id, &finished)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn publish(&self, row: ScheduledWriteRow) -> Result<DirectWritesOutput, ApiError> {
+        let input: DirectWritesInput = serde_json::from_str(&row.input)
+            .map_err(|error| ApiError::InvalidRequest(error.to_string()))?;
+        self.flags
+            .check_writable(input.writes.iter().map(|write| write.collection()))?;
+        let (Some(db), Some(account_db)) = (self.pool.get().await, self.pool.get().await) else {
+            return Err(ApiError::ServiceUnavailable);
+        };
+        let account_manager = AccountManager::new(Arc::new(account_db));
+        let account = account_manager
+            .get_account(
+                &row.did,
+                Some(AvailabilityFlags {
+                    include_deactivated: Some(true),
+                    include_taken_down: None,
+                }),
+            )
+            .await?
+            .ok_or(ApiError::AccountNotFound)?;
+        verify_account_keys(
+            &account,
+            input.ckb_addr,
+            &input.signing_key,
+            &self.cfg,
+            &self.did_methods,
+            &account_manager,
+            self.flags.get().chain_strict,
+            SCHEDULED_WRITES_LXM,
+        )
+        .await?;
+        // the session's collection scope was checked when the writes were scheduled
+        let writes = prepare_direct_writes(&row.did, input.writes, input.validate, &None).await?;
+        let swap_commit = parse_swap_commit(input.swap_commit)?;
+        commit_direct_writes(
+            &row.did,
+            writes,
+            input.validate,
+            swap_commit,
+            input.signing_key,
+            input.root,
+            &self.sequencer,
+            &self.blob_store,
+            &self.cfg,
+            db,
+            &account_manager,
+        )
+        .await
+    }
+}
+
+/// How publishing a claimed write went
+enum Finished {
+    Published(CommitMeta),
+    Failed(String),
+}
+
+/// Records how publishing write `id` went. Only a still pending write is
+/// updated, so a cancel made after its lease ran out isn't overwritten.
+fn finish(conn: &mut PgConnection, id: i64, finished: &Finished) -> QueryResult<usize> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    let row = update(ScheduledSchema::scheduled_write.find(id))
+        .filter(ScheduledSchema::status.eq(ScheduledWriteStatus::Pending.as_str()));
+    match finished {
+        Finished::Published(commit) => row
+            .set((
+                ScheduledSchema::status.eq(ScheduledWriteStatus::Published.as_str()),
+                ScheduledSchema::commitCid.eq(Some(commit.cid.clone())),
+                ScheduledSchema::commitRev.eq(Some(commit.rev.clone())),
+                ScheduledSchema::finishedAt.eq(Some(rsky_common::now())),
+            ))
+            .execute(conn),
+        Finished::Failed(error) => row
+            .set((
+                ScheduledSchema::status.eq(ScheduledWriteStatus::Failed.as_str()),
+                ScheduledSchema::error.eq(Some(error.clone())),
+                ScheduledSchema::finishedAt.eq(Some(rsky_common::now())),
+            ))
+            .execute(conn),
+    }
+}
+
+/// Leases the due pending writes to this replica, oldest first
+fn claim(conn: &mut PgConnection) -> Result<Vec<ScheduledWriteRow>> {
+    use crate::schema::pds::scheduled_write::dsl as ScheduledSchema;
+
+    let now = rsky_common::now();
+    Ok(conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let ids = ScheduledSchema::scheduled_write
+            .filter(ScheduledSchema::status.eq(ScheduledWriteStatus::Pending.as_str()))
+            .filter(ScheduledSchema::publishAt.le(&now))
+            .filter(
+                ScheduledSchema::claimedUntil
+                    .is_null()
+                    .or(ScheduledSchema::claimedUntil.le(&now)),
+            )
+            .order((ScheduledSchema::publishAt.asc(), ScheduledSchema::id.asc()))
+            .limit(CLAIM_BATCH_SIZE)
+            .select(ScheduledSchema::id)
+            .for_update()
+            .skip_locked()
+            .load::<i64>(conn)?;
+        let mut rows = update(ScheduledSchema::scheduled_write)
+            .filter(ScheduledSchema::id.eq_any(ids))
+            .set(ScheduledSchema::claimedUntil.eq(Some(format_after(CLAIM_LEASE_MS))))
+            .returning(ScheduledWriteRow::as_returning())
+            .get_results(conn)?;
+        rows.sort_by(|a, b| (&a.publish_at, a.id).cmp(&(&b.publish_at, b.id)));
+        Ok(rows)
+    })?)
+}
+
+fn format_after(ms: i64) -> String {
+    format!(
+        "{}",
+        (Utc::now() + ChronoDuration::milliseconds(ms)).format(RFC3339_VARIANT)
+    )
+}
+
+/// Starts the [`ScheduledWritesPublisher`] once Rocket has set up the
+/// database pool and managed state it publishes with.
+pub struct ScheduledWritesFairing;
+
+#[rocket::async_trait]
+impl Fairing for ScheduledWritesFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Publish scheduled writes",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (
+            Some(pool),
+            Some(sequencer),
+            Some(blob_store),
+            Some(cfg),
+            Some(did_methods),
+            Some(flags),
+            Some(shutdown),
+        ) = (
+            DbConnPool::of(rocket),
+            rocket.state::<SharedSequencer>(),
+            rocket.state::<SharedBlobStore>(),
+            rocket.state::<ServerConfig>(),
+            rocket.state::<SharedDidMethods>(),
+            rocket.state::<SharedFlagStore>(),
+            rocket.state::<SharedShutdown>(),
+        )
+        else {
+            tracing::error!("@LOG: ERROR: scheduled writes can't be published, missing state");
+            return;
+        };
+        let publisher = ScheduledWritesPublisher {
+            pool,
+            sequencer: RwLock::new(sequencer.sequencer.read().await.clone()),
+            blob_store: blob_store.clone(),
+            cfg: cfg.clone(),
+            did_methods: did_methods.clone(),
+            flags: flags.clone(),
+            shutdown: shutdown.clone(),
+        };
+        tokio::spawn(async move { publisher.start().await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_published_and_pending_writes() {
+        let row = ScheduledWriteRow {
+            id: 7,
+            did: "did:web5:alice".to_string(),
+            input: "{}".to_string(),
+            publish_at: "2025-01-03T00:00:00.000Z".to_string(),
+            status: "published".to_string(),
+            created_at: "2025-01-01T00:00:00.000Z".to_string(),
+            claimed_until: Some("2025-01-03T00:05:00.000Z".to_string()),
+            commit_cid: Some(
+                "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm".to_string(),
+            ),
+            commit_rev: Some("3l4qxdfqfwk2a".to_string()),
+            error: None,
+            finished_at: Some("2025-01-03T00:00:01.000Z".to_string()),
+        };
+        let view = to_view(row.clone()).unwrap();
+        assert_eq!(view.id, "7");
+        assert_eq!(view.status, ScheduledWriteStatus::Published);
+        assert_eq!(view.commit.unwrap().rev, "3l4qxdfqfwk2a");
+
+        let pending = to_view(ScheduledWriteRow {
+            status: "pending".to_string(),
+            commit_cid: None,
+            commit_rev: None,
+            ..row.clone()
+        })
+        .unwrap();
+        assert_eq!(pending.status, ScheduledWriteStatus::Pending);
+        assert!(pending.commit.is_none());
+
+        assert!(to_view(ScheduledWriteRow {
+            status: "sent".to_string(),
+            ..row
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn formats_publish_at_like_stored_timestamps() {
+        let publish_at = chrono::DateTime::from_timestamp(1_735_689_600, 0).unwrap();
+        assert_eq!(format_publish_at(&publish_at), "2025-01-01T00:00:00.000Z");
+    }
+}
@@ -10,6 +10,20 @@ pub mod pds {
             createdAt -> Varchar,
             invitesDisabled -> Int2,
             emailConfirmedAt -> Nullable<Varchar>,
+            passwordLogin -> Bool,
+        }
+    }
+
+    diesel::table! {
+        pds.account_event_outbox (id) {
+            id -> Int8,
+            handler -> Varchar,
+            did -> Varchar,
+            event -> Varchar,
+            attempts -> Int4,
+            nextAttemptAt -> Varchar,
+            lastError -> Nullable<Varchar>,
+            createdAt -> Varchar,
         }
     }
 
@@ -31,6 +45,7 @@ pub mod pds {
             deactivatedAt -> Nullable<Varchar>,
             deleteAfter -> Nullable<Varchar>,
             ckbAddress -> Nullable<Varchar>,
+            suspendedUntil -> Nullable<Varchar>,
         }
     }
 
@@ -40,6 +55,20 @@ pub mod pds {
             name -> Varchar,
             password -> Varchar,
             createdAt -> Varchar,
+            collections -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        pds.audit_log (id) {
+            id -> Int8,
+            action -> Varchar,
+            actorDid -> Nullable<Varchar>,
+            subjectDid -> Varchar,
+            subjectUri -> Nullable<Varchar>,
+            subjectCid -> Nullable<Varchar>,
+            detail -> Nullable<Varchar>,
+            createdAt -> Varchar,
         }
     }
 
@@ -51,6 +80,175 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.bbs_crosspost (uri) {
+            uri -> Varchar,
+            did -> Varchar,
+            mirrorUri -> Nullable<Varchar>,
+            createdAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_dm_convo (id) {
+            id -> Varchar,
+            createdAt -> Varchar,
+            lastMessageAt -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_dm_member (convoId, did) {
+            convoId -> Varchar,
+            did -> Varchar,
+            lastReadId -> Nullable<Int8>,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_dm_message (id) {
+            id -> Int8,
+            convoId -> Varchar,
+            senderDid -> Varchar,
+            text -> Varchar,
+            createdAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_draft (id) {
+            id -> Int8,
+            did -> Varchar,
+            collection -> Varchar,
+            value -> Varchar,
+            createdAt -> Varchar,
+            updatedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_graph (uri) {
+            uri -> Varchar,
+            collection -> Varchar,
+            creatorDid -> Varchar,
+            subjectDid -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_link_card (url) {
+            url -> Varchar,
+            status -> Varchar,
+            title -> Nullable<Varchar>,
+            description -> Nullable<Varchar>,
+            thumbCid -> Nullable<Varchar>,
+            thumb -> Nullable<Bytea>,
+            attempts -> Int4,
+            nextAttemptAt -> Nullable<Varchar>,
+            fetchedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_post_link (uri) {
+            uri -> Varchar,
+            url -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_read_state (did, thread) {
+            did -> Varchar,
+            thread -> Varchar,
+            readReplyCount -> Int8,
+            lastReadAt -> Varchar,
+            updatedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_review (uri) {
+            uri -> Varchar,
+            cid -> Varchar,
+            did -> Varchar,
+            source -> Varchar,
+            reason -> Varchar,
+            createdAt -> Varchar,
+            resolvedAt -> Nullable<Varchar>,
+            held -> Bool,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_section (sectionId) {
+            sectionId -> Int8,
+            status -> Varchar,
+            updatedAt -> Varchar,
+            updatedBy -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_section_stats (sectionId) {
+            sectionId -> Int8,
+            totalPosts -> Int8,
+            totalReplies -> Int8,
+            posts7d -> Int8,
+            posts30d -> Int8,
+            lastActivityAt -> Nullable<Varchar>,
+            computedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_stats (id) {
+            id -> Int4,
+            totalPosts -> Int8,
+            totalReplies -> Int8,
+            activeUsers7d -> Int8,
+            activeUsers30d -> Int8,
+            computedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_thread_index (uri) {
+            uri -> Varchar,
+            did -> Varchar,
+            collection -> Varchar,
+            thread -> Varchar,
+            sectionId -> Nullable<Int8>,
+            indexedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_thread_watch (uri) {
+            uri -> Varchar,
+            did -> Varchar,
+            thread -> Varchar,
+            muted -> Bool,
+        }
+    }
+
+    diesel::table! {
+        pds.bbs_trending (uri) {
+            uri -> Varchar,
+            cid -> Varchar,
+            did -> Varchar,
+            sectionId -> Int8,
+            title -> Varchar,
+            text -> Varchar,
+            rank -> Int8,
+            hotScore -> Float8,
+            voteScore -> Int8,
+            replyCount -> Int8,
+            createdAt -> Varchar,
+            lastActivityAt -> Varchar,
+            computedAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         pds.blob (cid, did) {
             cid -> Varchar,
@@ -65,6 +263,29 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.blob_gc (cid, did) {
+            cid -> Varchar,
+            did -> Varchar,
+            size -> Int4,
+            quarantinedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.blob_upload (id) {
+            id -> Varchar,
+            did -> Varchar,
+            mimeType -> Varchar,
+            size -> Int4,
+            bytesReceived -> Int4,
+            expectedCid -> Nullable<Varchar>,
+            createdAt -> Varchar,
+            expiresAt -> Varchar,
+            chunkCount -> Int4,
+        }
+    }
+
     diesel::table! {
         pds.did_doc (did) {
             did -> Varchar,
@@ -73,6 +294,15 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.email_notification_pref (did) {
+            did -> Varchar,
+            replies -> Bool,
+            mentions -> Bool,
+            updatedAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         pds.email_token (purpose, did) {
             purpose -> Varchar,
@@ -82,6 +312,16 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.handle_reservation (handle) {
+            handle -> Varchar,
+            ckbAddress -> Varchar,
+            signingKey -> Varchar,
+            reservedAt -> Varchar,
+            expiresAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         pds.invite_code (code) {
             code -> Varchar,
@@ -101,6 +341,58 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.jwt_key (kid) {
+            kid -> Varchar,
+            privateKeyHex -> Varchar,
+            activeAt -> Varchar,
+            retiredAt -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        pds.label (seq) {
+            seq -> Int8,
+            src -> Varchar,
+            uri -> Varchar,
+            cid -> Nullable<Varchar>,
+            val -> Varchar,
+            neg -> Bool,
+            cts -> Varchar,
+            exp -> Nullable<Varchar>,
+            sig -> Bytea,
+        }
+    }
+
+    diesel::table! {
+        pds.oauth_request (id) {
+            id -> Varchar,
+            clientId -> Varchar,
+            redirectUri -> Varchar,
+            scope -> Varchar,
+            state -> Nullable<Varchar>,
+            codeChallenge -> Varchar,
+            loginHint -> Nullable<Varchar>,
+            dpopJkt -> Varchar,
+            did -> Nullable<Varchar>,
+            code -> Nullable<Varchar>,
+            expiresAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.oauth_session (refreshTokenId) {
+            refreshTokenId -> Varchar,
+            did -> Varchar,
+            clientId -> Varchar,
+            scope -> Varchar,
+            dpopJkt -> Varchar,
+            accessTokenHash -> Varchar,
+            createdAt -> Varchar,
+            updatedAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         pds.record (uri) {
             uri -> Varchar,
@@ -122,6 +414,17 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.record_tombstone (uri) {
+            uri -> Varchar,
+            did -> Varchar,
+            cid -> Varchar,
+            content -> Bytea,
+            repoRev -> Varchar,
+            deletedAt -> Varchar,
+        }
+    }
+
     diesel::table! {
         pds.refresh_token (id) {
             id -> Varchar,
@@ -129,6 +432,7 @@ pub mod pds {
             expiresAt -> Varchar,
             nextId -> Nullable<Varchar>,
             appPasswordName -> Nullable<Varchar>,
+            familyId -> Nullable<Varchar>,
         }
     }
 
@@ -148,6 +452,8 @@ pub mod pds {
             cid -> Varchar,
             rev -> Varchar,
             indexedAt -> Varchar,
+            issuedRev -> Nullable<Varchar>,
+            issuedBase -> Nullable<Varchar>,
         }
     }
 
@@ -162,22 +468,132 @@ pub mod pds {
         }
     }
 
+    diesel::table! {
+        pds.revoked_session (id) {
+            id -> Varchar,
+            did -> Varchar,
+            expiresAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.rotated_refresh_token (id) {
+            id -> Varchar,
+            familyId -> Varchar,
+            did -> Varchar,
+            expiresAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.scheduled_write (id) {
+            id -> Int8,
+            did -> Varchar,
+            input -> Varchar,
+            publishAt -> Varchar,
+            status -> Varchar,
+            createdAt -> Varchar,
+            claimedUntil -> Nullable<Varchar>,
+            commitCid -> Nullable<Varchar>,
+            commitRev -> Nullable<Varchar>,
+            error -> Nullable<Varchar>,
+            finishedAt -> Nullable<Varchar>,
+        }
+    }
+
+    diesel::table! {
+        pds.signup_ip (did) {
+            did -> Varchar,
+            ip -> Varchar,
+            createdAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.subscriber_cursor (id) {
+            id -> Varchar,
+            cursor -> Int8,
+            updatedAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.webhook (id) {
+            id -> Varchar,
+            url -> Varchar,
+            secret -> Varchar,
+            collections -> Array<Text>,
+            dids -> Array<Text>,
+            cursor -> Int8,
+            attempts -> Int4,
+            nextAttemptAt -> Nullable<Varchar>,
+            lastError -> Nullable<Varchar>,
+            createdAt -> Varchar,
+        }
+    }
+
+    diesel::table! {
+        pds.webhook_dead_letter (id) {
+            id -> Int8,
+            webhookId -> Varchar,
+            seq -> Int8,
+            payload -> Varchar,
+            attempts -> Int4,
+            lastError -> Varchar,
+            failedAt -> Varchar,
+        }
+    }
+
     diesel::allow_tables_to_appear_in_same_query!(
         account,
+        account_event_outbox,
         account_pref,
         actor,
         app_password,
+        audit_log,
         backlink,
+        bbs_crosspost,
+        bbs_dm_convo,
+        bbs_dm_member,
+        bbs_dm_message,
+        bbs_draft,
+        bbs_graph,
+        bbs_link_card,
+        bbs_post_link,
+        bbs_read_state,
+        bbs_review,
+        bbs_section,
+        bbs_section_stats,
+        bbs_stats,
+        bbs_thread_index,
+        bbs_thread_watch,
+        bbs_trending,
         blob,
+        blob_gc,
+        blob_upload,
         did_doc,
+        email_notification_pref,
         email_token,
+        handle_reservation,
         invite_code,
         invite_code_use,
+        jwt_key,
+        label,
+        oauth_request,
+        oauth_session,
         record,
         record_blob,
+        record_tombstone,
         refresh_token,
         repo_block,
         repo_root,
         repo_seq,
+        revoked_session,
+        rotated_refresh_token,
+        scheduled_write,
+        signup_ip,
+        subscriber_cursor,
+        webhook,
+        webhook_dead_letter,
     );
 }
@@ -5,6 +5,7 @@ use anyhow::Result;
 use lexicon_cid::Cid;
 use rsky_common;
 use rsky_common::struct_to_cbor;
+use rsky_lexicon::app::bbs::SectionStatus;
 use rsky_lexicon::com::atproto::sync::AccountStatus as LexiconAccountStatus;
 use rsky_repo::block_map::BlockMap;
 use rsky_repo::car::blocks_to_car_file;
@@ -77,6 +78,18 @@ pub struct AccountEvt {
     pub status: Option<LexiconAccountStatus>,
 }
 
+/// A moderator opened or archived a BBS section. Not part of
+/// com.atproto.sync.subscribeRepos, only BBS appviews listening on the
+/// jetstream care about it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SectionEvt {
+    /// The moderator who changed the status
+    pub did: String,
+    #[serde(rename = "sectionId")]
+    pub section_id: i64,
+    pub status: SectionStatus,
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct SyncEvt {
     pub did: String,
@@ -161,6 +174,14 @@ pub struct TypedAccountEvt {
     pub evt: AccountEvt,
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TypedSectionEvt {
+    pub r#type: String, // 'section'
+    pub seq: i64,
+    pub time: String,
+    pub evt: SectionEvt,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum SeqEvt {
@@ -170,6 +191,7 @@ pub enum SeqEvt {
     TypedAccountEvt(TypedAccountEvt),
     // TypedTombstoneEvt(TypedTombstoneEvt),
     TypedSyncEvt(TypedSyncEvt),
+    TypedSectionEvt(TypedSectionEvt),
 }
 
 impl<'de> Deserialize<'de> for SeqEvt {
@@ -196,6 +218,9 @@ impl<'de> Deserialize<'de> for SeqEvt {
                 Some("account") => Ok(SeqEvt::TypedAccountEvt(
                     serde_json::from_value(value).map_err(DeserializerError::custom)?,
                 )),
+                Some("section") => Ok(SeqEvt::TypedSectionEvt(
+                    serde_json::from_value(value).map_err(DeserializerError::custom)?,
+                )),
                 _ => Err(DeserializerError::custom("Unknown event type")),
             }
         } else {
@@ -211,6 +236,17 @@ impl SeqEvt {
             SeqEvt::TypedIdentityEvt(this) => this.seq,
             SeqEvt::TypedAccountEvt(this) => this.seq,
             SeqEvt::TypedSyncEvt(this) => this.seq,
+            SeqEvt::TypedSectionEvt(this) => this.seq,
+        }
+    }
+
+    pub fn time(&self) -> &String {
+        match self {
+            SeqEvt::TypedCommitEvt(this) => &this.time,
+            SeqEvt::TypedIdentityEvt(this) => &this.time,
+            SeqEvt::TypedAccountEvt(this) => &this.time,
+            SeqEvt::TypedSyncEvt(this) => &this.time,
+            SeqEvt::TypedSectionEvt(this) => &this.time,
         }
     }
 }
@@ -322,6 +358,24 @@ pub async fn format_seq_account_evt(did: String, status: AccountStatus) -> Resul
     ))
 }
 
+pub async fn format_seq_section_evt(
+    did: String,
+    section_id: i64,
+    status: SectionStatus,
+) -> Result<models::RepoSeq> {
+    let evt = SectionEvt {
+        did: did.clone(),
+        section_id,
+        status,
+    };
+    Ok(models::RepoSeq::new(
+        did,
+        "section".to_string(),
+        struct_to_cbor(&evt)?,
+        rsky_common::now(),
+    ))
+}
+
 pub async fn format_seq_sync_evt(did: String, data: SyncEvtData) -> Result<models::RepoSeq> {
     let blocks = blocks_to_car_file(Some(&data.cid), data.blocks).await?;
     let evt = SyncEvt {
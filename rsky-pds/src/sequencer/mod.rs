@@ -1,24 +1,55 @@
+use crate::account_events::{AccountEvent, ACCOUNT_EVENTS};
 use crate::account_manager::helpers::account::AccountStatus;
+use crate::account_manager::helpers::account_cache;
 use crate::actor_store::repo::types::SyncEvtData;
 use crate::crawlers::Crawlers;
 use crate::db::establish_connection_for_sequencer;
+use crate::metrics;
 use crate::models;
 use crate::sequencer::events::{
     format_seq_account_evt, format_seq_commit, format_seq_handle_update, format_seq_identity_evt,
-    SeqEvt, TypedAccountEvt, TypedCommitEvt, TypedIdentityEvt, TypedSyncEvt,
+    format_seq_section_evt, CommitEvt, CommitEvtOp, CommitEvtOpAction, SeqEvt, TypedAccountEvt,
+    TypedCommitEvt, TypedIdentityEvt, TypedSectionEvt, TypedSyncEvt,
 };
+use crate::sequencer::store::SharedSequencerStore;
 use crate::EVENT_EMITTER;
 use anyhow::Result;
+use chrono::Utc;
 use diesel::*;
 use events::format_seq_sync_evt;
 use futures::{Stream, StreamExt};
-use rsky_common::time::SECOND;
-use rsky_common::{cbor_to_struct, wait};
+use rsky_common::time::{from_str_to_millis, SECOND};
+use rsky_common::{cbor_to_struct, struct_to_cbor, wait};
+use rsky_lexicon::app::bbs::SectionStatus;
 use rsky_repo::types::CommitDataWithOps;
 use std::cmp;
+use std::collections::HashSet;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::task::{Context, Poll, Waker};
 
+/// When the sequencer last polled repo_seq successfully, in ms since the epoch
+static LAST_POLLED_AT: AtomicI64 = AtomicI64::new(0);
+
+/// When the sequencer last polled repo_seq successfully, if it has since boot
+pub fn last_polled_at() -> Option<i64> {
+    match LAST_POLLED_AT.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(ms),
+    }
+}
+
+/// Highest seq the sequencer has emitted to subscribers, or skipped at boot
+static LAST_EMITTED_SEQ: AtomicI64 = AtomicI64::new(-1);
+
+/// Highest seq the sequencer has emitted to subscribers, if it has started
+pub fn last_emitted_seq() -> Option<i64> {
+    match LAST_EMITTED_SEQ.load(Ordering::Relaxed) {
+        -1 => None,
+        seq => Some(seq),
+    }
+}
+
 pub struct RequestSeqRangeOpts {
     pub earliest_seq: Option<i64>,
     pub latest_seq: Option<i64>,
@@ -32,23 +63,26 @@ pub struct Sequencer {
     pub tries_with_no_results: u32,
     pub waker: Option<Waker>,
     pub crawlers: Crawlers,
+    pub store: SharedSequencerStore,
     pub last_seen: Option<i64>,
 }
 
 impl Sequencer {
-    pub fn new(crawlers: Crawlers, last_seen: Option<i64>) -> Self {
+    pub fn new(crawlers: Crawlers, store: SharedSequencerStore, last_seen: Option<i64>) -> Self {
         Sequencer {
             destroyed: false,
             tries_with_no_results: 0,
             last_seen: Some(last_seen.unwrap_or(0)),
             waker: None,
             crawlers,
+            store,
         }
     }
 
     pub async fn start(&mut self) -> Result<()> {
         let curr = self.curr().await?;
         self.last_seen = Some(curr.unwrap_or(0));
+        LAST_EMITTED_SEQ.store(curr.unwrap_or(0), Ordering::Relaxed);
         if self.waker.is_none() {
             loop {
                 while let Some(_) = self.next().await {}
@@ -66,76 +100,19 @@ impl Sequencer {
     }
 
     pub async fn curr(&self) -> Result<Option<i64>> {
-        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-        let conn = &mut establish_connection_for_sequencer()?;
-
-        let got = RepoSeqSchema::repo_seq
-            .select(models::RepoSeq::as_select())
-            .order_by(RepoSeqSchema::seq.desc())
-            .first(conn)
-            .optional()?;
-        match got {
-            None => Ok(None),
-            Some(got) => Ok(got.seq),
-        }
+        self.store.curr().await
     }
 
     pub async fn next_seq(&self, cursor: i64) -> Result<Option<models::RepoSeq>> {
-        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-        let conn = &mut establish_connection_for_sequencer()?;
-
-        let got = RepoSeqSchema::repo_seq
-            .filter(RepoSeqSchema::seq.gt(cursor))
-            .select(models::RepoSeq::as_select())
-            .order_by(RepoSeqSchema::seq.asc())
-            .first(conn)
-            .optional()?;
-        Ok(got)
+        self.store.next_seq(cursor).await
     }
 
     pub async fn earliest_after_time(&self, time: String) -> Result<Option<models::RepoSeq>> {
-        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-        let conn = &mut establish_connection_for_sequencer()?;
-
-        let got = RepoSeqSchema::repo_seq
-            .filter(RepoSeqSchema::sequencedAt.ge(time))
-            .select(models::RepoSeq::as_select())
-            .order_by(RepoSeqSchema::sequencedAt.asc())
-            .first(conn)
-            .optional()?;
-        Ok(got)
+        self.store.earliest_after_time(&time).await
     }
 
     pub async fn request_seq_range(&self, opts: RequestSeqRangeOpts) -> Result<Vec<SeqEvt>> {
-        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-        let conn = &mut establish_connection_for_sequencer()?;
-
-        let RequestSeqRangeOpts {
-            earliest_seq,
-            latest_seq,
-            earliest_time,
-            limit,
-        } = opts;
-
-        let mut seq_qb = RepoSeqSchema::repo_seq
-            .select(models::RepoSeq::as_select())
-            .order_by(RepoSeqSchema::seq.asc())
-            .filter(RepoSeqSchema::invalidated.eq(0))
-            .into_boxed();
-        if let Some(earliest_seq) = earliest_seq {
-            seq_qb = seq_qb.filter(RepoSeqSchema::seq.gt(earliest_seq));
-        }
-        if let Some(latest_seq) = latest_seq {
-            seq_qb = seq_qb.filter(RepoSeqSchema::seq.le(latest_seq));
-        }
-        if let Some(earliest_time) = earliest_time {
-            seq_qb = seq_qb.filter(RepoSeqSchema::sequencedAt.ge(earliest_time));
-        }
-        if let Some(limit) = limit {
-            seq_qb = seq_qb.limit(limit);
-        }
-
-        let rows = seq_qb.get_results(conn)?;
+        let rows = self.store.range(&opts).await?;
         if rows.len() < 1 {
             return Ok(vec![]);
         }
@@ -178,6 +155,14 @@ impl Sequencer {
                             evt: cbor_to_struct(row.event)?,
                         }));
                     }
+                    "section" => {
+                        seq_evts.push(SeqEvt::TypedSectionEvt(TypedSectionEvt {
+                            r#type: "section".to_string(),
+                            seq,
+                            time,
+                            evt: cbor_to_struct(row.event)?,
+                        }));
+                    }
                     _ => {
                         eprintln!("ERROR: request_seq_range invalid event type");
                     }
@@ -201,19 +186,18 @@ impl Sequencer {
     }
 
     pub async fn sequence_evt(&mut self, evt: models::RepoSeq) -> Result<i64> {
-        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-        let conn = &mut establish_connection_for_sequencer()?;
-
-        let res = insert_into(RepoSeqSchema::repo_seq)
-            .values((
-                RepoSeqSchema::did.eq(evt.did),
-                RepoSeqSchema::event.eq(evt.event),
-                RepoSeqSchema::eventType.eq(evt.event_type),
-                RepoSeqSchema::sequencedAt.eq(evt.sequenced_at),
-            ))
-            .get_result::<models::RepoSeq>(conn)?;
+        let seq = self.store.append(vec![evt]).await?.into_iter().next();
         self.crawlers.notify_of_update().await?;
-        Ok(res.seq.expect("Sequence number wasn't updated on insert."))
+        Ok(seq.expect("Sequence number wasn't assigned on append."))
+    }
+
+    /// Sequences an event from an account that was idle or inactive until
+    /// now, asking the crawlers to crawl straight away so its content shows
+    /// up without waiting on the next periodic notification.
+    async fn sequence_wake_evt(&mut self, evt: models::RepoSeq) -> Result<i64> {
+        let seq = self.store.append(vec![evt]).await?.into_iter().next();
+        self.crawlers.notify_now();
+        Ok(seq.expect("Sequence number wasn't assigned on append."))
     }
 
     pub async fn sequence_commit(
@@ -221,8 +205,14 @@ impl Sequencer {
         did: String,
         commit_data: CommitDataWithOps,
     ) -> Result<i64> {
+        let woke = self
+            .crawlers
+            .is_idle(commit_data.commit_data.since.as_deref());
         let evt = format_seq_commit(did, commit_data).await?;
-        self.sequence_evt(evt).await
+        match woke {
+            true => self.sequence_wake_evt(evt).await,
+            false => self.sequence_evt(evt).await,
+        }
     }
 
     pub async fn sequence_handle_update(&mut self, did: String, handle: String) -> Result<i64> {
@@ -235,6 +225,12 @@ impl Sequencer {
         did: String,
         handle: Option<String>,
     ) -> Result<i64> {
+        ACCOUNT_EVENTS
+            .enqueue(&AccountEvent::Identity {
+                did: did.clone(),
+                handle: handle.clone(),
+            })
+            .await?;
         let evt = format_seq_identity_evt(did, handle).await?;
         self.sequence_evt(evt).await
     }
@@ -244,14 +240,103 @@ impl Sequencer {
         did: String,
         status: AccountStatus,
     ) -> Result<i64> {
+        // queued first, so handlers run even if we crash right after sequencing
+        ACCOUNT_EVENTS
+            .enqueue(&AccountEvent::Account {
+                did: did.clone(),
+                status: status.clone(),
+            })
+            .await?;
+        let woke = status == AccountStatus::Active;
         let evt = format_seq_account_evt(did, status).await?;
-        self.sequence_evt(evt).await
+        match woke {
+            true => self.sequence_wake_evt(evt).await,
+            false => self.sequence_evt(evt).await,
+        }
     }
 
     pub async fn sequence_sync_evt(&mut self, did: String, data: SyncEvtData) -> Result<i64> {
         let evt = format_seq_sync_evt(did, data).await?;
         self.sequence_evt(evt).await
     }
+
+    pub async fn sequence_section_evt(
+        &mut self,
+        did: String,
+        section_id: i64,
+        status: SectionStatus,
+    ) -> Result<i64> {
+        let evt = format_seq_section_evt(did, section_id, status).await?;
+        self.sequence_evt(evt).await
+    }
+
+    pub async fn delete_all_for_user(
+        &self,
+        did: &String,
+        excluding_seqs: Option<Vec<i64>>,
+    ) -> Result<()> {
+        self.store
+            .delete_all_for_user(did, &excluding_seqs.unwrap_or_default())
+            .await
+    }
+
+    /// Sequences fresh copies of commit events that were already emitted, so
+    /// downstream indexers can rebuild what they lost. Only accounts that are
+    /// active now are replayed, and creates and updates of records that have
+    /// since been deleted are left out, so a replay can't bring back what a
+    /// takedown, deactivation or delete removed.
+    pub async fn replay_commits(&mut self, opts: ReplayCommitsOpts) -> Result<ReplayedCommits> {
+        let rows = self.store.commits(&opts).await?;
+        let cursor = match rows.len() as i64 == opts.limit {
+            true => rows.last().and_then(|row| row.seq).map(|seq| seq + 1),
+            false => None,
+        };
+        let active = get_active_dids(rows.iter().map(|row| row.did.clone()).collect())?;
+
+        let sequenced_at = rsky_common::now();
+        let mut replayed = vec![];
+        let mut copies = vec![];
+        for row in rows {
+            if !active.contains(&row.did) {
+                continue;
+            }
+            let mut evt: CommitEvt = cbor_to_struct(row.event)?;
+            let existing = get_existing_paths(&row.did, &evt.ops)?;
+            evt.ops.retain(|op| match op.action {
+                CommitEvtOpAction::Delete => true,
+                _ => existing.contains(&op.path),
+            });
+            if evt.ops.is_empty() {
+                continue;
+            }
+            replayed.extend(row.seq);
+            copies.push(models::RepoSeq::new(
+                row.did,
+                row.event_type,
+                struct_to_cbor(&evt)?,
+                sequenced_at.clone(),
+            ));
+        }
+        if !copies.is_empty() {
+            self.store.append(copies).await?;
+            self.crawlers.notify_of_update().await?;
+        }
+        Ok(ReplayedCommits { replayed, cursor })
+    }
+}
+
+pub struct ReplayCommitsOpts {
+    pub did: Option<String>,
+    pub from_seq: Option<i64>,
+    pub to_seq: Option<i64>,
+    pub limit: i64,
+}
+
+pub struct ReplayedCommits {
+    /// Seqs of the original events that were replayed, in order
+    pub replayed: Vec<i64>,
+    /// Where to continue from when the limit was hit
+    pub cursor: Option<i64>,
 }
 
 impl Stream for Sequencer {
@@ -280,6 +365,7 @@ impl Stream for Sequencer {
                 Poll::Ready(Some(Err(err)))
             }
             Ok(evts) => {
+                LAST_POLLED_AT.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
                 if evts.len() > 0 {
                     self.tries_with_no_results = 0;
                     futures::executor::block_on(EVENT_EMITTER.write()).emit(
@@ -292,6 +378,10 @@ impl Stream for Sequencer {
                         None => self.last_seen,
                         Some(last_evt) => Some(last_evt.seq()),
                     };
+                    evts.iter().for_each(invalidate_cached_account);
+                    if let Some(last_evt) = evts.last() {
+                        record_emitted(last_evt);
+                    }
                     self.waker = Some(cx.waker().clone());
                     Poll::Ready(Some(Ok(())))
                 } else {
@@ -304,20 +394,106 @@ impl Stream for Sequencer {
     }
 }
 
-pub async fn delete_all_for_user(did: &String, excluding_seqs: Option<Vec<i64>>) -> Result<()> {
-    use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
-    let conn = &mut establish_connection_for_sequencer()?;
-    let excluding_seqs = excluding_seqs.unwrap_or_else(|| vec![]);
+/// Drops the cached account on identity and account events, which may come
+/// from another PDS instance sharing the database.
+fn invalidate_cached_account(evt: &SeqEvt) {
+    match evt {
+        SeqEvt::TypedIdentityEvt(evt) => account_cache::invalidate(&evt.evt.did),
+        SeqEvt::TypedAccountEvt(evt) => account_cache::invalidate(&evt.evt.did),
+        _ => (),
+    }
+}
 
-    let mut builder = delete(RepoSeqSchema::repo_seq)
-        .filter(RepoSeqSchema::did.eq(did))
-        .into_boxed();
-    if excluding_seqs.len() > 0 {
-        builder = builder.filter(RepoSeqSchema::seq.ne_all(excluding_seqs));
+fn record_emitted(evt: &SeqEvt) {
+    LAST_EMITTED_SEQ.store(evt.seq(), Ordering::Relaxed);
+    metrics::SEQUENCER_LAST_SEQ.set(evt.seq());
+    if let Ok(sequenced_at) = from_str_to_millis(evt.time()) {
+        let lag_ms = Utc::now().timestamp_millis() - sequenced_at;
+        metrics::SEQUENCER_LAG.set(lag_ms.max(0) as f64 / 1000.0);
     }
-    builder.execute(conn)?;
+}
+
+/// Which of `dids` are accounts that are neither taken down nor deactivated
+fn get_active_dids(dids: HashSet<String>) -> Result<HashSet<String>> {
+    use crate::schema::pds::actor::dsl as ActorSchema;
+    if dids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let conn = &mut establish_connection_for_sequencer()?;
+
+    Ok(ActorSchema::actor
+        .filter(ActorSchema::did.eq_any(dids))
+        .filter(ActorSchema::takedownRef.is_null())
+        .filter(ActorSchema::deactivatedAt.is_null())
+        .select(ActorSchema::did)
+        .load::<String>(conn)?
+        .into_iter()
+        .collect())
+}
+
+/// The paths `ops` touch that still hold a record
+fn get_existing_paths(did: &str, ops: &[CommitEvtOp]) -> Result<HashSet<String>> {
+    use crate::schema::pds::record::dsl as RecordSchema;
+    let conn = &mut establish_connection_for_sequencer()?;
+
+    let uris = ops
+        .iter()
+        .map(|op| format!("at://{did}/{}", op.path))
+        .collect::<Vec<String>>();
+    let prefix = format!("at://{did}/");
+    Ok(RecordSchema::record
+        .filter(RecordSchema::uri.eq_any(uris))
+        .select(RecordSchema::uri)
+        .load::<String>(conn)?
+        .into_iter()
+        .filter_map(|uri| Some(uri.strip_prefix(&prefix)?.to_string()))
+        .collect())
+}
+
+/// Returns the last seq delivered to a named firehose subscriber, if it has
+/// connected before.
+pub async fn get_subscriber_cursor(id: &String) -> Result<Option<i64>> {
+    use crate::schema::pds::subscriber_cursor::dsl as SubscriberCursorSchema;
+    let conn = &mut establish_connection_for_sequencer()?;
+
+    Ok(SubscriberCursorSchema::subscriber_cursor
+        .find(id)
+        .select(SubscriberCursorSchema::cursor)
+        .first::<i64>(conn)
+        .optional()?)
+}
+
+/// How many named subscribers have a cursor saved
+pub async fn count_subscriber_cursors() -> Result<i64> {
+    use crate::schema::pds::subscriber_cursor::dsl as SubscriberCursorSchema;
+    let conn = &mut establish_connection_for_sequencer()?;
+
+    Ok(SubscriberCursorSchema::subscriber_cursor
+        .count()
+        .get_result::<i64>(conn)?)
+}
+
+pub async fn save_subscriber_cursor(id: &String, cursor: i64) -> Result<()> {
+    use crate::schema::pds::subscriber_cursor::dsl as SubscriberCursorSchema;
+    let conn = &mut establish_connection_for_sequencer()?;
+
+    let updated_at = rsky_common::now();
+    insert_into(SubscriberCursorSchema::subscriber_cursor)
+        .values(models::SubscriberCursor {
+            id: id.clone(),
+            cursor,
+            updated_at: updated_at.clone(),
+        })
+        .on_conflict(SubscriberCursorSchema::id)
+        .do_update()
+        .set((
+            SubscriberCursorSchema::cursor.eq(cursor),
+            SubscriberCursorSchema::updatedAt.eq(updated_at),
+        ))
+        .execute(conn)?;
     Ok(())
 }
 
 pub mod events;
 pub mod outbox;
+pub mod store;
@@ -1,3 +1,4 @@
+use crate::config::SubscriptionBackpressure;
 use crate::sequencer::events::SeqEvt;
 use crate::sequencer::{RequestSeqRangeOpts, Sequencer};
 use crate::EVENT_EMITTER;
@@ -14,6 +15,22 @@ use tokio::time::{timeout, Duration};
 #[derive(Debug, Clone)]
 pub struct OutboxOpts {
     pub max_buffer_size: usize,
+    pub backpressure: SubscriptionBackpressure,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Stream consumer too slow")]
+pub struct ConsumerTooSlowError;
+
+#[derive(Debug, Clone)]
+pub enum OutboxEvt {
+    Evt(SeqEvt),
+    /// The subscriber fell too far behind and `count` buffered events after
+    /// `last_seen` were discarded
+    Dropped {
+        count: usize,
+        last_seen: i64,
+    },
 }
 
 pub struct Outbox {
@@ -23,14 +40,19 @@ pub struct Outbox {
     pub out_buffer: Arc<RwLock<AsyncBuffer<SeqEvt>>>,
     pub sequencer: Sequencer,
     pub backfill_cursor: Option<i64>,
+    pub backpressure: SubscriptionBackpressure,
 }
 
 const PAGE_SIZE: i64 = 500;
 
 impl Outbox {
     pub fn new(sequencer: Sequencer, opts: Option<OutboxOpts>) -> Self {
-        let OutboxOpts { max_buffer_size } = opts.unwrap_or(OutboxOpts {
+        let OutboxOpts {
+            max_buffer_size,
+            backpressure,
+        } = opts.unwrap_or(OutboxOpts {
             max_buffer_size: 500,
+            backpressure: SubscriptionBackpressure::Disconnect,
         });
         Self {
             sequencer,
@@ -39,19 +61,20 @@ impl Outbox {
             cutover_buffer: Arc::new(Mutex::new(vec![])),
             out_buffer: Arc::new(RwLock::new(AsyncBuffer::new(Some(max_buffer_size)))),
             backfill_cursor: None,
+            backpressure,
         }
     }
 
     pub async fn events<'a>(
         &'a mut self,
         backfill_cursor: Option<i64>,
-    ) -> impl Stream<Item = Result<SeqEvt>> + 'a {
+    ) -> impl Stream<Item = Result<OutboxEvt>> + 'a {
         try_stream! {
             if let Some(cursor) = backfill_cursor {
                 let backfill_stream = self.get_backfill(cursor).await;
                 pin_mut!(backfill_stream);
                 while let Some(Ok(evt)) = backfill_stream.next().await {
-                    yield evt;
+                    yield OutboxEvt::Evt(evt);
                 }
             } else {
                 let mut bool_lock = self.caught_up.lock().await;
@@ -114,17 +137,30 @@ impl Outbox {
                 *bool_lock = true;
             }
 
+            // the queue behind out_buffer, so it can be emptied while the write guard is held
+            let pending = Arc::clone(&self.out_buffer.read().await.buffer);
             loop {
                 while let Ok(Some(res)) = timeout(Duration::from_secs(2),self.out_buffer.write().await.next()).await {
-                    let evt = res.map_err(|error| {
-                        match error.downcast_ref() {
-                            Some(AsyncBufferFullError(_)) => anyhow!("Stream consumer too slow.".to_string()),
-                            _ => anyhow!(error.to_string())
+                    let evt = match res {
+                        Ok(evt) => evt,
+                        Err(error) if error.downcast_ref::<AsyncBufferFullError>().is_some() => {
+                            if self.backpressure == SubscriptionBackpressure::Disconnect {
+                                Err::<(), _>(ConsumerTooSlowError)?;
+                            }
+                            let count = {
+                                let mut pending = pending.lock().unwrap();
+                                let count = pending.len();
+                                pending.clear();
+                                count
+                            };
+                            yield OutboxEvt::Dropped { count, last_seen: self.last_seen };
+                            continue;
                         }
-                    })?;
+                        Err(error) => Err(anyhow!(error.to_string()))?,
+                    };
                     if evt.seq() > self.last_seen {
                         self.last_seen = evt.seq();
-                        yield evt;
+                        yield OutboxEvt::Evt(evt);
                     }
                 }
             }
@@ -0,0 +1,574 @@
+use crate::config::{env_to_cfg, SequencerConfig};
+use crate::db::establish_connection_for_sequencer;
+use crate::models;
+use crate::sequencer::{ReplayCommitsOpts, RequestSeqRangeOpts};
+use anyhow::{anyhow, bail, Result};
+use diesel::*;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Events copied per page by `migrate`
+const MIGRATE_PAGE_SIZE: usize = 1000;
+/// First key of the advisory lock appends to repo_seq take
+const SEQUENCER_LOCK_CLASS: i32 = 0x73657120;
+
+/// The event log behind the sequencer. Seqs are assigned by the store, start
+/// at 1 and only ever increase.
+#[rocket::async_trait]
+pub trait SequencerStore: Debug + Send + Sync {
+    /// The highest seq in the log
+    async fn curr(&self) -> Result<Option<i64>>;
+
+    /// The first event after `cursor`
+    async fn next_seq(&self, cursor: i64) -> Result<Option<models::RepoSeq>>;
+
+    /// The first event sequenced at or after `time`
+    async fn earliest_after_time(&self, time: &str) -> Result<Option<models::RepoSeq>>;
+
+    /// Events that haven't been invalidated within a range, in seq order
+    async fn range(&self, opts: &RequestSeqRangeOpts) -> Result<Vec<models::RepoSeq>>;
+
+    /// Commit events that haven't been invalidated, for `Sequencer::replay_commits`
+    async fn commits(&self, opts: &ReplayCommitsOpts) -> Result<Vec<models::RepoSeq>>;
+
+    /// Appends events in order, returning the seq each was given
+    async fn append(&self, evts: Vec<models::RepoSeq>) -> Result<Vec<i64>>;
+
+    async fn delete_all_for_user(&self, did: &str, excluding_seqs: &[i64]) -> Result<()>;
+
+    /// Every event after `after`, invalidated or not, in seq order
+    async fn export(&self, after: i64, limit: usize) -> Result<Vec<models::RepoSeq>>;
+
+    /// Writes events exported from another store, keeping their seqs
+    async fn import(&self, evts: Vec<models::RepoSeq>) -> Result<()>;
+}
+
+pub type SharedSequencerStore = Arc<dyn SequencerStore>;
+
+pub async fn open(cfg: &SequencerConfig) -> Result<SharedSequencerStore> {
+    Ok(match cfg {
+        SequencerConfig::Postgres => Arc::new(PostgresStore),
+        #[cfg(feature = "sequencer-redis")]
+        SequencerConfig::Redis { url, key, max_len } => {
+            Arc::new(RedisStore::new(url, key.clone(), *max_len).await?)
+        }
+        #[cfg(not(feature = "sequencer-redis"))]
+        SequencerConfig::Redis { .. } => bail!(
+            "PDS_SEQUENCER_REDIS_URL is set but rsky-pds was built without the sequencer-redis feature"
+        ),
+    })
+}
+
+/// The repo_seq table
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostgresStore;
+
+#[rocket::async_trait]
+impl SequencerStore for PostgresStore {
+    async fn curr(&self) -> Result<Option<i64>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let got = RepoSeqSchema::repo_seq
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::seq.desc())
+            .first(conn)
+            .optional()?;
+        Ok(got.and_then(|got| got.seq))
+    }
+
+    async fn next_seq(&self, cursor: i64) -> Result<Option<models::RepoSeq>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let got = RepoSeqSchema::repo_seq
+            .filter(RepoSeqSchema::seq.gt(cursor))
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::seq.asc())
+            .first(conn)
+            .optional()?;
+        Ok(got)
+    }
+
+    async fn earliest_after_time(&self, time: &str) -> Result<Option<models::RepoSeq>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let got = RepoSeqSchema::repo_seq
+            .filter(RepoSeqSchema::sequencedAt.ge(time))
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::sequencedAt.asc())
+            .first(conn)
+            .optional()?;
+        Ok(got)
+    }
+
+    async fn range(&self, opts: &RequestSeqRangeOpts) -> Result<Vec<models::RepoSeq>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let mut seq_qb = RepoSeqSchema::repo_seq
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::seq.asc())
+            .filter(RepoSeqSchema::invalidated.eq(0))
+            .into_boxed();
+        if let Some(earliest_seq) = opts.earliest_seq {
+            seq_qb = seq_qb.filter(RepoSeqSchema::seq.gt(earliest_seq));
+        }
+        if let Some(latest_seq) = opts.latest_seq {
+            seq_qb = seq_qb.filter(RepoSeqSchema::seq.le(latest_seq));
+        }
+        if let Some(ref earliest_time) = opts.earliest_time {
+            seq_qb = seq_qb.filter(RepoSeqSchema::sequencedAt.ge(earliest_time.clone()));
+        }
+        if let Some(limit) = opts.limit {
+            seq_qb = seq_qb.limit(limit);
+        }
+        Ok(seq_qb.get_results(conn)?)
+    }
+
+    async fn commits(&self, opts: &ReplayCommitsOpts) -> Result<Vec<models::RepoSeq>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let mut seq_qb = RepoSeqSchema::repo_seq
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::seq.asc())
+            .filter(RepoSeqSchema::invalidated.eq(0))
+            .filter(RepoSeqSchema::eventType.eq_any(vec!["append", "rebase"]))
+            .limit(opts.limit)
+            .into_boxed();
+        if let Some(ref did) = opts.did {
+            seq_qb = seq_qb.filter(RepoSeqSchema::did.eq(did.clone()));
+        }
+        if let Some(from_seq) = opts.from_seq {
+            seq_qb = seq_qb.filter(RepoSeqSchema::seq.ge(from_seq));
+        }
+        if let Some(to_seq) = opts.to_seq {
+            seq_qb = seq_qb.filter(RepoSeqSchema::seq.le(to_seq));
+        }
+        Ok(seq_qb.get_results(conn)?)
+    }
+
+    async fn append(&self, evts: Vec<models::RepoSeq>) -> Result<Vec<i64>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let values = evts
+            .into_iter()
+            .map(|evt| {
+                (
+                    RepoSeqSchema::did.eq(evt.did),
+                    RepoSeqSchema::event.eq(evt.event),
+                    RepoSeqSchema::eventType.eq(evt.event_type),
+                    RepoSeqSchema::sequencedAt.eq(evt.sequenced_at),
+                )
+            })
+            .collect::<Vec<_>>();
+        let seqs = conn.transaction(|conn| {
+            // Serial values are handed out in order but can commit out of
+            // order, and a poller that saw seq 11 commit before 10 would skip
+            // 10 for good. Holding this until commit makes every writer, on
+            // every replica, commit in the order its seqs were drawn.
+            sql_query("SELECT pg_advisory_xact_lock($1, 0)")
+                .bind::<sql_types::Integer, _>(SEQUENCER_LOCK_CLASS)
+                .execute(conn)?;
+            insert_into(RepoSeqSchema::repo_seq)
+                .values(values)
+                .returning(RepoSeqSchema::seq)
+                .get_results::<i64>(conn)
+        })?;
+        Ok(seqs)
+    }
+
+    async fn delete_all_for_user(&self, did: &str, excluding_seqs: &[i64]) -> Result<()> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let mut builder = delete(RepoSeqSchema::repo_seq)
+            .filter(RepoSeqSchema::did.eq(did))
+            .into_boxed();
+        if !excluding_seqs.is_empty() {
+            builder = builder.filter(RepoSeqSchema::seq.ne_all(excluding_seqs.to_vec()));
+        }
+        builder.execute(conn)?;
+        Ok(())
+    }
+
+    async fn export(&self, after: i64, limit: usize) -> Result<Vec<models::RepoSeq>> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        Ok(RepoSeqSchema::repo_seq
+            .filter(RepoSeqSchema::seq.gt(after))
+            .select(models::RepoSeq::as_select())
+            .order_by(RepoSeqSchema::seq.asc())
+            .limit(limit as i64)
+            .get_results(conn)?)
+    }
+
+    async fn import(&self, evts: Vec<models::RepoSeq>) -> Result<()> {
+        use crate::schema::pds::repo_seq::dsl as RepoSeqSchema;
+        let conn = &mut establish_connection_for_sequencer()?;
+
+        let values = evts
+            .into_iter()
+            .map(|evt| {
+                Ok((
+                    RepoSeqSchema::seq.eq(evt.seq.ok_or(anyhow!("Imported event has no seq"))?),
+                    RepoSeqSchema::did.eq(evt.did),
+                    RepoSeqSchema::event.eq(evt.event),
+                    RepoSeqSchema::eventType.eq(evt.event_type),
+                    RepoSeqSchema::invalidated.eq(evt.invalidated.unwrap_or(0)),
+                    RepoSeqSchema::sequencedAt.eq(evt.sequenced_at),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        conn.transaction(|conn| {
+            insert_into(RepoSeqSchema::repo_seq)
+                .values(values)
+                .execute(conn)?;
+            // seqs were given explicitly, so move the serial past them
+            sql_query(
+                "SELECT setval(pg_get_serial_sequence('pds.repo_seq', 'seq'), \
+                 (SELECT MAX(seq) FROM pds.repo_seq))",
+            )
+            .execute(conn)?;
+            Ok::<_, diesel::result::Error>(())
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sequencer-redis")]
+pub use redis_store::RedisStore;
+
+#[cfg(feature = "sequencer-redis")]
+mod redis_store {
+    use super::SequencerStore;
+    use crate::models;
+    use crate::sequencer::{ReplayCommitsOpts, RequestSeqRangeOpts};
+    use anyhow::{anyhow, Result};
+    use redis::streams::{StreamId, StreamRangeReply};
+    use redis::{from_redis_value, Script};
+    use std::fmt::{Debug, Formatter};
+
+    /// Stream entries read per XRANGE while scanning
+    const SCAN_PAGE_SIZE: usize = 500;
+
+    /// Assigns each event the next seq and adds it to the stream under that
+    /// seq, in one step so concurrent appends can't add ids out of order.
+    const APPEND_SCRIPT: &str = r#"
+local seqs = {}
+local max_len = tonumber(ARGV[1])
+for i = 2, #ARGV, 4 do
+    local seq = redis.call('INCR', KEYS[2])
+    local fields = {'did', ARGV[i], 'eventType', ARGV[i + 1], 'event', ARGV[i + 2], 'sequencedAt', ARGV[i + 3], 'invalidated', 0}
+    if max_len > 0 then
+        redis.call('XADD', KEYS[1], 'MAXLEN', '~', max_len, seq .. '-0', unpack(fields))
+    else
+        redis.call('XADD', KEYS[1], seq .. '-0', unpack(fields))
+    end
+    table.insert(seqs, seq)
+end
+return seqs
+"#;
+
+    /// A Redis stream whose entry ids are `{seq}-0`
+    pub struct RedisStore {
+        conn: redis::aio::ConnectionManager,
+        key: String,
+        seq_key: String,
+        max_len: Option<u64>,
+        append: Script,
+    }
+
+    impl Debug for RedisStore {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("RedisStore")
+                .field("key", &self.key)
+                .field("max_len", &self.max_len)
+                .finish()
+        }
+    }
+
+    impl RedisStore {
+        pub async fn new(url: &str, key: String, max_len: Option<u64>) -> Result<Self> {
+            let client = redis::Client::open(url)?;
+            Ok(RedisStore {
+                conn: redis::aio::ConnectionManager::new(client).await?,
+                seq_key: format!("{key}:seq"),
+                key,
+                max_len,
+                append: Script::new(APPEND_SCRIPT),
+            })
+        }
+
+        /// Pages through the stream from after `after` up to `until`, collecting
+        /// the events `keep` accepts until there are `limit` of them.
+        async fn scan<F>(
+            &self,
+            after: i64,
+            until: Option<i64>,
+            limit: Option<usize>,
+            keep: F,
+        ) -> Result<Vec<models::RepoSeq>>
+        where
+            F: Fn(&models::RepoSeq) -> bool + Send + Sync,
+        {
+            let mut conn = self.conn.clone();
+            let end = match until {
+                Some(until) => format!("{until}-0"),
+                None => "+".to_string(),
+            };
+            let mut start = after.max(-1) + 1;
+            let mut found = Vec::new();
+            loop {
+                let reply: StreamRangeReply = redis::cmd("XRANGE")
+                    .arg(&self.key)
+                    .arg(format!("{start}-0"))
+                    .arg(&end)
+                    .arg("COUNT")
+                    .arg(SCAN_PAGE_SIZE)
+                    .query_async(&mut conn)
+                    .await?;
+                let page_size = reply.ids.len();
+                for entry in reply.ids {
+                    let evt = to_repo_seq(&entry)?;
+                    start = evt.seq.unwrap_or(start) + 1;
+                    if keep(&evt) {
+                        found.push(evt);
+                        if limit == Some(found.len()) {
+                            return Ok(found);
+                        }
+                    }
+                }
+                if page_size < SCAN_PAGE_SIZE {
+                    return Ok(found);
+                }
+            }
+        }
+    }
+
+    fn to_repo_seq(entry: &StreamId) -> Result<models::RepoSeq> {
+        let seq = entry
+            .id
+            .split('-')
+            .next()
+            .and_then(|seq| seq.parse::<i64>().ok())
+            .ok_or_else(|| anyhow!("Invalid stream entry id `{}`", entry.id))?;
+        let field = |name: &str| {
+            entry
+                .map
+                .get(name)
+                .ok_or_else(|| anyhow!("Stream entry `{}` has no {name}", entry.id))
+        };
+        Ok(models::RepoSeq {
+            seq: Some(seq),
+            did: from_redis_value(field("did")?)?,
+            event_type: from_redis_value(field("eventType")?)?,
+            event: from_redis_value(field("event")?)?,
+            invalidated: Some(from_redis_value(field("invalidated")?)?),
+            sequenced_at: from_redis_value(field("sequencedAt")?)?,
+        })
+    }
+
+    fn is_valid(evt: &models::RepoSeq) -> bool {
+        evt.invalidated.unwrap_or(0) == 0
+    }
+
+    #[rocket::async_trait]
+    impl SequencerStore for RedisStore {
+        async fn curr(&self) -> Result<Option<i64>> {
+            let mut conn = self.conn.clone();
+            let reply: StreamRangeReply = redis::cmd("XREVRANGE")
+                .arg(&self.key)
+                .arg("+")
+                .arg("-")
+                .arg("COUNT")
+                .arg(1)
+                .query_async(&mut conn)
+                .await?;
+            match reply.ids.first() {
+                Some(entry) => Ok(to_repo_seq(entry)?.seq),
+                None => Ok(None),
+            }
+        }
+
+        async fn next_seq(&self, cursor: i64) -> Result<Option<models::RepoSeq>> {
+            let got = self.scan(cursor, None, Some(1), |_| true).await?;
+            Ok(got.into_iter().next())
+        }
+
+        async fn earliest_after_time(&self, time: &str) -> Result<Option<models::RepoSeq>> {
+            let got = self
+                .scan(-1, None, Some(1), |evt| evt.sequenced_at.as_str() >= time)
+                .await?;
+            Ok(got.into_iter().next())
+        }
+
+        async fn range(&self, opts: &RequestSeqRangeOpts) -> Result<Vec<models::RepoSeq>> {
+            let earliest_time = opts.earliest_time.clone();
+            self.scan(
+                opts.earliest_seq.unwrap_or(-1),
+                opts.latest_seq,
+                opts.limit.map(|limit| limit as usize),
+                |evt| {
+                    is_valid(evt)
+                        && earliest_time
+                            .as_ref()
+                            .map_or(true, |time| &evt.sequenced_at >= time)
+                },
+            )
+            .await
+        }
+
+        async fn commits(&self, opts: &ReplayCommitsOpts) -> Result<Vec<models::RepoSeq>> {
+            let did = opts.did.clone();
+            self.scan(
+                opts.from_seq.map_or(-1, |from_seq| from_seq - 1),
+                opts.to_seq,
+                Some(opts.limit as usize),
+                |evt| {
+                    is_valid(evt)
+                        && (evt.event_type == "append" || evt.event_type == "rebase")
+                        && did.as_ref().map_or(true, |did| &evt.did == did)
+                },
+            )
+            .await
+        }
+
+        async fn append(&self, evts: Vec<models::RepoSeq>) -> Result<Vec<i64>> {
+            let mut conn = self.conn.clone();
+            let mut invocation = self.append.prepare_invoke();
+            invocation
+                .key(&self.key)
+                .key(&self.seq_key)
+                .arg(self.max_len.unwrap_or(0));
+            for evt in evts {
+                invocation
+                    .arg(evt.did)
+                    .arg(evt.event_type)
+                    .arg(evt.event)
+                    .arg(evt.sequenced_at);
+            }
+            Ok(invocation.invoke_async(&mut conn).await?)
+        }
+
+        async fn delete_all_for_user(&self, did: &str, excluding_seqs: &[i64]) -> Result<()> {
+            let deleted = self
+                .scan(-1, None, None, |evt| {
+                    evt.did == did && !excluding_seqs.contains(&evt.seq.unwrap_or(-1))
+                })
+                .await?;
+            let mut conn = self.conn.clone();
+            for page in deleted.chunks(SCAN_PAGE_SIZE) {
+                let ids = page
+                    .iter()
+                    .filter_map(|evt| evt.seq)
+                    .map(|seq| format!("{seq}-0"))
+                    .collect::<Vec<_>>();
+                let _: i64 = redis::cmd("XDEL")
+                    .arg(&self.key)
+                    .arg(ids)
+                    .query_async(&mut conn)
+                    .await?;
+            }
+            Ok(())
+        }
+
+        async fn export(&self, after: i64, limit: usize) -> Result<Vec<models::RepoSeq>> {
+            self.scan(after, None, Some(limit), |_| true).await
+        }
+
+        async fn import(&self, evts: Vec<models::RepoSeq>) -> Result<()> {
+            let Some(last) = evts.last().and_then(|evt| evt.seq) else {
+                return Ok(());
+            };
+            let mut conn = self.conn.clone();
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for evt in evts {
+                let seq = evt.seq.ok_or(anyhow!("Imported event has no seq"))?;
+                pipe.cmd("XADD")
+                    .arg(&self.key)
+                    .arg(format!("{seq}-0"))
+                    .arg("did")
+                    .arg(evt.did)
+                    .arg("eventType")
+                    .arg(evt.event_type)
+                    .arg("event")
+                    .arg(evt.event)
+                    .arg("sequencedAt")
+                    .arg(evt.sequenced_at)
+                    .arg("invalidated")
+                    .arg(evt.invalidated.unwrap_or(0))
+                    .ignore();
+            }
+            // appends carry on from the last imported seq
+            pipe.cmd("SET").arg(&self.seq_key).arg(last).ignore();
+            let _: () = pipe.query_async(&mut conn).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Copies every event from one store to another, keeping seqs, and returns
+/// how many were copied. The target has to be empty.
+pub async fn migrate(from: &dyn SequencerStore, to: &dyn SequencerStore) -> Result<usize> {
+    if let Some(curr) = to.curr().await? {
+        bail!("Target sequencer store already has events up to seq {curr}");
+    }
+    let mut after = -1;
+    let mut copied = 0;
+    loop {
+        let evts = from.export(after, MIGRATE_PAGE_SIZE).await?;
+        let Some(last) = evts.last().and_then(|evt| evt.seq) else {
+            return Ok(copied);
+        };
+        copied += evts.len();
+        to.import(evts).await?;
+        after = last;
+    }
+}
+
+/// `rsky-pds migrate-sequencer <from> <to>`, where each side is `postgres` or
+/// `redis`. Redis is reached through the PDS_SEQUENCER_REDIS_* settings. Run
+/// it with the PDS stopped, then switch the PDS over to the target. Returns
+/// the process exit code.
+pub async fn run_migration(from: Option<&str>, to: Option<&str>) -> i32 {
+    let res = async {
+        let (Some(from), Some(to)) = (from, to) else {
+            bail!("expected `migrate-sequencer <from> <to>`, each postgres or redis");
+        };
+        let redis = env_to_cfg().sequencer;
+        let open_backend = |name: &str| {
+            let cfg = match name {
+                "postgres" => SequencerConfig::Postgres,
+                "redis" if redis != SequencerConfig::Postgres => redis.clone(),
+                "redis" => bail!("PDS_SEQUENCER_REDIS_URL is unset"),
+                name => bail!("unknown sequencer backend `{name}`, expected postgres or redis"),
+            };
+            Ok(cfg)
+        };
+        let (from_cfg, to_cfg) = (open_backend(from)?, open_backend(to)?);
+        if from_cfg == to_cfg {
+            bail!("source and target are the same store");
+        }
+        let copied = migrate(
+            open(&from_cfg).await?.as_ref(),
+            open(&to_cfg).await?.as_ref(),
+        )
+        .await?;
+        println!("Copied {copied} events from {from} to {to}");
+        Ok(())
+    }
+    .await;
+    match res {
+        Ok(()) => 0,
+        Err(error) => {
+            eprintln!("error: {error}");
+            1
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::apis::ApiError;
+use crate::SharedSequencer;
+use anyhow::Result;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Orbit, Request, Rocket, State};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::{sleep, timeout};
+
+/// Unrouted path requests that arrive while draining are sent to, so the
+/// catcher answers them. Kept outside /xrpc, where the proxy routes would pick
+/// it up.
+const DRAINING_PATH: &str = "/_shuttingDown";
+/// How often the sequencer is checked for events it hasn't emitted yet
+const SEQUENCER_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Tracks repo writes in flight so a deploy lets them commit and get
+/// sequenced before the process exits, instead of cutting a commit off
+/// halfway and leaving the repo head forked from what was sequenced.
+pub struct ShutdownCoordinator {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+    drain_timeout: Duration,
+}
+
+pub type SharedShutdown = Arc<ShutdownCoordinator>;
+
+impl ShutdownCoordinator {
+    pub fn new(drain_timeout_ms: u64) -> Self {
+        ShutdownCoordinator {
+            draining: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            drained: Notify::new(),
+            drain_timeout: Duration::from_millis(drain_timeout_ms),
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Registers a write, unless shutdown has begun. The write counts as in
+    /// flight until the returned guard is dropped.
+    pub fn begin_write(self: &Arc<Self>) -> Option<InFlightWrite> {
+        // Counted before checking, so a write can't slip in between shutdown
+        // beginning and the drain reading the count
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let write = InFlightWrite(self.clone());
+        match self.is_draining() {
+            true => None,
+            false => Some(write),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Stops new writes and waits for the ones in flight to finish.
+    pub async fn drain_writes(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+        loop {
+            let drained = self.drained.notified();
+            if self.in_flight() == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// A repo write in flight, see [`ShutdownCoordinator::begin_write`]. As a
+/// request guard it answers 503 once the PDS is shutting down.
+pub struct InFlightWrite(SharedShutdown);
+
+impl Drop for InFlightWrite {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
+
+fn shutting_down(req: &Request<'_>) -> ApiError {
+    let error = ApiError::ServiceUnavailable;
+    req.local_cache(|| Some(error.clone()));
+    error
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for InFlightWrite {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let coordinator = match req.guard::<&State<SharedShutdown>>().await {
+            Outcome::Success(coordinator) => coordinator,
+            _ => {
+                tracing::error!("@LOG: ERROR: shutdown coordinator isn't managed");
+                return Outcome::Error((Status::InternalServerError, ApiError::RuntimeError));
+            }
+        };
+        match coordinator.begin_write() {
+            Some(write) => Outcome::Success(write),
+            None => Outcome::Error((Status::ServiceUnavailable, shutting_down(req))),
+        }
+    }
+}
+
+/// Waits for the sequencer to emit every event committed so far, so
+/// subscribers aren't left behind the repos they follow.
+async fn flush_sequencer(sequencer: &SharedSequencer) -> Result<()> {
+    let Some(curr) = sequencer.sequencer.read().await.curr().await? else {
+        return Ok(());
+    };
+    loop {
+        match crate::sequencer::last_emitted_seq() {
+            Some(seq) if seq >= curr => return Ok(()),
+            _ => sleep(SEQUENCER_FLUSH_INTERVAL).await,
+        }
+    }
+}
+
+/// Answers xrpc requests with 503 once shutdown begins, then drains in-flight
+/// writes and the sequencer before Rocket finishes shutting down.
+pub struct ShutdownFairing(pub SharedShutdown);
+
+#[rocket::async_trait]
+impl Fairing for ShutdownFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Drain writes on shutdown",
+            kind: Kind::Request | Kind::Shutdown,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        if self.0.is_draining() && req.uri().path().starts_with("/xrpc/") {
+            shutting_down(req);
+            req.set_uri(Origin::parse(DRAINING_PATH).expect("valid draining path"));
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        let drain_timeout = self.0.drain_timeout;
+        tracing::info!(
+            "Shutting down, draining {} in-flight writes",
+            self.0.in_flight()
+        );
+        if timeout(drain_timeout, self.0.drain_writes()).await.is_err() {
+            tracing::warn!(
+                "Gave up on {} in-flight writes after {}ms",
+                self.0.in_flight(),
+                drain_timeout.as_millis()
+            );
+        }
+        let Some(sequencer) = rocket.state::<SharedSequencer>() else {
+            return;
+        };
+        match timeout(drain_timeout, flush_sequencer(sequencer)).await {
+            Ok(Ok(())) => tracing::info!("Sequencer flushed"),
+            Ok(Err(error)) => tracing::error!("@LOG: ERROR: failed to flush sequencer: {error}"),
+            Err(_) => tracing::warn!(
+                "Gave up flushing the sequencer after {}ms",
+                drain_timeout.as_millis()
+            ),
+        }
+    }
+}
+
+/// Tells a websocket subscriber the PDS is restarting, so it reconnects
+/// instead of treating the dropped connection as an error.
+pub fn close_for_shutdown() -> ws::Message {
+    ws::Message::Close(Some(ws::frame::CloseFrame {
+        code: ws::frame::CloseCode::Restart,
+        reason: "Server shutting down".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drains_in_flight_writes() {
+        let coordinator = Arc::new(ShutdownCoordinator::new(1000));
+        let write = coordinator.begin_write().expect("not draining yet");
+        let drain = tokio::spawn({
+            let coordinator = coordinator.clone();
+            async move { coordinator.drain_writes().await }
+        });
+        sleep(Duration::from_millis(10)).await;
+        assert!(coordinator.begin_write().is_none());
+        assert!(!drain.is_finished());
+        drop(write);
+        timeout(Duration::from_secs(1), drain)
+            .await
+            .expect("drained once the write finished")
+            .unwrap();
+        assert_eq!(coordinator.in_flight(), 0);
+    }
+}
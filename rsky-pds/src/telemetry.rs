@@ -0,0 +1,120 @@
+use anyhow::Result;
+use reqwest::header::HeaderMap;
+use rsky_common::env::env_str;
+use sha2::{Digest, Sha256};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+const OTLP_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Logs to stdout, and exports spans over OTLP as well when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set. Has to run inside the tokio runtime.
+pub fn init() -> Result<()> {
+    let registry = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(tracing_subscriber::fmt::layer());
+    match env_str(OTLP_ENDPOINT) {
+        #[cfg(feature = "otel")]
+        Some(endpoint) => registry.with(otel::layer(&endpoint)?).try_init()?,
+        #[cfg(not(feature = "otel"))]
+        Some(_) => anyhow::bail!(
+            "OTEL_EXPORTER_OTLP_ENDPOINT is set but rsky-pds was built without the otel feature"
+        ),
+        None => registry.try_init()?,
+    }
+    Ok(())
+}
+
+/// Flushes spans that haven't been exported yet.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Headers carrying the current span's trace context, for outgoing requests
+/// to show up under it in the trace.
+pub fn trace_headers() -> HeaderMap {
+    #[allow(unused_mut)]
+    let mut headers = HeaderMap::new();
+    #[cfg(feature = "otel")]
+    otel::inject(&mut headers);
+    headers
+}
+
+/// Short stable digest of a DID or CKB address, so spans can be correlated
+/// without identifying the account.
+pub fn hashed(value: &str) -> String {
+    hex::encode(&Sha256::digest(value.as_bytes())[..8])
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use anyhow::Result;
+    use opentelemetry::propagation::Injector;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+    use opentelemetry_sdk::{runtime, Resource};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+    use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    const SERVICE_NAME: &str = "rsky-pds";
+
+    pub fn layer<S>(endpoint: &str) -> Result<OpenTelemetryLayer<S, Tracer>>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                SERVICE_NAME,
+            )]))
+            .build();
+        let tracer = provider.tracer(SERVICE_NAME);
+        opentelemetry::global::set_tracer_provider(provider);
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+    }
+
+    pub fn inject(headers: &mut HeaderMap) {
+        let context = tracing::Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut HeaderInjector(headers))
+        });
+    }
+
+    struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+    impl Injector for HeaderInjector<'_> {
+        fn set(&mut self, key: &str, value: String) {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(key.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                self.0.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_identifiers_stably() {
+        let did = "did:web5:bafyreiexample";
+        assert_eq!(hashed(did), hashed(did));
+        assert_eq!(hashed(did).len(), 16);
+        assert_ne!(hashed(did), hashed("did:web5:bafyreiother"));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::config::WebhooksConfig;
+use crate::db::establish_connection_for_jobs;
+use crate::jetstream::{to_jetstream_evts, JetstreamFilter};
+use crate::models::Webhook;
+use crate::sequencer::{RequestSeqRangeOpts, Sequencer};
+use crate::APP_USER_AGENT;
+use anyhow::{bail, Result};
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::{insert_into, update};
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use rsky_common::RFC3339_VARIANT;
+use sha2::Sha256;
+use std::cmp;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "X-PDS-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-PDS-Webhook-Timestamp";
+pub const ID_HEADER: &str = "X-PDS-Webhook-Id";
+
+const MAX_BACKOFF_MS: i64 = 60 * 60 * 1000;
+/// How long one run spends delivering to a webhook before saving its cursor
+/// and leaving the rest for the next run, so a slow endpoint can't hold up
+/// the others
+const DELIVERY_BUDGET_MS: i64 = 60 * 1000;
+
+/// Signs a delivery as `hex(hmac_sha256(secret, "{timestamp}.{payload}"))`.
+/// Receivers recompute it from the timestamp header and the raw body, and
+/// should reject stale timestamps so a captured request can't be replayed.
+pub fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delivers repo events to the webhooks operators have registered.
+///
+/// Each webhook tails repo_seq from its own cursor and receives one POST per
+/// sequenced event that has matching ops, carrying the same JSON as the
+/// Jetstream endpoint. A webhook that fails is retried with exponential
+/// backoff and doesn't move on until the event is delivered or, after
+/// `max_attempts`, parked in the dead-letter table.
+///
+/// Due webhooks are claimed by pushing `nextAttemptAt` past the delivery
+/// budget, so each is delivered to by one replica at a time, and every
+/// claimed webhook is delivered to concurrently.
+pub struct WebhookDispatcher {
+    pub sequencer: Sequencer,
+    pub client: reqwest::Client,
+    pub interval_ms: u64,
+    pub max_attempts: i32,
+    pub batch_size: i64,
+    /// How long a claim lasts, the delivery budget plus one request timeout
+    pub lease_ms: i64,
+}
+
+impl WebhookDispatcher {
+    pub fn new(sequencer: Sequencer, cfg: &WebhooksConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .timeout(Duration::from_millis(cfg.timeout_ms))
+            .build()?;
+        Ok(WebhookDispatcher {
+            sequencer,
+            client,
+            interval_ms: cfg.interval_ms.max(100),
+            max_attempts: cfg.max_attempts.max(1),
+            batch_size: cfg.batch_size.max(1),
+            lease_ms: DELIVERY_BUDGET_MS + cfg.timeout_ms as i64 + 1000,
+        })
+    }
+
+    pub async fn start(&self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(self.interval_ms));
+        loop {
+            ticker.tick().await;
+            if let Err(error) = self.run().await {
+                tracing::error!("@LOG: ERROR: webhook delivery run failed: {error}");
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let due = claim(&mut establish_connection_for_jobs()?, self.lease_ms)?;
+        join_all(due.into_iter().map(|webhook| async move {
+            let id = webhook.id.clone();
+            if let Err(error) = self.deliver(webhook).await {
+                tracing::warn!("Webhook {id}: delivery failed: {error}");
+            }
+        }))
+        .await;
+        Ok(())
+    }
+
+    async fn deliver(&self, webhook: Webhook) -> Result<()> {
+        use crate::schema::pds::webhook::dsl as WebhookSchema;
+        use crate::schema::pds::webhook_dead_letter::dsl as DeadLetterSchema;
+
+        let conn = &mut establish_connection_for_jobs()?;
+        let deadline = Utc::now() + ChronoDuration::milliseconds(DELIVERY_BUDGET_MS);
+
+        let filter = JetstreamFilter::new(webhook.collections.clone(), webhook.dids.clone())?;
+        let evts = self
+            .sequencer
+            .request_seq_range(RequestSeqRangeOpts {
+                earliest_seq: Some(webhook.cursor),
+                latest_seq: None,
+                earliest_time: None,
+                limit: Some(self.batch_size),
+            })
+            .await?;
+
+        let mut cursor = webhook.cursor;
+        let mut attempts = webhook.attempts;
+        for evt in evts {
+            if Utc::now() >= deadline {
+                break;
+            }
+            let seq = evt.seq();
+            let matching = match to_jetstream_evts(evt, &filter).await {
+                Ok(matching) => matching,
+                Err(error) => {
+                    tracing::error!(
+                        "@LOG: ERROR: webhook {}: skipping event {seq} that can't be decoded: {error}",
+                        webhook.id
+                    );
+                    vec![]
+                }
+            };
+            if !matching.is_empty() {
+                let payload = serde_json::to_string(&matching)?;
+                if let Err(error) = self.post(&webhook, &payload).await {
+                    attempts += 1;
+                    let last_error = error.to_string();
+                    if attempts < self.max_attempts {
+                        update(WebhookSchema::webhook)
+                            .filter(WebhookSchema::id.eq(&webhook.id))
+                            .set((
+                                WebhookSchema::cursor.eq(cursor),
+                                WebhookSchema::attempts.eq(attempts),
+                                WebhookSchema::nextAttemptAt.eq(Some(next_attempt_at(attempts))),
+                                WebhookSchema::lastError.eq(Some(last_error)),
+                            ))
+                            .execute(conn)?;
+                        return Ok(());
+                    }
+                    tracing::warn!(
+                        "Webhook {}: giving up on event {seq} after {attempts} attempts: {last_error}",
+                        webhook.id
+                    );
+                    insert_into(DeadLetterSchema::webhook_dead_letter)
+                        .values((
+                            DeadLetterSchema::webhookId.eq(&webhook.id),
+                            DeadLetterSchema::seq.eq(seq),
+                            DeadLetterSchema::payload.eq(payload),
+                            DeadLetterSchema::attempts.eq(attempts),
+                            DeadLetterSchema::lastError.eq(last_error),
+                            DeadLetterSchema::failedAt.eq(rsky_common::now()),
+                        ))
+                        .execute(conn)?;
+                }
+            }
+            cursor = seq;
+            attempts = 0;
+        }
+
+        update(WebhookSchema::webhook)
+            .filter(WebhookSchema::id.eq(&webhook.id))
+            .set((
+                WebhookSchema::cursor.eq(cursor),
+                WebhookSchema::attempts.eq(0),
+                WebhookSchema::nextAttemptAt.eq(None::<String>),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    async fn post(&self, webhook: &Webhook, payload: &String) -> Result<()> {
+        let timestamp = Utc::now().timestamp();
+        let res = self
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ID_HEADER, &webhook.id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                format!(
+                    "sha256={}",
+                    sign_payload(&webhook.secret, timestamp, payload)
+                ),
+            )
+            .body(payload.clone())
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            bail!("Endpoint responded with {}", res.status());
+        }
+        Ok(())
+    }
+}
+
+/// Leases the due webhooks to this replica until `lease_ms` from now
+fn claim(conn: &mut PgConnection, lease_ms: i64) -> Result<Vec<Webhook>> {
+    use crate::schema::pds::webhook::dsl as WebhookSchema;
+
+    let now = rsky_common::now();
+    let lease_until = format!(
+        "{}",
+        (Utc::now() + ChronoDuration::milliseconds(lease_ms)).format(RFC3339_VARIANT)
+    );
+    Ok(conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        let ids = WebhookSchema::webhook
+            .filter(
+                WebhookSchema::nextAttemptAt
+                    .is_null()
+                    .or(WebhookSchema::nextAttemptAt.le(now)),
+            )
+            .select(WebhookSchema::id)
+            .for_update()
+            .skip_locked()
+            .load::<String>(conn)?;
+        update(WebhookSchema::webhook)
+            .filter(WebhookSchema::id.eq_any(ids))
+            .set(WebhookSchema::nextAttemptAt.eq(Some(lease_until)))
+            .returning(Webhook::as_returning())
+            .get_results(conn)
+    })?)
+}
+
+fn next_attempt_at(attempts: i32) -> String {
+    let backoff_ms = cmp::min(
+        1000i64.saturating_mul(2i64.saturating_pow(attempts as u32)),
+        MAX_BACKOFF_MS,
+    );
+    format!(
+        "{}",
+        (Utc::now() + ChronoDuration::milliseconds(backoff_ms)).format(RFC3339_VARIANT)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_payload() {
+        assert_eq!(
+            sign_payload("secret", 1700000000, "[]"),
+            "74f76d8933679a54d6be8c7560a5233b124658241ca5a3b0f09af80d3ea60d78"
+        );
+    }
+}
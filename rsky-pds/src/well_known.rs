@@ -1,10 +1,13 @@
 use crate::account_manager::AccountManager;
 use crate::config::ServerConfig;
+use crate::labeler;
 use anyhow::Result;
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome};
 use rocket::response::status;
+use rocket::serde::json::Json;
 use rocket::{Request, State};
+use serde_json::{json, Value};
 
 pub struct HostHeader(pub String);
 
@@ -58,3 +61,42 @@ pub async fn well_known(
         )),
     }
 }
+
+/// DID document for the service DID when it's `did:web:{hostname}`, carrying
+/// the `#atproto_label` key labels are signed with and the `#atproto_labeler`
+/// endpoint they're served from. With a did:plc service DID the same entries
+/// go in through a PLC operation instead.
+#[rocket::get("/.well-known/did.json")]
+pub async fn did_document(
+    cfg: &State<ServerConfig>,
+) -> Result<Json<Value>, status::Custom<String>> {
+    let did = &cfg.service.did;
+    if *did != format!("did:web:{}", cfg.service.hostname) {
+        return Err(status::Custom(Status::NotFound, "Not Found".to_string()));
+    }
+    let verification_methods = match labeler::public_signing_key() {
+        Ok(did_key) => vec![json!({
+            "id": format!("{did}#atproto_label"),
+            "type": "Multikey",
+            "controller": did,
+            "publicKeyMultibase": did_key.trim_start_matches("did:key:"),
+        })],
+        Err(error) => {
+            tracing::warn!("Serving the DID document without a label key: {error}");
+            vec![]
+        }
+    };
+    Ok(Json(json!({
+        "@context": [
+            "https://www.w3.org/ns/did/v1",
+            "https://w3id.org/security/multikey/v1",
+        ],
+        "id": did,
+        "verificationMethod": verification_methods,
+        "service": [{
+            "id": "#atproto_labeler",
+            "type": "AtprotoLabeler",
+            "serviceEndpoint": cfg.service.public_url,
+        }],
+    })))
+}
@@ -1,16 +1,18 @@
 use crate::account_manager::helpers::auth::{create_service_jwt, ServiceJwtParams};
 use anyhow::{anyhow, bail, Result};
 use atrium_api::xrpc::http::HeaderMap;
-use base64ct::{Base64, Encoding};
 use reqwest::header::{HeaderValue, AUTHORIZATION};
 use rsky_crypto::types::VerifyOptions;
 use rsky_crypto::verify::verify_signature;
+use sha2::{Digest, Sha256};
 use std::time::{Duration, SystemTime};
 
 pub struct ServiceJwtPayload {
     pub iss: String,
     pub aud: String,
     pub exp: Option<Duration>,
+    pub lxm: Option<String>,
+    pub collections: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -18,6 +20,10 @@ pub struct JwtPayload {
     pub iss: String,
     pub aud: String,
     pub exp: u64,
+    pub lxm: Option<String>,
+    pub jti: Option<String>,
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
 }
 
 pub async fn create_service_auth_headers(params: ServiceJwtParams) -> Result<HeaderMap> {
@@ -30,7 +36,7 @@ pub async fn create_service_auth_headers(params: ServiceJwtParams) -> Result<Hea
 
 pub fn parse_b64_url_to_json(b64: &str) -> Result<JwtPayload> {
     Ok(serde_json::from_slice::<JwtPayload>(
-        Base64::decode_vec(b64)
+        base64_url::decode(b64)
             .map_err(|err| anyhow!(err.to_string()))?
             .as_slice(),
     )?)
@@ -41,10 +47,41 @@ pub fn parse_payload(b64: &str) -> Result<JwtPayload> {
     Ok(payload)
 }
 
+/// Checks the claims that need no key: expiry, audience (unless `own_did` is
+/// None) and lexicon method (unless `lxm` is None). Cheap enough to run before
+/// anything is looked up for the unverified issuer.
+pub fn check_claims(payload: &JwtPayload, own_did: Option<&str>, lxm: Option<&str>) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("timestamp in seconds since UNIX epoch")
+        .as_secs();
+    if now > payload.exp {
+        bail!("JwtExpired: jwt expired")
+    }
+    if let Some(own_did) = own_did {
+        if payload.aud != own_did {
+            bail!("BadJwtAudience: jwt audience does not match service did")
+        }
+    }
+    if let Some(lxm) = lxm {
+        match payload.lxm {
+            Some(ref payload_lxm) if payload_lxm == lxm => (),
+            Some(ref payload_lxm) => bail!(
+                "BadJwtLexiconMethod: bad jwt lexicon method (\"lxm\"). must match: {lxm}, got: {payload_lxm}"
+            ),
+            None => bail!(
+                "BadJwtLexiconMethod: missing jwt lexicon method (\"lxm\"). must match: {lxm}"
+            ),
+        }
+    }
+    Ok(())
+}
+
 #[tracing::instrument(skip_all)]
 pub async fn verify_jwt<G>(
     jwt_str: String,
     own_did: Option<String>, // None indicates to skip the audience check
+    lxm: Option<String>,     // None indicates to skip the lxm check
     get_signing_key: G,
 ) -> Result<ServiceJwtPayload>
 where
@@ -56,24 +93,16 @@ where
             let parts_1 = *parts_1;
             let sig = *sig;
             let payload = parse_payload(parts_1)?;
-            let now = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("timestamp in micros since UNIX epoch")
-                .as_micros();
-            if now > payload.exp as u128 {
-                bail!("JwtExpired: jwt expired")
-            }
-            if own_did.is_some() && payload.aud != own_did.unwrap() {
-                bail!("BadJwtAudience: jwt audience does not match service did")
-            }
-            let msg_bytes = parts[0..2].join(".").into_bytes();
-            let sig_bytes = Base64::encode_string(sig.as_bytes())
-                .replace("=", "")
-                .into_bytes();
+            check_claims(&payload, own_did.as_deref(), lxm.as_deref())?;
+            let msg_hash = Sha256::digest(parts[0..2].join("."));
+            let sig_bytes = match base64_url::decode(sig) {
+                Ok(sig_bytes) => sig_bytes,
+                Err(_) => bail!("BadJwtSignature: poorly formatted jwt signature"),
+            };
             let verify_signature_with_key = |key: String| -> Result<bool> {
                 verify_signature(
                     &key,
-                    msg_bytes.as_slice(),
+                    msg_hash.as_slice(),
                     sig_bytes.as_slice(),
                     Some(VerifyOptions {
                         allow_malleable_sig: Some(true),
@@ -114,9 +143,100 @@ where
             Ok(ServiceJwtPayload {
                 iss: payload.iss,
                 aud: payload.aud,
-                exp: Some(Duration::from_micros(payload.exp)),
+                exp: Some(Duration::from_secs(payload.exp)),
+                lxm: payload.lxm,
+                collections: payload.collections,
             })
         }
         _ => bail!("BadJwt: poorly formatted jwt"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_crypto::utils::encode_did_key;
+    use secp256k1::{Keypair, Secp256k1, SecretKey};
+
+    async fn signed_jwt(secret_key: SecretKey, lxm: Option<String>) -> String {
+        create_service_jwt(ServiceJwtParams {
+            iss: "did:web5:alice".to_string(),
+            aud: "did:web:appview.example.com".to_string(),
+            exp: None,
+            lxm,
+            jti: None,
+            keypair: secret_key,
+            collections: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_round_trip() {
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret_key);
+        let did_key = encode_did_key(&keypair.public_key());
+        let jwt = signed_jwt(secret_key, Some("app.bbs.getThread".to_string())).await;
+
+        let payload = verify_jwt(
+            jwt.clone(),
+            Some("did:web:appview.example.com".to_string()),
+            Some("app.bbs.getThread".to_string()),
+            |_, _| Ok(did_key.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(payload.iss, "did:web5:alice");
+        assert_eq!(payload.lxm, Some("app.bbs.getThread".to_string()));
+
+        let wrong_lxm = verify_jwt(
+            jwt.clone(),
+            None,
+            Some("app.bbs.searchPosts".to_string()),
+            |_, _| Ok(did_key.clone()),
+        )
+        .await;
+        assert!(wrong_lxm.is_err());
+
+        let wrong_aud = verify_jwt(
+            jwt,
+            Some("did:web:other.example.com".to_string()),
+            None,
+            |_, _| Ok(did_key.clone()),
+        )
+        .await;
+        assert!(wrong_aud.is_err());
+    }
+
+    #[test]
+    fn check_claims_bounds() {
+        let payload = JwtPayload {
+            iss: "did:web5:alice".to_string(),
+            aud: "did:web:pds.example.com".to_string(),
+            exp: u64::MAX,
+            lxm: Some("app.bbs.getThread".to_string()),
+            jti: None,
+            collections: None,
+        };
+        let aud = Some("did:web:pds.example.com");
+        assert!(check_claims(&payload, aud, Some("app.bbs.getThread")).is_ok());
+        assert!(check_claims(&payload, None, None).is_ok());
+        assert!(check_claims(&payload, Some("did:web:other.example.com"), None).is_err());
+        assert!(check_claims(&payload, aud, Some("app.bbs.searchPosts")).is_err());
+
+        let expired = JwtPayload { exp: 1, ..payload };
+        assert!(check_claims(&expired, aud, Some("app.bbs.getThread")).is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_jwt_rejects_other_key() {
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let other = SecretKey::from_slice(&[8u8; 32]).unwrap();
+        let other_key =
+            encode_did_key(&Keypair::from_secret_key(&Secp256k1::new(), &other).public_key());
+        let jwt = signed_jwt(secret_key, None).await;
+        let result = verify_jwt(jwt, None, None, |_, _| Ok(other_key.clone())).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::apis::ApiError;
+use rocket::data::{FromData, Outcome, ToByteUnit};
+use rocket::http::Status;
+use rocket::{Data, Request};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::ops::Deref;
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+
+/// Largest directWrites, preDirectWrites and applyWrites batch
+pub const WRITES_MAX_BYTES: u64 = 2 * MIB;
+/// Largest createRecord or putRecord body
+pub const RECORD_MAX_BYTES: u64 = MIB;
+/// Largest indexAction or preIndexAction body, which carry no records
+pub const INDEX_ACTION_MAX_BYTES: u64 = 64 * KIB;
+/// Largest app.bbs.actor.putPreferences body
+pub const PREFERENCES_MAX_BYTES: u64 = 64 * KIB;
+/// Deepest nesting of arrays and objects a request body may have. Lexicon
+/// records don't come close, while a deeply nested value is cheap to send and
+/// costly to parse, validate and re-encode.
+pub const MAX_JSON_DEPTH: usize = 64;
+
+/// A JSON body read with a limit of `MAX_BYTES` instead of Rocket's global
+/// one, and refused before parsing when nested deeper than
+/// [`MAX_JSON_DEPTH`]. Both are answered with `PayloadTooLarge`.
+pub struct BoundedJson<T, const MAX_BYTES: u64>(pub T);
+
+impl<T, const MAX_BYTES: u64> BoundedJson<T, MAX_BYTES> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, const MAX_BYTES: u64> Deref for BoundedJson<T, MAX_BYTES> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug, const MAX_BYTES: u64> fmt::Debug for BoundedJson<T, MAX_BYTES> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+fn reject<'r, T>(
+    req: &'r Request<'_>,
+    status: Status,
+    error: ApiError,
+) -> Outcome<'r, T, ApiError> {
+    req.local_cache(|| Some(error.clone()));
+    Outcome::Error((status, error))
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned, const MAX_BYTES: u64> FromData<'r> for BoundedJson<T, MAX_BYTES> {
+    type Error = ApiError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> Outcome<'r, Self, Self::Error> {
+        let too_large =
+            || ApiError::PayloadTooLarge(format!("Request body is larger than {MAX_BYTES} bytes"));
+        if let Some(content_length) = req.headers().get_one("Content-Length") {
+            if content_length
+                .parse::<u64>()
+                .map_or(false, |len| len > MAX_BYTES)
+            {
+                return reject(req, Status::PayloadTooLarge, too_large());
+            }
+        }
+        let bytes = match data.open(MAX_BYTES.bytes()).into_bytes().await {
+            Ok(bytes) if bytes.is_complete() => bytes.into_inner(),
+            Ok(_) => return reject(req, Status::PayloadTooLarge, too_large()),
+            Err(error) => {
+                return reject(
+                    req,
+                    Status::BadRequest,
+                    ApiError::InvalidRequest(format!("Failed to read request body: {error}")),
+                )
+            }
+        };
+        if exceeds_depth(&bytes, MAX_JSON_DEPTH) {
+            return reject(
+                req,
+                Status::PayloadTooLarge,
+                ApiError::PayloadTooLarge(format!(
+                    "Request body is nested deeper than {MAX_JSON_DEPTH} levels"
+                )),
+            );
+        }
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => Outcome::Success(BoundedJson(value)),
+            Err(error) => reject(
+                req,
+                Status::BadRequest,
+                ApiError::InvalidRequest(format!("Invalid request body: {error}")),
+            ),
+        }
+    }
+}
+
+/// Whether arrays and objects in `json` nest deeper than `max_depth`, without
+/// parsing it. Brackets inside strings don't count.
+fn exceeds_depth(json: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in json {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_nesting_outside_strings() {
+        assert!(!exceeds_depth(br#"{"a":[{"b":[]}]}"#, 4));
+        assert!(exceeds_depth(br#"{"a":[{"b":[[]]}]}"#, 4));
+        assert!(!exceeds_depth(br#"{"a":"[[[[[[\"{{{{"}"#, 1));
+        assert!(exceeds_depth(&[b'['; 65], MAX_JSON_DEPTH));
+    }
+}
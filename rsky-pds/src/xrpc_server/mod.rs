@@ -1,3 +1,4 @@
 pub mod auth;
+pub mod body;
 pub mod stream;
 pub mod types;
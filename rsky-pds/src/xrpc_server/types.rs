@@ -47,6 +47,8 @@ pub enum InvalidRequestError {
     NoServiceId,
     #[error("No service configured for `{0}`")]
     NoServiceConfigured(String),
+    #[error("Bad token method: `{0}` can't be proxied")]
+    ProtectedMethod(String),
     #[error("AuthError: `{0}`")]
     AuthError(AuthError),
     #[error("XRPCError: {0}")]
@@ -0,0 +1,110 @@
+use lexicon_cid::Cid;
+use rsky_common::get_random_str;
+use rsky_common::ipld::sha256_to_cid;
+use rsky_pds::actor_store::blob::store::{BlobStore, SharedBlobStore};
+use rsky_pds::config::BlobstoreConfig;
+use sha2::{Digest, Sha256};
+
+const DID: &str = "did:web5:blobstoretest";
+
+fn cid_for(bytes: &[u8]) -> Cid {
+    let hash = Sha256::digest(bytes);
+    sha256_to_cid(hash.to_vec())
+}
+
+async fn exercise_blob_store(blob_store: Box<dyn BlobStore>) {
+    let bytes = b"hello blob store".to_vec();
+    let cid = cid_for(&bytes);
+
+    assert!(!blob_store.bucket_exists().await.unwrap());
+    assert!(blob_store.create_bucket().await.unwrap());
+    assert!(blob_store.bucket_exists().await.unwrap());
+
+    let key = blob_store.put_temp(bytes.clone()).await.unwrap();
+    assert!(blob_store.has_temp(key.clone()).await.unwrap());
+    assert!(!blob_store.has_stored(cid).await.unwrap());
+
+    blob_store.make_permanent(key.clone(), cid).await.unwrap();
+    assert!(!blob_store.has_temp(key).await.unwrap());
+    assert!(blob_store.has_stored(cid).await.unwrap());
+    assert_eq!(blob_store.get_bytes(cid).await.unwrap(), bytes);
+    let streamed = blob_store.get_stream(cid).await.unwrap().collect().await;
+    assert_eq!(streamed.unwrap().into_bytes().to_vec(), bytes);
+
+    blob_store.quarantine(cid).await.unwrap();
+    assert!(!blob_store.has_stored(cid).await.unwrap());
+    assert!(blob_store.get_bytes(cid).await.is_err());
+    blob_store.unquarantine(cid).await.unwrap();
+    assert!(blob_store.has_stored(cid).await.unwrap());
+
+    let thumbnail = b"thumbnail".to_vec();
+    blob_store
+        .put_derived(cid, "thumbnail".to_string(), thumbnail.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        blob_store
+            .get_derived_bytes(cid, "thumbnail".to_string())
+            .await
+            .unwrap(),
+        thumbnail
+    );
+    assert!(blob_store
+        .get_derived_bytes(cid, "fullsize".to_string())
+        .await
+        .is_err());
+
+    blob_store.delete(cid.to_string()).await.unwrap();
+    assert!(!blob_store.has_stored(cid).await.unwrap());
+    assert!(blob_store
+        .get_derived_bytes(cid, "thumbnail".to_string())
+        .await
+        .is_err());
+
+    blob_store.put_permanent(cid, bytes.clone()).await.unwrap();
+    blob_store.delete_many(vec![cid]).await.unwrap();
+    assert!(!blob_store.has_stored(cid).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_memory_blob_store() {
+    let shared = SharedBlobStore::new(&BlobstoreConfig::Memory);
+    exercise_blob_store(shared.for_did(DID.to_string())).await;
+}
+
+#[tokio::test]
+async fn test_memory_blob_store_is_shared_between_instances() {
+    let shared = SharedBlobStore::new(&BlobstoreConfig::Memory);
+    let bytes = b"shared".to_vec();
+    let cid = cid_for(&bytes);
+    shared
+        .for_did(DID.to_string())
+        .put_permanent(cid, bytes.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        shared
+            .for_did(DID.to_string())
+            .get_bytes(cid)
+            .await
+            .unwrap(),
+        bytes
+    );
+    assert!(!shared
+        .for_did("did:web5:someoneelse".to_string())
+        .has_stored(cid)
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn test_disk_blob_store() {
+    let location = std::env::temp_dir().join(format!("rsky-pds-blobs-{}", get_random_str()));
+    let shared = SharedBlobStore::new(&BlobstoreConfig::Disk {
+        location: location.to_string_lossy().to_string(),
+        tmp_location: None,
+        quarantine_location: None,
+    });
+    exercise_blob_store(shared.for_did(DID.to_string())).await;
+    std::fs::remove_dir_all(location).unwrap();
+}
@@ -0,0 +1,48 @@
+use rsky_mock_chain::{did_document, test_address, MockChain};
+use rsky_pds::apis::ApiError;
+use rsky_pds::plc::web5_types::get_didoc_from_chain;
+
+// The chain URLs are read once per process, so every case shares one chain
+#[tokio::test]
+async fn test_reads_did_docs_from_the_chain() {
+    let chain = MockChain::start().await.unwrap();
+    for (key, value) in chain.env() {
+        std::env::set_var(key, value);
+    }
+    let alice = test_address(1);
+    let bob = test_address(2);
+    chain
+        .put_did_doc(
+            &alice,
+            &did_document("did:key:zAlice", "alice.test", "https://pds.test"),
+        )
+        .unwrap();
+
+    let doc = get_didoc_from_chain(&alice).await.unwrap();
+    assert_eq!(doc.also_known_as, vec!["at://alice.test".to_string()]);
+    assert_eq!(doc.verification_methods["atproto"], "did:key:zAlice");
+    assert_eq!(doc.services["atproto_pds"].endpoint, "https://pds.test");
+
+    assert!(matches!(
+        get_didoc_from_chain(&bob).await,
+        Err(ApiError::CkbDidocCellNotFound)
+    ));
+
+    chain.put_cell_data(&bob, vec![1, 2, 3]).unwrap();
+    assert!(matches!(
+        get_didoc_from_chain(&bob).await,
+        Err(ApiError::InvalidCkbError(_))
+    ));
+
+    chain.consume_cell(&alice).unwrap();
+    assert!(matches!(
+        get_didoc_from_chain(&alice).await,
+        Err(ApiError::InvalidCkbError(_))
+    ));
+
+    chain.set_unavailable(true);
+    assert!(matches!(
+        get_didoc_from_chain(&bob).await,
+        Err(ApiError::InvalidCkbError(_))
+    ));
+}
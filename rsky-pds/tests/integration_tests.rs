@@ -2,6 +2,7 @@ use crate::common::{create_account, get_admin_token};
 use rocket::http::{ContentType, Header, Status};
 use rsky_lexicon::com::atproto::server::CreateInviteCodeOutput;
 use rsky_pds::config::ServerConfig;
+use rsky_pds::models::Readiness;
 use serde_json::json;
 
 mod common;
@@ -28,6 +29,27 @@ async fn test_robots_txt() {
     );
 }
 
+#[tokio::test]
+async fn test_ready_reports_each_dependency() {
+    let postgres = common::get_postgres().await;
+    let client = common::get_client(&postgres).await;
+    let response = client.get("/xrpc/_ready").dispatch().await;
+    let response_status = response.status();
+    let readiness = response.into_json::<Readiness>().await.unwrap();
+    assert_eq!(
+        readiness.checks.keys().collect::<Vec<_>>(),
+        vec!["blobstore", "ckbRpc", "postgres", "schema", "sequencer"]
+    );
+    assert!(readiness.checks["postgres"].ok);
+    assert!(readiness.checks["schema"].ok);
+    // CKB testnet may not be reachable from where the tests run
+    let expected_status = match readiness.ready {
+        true => Status::Ok,
+        false => Status::ServiceUnavailable,
+    };
+    assert_eq!(response_status, expected_status);
+}
+
 #[tokio::test]
 async fn test_create_invite_code() {
     let postgres = common::get_postgres().await;
@@ -0,0 +1,355 @@
+use crate::common::{create_invite_code, get_client_with_blobstore, get_database_url};
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::asynchronous::Client;
+use rsky_common::get_random_str;
+use rsky_crypto::utils::encode_did_key;
+use rsky_lexicon::com::atproto::web5::{
+    CreateAccountOutput, GetRecordHistoryOutput, IndexActionOutput, IndexActionOutputRefResult,
+    ListTombstonesOutput, PreCreateAccountOutput, PreDirectWritesOutput, PreIndexActionOutput,
+};
+use rsky_mock_chain::{did_document, test_address, MockChain};
+use rsky_pds::config::{BlobstoreConfig, ServerConfig};
+use rsky_pds::sequencer::events::{CommitEvtOpAction, SeqEvt};
+use rsky_pds::sequencer::RequestSeqRangeOpts;
+use rsky_pds::SharedSequencer;
+use rsky_web5_client::signer::sign_bytes;
+use rsky_web5_client::UnsignedRoot;
+use secp256k1::{Secp256k1, SecretKey};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+#[allow(dead_code)]
+mod common;
+
+const DID: &str = "did:web5:lifecycletest";
+
+async fn post<T: DeserializeOwned>(
+    client: &Client,
+    method: &str,
+    body: Value,
+    access_jwt: Option<&str>,
+) -> T {
+    let mut request = client
+        .post(format!("/xrpc/{method}"))
+        .header(ContentType::JSON)
+        .body(body.to_string());
+    if let Some(access_jwt) = access_jwt {
+        request = request.header(Header::new("Authorization", format!("Bearer {access_jwt}")));
+    }
+    let response = request.dispatch().await;
+    let status = response.status();
+    let body = response.into_string().await.unwrap_or_default();
+    assert_eq!(status, Status::Ok, "{method} failed: {body}");
+    serde_json::from_str(&body).unwrap()
+}
+
+/// The events the sequencer has for `did`, oldest first
+async fn events_for(client: &Client, did: &str) -> Vec<SeqEvt> {
+    let sequencer = client.rocket().state::<SharedSequencer>().unwrap();
+    let lock = sequencer.sequencer.read().await;
+    lock.request_seq_range(RequestSeqRangeOpts {
+        earliest_seq: None,
+        latest_seq: None,
+        earliest_time: None,
+        limit: None,
+    })
+    .await
+    .unwrap()
+    .into_iter()
+    .filter(|evt| match evt {
+        SeqEvt::TypedCommitEvt(commit) => commit.evt.repo == did,
+        SeqEvt::TypedIdentityEvt(identity) => identity.evt.did == did,
+        SeqEvt::TypedAccountEvt(account) => account.evt.did == did,
+        SeqEvt::TypedSyncEvt(sync) => sync.evt.did == did,
+        SeqEvt::TypedSectionEvt(section) => section.evt.did == did,
+    })
+    .collect()
+}
+
+async fn latest_rev(client: &Client, did: &str) -> Option<String> {
+    let response = client
+        .get(format!("/xrpc/com.atproto.sync.getLatestCommit?did={did}"))
+        .dispatch()
+        .await;
+    if response.status() != Status::Ok {
+        return None;
+    }
+    let commit: Value = response.into_json().await.unwrap();
+    commit["rev"].as_str().map(str::to_string)
+}
+
+/// Runs a web5 account from signup to deletion against a real database and
+/// blobstore, with the mock chain standing in for CKB.
+#[tokio::test]
+async fn test_web5_account_lifecycle() {
+    let postgres = common::get_postgres().await;
+    let chain = MockChain::start().await.unwrap();
+    // The sequencer and chain lookups read these from the environment
+    std::env::set_var("DATABASE_URL", get_database_url(&postgres).await);
+    if std::env::var("PDS_HOSTNAME").is_err() {
+        std::env::set_var("PDS_HOSTNAME", "localhost");
+    }
+    for (key, value) in chain.env() {
+        std::env::set_var(key, value);
+    }
+    let location = std::env::temp_dir().join(format!("rsky-pds-lifecycle-{}", get_random_str()));
+    let client = get_client_with_blobstore(
+        &postgres,
+        BlobstoreConfig::Disk {
+            location: location.to_string_lossy().to_string(),
+            tmp_location: None,
+            quarantine_location: None,
+        },
+    )
+    .await;
+    let cfg = client.rocket().state::<ServerConfig>().unwrap();
+    let handle = format!(
+        "lifecycle{}",
+        cfg.identity.service_handle_domains.first().unwrap()
+    );
+    let public_url = cfg.service.public_url.clone();
+
+    let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+    let signing_key = encode_did_key(&secret_key.public_key(&Secp256k1::new()));
+    let ckb_addr = test_address(7);
+    let invite_code = create_invite_code(&client).await;
+
+    // preCreateAccount -> createAccount
+    let pre_create: PreCreateAccountOutput = post(
+        &client,
+        "com.atproto.web5.preCreateAccount",
+        json!({
+            "handle": handle,
+            "did": DID,
+            "signingKey": signing_key,
+            "inviteCode": invite_code,
+        }),
+        None,
+    )
+    .await;
+    assert_eq!(pre_create.did, DID);
+    let root = UnsignedRoot::from(pre_create)
+        .sign_with_key(&secret_key)
+        .unwrap();
+    let created: CreateAccountOutput = post(
+        &client,
+        "com.atproto.web5.createAccount",
+        json!({
+            "handle": handle,
+            "signingKey": signing_key,
+            "root": root,
+            "ckbAddr": ckb_addr,
+            "inviteCode": invite_code,
+        }),
+        None,
+    )
+    .await;
+    assert_eq!(created.did, DID);
+    assert_eq!(created.handle, handle);
+    assert_eq!(latest_rev(&client, DID).await, Some(root.rev.clone()));
+    let events = events_for(&client, DID).await;
+    assert!(matches!(
+        &events[..],
+        [
+            SeqEvt::TypedIdentityEvt(_),
+            SeqEvt::TypedAccountEvt(account),
+            SeqEvt::TypedCommitEvt(commit),
+            SeqEvt::TypedSyncEvt(_),
+        ] if account.evt.active && commit.evt.rev == root.rev
+    ));
+
+    // The wallet publishes the DID doc once the account exists
+    chain
+        .put_did_doc(&ckb_addr, &did_document(&signing_key, &handle, &public_url))
+        .unwrap();
+
+    // preDirectWrites -> directWrites
+    let record = json!({
+        "$type": "app.bsky.feed.post",
+        "text": "hello from web5",
+        "createdAt": "2026-01-01T00:00:00.000Z",
+    });
+    let pre_writes: PreDirectWritesOutput = post(
+        &client,
+        "com.atproto.web5.preDirectWrites",
+        json!({
+            "repo": DID,
+            "writes": [{
+                "$type": "com.atproto.web5.preDirectWrites#create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "lifecycle",
+                "value": record,
+            }],
+        }),
+        Some(&created.access_jwt),
+    )
+    .await;
+    let root = UnsignedRoot::from(pre_writes)
+        .sign_with_key(&secret_key)
+        .unwrap();
+    let _: Value = post(
+        &client,
+        "com.atproto.web5.directWrites",
+        json!({
+            "repo": DID,
+            "writes": [{
+                "$type": "com.atproto.web5.directWrites#create",
+                "collection": "app.bsky.feed.post",
+                "rkey": "lifecycle",
+                "value": record,
+            }],
+            "signingKey": signing_key,
+            "ckbAddr": ckb_addr,
+            "root": root,
+        }),
+        Some(&created.access_jwt),
+    )
+    .await;
+    assert_eq!(latest_rev(&client, DID).await, Some(root.rev.clone()));
+    let response = client
+        .get(format!(
+            "/xrpc/com.atproto.repo.getRecord?repo={DID}&collection=app.bsky.feed.post&rkey=lifecycle"
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let stored: Value = response.into_json().await.unwrap();
+    assert_eq!(stored["value"]["text"], "hello from web5");
+    let events = events_for(&client, DID).await;
+    let Some(SeqEvt::TypedCommitEvt(commit)) = events
+        .iter()
+        .rev()
+        .find(|evt| matches!(evt, SeqEvt::TypedCommitEvt(_)))
+    else {
+        panic!("no commit event for directWrites");
+    };
+    assert_eq!(commit.evt.rev, root.rev);
+    assert_eq!(commit.evt.ops.len(), 1);
+    assert_eq!(commit.evt.ops[0].action, CommitEvtOpAction::Create);
+    assert_eq!(commit.evt.ops[0].path, "app.bsky.feed.post/lifecycle");
+    let response = client
+        .get(format!(
+            "/xrpc/com.atproto.web5.getRecordHistory?repo={DID}&collection=app.bsky.feed.post&rkey=lifecycle"
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let history: GetRecordHistoryOutput = response.into_json().await.unwrap();
+    assert_eq!(history.versions.len(), 1);
+    assert_eq!(history.versions[0].action, "create");
+    assert_eq!(history.versions[0].rev, root.rev);
+    assert_eq!(
+        history.versions[0].value.as_ref().unwrap()["text"],
+        "hello from web5"
+    );
+    assert_eq!(history.cursor, None);
+
+    // Deleting the record leaves a tombstone its owner can read back
+    let pre_writes: PreDirectWritesOutput = post(
+        &client,
+        "com.atproto.web5.preDirectWrites",
+        json!({
+            "repo": DID,
+            "writes": [{
+                "$type": "com.atproto.web5.preDirectWrites#delete",
+                "collection": "app.bsky.feed.post",
+                "rkey": "lifecycle",
+            }],
+        }),
+        Some(&created.access_jwt),
+    )
+    .await;
+    let delete_root = UnsignedRoot::from(pre_writes)
+        .sign_with_key(&secret_key)
+        .unwrap();
+    let _: Value = post(
+        &client,
+        "com.atproto.web5.directWrites",
+        json!({
+            "repo": DID,
+            "writes": [{
+                "$type": "com.atproto.web5.directWrites#delete",
+                "collection": "app.bsky.feed.post",
+                "rkey": "lifecycle",
+            }],
+            "signingKey": signing_key,
+            "ckbAddr": ckb_addr,
+            "root": delete_root,
+        }),
+        Some(&created.access_jwt),
+    )
+    .await;
+    let response = client
+        .get(format!(
+            "/xrpc/com.atproto.repo.getRecord?repo={DID}&collection=app.bsky.feed.post&rkey=lifecycle"
+        ))
+        .dispatch()
+        .await;
+    assert_ne!(response.status(), Status::Ok);
+    let response = client
+        .get("/xrpc/com.atproto.web5.listTombstones")
+        .header(Header::new(
+            "Authorization",
+            format!("Bearer {}", created.access_jwt),
+        ))
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::Ok);
+    let tombstones: ListTombstonesOutput = response.into_json().await.unwrap();
+    assert_eq!(tombstones.tombstones.len(), 1);
+    assert_eq!(
+        tombstones.tombstones[0].uri,
+        format!("at://{DID}/app.bsky.feed.post/lifecycle")
+    );
+    assert_eq!(tombstones.tombstones[0].value["text"], "hello from web5");
+
+    // preIndexAction -> indexAction(deleteAccount)
+    let challenge: PreIndexActionOutput = post(
+        &client,
+        "com.atproto.web5.preIndexAction",
+        json!({
+            "did": DID,
+            "ckbAddr": ckb_addr,
+            "index": { "$type": "com.atproto.web5.preIndexAction#deleteAccount" },
+        }),
+        None,
+    )
+    .await;
+    assert_eq!(challenge.handle, handle);
+    let signed_bytes = hex::encode(sign_bytes(&secret_key, challenge.message.as_bytes()));
+    let deleted: IndexActionOutput = post(
+        &client,
+        "com.atproto.web5.indexAction",
+        json!({
+            "did": DID,
+            "message": challenge.message,
+            "signingKey": signing_key,
+            "signedBytes": signed_bytes,
+            "ckbAddr": ckb_addr,
+            "index": { "$type": "com.atproto.web5.indexAction#deleteAccount" },
+        }),
+        None,
+    )
+    .await;
+    assert!(matches!(
+        deleted.result,
+        IndexActionOutputRefResult::DeleteAccountResult(_)
+    ));
+
+    // Only the deletion itself is left in the sequencer, and the repo is gone
+    let events = events_for(&client, DID).await;
+    assert!(matches!(
+        &events[..],
+        [SeqEvt::TypedAccountEvt(account)] if !account.evt.active
+    ));
+    assert_eq!(latest_rev(&client, DID).await, None);
+    let response = client
+        .get(format!(
+            "/xrpc/com.atproto.repo.getRecord?repo={DID}&collection=app.bsky.feed.post&rkey=lifecycle"
+        ))
+        .dispatch()
+        .await;
+    assert_ne!(response.status(), Status::Ok);
+
+    let _ = std::fs::remove_dir_all(location);
+}
@@ -0,0 +1,23 @@
+use anyhow::{Context, Result};
+use std::fs;
+
+use crate::util::{env, http_client};
+
+/// Execute the export-repo command
+pub fn execute(did: &str, out: Option<&str>) -> Result<()> {
+    env::load_env().context("Failed to load environment variables")?;
+
+    // Admin auth, so repos that are taken down or deactivated export too
+    let car = http_client::admin_get_bytes(&format!("com.atproto.sync.getRepo?did={}", did))
+        .context(format!("Failed to export repo for {}", did))?;
+
+    let path = match out {
+        Some(path) => path.to_string(),
+        None => format!("{}.car", did.replace(':', "_")),
+    };
+    fs::write(&path, &car).context(format!("Failed to write {}", path))?;
+
+    println!("Exported {} ({} bytes) to {}", did, car.len(), path);
+
+    Ok(())
+}
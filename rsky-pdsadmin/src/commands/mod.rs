@@ -1,8 +1,12 @@
 pub mod account;
 pub mod create_invite_code;
+pub mod export_repo;
+pub mod replay_sequencer;
 pub mod request_crawl;
+pub mod rotate_jwt_secret;
 pub mod rsky_pds;
 pub mod update;
+pub mod verify_chain_consistency;
 
 use anyhow::{Context, Result, anyhow};
 use clap::{Parser, Subcommand};
@@ -30,9 +34,66 @@ pub enum Commands {
     },
 
     /// Create an invite code
-    #[command(name = "create-invite-code")]
+    #[command(name = "create-invite-code", visible_alias = "create-invite")]
     CreateInviteCode,
 
+    /// List accounts, same as `account list`
+    #[command(name = "list-accounts")]
+    ListAccounts,
+
+    /// Takedown an account, same as `account takedown`
+    Takedown {
+        /// DID of the account to takedown
+        did: String,
+    },
+
+    /// Replace the key the PDS signs session tokens with
+    #[command(name = "rotate-jwt-secret")]
+    RotateJwtSecret {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Download a repo as a CAR file
+    #[command(name = "export-repo")]
+    ExportRepo {
+        /// DID of the repo to export
+        did: String,
+
+        /// File to write, <DID>.car if unset
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+
+    /// Check web5 accounts against their DID doc cells on chain
+    #[command(name = "verify-chain-consistency")]
+    VerifyChainConsistency {
+        /// Only check this account
+        #[arg(long)]
+        did: Option<String>,
+    },
+
+    /// Re-emit sequenced commit events onto the firehose
+    #[command(name = "replay-sequencer")]
+    ReplaySequencer {
+        /// Only replay events for this repo
+        #[arg(long)]
+        did: Option<String>,
+
+        /// First seq to replay, inclusive
+        #[arg(long)]
+        from_seq: Option<i64>,
+
+        /// Last seq to replay, inclusive
+        #[arg(long)]
+        to_seq: Option<i64>,
+
+        /// Events replayed per request
+        #[arg(long, default_value_t = 1000)]
+        limit: i64,
+    },
+
     /// Request a crawl from a relay
     #[command(name = "request-crawl")]
     RequestCrawl {
@@ -141,6 +202,30 @@ pub fn execute() -> Result<()> {
         Commands::CreateInviteCode => {
             create_invite_code::execute().context("Failed to create invite code")
         }
+        Commands::ListAccounts => {
+            account::execute(&account::AccountCommands::List).context("Failed to list accounts")
+        }
+        Commands::Takedown { did } => {
+            account::execute(&account::AccountCommands::Takedown { did: did.clone() })
+                .context("Failed to takedown account")
+        }
+        Commands::RotateJwtSecret { yes } => {
+            rotate_jwt_secret::execute(*yes).context("Failed to rotate JWT secret")
+        }
+        Commands::ExportRepo { did, out } => {
+            export_repo::execute(did, out.as_deref()).context("Failed to export repo")
+        }
+        Commands::VerifyChainConsistency { did } => {
+            verify_chain_consistency::execute(did.as_deref())
+                .context("Failed to verify chain consistency")
+        }
+        Commands::ReplaySequencer {
+            did,
+            from_seq,
+            to_seq,
+            limit,
+        } => replay_sequencer::execute(did.as_deref(), *from_seq, *to_seq, *limit)
+            .context("Failed to replay sequencer events"),
         Commands::RequestCrawl { relay_hosts } => {
             request_crawl::execute(relay_hosts).context("Failed to request crawl")
         }
@@ -212,6 +297,31 @@ fn print_help() {
     println!("  Create a new invite code.");
     println!("    e.g. pdsadmin create-invite-code");
     println!();
+    println!("list-accounts");
+    println!("  List accounts, same as 'account list'.");
+    println!("    e.g. pdsadmin list-accounts");
+    println!();
+    println!("takedown <DID>");
+    println!("  Takedown an account, same as 'account takedown'.");
+    println!("    e.g. pdsadmin takedown did:web5:xyz123abc456");
+    println!();
+    println!("rotate-jwt-secret [--yes]");
+    println!("  Write a new JWT signing key to pds.env. Every account is signed out");
+    println!("  once the PDS is restarted.");
+    println!("    e.g. pdsadmin rotate-jwt-secret");
+    println!();
+    println!("export-repo <DID> [--out <FILE>]");
+    println!("  Download a repo as a CAR file.");
+    println!("    e.g. pdsadmin export-repo did:web5:xyz123abc456 --out backup.car");
+    println!();
+    println!("verify-chain-consistency [--did <DID>]");
+    println!("  Check web5 accounts against their DID doc cells on chain.");
+    println!("    e.g. pdsadmin verify-chain-consistency");
+    println!();
+    println!("replay-sequencer [--did <DID>] [--from-seq <SEQ>] [--to-seq <SEQ>]");
+    println!("  Re-emit sequenced commit events onto the firehose.");
+    println!("    e.g. pdsadmin replay-sequencer --from-seq 1000");
+    println!();
     println!("rsky-pds");
     println!("  init-db");
     println!("    Initialize the database with the required schema.");
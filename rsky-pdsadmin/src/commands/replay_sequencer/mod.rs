@@ -0,0 +1,48 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+
+use crate::util::{env, http_client};
+
+/// Execute the replay-sequencer command
+pub fn execute(
+    did: Option<&str>,
+    from_seq: Option<i64>,
+    to_seq: Option<i64>,
+    limit: i64,
+) -> Result<()> {
+    env::load_env().context("Failed to load environment variables")?;
+
+    if did.is_none() && from_seq.is_none() {
+        return Err(anyhow!(
+            "Pass --did and/or --from-seq to choose what to replay"
+        ));
+    }
+
+    let mut from_seq = from_seq;
+    let mut total = 0;
+    loop {
+        let response: Value = http_client::admin_post(
+            "com.atproto.admin.replayEvents",
+            json!({
+                "did": did,
+                "fromSeq": from_seq,
+                "toSeq": to_seq,
+                "limit": limit,
+            }),
+        )
+        .context("Failed to replay events")?;
+
+        total += response["replayed"].as_u64().unwrap_or(0);
+        println!("Replayed {} events", total);
+
+        // The server hands back a cursor while there's more to replay
+        match response["cursor"].as_i64() {
+            Some(cursor) => from_seq = Some(cursor),
+            None => break,
+        }
+    }
+
+    println!("done");
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use dialoguer::Confirm;
+use rand::RngCore;
+
+use crate::util::env;
+
+/// The variable the PDS signs access and refresh tokens with
+pub const JWT_KEY_VAR: &str = "PDS_JWT_KEY_K256_PRIVATE_KEY_HEX";
+
+/// A random secp256k1 private key, hex encoded
+pub fn generate_key_hex() -> String {
+    let mut key = [0u8; 32];
+    // Anything below 0xff.. is under the curve order, and zero isn't a key
+    while key[0] == 0xff || key.iter().all(|byte| *byte == 0) {
+        rand::thread_rng().fill_bytes(&mut key);
+    }
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Execute the rotate-jwt-secret command
+pub fn execute(yes: bool) -> Result<()> {
+    let env_path = env::load_env().context("Failed to load environment variables")?;
+
+    if !yes
+        && !Confirm::new()
+            .with_prompt("Rotating the JWT key signs every account out. Continue?")
+            .default(false)
+            .interact()?
+    {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    env::set_env_file_var(&env_path, JWT_KEY_VAR, &generate_key_hex())
+        .context(format!("Failed to update {}", env_path))?;
+
+    println!("Wrote a new {} to {}", JWT_KEY_VAR, env_path);
+    println!("Restart the PDS for it to take effect");
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+
+use crate::util::{env, http_client};
+
+/// Execute the verify-chain-consistency command
+pub fn execute(did: Option<&str>) -> Result<()> {
+    env::load_env().context("Failed to load environment variables")?;
+
+    println!("Checking web5 accounts against their DID doc cells...");
+
+    let mut cursor: Option<String> = None;
+    let mut checked = 0;
+    let mut inconsistent = 0;
+    loop {
+        let mut endpoint = "com.atproto.admin.checkChainConsistency?limit=100".to_string();
+        if let Some(did) = did {
+            endpoint.push_str(&format!("&did={}", did));
+        }
+        if let Some(ref cursor) = cursor {
+            endpoint.push_str(&format!("&cursor={}", cursor));
+        }
+        let response: Value =
+            http_client::admin_get(&endpoint).context("Failed to check chain consistency")?;
+
+        checked += response["checked"].as_u64().unwrap_or(0);
+        for account in response["inconsistencies"].as_array().into_iter().flatten() {
+            inconsistent += 1;
+            let problems: Vec<&str> = account["problems"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|problem| problem.as_str())
+                .collect();
+            println!(
+                "{}\t{}\t{}\t{}",
+                account["did"].as_str().unwrap_or(""),
+                account["handle"].as_str().unwrap_or("-"),
+                account["ckbAddress"].as_str().unwrap_or(""),
+                problems.join(",")
+            );
+        }
+
+        match response["cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    println!("Checked {} accounts", checked);
+    if inconsistent > 0 {
+        return Err(anyhow!("{} accounts disagree with the chain", inconsistent));
+    }
+
+    Ok(())
+}
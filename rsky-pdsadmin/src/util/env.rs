@@ -48,6 +48,30 @@ fn load_from_path(path: &str) -> Result<()> {
     Ok(())
 }
 
+/// Set a variable in an environment file, replacing its current value or
+/// appending it if the file doesn't have one
+pub fn set_env_file_var(path: &str, name: &str, value: &str) -> Result<()> {
+    let contents = std::fs::read_to_string(path).context(format!("Failed to read {}", path))?;
+    let prefix = format!("{}=", name);
+    let mut found = false;
+    let mut lines: Vec<String> = contents
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with(&prefix) {
+                found = true;
+                format!("{}{}", prefix, value)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("{}{}", prefix, value));
+    }
+    std::fs::write(path, lines.join("\n") + "\n").context(format!("Failed to write {}", path))?;
+    Ok(())
+}
+
 /// Get a required environment variable
 pub fn get_env_var(name: &str) -> Result<String> {
     env::var(name).context(format!("Environment variable {} is not set", name))
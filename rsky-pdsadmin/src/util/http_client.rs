@@ -107,6 +107,38 @@ pub fn admin_get<T: DeserializeOwned>(endpoint: &str) -> Result<T> {
     ))
 }
 
+/// Make a GET request to the PDS with admin authentication, for endpoints that
+/// answer with binary data such as CAR files
+pub fn admin_get_bytes(endpoint: &str) -> Result<Vec<u8>> {
+    let client = create_admin_client()?;
+    let url = build_pds_url(endpoint)?;
+
+    log_verbose(&format!("Making GET request to {}", url));
+
+    let response = client
+        .get(&url)
+        .send()
+        .context(format!("Failed to send request to {}", url))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let err_text = response
+            .text()
+            .unwrap_or_else(|_| "Could not read error response".to_string());
+
+        return Err(anyhow::anyhow!(
+            "Server returned error status {}: {}",
+            status,
+            err_text
+        ));
+    }
+
+    Ok(response
+        .bytes()
+        .context(format!("Failed to read response from {}", endpoint))?
+        .to_vec())
+}
+
 /// Make a POST request to the PDS with admin authentication
 pub fn admin_post<T: DeserializeOwned, D: Serialize>(endpoint: &str, data: D) -> Result<T> {
     let client = create_admin_client()?;
@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod env_file_tests {
+    use rsky_pdsadmin::commands::rotate_jwt_secret::{JWT_KEY_VAR, generate_key_hex};
+    use rsky_pdsadmin::util::env::set_env_file_var;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_env_file_var() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("pds.env");
+        let path = path.to_str().unwrap();
+        fs::write(
+            path,
+            "PDS_HOSTNAME=example.com\nPDS_JWT_KEY_K256_PRIVATE_KEY_HEX=old\n",
+        )
+        .unwrap();
+
+        // Replaces an existing value and leaves the rest alone
+        set_env_file_var(path, JWT_KEY_VAR, "new").unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "PDS_HOSTNAME=example.com\nPDS_JWT_KEY_K256_PRIVATE_KEY_HEX=new\n"
+        );
+
+        // Appends a variable the file doesn't have yet
+        set_env_file_var(path, "PDS_CRAWLERS", "https://bsky.network").unwrap();
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "PDS_HOSTNAME=example.com\nPDS_JWT_KEY_K256_PRIVATE_KEY_HEX=new\nPDS_CRAWLERS=https://bsky.network\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_key_hex() {
+        let key = generate_key_hex();
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        assert!(!key.starts_with("ff"));
+        assert_ne!(key, generate_key_hex());
+    }
+}
@@ -0,0 +1,192 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use lexicon_cid::Cid;
+use rsky_common::tid::Ticker;
+use rsky_crypto::utils::encode_did_key;
+use rsky_lexicon::com::atproto::web5::SignedRoot;
+use rsky_repo::block_map::BlockMap;
+use rsky_repo::repo::Repo;
+use rsky_repo::storage::memory_blockstore::MemoryBlockstore;
+use rsky_repo::storage::types::RepoStorage;
+use rsky_repo::types::{
+    RecordCreateOrUpdateOp, RecordWriteEnum, RecordWriteOp, RepoRecord, WriteOpAction,
+};
+use rsky_repo::util::{format_data_key, record_to_block, sign_commit};
+use secp256k1::{Keypair, Secp256k1, SecretKey};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::RwLock;
+
+const DID: &str = "did:web5:bench";
+const COLLECTION: &str = "app.bbs.post";
+/// One write, a typical batch, and the most directWrites accepts
+const BATCH_SIZES: [usize; 3] = [1, 50, 200];
+/// Records already in the repo, so the MST has a realistic depth
+const EXISTING_RECORDS: usize = 2_000;
+
+fn record(i: usize) -> RepoRecord {
+    serde_json::from_value(json!({
+        "$type": COLLECTION,
+        "section_id": "1",
+        "title": format!("Post {i}"),
+        "text": "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20),
+        "langs": ["en"],
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    }))
+    .expect("valid record")
+}
+
+fn creates(size: usize) -> Vec<RecordWriteOp> {
+    let mut ticker = Ticker::new();
+    (0..size)
+        .map(|i| {
+            RecordWriteOp::Create(RecordCreateOrUpdateOp {
+                action: WriteOpAction::Create,
+                collection: COLLECTION.to_string(),
+                rkey: ticker.next(None).to_string(),
+                record: record(i),
+            })
+        })
+        .collect()
+}
+
+/// A repo's blocks and head, copied into a fresh store for every iteration so
+/// each commit lands on the same repo rather than one that keeps growing
+struct Snapshot {
+    blocks: BlockMap,
+    cid: Cid,
+}
+
+impl Snapshot {
+    async fn new(keypair: Keypair) -> Snapshot {
+        let store = MemoryBlockstore::new(None).await.unwrap();
+        let blocks = store.blocks.clone();
+        let storage: Arc<RwLock<dyn RepoStorage>> = Arc::new(RwLock::new(store));
+        let mut repo = Repo::create(storage, DID.to_string(), keypair, None)
+            .await
+            .unwrap();
+        let repo = repo
+            .apply_writes(RecordWriteEnum::List(creates(EXISTING_RECORDS)), keypair)
+            .await
+            .unwrap();
+        let blocks = blocks.read().await.clone();
+        Snapshot {
+            blocks,
+            cid: repo.cid,
+        }
+    }
+
+    async fn load(&self) -> Repo {
+        let store = MemoryBlockstore::new(Some(self.blocks.clone()))
+            .await
+            .unwrap();
+        let storage: Arc<RwLock<dyn RepoStorage>> = Arc::new(RwLock::new(store));
+        Repo::load(storage, Some(self.cid)).await.unwrap()
+    }
+}
+
+/// What preDirectWrites and directWrites do between them for one commit: build
+/// the unsigned commit, have the wallet sign it, rebuild and check it against
+/// the signature, then store it.
+fn direct_writes(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let keypair = Keypair::from_secret_key(
+        &Secp256k1::new(),
+        &SecretKey::from_slice(&[1u8; 32]).unwrap(),
+    );
+    let signing_key = encode_did_key(&keypair.public_key());
+    let snapshot = runtime.block_on(Snapshot::new(keypair));
+
+    let mut group = c.benchmark_group("directWrites commit");
+    // reported as commits/s
+    group.throughput(Throughput::Elements(1));
+    for size in BATCH_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || (runtime.block_on(snapshot.load()), creates(size)),
+                |(mut repo, writes)| {
+                    runtime.block_on(async {
+                        let unsigned = repo
+                            .generate_commit(RecordWriteEnum::List(writes.clone()))
+                            .await
+                            .unwrap();
+                        let commit = sign_commit(unsigned, keypair).unwrap();
+                        let root = SignedRoot {
+                            did: commit.did,
+                            rev: commit.rev,
+                            data: commit.data.to_string(),
+                            prev: None,
+                            version: commit.version,
+                            signed_bytes: hex::encode(commit.sig),
+                        };
+                        let commit = repo
+                            .verify_commit(RecordWriteEnum::List(writes), signing_key.clone(), root)
+                            .await
+                            .unwrap();
+                        repo.apply_commit(commit).await.unwrap()
+                    })
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// The MST side of a commit on its own: adding the batch's keys and hashing
+/// the new root.
+fn mst_update(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let keypair = Keypair::from_secret_key(
+        &Secp256k1::new(),
+        &SecretKey::from_slice(&[1u8; 32]).unwrap(),
+    );
+    let snapshot = runtime.block_on(Snapshot::new(keypair));
+    let record_cid = record_to_block(&record(0), &mut Vec::new()).unwrap();
+
+    let mut group = c.benchmark_group("directWrites mst update");
+    group.throughput(Throughput::Elements(1));
+    for size in BATCH_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || (runtime.block_on(snapshot.load()), creates(size)),
+                |(repo, writes)| {
+                    runtime.block_on(async {
+                        let mut data = repo.data;
+                        for write in writes {
+                            let key = format_data_key(write.collection(), write.rkey());
+                            data = data.add(&key, record_cid, None).await.unwrap();
+                        }
+                        data.get_pointer().await.unwrap()
+                    })
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// The CBOR side of a commit on its own: encoding and hashing each record.
+fn record_encoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("directWrites record encoding");
+    group.throughput(Throughput::Elements(1));
+    for size in BATCH_SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || (0..size).map(record).collect::<Vec<_>>(),
+                |records| {
+                    let mut buf = Vec::new();
+                    for record in &records {
+                        record_to_block(record, &mut buf).unwrap();
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, direct_writes, mst_update, record_encoding);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rsky_repo::types::{Lex, RepoRecord};
+use rsky_repo::util::{cbor_to_lex, lex_to_ipld, record_to_block};
+use serde_json::json;
+
+/// The largest batch directWrites accepts
+const BATCH_SIZE: usize = 200;
+
+fn batch() -> Vec<RepoRecord> {
+    (0..BATCH_SIZE)
+        .map(|i| {
+            serde_json::from_value(json!({
+                "$type": "app.bbs.post",
+                "section_id": "1",
+                "title": format!("Post {i}"),
+                "text": "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(20),
+                "langs": ["en"],
+                "createdAt": "2025-01-01T00:00:00.000Z",
+            }))
+            .expect("valid record")
+        })
+        .collect()
+}
+
+fn encode_records(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode 200 records");
+    // what preparing a write used to do: encode, decode to check the
+    // encoding, then encode again to hash it
+    group.bench_function("encode, decode and re-encode", |b| {
+        b.iter_batched(
+            batch,
+            |records| {
+                for record in records {
+                    let ipld = lex_to_ipld(Lex::Map(record));
+                    let block = serde_ipld_dagcbor::to_vec(&ipld).unwrap();
+                    cbor_to_lex(block).unwrap();
+                    rsky_common::ipld::cid_for_cbor(&ipld).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("record_to_block", |b| {
+        b.iter_batched(
+            batch,
+            |records| {
+                let mut buf = Vec::new();
+                for record in &records {
+                    record_to_block(record, &mut buf).unwrap();
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode_records);
+criterion_main!(benches);
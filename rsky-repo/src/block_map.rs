@@ -27,11 +27,9 @@ impl BlockMap {
     }
 
     pub fn add<T: Serialize>(&mut self, value: T) -> Result<Cid> {
-        let cid = ipld::cid_for_cbor(&value)?;
-        self.set(
-            cid,
-            rsky_common::struct_to_cbor(&value)?, //bytes
-        );
+        let bytes = rsky_common::struct_to_cbor(&value)?;
+        let cid = ipld::cid_for_cbor_bytes(&bytes)?;
+        self.set(cid, bytes);
         Ok(cid)
     }
 
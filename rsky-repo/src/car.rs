@@ -6,7 +6,7 @@ use futures::{pin_mut, Stream, StreamExt};
 use iroh_car::{CarHeader, CarReader, CarWriter};
 use lexicon_cid::Cid;
 use std::future::Future;
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::{
     io::{AsyncReadExt, DuplexStream},
     sync::oneshot,
@@ -111,6 +111,24 @@ where
     write_car_stream(root, user_fn)
 }
 
+/// Writes a CAR of `blocks` to `writer` as the stream yields them, so
+/// exports never hold more than one block in memory. Returns the writer once
+/// the CAR is complete.
+pub async fn write_car_to<W, S>(writer: W, root: Option<&Cid>, blocks: S) -> Result<W>
+where
+    W: AsyncWrite + Send + Unpin,
+    S: Stream<Item = Result<(Cid, Vec<u8>)>>,
+{
+    let roots = root.map_or_else(Vec::new, |r| vec![r.clone()]);
+    let mut car_writer = CarWriter::new(CarHeader::new_v1(roots), writer);
+    pin_mut!(blocks);
+    while let Some(block) = blocks.next().await {
+        let (cid, bytes) = block?;
+        car_writer.write(cid, bytes).await?;
+    }
+    Ok(car_writer.finish().await?)
+}
+
 /// Converts a BlockMap to a CAR stream
 pub fn blocks_to_car_stream(
     root: Option<&Cid>,
@@ -169,7 +187,10 @@ pub async fn read_stream_car<R: AsyncRead + Send + Unpin>(bytes: R) -> Result<Ca
 #[cfg(test)]
 mod tests {
     use crate::block_map::{BlockMap, Bytes};
-    use crate::car::{read_car_with_root, read_stream_car_with_root, CarWithRoot};
+    use crate::car::{
+        blocks_to_car_file, read_car_with_root, read_stream_car_with_root, write_car_to,
+        CarWithRoot,
+    };
     use lexicon_cid::multihash::Multihash;
     use lexicon_cid::{Cid, Version};
     use std::collections::BTreeMap;
@@ -426,4 +447,28 @@ mod tests {
         assert_eq!(car_with_root.root, expected.root);
         assert_eq!(car_with_root.blocks, expected.blocks);
     }
+
+    #[tokio::test]
+    async fn test_write_car_to_matches_buffered_car() {
+        let (root, blocks) = fetch_valid_repo();
+        let buffered = blocks_to_car_file(Some(&root), blocks.clone())
+            .await
+            .expect("Failed to write car");
+        let block_stream = futures::stream::iter(
+            blocks
+                .clone()
+                .into_iter()
+                .map(|entry| Ok((entry.cid, entry.bytes))),
+        );
+        let streamed = write_car_to(Vec::new(), Some(&root), block_stream)
+            .await
+            .expect("Failed to stream car");
+        assert_eq!(streamed, buffered);
+
+        let car_with_root = read_car_with_root(streamed)
+            .await
+            .expect("Failed to read car");
+        assert_eq!(car_with_root.root, root);
+        assert_eq!(car_with_root.blocks, blocks);
+    }
 }
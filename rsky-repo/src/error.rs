@@ -23,6 +23,28 @@ pub enum RepoError {
     InvalidRecordError,
 }
 
+/// A signed root that doesn't describe the commit the server derived for the
+/// same writes, or whose signature isn't over that commit's bytes.
+#[derive(Error, Debug)]
+pub enum SignedRootError {
+    #[error("root did invalid: expected `{expected}`, got `{got}`")]
+    Did { expected: String, got: String },
+    #[error("root version invalid: expected 3, got {0}")]
+    Version(u8),
+    #[error("root prev invalid: expected none, got `{0}`")]
+    Prev(String),
+    #[error("root data invalid: expected `{expected}`, got `{got}`")]
+    Data { expected: String, got: String },
+    #[error("root rev invalid: `{0}` is not a canonical TID")]
+    Rev(String),
+    #[error("root rev `{rev}` is not after the repo's current rev `{current}`")]
+    StaleRev { rev: String, current: String },
+    #[error("root signed bytes invalid: {0}")]
+    SignedBytes(String),
+    #[error("root signature does not cover the canonical unsigned commit")]
+    Signature,
+}
+
 #[derive(Error, Debug)]
 pub enum BlobError {
     #[error("Blob not found")]
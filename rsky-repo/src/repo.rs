@@ -1,7 +1,7 @@
 use crate::block_map::BlockMap;
 use crate::cid_set::CidSet;
 use crate::data_diff::DataDiff;
-use crate::error::DataStoreError;
+use crate::error::{DataStoreError, SignedRootError};
 use crate::mst::MST;
 use crate::storage::types::RepoStorage;
 use crate::types::{
@@ -17,6 +17,7 @@ use rsky_common::tid::{Ticker, TID};
 use rsky_lexicon::com::atproto::web5::SignedRoot;
 use secp256k1::Keypair;
 use serde_cbor::Value as CborValue;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -234,49 +235,13 @@ impl Repo {
         let diff = DataDiff::of(&mut data, None).await?;
         new_blocks.add_map(diff.new_mst_blocks)?;
 
-        // verify commit content validity
-        if did != root.did {
-            bail!("root did invalid");
-        }
-        if root.version != 3 {
-            bail!("root version invalid");
-        }
-        if root.prev != None {
-            bail!("root prev invalid");
-        }
-        if data_cid.to_string() != root.data {
-            bail!("root data invalid");
-        }
-
-        let sig = if root.signed_bytes.starts_with("0x") || root.signed_bytes.starts_with("0X") {
-            &root.signed_bytes[2..]
-        } else {
-            &root.signed_bytes
-        };
-
-        let rev = TID::new(root.rev)?;
-        let commit = Commit {
-            did: root.did,
-            rev: rev.0.clone(),
-            version: root.version,
-            prev: None,
-            data: data_cid,
-            sig: hex::decode(sig).map_err(|error| {
-                let context = format!("root signed bytes invalid");
-                anyhow::Error::new(error).context(context)
-            })?,
-        };
-
-        // todo signing key check through did doc
-
-        if !util::verify_commit_sig(commit.clone(), &signing_key)? {
-            bail!("root sign data verified failed");
-        }
+        let commit = Self::verify_signed_root(root, &did, data_cid, &signing_key)?;
+        let rev = commit.rev.clone();
 
         let commit_cid = new_blocks.add(commit)?;
         Ok(CommitData {
             cid: commit_cid,
-            rev: rev.0,
+            rev,
             since: None,
             prev: None,
             new_blocks: new_blocks.clone(),
@@ -448,42 +413,13 @@ impl Repo {
         new_blocks.add_map(added_leaves.blocks.clone())?;
         relevant_blocks.add_map(added_leaves.blocks)?;
 
-        // verify commit content validity
-        if self.did() != root.did {
-            bail!("root did invalid");
-        }
-        if root.version != 3 {
-            bail!("root version invalid");
-        }
-        if root.prev != None {
-            bail!("root prev invalid");
-        }
-        if data_cid.to_string() != root.data {
-            bail!("root data invalid");
-        }
-
-        let sig = if root.signed_bytes.starts_with("0x") || root.signed_bytes.starts_with("0X") {
-            &root.signed_bytes[2..]
-        } else {
-            &root.signed_bytes
-        };
-
-        let commit = Commit {
-            did: root.did,
-            rev: TID::new(root.rev)?.0,
-            version: root.version,
-            prev: None,
-            data: data_cid,
-            sig: hex::decode(sig).map_err(|error| {
-                let context = format!("root signed bytes invalid");
-                anyhow::Error::new(error).context(context)
-            })?,
-        };
-
-        // todo signing key check through did doc
-
-        if !util::verify_commit_sig(commit.clone(), &signing_key)? {
-            bail!("root sign data verified failed");
+        let commit = Self::verify_signed_root(root, &self.did(), data_cid, &signing_key)?;
+        if !TID(commit.rev.clone()).newer_than(&TID(self.commit.rev.clone())) {
+            return Err(SignedRootError::StaleRev {
+                rev: commit.rev,
+                current: self.commit.rev.clone(),
+            }
+            .into());
         }
 
         let commit_block_bytes = rsky_common::struct_to_cbor(&commit)?;
@@ -506,7 +442,97 @@ impl Repo {
         })
     }
 
+    /// Re-derives the unsigned commit from the server's own view of the
+    /// writes and checks a client's signed root against it field by field, so
+    /// nothing in the root can differ from what the pre* call handed out. The
+    /// signature has to be over the canonical DAG-CBOR of that commit.
+    fn verify_signed_root(
+        root: SignedRoot,
+        did: &str,
+        data: Cid,
+        signing_key: &String,
+    ) -> Result<Commit> {
+        if root.did != did {
+            return Err(SignedRootError::Did {
+                expected: did.to_string(),
+                got: root.did,
+            }
+            .into());
+        }
+        if root.version != 3 {
+            return Err(SignedRootError::Version(root.version).into());
+        }
+        if let Some(prev) = root.prev {
+            return Err(SignedRootError::Prev(prev).into());
+        }
+        if root.data != data.to_string() {
+            return Err(SignedRootError::Data {
+                expected: data.to_string(),
+                got: root.data,
+            }
+            .into());
+        }
+        if !TID::is_canonical(&root.rev) {
+            return Err(SignedRootError::Rev(root.rev).into());
+        }
+
+        let sig = root
+            .signed_bytes
+            .strip_prefix("0x")
+            .or_else(|| root.signed_bytes.strip_prefix("0X"))
+            .unwrap_or(&root.signed_bytes);
+        let sig =
+            hex::decode(sig).map_err(|error| SignedRootError::SignedBytes(error.to_string()))?;
+        if sig.len() != 64 {
+            return Err(SignedRootError::SignedBytes(format!(
+                "expected a 64 byte compact signature, got {} bytes",
+                sig.len()
+            ))
+            .into());
+        }
+
+        let unsigned = UnsignedCommit {
+            did: did.to_string(),
+            version: 3,
+            rev: root.rev,
+            prev: None,
+            data,
+        };
+        let un_sign_bytes = serde_ipld_dagcbor::to_vec(&unsigned)?;
+        let hash = Sha256::digest(&un_sign_bytes);
+        // todo signing key check through did doc
+        if !rsky_crypto::verify::verify_signature(signing_key, hash.as_ref(), &sig, None)? {
+            return Err(SignedRootError::Signature.into());
+        }
+        Ok(Commit {
+            did: unsigned.did,
+            version: unsigned.version,
+            rev: unsigned.rev,
+            prev: None,
+            data,
+            sig,
+        })
+    }
+
     pub async fn generate_commit(&mut self, to_write: RecordWriteEnum) -> Result<UnsignedCommit> {
+        let rev = Ticker::new().next(Some(TID(self.commit.rev.clone())));
+        self.generate_commit_at(to_write, rev).await
+    }
+
+    /// Like `generate_commit`, with a rev the caller issued. It has to be after
+    /// the current commit's rev.
+    pub async fn generate_commit_at(
+        &mut self,
+        to_write: RecordWriteEnum,
+        rev: TID,
+    ) -> Result<UnsignedCommit> {
+        if !rev.newer_than(&TID(self.commit.rev.clone())) {
+            return Err(SignedRootError::StaleRev {
+                rev: rev.0,
+                current: self.commit.rev.clone(),
+            }
+            .into());
+        }
         let writes = match to_write {
             RecordWriteEnum::List(to_write) => to_write,
             RecordWriteEnum::Single(to_write) => vec![to_write],
@@ -554,8 +580,6 @@ impl Repo {
         new_blocks.add_map(added_leaves.blocks.clone())?;
         relevant_blocks.add_map(added_leaves.blocks)?;
 
-        let rev = Ticker::new().next(Some(TID(self.commit.rev.clone())));
-
         return Ok(UnsignedCommit {
             did: self.did(),
             version: 3,
@@ -1524,4 +1548,138 @@ mod tests {
         assert_eq!(contents_from_ops, repo_data);
         Ok(())
     }
+
+    fn sign_root(unsigned: &UnsignedCommit, keypair: &Keypair) -> Result<SignedRoot> {
+        Ok(SignedRoot {
+            did: unsigned.did.clone(),
+            rev: unsigned.rev.clone(),
+            data: unsigned.data.to_string(),
+            prev: None,
+            version: unsigned.version,
+            signed_bytes: hex::encode(sign_without_indexmap(unsigned, &keypair.secret_key())?),
+        })
+    }
+
+    #[tokio::test]
+    async fn rejects_signed_roots_that_differ_from_the_derived_commit() -> Result<()> {
+        let storage = MemoryBlockstore::default();
+        let secp = Secp256k1::new();
+        let keypair = Keypair::new(&secp, &mut thread_rng());
+        let did_key = encode_did_key(&keypair.public_key());
+        let mut repo = Repo::create(
+            Arc::new(RwLock::new(storage)),
+            did_key.clone(),
+            keypair,
+            None,
+        )
+        .await?;
+        let write = RecordWriteOp::Create(RecordCreateOrUpdateOp {
+            action: WriteOpAction::Create,
+            collection: COLL_NAME.to_string(),
+            rkey: TID::next_str(None)?,
+            record: generate_object(),
+        });
+        let unsigned = repo
+            .generate_commit(RecordWriteEnum::Single(write.clone()))
+            .await?;
+        let root = sign_root(&unsigned, &keypair)?;
+
+        let (head, tail) = root.rev.split_at(4);
+        let tampered = [
+            SignedRoot {
+                did: "did:example:someone-else".to_string(),
+                ..root.clone()
+            },
+            SignedRoot {
+                version: 2,
+                ..root.clone()
+            },
+            SignedRoot {
+                prev: Some(repo.cid.to_string()),
+                ..root.clone()
+            },
+            SignedRoot {
+                data: repo.commit.data.to_string(),
+                ..root.clone()
+            },
+            SignedRoot {
+                rev: format!("{head}-{tail}"),
+                ..root.clone()
+            },
+            SignedRoot {
+                rev: TID::next_str(Some(root.rev.clone()))?,
+                ..root.clone()
+            },
+            SignedRoot {
+                signed_bytes: root.signed_bytes[2..].to_string(),
+                ..root.clone()
+            },
+        ];
+        for tampered in tampered {
+            let error = repo
+                .verify_commit(
+                    RecordWriteEnum::Single(write.clone()),
+                    did_key.clone(),
+                    tampered,
+                )
+                .await
+                .expect_err("tampered root verified");
+            assert!(error.downcast_ref::<SignedRootError>().is_some(), "{error}");
+        }
+
+        let commit = repo
+            .verify_commit(RecordWriteEnum::Single(write), did_key, root)
+            .await?;
+        assert_eq!(commit.rev, unsigned.rev);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_revs_that_go_backwards() -> Result<()> {
+        let storage = MemoryBlockstore::default();
+        let secp = Secp256k1::new();
+        let keypair = Keypair::new(&secp, &mut thread_rng());
+        let did_key = encode_did_key(&keypair.public_key());
+        let mut repo = Repo::create(
+            Arc::new(RwLock::new(storage)),
+            did_key.clone(),
+            keypair,
+            None,
+        )
+        .await?;
+        let write = RecordWriteOp::Create(RecordCreateOrUpdateOp {
+            action: WriteOpAction::Create,
+            collection: COLL_NAME.to_string(),
+            rkey: TID::next_str(None)?,
+            record: generate_object(),
+        });
+
+        let current = TID(repo.commit.rev.clone());
+        let error = repo
+            .generate_commit_at(RecordWriteEnum::Single(write.clone()), current.clone())
+            .await
+            .expect_err("issued a rev that isn't after the head");
+        assert!(matches!(
+            error.downcast_ref::<SignedRootError>(),
+            Some(SignedRootError::StaleRev { .. })
+        ));
+
+        let mut unsigned = repo
+            .generate_commit(RecordWriteEnum::Single(write.clone()))
+            .await?;
+        unsigned.rev = current.0;
+        let error = repo
+            .verify_commit(
+                RecordWriteEnum::Single(write),
+                did_key,
+                sign_root(&unsigned, &keypair)?,
+            )
+            .await
+            .expect_err("verified a rev that isn't after the head");
+        assert!(matches!(
+            error.downcast_ref::<SignedRootError>(),
+            Some(SignedRootError::StaleRev { .. })
+        ));
+        Ok(())
+    }
 }
@@ -112,6 +112,15 @@ pub fn cbor_to_lex_record(val: Vec<u8>) -> Result<RepoRecord> {
     }
 }
 
+/// Encodes a record into `buf` as the DAG-CBOR block the repo stores for it
+/// and returns the block's CID. The buffer is cleared first, so one buffer
+/// can be reused across a batch of writes.
+pub fn record_to_block(record: &RepoRecord, buf: &mut Vec<u8>) -> Result<Cid> {
+    buf.clear();
+    serde_ipld_dagcbor::to_writer(&mut *buf, record)?;
+    rsky_common::ipld::cid_for_cbor_bytes(buf)
+}
+
 pub fn ensure_creates(
     descripts: Vec<RecordWriteDescript>,
 ) -> Result<Vec<RecordCreateOrDeleteDescript>> {
@@ -227,3 +236,35 @@ where
     }
     Ok(buffer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_map::BlockMap;
+    use serde_json::json;
+
+    #[test]
+    fn record_block_matches_repo_encoding() -> Result<()> {
+        let record: RepoRecord = serde_json::from_value(json!({
+            "$type": "app.bbs.post",
+            "text": "hello world",
+            "createdAt": "2025-01-01T00:00:00.000Z",
+            "langs": ["en", "zh"],
+            "reply": { "root": { "uri": "at://did:web5:abc/app.bbs.post/1" } },
+            "count": 3,
+        }))?;
+        let mut buf = Vec::new();
+        let cid = record_to_block(&record, &mut buf)?;
+        let mut blocks = BlockMap::new();
+        assert_eq!(blocks.add(record.clone())?, cid);
+        assert_eq!(blocks.get(cid), Some(&buf));
+        assert_eq!(
+            rsky_common::ipld::cid_for_cbor(&lex_to_ipld(Lex::Map(record.clone())))?,
+            cid
+        );
+        // the buffer is cleared rather than appended to
+        assert_eq!(record_to_block(&record, &mut buf)?, cid);
+        assert_eq!(blocks.get(cid), Some(&buf));
+        Ok(())
+    }
+}
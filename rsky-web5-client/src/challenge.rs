@@ -0,0 +1,101 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// First line of every message preIndexAction asks to be signed
+const HEADER: &str = "Web5 Login";
+
+/// The message preIndexAction returns for the wallet to sign, split into its
+/// fields so a frontend can show what's being signed and check it's for the
+/// right account before asking the wallet.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Challenge {
+    /// Host of the PDS that issued the challenge
+    pub domain: String,
+    /// CKB address of the account
+    pub address: String,
+    pub handle: String,
+    /// Unix seconds; the PDS only accepts a signature within two minutes of it
+    pub timestamp: u64,
+    /// What the signature authorizes, e.g. signing in or deleting the account
+    pub statement: String,
+}
+
+impl Challenge {
+    pub fn parse(message: &str) -> Result<Challenge> {
+        let mut lines = message.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(Error::InvalidInput(format!(
+                "challenge doesn't start with {HEADER:?}"
+            )));
+        }
+        let mut field = |name: &str| -> Result<String> {
+            let line = lines.next().unwrap_or_default();
+            match line
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(": "))
+            {
+                Some(value) => Ok(value.to_string()),
+                None => Err(Error::InvalidInput(format!(
+                    "expected {name} in challenge, found {line:?}"
+                ))),
+            }
+        };
+        let domain = field("Domain")?;
+        let address = field("Address")?;
+        let handle = field("Handle")?;
+        let timestamp = field("Timestamp")?;
+        let statement = field("Statement")?;
+        let timestamp = timestamp
+            .trim()
+            .parse()
+            .map_err(|_| Error::InvalidInput(format!("bad challenge timestamp {timestamp:?}")))?;
+        Ok(Challenge {
+            domain,
+            address,
+            handle,
+            timestamp,
+            statement,
+        })
+    }
+}
+
+/// The message exactly as the PDS generated it, which is what gets signed.
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{HEADER}\nDomain: {}\nAddress: {}\nHandle: {}\nTimestamp: {}\nStatement: {}",
+            self.domain, self.address, self.handle, self.timestamp, self.statement
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = "Web5 Login\nDomain: web5.bbs.fans\nAddress: ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqfkcv98jy0q4vpxzpm9hmxlrrkn4rmd35gqqp2ay\nHandle: alice.web5.bbs.fans\nTimestamp: 1760000000\nStatement: Sign this message to authenticate with login on pds: web5.bbs.fans.";
+
+    #[test]
+    fn parses_and_reproduces_challenges() {
+        let challenge = Challenge::parse(MESSAGE).unwrap();
+        assert_eq!(challenge.domain, "web5.bbs.fans");
+        assert_eq!(challenge.handle, "alice.web5.bbs.fans");
+        assert_eq!(challenge.timestamp, 1760000000);
+        assert_eq!(
+            challenge.statement,
+            "Sign this message to authenticate with login on pds: web5.bbs.fans."
+        );
+        assert_eq!(challenge.to_string(), MESSAGE);
+    }
+
+    #[test]
+    fn rejects_other_messages() {
+        assert!(Challenge::parse("Hello").is_err());
+        assert!(Challenge::parse(&MESSAGE.replace("Handle", "Name")).is_err());
+        assert!(Challenge::parse(&MESSAGE.replace("1760000000", "soon")).is_err());
+        assert!(Challenge::parse("Web5 Login\nDomain: web5.bbs.fans").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+use crate::commit::UnsignedRoot;
+use crate::error::{Error, Result};
+use crate::signer::Signer;
+use rsky_lexicon::com::atproto::web5::{
+    CreateAccountInput, CreateAccountOutput, DirectWritesInput, DirectWritesInputRefWrite,
+    DirectWritesOutput, IndexActionInput, IndexActionInputRef, IndexActionOutput,
+    IndexActionOutputRefResult, PreCreateAccountInput, PreCreateAccountOutput,
+    PreDirectWritesInput, PreDirectWritesInputRefWrite, PreDirectWritesOutput, PreIndexActionInput,
+    PreIndexActionInputRef, PreIndexActionOutput, RefCreateSessionIndex, RefCreateSessionResult,
+    RefDeleteAccountIndex, RefStepUpIndex, RefStepUpResult,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+/// Tokens of the logged in account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub did: String,
+    pub handle: String,
+    pub access_jwt: String,
+    pub refresh_jwt: String,
+}
+
+/// What a new web5 account is created with, besides the signing key.
+#[derive(Debug, Clone, Default)]
+pub struct CreateAccountOpts {
+    pub did: String,
+    pub handle: String,
+    /// CKB address the account's DID cell is bound to
+    pub ckb_addr: String,
+    pub password: Option<String>,
+    pub invite_code: Option<String>,
+    pub captcha_token: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Options for a batch of record writes.
+#[derive(Debug, Clone, Default)]
+pub struct DirectWritesOpts {
+    /// Set to `Some(false)` to skip Lexicon validation of the records
+    pub validate: Option<bool>,
+    /// Only apply the writes if the repo is still at this commit
+    pub swap_commit: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct XrpcError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    message: String,
+}
+
+pub struct Web5Client {
+    service: String,
+    http: reqwest::Client,
+    session: RwLock<Option<Session>>,
+}
+
+impl Web5Client {
+    /// A client for the PDS at `service`, e.g. `https://web5.bbs.fans`.
+    pub fn new(service: &str) -> Self {
+        Web5Client {
+            service: service.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            session: RwLock::new(None),
+        }
+    }
+
+    pub async fn session(&self) -> Option<Session> {
+        self.session.read().await.clone()
+    }
+
+    /// Resumes a session saved from an earlier run.
+    pub async fn resume_session(&self, session: Session) {
+        *self.session.write().await = Some(session);
+    }
+
+    async fn post<I: Serialize, O: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        input: &I,
+        auth: bool,
+    ) -> Result<O> {
+        let mut req = self
+            .http
+            .post(format!("{}/xrpc/{method}", self.service))
+            .json(input);
+        if auth {
+            match *self.session.read().await {
+                Some(ref session) => req = req.bearer_auth(&session.access_jwt),
+                None => return Err(Error::NotLoggedIn),
+            }
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            let XrpcError { error, message } = res.json().await.unwrap_or(XrpcError {
+                error: status.to_string(),
+                message: String::new(),
+            });
+            return Err(Error::Xrpc {
+                method,
+                status: status.as_u16(),
+                error,
+                message,
+            });
+        }
+        Ok(res.json().await?)
+    }
+
+    async fn sign_hex(signer: &dyn Signer, bytes: &[u8]) -> Result<String> {
+        let sig = signer.sign(bytes).await.map_err(Error::Signer)?;
+        Ok(hex::encode(sig))
+    }
+
+    /// Creates a web5 account whose repo is signed by `signer`, and logs in
+    /// as it. The DID cell on chain should already list the signer's key.
+    pub async fn create_account(
+        &self,
+        opts: CreateAccountOpts,
+        signer: &dyn Signer,
+    ) -> Result<CreateAccountOutput> {
+        let signing_key = signer.did_key();
+        let unsigned: PreCreateAccountOutput = self
+            .post(
+                "com.atproto.web5.preCreateAccount",
+                &PreCreateAccountInput {
+                    handle: opts.handle.clone(),
+                    did: opts.did,
+                    signing_key: Some(signing_key.clone()),
+                    invite_code: opts.invite_code.clone(),
+                },
+                false,
+            )
+            .await?;
+        let root = UnsignedRoot::from(unsigned).sign(signer).await?;
+        let output: CreateAccountOutput = self
+            .post(
+                "com.atproto.web5.createAccount",
+                &CreateAccountInput {
+                    handle: opts.handle,
+                    signing_key,
+                    password: opts.password,
+                    root,
+                    ckb_addr: opts.ckb_addr,
+                    invite_code: opts.invite_code,
+                    captcha_token: opts.captcha_token,
+                    email: opts.email,
+                },
+                false,
+            )
+            .await?;
+        self.resume_session(Session {
+            did: output.did.clone(),
+            handle: output.handle.clone(),
+            access_jwt: output.access_jwt.clone(),
+            refresh_jwt: output.refresh_jwt.clone(),
+        })
+        .await;
+        Ok(output)
+    }
+
+    /// Runs a preIndexAction/indexAction round for `did`, signing the
+    /// message the PDS hands out.
+    pub async fn index_action(
+        &self,
+        did: &str,
+        ckb_addr: &str,
+        index: PreIndexActionInputRef,
+        signer: &dyn Signer,
+    ) -> Result<IndexActionOutputRefResult> {
+        let PreIndexActionOutput { message, .. } = self
+            .post(
+                "com.atproto.web5.preIndexAction",
+                &PreIndexActionInput {
+                    did: did.to_string(),
+                    ckb_addr: Some(ckb_addr.to_string()),
+                    index: index.clone(),
+                },
+                false,
+            )
+            .await?;
+        let signed_bytes = Self::sign_hex(signer, message.as_bytes()).await?;
+        let IndexActionOutput { result } = self
+            .post(
+                "com.atproto.web5.indexAction",
+                &IndexActionInput {
+                    did: did.to_string(),
+                    message,
+                    signing_key: signer.did_key(),
+                    signed_bytes,
+                    ckb_addr: Some(ckb_addr.to_string()),
+                    index: to_index_action(index),
+                },
+                false,
+            )
+            .await?;
+        Ok(result)
+    }
+
+    /// Logs in with the signing key and keeps the session for later calls.
+    pub async fn create_session(
+        &self,
+        did: &str,
+        ckb_addr: &str,
+        signer: &dyn Signer,
+    ) -> Result<RefCreateSessionResult> {
+        let index = PreIndexActionInputRef::CreateSessionIndex(RefCreateSessionIndex {});
+        match self.index_action(did, ckb_addr, index, signer).await? {
+            IndexActionOutputRefResult::CreateSessionResult(result) => {
+                self.resume_session(Session {
+                    did: result.did.clone(),
+                    handle: result.handle.clone(),
+                    access_jwt: result.access_jwt.clone(),
+                    refresh_jwt: result.refresh_jwt.clone(),
+                })
+                .await;
+                Ok(result)
+            }
+            other => Err(Error::InvalidResponse(format!(
+                "expected a createSession result, got {other:?}"
+            ))),
+        }
+    }
+
+    /// Gets a step-up token for `lxm`, to send in the `Web5-Step-Up` header.
+    pub async fn step_up(
+        &self,
+        did: &str,
+        ckb_addr: &str,
+        lxm: &str,
+        signer: &dyn Signer,
+    ) -> Result<RefStepUpResult> {
+        let index = PreIndexActionInputRef::StepUpIndex(RefStepUpIndex {
+            lxm: lxm.to_string(),
+        });
+        match self.index_action(did, ckb_addr, index, signer).await? {
+            IndexActionOutputRefResult::StepUpResult(result) => Ok(result),
+            other => Err(Error::InvalidResponse(format!(
+                "expected a stepUp result, got {other:?}"
+            ))),
+        }
+    }
+
+    /// Deletes the account and forgets the session.
+    pub async fn delete_account(
+        &self,
+        did: &str,
+        ckb_addr: &str,
+        signer: &dyn Signer,
+    ) -> Result<()> {
+        let index = PreIndexActionInputRef::DeleteAccountIndex(RefDeleteAccountIndex {});
+        match self.index_action(did, ckb_addr, index, signer).await? {
+            IndexActionOutputRefResult::DeleteAccountResult(_) => {
+                *self.session.write().await = None;
+                Ok(())
+            }
+            other => Err(Error::InvalidResponse(format!(
+                "expected a deleteAccount result, got {other:?}"
+            ))),
+        }
+    }
+
+    /// Applies a batch of record writes to the logged in account's repo,
+    /// signing the commit the PDS builds for them.
+    pub async fn direct_writes(
+        &self,
+        ckb_addr: &str,
+        writes: Vec<PreDirectWritesInputRefWrite>,
+        opts: DirectWritesOpts,
+        signer: &dyn Signer,
+    ) -> Result<DirectWritesOutput> {
+        let repo = self.session().await.ok_or(Error::NotLoggedIn)?.did;
+        let unsigned: PreDirectWritesOutput = self
+            .post(
+                "com.atproto.web5.preDirectWrites",
+                &PreDirectWritesInput {
+                    repo: repo.clone(),
+                    validate: opts.validate,
+                    writes: writes.clone(),
+                    swap_commit: opts.swap_commit.clone(),
+                },
+                true,
+            )
+            .await?;
+        let root = UnsignedRoot::from(unsigned).sign(signer).await?;
+        self.post(
+            "com.atproto.web5.directWrites",
+            &DirectWritesInput {
+                repo,
+                validate: opts.validate,
+                writes: writes.into_iter().map(to_direct_write).collect(),
+                swap_commit: opts.swap_commit,
+                signing_key: signer.did_key(),
+                ckb_addr: Some(ckb_addr.to_string()),
+                root,
+            },
+            true,
+        )
+        .await
+    }
+}
+
+fn to_index_action(index: PreIndexActionInputRef) -> IndexActionInputRef {
+    match index {
+        PreIndexActionInputRef::CreateSessionIndex(index) => {
+            IndexActionInputRef::CreateSessionIndex(index)
+        }
+        PreIndexActionInputRef::DeleteAccountIndex(index) => {
+            IndexActionInputRef::DeleteAccountIndex(index)
+        }
+        PreIndexActionInputRef::StepUpIndex(index) => IndexActionInputRef::StepUpIndex(index),
+    }
+}
+
+fn to_direct_write(write: PreDirectWritesInputRefWrite) -> DirectWritesInputRefWrite {
+    match write {
+        PreDirectWritesInputRefWrite::Create(write) => DirectWritesInputRefWrite::Create(write),
+        PreDirectWritesInputRefWrite::Update(write) => DirectWritesInputRefWrite::Update(write),
+        PreDirectWritesInputRefWrite::Delete(write) => DirectWritesInputRefWrite::Delete(write),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsky_lexicon::com::atproto::web5::RefWriteDelete;
+
+    #[test]
+    fn converts_pre_calls_to_their_signed_counterparts() {
+        let write = to_direct_write(PreDirectWritesInputRefWrite::Delete(RefWriteDelete {
+            collection: "app.bbs.post".to_string(),
+            rkey: "3abc".to_string(),
+        }));
+        assert_eq!(
+            serde_json::to_value(write).unwrap()["$type"],
+            "com.atproto.web5.directWrites#delete"
+        );
+        let index = to_index_action(PreIndexActionInputRef::StepUpIndex(RefStepUpIndex {
+            lxm: "com.atproto.server.deleteAccount".to_string(),
+        }));
+        assert_eq!(
+            serde_json::to_value(index).unwrap()["$type"],
+            "com.atproto.web5.indexAction#stepUp"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::{Error, Result};
+#[cfg(feature = "client")]
+use crate::signer::{sign_bytes, Signer};
+use rsky_lexicon::com::atproto::web5::{PreCreateAccountOutput, PreDirectWritesOutput, SignedRoot};
+#[cfg(feature = "client")]
+use secp256k1::SecretKey;
+
+/// Decodes the hex `unSignBytes` of a pre* response, with or without a `0x`
+/// prefix.
+pub fn decode_un_sign_bytes(un_sign_bytes: &str) -> Result<Vec<u8>> {
+    let un_sign_bytes = un_sign_bytes.strip_prefix("0x").unwrap_or(un_sign_bytes);
+    hex::decode(un_sign_bytes)
+        .map_err(|error| Error::InvalidResponse(format!("unSignBytes: {error}")))
+}
+
+/// A commit the PDS built but left for the account to sign, as returned by
+/// preCreateAccount and preDirectWrites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsignedRoot {
+    pub did: String,
+    pub rev: String,
+    pub data: String,
+    pub prev: Option<String>,
+    pub version: u8,
+    /// Hex of the DAG-CBOR encoded commit without its signature
+    pub un_sign_bytes: String,
+}
+
+impl From<PreDirectWritesOutput> for UnsignedRoot {
+    fn from(output: PreDirectWritesOutput) -> Self {
+        UnsignedRoot {
+            did: output.did,
+            rev: output.rev,
+            data: output.data,
+            prev: output.prev,
+            version: output.version,
+            un_sign_bytes: output.un_sign_bytes,
+        }
+    }
+}
+
+impl From<PreCreateAccountOutput> for UnsignedRoot {
+    fn from(output: PreCreateAccountOutput) -> Self {
+        UnsignedRoot {
+            did: output.did,
+            rev: output.rev,
+            data: output.data,
+            prev: output.prev,
+            version: output.version,
+            un_sign_bytes: output.un_sign_bytes,
+        }
+    }
+}
+
+impl UnsignedRoot {
+    /// The encoded commit to sign. The signature is over these bytes, not
+    /// over the hex string or a re-encoding of the other fields.
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        decode_un_sign_bytes(&self.un_sign_bytes)
+    }
+
+    /// Attaches a signature over `bytes()`, hex encoded as `signedBytes`.
+    pub fn into_signed(self, sig: &[u8]) -> SignedRoot {
+        SignedRoot {
+            did: self.did,
+            rev: self.rev,
+            data: self.data,
+            prev: self.prev,
+            version: self.version,
+            signed_bytes: hex::encode(sig),
+        }
+    }
+
+    #[cfg(feature = "client")]
+    pub fn sign_with_key(self, secret_key: &SecretKey) -> Result<SignedRoot> {
+        let sig = sign_bytes(secret_key, &self.bytes()?);
+        Ok(self.into_signed(&sig))
+    }
+
+    #[cfg(feature = "client")]
+    pub async fn sign(self, signer: &dyn Signer) -> Result<SignedRoot> {
+        let sig = signer.sign(&self.bytes()?).await.map_err(Error::Signer)?;
+        Ok(self.into_signed(&sig))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::LocalSigner;
+    use sha2::Digest;
+
+    /// Commit for `did:web5:test` at rev `3l4ld2dm5uk2a`, with the CID of an
+    /// empty block as its data
+    const UN_SIGN_BYTES: &str = "a5636469646d6469643a776562353a74657374637265766d336c346c6432646d35756b32616464617461d82a58250001711220e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b8556470726576f66776657273696f6e03";
+    /// `01` repeated 32 times
+    const SECRET_KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+    const DID_KEY: &str = "did:key:zQ3shgVXZLaMzm5S5x7XzGUG6YFHFLtoEMiv9ao2Bqa7hGyg2";
+    const SIGNED_BYTES: &str = "703547861f5d012efb813f6d6e3bf27933fb146e934e8989b06681393270c0cf7c1b096a761d8b7756e1971e2e0935295c8f1a2c1825c69b3c82a1ebac1e5dc5";
+
+    fn unsigned_root(un_sign_bytes: &str) -> UnsignedRoot {
+        UnsignedRoot::from(PreDirectWritesOutput {
+            did: "did:web5:test".to_string(),
+            rev: "3l4ld2dm5uk2a".to_string(),
+            data: "bafyreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku".to_string(),
+            prev: None,
+            version: 3,
+            un_sign_bytes: un_sign_bytes.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn signs_commits_like_the_test_vector() {
+        let secret_key = SecretKey::from_slice(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+        let signer = LocalSigner::new(secret_key);
+        assert_eq!(signer.did_key(), DID_KEY);
+
+        let root = unsigned_root(UN_SIGN_BYTES)
+            .sign_with_key(&secret_key)
+            .unwrap();
+        assert_eq!(root.signed_bytes, SIGNED_BYTES);
+        assert_eq!(root.did, "did:web5:test");
+        assert_eq!(root.version, 3);
+
+        // A 0x prefix doesn't change what's signed, nor does signing through the trait
+        let prefixed = unsigned_root(&format!("0x{UN_SIGN_BYTES}"))
+            .sign(&signer)
+            .await
+            .unwrap();
+        assert_eq!(prefixed, root);
+    }
+
+    #[test]
+    fn signatures_verify_as_commit_signatures() {
+        let secret_key = SecretKey::from_slice(&hex::decode(SECRET_KEY).unwrap()).unwrap();
+        let root = unsigned_root(UN_SIGN_BYTES)
+            .sign_with_key(&secret_key)
+            .unwrap();
+        let hash = sha2::Sha256::digest(hex::decode(UN_SIGN_BYTES).unwrap());
+        let sig = hex::decode(&root.signed_bytes).unwrap();
+        assert!(
+            rsky_crypto::verify::verify_signature(&DID_KEY.to_string(), &hash, &sig, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_bytes_that_are_not_hex() {
+        assert!(unsigned_root("not hex").bytes().is_err());
+    }
+}
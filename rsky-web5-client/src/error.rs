@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The PDS answered with an XRPC error body
+    #[error("{method} failed with {status}: {error}: {message}")]
+    Xrpc {
+        method: &'static str,
+        status: u16,
+        error: String,
+        message: String,
+    },
+    #[cfg(feature = "client")]
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Signing failed: {0}")]
+    Signer(anyhow::Error),
+    #[error("Unexpected response from the PDS: {0}")]
+    InvalidResponse(String),
+    #[error("Not logged in")]
+    NotLoggedIn,
+    /// Input handed to one of the offline helpers was malformed
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
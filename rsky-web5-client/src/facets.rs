@@ -0,0 +1,193 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use rsky_lexicon::app::bsky::richtext::{ByteSlice, Facet, Features, Link, Mention, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Longest tag the appview indexes, not counting the `#`
+const MAX_TAG_LENGTH: usize = 64;
+
+lazy_static! {
+    static ref MENTION_REGEX: Regex = Regex::new(
+        r"(?:^|[\s(])(@(?:[a-zA-Z0-9](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z](?:[a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)"
+    )
+    .unwrap();
+    static ref LINK_REGEX: Regex = Regex::new(r"(?:^|[\s(])(https?://\S+)").unwrap();
+    static ref TAG_REGEX: Regex = Regex::new(r"(?:^|\s)([#＃][^\s#＃]+)").unwrap();
+}
+
+/// What a span of text was recognised as. Mentions carry the handle as typed;
+/// the facet needs the DID it resolves to.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DetectedFeature {
+    Mention { handle: String },
+    Link { uri: String },
+    Tag { tag: String },
+}
+
+/// A mention, link or tag found in post text, with its range in UTF-8 bytes.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DetectedFacet {
+    pub index: ByteSlice,
+    pub feature: DetectedFeature,
+}
+
+fn detected(start: usize, end: usize, feature: DetectedFeature) -> DetectedFacet {
+    DetectedFacet {
+        index: ByteSlice {
+            byte_start: start,
+            byte_end: end,
+        },
+        feature,
+    }
+}
+
+/// Drops punctuation that ends the sentence rather than the link, and a
+/// closing paren when the link is wrapped in parens.
+fn trim_link(uri: &str) -> &str {
+    let mut uri = uri.trim_end_matches(['.', ',', ';', ':', '!', '?', '"', '\'']);
+    if uri.ends_with(')') && !uri.contains('(') {
+        uri = uri.trim_end_matches(')');
+    }
+    uri
+}
+
+/// Finds the mentions, links and tags in `text` the way the Bluesky app does,
+/// so posts written through web5 get the same facets. Sorted by position.
+pub fn detect_facets(text: &str) -> Vec<DetectedFacet> {
+    let mut facets = Vec::new();
+    for captures in MENTION_REGEX.captures_iter(text) {
+        let mention = captures.get(1).expect("mention group");
+        facets.push(detected(
+            mention.start(),
+            mention.end(),
+            DetectedFeature::Mention {
+                handle: mention.as_str()[1..].to_lowercase(),
+            },
+        ));
+    }
+    for captures in LINK_REGEX.captures_iter(text) {
+        let link = captures.get(1).expect("link group");
+        let uri = trim_link(link.as_str());
+        facets.push(detected(
+            link.start(),
+            link.start() + uri.len(),
+            DetectedFeature::Link {
+                uri: uri.to_string(),
+            },
+        ));
+    }
+    for captures in TAG_REGEX.captures_iter(text) {
+        let tag = captures.get(1).expect("tag group");
+        let hash_len = tag.as_str().chars().next().map_or(1, char::len_utf8);
+        let name = tag.as_str()[hash_len..].trim_end_matches(|c: char| c.is_ascii_punctuation());
+        if name.is_empty()
+            || name.chars().count() > MAX_TAG_LENGTH
+            || name.chars().all(|c| c.is_ascii_digit())
+        {
+            continue;
+        }
+        facets.push(detected(
+            tag.start(),
+            tag.start() + hash_len + name.len(),
+            DetectedFeature::Tag {
+                tag: name.to_string(),
+            },
+        ));
+    }
+    facets.sort_by_key(|facet| facet.index.byte_start);
+    facets
+}
+
+/// Turns detected facets into `app.bsky.richtext.facet`s, looking mentioned
+/// handles up in `dids`. Mentions of handles that didn't resolve are dropped.
+pub fn resolve_facets(detected: Vec<DetectedFacet>, dids: &BTreeMap<String, String>) -> Vec<Facet> {
+    detected
+        .into_iter()
+        .filter_map(|facet| {
+            let feature = match facet.feature {
+                DetectedFeature::Mention { handle } => Features::Mention(Mention {
+                    did: dids.get(&handle)?.clone(),
+                }),
+                DetectedFeature::Link { uri } => Features::Link(Link { uri }),
+                DetectedFeature::Tag { tag } => Features::Tag(Tag { tag }),
+            };
+            Some(Facet {
+                index: facet.index,
+                features: vec![feature],
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(text: &str) -> Vec<(&str, DetectedFeature)> {
+        detect_facets(text)
+            .into_iter()
+            .map(|facet| {
+                (
+                    &text[facet.index.byte_start..facet.index.byte_end],
+                    facet.feature,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_facets_by_utf8_byte_offsets() {
+        let text = "héllo @Alice.web5.bbs.fans, see (https://bbs.fans/p/1) 🎉 #Web5. #2024 ＃日本";
+        assert_eq!(
+            spans(text),
+            vec![
+                (
+                    "@Alice.web5.bbs.fans",
+                    DetectedFeature::Mention {
+                        handle: "alice.web5.bbs.fans".to_string()
+                    }
+                ),
+                (
+                    "https://bbs.fans/p/1",
+                    DetectedFeature::Link {
+                        uri: "https://bbs.fans/p/1".to_string()
+                    }
+                ),
+                (
+                    "#Web5",
+                    DetectedFeature::Tag {
+                        tag: "Web5".to_string()
+                    }
+                ),
+                (
+                    "＃日本",
+                    DetectedFeature::Tag {
+                        tag: "日本".to_string()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_emails_and_bare_words() {
+        assert!(detect_facets("mail bob@example.com or @alice").is_empty());
+    }
+
+    #[test]
+    fn drops_unresolved_mentions() {
+        let detected = detect_facets("@alice.bbs.fans and @bob.bbs.fans");
+        let dids = BTreeMap::from([("alice.bbs.fans".to_string(), "did:web5:alice".to_string())]);
+        let facets = resolve_facets(detected, &dids);
+        assert_eq!(facets.len(), 1);
+        assert_eq!(
+            facets[0].features,
+            vec![Features::Mention(Mention {
+                did: "did:web5:alice".to_string()
+            })]
+        );
+        assert_eq!(facets[0].index.byte_end, 15);
+    }
+}
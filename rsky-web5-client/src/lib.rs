@@ -0,0 +1,28 @@
+//! Client for the web5 flows of rsky-pds. Every write and sign-in there takes
+//! two round trips: a `pre*` call that returns bytes to sign, then the real
+//! call carrying the signature. [`Web5Client`] runs both behind one method,
+//! with a [`Signer`] producing the signatures.
+//!
+//! Without the default `client` feature only the parts that don't touch the
+//! network are built: challenge parsing, commit decoding and assembly, and
+//! facet detection. The `wasm` feature exports those to JavaScript.
+
+pub mod challenge;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod commit;
+pub mod error;
+pub mod facets;
+#[cfg(feature = "client")]
+pub mod signer;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use challenge::Challenge;
+#[cfg(feature = "client")]
+pub use client::{CreateAccountOpts, DirectWritesOpts, Session, Web5Client};
+pub use commit::UnsignedRoot;
+pub use error::{Error, Result};
+pub use facets::{detect_facets, DetectedFacet, DetectedFeature};
+#[cfg(feature = "client")]
+pub use signer::{LocalSigner, Signer};
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use rsky_crypto::utils::encode_did_key;
+use secp256k1::{Message, Secp256k1, SecretKey};
+use sha2::{Digest, Sha256};
+
+/// Holds the key a web5 repo is signed with. Commits and indexAction messages
+/// are signed the same way: a compact ECDSA signature over the SHA-256 hash of
+/// the bytes, which is what the PDS checks against the key's `did:key`.
+///
+/// Implement it to keep the key somewhere other than process memory, such as
+/// a hardware wallet or a bridge to a CKB wallet extension.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// `did:key` of the signing key, as listed in the account's DID doc
+    fn did_key(&self) -> String;
+
+    /// Signs the SHA-256 hash of `bytes`, returning the 64 byte compact signature.
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Signs `bytes` the way the PDS verifies them: SHA-256, then a deterministic
+/// (RFC 6979) low-S ECDSA signature in 64 byte compact form.
+pub fn sign_bytes(secret_key: &SecretKey, bytes: &[u8]) -> [u8; 64] {
+    let digest = Message::from_digest(Sha256::digest(bytes).into());
+    Secp256k1::new()
+        .sign_ecdsa(&digest, secret_key)
+        .serialize_compact()
+}
+
+/// A secp256k1 key held in memory, for bots and scripts.
+pub struct LocalSigner {
+    secret_key: SecretKey,
+}
+
+impl LocalSigner {
+    pub fn new(secret_key: SecretKey) -> Self {
+        LocalSigner { secret_key }
+    }
+
+    pub fn from_hex(secret_key: &str) -> Result<Self> {
+        Ok(LocalSigner::new(SecretKey::from_slice(&hex::decode(
+            secret_key,
+        )?)?))
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    fn did_key(&self) -> String {
+        encode_did_key(&self.secret_key.public_key(&Secp256k1::new()))
+    }
+
+    async fn sign(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        Ok(sign_bytes(&self.secret_key, bytes).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn signatures_verify_against_the_did_key() {
+        let signer = LocalSigner::new(SecretKey::from_slice(&[7u8; 32]).unwrap());
+        let message = b"Sign this message to authenticate with login on pds: web5.bbs.fans.";
+        let sig = signer.sign(message).await.unwrap();
+        assert_eq!(sig.len(), 64);
+        let hash = Sha256::digest(message);
+        assert!(
+            rsky_crypto::verify::verify_signature(&signer.did_key(), &hash, &sig, None).unwrap()
+        );
+        assert!(
+            !rsky_crypto::verify::verify_signature(&signer.did_key(), &[0u8; 32], &sig, None)
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! JavaScript bindings for the parts of a web5 flow that run in the browser:
+//! the wallet signs, and these do the parsing and assembly around it, so the
+//! BBS frontend doesn't keep its own copy of the formats.
+//!
+//! Build with `wasm-pack build rsky-web5-client --no-default-features --features wasm`.
+
+use crate::challenge::Challenge;
+use crate::commit::{self, UnsignedRoot};
+use crate::error::Error;
+use crate::facets::{self, DetectedFacet};
+use rsky_lexicon::com::atproto::web5::PreDirectWritesOutput;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// Splits a preIndexAction message into `{ domain, address, handle,
+/// timestamp, statement }`.
+#[wasm_bindgen(js_name = parseChallenge)]
+pub fn parse_challenge(message: &str) -> Result<JsValue, JsError> {
+    to_js(&Challenge::parse(message)?)
+}
+
+/// The bytes to sign from the `unSignBytes` of a preCreateAccount or
+/// preDirectWrites response.
+#[wasm_bindgen(js_name = decodeUnSignBytes)]
+pub fn decode_un_sign_bytes(un_sign_bytes: &str) -> Result<Vec<u8>, JsError> {
+    Ok(commit::decode_un_sign_bytes(un_sign_bytes)?)
+}
+
+/// Turns a preCreateAccount or preDirectWrites response and the wallet's
+/// 64 byte compact signature over its `unSignBytes` into the `root` the
+/// matching createAccount or directWrites call takes.
+#[wasm_bindgen(js_name = assembleSignedRoot)]
+pub fn assemble_signed_root(pre_output: JsValue, signature: &[u8]) -> Result<JsValue, JsError> {
+    if signature.len() != 64 {
+        return Err(Error::InvalidInput(format!(
+            "expected a 64 byte compact signature, got {} bytes",
+            signature.len()
+        ))
+        .into());
+    }
+    let pre_output: PreDirectWritesOutput = serde_wasm_bindgen::from_value(pre_output)?;
+    let root = UnsignedRoot::from(pre_output);
+    // Fail on bad hex here rather than at the PDS
+    root.bytes()?;
+    to_js(&root.into_signed(signature))
+}
+
+/// Mentions, links and tags in post text, with UTF-8 byte ranges.
+#[wasm_bindgen(js_name = detectFacets)]
+pub fn detect_facets(text: &str) -> Result<JsValue, JsError> {
+    to_js(&facets::detect_facets(text))
+}
+
+/// Facets ready for a post record from `detectFacets` output and a map of
+/// handle to DID for the mentions that resolved.
+#[wasm_bindgen(js_name = resolveFacets)]
+pub fn resolve_facets(detected: JsValue, dids: JsValue) -> Result<JsValue, JsError> {
+    let detected: Vec<DetectedFacet> = serde_wasm_bindgen::from_value(detected)?;
+    let dids: BTreeMap<String, String> = serde_wasm_bindgen::from_value(dids)?;
+    to_js(&facets::resolve_facets(detected, &dids))
+}